@@ -1,10 +1,18 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
 
 // Import from the library
 use crossref_citation_extraction::extract::{
-    extract_arxiv_matches_from_text, extract_doi_matches_from_text, normalize_doi,
+    extract_arxiv_matches_within, extract_doi_matches_from_text, normalize_arxiv_id, normalize_doi,
+    LegacyArxivCategories, ARXIV_ID_PATTERNS,
 };
 use crossref_citation_extraction::index::DoiIndex;
+use crossref_citation_extraction::streaming::gzip_reader::{
+    ReadaheadReader, READAHEAD_CHUNK_SIZE, READAHEAD_DEPTH,
+};
 
 fn bench_doi_extraction(c: &mut Criterion) {
     let sample_texts = vec![
@@ -38,13 +46,78 @@ fn bench_arxiv_extraction(c: &mut Criterion) {
         "No arXiv references here",
     ];
 
+    let categories = LegacyArxivCategories::bundled();
     let mut group = c.benchmark_group("arxiv_extraction");
     group.throughput(Throughput::Elements(sample_texts.len() as u64));
 
     group.bench_function("extract_arxiv_matches", |b| {
         b.iter(|| {
             for text in &sample_texts {
-                black_box(extract_arxiv_matches_from_text(text));
+                black_box(extract_arxiv_matches_within(text, &categories));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_arxiv_combined_scan(c: &mut Criterion) {
+    // Representative references: mostly journal citations, some naming arXiv
+    let sample_texts = vec![
+        "Vaswani A, et al. Attention is all you need. arXiv:1706.03762 (2017)",
+        "He K, Zhang X, Ren S, Sun J. Deep residual learning. CVPR 2016, arXiv preprint arXiv:1512.03385",
+        "Maldacena J. The large N limit. Adv Theor Math Phys 2, 231 (1998), arXiv:hep-th/9711200",
+        "Brown T, et al. Language models are few-shot learners. https://arxiv.org/abs/2005.14165",
+        "Smith J. Results from the arXiv survey. J Inf Sci. 2019;45(2):123-140. doi:10.1177/0165551518",
+        "Kingma DP, Ba J. Adam. 10.48550/arXiv.1412.6980",
+    ];
+
+    let categories = LegacyArxivCategories::bundled();
+    let mut group = c.benchmark_group("arxiv_combined_scan");
+    group.throughput(Throughput::Elements(sample_texts.len() as u64));
+
+    // The pattern-by-pattern scan the combined set replaced
+    group.bench_function("separate_passes", |b| {
+        b.iter(|| {
+            for text in &sample_texts {
+                for pattern in ARXIV_ID_PATTERNS.iter() {
+                    for cap in pattern.captures_iter(text) {
+                        black_box(normalize_arxiv_id(&cap[2]));
+                    }
+                }
+            }
+        })
+    });
+
+    group.bench_function("combined_scan", |b| {
+        b.iter(|| {
+            for text in &sample_texts {
+                black_box(extract_arxiv_matches_within(text, &categories));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_unmatched_references(c: &mut Criterion) {
+    // Most reference texts hold no DOI or arXiv ID; the prescreen skips their regexes
+    let sample_texts = vec![
+        "Smith J, Jones K. A study of things. J Stud. 2019;12(3):45-67.",
+        "Knuth DE. The Art of Computer Programming. Addison-Wesley; 1997.",
+        "World Health Organization. Global report on diabetes. Geneva: WHO; 2016.",
+        "R Core Team (2023). R: A language and environment for statistical computing.",
+    ];
+
+    let categories = LegacyArxivCategories::bundled();
+    let mut group = c.benchmark_group("unmatched_references");
+    group.throughput(Throughput::Elements(sample_texts.len() as u64));
+
+    group.bench_function("extract_doi_and_arxiv_matches", |b| {
+        b.iter(|| {
+            for text in &sample_texts {
+                black_box(extract_doi_matches_from_text(text));
+                black_box(extract_arxiv_matches_within(text, &categories));
             }
         })
     });
@@ -102,11 +175,67 @@ fn bench_normalize_doi(c: &mut Criterion) {
     });
 }
 
+/// Scan decompressed lines for DOIs, approximating the per-byte work of extraction
+fn consume_lines<R: Read>(reader: R) -> usize {
+    BufReader::new(reader)
+        .lines()
+        .map(|line| extract_doi_matches_from_text(&line.unwrap()).len())
+        .sum()
+}
+
+/// Size of the decompressed payload, for byte throughput reporting
+fn decompressed_len(compressed: &[u8]) -> usize {
+    let mut out = Vec::new();
+    MultiGzDecoder::new(compressed)
+        .read_to_end(&mut out)
+        .unwrap();
+    out.len()
+}
+
+fn bench_gzip_decode(c: &mut Criterion) {
+    // ~32 MB of reference-like JSON lines, gzip-compressed in memory
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for i in 0..200_000u64 {
+        writeln!(
+            encoder,
+            r#"{{"key":"ref{}","unstructured":"Author A. Title of work {}. Journal. 2020. doi:10.{}/{}","journal-title":"Journal of Things"}}"#,
+            i,
+            i,
+            1000 + (i % 5000),
+            i
+        )
+        .unwrap();
+    }
+    let compressed = encoder.finish().unwrap();
+    let decompressed_len = decompressed_len(&compressed);
+
+    let mut group = c.benchmark_group("gzip_decode");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(decompressed_len as u64));
+
+    group.bench_function("inline_decoder", |b| {
+        b.iter(|| black_box(consume_lines(MultiGzDecoder::new(Cursor::new(&compressed)))))
+    });
+
+    group.bench_function("readahead_decoder", |b| {
+        b.iter(|| {
+            let decoder = MultiGzDecoder::new(Cursor::new(compressed.clone()));
+            let reader = ReadaheadReader::new(decoder, READAHEAD_CHUNK_SIZE, READAHEAD_DEPTH);
+            black_box(consume_lines(reader))
+        })
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_doi_extraction,
     bench_arxiv_extraction,
+    bench_arxiv_combined_scan,
+    bench_unmatched_references,
     bench_doi_index_lookup,
     bench_normalize_doi,
+    bench_gzip_decode,
 );
 criterion_main!(benches);
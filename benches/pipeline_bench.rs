@@ -0,0 +1,114 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use tempfile::tempdir;
+
+use crossref_citation_extraction::synthetic::SyntheticSnapshot;
+
+/// Multiplies the number of files in every shape, e.g. PIPELINE_BENCH_SCALE=10
+/// before a release to benchmark larger snapshots
+const SCALE_ENV: &str = "PIPELINE_BENCH_SCALE";
+
+fn scale() -> usize {
+    std::env::var(SCALE_ENV)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1)
+}
+
+/// Snapshot shapes: typical reference lists, dense references, and arXiv-heavy
+fn shapes() -> Vec<(&'static str, SyntheticSnapshot)> {
+    let files = 20 * scale();
+    vec![
+        (
+            "typical",
+            SyntheticSnapshot {
+                files,
+                ..Default::default()
+            },
+        ),
+        (
+            "dense_references",
+            SyntheticSnapshot {
+                files,
+                items_per_file: 50,
+                references_per_item: 80,
+                asserted_doi_rate: 0.6,
+                mined_doi_rate: 0.3,
+                ..Default::default()
+            },
+        ),
+        (
+            "arxiv_heavy",
+            SyntheticSnapshot {
+                files,
+                arxiv_rate: 0.4,
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+/// Run the full pipeline binary (extract, invert, validate against the built index)
+fn run_pipeline(snapshot: &Path, source: &str) {
+    let run_dir = tempdir().unwrap();
+    let output = run_dir.path().join("out.jsonl");
+    let output_flag = match source {
+        "arxiv" => "--output-arxiv",
+        _ => "--output-crossref",
+    };
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_crossref-citation-extraction"));
+    command
+        .arg("pipeline")
+        .arg("--input")
+        .arg(snapshot)
+        .args(["--source", source, "--log-level", "OFF"])
+        .arg(output_flag)
+        .arg(&output)
+        .arg("--temp-dir")
+        .arg(run_dir.path().join("partitions"));
+    if source == "arxiv" {
+        // Validation needs DataCite records; an empty file keeps it to the lookup cost
+        let records = run_dir.path().join("datacite.jsonl.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&records).unwrap(),
+            flate2::Compression::fast(),
+        );
+        encoder.finish().unwrap();
+        command.arg("--datacite-records").arg(&records);
+    }
+
+    let status = command.status().expect("Failed to run pipeline");
+    assert!(status.success(), "Pipeline failed on {:?}", snapshot);
+}
+
+fn bench_pipeline_end_to_end(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+
+    let mut group = c.benchmark_group("pipeline_end_to_end");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(30));
+
+    for (name, shape) in shapes() {
+        let snapshot = dir.path().join(format!("{}.tar.gz", name));
+        let stats = shape.write_tar_gz(&snapshot).unwrap();
+        let source = if name == "arxiv_heavy" {
+            "arxiv"
+        } else {
+            "crossref"
+        };
+
+        // Throughput in works, so results compare across shapes and scales
+        group.throughput(Throughput::Elements(stats.items as u64));
+        group.bench_with_input(BenchmarkId::new(name, stats.items), &snapshot, |b, path| {
+            b.iter(|| run_pipeline(path, source))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline_end_to_end);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde_json::json;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use tempfile::tempdir;
+
+use crossref_citation_extraction::cli::Source;
+use crossref_citation_extraction::common::CancellationToken;
+use crossref_citation_extraction::extract::DoiConstraints;
+use crossref_citation_extraction::index::DoiIndex;
+use crossref_citation_extraction::validation::validate_citations;
+
+const RECORDS: u64 = 200_000;
+
+/// Citation records with a few citing works each; every other DOI is in the index
+fn write_records(path: &Path) -> DoiIndex {
+    let mut index = DoiIndex::new();
+    let mut out = BufWriter::new(std::fs::File::create(path).unwrap());
+    for i in 0..RECORDS {
+        let doi = format!("10.{}/work.{}", 1000 + i % 5000, i);
+        if i % 2 == 0 {
+            index.insert(&doi);
+        }
+        let cited_by: Vec<_> = (0..3)
+            .map(|j| {
+                json!({
+                    "doi": format!("10.9999/citing.{}.{}", i, j),
+                    "raw_match": doi,
+                    "reference": {"key": format!("ref{}", j), "DOI": doi},
+                    "provenance": "publisher"
+                })
+            })
+            .collect();
+        let record = json!({
+            "doi": doi,
+            "reference_count": 3,
+            "citation_count": 3,
+            "cited_by": cited_by
+        });
+        writeln!(out, "{}", record).unwrap();
+    }
+    out.flush().unwrap();
+    index
+}
+
+/// Index lookup (phase 1) across rayon pool sizes, showing the speedup of
+/// parallel parsing over a single thread
+fn bench_validate_index_lookup(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("citations.jsonl");
+    let index = write_records(&input);
+    let input = input.to_str().unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let cancel = CancellationToken::new();
+
+    let mut group = c.benchmark_group("validate_index_lookup");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(20));
+    group.throughput(Throughput::Elements(RECORDS));
+
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut thread_counts = vec![1];
+    if max_threads > 1 {
+        thread_counts.push(max_threads);
+    }
+    for threads in thread_counts {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new("threads", threads), &threads, |b, _| {
+            b.iter(|| {
+                pool.install(|| {
+                    runtime
+                        .block_on(validate_citations(
+                            input,
+                            Some(&index),
+                            None,
+                            Source::Crossref,
+                            None,
+                            DoiConstraints::default(),
+                            &cancel,
+                        ))
+                        .unwrap()
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_validate_index_lookup);
+criterion_main!(benches);
@@ -1,5 +1,14 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::str::FromStr;
+use std::time::Duration;
+
+use crate::common::{HttpClientConfig, PartialDate};
+use crate::extract::{
+    UnattributedPolicy, DEFAULT_ARXIV_RECALL_WINDOW, DEFAULT_MAX_DOI_LENGTH,
+    DEFAULT_MAX_PREFIX_DIGITS, DEFAULT_MIN_PREFIX_DIGITS,
+};
+use crate::index::IndexBackend;
+use crate::streaming::{GraphFormat, SampleStrata, DEFAULT_COMPACT_TARGET_ROWS};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Source {
@@ -38,6 +47,31 @@ impl std::fmt::Display for Source {
     }
 }
 
+/// Phase a pipeline resumed from kept intermediates starts at (--resume-phase)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ResumePhase {
+    /// Re-invert the partitions, then validate
+    Aggregate,
+    /// Validate the inverted output as it is
+    Validate,
+}
+
+/// Source whose unmatched DOIs the pipeline resolves via doi.org (--http-fallback)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HttpFallbackSource {
+    Crossref,
+    Datacite,
+    All,
+}
+
+impl HttpFallbackSource {
+    /// Whether any of `selected` enables fallback for Crossref, and for DataCite
+    pub fn enabled(selected: &[HttpFallbackSource]) -> (bool, bool) {
+        let has = |source| selected.contains(&source) || selected.contains(&Self::All);
+        (has(Self::Crossref), has(Self::Datacite))
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "crossref-citation-extraction")]
 #[command(about = "Extract, invert, and validate DOI references from Crossref data")]
@@ -58,21 +92,99 @@ pub enum Commands {
 
     /// Validate citations against records without re-running extraction
     Validate(ValidateArgs),
+
+    /// Re-validate previously failed citations and merge new matches into valid output
+    ///
+    /// Runs only the failed records against updated indexes and/or HTTP, appends
+    /// newly valid records to the existing valid output (rewriting its asserted/mined
+    /// splits), and replaces the failed output with the records that still fail.
+    Revalidate(RevalidateArgs),
+
+    /// Print schema, row count, unique cited IDs, size, and sample rows of a file
+    ///
+    /// Works on partition and inverted Parquet files and on JSONL outputs (plain or
+    /// compressed).
+    Inspect(InspectArgs),
+
+    /// Export validated citations as a graph for Neo4j bulk import or GraphML
+    ///
+    /// Works become nodes and each citing work a CITES relationship to the cited
+    /// work, carrying its provenance.
+    ExportGraph(ExportGraphArgs),
+
+    /// Draw a random sample of records from inverted or validated JSONL outputs
+    ///
+    /// Reads the inputs once, keeping only the sample in memory, for manual QA and
+    /// annotation of outputs too large to open.
+    Sample(SampleArgs),
+
+    /// Harvest works updated since a date from the Crossref REST API
+    ///
+    /// Writes a tar.gz in the snapshot layout, which `pipeline --input` reads like a
+    /// snapshot, so outputs can be kept fresh without snapshot access.
+    Harvest(HarvestArgs),
+
+    /// Remove partition directories that interrupted or crashed runs left in temp
+    ///
+    /// Only directories the pipeline created itself are considered, and never one a
+    /// running pipeline is still using.
+    Cleanup(CleanupArgs),
+
+    /// Merge small partition files and split oversized ones in a partition directory
+    ///
+    /// Every cited ID stays in one file, so the directory inverts to the same output
+    /// with more even work per partition.
+    #[cfg(feature = "parquet")]
+    CompactPartitions(CompactPartitionsArgs),
+
+    /// Build DOI indexes without running the pipeline
+    #[cfg(feature = "parquet")]
+    #[command(subcommand)]
+    Index(IndexCommands),
+
+    /// Keep a local table of Crossref members and their DOI prefixes
+    #[cfg(feature = "parquet")]
+    #[command(subcommand)]
+    Members(MembersCommands),
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Subcommand)]
+pub enum IndexCommands {
+    /// Build the Crossref DOI index from a snapshot, reading only work DOIs
+    ///
+    /// Skips reference extraction entirely, so it runs faster than a
+    /// pipeline run with --save-crossref-index. Load the result with
+    /// --load-crossref-index or validate --crossref-index.
+    BuildCrossref(BuildCrossrefIndexArgs),
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Subcommand)]
+pub enum MembersCommands {
+    /// Download the prefix to member table from the Crossref REST API members route
+    ///
+    /// Writes one row per DOI prefix (prefix, member_id, member_name, prefix_name)
+    /// as Parquet, which --publisher-names reads. A table younger than --max-age-days
+    /// is kept without contacting the API.
+    Sync(MembersSyncArgs),
 }
 
 #[derive(Parser, Clone)]
 pub struct PipelineArgs {
-    /// Path to the Crossref snapshot tar.gz file
+    /// Path to the Crossref snapshot: a tar.gz file, an uncompressed tar, or a
+    /// directory of its unpacked files
     #[arg(short, long, required = true)]
     pub input: String,
 
-    /// DataCite records.jsonl.gz file for validation
+    /// DataCite records.jsonl.gz file for validation (.bz2, .zst or plain also work)
     #[arg(long)]
     pub datacite_records: Option<String>,
 
-    /// Source to extract: all, crossref, datacite, arxiv
-    #[arg(long, default_value = "all")]
-    pub source: Source,
+    /// Sources to extract, comma-separated: all, crossref, datacite, arxiv
+    /// (e.g. crossref,arxiv; crossref,datacite is the same as all)
+    #[arg(long, value_delimiter = ',', default_value = "all")]
+    pub source: Vec<Source>,
 
     /// Output file for Crossref citations (JSONL)
     #[arg(long)]
@@ -82,7 +194,7 @@ pub struct PipelineArgs {
     #[arg(long)]
     pub output_datacite: Option<String>,
 
-    /// Output file for arXiv citations (JSONL, arxiv mode only)
+    /// Output file for arXiv citations (JSONL, arxiv source only)
     #[arg(long)]
     pub output_arxiv: Option<String>,
 
@@ -98,11 +210,48 @@ pub struct PipelineArgs {
     #[arg(long)]
     pub output_arxiv_failed: Option<String>,
 
-    /// Enable HTTP fallback for specified sources (comma-separated: crossref,datacite)
-    #[arg(long, value_delimiter = ',')]
-    pub http_fallback: Vec<String>,
+    /// Write Crossref/DataCite citations resolved via --http-fallback here instead of
+    /// the valid outputs
+    #[arg(long)]
+    pub output_http_resolved: Option<String>,
+
+    /// Write arXiv citations resolved via --http-fallback here instead of --output-arxiv
+    #[arg(long)]
+    pub output_arxiv_http_resolved: Option<String>,
 
-    /// Load Crossref DOI index from Parquet file
+    /// Also write the resolved citations of every source grouped by citing work: one
+    /// record per citing DOI listing the DOIs and arXiv IDs it cites
+    #[arg(long)]
+    pub output_citing: Option<String>,
+
+    /// Fsync plain JSONL outputs every this many records and record how far they are
+    /// durable in `<output>.hwm`, so readers can tail them while they are written and
+    /// a rerun resumes after the mark (0: off)
+    #[arg(long, default_value = "0")]
+    pub output_sync_every: usize,
+
+    /// Write each output as numbered shards (`<stem>_00001.<ext>`, ...) of at most
+    /// this many bytes of JSON Lines (e.g. 5GB), listed in `<output>.shards.json`
+    #[arg(long, value_parser = crate::common::parse_byte_size, conflicts_with = "shard_records")]
+    pub output_shard_size: Option<u64>,
+
+    /// Write each output as numbered shards of at most this many records, as
+    /// --output-shard-size does by size
+    #[arg(long)]
+    pub shard_records: Option<usize>,
+
+    /// Write DOIs of the indexes built in this run that differ only by case or
+    /// whitespace, with the cited DOIs that match them, here (costs about 32 bytes
+    /// of memory per indexed DOI)
+    #[arg(long)]
+    pub collision_report: Option<String>,
+
+    /// Resolve DOIs missing from the index via doi.org for these sources
+    /// (comma-separated: crossref, datacite, all); datacite also covers arXiv DOIs
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub http_fallback: Vec<HttpFallbackSource>,
+
+    /// Load Crossref DOI index from Parquet file, or from a sharded index directory
     #[arg(long)]
     pub load_crossref_index: Option<String>,
 
@@ -110,7 +259,7 @@ pub struct PipelineArgs {
     #[arg(long)]
     pub save_crossref_index: Option<String>,
 
-    /// Load DataCite DOI index from Parquet file
+    /// Load DataCite DOI index from Parquet file, or from a sharded index directory
     #[arg(long)]
     pub load_datacite_index: Option<String>,
 
@@ -118,6 +267,16 @@ pub struct PipelineArgs {
     #[arg(long)]
     pub save_datacite_index: Option<String>,
 
+    /// Save indexes as this many prefix-hashed shards, treating --save-crossref-index
+    /// and --save-datacite-index as directories
+    #[arg(long)]
+    pub index_shards: Option<usize>,
+
+    /// Keep only DataCite prefixes in the index; prefix matches count as probable
+    /// DataCite DOIs and HTTP fallback confirms a sample of them
+    #[arg(long, default_value = "false")]
+    pub datacite_prefix_only: bool,
+
     /// Logging level (DEBUG, INFO, WARN, ERROR)
     #[arg(short, long, default_value = "INFO")]
     pub log_level: String,
@@ -126,10 +285,225 @@ pub struct PipelineArgs {
     #[arg(short, long, default_value = "50")]
     pub concurrency: usize,
 
+    /// Adapt HTTP fallback concurrency to throttling, errors and latency (AIMD),
+    /// starting from --concurrency
+    #[arg(long)]
+    pub adaptive_concurrency: bool,
+
+    /// Lower bound for --adaptive-concurrency
+    #[arg(long, default_value = "4")]
+    pub min_concurrency: usize,
+
+    /// Upper bound for --adaptive-concurrency
+    #[arg(long, default_value = "500")]
+    pub max_concurrency: usize,
+
     /// Timeout in seconds per validation request
     #[arg(long, default_value = "5")]
     pub timeout: u64,
 
+    /// Stop checking a DOI prefix over HTTP once this many of its DOIs failed to
+    /// resolve and none resolved; its remaining DOIs count as failed (0: check all)
+    #[arg(long, default_value = "0")]
+    pub bad_prefix_threshold: usize,
+
+    /// Persist HTTP fallback results to this file and skip DOIs it already holds
+    /// (default: http_checkpoint.jsonl in --temp-dir, when given)
+    #[arg(long)]
+    pub http_checkpoint: Option<String>,
+
+    /// Answer HTTP fallback checks from this file of recorded results (the
+    /// --http-checkpoint format) instead of doi.org, for offline runs
+    #[arg(long, requires = "http_fallback")]
+    pub http_resolver_file: Option<String>,
+
+    /// Look up the registration agency of each failed DOI at doi.org/ra and record
+    /// it as `registration_agency` in the failed output
+    #[arg(long)]
+    pub registration_agency_lookup: bool,
+
+    #[command(flatten)]
+    pub http_client: HttpClientArgs,
+
+    /// Export unstructured reference strings with their DOI labels to a Parquet corpus
+    #[arg(long)]
+    pub reference_corpus: Option<String>,
+
+    /// Write references that mention arXiv or "doi" but yield no extractable ID to this
+    /// JSONL file, with their citing DOI
+    #[arg(long)]
+    pub unmatched_refs_output: Option<String>,
+
+    /// Characters of unstructured text to capture on each side of a mined match as
+    /// `context` in cited_by entries (0 disables)
+    #[arg(long, default_value = "0")]
+    pub context_chars: usize,
+
+    /// Capture the citing work's `license` URLs and `funder` DOIs and add them to its
+    /// cited_by entries as `licenses` and `funders`
+    #[arg(long, default_value = "false")]
+    pub citing_license_funder: bool,
+
+    /// Reference texts whose DOI and arXiv matches are cached, so repeated references
+    /// (textbooks, software) skip the regexes; 0 disables the cache
+    #[arg(long, default_value = "100000")]
+    pub match_cache_size: usize,
+
+    /// Fewest digits in a mined DOI's registrant code (the 1234 of 10.1234)
+    #[arg(long, default_value_t = DEFAULT_MIN_PREFIX_DIGITS)]
+    pub min_prefix_digits: usize,
+
+    /// Most digits in a mined DOI's registrant code
+    #[arg(long, default_value_t = DEFAULT_MAX_PREFIX_DIGITS)]
+    pub max_prefix_digits: usize,
+
+    /// Longest mined DOI, in bytes; longer matches are dropped
+    #[arg(long, default_value_t = DEFAULT_MAX_DOI_LENGTH)]
+    pub max_doi_length: usize,
+
+    /// Also match DOIs with whitespace around the prefix dot or slash, as OCR leaves
+    /// them ("10. 1016/j.jcp", "10.1016 /j.jcp"); the whitespace is dropped
+    #[arg(long)]
+    pub doi_spacing_tolerant: bool,
+
+    /// Also trim a capitalized name and year glued onto a mined DOI outside Elsevier,
+    /// Springer and IEEE ("10.1000/xyzSmith2020" -> "10.1000/xyz"); free-form suffixes
+    /// can end that way too
+    #[arg(long)]
+    pub trim_glued_author_year: bool,
+
+    /// Provenance for a structured DOI whose doi-asserted-by is missing or unrecognized:
+    /// mined (default), structured, or crossref
+    #[arg(long, default_value = "mined")]
+    pub unattributed_doi_provenance: UnattributedPolicy,
+
+    /// Strip HTML/JATS tags and decode entities in reference text before matching;
+    /// the raw reference is still written to the output
+    #[arg(long)]
+    pub strip_markup: bool,
+
+    /// Cap the reference JSON written with each match at about this many bytes,
+    /// shortening its longest text fields and marking it `"truncated": true`
+    #[arg(long)]
+    pub max_ref_json_bytes: Option<usize>,
+
+    /// With a DOI source, also mine arXiv IDs (arXiv:..., arxiv.org URLs) and emit them
+    /// as 10.48550/arXiv DOIs validated against DataCite, in the same pass
+    #[arg(long)]
+    pub hybrid_arxiv: bool,
+
+    /// Also match arXiv IDs written apart from "arXiv", as in "arXiv preprint
+    /// 2403.12345" or "(2023), 2403.12345, arXiv", when within --arxiv-recall-window
+    /// words of it; applies to --source arxiv and --hybrid-arxiv
+    #[arg(long)]
+    pub arxiv_recall: bool,
+
+    /// Words allowed between a bare arXiv ID and an arXiv mention with --arxiv-recall
+    #[arg(long, default_value_t = DEFAULT_ARXIV_RECALL_WINDOW, requires = "arxiv_recall")]
+    pub arxiv_recall_window: usize,
+
+    /// Categories accepted in old-format arXiv IDs such as hep-ph/9901234, one per line
+    /// (`#` comments), replacing the bundled list of archives and subject classes
+    #[arg(long)]
+    pub arxiv_legacy_categories: Option<String>,
+
+    /// Capture Crossref's is-referenced-by-count during extraction and write a Parquet
+    /// comparison with our asserted and mined citation counts per cited DOI
+    #[arg(long)]
+    pub referenced_by_comparison: Option<String>,
+
+    /// Earlier snapshot (e.g. last year's public data file) to extract and invert
+    /// alongside --input for --snapshot-comparison
+    #[arg(long)]
+    pub baseline_snapshot: Option<String>,
+
+    /// Write per-cited-work citation growth, newly citing works, and provenance shifts
+    /// from --baseline-snapshot to --input as Parquet
+    #[arg(long)]
+    pub snapshot_comparison: Option<String>,
+
+    /// Add `published_doi` to arXiv output records, from DataCite relatedIdentifiers
+    /// linking each preprint to its published version (reads --datacite-records again)
+    #[arg(long, default_value = "false")]
+    pub link_published_dois: bool,
+
+    /// Add `cited_publisher` to output records and `citing_publisher` to their
+    /// cited_by entries, from a bundled DOI prefix to publisher name table
+    #[arg(long)]
+    pub annotate_publishers: bool,
+
+    /// Tab-separated DOI prefix and publisher name per line, or a `members sync`
+    /// Parquet table, adding to or overriding the bundled table of --annotate-publishers
+    #[arg(long, requires = "annotate_publishers")]
+    pub publisher_names: Option<String>,
+
+    /// Write validated arXiv citation counts per primary category as JSONL (arxiv mode only)
+    #[arg(long)]
+    pub arxiv_categories: Option<String>,
+
+    /// arXiv metadata, JSONL with "id" and "categories" or OAI-PMH XML (plain or
+    /// compressed), used to assign categories to modern IDs for --arxiv-categories, and to
+    /// validate arXiv citations when neither --datacite-records nor --load-datacite-index
+    /// is given
+    #[arg(long)]
+    pub arxiv_metadata: Option<String>,
+
+    /// Write a top-cited report after aggregation (.md for Markdown, otherwise JSON)
+    #[arg(long)]
+    pub leaderboard: Option<String>,
+
+    /// Number of most cited works in the --leaderboard report
+    #[arg(long, default_value = "25")]
+    pub leaderboard_size: usize,
+
+    /// Text file of citing work DOIs, one per line; only these works' references are
+    /// extracted (the Crossref index still covers every work)
+    #[arg(long)]
+    pub only_dois: Option<String>,
+
+    /// Only extract references of citing works issued on or after this date (YYYY,
+    /// YYYY-MM or YYYY-MM-DD); works without an issued date are skipped
+    #[arg(long)]
+    pub since: Option<PartialDate>,
+
+    /// Only extract references of citing works issued on or before this date (YYYY,
+    /// YYYY-MM or YYYY-MM-DD); works without an issued date are skipped
+    #[arg(long)]
+    pub until: Option<PartialDate>,
+
+    /// Skip references of the first N items in the snapshot (they are still indexed);
+    /// for debugging
+    #[arg(long, default_value = "0")]
+    pub skip_records: usize,
+
+    /// Stop streaming after N items past --skip-records; for debugging
+    #[arg(long)]
+    pub limit_records: Option<usize>,
+
+    /// Path of the manifest listing every output file with its record count, size and
+    /// SHA-256 (default: manifest.json next to the first output)
+    #[arg(long)]
+    pub manifest: Option<String>,
+
+    /// Hold an exclusive lock on this file for the whole run; a run started while
+    /// another holds it fails at once
+    #[arg(long)]
+    pub lock_file: Option<String>,
+
+    /// Skip the run when the manifest records a completed run with the same
+    /// arguments and input checksums and its outputs are still present
+    #[arg(long)]
+    pub skip_if_unchanged: bool,
+
+    /// Append this run's per-source extraction and validation counts to a Parquet
+    /// history, created when missing
+    #[arg(long)]
+    pub stats_history: Option<String>,
+
+    /// Snapshot label recorded in --stats-history (default: the --input file name)
+    #[arg(long, requires = "stats_history")]
+    pub snapshot_id: Option<String>,
+
     /// Keep intermediate files (partitions, temp parquet)
     #[arg(long, default_value = "false")]
     pub keep_intermediates: bool,
@@ -138,9 +512,57 @@ pub struct PipelineArgs {
     #[arg(long)]
     pub temp_dir: Option<String>,
 
+    /// Skip extraction and resume from the partition directory of a run kept with
+    /// --keep-intermediates or --temp-dir, as listed in its intermediates.json
+    #[arg(long, conflicts_with_all = ["temp_dir", "retry_failed_partitions"])]
+    pub from_intermediates: Option<String>,
+
+    /// Phase to resume --from-intermediates at (default: validate when every target
+    /// was inverted, otherwise aggregate)
+    #[arg(long, requires = "from_intermediates")]
+    pub resume_phase: Option<ResumePhase>,
+
+    /// Skip extraction and re-invert the partitions that failed in an earlier run in
+    /// --temp-dir, merging them into its outputs and validating again
+    #[arg(long, default_value = "false")]
+    pub retry_failed_partitions: bool,
+
+    /// Continue an extraction interrupted in --temp-dir after the last tar entry its
+    /// checkpoint records, instead of extracting the snapshot from the start
+    #[arg(long, conflicts_with_all = ["from_intermediates", "retry_failed_partitions"])]
+    pub resume_extraction: bool,
+
+    /// Merge small partitions and split oversized ones after extraction, before inversion
+    #[arg(long)]
+    pub compact_partitions: bool,
+
+    /// Rows per partition aimed for by --compact-partitions
+    #[arg(long, default_value_t = DEFAULT_COMPACT_TARGET_ROWS, requires = "compact_partitions")]
+    pub compact_target_rows: usize,
+
+    /// Seconds between extraction progress log lines (files, items, matches, rows
+    /// buffered, partitions); 0 disables them
+    #[arg(long, default_value = "30")]
+    pub stats_interval: u64,
+
+    /// Threads reading and parsing the entries of an uncompressed tar or directory
+    /// --input ahead of extraction, which still takes them in archive order on one thread
+    #[arg(long, default_value = "1", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub parse_workers: usize,
+
     /// Batch size for memory management during streaming
     #[arg(long, default_value = "5000000")]
     pub batch_size: usize,
+
+    /// Memory budget (e.g. 32GB, 512MiB); derives flush thresholds, Polars chunk
+    /// sizes, and inversion concurrency, overriding --batch-size
+    #[arg(long, value_parser = crate::common::parse_byte_size)]
+    pub memory_limit: Option<u64>,
+
+    /// Warn and flush partition buffers early when RSS comes within this percentage
+    /// of --memory-limit or the cgroup memory limit, whichever is lower
+    #[arg(long, default_value = "10", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub memory_warn_percent: u8,
 }
 
 #[derive(Parser, Clone)]
@@ -149,15 +571,44 @@ pub struct ValidateArgs {
     #[arg(short, long, required = true)]
     pub input: String,
 
-    /// DataCite records.jsonl.gz file (for datacite/arxiv validation)
+    /// DataCite records.jsonl.gz file (for datacite/arxiv validation; .bz2, .zst or
+    /// plain also work)
     #[arg(long)]
     pub datacite_records: Option<String>,
 
-    /// Crossref DOI index Parquet file (for crossref validation)
+    /// Crossref DOI index Parquet file or sharded index directory (for crossref
+    /// validation)
     #[arg(long)]
     pub crossref_index: Option<String>,
 
-    /// Source type of the input file: crossref, datacite, arxiv
+    /// In-memory representation of --crossref-index: hash (default); fst, a
+    /// read-only compact form that uses a fraction of the memory; or mmap, the fst
+    /// form memory-mapped from a `<index>.fcs` file built on first use
+    #[arg(long, default_value = "hash")]
+    pub index_backend: IndexBackend,
+
+    /// Load only these shards (comma-separated) of a sharded --crossref-index and
+    /// skip records whose DOIs belong to other shards
+    #[arg(long, value_delimiter = ',')]
+    pub index_shard_ids: Vec<usize>,
+
+    /// Keep only DataCite prefixes in the index; prefix matches count as probable
+    /// DataCite DOIs and HTTP fallback confirms a sample of them
+    #[arg(long, default_value = "false")]
+    pub datacite_prefix_only: bool,
+
+    /// arXiv metadata, JSONL (e.g. the arXiv OAI snapshot) or OAI-PMH XML, plain or
+    /// compressed, to validate --source arxiv against instead of --datacite-records
+    #[arg(long, conflicts_with = "datacite_records")]
+    pub arxiv_metadata: Option<String>,
+
+    /// With --source all, look records up in --crossref-index first and validate the
+    /// rest against --datacite-records in a second pass, holding one index at a time
+    #[arg(long)]
+    pub two_pass: bool,
+
+    /// Source type of the input file: crossref, datacite, arxiv, or all (check both
+    /// indexes and attribute each DOI to the one holding it)
     #[arg(long, required = true)]
     pub source: Source,
 
@@ -169,6 +620,47 @@ pub struct ValidateArgs {
     #[arg(long, required = true)]
     pub output_failed: String,
 
+    /// With --source all, also write the valid citations attributed to Crossref here
+    #[arg(long)]
+    pub output_crossref: Option<String>,
+
+    /// With --source all, also write the valid citations attributed to DataCite here
+    #[arg(long)]
+    pub output_datacite: Option<String>,
+
+    /// With --source all, also write the failed citations attributed to Crossref here
+    #[arg(long)]
+    pub output_crossref_failed: Option<String>,
+
+    /// With --source all, also write the failed citations attributed to DataCite here
+    #[arg(long)]
+    pub output_datacite_failed: Option<String>,
+
+    /// Write citations resolved via --http-fallback here instead of --output-valid
+    #[arg(long)]
+    pub output_http_resolved: Option<String>,
+
+    /// Also write the valid citations grouped by citing work: one record per citing
+    /// DOI listing the works it cites
+    #[arg(long)]
+    pub output_citing: Option<String>,
+
+    /// Fsync plain JSONL outputs every this many records and record how far they are
+    /// durable in `<output>.hwm`, so readers can tail them while they are written and
+    /// a rerun resumes after the mark (0: off)
+    #[arg(long, default_value = "0")]
+    pub output_sync_every: usize,
+
+    /// Write each output as numbered shards (`<stem>_00001.<ext>`, ...) of at most
+    /// this many bytes of JSON Lines (e.g. 5GB), listed in `<output>.shards.json`
+    #[arg(long, value_parser = crate::common::parse_byte_size, conflicts_with = "shard_records")]
+    pub output_shard_size: Option<u64>,
+
+    /// Write each output as numbered shards of at most this many records, as
+    /// --output-shard-size does by size
+    #[arg(long)]
+    pub shard_records: Option<usize>,
+
     /// Enable HTTP fallback validation
     #[arg(long, default_value = "false")]
     pub http_fallback: bool,
@@ -177,10 +669,433 @@ pub struct ValidateArgs {
     #[arg(short, long, default_value = "50")]
     pub concurrency: usize,
 
+    /// Adapt HTTP fallback concurrency to throttling, errors and latency (AIMD),
+    /// starting from --concurrency
+    #[arg(long)]
+    pub adaptive_concurrency: bool,
+
+    /// Lower bound for --adaptive-concurrency
+    #[arg(long, default_value = "4")]
+    pub min_concurrency: usize,
+
+    /// Upper bound for --adaptive-concurrency
+    #[arg(long, default_value = "500")]
+    pub max_concurrency: usize,
+
     /// Timeout in seconds per request
     #[arg(short, long, default_value = "5")]
     pub timeout: u64,
 
+    /// Stop checking a DOI prefix over HTTP once this many of its DOIs failed to
+    /// resolve and none resolved; its remaining DOIs count as failed (0: check all)
+    #[arg(long, default_value = "0")]
+    pub bad_prefix_threshold: usize,
+
+    /// Persist HTTP fallback results to this file and skip DOIs it already holds
+    #[arg(long)]
+    pub http_checkpoint: Option<String>,
+
+    /// Answer HTTP fallback checks from this file of recorded results (the
+    /// --http-checkpoint format) instead of doi.org, for offline runs
+    #[arg(long, requires = "http_fallback")]
+    pub http_resolver_file: Option<String>,
+
+    /// Look up the registration agency of each failed DOI at doi.org/ra and record
+    /// it as `registration_agency` in the failed output
+    #[arg(long)]
+    pub registration_agency_lookup: bool,
+
+    #[command(flatten)]
+    pub http_client: HttpClientArgs,
+
+    /// Add `published_doi` to arXiv output records, from DataCite relatedIdentifiers
+    /// in --datacite-records (arxiv source only)
+    #[arg(long, default_value = "false")]
+    pub link_published_dois: bool,
+
+    /// Add `cited_publisher` to output records and `citing_publisher` to their
+    /// cited_by entries, from a bundled DOI prefix to publisher name table
+    #[arg(long)]
+    pub annotate_publishers: bool,
+
+    /// Tab-separated DOI prefix and publisher name per line, or a `members sync`
+    /// Parquet table, adding to or overriding the bundled table of --annotate-publishers
+    #[arg(long, requires = "annotate_publishers")]
+    pub publisher_names: Option<String>,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+#[derive(Parser, Clone)]
+pub struct RevalidateArgs {
+    /// Previously failed citations JSONL file
+    #[arg(short, long, required = true)]
+    pub input: String,
+
+    /// Existing valid citations JSONL file to merge newly valid records into
+    #[arg(long, required = true)]
+    pub valid: String,
+
+    /// Output file for citations that still fail (default: overwrite --input)
+    #[arg(long)]
+    pub output_failed: Option<String>,
+
+    /// HTTP-resolved citations JSONL file to merge citations newly resolved via
+    /// --http-fallback into, instead of --valid
+    #[arg(long)]
+    pub output_http_resolved: Option<String>,
+
+    /// Rewrite the outputs as numbered shards (`<stem>_00001.<ext>`, ...) of at most
+    /// this many bytes of JSON Lines, as validate does; required to rewrite an output
+    /// that was written sharded
+    #[arg(long, value_parser = crate::common::parse_byte_size, conflicts_with = "shard_records")]
+    pub output_shard_size: Option<u64>,
+
+    /// Rewrite the outputs as numbered shards of at most this many records, as
+    /// --output-shard-size does by size
+    #[arg(long)]
+    pub shard_records: Option<usize>,
+
+    /// DataCite records.jsonl.gz file (for datacite/arxiv validation; .bz2, .zst or
+    /// plain also work)
+    #[arg(long)]
+    pub datacite_records: Option<String>,
+
+    /// Crossref DOI index Parquet file or sharded index directory (for crossref
+    /// validation)
+    #[arg(long)]
+    pub crossref_index: Option<String>,
+
+    /// In-memory representation of --crossref-index: hash (default); fst, a
+    /// read-only compact form that uses a fraction of the memory; or mmap, the fst
+    /// form memory-mapped from a `<index>.fcs` file built on first use
+    #[arg(long, default_value = "hash")]
+    pub index_backend: IndexBackend,
+
+    /// Load only these shards (comma-separated) of a sharded --crossref-index and
+    /// skip records whose DOIs belong to other shards
+    #[arg(long, value_delimiter = ',')]
+    pub index_shard_ids: Vec<usize>,
+
+    /// Keep only DataCite prefixes in the index; prefix matches count as probable
+    /// DataCite DOIs and HTTP fallback confirms a sample of them
+    #[arg(long, default_value = "false")]
+    pub datacite_prefix_only: bool,
+
+    /// arXiv metadata, JSONL (e.g. the arXiv OAI snapshot) or OAI-PMH XML, plain or
+    /// compressed, to validate --source arxiv against instead of --datacite-records
+    #[arg(long, conflicts_with = "datacite_records")]
+    pub arxiv_metadata: Option<String>,
+
+    /// Source type of the input file: crossref, datacite, arxiv
+    #[arg(long, required = true)]
+    pub source: Source,
+
+    /// Enable HTTP fallback validation
+    #[arg(long, default_value = "false")]
+    pub http_fallback: bool,
+
+    /// Concurrent HTTP requests
+    #[arg(short, long, default_value = "50")]
+    pub concurrency: usize,
+
+    /// Adapt HTTP fallback concurrency to throttling, errors and latency (AIMD),
+    /// starting from --concurrency
+    #[arg(long)]
+    pub adaptive_concurrency: bool,
+
+    /// Lower bound for --adaptive-concurrency
+    #[arg(long, default_value = "4")]
+    pub min_concurrency: usize,
+
+    /// Upper bound for --adaptive-concurrency
+    #[arg(long, default_value = "500")]
+    pub max_concurrency: usize,
+
+    /// Timeout in seconds per request
+    #[arg(short, long, default_value = "5")]
+    pub timeout: u64,
+
+    /// Stop checking a DOI prefix over HTTP once this many of its DOIs failed to
+    /// resolve and none resolved; its remaining DOIs count as failed (0: check all)
+    #[arg(long, default_value = "0")]
+    pub bad_prefix_threshold: usize,
+
+    /// Persist HTTP fallback results to this file and skip DOIs it already holds
+    #[arg(long)]
+    pub http_checkpoint: Option<String>,
+
+    /// Answer HTTP fallback checks from this file of recorded results (the
+    /// --http-checkpoint format) instead of doi.org, for offline runs
+    #[arg(long, requires = "http_fallback")]
+    pub http_resolver_file: Option<String>,
+
+    /// Look up the registration agency of each failed DOI at doi.org/ra and record
+    /// it as `registration_agency` in the failed output
+    #[arg(long)]
+    pub registration_agency_lookup: bool,
+
+    #[command(flatten)]
+    pub http_client: HttpClientArgs,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+#[derive(Parser, Clone)]
+pub struct InspectArgs {
+    /// Parquet, JSONL, or JSONL.gz file to inspect
+    #[arg(short, long, required = true)]
+    pub input: String,
+
+    /// Number of sample rows to print
+    #[arg(long, default_value = "5")]
+    pub sample: usize,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "WARN")]
+    pub log_level: String,
+}
+
+#[derive(Parser, Clone)]
+pub struct ExportGraphArgs {
+    /// Validated citations JSONL files (plain or compressed, comma-separated)
+    #[arg(short, long, required = true, value_delimiter = ',')]
+    pub input: Vec<String>,
+
+    /// Output layout: neo4j (directory of admin-import CSVs) or graphml (single file)
+    #[arg(long, default_value = "neo4j")]
+    pub format: GraphFormat,
+
+    /// Output directory (neo4j) or file (graphml)
+    #[arg(short, long, required = true)]
+    pub output: String,
+
+    /// Write the neo4j node and relationship CSVs as a header file and numbered data
+    /// files (`cites_00001.csv`, ...) of at most this many bytes (e.g. 5GB)
+    #[arg(long, value_parser = crate::common::parse_byte_size, conflicts_with = "shard_records")]
+    pub output_shard_size: Option<u64>,
+
+    /// Write the neo4j CSVs as numbered data files of at most this many rows, as
+    /// --output-shard-size does by size
+    #[arg(long)]
+    pub shard_records: Option<usize>,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+#[derive(Parser, Clone)]
+pub struct SampleArgs {
+    /// JSONL files to sample from (plain or compressed, comma-separated)
+    #[arg(short, long, required = true, value_delimiter = ',')]
+    pub input: Vec<String>,
+
+    /// Output file (JSONL, .gz, .parquet; - for stdout)
+    #[arg(short, long, required = true)]
+    pub output: String,
+
+    /// Records to sample, from each stratum with --stratify
+    #[arg(short = 'n', long, required = true)]
+    pub size: usize,
+
+    /// Sample each stratum separately: prefix (DOI prefix of the cited work) or
+    /// citation-count (power-of-ten buckets of citation_count)
+    #[arg(long)]
+    pub stratify: Option<SampleStrata>,
+
+    /// Random seed; the same seed and inputs give the same sample
+    #[arg(long, default_value = "42")]
+    pub seed: u64,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+/// How commands making HTTP requests identify themselves and connect
+#[derive(Args, Clone, Debug, Default)]
+pub struct HttpClientArgs {
+    /// Contact email sent with every request, in the User-Agent and to the Crossref
+    /// API for its polite pool
+    #[arg(long)]
+    pub mailto: Option<String>,
+
+    /// User-Agent product sent with every request (default:
+    /// crossref-citation-extraction/<version>)
+    #[arg(long)]
+    pub user_agent: Option<String>,
+
+    /// Proxy URL for all requests (default: the HTTP_PROXY and HTTPS_PROXY environment
+    /// variables)
+    #[arg(long)]
+    pub proxy: Option<String>,
+
+    /// Hosts to reach without --proxy, comma-separated like NO_PROXY (`*`: use no
+    /// proxy at all, ignoring the environment variables too)
+    #[arg(long)]
+    pub no_proxy: Option<String>,
+
+    /// PEM bundle of CA certificates to trust besides the bundled roots, such as an
+    /// internal CA
+    #[arg(long)]
+    pub ca_bundle: Option<String>,
+
+    /// TCP keepalive interval in seconds, also how long idle connections stay open
+    #[arg(long)]
+    pub keepalive: Option<u64>,
+
+    /// Idle connections kept open per host
+    #[arg(long)]
+    pub max_idle_per_host: Option<usize>,
+
+    /// Requests in flight to any one host (default: bounded only by --concurrency)
+    #[arg(long)]
+    pub max_requests_per_host: Option<usize>,
+}
+
+impl HttpClientArgs {
+    pub fn config(&self) -> HttpClientConfig {
+        HttpClientConfig {
+            user_agent: self.user_agent.clone(),
+            mailto: self.mailto.clone(),
+            proxy: self.proxy.clone(),
+            no_proxy: self.no_proxy.clone(),
+            ca_bundle: self.ca_bundle.as_ref().map(Into::into),
+            keepalive: self.keepalive.map(Duration::from_secs),
+            max_idle_per_host: self.max_idle_per_host,
+            max_requests_per_host: self.max_requests_per_host,
+        }
+    }
+}
+
+#[derive(Parser, Clone)]
+pub struct HarvestArgs {
+    /// Harvest works updated on or after this date (YYYY, YYYY-MM or YYYY-MM-DD)
+    #[arg(long, required = true)]
+    pub from_update_date: PartialDate,
+
+    /// Harvest works updated on or before this date (YYYY, YYYY-MM or YYYY-MM-DD)
+    #[arg(long)]
+    pub until_update_date: Option<PartialDate>,
+
+    /// Snapshot-shaped tar.gz to write
+    #[arg(short, long, required = true)]
+    pub output: String,
+
+    /// Metadata Plus API token (default: the CROSSREF_PLUS_API_TOKEN environment
+    /// variable)
+    #[arg(long)]
+    pub plus_token: Option<String>,
+
+    /// Works per request (at most 1000)
+    #[arg(long, default_value = "1000")]
+    pub rows: usize,
+
+    /// Timeout in seconds per request
+    #[arg(short, long, default_value = "60")]
+    pub timeout: u64,
+
+    #[command(flatten)]
+    pub http_client: HttpClientArgs,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+#[derive(Parser, Clone)]
+pub struct CleanupArgs {
+    /// Directory to scan (default: the system temp directory)
+    #[arg(long)]
+    pub temp_base: Option<String>,
+
+    /// Leave directories younger than this many hours, which an interrupted run may
+    /// still be resumed from
+    #[arg(long, default_value = "24")]
+    pub min_age_hours: u64,
+
+    /// Print the directories that would be removed without removing them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Parser, Clone)]
+pub struct BuildCrossrefIndexArgs {
+    /// Crossref snapshot tar.gz
+    #[arg(short, long, required = true)]
+    pub input: String,
+
+    /// Index Parquet file to write, or directory with --shards
+    #[arg(short, long, required = true)]
+    pub output: String,
+
+    /// Save the index as this many prefix-hashed shards in the --output directory
+    #[arg(long)]
+    pub shards: Option<usize>,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Parser, Clone)]
+pub struct MembersSyncArgs {
+    /// Members table Parquet file to write
+    #[arg(short, long, default_value = "members.parquet")]
+    pub output: String,
+
+    /// Keep an existing table written less than this many days ago (0: always sync)
+    #[arg(long, default_value = "7")]
+    pub max_age_days: u64,
+
+    /// Metadata Plus API token (default: the CROSSREF_PLUS_API_TOKEN environment
+    /// variable)
+    #[arg(long)]
+    pub plus_token: Option<String>,
+
+    /// Members per request (at most 1000)
+    #[arg(long, default_value = "1000")]
+    pub rows: usize,
+
+    /// Timeout in seconds per request
+    #[arg(short, long, default_value = "60")]
+    pub timeout: u64,
+
+    #[command(flatten)]
+    pub http_client: HttpClientArgs,
+
+    /// Logging level (DEBUG, INFO, WARN, ERROR)
+    #[arg(short, long, default_value = "INFO")]
+    pub log_level: String,
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Parser, Clone)]
+pub struct CompactPartitionsArgs {
+    /// Partition directory (a kept --temp-dir, or its per-source subdirectory)
+    #[arg(short, long, required = true)]
+    pub dir: String,
+
+    /// Rows per partition aimed for: smaller partitions are merged up to it, and ones
+    /// over twice it are split into pieces of about this size
+    #[arg(long, default_value_t = DEFAULT_COMPACT_TARGET_ROWS)]
+    pub target_rows: usize,
+
+    /// Print the merges and splits without changing any file
+    #[arg(long)]
+    pub dry_run: bool,
+
     /// Logging level (DEBUG, INFO, WARN, ERROR)
     #[arg(short, long, default_value = "INFO")]
     pub log_level: String,
@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cli::CleanupArgs;
+use crate::common::{find_stale_temp_dirs, format_bytes, format_elapsed, setup_logging};
+
+pub fn run_cleanup(args: CleanupArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+
+    let base = args
+        .temp_base
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let min_age = Duration::from_secs(args.min_age_hours * 3600);
+    let stale = find_stale_temp_dirs(&base, min_age)
+        .with_context(|| format!("Failed to scan {}", base.display()))?;
+    if stale.is_empty() {
+        info!("No stale temp directories in {}", base.display());
+        return Ok(());
+    }
+
+    let mut removed = 0;
+    let mut freed = 0;
+    for dir in &stale {
+        let size = dir.size();
+        if args.dry_run {
+            println!(
+                "{} ({}, {} old)",
+                dir.path.display(),
+                format_bytes(size),
+                format_elapsed(dir.age)
+            );
+            continue;
+        }
+        match std::fs::remove_dir_all(&dir.path) {
+            Ok(()) => {
+                info!("Removed {} ({})", dir.path.display(), format_bytes(size));
+                removed += 1;
+                freed += size;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", dir.path.display(), e),
+        }
+    }
+    if !args.dry_run {
+        info!(
+            "Removed {} of {} stale temp directories, freeing {}",
+            removed,
+            stale.len(),
+            format_bytes(freed)
+        );
+    }
+    Ok(())
+}
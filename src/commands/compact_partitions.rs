@@ -0,0 +1,41 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::cli::CompactPartitionsArgs;
+use crate::common::{install_signal_handlers, setup_logging, CancellationToken};
+use crate::streaming::{compact_partitions, plan_compaction, CompactStep};
+
+pub fn run_compact_partitions(args: CompactPartitionsArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+
+    let dir = Path::new(&args.dir);
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Partition directory does not exist: {}",
+            args.dir
+        ));
+    }
+
+    if args.dry_run {
+        for step in plan_compaction(dir, args.target_rows)? {
+            match step {
+                CompactStep::Merge { inputs, rows } => {
+                    println!("merge {} partitions ({} rows):", inputs.len(), rows);
+                    for input in inputs {
+                        println!("  {}", input.display());
+                    }
+                }
+                CompactStep::Split {
+                    input,
+                    rows,
+                    pieces,
+                } => println!("split {} ({} rows) into {}", input.display(), rows, pieces),
+            }
+        }
+        return Ok(());
+    }
+
+    install_signal_handlers()?;
+    compact_partitions(dir, args.target_rows, &CancellationToken::new())?;
+    Ok(())
+}
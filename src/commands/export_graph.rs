@@ -0,0 +1,21 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::cli::ExportGraphArgs;
+use crate::common::{setup_logging, ShardLimit};
+use crate::streaming::export_graph;
+
+pub fn run_export_graph(args: ExportGraphArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+
+    for input in &args.input {
+        if !Path::new(input).exists() {
+            return Err(anyhow::anyhow!("Input file does not exist: {}", input));
+        }
+    }
+
+    let inputs: Vec<PathBuf> = args.input.iter().map(PathBuf::from).collect();
+    let shard = ShardLimit::from_size_or_records(args.output_shard_size, args.shard_records);
+    export_graph(&inputs, args.format, Path::new(&args.output), shard)?;
+    Ok(())
+}
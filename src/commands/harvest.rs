@@ -0,0 +1,46 @@
+use anyhow::Result;
+use log::info;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::cli::HarvestArgs;
+use crate::common::{format_elapsed, install_signal_handlers, setup_logging, CancellationToken};
+use crate::streaming::{harvest_works, HarvestOptions};
+
+/// Environment variable read for the Metadata Plus token when --plus-token is not given
+const PLUS_TOKEN_ENV: &str = "CROSSREF_PLUS_API_TOKEN";
+
+pub async fn run_harvest(args: HarvestArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+    install_signal_handlers()?;
+
+    let options = HarvestOptions {
+        from_update_date: args.from_update_date,
+        until_update_date: args.until_update_date,
+        client: args.http_client.config(),
+        plus_token: plus_token(args.plus_token),
+        rows: args.rows,
+        timeout: Duration::from_secs(args.timeout),
+    };
+    if options.client.mailto.is_none() && options.plus_token.is_none() {
+        info!("No --mailto or Plus token given; requests go to the public pool");
+    }
+
+    let start = Instant::now();
+    let stats = harvest_works(&options, Path::new(&args.output), &CancellationToken::new()).await?;
+    info!("Harvest complete:");
+    info!("  Pages: {}", stats.pages);
+    info!("  Works: {}", stats.works);
+    if stats.retries > 0 {
+        info!("  Retried requests: {}", stats.retries);
+    }
+    info!("  Output: {}", args.output);
+    info!("  Time: {}", format_elapsed(start.elapsed()));
+    Ok(())
+}
+
+/// The Metadata Plus token given, or else the one in the environment
+pub(super) fn plus_token(arg: Option<String>) -> Option<String> {
+    arg.or_else(|| std::env::var(PLUS_TOKEN_ENV).ok())
+        .filter(|token| !token.is_empty())
+}
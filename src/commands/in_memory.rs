@@ -0,0 +1,326 @@
+use anyhow::{Context, Result};
+use futures::executor::block_on;
+use log::{info, warn};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::pipeline::{resolve_sources, ExtractionOptions, ExtractionStats, WorkExtractor};
+use crate::cli::Source;
+use crate::common::{CancellationToken, CitationRecord};
+use crate::index::DoiIndex;
+use crate::streaming::{
+    invert_exploded_rows, ExplodedRow, OutputMode, SnapshotEntry, SnapshotInput,
+};
+use crate::validation::validate_records;
+
+/// Works an in-memory run extracts references from
+#[derive(Debug, Clone)]
+pub enum PipelineInput {
+    /// Crossref snapshot, a tar.gz of JSON or deposit XML files as `pipeline --input`
+    Snapshot(PathBuf),
+    /// Crossref work items, as in the `items` array of a snapshot file
+    Items(Vec<Value>),
+}
+
+/// Settings of [`run_pipeline_in_memory`]
+#[derive(Debug, Clone)]
+pub struct InMemoryConfig {
+    pub input: PipelineInput,
+    /// Cited IDs to extract and validate, as `pipeline --source`
+    pub sources: Vec<Source>,
+    /// Crossref DOIs to validate against; with a Crossref source and none given, the
+    /// input's works are indexed as the pipeline does
+    pub crossref_index: Option<DoiIndex>,
+    /// DataCite DOIs to validate DataCite DOIs and arXiv IDs against
+    pub datacite_index: Option<DoiIndex>,
+    /// Works to extract from, as `pipeline --only-dois`; all when None
+    pub only_dois: Option<DoiIndex>,
+    /// Matching and filtering settings, as the `pipeline` flags set them
+    pub extraction: ExtractionOptions,
+}
+
+impl InMemoryConfig {
+    /// Extract and validate `sources` from `input` with the pipeline's defaults
+    pub fn new(input: PipelineInput, sources: Vec<Source>) -> Self {
+        Self {
+            input,
+            sources,
+            crossref_index: None,
+            datacite_index: None,
+            only_dois: None,
+            extraction: ExtractionOptions::default(),
+        }
+    }
+}
+
+/// A cited work from an in-memory run, with its citing works and validation outcome
+#[derive(Debug, Clone)]
+pub struct ValidatedCitation {
+    /// Inverted record, with `validation_status` set
+    pub record: CitationRecord,
+    /// Source the record is attributed to, by prefix in all mode when no index has it
+    pub source: Source,
+    /// Found in an index
+    pub valid: bool,
+}
+
+/// Run extraction, inversion and validation in memory, without partitions or a temp
+/// directory
+///
+/// Every extracted row is held until inversion, so this suits inputs of modest size.
+/// Validation uses the indexes alone: DOIs missing from them fail without a doi.org
+/// check. Each target yields its valid records, most cited first, then its failed ones.
+pub fn run_pipeline_in_memory(
+    mut config: InMemoryConfig,
+) -> Result<impl Iterator<Item = ValidatedCitation>> {
+    let targets = resolve_sources(&config.sources);
+    if targets.is_empty() {
+        return Err(anyhow::anyhow!("At least one source is required"));
+    }
+
+    let mut crossref_index = config.crossref_index.take();
+    let build_crossref_index = crossref_index.is_none()
+        && config
+            .sources
+            .iter()
+            .any(|source| matches!(source, Source::All | Source::Crossref));
+    if build_crossref_index {
+        crossref_index = Some(DoiIndex::new());
+    }
+
+    let mut extractor = Extractor {
+        works: WorkExtractor::new(
+            &config.extraction,
+            targets.clone(),
+            config.only_dois.as_ref(),
+            0,
+        ),
+        rows: vec![Vec::new(); targets.len()],
+        stats: ExtractionStats::default(),
+        crossref_index: crossref_index.as_mut().filter(|_| build_crossref_index),
+    };
+    match config.input {
+        PipelineInput::Items(ref items) => {
+            for item in items {
+                extractor.add(item)?;
+            }
+        }
+        PipelineInput::Snapshot(ref path) => {
+            for_each_snapshot_item(path, |item| extractor.add(item))?
+        }
+    }
+    info!(
+        "Extracted {} matches from {} references in memory",
+        extractor.stats.total_matches, extractor.stats.refs_with_matches
+    );
+    let target_rows = extractor.rows;
+
+    let mut citations = Vec::new();
+    for (source, rows) in targets.into_iter().zip(target_rows) {
+        let output_mode = match source {
+            Source::Arxiv => OutputMode::Arxiv,
+            _ => OutputMode::Generic,
+        };
+        let records = invert_exploded_rows(rows, output_mode)
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<serde_json::Result<Vec<CitationRecord>>>()
+            .context("Failed to read inverted record")?;
+        info!(
+            "Inverted {} cited {} works in memory",
+            records.len(),
+            source
+        );
+
+        // Without HTTP fallback validation never awaits, so no runtime is needed
+        let results = block_on(validate_records(
+            records,
+            crossref_index.as_ref(),
+            config.datacite_index.as_ref(),
+            source,
+            None,
+            config.extraction.doi_constraints,
+            &CancellationToken::new(),
+        ))?;
+        let valid = results
+            .valid
+            .into_iter()
+            .map(|(record, source)| (record, source, true));
+        let failed = results
+            .failed
+            .into_iter()
+            .map(|(record, source)| (record, source, false));
+        citations.extend(
+            valid
+                .chain(failed)
+                .map(|(record, source, valid)| ValidatedCitation {
+                    record,
+                    source,
+                    valid,
+                }),
+        );
+    }
+
+    Ok(citations.into_iter())
+}
+
+/// Extracted rows of each target, collected a work item at a time
+struct Extractor<'a> {
+    works: WorkExtractor<'a>,
+    rows: Vec<Vec<ExplodedRow>>,
+    stats: ExtractionStats,
+    /// Crossref index built from the input's works
+    crossref_index: Option<&'a mut DoiIndex>,
+}
+
+impl Extractor<'_> {
+    fn add(&mut self, item: &Value) -> Result<()> {
+        let Some(raw_doi) = item.get("DOI").and_then(Value::as_str) else {
+            return Ok(());
+        };
+        if let Some(ref mut index) = self.crossref_index {
+            index.insert(raw_doi);
+        }
+        let work_doi: Arc<str> = Arc::from(raw_doi.to_lowercase());
+        self.works.extract(
+            item,
+            &work_doi,
+            &mut self.rows,
+            &mut self.stats,
+            |_, _, _| Ok(()),
+        )
+    }
+}
+
+/// Call `f` with every work item of a snapshot, skipping unreadable entries
+///
+/// The snapshot is opened as `pipeline --input` is, so the same formats are read.
+fn for_each_snapshot_item(path: &Path, mut f: impl FnMut(&Value) -> Result<()>) -> Result<()> {
+    let mut input = SnapshotInput::open(path)?;
+    for entry in input.entries(1, 0)? {
+        let SnapshotEntry { path, items } = entry?;
+        match items {
+            Some(Ok(items)) => {
+                for item in &items {
+                    f(item)?;
+                }
+            }
+            Some(Err(e)) => warn!("Failed to parse {}: {:#}", path, e),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ValidationStatus;
+    use serde_json::json;
+
+    fn work(doi: &str, references: &[&str]) -> Value {
+        json!({
+            "DOI": doi,
+            "type": "journal-article",
+            "reference": references
+                .iter()
+                .map(|text| json!({ "unstructured": text }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    #[test]
+    fn test_run_pipeline_in_memory() {
+        let items = vec![
+            work(
+                "10.1234/citing",
+                &[
+                    "Smith. A study. doi:10.1234/cited",
+                    "Jones. Unregistered. https://doi.org/10.1234/missing",
+                    "Self. doi:10.1234/citing",
+                ],
+            ),
+            work("10.1234/other", &["See 10.1234/cited and arXiv:2403.12345"]),
+            work("10.1234/cited", &[]),
+        ];
+        let mut config = InMemoryConfig::new(
+            PipelineInput::Items(items),
+            vec![Source::Crossref, Source::Arxiv],
+        );
+        let mut datacite = DoiIndex::new();
+        datacite.insert("10.48550/arXiv.2403.12345");
+        config.datacite_index = Some(datacite);
+
+        let citations: Vec<ValidatedCitation> = run_pipeline_in_memory(config).unwrap().collect();
+        assert_eq!(citations.len(), 3);
+
+        let cited = &citations[0];
+        assert!(cited.valid);
+        assert_eq!(cited.source, Source::Crossref);
+        assert_eq!(cited.record.doi, "10.1234/cited");
+        assert_eq!(cited.record.citation_count, 2);
+        assert_eq!(cited.record.cited_by[0]["doi"], "10.1234/citing");
+        assert_eq!(
+            cited.record.validation_status,
+            Some(ValidationStatus::MatchedIndex)
+        );
+
+        let missing = &citations[1];
+        assert!(!missing.valid);
+        assert_eq!(missing.record.doi, "10.1234/missing");
+        assert_eq!(
+            missing.record.validation_status,
+            Some(ValidationStatus::PrefixKnownUnresolved)
+        );
+
+        let arxiv = &citations[2];
+        assert!(arxiv.valid);
+        assert_eq!(arxiv.source, Source::Datacite);
+        assert_eq!(arxiv.record.arxiv_id.as_deref(), Some("2403.12345"));
+        assert_eq!(arxiv.record.cited_by[0]["doi"], "10.1234/other");
+    }
+
+    #[test]
+    fn test_run_pipeline_in_memory_extracts_as_pipeline() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let items = json!({"items": [
+            work("10.1234/listed", &["Smith. doi:10. 1234/cited"]),
+            work("10.1234/unlisted", &["Jones. doi:10.1234/other"]),
+        ]})
+        .to_string();
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("snapshot.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            std::fs::File::create(&snapshot).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(items.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "snapshot/0.json", items.as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let mut config =
+            InMemoryConfig::new(PipelineInput::Snapshot(snapshot), vec![Source::Crossref]);
+        let mut only_dois = DoiIndex::new();
+        only_dois.insert("10.1234/listed");
+        config.only_dois = Some(only_dois);
+        config.extraction.doi_spacing_tolerant = true;
+
+        let citations: Vec<ValidatedCitation> = run_pipeline_in_memory(config).unwrap().collect();
+        let dois: Vec<&str> = citations.iter().map(|c| c.record.doi.as_str()).collect();
+        assert_eq!(dois, ["10.1234/cited"]);
+    }
+
+    #[test]
+    fn test_run_pipeline_in_memory_requires_source() {
+        let config = InMemoryConfig::new(PipelineInput::Items(Vec::new()), Vec::new());
+        assert!(run_pipeline_in_memory(config).is_err());
+    }
+}
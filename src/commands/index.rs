@@ -0,0 +1,42 @@
+use anyhow::Result;
+use log::info;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::cli::{BuildCrossrefIndexArgs, IndexCommands};
+use crate::common::{format_elapsed, setup_logging};
+use crate::index::{
+    build_crossref_index_from_snapshot, save_index_to_parquet, save_sharded_index, DoiIndex,
+};
+
+pub fn run_index(command: IndexCommands) -> Result<()> {
+    match command {
+        IndexCommands::BuildCrossref(args) => run_build_crossref_index(args),
+    }
+}
+
+fn run_build_crossref_index(args: BuildCrossrefIndexArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+
+    if !Path::new(&args.input).exists() {
+        return Err(anyhow::anyhow!("Input file does not exist: {}", args.input));
+    }
+    if args.shards == Some(0) {
+        return Err(anyhow::anyhow!("--shards must be at least 1"));
+    }
+
+    let start = Instant::now();
+    let index = build_crossref_index_from_snapshot(&args.input, DoiIndex::new())?;
+    match args.shards {
+        Some(shards) => save_sharded_index(&index, &args.output, shards)?,
+        None => save_index_to_parquet(&index, &args.output)?,
+    }
+
+    info!(
+        "Crossref index with {} DOIs written to {} in {}",
+        index.len(),
+        args.output,
+        format_elapsed(start.elapsed())
+    );
+    Ok(())
+}
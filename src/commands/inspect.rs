@@ -0,0 +1,294 @@
+use anyhow::{Context, Result};
+#[cfg(feature = "parquet")]
+use polars::prelude::*;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::cli::InspectArgs;
+use crate::common::{format_bytes, setup_logging};
+use crate::streaming::open_compressed_reader;
+
+/// Columns/fields holding the cited identifier, in order of preference
+const ID_COLUMNS: [&str; 3] = ["cited_id", "doi", "arxiv_doi"];
+/// Longest sample JSONL line printed before truncation
+const MAX_SAMPLE_LINE_CHARS: usize = 500;
+
+/// Summary of a partition, inverted, or output file
+#[derive(Debug)]
+pub struct InspectReport {
+    pub format: &'static str,
+    pub size_bytes: u64,
+    pub rows: usize,
+    /// Column or field name and type
+    pub schema: Vec<(String, String)>,
+    /// Column the unique count was taken from, and the count
+    pub unique_ids: Option<(String, usize)>,
+    pub sample: String,
+}
+
+pub fn run_inspect(args: InspectArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+
+    let path = Path::new(&args.input);
+    let report = inspect_file(path, args.sample)?;
+    print!("{}", report.render(path));
+    Ok(())
+}
+
+/// Inspect a Parquet file, or JSONL (plain, .ndjson partition, or .gz), chosen by extension
+pub fn inspect_file(path: &Path, sample_rows: usize) -> Result<InspectReport> {
+    let size_bytes = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read: {:?}", path))?
+        .len();
+
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let mut report = if name.ends_with(".parquet") {
+        #[cfg(not(feature = "parquet"))]
+        return Err(crate::error::Error::requires_parquet("Inspecting a .parquet file").into());
+        #[cfg(feature = "parquet")]
+        inspect_parquet(path, sample_rows)?
+    } else if [".jsonl", ".ndjson", ".json", ".gz"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+    {
+        inspect_jsonl(path, sample_rows)?
+    } else {
+        return Err(anyhow::anyhow!(
+            "Unsupported file type: {:?} (expected .parquet, .jsonl, .ndjson, or .jsonl.gz)",
+            path
+        ));
+    };
+    report.size_bytes = size_bytes;
+    Ok(report)
+}
+
+#[cfg(feature = "parquet")]
+fn inspect_parquet(path: &Path, sample_rows: usize) -> Result<InspectReport> {
+    let mut lf = LazyFrame::scan_parquet(path, Default::default())
+        .with_context(|| format!("Failed to scan: {:?}", path))?;
+    let schema = lf.collect_schema()?;
+
+    let rows = lf
+        .clone()
+        .select([len().cast(DataType::UInt64)])
+        .collect()?
+        .column("len")?
+        .u64()?
+        .get(0)
+        .unwrap_or(0) as usize;
+
+    let unique_ids = match ID_COLUMNS.iter().find(|c| schema.contains(c)) {
+        Some(&id_column) => {
+            let unique = lf
+                .clone()
+                .select([col(id_column).n_unique().cast(DataType::UInt64)])
+                .collect()?
+                .column(id_column)?
+                .u64()?
+                .get(0)
+                .unwrap_or(0) as usize;
+            Some((id_column.to_string(), unique))
+        }
+        None => None,
+    };
+
+    let sample = lf.limit(sample_rows as IdxSize).collect()?;
+
+    Ok(InspectReport {
+        format: "parquet",
+        size_bytes: 0,
+        rows,
+        schema: schema
+            .iter()
+            .map(|(name, dtype)| (name.to_string(), dtype.to_string()))
+            .collect(),
+        unique_ids,
+        sample: format!("{}\n", sample),
+    })
+}
+
+fn inspect_jsonl(path: &Path, sample_rows: usize) -> Result<InspectReport> {
+    let input = open_compressed_reader(path)?;
+
+    let mut rows = 0;
+    let mut failed = 0;
+    let mut fields: Vec<(String, Vec<&'static str>)> = Vec::new();
+    let mut id_field: Option<&'static str> = None;
+    let mut ids: HashSet<String> = HashSet::new();
+    let mut sample = String::new();
+
+    for line_result in BufReader::new(input).lines() {
+        let line = line_result.context("Failed to read line")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        rows += 1;
+
+        if rows <= sample_rows {
+            sample.push_str(&truncate_chars(&line, MAX_SAMPLE_LINE_CHARS));
+            sample.push('\n');
+        }
+
+        let Ok(Value::Object(record)) = serde_json::from_str::<Value>(&line) else {
+            failed += 1;
+            continue;
+        };
+
+        for (key, value) in &record {
+            let type_name = json_type_name(value);
+            match fields.iter_mut().find(|(name, _)| name == key) {
+                Some((_, types)) if !types.contains(&type_name) => types.push(type_name),
+                Some(_) => {}
+                None => fields.push((key.clone(), vec![type_name])),
+            }
+        }
+
+        let id_field = *id_field.get_or_insert_with(|| {
+            ID_COLUMNS
+                .iter()
+                .copied()
+                .find(|f| record.contains_key(*f))
+                .unwrap_or("")
+        });
+        if let Some(id) = record.get(id_field).and_then(|v| v.as_str()) {
+            if !ids.contains(id) {
+                ids.insert(id.to_string());
+            }
+        }
+    }
+
+    let mut schema: Vec<(String, String)> = fields
+        .into_iter()
+        .map(|(name, types)| (name, types.join(" | ")))
+        .collect();
+    if failed > 0 {
+        schema.push(("(unparsed lines)".to_string(), failed.to_string()));
+    }
+
+    Ok(InspectReport {
+        format: "jsonl",
+        size_bytes: 0,
+        rows,
+        schema,
+        unique_ids: id_field
+            .filter(|f| !f.is_empty())
+            .map(|f| (f.to_string(), ids.len())),
+        sample,
+    })
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn truncate_chars(line: &str, max_chars: usize) -> String {
+    match line.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+impl InspectReport {
+    /// Human-readable report
+    pub fn render(&self, path: &Path) -> String {
+        let mut out = format!("File: {}\n", path.display());
+        out.push_str(&format!("Format: {}\n", self.format));
+        out.push_str(&format!("Size: {}\n", format_bytes(self.size_bytes)));
+        out.push_str(&format!("Rows: {}\n", self.rows));
+        if let Some((column, unique)) = &self.unique_ids {
+            out.push_str(&format!("Unique {}: {}\n", column, unique));
+        }
+        out.push_str("\nSchema:\n");
+        for (name, dtype) in &self.schema {
+            out.push_str(&format!("  {}: {}\n", name, dtype));
+        }
+        if !self.sample.is_empty() {
+            out.push_str("\nSample:\n");
+            out.push_str(&self.sample);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_inspect_jsonl() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("out.jsonl");
+        let mut file = File::create(&path).unwrap();
+        writeln!(
+            file,
+            r#"{{"doi": "10.1/a", "citation_count": 2, "cited_by": []}}"#
+        )
+        .unwrap();
+        writeln!(file, r#"{{"doi": "10.1/a", "citation_count": null}}"#).unwrap();
+        writeln!(file, r#"{{"doi": "10.1/b", "citation_count": 1}}"#).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        let report = inspect_file(&path, 2).unwrap();
+        assert_eq!(report.format, "jsonl");
+        assert_eq!(report.rows, 4);
+        assert_eq!(report.unique_ids, Some(("doi".to_string(), 2)));
+        assert_eq!(report.sample.lines().count(), 2);
+        assert!(report
+            .schema
+            .contains(&("citation_count".to_string(), "integer | null".to_string())));
+        assert!(report
+            .schema
+            .contains(&("(unparsed lines)".to_string(), "1".to_string())));
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_inspect_parquet() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("partition.parquet");
+        let mut df = df!(
+            "citing_doi" => ["10.9/x", "10.9/y", "10.9/z"],
+            "cited_id" => ["10.1/a", "10.1/a", "10.1/b"],
+        )
+        .unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        let report = inspect_file(&path, 1).unwrap();
+        assert_eq!(report.format, "parquet");
+        assert_eq!(report.rows, 3);
+        assert_eq!(report.unique_ids, Some(("cited_id".to_string(), 2)));
+        assert_eq!(
+            report.schema[0],
+            ("citing_doi".to_string(), "str".to_string())
+        );
+        assert!(report.size_bytes > 0);
+        assert!(report.render(&path).contains("Unique cited_id: 2"));
+    }
+
+    #[test]
+    fn test_inspect_unsupported_extension() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        File::create(&path).unwrap();
+        assert!(inspect_file(&path, 5).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use log::info;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::harvest::plus_token;
+use crate::cli::{MembersCommands, MembersSyncArgs};
+use crate::common::{format_elapsed, install_signal_handlers, setup_logging, CancellationToken};
+use crate::streaming::{members_table_is_fresh, sync_members, MembersOptions};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub async fn run_members(command: MembersCommands) -> Result<()> {
+    match command {
+        MembersCommands::Sync(args) => run_members_sync(args).await,
+    }
+}
+
+async fn run_members_sync(args: MembersSyncArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+    install_signal_handlers()?;
+
+    let output = Path::new(&args.output);
+    let max_age = Duration::from_secs(args.max_age_days * SECONDS_PER_DAY);
+    if members_table_is_fresh(output, max_age) {
+        info!(
+            "Members table {} is less than {} days old; keeping it",
+            args.output, args.max_age_days
+        );
+        return Ok(());
+    }
+
+    let options = MembersOptions {
+        client: args.http_client.config(),
+        plus_token: plus_token(args.plus_token),
+        rows: args.rows,
+        timeout: Duration::from_secs(args.timeout),
+    };
+    if options.client.mailto.is_none() && options.plus_token.is_none() {
+        info!("No --mailto or Plus token given; requests go to the public pool");
+    }
+
+    let start = Instant::now();
+    let stats = sync_members(&options, output, &CancellationToken::new()).await?;
+    info!("Members sync complete:");
+    info!("  Members: {}", stats.members);
+    info!("  Prefixes: {}", stats.prefixes);
+    if stats.retries > 0 {
+        info!("  Retried requests: {}", stats.retries);
+    }
+    info!("  Output: {}", args.output);
+    info!("  Time: {}", format_elapsed(start.elapsed()));
+    Ok(())
+}
@@ -1,5 +1,38 @@
+pub mod cleanup;
+#[cfg(feature = "parquet")]
+pub mod compact_partitions;
+pub mod export_graph;
+pub mod harvest;
+pub mod in_memory;
+#[cfg(feature = "parquet")]
+pub mod index;
+pub mod inspect;
+#[cfg(feature = "parquet")]
+pub mod members;
 pub mod pipeline;
+pub mod revalidate;
+pub mod sample;
 pub mod validate;
 
+pub use cleanup::run_cleanup;
+#[cfg(feature = "parquet")]
+pub use compact_partitions::run_compact_partitions;
+pub use export_graph::run_export_graph;
+pub use harvest::run_harvest;
+#[cfg(feature = "parquet")]
+pub use index::run_index;
+pub use inspect::run_inspect;
+#[cfg(feature = "parquet")]
+pub use members::run_members;
 pub use pipeline::run_pipeline;
+pub use revalidate::run_revalidate;
+pub use sample::run_sample;
 pub use validate::run_validate;
+
+// Re-export the cancellable pipeline for library users
+#[allow(unused_imports)]
+pub use pipeline::run_pipeline_with_cancel;
+
+// Re-export the in-memory pipeline for library users
+pub use in_memory::{run_pipeline_in_memory, InMemoryConfig, PipelineInput, ValidatedCitation};
+pub use pipeline::ExtractionOptions;
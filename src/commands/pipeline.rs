@@ -1,29 +1,75 @@
 use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
 use log::{debug, info, warn};
 use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use tar::Archive;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::cli::{PipelineArgs, Source};
-use crate::common::setup_logging;
-use crate::extract::{extract_arxiv_matches_from_text, extract_doi_matches_from_text, Provenance};
+use crate::cli::{HttpFallbackSource, PipelineArgs, ResumePhase, Source};
+#[cfg(feature = "parquet")]
+use crate::common::{append_stats_history, StatsRow};
+use crate::common::{
+    describe_inputs, find_stale_temp_dirs, format_bytes, install_signal_handlers, is_interrupted,
+    manifest_is_current, open_sink_with, run_blocking, setup_logging, write_manifest,
+    CancellationToken, DateRange, ExtractionProgress, HighWaterMark, Interrupted, MemoryBudget,
+    MemoryMonitor, MemoryPressure, MultiValidateStats, OutputOptions, RunLock, ShardLimit,
+    ShardManifest, SplitOutputPaths, StatsLogger, TempDirMarker, DEFAULT_STALE_AGE, MANIFEST_FILE,
+    STDOUT_PATH, TEMP_DIR_PREFIX,
+};
+use crate::extract::{
+    extract_context, normalize_arxiv_id, strip_markup, DoiConstraints, LegacyArxivCategories,
+    MatchCache, MatchField, Provenance, UnattributedPolicy,
+};
+#[cfg(feature = "parquet")]
+use crate::extract::{extract_doi_matches_from_text, normalize_doi};
+use crate::index::{
+    aggregate_by_category, build_arxiv_index_from_metadata, build_index_from_doi_list,
+    build_index_from_jsonl_gz, build_index_from_jsonl_gz_into, build_prefix_index_from_jsonl_gz,
+    write_category_counts, ArxivCategories, CollisionReport, DoiIndex, PublisherNames,
+};
+#[cfg(feature = "parquet")]
 use crate::index::{
-    build_index_from_jsonl_gz, load_index_from_parquet, save_index_to_parquet, DoiIndex,
+    is_sharded_index, load_index_from_parquet, load_prefix_index_from_parquet, load_sharded_index,
+    load_sharded_prefix_index, save_index_to_parquet, save_sharded_index, IndexBackend,
+};
+#[cfg(feature = "parquet")]
+use crate::streaming::{
+    compact_partitions, compare_referenced_by_counts, compare_snapshots, CorpusRow, Leaderboard,
+    ReferenceCorpusWriter, ReferencedByWriter,
+};
+use crate::streaming::{
+    export_inverted_jsonl, invert_partitions, retry_failed_partitions, Checkpoint, CitingWork,
+    ExplodedRow, ExtractedMatch, IntermediatesManifest, InvertStats, OutputMode, PartitionWriter,
+    PipelinePhase, SnapshotEntry, SnapshotFormat, SnapshotInput, UnmatchedHint,
+    UnmatchedRefsWriter, INTERMEDIATES_FILE, INVERTED_FILE, PARTITION_EXTENSION,
 };
-use crate::streaming::{invert_partitions, Checkpoint, OutputMode, PartitionWriter};
 use crate::validation::{
-    validate_citations, write_arxiv_validation_results_with_split, write_split_validation_results,
-    write_validation_results_with_split,
+    annotate_publishers, link_published_dois, log_validation_stats, lookup_registration_agencies,
+    open_resolver, split_off_http_resolved, validate_citations,
+    write_arxiv_validation_results_with_split, write_http_resolved_results,
+    write_split_validation_results, write_validation_results_with_split, CitingWorks,
+    ConcurrencyBounds, HttpFallback, ValidationResults,
 };
 
-/// Progress logging interval (every N files)
-const PROGRESS_LOG_INTERVAL: usize = 100;
 /// Divisor for computing flush threshold from batch size
 const FLUSH_THRESHOLD_DIVISOR: usize = 100;
+/// Pipeline checkpoint file within the partition directory
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+/// Partitions of the --baseline-snapshot, within each target's partition directory
+const BASELINE_DIR: &str = "baseline";
+/// Default HTTP fallback results sidecar within a persistent --temp-dir
+const HTTP_CHECKPOINT_FILE: &str = "http_checkpoint.jsonl";
+/// Captured is-referenced-by-count values, in a subdirectory so inversion never
+/// mistakes them for a partition
+const REFERENCED_BY_COUNTS_FILE: &str = "referenced_by/counts.parquet";
+/// Normalized (lowercase) prefix of the DOIs arXiv registers with DataCite
+const ARXIV_DOI_PREFIX: &str = "10.48550/arxiv.";
 
 /// Check if a citation should be included (filters out self-citations)
 fn should_include_citation(citing_doi: &str, cited_id: &str) -> bool {
@@ -32,7 +78,14 @@ fn should_include_citation(citing_doi: &str, cited_id: &str) -> bool {
 }
 
 /// Determine the provenance of a DOI based on how it was found in the reference
-fn determine_provenance(reference: &Value, extracted_doi: &str) -> Provenance {
+///
+/// A DOI from the structured DOI field without a recognized `doi-asserted-by` is
+/// labelled according to `policy`.
+fn determine_provenance(
+    reference: &Value,
+    extracted_doi: &str,
+    policy: UnattributedPolicy,
+) -> Provenance {
     // Check if there's an explicit DOI field
     if let Some(doi_field) = reference.get("DOI").and_then(|v| v.as_str()) {
         // Check if the extracted DOI matches the DOI field (normalized comparison)
@@ -41,15 +94,8 @@ fn determine_provenance(reference: &Value, extracted_doi: &str) -> Provenance {
 
         if doi_field_normalized == extracted_normalized {
             // DOI came from the explicit DOI field - check doi-asserted-by
-            if let Some(asserted_by) = reference.get("doi-asserted-by").and_then(|v| v.as_str()) {
-                return match asserted_by {
-                    "publisher" => Provenance::Publisher,
-                    "crossref" => Provenance::Crossref,
-                    _ => Provenance::Mined, // Unknown assertion type
-                };
-            }
-            // DOI field present but no doi-asserted-by
-            return Provenance::Mined;
+            let asserted_by = reference.get("doi-asserted-by").and_then(|v| v.as_str());
+            return Provenance::from_asserted_by(asserted_by, policy);
         }
     }
 
@@ -57,360 +103,1802 @@ fn determine_provenance(reference: &Value, extracted_doi: &str) -> Provenance {
     Provenance::Mined
 }
 
+/// Capture the unstructured text surrounding a mined match
+///
+/// Asserted DOIs come from the DOI field and have no meaningful context, so only mined
+/// matches found in the unstructured string get one.
+fn match_context(
+    reference: &Value,
+    raw_match: &str,
+    provenance: Provenance,
+    context_chars: usize,
+) -> Option<String> {
+    if provenance != Provenance::Mined || context_chars == 0 {
+        return None;
+    }
+    let unstructured = reference.get("unstructured").and_then(|v| v.as_str())?;
+    extract_context(unstructured, raw_match, context_chars)
+}
+
+/// Attach the matched reference field, its offsets and optional context to a raw match
+fn build_extracted_match(
+    reference: &Value,
+    raw_match: String,
+    cited_id: String,
+    provenance: Provenance,
+    context_chars: usize,
+) -> ExtractedMatch {
+    let located = MatchField::locate(reference, &raw_match);
+    let context = match_context(reference, &raw_match, provenance, context_chars);
+    ExtractedMatch {
+        raw_match,
+        cited_id,
+        provenance,
+        match_field: located.map(|(field, _)| field),
+        match_span: located.map(|(_, span)| span),
+        context,
+    }
+}
+
+/// A kind of cited ID extracted, inverted, and validated by a pipeline run
+struct SourceTarget {
+    /// DOI mode (all, crossref, or datacite) or arxiv
+    source: Source,
+    partition_dir: PathBuf,
+}
+
+/// Reduce the selected sources to one DOI target and/or one arXiv target
+///
+/// Crossref and DataCite DOIs come from the same extraction, so selecting both is
+/// equivalent to all.
+pub(super) fn resolve_sources(sources: &[Source]) -> Vec<Source> {
+    let has = |source: Source| sources.contains(&source);
+
+    let doi_source = if has(Source::All) || (has(Source::Crossref) && has(Source::Datacite)) {
+        Some(Source::All)
+    } else if has(Source::Crossref) {
+        Some(Source::Crossref)
+    } else if has(Source::Datacite) {
+        Some(Source::Datacite)
+    } else {
+        None
+    };
+
+    doi_source
+        .into_iter()
+        .chain(has(Source::Arxiv).then_some(Source::Arxiv))
+        .collect()
+}
+
 struct PipelineIndexes {
     crossref: Option<DoiIndex>,
     datacite: Option<DoiIndex>,
+    /// arXiv DOIs from --arxiv-metadata, validating the arXiv target alone when no
+    /// DataCite index is loaded
+    arxiv: Option<DoiIndex>,
+    /// Citing works to extract references from (--only-dois)
+    only_dois: Option<DoiIndex>,
+    /// Publisher names to annotate outputs with (--annotate-publishers)
+    publishers: Option<PublisherNames>,
 }
 
 /// Statistics from the extraction phase
 #[derive(Debug, Clone, Default)]
 pub struct ExtractionStats {
+    pub tar_entries_processed: usize,
     pub files_processed: usize,
     pub items_processed: usize,
+    /// Items skipped because their DOI is not in --only-dois
+    pub items_not_listed: usize,
+    /// Items skipped because their issued date is outside --since/--until
+    pub items_out_of_range: usize,
+    /// Items skipped by --skip-records
+    pub items_skipped: usize,
+    /// Partition buffer flushes forced by the memory monitor
+    pub emergency_flushes: usize,
+    /// References identical to an earlier reference of the same work, not emitted
+    pub duplicate_refs: usize,
+    /// References whose text contained markup stripped by --strip-markup
+    pub markup_refs_stripped: usize,
+    /// Matched references whose JSON was shortened to --max-ref-json-bytes
+    pub refs_truncated: usize,
+    /// Regex lookups answered by the --match-cache-size cache
+    pub match_cache_hits: u64,
+    /// Regex lookups that missed the cache
+    pub match_cache_misses: u64,
+    /// Regex lookups skipped because the text had no "10." or "arxiv"
+    pub match_lookups_screened: u64,
     pub refs_with_matches: usize,
     pub total_matches: usize,
     pub crossref_dois_indexed: usize,
+    pub corpus_rows_written: usize,
+    pub unmatched_refs_written: usize,
 }
 
 fn load_indexes(args: &PipelineArgs) -> Result<PipelineIndexes> {
     let mut indexes = PipelineIndexes {
         crossref: None,
         datacite: None,
+        arxiv: None,
+        only_dois: None,
+        publishers: None,
     };
 
+    if args.annotate_publishers {
+        indexes.publishers = Some(PublisherNames::load(args.publisher_names.as_deref())?);
+    }
+
+    if let Some(ref path) = args.only_dois {
+        indexes.only_dois = Some(build_index_from_doi_list(path)?);
+    }
+
     // Load or defer Crossref index (built during streaming)
     if let Some(ref path) = args.load_crossref_index {
         info!("Loading Crossref index from: {}", path);
-        indexes.crossref = Some(load_index_from_parquet(path)?);
+        indexes.crossref = Some(load_index(path)?);
     }
 
     // Load or build DataCite index
     if let Some(ref path) = args.load_datacite_index {
-        info!("Loading DataCite index from: {}", path);
-        indexes.datacite = Some(load_index_from_parquet(path)?);
+        if args.datacite_prefix_only {
+            info!("Loading DataCite prefixes from: {}", path);
+            indexes.datacite = Some(load_prefix_index(path)?);
+        } else {
+            info!("Loading DataCite index from: {}", path);
+            indexes.datacite = Some(load_index(path)?);
+        }
     } else if let Some(ref path) = args.datacite_records {
-        info!("Building DataCite index from: {}", path);
-        indexes.datacite = Some(build_index_from_jsonl_gz(path, "id")?);
+        if args.datacite_prefix_only {
+            info!("Building DataCite prefix index from: {}", path);
+            indexes.datacite = Some(build_prefix_index_from_jsonl_gz(path, "id")?);
+        } else if args.collision_report.is_some() {
+            info!("Building DataCite index from: {}", path);
+            let index = DoiIndex::with_capacity(10_000_000, 100_000).with_collision_tracking();
+            indexes.datacite = Some(build_index_from_jsonl_gz_into(path, "id", index)?);
+        } else {
+            info!("Building DataCite index from: {}", path);
+            indexes.datacite = Some(build_index_from_jsonl_gz(path, "id")?);
+        }
+    } else if let Some(ref path) = args.arxiv_metadata {
+        // arXiv DOIs are DataCite DOIs; without the dump, index them from arXiv metadata
+        if resolve_sources(&args.source).contains(&Source::Arxiv) {
+            indexes.arxiv = Some(build_arxiv_index_from_metadata(path)?);
+        }
     }
 
     Ok(indexes)
 }
 
+/// Load a saved index file or every shard of a sharded index directory
+#[cfg(feature = "parquet")]
+fn load_index(path: &str) -> Result<DoiIndex> {
+    let index = if is_sharded_index(path) {
+        load_sharded_index(path, &[], IndexBackend::Hash)?
+    } else {
+        load_index_from_parquet(path)?
+    };
+    Ok(index)
+}
+
+/// Load the prefixes of a saved index file or of every shard of a sharded index
+#[cfg(feature = "parquet")]
+fn load_prefix_index(path: &str) -> Result<DoiIndex> {
+    let index = if is_sharded_index(path) {
+        load_sharded_prefix_index(path)?
+    } else {
+        load_prefix_index_from_parquet(path)?
+    };
+    Ok(index)
+}
+
+/// Save an index as one file, or as `shards` files in a directory
+#[cfg(feature = "parquet")]
+fn save_index(index: &DoiIndex, path: &str, shards: Option<usize>) -> Result<()> {
+    match shards {
+        Some(shards) => save_sharded_index(index, path, shards)?,
+        None => save_index_to_parquet(index, path)?,
+    }
+    Ok(())
+}
+
+/// Saved indexes are Parquet; `check_parquet_options` rejects them before a run starts
+#[cfg(not(feature = "parquet"))]
+fn load_index(path: &str) -> Result<DoiIndex> {
+    Err(crate::error::Error::requires_parquet(format!("Loading the index {}", path)).into())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn load_prefix_index(path: &str) -> Result<DoiIndex> {
+    load_index(path)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn save_index(_index: &DoiIndex, path: &str, _shards: Option<usize>) -> Result<()> {
+    Err(crate::error::Error::requires_parquet(format!("Saving the index {}", path)).into())
+}
+
+#[cfg(feature = "parquet")]
+/// Build a corpus row for a reference with unstructured text
+///
+/// The DOI label is the reference's DOI field when present, otherwise the first DOI
+/// mined from the unstructured string; references with neither are kept unlabeled.
+fn build_corpus_row(
+    citing_doi: &str,
+    ref_index: u32,
+    reference: &Value,
+    policy: UnattributedPolicy,
+) -> Option<CorpusRow> {
+    let unstructured = reference.get("unstructured").and_then(|v| v.as_str())?;
+
+    let (doi, provenance) = match reference.get("DOI").and_then(|v| v.as_str()) {
+        Some(doi_field) => {
+            let doi = normalize_doi(doi_field);
+            let provenance = determine_provenance(reference, &doi, policy);
+            (Some(doi), Some(provenance))
+        }
+        None => match extract_doi_matches_from_text(unstructured)
+            .into_iter()
+            .next()
+        {
+            Some(m) => (Some(m.doi), Some(Provenance::Mined)),
+            None => (None, None),
+        },
+    };
+
+    Some(CorpusRow {
+        citing_doi: citing_doi.to_string(),
+        ref_index,
+        unstructured: unstructured.to_string(),
+        doi,
+        provenance,
+    })
+}
+
+/// Copy of a reference with markup stripped from its searched text fields, or None
+/// when no field contains markup
+///
+/// Matching and context use the copy; the original reference is what gets written out.
+fn strip_reference_markup(reference: &Value) -> Option<Value> {
+    let mut stripped: Option<Value> = None;
+    for field in MatchField::SEARCH_ORDER {
+        let Some(text) = reference.get(field.json_key()).and_then(Value::as_str) else {
+            continue;
+        };
+        if let Cow::Owned(clean) = strip_markup(text) {
+            if clean != text {
+                stripped.get_or_insert_with(|| reference.clone())[field.json_key()] =
+                    Value::String(clean);
+            }
+        }
+    }
+    stripped
+}
+
+/// Key added to a reference whose JSON was shortened by [`truncate_reference`]
+const TRUNCATED_KEY: &str = "truncated";
+
+/// Copy of a reference shortened to serialize in about `max_bytes`, or None when it
+/// already fits
+///
+/// Pathological deposits put megabytes of text in a single field, so the longest
+/// string fields are cut first, each at a character boundary, and the copy is marked
+/// `"truncated": true`. The `key` is never cut, so matches still map back to the
+/// citing work's reference. Only the JSON written with matches is shortened; matching and
+/// context use the whole reference, so match offsets may point past a cut field.
+fn truncate_reference(reference: &Value, max_bytes: usize) -> Option<Value> {
+    let size = reference.to_string().len();
+    let Value::Object(fields) = reference else {
+        return None;
+    };
+    if size <= max_bytes {
+        return None;
+    }
+
+    // Cutting a raw byte shortens the JSON by at least a byte, escapes by more
+    let flag_bytes = format!(",\"{}\":true", TRUNCATED_KEY).len();
+    let mut excess = size - max_bytes + flag_bytes;
+    let mut by_length: Vec<(&String, &str)> = fields
+        .iter()
+        .filter(|(key, _)| key.as_str() != "key")
+        .filter_map(|(key, value)| Some((key, value.as_str()?)))
+        .collect();
+    by_length.sort_by_key(|(_, text)| std::cmp::Reverse(text.len()));
+
+    let mut truncated = fields.clone();
+    for (key, text) in by_length {
+        if excess == 0 {
+            break;
+        }
+        let keep = text.floor_char_boundary(text.len().saturating_sub(excess));
+        excess = excess.saturating_sub(text.len() - keep);
+        truncated.insert(key.clone(), Value::String(text[..keep].to_string()));
+    }
+    truncated.insert(TRUNCATED_KEY.to_string(), Value::Bool(true));
+    Some(Value::Object(truncated))
+}
+
+/// Indexes of references identical to an earlier reference of the same work
+///
+/// Publishers sometimes deposit a reference twice, key included. Only the first copy
+/// is emitted, so a duplicate does not count as a second citation. Candidates are
+/// bucketed by key, DOI and unstructured string so only likely duplicates are compared.
+fn duplicate_references(references: &[Value]) -> HashSet<usize> {
+    fn field<'a>(reference: &'a Value, name: &str) -> Option<&'a str> {
+        reference.get(name).and_then(Value::as_str)
+    }
+    let mut buckets: HashMap<[Option<&str>; 3], Vec<usize>> = HashMap::new();
+    let mut duplicates = HashSet::new();
+    for (idx, reference) in references.iter().enumerate() {
+        let bucket = buckets
+            .entry([
+                field(reference, "key"),
+                field(reference, "DOI"),
+                field(reference, "unstructured"),
+            ])
+            .or_default();
+        if bucket
+            .iter()
+            .any(|&earlier| references[earlier] == *reference)
+        {
+            duplicates.insert(idx);
+        } else {
+            bucket.push(idx);
+        }
+    }
+    duplicates
+}
+
+/// Text of every reference field that might contain arXiv IDs or DOIs, space-separated
+fn search_text(reference: &Value) -> String {
+    let mut search_text = String::new();
+    for field in MatchField::SEARCH_ORDER {
+        if let Some(text) = reference.get(field.json_key()).and_then(|v| v.as_str()) {
+            search_text.push_str(text);
+            search_text.push(' ');
+        }
+    }
+    search_text
+}
+
 /// Determine if we should build the Crossref index during extraction
 fn should_build_crossref_index(args: &PipelineArgs) -> bool {
     // Build the index if:
     // 1. We're extracting DOIs (not arxiv mode) AND
     // 2. We don't already have a loaded index AND
     // 3. We need the index for validation (crossref or all mode)
-    args.load_crossref_index.is_none() && matches!(args.source, Source::All | Source::Crossref)
+    args.load_crossref_index.is_none()
+        && args
+            .source
+            .iter()
+            .any(|source| matches!(source, Source::All | Source::Crossref))
+}
+
+/// Extract matches from a reference's search text based on source mode
+///
+/// With `hybrid_arxiv`, a DOI source also yields the reference's arXiv IDs in DOI form.
+fn extract_matches(
+    source: Source,
+    reference: &Value,
+    search_text: &str,
+    context_chars: usize,
+    policy: UnattributedPolicy,
+    hybrid_arxiv: bool,
+    cache: &mut MatchCache,
+) -> Vec<ExtractedMatch> {
+    match source {
+        Source::Arxiv => {
+            // Extract arXiv IDs (just the ID, not the DOI - DOI is constructed in invert step)
+            // For arXiv, determine provenance based on whether DOI field exists
+            cache
+                .arxiv_matches(search_text)
+                .into_iter()
+                .map(|m| {
+                    let arxiv_doi = format!("10.48550/arXiv.{}", m.id);
+                    let provenance = determine_provenance(reference, &arxiv_doi, policy);
+                    build_extracted_match(reference, m.raw, m.id, provenance, context_chars)
+                })
+                .collect()
+        }
+        Source::All | Source::Crossref | Source::Datacite => {
+            // Extract DOIs
+            let mut matches: Vec<ExtractedMatch> = cache
+                .doi_matches(search_text)
+                .into_iter()
+                .map(|m| {
+                    let provenance = determine_provenance(reference, &m.doi, policy);
+                    build_extracted_match(reference, m.raw, m.doi, provenance, context_chars)
+                })
+                .collect();
+            if hybrid_arxiv {
+                add_arxiv_doi_matches(
+                    &mut matches,
+                    reference,
+                    search_text,
+                    context_chars,
+                    policy,
+                    cache,
+                );
+            }
+            matches
+        }
+    }
+}
+
+/// Add a reference's arXiv IDs to its DOI matches as 10.48550/arxiv DOIs
+///
+/// arXiv DOIs already mined as DOIs are canonicalized the way arXiv IDs are (version
+/// suffix dropped), so `arXiv:2403.03542v2` and `10.48550/arXiv.2403.03542` in one
+/// reference are a single match. Provenance is decided before canonicalizing, so an
+/// asserted DOI field with a version suffix stays asserted.
+fn add_arxiv_doi_matches(
+    matches: &mut Vec<ExtractedMatch>,
+    reference: &Value,
+    search_text: &str,
+    context_chars: usize,
+    policy: UnattributedPolicy,
+    cache: &mut MatchCache,
+) {
+    let mut seen = HashSet::new();
+    matches.retain_mut(|m| {
+        if let Some(id) = m.cited_id.strip_prefix(ARXIV_DOI_PREFIX) {
+            m.cited_id = format!("{}{}", ARXIV_DOI_PREFIX, normalize_arxiv_id(id));
+        }
+        seen.insert(m.cited_id.clone())
+    });
+
+    for m in cache.arxiv_matches(search_text) {
+        let arxiv_doi = m.arxiv_doi.to_lowercase();
+        if !seen.insert(arxiv_doi.clone()) {
+            continue;
+        }
+        let provenance = determine_provenance(reference, &arxiv_doi, policy);
+        matches.push(build_extracted_match(
+            reference,
+            m.raw,
+            arxiv_doi,
+            provenance,
+            context_chars,
+        ));
+    }
+}
+
+/// Identifier hint in a reference that extracted nothing for this source
+fn unmatched_hint(source: Source, search_text: &str) -> Option<UnmatchedHint> {
+    match source {
+        Source::Arxiv => UnmatchedHint::arxiv_in(search_text).then_some(UnmatchedHint::Arxiv),
+        _ => UnmatchedHint::doi_in(search_text).then_some(UnmatchedHint::Doi),
+    }
+}
+
+/// Extraction settings the pipeline and in-memory runs share
+///
+/// The defaults are those of `pipeline`.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractionOptions {
+    /// Characters of unstructured text captured around mined matches (0 disables)
+    pub context_chars: usize,
+    /// Provenance of structured DOIs without a recognized `doi-asserted-by`
+    pub unattributed_doi_provenance: UnattributedPolicy,
+    /// With a DOI source, also extract arXiv IDs as 10.48550/arXiv DOIs
+    pub hybrid_arxiv: bool,
+    /// Limits mined DOIs are held to
+    pub doi_constraints: DoiConstraints,
+    /// Also match DOIs with whitespace around the prefix dot or slash
+    pub doi_spacing_tolerant: bool,
+    /// Also match bare arXiv IDs within this many words of an arXiv mention
+    pub arxiv_recall_window: Option<usize>,
+    /// Categories accepted in old-format arXiv IDs, in place of the bundled list
+    pub legacy_arxiv_categories: Option<LegacyArxivCategories>,
+    /// Match reference text with its JATS/HTML markup stripped
+    pub strip_markup: bool,
+    /// Record the licenses and funders of citing works
+    pub citing_license_funder: bool,
+    /// Shorten the stored JSON of a matched reference to about this many bytes
+    pub max_ref_json_bytes: Option<usize>,
+    /// Extract only from works issued in this range
+    pub date_range: DateRange,
+}
+
+/// The DOI limits of a `pipeline` run, which extraction and validation share
+fn doi_constraints(args: &PipelineArgs) -> DoiConstraints {
+    DoiConstraints {
+        min_prefix_digits: args.min_prefix_digits,
+        max_prefix_digits: args.max_prefix_digits,
+        max_length: args.max_doi_length,
+        trim_glued_author_year: args.trim_glued_author_year,
+    }
+}
+
+impl ExtractionOptions {
+    /// The options of a `pipeline` run
+    fn from_args(args: &PipelineArgs) -> Result<Self> {
+        let legacy_arxiv_categories = match args.arxiv_legacy_categories {
+            Some(ref path) => Some(LegacyArxivCategories::load(Some(path.as_str()))?),
+            None => None,
+        };
+        Ok(Self {
+            context_chars: args.context_chars,
+            unattributed_doi_provenance: args.unattributed_doi_provenance,
+            hybrid_arxiv: args.hybrid_arxiv,
+            doi_constraints: doi_constraints(args),
+            doi_spacing_tolerant: args.doi_spacing_tolerant,
+            arxiv_recall_window: args.arxiv_recall.then_some(args.arxiv_recall_window),
+            legacy_arxiv_categories,
+            strip_markup: args.strip_markup,
+            citing_license_funder: args.citing_license_funder,
+            max_ref_json_bytes: args.max_ref_json_bytes,
+            date_range: DateRange {
+                since: args.since,
+                until: args.until,
+            },
+        })
+    }
+
+    /// A match cache of `capacity` entries that matches as these options say
+    fn match_cache(&self, capacity: usize) -> MatchCache {
+        let mut cache = MatchCache::new(capacity).with_doi_constraints(self.doi_constraints);
+        if self.doi_spacing_tolerant {
+            cache = cache.with_doi_spacing_tolerance();
+        }
+        if let Some(window) = self.arxiv_recall_window {
+            cache = cache.with_arxiv_recall(window);
+        }
+        if let Some(ref categories) = self.legacy_arxiv_categories {
+            cache = cache.with_legacy_arxiv_categories(categories.clone());
+        }
+        cache
+    }
+}
+
+/// Where the rows extracted for one target go
+pub(super) trait RowSink {
+    fn write_ref(
+        &mut self,
+        citing: &CitingWork,
+        ref_index: u32,
+        ref_json: &Arc<str>,
+        matches: &[ExtractedMatch],
+    ) -> Result<()>;
+}
+
+impl RowSink for PartitionWriter {
+    fn write_ref(
+        &mut self,
+        citing: &CitingWork,
+        ref_index: u32,
+        ref_json: &Arc<str>,
+        matches: &[ExtractedMatch],
+    ) -> Result<()> {
+        self.write_extracted_ref(citing, ref_index, ref_json, matches)?;
+        Ok(())
+    }
+}
+
+impl RowSink for Vec<ExplodedRow> {
+    fn write_ref(
+        &mut self,
+        citing: &CitingWork,
+        ref_index: u32,
+        ref_json: &Arc<str>,
+        matches: &[ExtractedMatch],
+    ) -> Result<()> {
+        self.extend(citing.explode(ref_index, ref_json, matches));
+        Ok(())
+    }
+}
+
+/// Extracts the references of a work at a time, into a row sink per source
+///
+/// The pipeline and in-memory runs both extract through it, so they skip the same
+/// works and references and store the same rows.
+pub(super) struct WorkExtractor<'a> {
+    options: &'a ExtractionOptions,
+    sources: Vec<Source>,
+    /// Works to extract from (--only-dois); all when None
+    only_dois: Option<&'a DoiIndex>,
+    cache: MatchCache,
+    /// Look for an identifier hint in references that extracted nothing
+    unmatched_hints: bool,
+}
+
+impl<'a> WorkExtractor<'a> {
+    pub(super) fn new(
+        options: &'a ExtractionOptions,
+        sources: Vec<Source>,
+        only_dois: Option<&'a DoiIndex>,
+        cache_capacity: usize,
+    ) -> Self {
+        Self {
+            options,
+            sources,
+            only_dois,
+            cache: options.match_cache(cache_capacity),
+            unmatched_hints: false,
+        }
+    }
+
+    fn with_unmatched_hints(mut self) -> Self {
+        self.unmatched_hints = true;
+        self
+    }
+
+    /// Extract a work's references into `sinks`, one per source
+    ///
+    /// Works outside --only-dois or the date range are counted and skipped. `visit`
+    /// sees every reference not repeating an earlier one, with its identifier hint
+    /// when hints are on and a source extracted nothing from it.
+    pub(super) fn extract<S: RowSink>(
+        &mut self,
+        item: &Value,
+        work_doi: &Arc<str>,
+        sinks: &mut [S],
+        stats: &mut ExtractionStats,
+        mut visit: impl FnMut(u32, &Value, Option<UnmatchedHint>) -> Result<()>,
+    ) -> Result<()> {
+        if let Some(only_dois) = self.only_dois {
+            if !only_dois.contains(work_doi) {
+                stats.items_not_listed += 1;
+                return Ok(());
+            }
+        }
+        if !self.options.date_range.contains_item(item) {
+            stats.items_out_of_range += 1;
+            return Ok(());
+        }
+        let Some(references) = item.get("reference").and_then(Value::as_array) else {
+            return Ok(());
+        };
+
+        let mut citing_work = CitingWork::from_item(Arc::clone(work_doi), item);
+        if self.options.citing_license_funder {
+            citing_work = citing_work.with_license_funder(item);
+        }
+        let duplicates = duplicate_references(references);
+        stats.duplicate_refs += duplicates.len();
+        for (ref_idx, reference) in references.iter().enumerate() {
+            if duplicates.contains(&ref_idx) {
+                continue;
+            }
+            let hint =
+                self.extract_reference(&citing_work, ref_idx as u32, reference, sinks, stats)?;
+            visit(ref_idx as u32, reference, hint)?;
+        }
+        Ok(())
+    }
+
+    /// Extract one reference into `sinks`, returning its identifier hint
+    fn extract_reference<S: RowSink>(
+        &mut self,
+        citing_work: &CitingWork,
+        ref_idx: u32,
+        reference: &Value,
+        sinks: &mut [S],
+        stats: &mut ExtractionStats,
+    ) -> Result<Option<UnmatchedHint>> {
+        let stripped = if self.options.strip_markup {
+            strip_reference_markup(reference)
+        } else {
+            None
+        };
+        if stripped.is_some() {
+            stats.markup_refs_stripped += 1;
+        }
+        let searched = stripped.as_ref().unwrap_or(reference);
+
+        let search_text = search_text(searched);
+        if search_text.is_empty() {
+            return Ok(None);
+        }
+
+        // Serialized once, on the first match, and shared across targets
+        let mut ref_json: Option<Arc<str>> = None;
+        let mut ref_matched = false;
+        let mut hint = None;
+        for (source, sink) in self.sources.iter().zip(sinks.iter_mut()) {
+            let extracted = extract_matches(
+                *source,
+                searched,
+                &search_text,
+                self.options.context_chars,
+                self.options.unattributed_doi_provenance,
+                self.options.hybrid_arxiv,
+                &mut self.cache,
+            );
+            if extracted.is_empty() && self.unmatched_hints {
+                hint = hint.or(unmatched_hint(*source, &search_text));
+            }
+
+            // Filter out self-citations
+            let matches: Vec<ExtractedMatch> = extracted
+                .into_iter()
+                .filter(|m| should_include_citation(&citing_work.doi, &m.cited_id))
+                .collect();
+            if matches.is_empty() {
+                continue;
+            }
+            ref_matched = true;
+            stats.total_matches += matches.len();
+
+            let ref_json = ref_json.get_or_insert_with(|| {
+                let truncated = self
+                    .options
+                    .max_ref_json_bytes
+                    .and_then(|max| truncate_reference(reference, max));
+                if truncated.is_some() {
+                    stats.refs_truncated += 1;
+                }
+                Arc::from(truncated.as_ref().unwrap_or(reference).to_string())
+            });
+            sink.write_ref(citing_work, ref_idx, ref_json, &matches)?;
+        }
+        if ref_matched {
+            stats.refs_with_matches += 1;
+        }
+        Ok(hint)
+    }
+}
+
+/// Share extraction counters with the stats logger thread
+fn publish_progress(
+    progress: &ExtractionProgress,
+    stats: &ExtractionStats,
+    writers: &[PartitionWriter],
+) {
+    progress
+        .files
+        .store(stats.files_processed, Ordering::Relaxed);
+    progress
+        .items
+        .store(stats.items_processed, Ordering::Relaxed);
+    progress
+        .matches
+        .store(stats.total_matches, Ordering::Relaxed);
+    progress.rows_buffered.store(
+        writers.iter().map(PartitionWriter::buffered_rows).sum(),
+        Ordering::Relaxed,
+    );
+    progress.partitions.store(
+        writers.iter().map(PartitionWriter::partition_count).sum(),
+        Ordering::Relaxed,
+    );
+}
+
+/// Checkpoint an extraction records its watermark in, with the file it is saved to as
+/// partitions are flushed (none for an extraction that is not resumed)
+struct ExtractionCheckpoint<'a> {
+    checkpoint: &'a mut Checkpoint,
+    path: Option<&'a Path>,
 }
 
 /// Run the extraction phase: stream through tar.gz, extract references, build Crossref index
+///
+/// Each target gets its own partition writer, so DOIs and arXiv IDs selected together
+/// are extracted in a single pass over the archive.
+#[cfg_attr(not(feature = "parquet"), allow(unused_variables))]
 fn run_extraction(
     args: &PipelineArgs,
     indexes: &mut PipelineIndexes,
-    partition_dir: &Path,
+    targets: &[SourceTarget],
+    referenced_by_path: Option<&Path>,
+    checkpoint: ExtractionCheckpoint,
+    memory: &MemoryPressure,
+    cancel: &CancellationToken,
 ) -> Result<ExtractionStats> {
+    let ExtractionCheckpoint {
+        checkpoint,
+        path: checkpoint_path,
+    } = checkpoint;
     let mut stats = ExtractionStats::default();
     let build_crossref_index = should_build_crossref_index(args);
+    let options = ExtractionOptions::from_args(args)?;
 
     // Initialize Crossref index if we're building it
     if build_crossref_index && indexes.crossref.is_none() {
         info!("Will build Crossref index during extraction");
-        indexes.crossref = Some(DoiIndex::new());
+        let index = DoiIndex::new();
+        indexes.crossref = Some(match args.collision_report {
+            Some(_) => index.with_collision_tracking(),
+            None => index,
+        });
     }
 
-    // Create partition writer
-    let flush_threshold = args.batch_size / FLUSH_THRESHOLD_DIVISOR;
-    let mut writer = PartitionWriter::new(partition_dir, flush_threshold.max(10000))?;
+    // Create partition writer, sized from the memory budget when one is given
+    let memory_budget = args.memory_limit.map(MemoryBudget::new);
+    let flush_threshold = match memory_budget {
+        Some(budget) => budget.flush_threshold_rows(),
+        None => (args.batch_size / FLUSH_THRESHOLD_DIVISOR).max(10000),
+    };
+    let mut writers = targets
+        .iter()
+        .map(|target| {
+            let writer = PartitionWriter::new(&target.partition_dir, flush_threshold)?;
+            Ok(match memory_budget {
+                Some(budget) => {
+                    writer.with_memory_budget(budget.partition_buffer_bytes() / targets.len())
+                }
+                None => writer,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    #[cfg(feature = "parquet")]
+    let mut corpus_writer = match args.reference_corpus {
+        Some(ref path) => {
+            info!("Exporting reference corpus to: {}", path);
+            Some(ReferenceCorpusWriter::new(
+                Path::new(path),
+                &output_options(args),
+            )?)
+        }
+        None => None,
+    };
 
-    // Open and stream the tar.gz
-    let file = File::open(&args.input)
-        .with_context(|| format!("Failed to open input file: {}", args.input))?;
-    let gz = GzDecoder::new(file);
-    let mut archive = Archive::new(gz);
+    let mut unmatched_writer = match args.unmatched_refs_output {
+        Some(ref path) => {
+            info!("Writing unmatched references to: {}", path);
+            Some(UnmatchedRefsWriter::new(
+                Path::new(path),
+                &output_options(args),
+            )?)
+        }
+        None => None,
+    };
 
-    // Log extraction behavior based on source mode
-    match args.source {
-        Source::Arxiv => {
-            info!("Extracting arXiv IDs from references...");
+    #[cfg(feature = "parquet")]
+    let mut referenced_by_writer = match referenced_by_path {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Some(ReferencedByWriter::new(path)?)
         }
-        _ => {
-            info!("Extracting all DOIs from references (source filtering happens during validation)...");
+        None => None,
+    };
+
+    // An interrupted extraction resumes after the last entry it got through; the
+    // partitions already hold the rows of every entry up to it
+    let resume_after = checkpoint
+        .extraction_watermark(&args.input)?
+        .map(|(entries, name)| (entries, name.to_string()));
+    if let Some((entries, ref name)) = resume_after {
+        info!(
+            "Resuming extraction after {} tar entries (last: {})",
+            entries, name
+        );
+        stats.files_processed = checkpoint.stats.json_files_processed;
+        stats.items_processed = checkpoint.stats.total_records;
+        stats.refs_with_matches = checkpoint.stats.references_with_matches;
+        stats.total_matches = checkpoint.stats.total_matches;
+    }
+    checkpoint.input = Some(args.input.clone());
+
+    // A compressed tar is streamed; an uncompressed tar or a directory is read by
+    // workers that parse entries ahead, still handed over in archive order. References
+    // are extracted here, on this thread. Entries the interrupted run got through are
+    // passed over unparsed.
+    let skip_entries = resume_after.as_ref().map_or(0, |(entries, _)| *entries);
+    let mut input = SnapshotInput::open(Path::new(&args.input))?;
+    match input.format() {
+        SnapshotFormat::Compressed if args.parse_workers > 1 => {
+            return Err(anyhow::anyhow!(
+                "--parse-workers needs an uncompressed tar or a directory as --input; {} \
+                 is compressed",
+                args.input
+            ));
         }
+        SnapshotFormat::Compressed => {}
+        format => info!(
+            "Parsing {} entries ahead with {} workers",
+            match format {
+                SnapshotFormat::Directory => "directory",
+                _ => "uncompressed tar",
+            },
+            args.parse_workers
+        ),
+    }
+    let mut entries = input.entries(args.parse_workers, skip_entries)?;
+
+    let sources = targets.iter().map(|target| target.source).collect();
+    let mut extractor = WorkExtractor::new(
+        &options,
+        sources,
+        indexes.only_dois.as_ref(),
+        args.match_cache_size,
+    );
+    if unmatched_writer.is_some() {
+        extractor = extractor.with_unmatched_hints();
     }
-    info!("Streaming through Crossref archive...");
 
-    for entry_result in archive.entries()? {
-        let entry = entry_result.context("Failed to read tar entry")?;
-        let path = entry.path()?.to_path_buf();
+    // Log extraction behavior based on source mode
+    for target in targets {
+        match target.source {
+            Source::Arxiv => {
+                info!("Extracting arXiv IDs from references...");
+            }
+            _ => {
+                info!("Extracting all DOIs from references (source filtering happens during validation)...");
+            }
+        }
+    }
+    info!("Streaming through Crossref archive...");
 
-        // Skip non-JSON files
-        let path_str = path.to_string_lossy();
-        if !path_str.ends_with(".json") {
-            continue;
+    let progress = Arc::new(ExtractionProgress::default());
+    let _stats_logger = (args.stats_interval > 0).then(|| {
+        StatsLogger::spawn(
+            Arc::clone(&progress),
+            Duration::from_secs(args.stats_interval),
+        )
+    });
+
+    // Items read before streaming stops (--skip-records plus --limit-records)
+    let item_limit = args
+        .limit_records
+        .map(|limit| args.skip_records.saturating_add(limit));
+    let limit_reached =
+        |stats: &ExtractionStats| item_limit.is_some_and(|limit| stats.items_processed >= limit);
+
+    let rows_written = |writers: &[PartitionWriter]| -> usize {
+        writers.iter().map(PartitionWriter::rows_written).sum()
+    };
+    let mut rows_saved = rows_written(&writers);
+
+    let mut interrupted = false;
+    loop {
+        // Stop between entries so every processed work is fully written
+        if cancel.is_cancelled() {
+            interrupted = true;
+            break;
         }
+        if limit_reached(&stats) {
+            break;
+        }
+        let Some(entry) = entries.next() else {
+            break;
+        };
+        stats.tar_entries_processed += 1;
+        let SnapshotEntry { path, items } = entry?;
 
-        debug!("Processing: {}", path_str);
+        if let Some((entries, ref name)) = resume_after {
+            if stats.tar_entries_processed < entries {
+                continue;
+            }
+            if stats.tar_entries_processed == entries {
+                if path != *name {
+                    return Err(anyhow::anyhow!(
+                        "Tar entry {} of {} is {}, but the checkpoint recorded {}; the \
+                         snapshot changed since the interrupted run",
+                        entries,
+                        args.input,
+                        path,
+                        name
+                    ));
+                }
+                continue;
+            }
+        }
+        checkpoint.last_tar_entry = Some(path.clone());
 
-        // Read and parse JSON
-        let reader = BufReader::new(entry);
-        let json: Value = match serde_json::from_reader(reader) {
-            Ok(v) => v,
-            Err(e) => {
-                warn!("Failed to parse JSON in {}: {}", path_str, e);
+        // Snapshot JSON and deposit XML files hold items; anything else is skipped
+        let items = match items {
+            Some(Ok(items)) => items,
+            Some(Err(e)) => {
+                warn!("Failed to parse {}: {:#}", path, e);
                 continue;
             }
+            None => continue,
         };
 
         // Process items array
-        if let Some(items) = json.get("items").and_then(|v| v.as_array()) {
-            for item in items {
-                stats.items_processed += 1;
-
-                // Extract the work's DOI
-                let work_doi = match item.get("DOI").and_then(|v| v.as_str()) {
-                    Some(doi) => doi.to_lowercase(),
-                    None => continue, // Skip items without DOI
-                };
+        for item in &items {
+            if limit_reached(&stats) {
+                break;
+            }
+            stats.items_processed += 1;
+            publish_progress(&progress, &stats, &writers);
 
-                // Add to Crossref index if building
-                if build_crossref_index {
-                    if let Some(ref mut index) = indexes.crossref {
-                        index.insert(&work_doi);
-                        stats.crossref_dois_indexed += 1;
-                    }
+            if memory.take_flush_request() {
+                for writer in &mut writers {
+                    writer.flush_all()?;
                 }
+                stats.emergency_flushes += 1;
+            }
 
-                // Process references
-                if let Some(references) = item.get("reference").and_then(|v| v.as_array()) {
-                    for (ref_idx, reference) in references.iter().enumerate() {
-                        let ref_json = reference.to_string();
-
-                        // Collect text to search for matches
-                        // Search all fields that might contain arXiv IDs or DOIs
-                        let mut search_text = String::new();
-
-                        // Include the DOI field if present
-                        if let Some(doi) = reference.get("DOI").and_then(|v| v.as_str()) {
-                            search_text.push_str(doi);
-                            search_text.push(' ');
-                        }
-
-                        // Include URL field if present
-                        if let Some(url) = reference.get("URL").and_then(|v| v.as_str()) {
-                            search_text.push_str(url);
-                            search_text.push(' ');
-                        }
-
-                        // Include article-title field if present
-                        if let Some(title) = reference.get("article-title").and_then(|v| v.as_str())
-                        {
-                            search_text.push_str(title);
-                            search_text.push(' ');
-                        }
+            // Extract the work's DOI, shared by every row exploded from its references
+            let Some(raw_doi) = item.get("DOI").and_then(|v| v.as_str()) else {
+                continue; // Skip items without DOI
+            };
+            let work_doi: Arc<str> = Arc::from(raw_doi.to_lowercase());
 
-                        // Include journal-title field if present
-                        if let Some(journal) =
-                            reference.get("journal-title").and_then(|v| v.as_str())
-                        {
-                            search_text.push_str(journal);
-                            search_text.push(' ');
-                        }
+            #[cfg(feature = "parquet")]
+            if let Some(ref mut counts) = referenced_by_writer {
+                if let Some(count) = item.get("is-referenced-by-count").and_then(|v| v.as_u64()) {
+                    counts.write(&work_doi, count.min(u32::MAX as u64) as u32)?;
+                }
+            }
 
-                        // Include unstructured text if present
-                        if let Some(unstructured) =
-                            reference.get("unstructured").and_then(|v| v.as_str())
-                        {
-                            search_text.push_str(unstructured);
-                        }
+            // Add to Crossref index if building
+            if build_crossref_index {
+                if let Some(ref mut index) = indexes.crossref {
+                    // As registered, so collision tracking sees the original case
+                    index.insert(raw_doi);
+                    stats.crossref_dois_indexed += 1;
+                }
+            }
 
-                        if search_text.is_empty() {
-                            continue;
-                        }
+            if stats.items_processed <= args.skip_records {
+                stats.items_skipped += 1;
+                continue;
+            }
 
-                        // Extract matches based on source mode
-                        let (raw_matches, cited_ids, provenances): (
-                            Vec<String>,
-                            Vec<String>,
-                            Vec<Provenance>,
-                        ) = match args.source {
-                            Source::Arxiv => {
-                                // Extract arXiv IDs (just the ID, not the DOI - DOI is constructed in invert step)
-                                let matches = extract_arxiv_matches_from_text(&search_text);
-                                let raws: Vec<String> =
-                                    matches.iter().map(|m| m.raw.clone()).collect();
-                                let ids: Vec<String> =
-                                    matches.iter().map(|m| m.id.clone()).collect();
-                                // For arXiv, determine provenance based on whether DOI field exists
-                                let provs: Vec<Provenance> = ids
-                                    .iter()
-                                    .map(|id| {
-                                        let arxiv_doi = format!("10.48550/arXiv.{}", id);
-                                        determine_provenance(reference, &arxiv_doi)
-                                    })
-                                    .collect();
-                                (raws, ids, provs)
-                            }
-                            Source::All | Source::Crossref | Source::Datacite => {
-                                // Extract DOIs
-                                let matches = extract_doi_matches_from_text(&search_text);
-                                let raws: Vec<String> =
-                                    matches.iter().map(|m| m.raw.clone()).collect();
-                                let ids: Vec<String> =
-                                    matches.iter().map(|m| m.doi.clone()).collect();
-                                let provs: Vec<Provenance> = ids
-                                    .iter()
-                                    .map(|doi| determine_provenance(reference, doi))
-                                    .collect();
-                                (raws, ids, provs)
-                            }
-                        };
-
-                        if !cited_ids.is_empty() {
-                            // Filter out self-citations
-                            let (filtered_raw_matches, filtered_cited_ids, filtered_provenances): (
-                                Vec<_>,
-                                Vec<_>,
-                                Vec<_>,
-                            ) = raw_matches
-                                .iter()
-                                .zip(cited_ids.iter())
-                                .zip(provenances.iter())
-                                .filter(|((_, cited_id), _)| {
-                                    should_include_citation(&work_doi, cited_id)
-                                })
-                                .map(|((raw, cited), prov)| (raw.clone(), cited.clone(), *prov))
-                                .fold(
-                                    (Vec::new(), Vec::new(), Vec::new()),
-                                    |mut acc, (raw, cited, prov)| {
-                                        acc.0.push(raw);
-                                        acc.1.push(cited);
-                                        acc.2.push(prov);
-                                        acc
-                                    },
-                                );
-
-                            if !filtered_cited_ids.is_empty() {
-                                stats.refs_with_matches += 1;
-                                stats.total_matches += filtered_cited_ids.len();
-
-                                writer.write_extracted_ref(
-                                    &work_doi,
-                                    ref_idx as u32,
-                                    &ref_json,
-                                    &filtered_raw_matches,
-                                    &filtered_cited_ids,
-                                    &filtered_provenances,
-                                )?;
-                            }
+            extractor.extract(
+                item,
+                &work_doi,
+                &mut writers,
+                &mut stats,
+                |ref_idx, reference, hint| {
+                    #[cfg(feature = "parquet")]
+                    if let Some(ref mut corpus) = corpus_writer {
+                        if let Some(row) = build_corpus_row(
+                            &work_doi,
+                            ref_idx,
+                            reference,
+                            args.unattributed_doi_provenance,
+                        ) {
+                            corpus.write(row)?;
                         }
                     }
-                }
-            }
+                    if let (Some(unmatched), Some(hint)) = (unmatched_writer.as_mut(), hint) {
+                        unmatched.write(&work_doi, ref_idx, hint, reference)?;
+                    }
+                    Ok(())
+                },
+            )?;
         }
 
         stats.files_processed += 1;
-
-        // Log progress periodically
-        if stats.files_processed % PROGRESS_LOG_INTERVAL == 0 {
-            info!(
-                "Progress: {} files, {} items, {} matches",
-                stats.files_processed, stats.items_processed, stats.total_matches
-            );
+        publish_progress(&progress, &stats, &writers);
+
+        // Once a flush has put rows on disk, flush the rest and move the watermark past
+        // this entry, so a run that dies without saving its checkpoint resumes from here
+        if let Some(path) = checkpoint_path {
+            if rows_written(&writers) != rows_saved {
+                for writer in &mut writers {
+                    writer.flush_all()?;
+                }
+                rows_saved = rows_written(&writers);
+                checkpoint.tar_entries_processed = stats.tar_entries_processed;
+                record_extraction_stats(checkpoint, &stats);
+                checkpoint.save(path)?;
+            }
         }
     }
 
+    stats.match_cache_hits = extractor.cache.hits();
+    stats.match_cache_misses = extractor.cache.misses();
+    stats.match_lookups_screened = extractor.cache.screened();
+
     // Flush remaining data
-    writer.flush_all()?;
+    for writer in &mut writers {
+        writer.flush_all()?;
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(corpus) = corpus_writer {
+        stats.corpus_rows_written = corpus.finish()?;
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(counts) = referenced_by_writer {
+        counts.finish()?;
+    }
+    if let Some(unmatched) = unmatched_writer {
+        stats.unmatched_refs_written = unmatched.finish()?;
+    }
+
+    // Interrupted while passing over entries, the watermark stays where it was
+    drop(entries);
+    let resumed_entries = skip_entries;
+    if !interrupted && !limit_reached(&stats) && stats.tar_entries_processed < resumed_entries {
+        return Err(anyhow::anyhow!(
+            "{} has {} tar entries, but the checkpoint recorded {}; the snapshot changed \
+             since the interrupted run",
+            args.input,
+            stats.tar_entries_processed,
+            resumed_entries
+        ));
+    }
+    checkpoint.tar_entries_processed = stats.tar_entries_processed.max(resumed_entries);
+    record_extraction_stats(checkpoint, &stats);
+    if interrupted {
+        warn!(
+            "Extraction interrupted after {} files ({} items); partitions flushed",
+            stats.files_processed, stats.items_processed
+        );
+        return Err(Interrupted.into());
+    }
 
     info!("Extraction complete:");
     info!("  Files processed: {}", stats.files_processed);
     info!("  Items processed: {}", stats.items_processed);
+    if args.skip_records > 0 {
+        info!("  Items skipped (--skip-records): {}", stats.items_skipped);
+    }
+    if limit_reached(&stats) {
+        info!(
+            "  Stopped at --limit-records after {} items",
+            stats.items_processed
+        );
+    }
+    if indexes.only_dois.is_some() {
+        info!("  Items not in --only-dois: {}", stats.items_not_listed);
+    }
+    info!(
+        "  Match lookups skipped by prescreen: {}",
+        stats.match_lookups_screened
+    );
+    if args.match_cache_size > 0 {
+        info!(
+            "  Match cache: {} of {} lookups hit ({:.1}%)",
+            stats.match_cache_hits,
+            stats.match_cache_hits + stats.match_cache_misses,
+            extractor.cache.hit_rate() * 100.0
+        );
+    }
+    if stats.emergency_flushes > 0 {
+        info!(
+            "  Emergency partition flushes (memory pressure): {}",
+            stats.emergency_flushes
+        );
+    }
+    if options.date_range.is_bounded() {
+        info!(
+            "  Items outside --since/--until: {}",
+            stats.items_out_of_range
+        );
+    }
+    if stats.duplicate_refs > 0 {
+        info!("  Duplicate references collapsed: {}", stats.duplicate_refs);
+    }
+    if args.strip_markup {
+        info!(
+            "  References with markup stripped: {}",
+            stats.markup_refs_stripped
+        );
+    }
+    if args.max_ref_json_bytes.is_some() {
+        info!(
+            "  References truncated (--max-ref-json-bytes): {}",
+            stats.refs_truncated
+        );
+    }
     info!("  References with matches: {}", stats.refs_with_matches);
     info!("  Total matches: {}", stats.total_matches);
     if build_crossref_index {
         info!("  Crossref DOIs indexed: {}", stats.crossref_dois_indexed);
     }
+    if args.reference_corpus.is_some() {
+        info!("  Reference corpus rows: {}", stats.corpus_rows_written);
+    }
+    if args.unmatched_refs_output.is_some() {
+        info!("  Unmatched references: {}", stats.unmatched_refs_written);
+    }
 
     Ok(stats)
 }
 
-pub fn run_pipeline(args: PipelineArgs) -> Result<()> {
+/// Run the full pipeline on the caller's tokio runtime, stopping on SIGINT/SIGTERM
+///
+/// Index loading, extraction, inversion and output writing are CPU-bound and run through
+/// `run_blocking`, so a spawned pipeline hands its worker's other tasks to the rest of a
+/// multi-threaded runtime instead of stalling them.
+pub async fn run_pipeline(args: PipelineArgs) -> Result<()> {
+    install_signal_handlers()?;
+    run_pipeline_with_cancel(args, CancellationToken::new()).await
+}
+
+/// Run the full pipeline until it completes or `cancel` is cancelled
+///
+/// No signal handlers are installed, so an embedding service keeps control of its
+/// process. A cancelled run flushes its partitions and checkpoint, keeps the
+/// intermediate files, and fails with `Interrupted`.
+pub async fn run_pipeline_with_cancel(args: PipelineArgs, cancel: CancellationToken) -> Result<()> {
     setup_logging(&args.log_level)?;
 
     info!("Starting citation extraction pipeline");
     info!("Input: {}", args.input);
-    info!("Source mode: {}", args.source);
+    info!("Source mode: {}", format_sources(&args.source));
 
     validate_args(&args)?;
 
-    if !Path::new(&args.input).exists() {
+    let skips_extraction = args.retry_failed_partitions || args.from_intermediates.is_some();
+    if !skips_extraction && !Path::new(&args.input).exists() {
         return Err(anyhow::anyhow!("Input file does not exist: {}", args.input));
     }
 
-    // Phase 1: Load indexes
-    info!("");
-    info!("=== Loading Indexes ===");
-    let mut indexes = load_indexes(&args)?;
-
+    // Held until the run returns, so overlapping scheduled runs cannot share outputs
+    let _lock = match args.lock_file {
+        Some(ref path) => Some(RunLock::acquire(Path::new(path))?),
+        None => None,
+    };
+    let inputs = if args.skip_if_unchanged {
+        describe_inputs(&input_paths(&args))?
+    } else {
+        Vec::new()
+    };
+    if let Some(path) = manifest_path(&args).filter(|_| args.skip_if_unchanged) {
+        if manifest_is_current(&path, &inputs)? {
+            info!(
+                "Manifest {:?} records a completed run on the same inputs; skipping",
+                path
+            );
+            return Ok(());
+        }
+    }
+    if let Some(ref baseline) = args.baseline_snapshot {
+        if !Path::new(baseline).exists() {
+            return Err(anyhow::anyhow!(
+                "Baseline snapshot does not exist: {}",
+                baseline
+            ));
+        }
+    }
+
+    let memory_budget = args.memory_limit.map(MemoryBudget::new);
+    if let Some(budget) = memory_budget {
+        apply_memory_budget(&budget);
+    }
+    let memory_monitor = MemoryMonitor::spawn(
+        args.memory_limit,
+        args.memory_warn_percent,
+        Duration::from_secs(args.stats_interval),
+    );
+
+    // Phase 1: Load indexes
+    info!("");
+    info!("=== Loading Indexes ===");
+    let mut indexes = run_blocking(|| load_indexes(&args))?;
+
     // Set up partition directory
-    let partition_dir = if let Some(ref dir) = args.temp_dir {
+    let mut temp_marker = None;
+    let partition_dir = if let Some(ref dir) = args.from_intermediates {
+        PathBuf::from(dir)
+    } else if let Some(ref dir) = args.temp_dir {
         let path = PathBuf::from(dir);
         std::fs::create_dir_all(&path)?;
         path
     } else {
         // Create a unique temp directory
         let temp_base = std::env::temp_dir();
-        let unique_dir = temp_base.join(format!("crossref-extract-{}", Uuid::new_v4()));
+        warn_stale_temp_dirs(&temp_base);
+        let unique_dir = temp_base.join(format!("{}{}", TEMP_DIR_PREFIX, Uuid::new_v4()));
         std::fs::create_dir_all(&unique_dir).context("Failed to create temp directory")?;
+        temp_marker = Some(TempDirMarker::acquire(&unique_dir)?);
         unique_dir
     };
-    let cleanup_temp = args.temp_dir.is_none() && !args.keep_intermediates;
+    // A directory the user named is never removed
+    let persistent_dir = args.temp_dir.is_some() || args.from_intermediates.is_some();
+    let mut cleanup_temp = !persistent_dir && !args.keep_intermediates;
     info!("Partition directory: {}", partition_dir.display());
 
-    // Phase 2: Extract and build Crossref index
-    info!("");
-    info!("=== Extraction Phase ===");
-    let extraction_stats = run_extraction(&args, &mut indexes, &partition_dir)?;
+    let intermediates = match args.from_intermediates {
+        Some(_) => Some(IntermediatesManifest::load(&partition_dir)?),
+        None => None,
+    };
+    let sources = resolve_sources(&args.source);
+    let targets: Vec<SourceTarget> = sources
+        .iter()
+        .map(|&source| {
+            let target_dir = match intermediates {
+                Some(ref manifest) => {
+                    let target = manifest.target(&source.to_string()).with_context(|| {
+                        format!(
+                            "{} was not extracted in the run that left {}",
+                            source,
+                            partition_dir.display()
+                        )
+                    })?;
+                    partition_dir.join(&target.partition_dir)
+                }
+                // Separate directories keep DOI and arXiv partitions apart when both are
+                // selected
+                None if sources.len() > 1 => partition_dir.join(source.to_string()),
+                None => partition_dir.clone(),
+            };
+            Ok(SourceTarget {
+                source,
+                partition_dir: target_dir,
+            })
+        })
+        .collect::<Result<_>>()?;
+    let resume_phase = match intermediates {
+        Some(ref manifest) => Some(resume_phase(&args, manifest, &sources)?),
+        None => None,
+    };
+
+    let referenced_by_path = args
+        .referenced_by_comparison
+        .as_ref()
+        .map(|_| partition_dir.join(REFERENCED_BY_COUNTS_FILE));
+    #[cfg(feature = "parquet")]
+    let mut extraction_stats = None;
+    let mut checkpoint = if args.retry_failed_partitions {
+        // The partitions, inverted outputs and checkpoint of the earlier run are reused
+        let checkpoint_path = partition_dir.join(CHECKPOINT_FILE);
+        let checkpoint = Checkpoint::load(&checkpoint_path)?.with_context(|| {
+            format!(
+                "--retry-failed-partitions requires a checkpoint from an earlier run: {:?}",
+                checkpoint_path
+            )
+        })?;
+        if checkpoint.partitions_failed.is_empty() {
+            info!("No failed partitions recorded in the checkpoint, nothing to retry");
+            return Ok(());
+        }
+        info!(
+            "Skipping extraction, retrying {} failed partitions",
+            checkpoint.partitions_failed.len()
+        );
+        checkpoint
+    } else if let (Some(manifest), Some(phase)) = (&intermediates, resume_phase) {
+        let checkpoint_path = partition_dir.join(&manifest.checkpoint);
+        let mut checkpoint = Checkpoint::load(&checkpoint_path)?.with_context(|| {
+            format!(
+                "Checkpoint listed in the intermediates manifest is missing: {:?}",
+                checkpoint_path
+            )
+        })?;
+        if checkpoint.phase == PipelinePhase::ConvertExtract {
+            return Err(anyhow::anyhow!(
+                "Extraction did not complete in the run that left {}; its partitions are partial",
+                partition_dir.display()
+            ));
+        }
+        if phase == ResumePhase::Aggregate {
+            // Every partition is inverted again, so none counts as done
+            checkpoint.partitions_inverted.clear();
+            checkpoint.partitions_failed.clear();
+        }
+        info!(
+            "Skipping extraction, resuming run {} at the {:?} phase",
+            manifest.run_id, phase
+        );
+        checkpoint
+    } else {
+        // Phase 2: Extract and build Crossref index
+        info!("");
+        info!("=== Extraction Phase ===");
+        let checkpoint_path = partition_dir.join(CHECKPOINT_FILE);
+        let mut checkpoint = match Checkpoint::load(&checkpoint_path)? {
+            Some(checkpoint) if args.resume_extraction => {
+                if checkpoint.phase != PipelinePhase::ConvertExtract {
+                    return Err(anyhow::anyhow!(
+                        "Extraction completed in the run that left {}; resume it with \
+                         --from-intermediates",
+                        partition_dir.display()
+                    ));
+                }
+                checkpoint
+            }
+            None if args.resume_extraction => {
+                // Without a watermark every entry is extracted again, and the rows
+                // already in the partitions would be written twice
+                if let Some(target) = targets
+                    .iter()
+                    .find(|target| has_partition_files(&target.partition_dir))
+                {
+                    return Err(anyhow::anyhow!(
+                        "{} holds partitions but no checkpoint to resume extraction from; \
+                         remove them to extract from the start",
+                        target.partition_dir.display()
+                    ));
+                }
+                info!(
+                    "No checkpoint in {}; extracting from the start",
+                    partition_dir.display()
+                );
+                Checkpoint::new(&format!("pipeline-{}", Uuid::new_v4()))
+            }
+            _ => Checkpoint::new(&format!("pipeline-{}", Uuid::new_v4())),
+        };
+        let stats = save_on_interrupt(
+            run_blocking(|| {
+                run_extraction(
+                    &args,
+                    &mut indexes,
+                    &targets,
+                    referenced_by_path.as_deref(),
+                    ExtractionCheckpoint {
+                        checkpoint: &mut checkpoint,
+                        path: Some(&checkpoint_path),
+                    },
+                    memory_monitor.pressure(),
+                    &cancel,
+                )
+            }),
+            &checkpoint,
+            &partition_dir,
+        )?;
+
+        if stats.total_matches == 0 {
+            warn!("No matches found during extraction");
+        }
+        #[cfg(feature = "parquet")]
+        {
+            extraction_stats = Some(stats);
+        }
+
+        if let Some(ref baseline) = args.baseline_snapshot {
+            save_on_interrupt(
+                run_blocking(|| {
+                    extract_baseline(
+                        &args,
+                        baseline,
+                        &mut indexes,
+                        &targets,
+                        memory_monitor.pressure(),
+                        &cancel,
+                    )
+                }),
+                &checkpoint,
+                &partition_dir,
+            )?;
+        }
+        checkpoint
+    };
+
+    let (http_crossref, http_datacite) = HttpFallbackSource::enabled(&args.http_fallback);
+
+    // With a persistent --temp-dir, HTTP results live next to the partitions so a
+    // rerun after an interruption skips DOIs that were already checked
+    let http_checkpoint = args
+        .http_checkpoint
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| persistent_dir.then(|| partition_dir.join(HTTP_CHECKPOINT_FILE)));
+    let resolver = open_resolver(args.http_resolver_file.as_deref())?;
+    let http_fallback = (http_crossref || http_datacite).then(|| HttpFallback {
+        concurrency: args.concurrency,
+        timeout_secs: args.timeout,
+        checkpoint: http_checkpoint.clone(),
+        adaptive: args.adaptive_concurrency.then_some(ConcurrencyBounds {
+            min: args.min_concurrency,
+            max: args.max_concurrency,
+        }),
+        crossref: http_crossref,
+        datacite: http_datacite,
+        bad_prefix_threshold: args.bad_prefix_threshold,
+        resolver,
+        client: args.http_client.config(),
+    });
+
+    // Citing-side output gathers the valid citations of every target
+    let mut citing_works = args.output_citing.as_ref().map(|_| CitingWorks::default());
+    let mut collision_report = args
+        .collision_report
+        .as_ref()
+        .map(|_| collision_report(&indexes));
+    #[cfg(feature = "parquet")]
+    let mut stats_rows = Vec::new();
+
+    for target in &targets {
+        if args.retry_failed_partitions
+            && checkpoint
+                .failed_partitions_in(&target.partition_dir)
+                .is_empty()
+        {
+            info!("No failed {} partitions to retry", target.source);
+            continue;
+        }
+
+        #[cfg(feature = "parquet")]
+        if args.compact_partitions && !skips_extraction {
+            save_on_interrupt(
+                run_blocking(|| {
+                    compact_partitions(&target.partition_dir, args.compact_target_rows, &cancel)
+                })
+                .map_err(anyhow::Error::from),
+                &checkpoint,
+                &partition_dir,
+            )?;
+        }
+
+        // Phase 3: Invert partitions, unless resuming from an existing inverted output
+        let invert_stats = if resume_phase == Some(ResumePhase::Validate) {
+            run_blocking(|| reuse_inverted(&args, target))?
+        } else {
+            checkpoint.start_invert_phase();
+            save_on_interrupt(
+                run_blocking(|| {
+                    aggregate_target(
+                        &args,
+                        target,
+                        targets.len() > 1,
+                        &mut checkpoint,
+                        memory_budget,
+                        &cancel,
+                    )
+                }),
+                &checkpoint,
+                &partition_dir,
+            )?
+        };
+
+        #[cfg(feature = "parquet")]
+        if let Some(ref output) = args.snapshot_comparison {
+            let output = per_source_path(output, target.source, targets.len() > 1);
+            save_on_interrupt(
+                run_blocking(|| compare_target_snapshots(target, &output, memory_budget, &cancel)),
+                &checkpoint,
+                &partition_dir,
+            )?;
+        }
+
+        // arXiv IDs are not Crossref works, so only DOI targets are compared
+        #[cfg(feature = "parquet")]
+        let comparison = match (&args.referenced_by_comparison, &referenced_by_path) {
+            (Some(output), Some(counts)) if target.source != Source::Arxiv => {
+                Some((output, counts))
+            }
+            _ => None,
+        };
+        #[cfg(feature = "parquet")]
+        if let Some((output, counts)) = comparison {
+            if invert_stats.unique_cited_works > 0 {
+                run_blocking(|| {
+                    compare_referenced_by_counts(
+                        &target.partition_dir.join(INVERTED_FILE),
+                        counts,
+                        Path::new(output),
+                    )
+                })?;
+            } else {
+                warn!("No cited works to compare, skipping is-referenced-by-count comparison");
+            }
+        }
+
+        // Phase 4: Validate
+        info!("");
+        info!("=== Validating Citations ===");
+        info!(
+            "Filtering {} extracted works to {} source DOIs...",
+            invert_stats.unique_cited_works, target.source
+        );
+
+        checkpoint.start_validate_phase(http_fallback.as_ref().and(http_checkpoint.clone()));
+        checkpoint.save(&partition_dir.join(CHECKPOINT_FILE))?;
+
+        // Only the stats history reads the validation counts
+        #[cfg_attr(not(feature = "parquet"), allow(unused_variables))]
+        let validate_stats = save_on_interrupt(
+            validate_target(
+                &args,
+                &indexes,
+                target,
+                http_fallback.as_ref(),
+                citing_works.as_mut(),
+                collision_report.as_mut(),
+                &cancel,
+            )
+            .await,
+            &checkpoint,
+            &partition_dir,
+        )?;
+
+        #[cfg(feature = "parquet")]
+        stats_rows.push(StatsRow {
+            source: target.source.to_string(),
+            items_processed: extraction_stats.as_ref().map(|stats| stats.items_processed),
+            refs_with_matches: extraction_stats
+                .as_ref()
+                .map(|stats| stats.refs_with_matches),
+            total_matches: extraction_stats.as_ref().map(|stats| stats.total_matches),
+            unique_cited_works: invert_stats.unique_cited_works,
+            total_citations: invert_stats.total_citations,
+            validation: validate_stats,
+        });
+    }
+
+    if let (Some(path), Some(citing_works)) = (&args.output_citing, citing_works) {
+        run_blocking(|| citing_works.write(path, &output_options(&args)))?;
+    }
+    if let (Some(path), Some(report)) = (&args.collision_report, collision_report) {
+        report.write(path)?;
+    }
+
+    checkpoint.mark_complete();
+    checkpoint.save(&partition_dir.join(CHECKPOINT_FILE))?;
+
+    if !checkpoint.partitions_failed.is_empty() {
+        // Keep the partitions so the failed ones can be retried
+        cleanup_temp = false;
+        warn!(
+            "{} partitions failed to invert and are missing from the outputs:",
+            checkpoint.partitions_failed.len()
+        );
+        for (partition, error) in &checkpoint.partitions_failed {
+            warn!("  {}: {}", partition, error);
+        }
+        warn!(
+            "Rerun with --temp-dir {} --retry-failed-partitions to retry them",
+            partition_dir.display()
+        );
+    }
+
+    if !cleanup_temp {
+        let sidecars: Vec<PathBuf> = http_checkpoint
+            .into_iter()
+            .chain(referenced_by_path)
+            .filter(|path| path.starts_with(&partition_dir))
+            .collect();
+        let target_dirs: Vec<(String, PathBuf)> = targets
+            .iter()
+            .map(|target| (target.source.to_string(), target.partition_dir.clone()))
+            .collect();
+        IntermediatesManifest::collect(
+            &partition_dir,
+            &checkpoint.run_id,
+            &partition_dir.join(CHECKPOINT_FILE),
+            &target_dirs,
+            &sidecars,
+        )?
+        .write(&partition_dir)?;
+        info!(
+            "Intermediate files listed in {}",
+            partition_dir.join(INTERMEDIATES_FILE).display()
+        );
+    }
+
+    // Save indexes if requested
+    run_blocking(|| -> Result<()> {
+        if let Some(ref path) = args.save_crossref_index {
+            if let Some(ref index) = indexes.crossref {
+                save_index(index, path, args.index_shards)?;
+            }
+        }
+        if let Some(ref path) = args.save_datacite_index {
+            if let Some(ref index) = indexes.datacite {
+                save_index(index, path, args.index_shards)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    if let Some(path) = manifest_path(&args) {
+        write_manifest(&path, &output_files(&args, &targets), &inputs)?;
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(ref path) = args.stats_history {
+        append_stats_history(Path::new(path), &snapshot_id(&args), &stats_rows)?;
+    }
+
+    let peak_rss = memory_monitor.pressure().peak_rss_bytes();
+    if peak_rss > 0 {
+        info!("Peak RSS: {}", format_bytes(peak_rss));
+    }
+
+    // Cleanup temp directory if needed
+    if cleanup_temp {
+        drop(temp_marker);
+        info!("Cleaning up temp directory: {}", partition_dir.display());
+        if let Err(e) = std::fs::remove_dir_all(&partition_dir) {
+            warn!("Failed to cleanup temp directory: {}", e);
+        }
+    } else if let Some(marker) = temp_marker {
+        // Kept on purpose, so `cleanup` must not treat it as left by a crashed run
+        marker.release()?;
+    }
+
+    Ok(())
+}
+
+/// Warn about partition directories that earlier runs left in the system temp directory
+fn warn_stale_temp_dirs(temp_base: &Path) {
+    let stale = match find_stale_temp_dirs(temp_base, DEFAULT_STALE_AGE) {
+        Ok(stale) => stale,
+        Err(e) => {
+            debug!(
+                "Could not scan {} for stale temp directories: {}",
+                temp_base.display(),
+                e
+            );
+            return;
+        }
+    };
+    if stale.is_empty() {
+        return;
+    }
+    let total: u64 = stale.iter().map(|dir| dir.size()).sum();
+    warn!(
+        "{} temp directories from interrupted or crashed runs use {} in {}",
+        stale.len(),
+        format_bytes(total),
+        temp_base.display()
+    );
+    warn!("Run `crossref-citation-extraction cleanup` to remove them");
+}
 
-    if extraction_stats.total_matches == 0 {
-        warn!("No matches found during extraction");
+/// Save the checkpoint before propagating an interruption
+///
+/// Intermediate files are kept, including a system temp partition directory.
+fn save_on_interrupt<T>(
+    result: Result<T>,
+    checkpoint: &Checkpoint,
+    partition_dir: &Path,
+) -> Result<T> {
+    if result.as_ref().is_err_and(is_interrupted) {
+        checkpoint.save(&partition_dir.join(CHECKPOINT_FILE))?;
+        warn!(
+            "Run interrupted during {:?}; intermediate files kept in {}",
+            checkpoint.phase,
+            partition_dir.display()
+        );
     }
+    result
+}
 
-    // Phase 3: Invert partitions
+/// Whether `dir` holds partition files written by an extraction
+fn has_partition_files(dir: &Path) -> bool {
+    std::fs::read_dir(dir).is_ok_and(|entries| {
+        entries.filter_map(|entry| entry.ok()).any(|entry| {
+            let name = entry.file_name();
+            Path::new(&name)
+                .extension()
+                .is_some_and(|ext| ext == PARTITION_EXTENSION)
+                && name != INVERTED_FILE
+        })
+    })
+}
+
+/// Carry the extraction totals into the checkpoint, for a resumed run to start from
+fn record_extraction_stats(checkpoint: &mut Checkpoint, stats: &ExtractionStats) {
+    checkpoint.stats.json_files_processed = stats.files_processed;
+    checkpoint.stats.total_records = stats.items_processed;
+    checkpoint.stats.references_with_matches = stats.refs_with_matches;
+    checkpoint.stats.total_matches = stats.total_matches;
+}
+
+/// Invert a target's partitions and write its optional leaderboard report
+#[cfg_attr(not(feature = "parquet"), allow(unused_variables))]
+fn aggregate_target(
+    args: &PipelineArgs,
+    target: &SourceTarget,
+    multiple_targets: bool,
+    checkpoint: &mut Checkpoint,
+    memory_budget: Option<MemoryBudget>,
+    cancel: &CancellationToken,
+) -> Result<InvertStats> {
     info!("");
-    info!("=== Aggregating Citations ===");
+    info!("=== Aggregating Citations ({}) ===", target.source);
 
-    let output_mode = match args.source {
+    let output_mode = match target.source {
         Source::Arxiv => OutputMode::Arxiv,
         _ => OutputMode::Generic,
     };
+    let inverted_output = target.partition_dir.join(INVERTED_FILE);
+    let output_jsonl = inverted_jsonl_path(args, target);
 
-    // Determine output paths
-    let output_parquet = partition_dir.join("inverted.parquet");
-    let output_jsonl = match args.source {
-        Source::Arxiv => args.output_arxiv.as_ref().map(PathBuf::from),
-        Source::Crossref => args.output_crossref.as_ref().map(PathBuf::from),
-        Source::Datacite => args.output_datacite.as_ref().map(PathBuf::from),
-        Source::All => None, // Will handle separately in validation phase
+    let invert = if args.retry_failed_partitions {
+        retry_failed_partitions
+    } else {
+        invert_partitions
     };
-
-    let mut checkpoint = Checkpoint::new(&format!("pipeline-{}", Uuid::new_v4()));
-
-    let invert_stats = invert_partitions(
-        &partition_dir,
-        &output_parquet,
-        output_jsonl.as_deref(),
-        &mut checkpoint,
-        output_mode,
-    )?;
+    let invert_stats = invert_pool(memory_budget)?.install(|| {
+        invert(
+            &target.partition_dir,
+            &inverted_output,
+            Some(&output_jsonl),
+            checkpoint,
+            output_mode,
+            cancel,
+        )
+    })?;
 
     info!("Aggregation complete:");
     info!(
         "  Partitions processed: {}",
         invert_stats.partitions_processed
     );
+    if invert_stats.partitions_failed > 0 {
+        warn!("  Partitions failed: {}", invert_stats.partitions_failed);
+    }
     info!(
         "  Unique cited works (all extracted): {}",
         invert_stats.unique_cited_works
@@ -419,138 +1907,852 @@ pub fn run_pipeline(args: PipelineArgs) -> Result<()> {
         "  Total citations (all extracted): {}",
         invert_stats.total_citations
     );
+    let counts = &invert_stats.provenance_counts;
+    info!(
+        "    {} publisher, {} crossref, {} structured, {} mined",
+        counts.publisher_citation_count,
+        counts.crossref_citation_count,
+        counts.structured_citation_count,
+        counts.mined_citation_count
+    );
+
+    #[cfg(feature = "parquet")]
+    if let Some(ref report_path) = args.leaderboard {
+        let report_path = per_source_path(report_path, target.source, multiple_targets);
+        if invert_stats.unique_cited_works > 0 {
+            Leaderboard::from_inverted_parquet(&inverted_output, args.leaderboard_size)?
+                .write(&report_path, &output_options(args))?;
+        } else {
+            warn!("No cited works to report, skipping leaderboard");
+        }
+    }
 
-    // Phase 4: Validate
+    Ok(invert_stats)
+}
+
+/// Phase a run resumed from intermediates starts at
+///
+/// Without --resume-phase, validation reuses the inverted outputs when every target has
+/// one, and the partitions are inverted again otherwise.
+fn resume_phase(
+    args: &PipelineArgs,
+    manifest: &IntermediatesManifest,
+    sources: &[Source],
+) -> Result<ResumePhase> {
+    let inverted = |source: &Source| {
+        manifest
+            .target(&source.to_string())
+            .is_some_and(|target| target.inverted.is_some())
+    };
+    match args.resume_phase {
+        Some(ResumePhase::Validate) => match sources.iter().find(|source| !inverted(source)) {
+            Some(source) => Err(anyhow::anyhow!(
+                "--resume-phase validate requires inverted output, but {} was not inverted",
+                source
+            )),
+            None => Ok(ResumePhase::Validate),
+        },
+        Some(phase) => Ok(phase),
+        None if sources.iter().all(inverted) => Ok(ResumePhase::Validate),
+        None => Ok(ResumePhase::Aggregate),
+    }
+}
+
+/// Rewrite a target's validation input from the inverted output a kept run left
+fn reuse_inverted(args: &PipelineArgs, target: &SourceTarget) -> Result<InvertStats> {
     info!("");
-    info!("=== Validating Citations ===");
+    info!("=== Reusing Inverted Citations ({}) ===", target.source);
+    let inverted = target.partition_dir.join(INVERTED_FILE);
+    #[cfg(feature = "parquet")]
+    let stats = {
+        let output_mode = match target.source {
+            Source::Arxiv => OutputMode::Arxiv,
+            _ => OutputMode::Generic,
+        };
+        export_inverted_jsonl(&inverted, &inverted_jsonl_path(args, target), output_mode)?
+    };
+    #[cfg(not(feature = "parquet"))]
+    let stats = export_inverted_jsonl(&inverted, &inverted_jsonl_path(args, target))?;
     info!(
-        "Filtering {} extracted works to {} source DOIs...",
-        invert_stats.unique_cited_works, args.source
+        "  Unique cited works (all extracted): {}",
+        stats.unique_cited_works
     );
+    info!(
+        "  Total citations (all extracted): {}",
+        stats.total_citations
+    );
+    Ok(stats)
+}
+
+/// Thread pool for inverting partitions, sized from the memory budget when one is given
+fn invert_pool(memory_budget: Option<MemoryBudget>) -> Result<rayon::ThreadPool> {
+    let invert_concurrency = memory_budget
+        .map(|budget| budget.invert_concurrency())
+        .unwrap_or_else(num_cpus::get);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(invert_concurrency)
+        .build()
+        .context("Failed to build inversion thread pool")
+}
 
-    let http_fallback_enabled = args
-        .http_fallback
+/// Extract the --baseline-snapshot into a baseline directory under each target
+///
+/// The loaded indexes are reused. Works registered in the baseline stay registered, so
+/// a Crossref index built during extraction is simply extended with them.
+fn extract_baseline(
+    args: &PipelineArgs,
+    baseline: &str,
+    indexes: &mut PipelineIndexes,
+    targets: &[SourceTarget],
+    memory: &MemoryPressure,
+    cancel: &CancellationToken,
+) -> Result<ExtractionStats> {
+    info!("");
+    info!("=== Baseline Extraction Phase ===");
+    info!("Baseline snapshot: {}", baseline);
+
+    let baseline_args = PipelineArgs {
+        input: baseline.to_string(),
+        reference_corpus: None,
+        unmatched_refs_output: None,
+        ..args.clone()
+    };
+    let baseline_targets: Vec<SourceTarget> = targets
         .iter()
-        .any(|s| s == "crossref" || s == "datacite" || s == "all");
-
-    // Only run validation if we have an index to validate against and JSONL output
-    if indexes.crossref.is_some() || indexes.datacite.is_some() {
-        if let Some(ref jsonl_path) = output_jsonl {
-            let validation_input = jsonl_path.to_string_lossy().to_string();
-
-            let rt = tokio::runtime::Runtime::new()?;
-            let validation_results = rt.block_on(validate_citations(
-                &validation_input,
-                indexes.crossref.as_ref(),
-                indexes.datacite.as_ref(),
-                args.source,
-                http_fallback_enabled,
-                args.concurrency,
-                args.timeout,
-            ))?;
-
-            info!("Validation results:");
-            info!(
-                "  Total records checked: {}",
-                validation_results.stats.total_records
-            );
-            info!(
-                "  Crossref index matched: {}",
-                validation_results.stats.crossref_matched
-            );
+        .map(|target| SourceTarget {
+            source: target.source,
+            partition_dir: target.partition_dir.join(BASELINE_DIR),
+        })
+        .collect();
+
+    // Not persisted: an interrupted run extracts the baseline again
+    let mut checkpoint = Checkpoint::new(&format!("baseline-{}", Uuid::new_v4()));
+    run_extraction(
+        &baseline_args,
+        indexes,
+        &baseline_targets,
+        None,
+        ExtractionCheckpoint {
+            checkpoint: &mut checkpoint,
+            path: None,
+        },
+        memory,
+        cancel,
+    )
+}
+
+#[cfg(feature = "parquet")]
+/// Invert a target's baseline partitions and compare them with its inverted output
+fn compare_target_snapshots(
+    target: &SourceTarget,
+    output: &Path,
+    memory_budget: Option<MemoryBudget>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    info!("");
+    info!("=== Comparing Snapshots ({}) ===", target.source);
+
+    let output_mode = match target.source {
+        Source::Arxiv => OutputMode::Arxiv,
+        _ => OutputMode::Generic,
+    };
+    let baseline_dir = target.partition_dir.join(BASELINE_DIR);
+    let baseline_parquet = baseline_dir.join(INVERTED_FILE);
+    let mut checkpoint = Checkpoint::new(&format!("baseline-{}", Uuid::new_v4()));
+    let baseline_stats = invert_pool(memory_budget)?.install(|| {
+        invert_partitions(
+            &baseline_dir,
+            &baseline_parquet,
+            None,
+            &mut checkpoint,
+            output_mode,
+            cancel,
+        )
+    })?;
+    info!(
+        "Baseline: {} unique cited works, {} citations",
+        baseline_stats.unique_cited_works, baseline_stats.total_citations
+    );
+    if baseline_stats.partitions_failed > 0 {
+        warn!(
+            "  {} baseline partitions failed to invert; their citations are missing from the comparison",
+            baseline_stats.partitions_failed
+        );
+    }
+
+    let current_parquet = target.partition_dir.join(INVERTED_FILE);
+    if !baseline_parquet.exists() || !current_parquet.exists() {
+        warn!("No cited works in one of the snapshots, skipping snapshot comparison");
+        return Ok(());
+    }
+    compare_snapshots(&baseline_parquet, &current_parquet, output)?;
+    Ok(())
+}
+
+/// Validate a target's inverted citations and write its outputs
+async fn validate_target(
+    args: &PipelineArgs,
+    indexes: &PipelineIndexes,
+    target: &SourceTarget,
+    http_fallback: Option<&HttpFallback>,
+    citing_works: Option<&mut CitingWorks>,
+    collisions: Option<&mut CollisionReport>,
+    cancel: &CancellationToken,
+) -> Result<Option<MultiValidateStats>> {
+    // arXiv DOIs are DataCite DOIs; the arXiv metadata index stands in for the dump
+    let datacite_index = match target.source {
+        Source::Arxiv => indexes.datacite.as_ref().or(indexes.arxiv.as_ref()),
+        _ => indexes.datacite.as_ref(),
+    };
+
+    // Only run validation if we have an index to validate against
+    if indexes.crossref.is_none() && datacite_index.is_none() {
+        info!("No indexes available for validation, skipping...");
+        publish_unvalidated(args, target)?;
+        return Ok(None);
+    }
+
+    let validation_input = inverted_jsonl_path(args, target)
+        .to_string_lossy()
+        .to_string();
+    if !Path::new(&validation_input).exists() {
+        warn!(
+            "No inverted {} citations to validate, skipping...",
+            target.source
+        );
+        return Ok(None);
+    }
+
+    let mut validation_results = validate_citations(
+        &validation_input,
+        indexes.crossref.as_ref(),
+        datacite_index,
+        target.source,
+        http_fallback,
+        doi_constraints(args),
+        cancel,
+    )
+    .await?;
+
+    log_validation_stats(
+        &validation_results,
+        target.source,
+        args.datacite_prefix_only,
+        http_fallback.is_some(),
+    );
+
+    if args.registration_agency_lookup && !validation_results.failed.is_empty() {
+        lookup_registration_agencies(
+            &mut validation_results.failed,
+            args.concurrency,
+            args.timeout,
+            &args.http_client.config(),
+            cancel,
+        )
+        .await?;
+    }
+
+    run_blocking(|| {
+        write_target_results(
+            args,
+            indexes,
+            target,
+            validation_results,
+            citing_works,
+            collisions,
+        )
+    })
+    .map(Some)
+}
+
+/// Annotate a target's validation results and write its outputs
+fn write_target_results(
+    args: &PipelineArgs,
+    indexes: &PipelineIndexes,
+    target: &SourceTarget,
+    mut validation_results: ValidationResults,
+    citing_works: Option<&mut CitingWorks>,
+    collisions: Option<&mut CollisionReport>,
+) -> Result<MultiValidateStats> {
+    if target.source == Source::Arxiv && args.link_published_dois {
+        link_published_dois(
+            &mut validation_results,
+            args.datacite_records.as_ref().unwrap(),
+        )?;
+    }
+
+    if let Some(ref publishers) = indexes.publishers {
+        annotate_publishers(&mut validation_results, publishers);
+    }
+
+    let output_options = output_options(args);
+
+    // HTTP-resolved records go to their own output when one is given
+    let http_output = match target.source {
+        Source::Arxiv => args.output_arxiv_http_resolved.as_deref(),
+        _ => args.output_http_resolved.as_deref(),
+    };
+    let http_resolved = match http_output {
+        Some(path) => {
+            let http_resolved = split_off_http_resolved(&mut validation_results);
+            write_http_resolved_results(&http_resolved, path, target.source, &output_options)?;
+            http_resolved
+        }
+        None => Vec::new(),
+    };
+
+    // Write outputs based on source mode (all modes use split output by provenance)
+    match target.source {
+        Source::All => {
+            let (crossref_written, datacite_written) = write_split_validation_results(
+                &validation_results,
+                args.output_crossref.as_deref(),
+                args.output_datacite.as_deref(),
+                args.output_crossref_failed.as_deref(),
+                args.output_datacite_failed.as_deref(),
+                &output_options,
+            )?;
             info!(
-                "  DataCite index matched: {}",
-                validation_results.stats.datacite_matched
+                "Output written: {} Crossref, {} DataCite",
+                crossref_written, datacite_written
             );
-            if http_fallback_enabled {
-                info!(
-                    "  HTTP resolved: {} crossref, {} datacite",
-                    validation_results.stats.crossref_http_resolved,
-                    validation_results.stats.datacite_http_resolved
+        }
+        Source::Crossref => {
+            write_validation_results_with_split(
+                &validation_results.valid,
+                &validation_results.failed,
+                args.output_crossref.as_ref().unwrap(),
+                args.output_crossref_failed.as_deref(),
+                &output_options,
+            )?;
+        }
+        Source::Datacite => {
+            write_validation_results_with_split(
+                &validation_results.valid,
+                &validation_results.failed,
+                args.output_datacite.as_ref().unwrap(),
+                args.output_datacite_failed.as_deref(),
+                &output_options,
+            )?;
+        }
+        Source::Arxiv => {
+            write_arxiv_validation_results_with_split(
+                &validation_results,
+                args.output_arxiv.as_ref().unwrap(),
+                args.output_arxiv_failed.as_deref(),
+                &output_options,
+            )?;
+
+            if let Some(ref categories_path) = args.arxiv_categories {
+                let categories = match args.arxiv_metadata {
+                    Some(ref metadata) => ArxivCategories::load(metadata)?,
+                    None => ArxivCategories::default(),
+                };
+                let counts = aggregate_by_category(
+                    validation_results
+                        .valid
+                        .iter()
+                        .chain(&http_resolved)
+                        .map(|(record, _)| record),
+                    &categories,
                 );
+                write_category_counts(&counts, categories_path)?;
             }
+        }
+    }
+
+    if let Some(citing_works) = citing_works {
+        citing_works.add(
+            validation_results.valid.iter().chain(&http_resolved),
+            target.source,
+        );
+    }
+    if let Some(collisions) = collisions {
+        collisions.add_cited(
+            validation_results
+                .valid
+                .iter()
+                .chain(&http_resolved)
+                .chain(&validation_results.failed)
+                .map(|(record, _)| record),
+        );
+    }
+
+    Ok(validation_results.stats)
+}
+
+/// Snapshot label for --stats-history: --snapshot-id, or the --input file name
+#[cfg(feature = "parquet")]
+fn snapshot_id(args: &PipelineArgs) -> String {
+    args.snapshot_id.clone().unwrap_or_else(|| {
+        Path::new(&args.input)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| args.input.clone())
+    })
+}
+
+/// Collision report seeded with the case and whitespace collisions of the built indexes
+fn collision_report(indexes: &PipelineIndexes) -> CollisionReport {
+    let mut report = CollisionReport::default();
+    for (name, index) in [
+        ("crossref", &indexes.crossref),
+        ("datacite", &indexes.datacite),
+    ] {
+        if let Some(collisions) = index.as_ref().and_then(|index| index.collisions.as_ref()) {
             info!(
-                "  Valid {} citations: {}",
-                args.source,
-                validation_results.valid.len()
-            );
-            info!(
-                "  Failed (not in {} index): {}",
-                args.source,
-                validation_results.failed.len()
+                "{} index: {} DOIs collide with another by case or whitespace",
+                name,
+                collisions.len()
             );
+            report.add_index(name, collisions);
+        }
+    }
+    report
+}
 
-            // Write outputs based on source mode (all modes use split output by provenance)
-            match args.source {
-                Source::All => {
-                    let (crossref_written, datacite_written) = write_split_validation_results(
-                        &validation_results,
-                        args.output_crossref.as_deref(),
-                        args.output_datacite.as_deref(),
-                        args.output_crossref_failed.as_deref(),
-                        args.output_datacite_failed.as_deref(),
-                    )?;
-                    info!(
-                        "Output written: {} Crossref, {} DataCite",
-                        crossref_written, datacite_written
-                    );
-                }
-                Source::Crossref => {
-                    write_validation_results_with_split(
-                        &validation_results.valid,
-                        &validation_results.failed,
-                        args.output_crossref.as_ref().unwrap(),
-                        args.output_crossref_failed.as_deref(),
-                    )?;
-                }
-                Source::Datacite => {
-                    write_validation_results_with_split(
-                        &validation_results.valid,
-                        &validation_results.failed,
-                        args.output_datacite.as_ref().unwrap(),
-                        args.output_datacite_failed.as_deref(),
-                    )?;
-                }
-                Source::Arxiv => {
-                    write_arxiv_validation_results_with_split(
-                        &validation_results,
-                        args.output_arxiv.as_ref().unwrap(),
-                        args.output_arxiv_failed.as_deref(),
-                    )?;
-                }
-            }
-        } else {
-            info!("No JSONL output specified, skipping validation...");
+/// Inverted JSONL for a target: its output file, which validation then rewrites
+///
+/// In all mode the inverted works are split between the Crossref and DataCite outputs
+/// only after validation, so they go to an intermediate file in the partition directory.
+/// So do they with `--output-sync-every`, where a rerun resumes the output after its
+/// high-water mark and must not find the inverted works in its place, and for outputs
+/// written sharded or in another format than plain JSON Lines.
+fn inverted_jsonl_path(args: &PipelineArgs, target: &SourceTarget) -> PathBuf {
+    let options = output_options(args);
+    target_output(args, target)
+        .filter(|output| {
+            options.sync_every == 0 && options.shard.is_none() && is_plain_jsonl_file(output)
+        })
+        .unwrap_or_else(|| target.partition_dir.join("inverted.jsonl"))
+}
+
+/// Whether an output is written as plain JSON Lines to a file, as inverted works are
+fn is_plain_jsonl_file(output: &Path) -> bool {
+    output.as_os_str() != STDOUT_PATH
+        && !matches!(
+            output.extension().and_then(|ext| ext.to_str()),
+            Some("gz" | "parquet" | "arrow" | "feather" | "arrows")
+        )
+}
+
+/// Write the inverted works of a target left unvalidated to its output, when they were
+/// written elsewhere, in the output's format and with its shard options
+fn publish_unvalidated(args: &PipelineArgs, target: &SourceTarget) -> Result<()> {
+    let inverted = inverted_jsonl_path(args, target);
+    let Some(output) = target_output(args, target).filter(|output| *output != inverted) else {
+        return Ok(());
+    };
+    if !inverted.exists() {
+        return Ok(());
+    }
+    // Left by an earlier synced write, it no longer describes the output
+    let mark = HighWaterMark::path_for(&output);
+    if mark.exists() {
+        std::fs::remove_file(&mark).with_context(|| format!("Failed to remove: {:?}", mark))?;
+    }
+
+    let options = OutputOptions {
+        restart: true,
+        ..output_options(args)
+    };
+    let mut sink = open_sink_with(&output, &options)?;
+    let reader = BufReader::new(
+        File::open(&inverted).with_context(|| format!("Failed to open: {:?}", inverted))?,
+    );
+    for line in reader.lines() {
+        let line = line.with_context(|| format!("Failed to read: {:?}", inverted))?;
+        if line.trim().is_empty() {
+            continue;
         }
-    } else {
-        info!("No indexes available for validation, skipping...");
+        let record: Value = serde_json::from_str(&line)
+            .with_context(|| format!("Failed to parse record in {:?}", inverted))?;
+        sink.write_record(&record)?;
     }
+    let records = sink.finish()?;
+    info!("Wrote {} unvalidated records to: {:?}", records, output);
+    Ok(())
+}
 
-    // Save indexes if requested
-    if let Some(ref path) = args.save_crossref_index {
-        if let Some(ref index) = indexes.crossref {
-            save_index_to_parquet(index, path)?;
+/// The `--output-*` path of a single-source target
+fn target_output(args: &PipelineArgs, target: &SourceTarget) -> Option<PathBuf> {
+    let output = match target.source {
+        Source::Arxiv => args.output_arxiv.as_ref(),
+        Source::Crossref => args.output_crossref.as_ref(),
+        Source::Datacite => args.output_datacite.as_ref(),
+        Source::All => None,
+    };
+    output.map(PathBuf::from)
+}
+
+/// Input files whose checksums decide whether a run can be skipped
+///
+/// Sharded index directories are left out; a directory's contents are not hashed.
+fn input_paths(args: &PipelineArgs) -> Vec<&Path> {
+    [
+        Some(&args.input),
+        args.datacite_records.as_ref(),
+        args.load_crossref_index.as_ref(),
+        args.load_datacite_index.as_ref(),
+        args.only_dois.as_ref(),
+        args.arxiv_metadata.as_ref(),
+        args.baseline_snapshot.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(Path::new)
+    .filter(|path| path.is_file())
+    .collect()
+}
+
+/// Manifest location: --manifest, or manifest.json next to the first file output
+fn manifest_path(args: &PipelineArgs) -> Option<PathBuf> {
+    if let Some(ref path) = args.manifest {
+        return Some(PathBuf::from(path));
+    }
+    [
+        &args.output_crossref,
+        &args.output_datacite,
+        &args.output_arxiv,
+    ]
+    .into_iter()
+    .flatten()
+    .find(|output| output.as_str() != STDOUT_PATH)
+    .map(|output| {
+        Path::new(output)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(MANIFEST_FILE)
+    })
+}
+
+/// Every file the pipeline may have written, for the manifest
+///
+/// Outputs that were not requested or not produced are filtered out when the manifest
+/// is written.
+fn output_files(args: &PipelineArgs, targets: &[SourceTarget]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for output in [
+        &args.output_crossref,
+        &args.output_datacite,
+        &args.output_arxiv,
+        &args.output_crossref_failed,
+        &args.output_datacite_failed,
+        &args.output_arxiv_failed,
+        &args.output_http_resolved,
+        &args.output_arxiv_http_resolved,
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|output| output.as_str() != STDOUT_PATH)
+    {
+        let split = SplitOutputPaths::from_base(output);
+        for path in [split.all, split.asserted, split.mined] {
+            files.extend(ShardManifest::files_of(&path));
+        }
+    }
+
+    let multiple_targets = targets.len() > 1;
+    for target in targets {
+        for report in [&args.leaderboard, &args.snapshot_comparison]
+            .into_iter()
+            .flatten()
+        {
+            files.extend(ShardManifest::files_of(&per_source_path(
+                report,
+                target.source,
+                multiple_targets,
+            )));
+        }
+    }
+
+    files.extend(
+        [
+            &args.output_citing,
+            &args.collision_report,
+            &args.referenced_by_comparison,
+            &args.arxiv_categories,
+            &args.reference_corpus,
+            &args.unmatched_refs_output,
+            &args.save_crossref_index,
+            &args.save_datacite_index,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|path| ShardManifest::files_of(Path::new(path))),
+    );
+    files
+}
+
+/// How the validation and citing-side outputs are written
+fn output_options(args: &PipelineArgs) -> OutputOptions {
+    OutputOptions {
+        sync_every: args.output_sync_every,
+        shard: ShardLimit::from_size_or_records(args.output_shard_size, args.shard_records),
+        // HTTP outcomes can change between runs, so a rerun's records may not line up
+        restart: !args.http_fallback.is_empty(),
+    }
+}
+
+/// Suffix a report path with the source name when several sources are selected
+fn per_source_path(path: &str, source: Source, multiple_targets: bool) -> PathBuf {
+    let path = PathBuf::from(path);
+    if !multiple_targets {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, source, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, source),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Log the settings derived from a memory budget and apply the Polars streaming chunk size
+fn apply_memory_budget(budget: &MemoryBudget) {
+    info!("Memory limit: {}", format_bytes(budget.total_bytes()));
+    info!(
+        "  Partition buffer budget: {}",
+        format_bytes(budget.partition_buffer_bytes() as u64)
+    );
+    info!(
+        "  Partition flush threshold: {} rows",
+        budget.flush_threshold_rows()
+    );
+    info!(
+        "  Inversion concurrency: {} partitions",
+        budget.invert_concurrency()
+    );
+
+    // Respect an explicit user override of the Polars setting
+    if std::env::var_os("POLARS_STREAMING_CHUNK_SIZE").is_none() {
+        let chunk_size = budget.streaming_chunk_size();
+        info!("  Polars streaming chunk size: {} rows", chunk_size);
+        std::env::set_var("POLARS_STREAMING_CHUNK_SIZE", chunk_size.to_string());
+    }
+}
+
+/// Comma-separated source names for logging
+fn format_sources(sources: &[Source]) -> String {
+    sources
+        .iter()
+        .map(|source| source.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Reject the options that read or write Parquet, in a build without it
+#[cfg(not(feature = "parquet"))]
+fn check_parquet_options(args: &PipelineArgs) -> Result<()> {
+    let parquet_options = [
+        ("--load-crossref-index", args.load_crossref_index.is_some()),
+        ("--save-crossref-index", args.save_crossref_index.is_some()),
+        ("--load-datacite-index", args.load_datacite_index.is_some()),
+        ("--save-datacite-index", args.save_datacite_index.is_some()),
+        ("--reference-corpus", args.reference_corpus.is_some()),
+        (
+            "--referenced-by-comparison",
+            args.referenced_by_comparison.is_some(),
+        ),
+        ("--snapshot-comparison", args.snapshot_comparison.is_some()),
+        ("--leaderboard", args.leaderboard.is_some()),
+        ("--stats-history", args.stats_history.is_some()),
+        ("--compact-partitions", args.compact_partitions),
+    ];
+    match parquet_options.iter().find(|(_, given)| *given) {
+        Some((option, _)) => Err(crate::error::Error::requires_parquet(option).into()),
+        None => Ok(()),
+    }
+}
+
+fn validate_args(args: &PipelineArgs) -> Result<()> {
+    let sources = resolve_sources(&args.source);
+    if sources.is_empty() {
+        return Err(anyhow::anyhow!("At least one --source is required"));
+    }
+    #[cfg(not(feature = "parquet"))]
+    check_parquet_options(args)?;
+    if args.min_prefix_digits > args.max_prefix_digits {
+        return Err(anyhow::anyhow!(
+            "--min-prefix-digits ({}) is above --max-prefix-digits ({})",
+            args.min_prefix_digits,
+            args.max_prefix_digits
+        ));
+    }
+    if args.skip_if_unchanged && manifest_path(args).is_none() {
+        return Err(anyhow::anyhow!(
+            "--skip-if-unchanged requires a manifest: --manifest or an output file"
+        ));
+    }
+    if args.collision_report.is_some()
+        && !should_build_crossref_index(args)
+        && (args.datacite_records.is_none() || args.load_datacite_index.is_some())
+    {
+        return Err(anyhow::anyhow!(
+            "--collision-report requires an index built in this run: Crossref (without \
+             --load-crossref-index) or DataCite from --datacite-records"
+        ));
+    }
+    // A prefix-only index holds no DOIs, so a saved one would validate nothing
+    if args.datacite_prefix_only && args.save_datacite_index.is_some() {
+        return Err(anyhow::anyhow!(
+            "--save-datacite-index cannot be combined with --datacite-prefix-only"
+        ));
+    }
+    if args.referenced_by_comparison.is_some() && sources == [Source::Arxiv] {
+        return Err(anyhow::anyhow!(
+            "--referenced-by-comparison requires a DOI source (all, crossref, or datacite)"
+        ));
+    }
+    if args.link_published_dois {
+        if !sources.contains(&Source::Arxiv) {
+            return Err(anyhow::anyhow!(
+                "--link-published-dois requires --source arxiv"
+            ));
+        }
+        if args.datacite_records.is_none() {
+            return Err(anyhow::anyhow!(
+                "--link-published-dois requires --datacite-records"
+            ));
+        }
+    }
+    if args.baseline_snapshot.is_some() != args.snapshot_comparison.is_some() {
+        return Err(anyhow::anyhow!(
+            "--baseline-snapshot and --snapshot-comparison must be given together"
+        ));
+    }
+    if args.from_intermediates.is_some() {
+        if args.baseline_snapshot.is_some() {
+            return Err(anyhow::anyhow!(
+                "--from-intermediates cannot be combined with --baseline-snapshot"
+            ));
         }
+        // The partitions listed in the manifest are used as they are
+        #[cfg(feature = "parquet")]
+        if args.compact_partitions {
+            return Err(anyhow::anyhow!(
+                "--from-intermediates cannot be combined with --compact-partitions"
+            ));
+        }
+        // Extraction is skipped, so the Crossref index cannot be built from the snapshot
+        if should_build_crossref_index(args) {
+            return Err(anyhow::anyhow!(
+                "--from-intermediates requires --load-crossref-index for Crossref validation"
+            ));
+        }
+    }
+    if args.resume_extraction {
+        if args.temp_dir.is_none() {
+            return Err(anyhow::anyhow!(
+                "--resume-extraction requires the --temp-dir of the interrupted run"
+            ));
+        }
+        // The skipped entries would be missing from an index or export built while
+        // extracting
+        if should_build_crossref_index(args) {
+            return Err(anyhow::anyhow!(
+                "--resume-extraction requires --load-crossref-index for Crossref validation"
+            ));
+        }
+        for (given, flag) in [
+            (args.reference_corpus.is_some(), "--reference-corpus"),
+            (
+                args.unmatched_refs_output.is_some(),
+                "--unmatched-refs-output",
+            ),
+            (
+                args.referenced_by_comparison.is_some(),
+                "--referenced-by-comparison",
+            ),
+            (args.baseline_snapshot.is_some(), "--baseline-snapshot"),
+        ] {
+            if given {
+                return Err(anyhow::anyhow!(
+                    "--resume-extraction cannot be combined with {}",
+                    flag
+                ));
+            }
+        }
+    }
+    if args.retry_failed_partitions {
+        if args.baseline_snapshot.is_some() {
+            return Err(anyhow::anyhow!(
+                "--retry-failed-partitions cannot be combined with --baseline-snapshot"
+            ));
+        }
+        if args.temp_dir.is_none() {
+            return Err(anyhow::anyhow!(
+                "--retry-failed-partitions requires the --temp-dir of the earlier run"
+            ));
+        }
+        // Extraction is skipped, so the Crossref index cannot be built from the snapshot
+        if should_build_crossref_index(args) {
+            return Err(anyhow::anyhow!(
+                "--retry-failed-partitions requires --load-crossref-index for Crossref validation"
+            ));
+        }
+    }
+    // arXiv DOIs are DataCite DOIs, so only a target validated against DataCite keeps them
+    if args.hybrid_arxiv
+        && !sources
+            .iter()
+            .any(|s| matches!(s, Source::All | Source::Datacite))
+    {
+        return Err(anyhow::anyhow!(
+            "--hybrid-arxiv requires --source all or datacite"
+        ));
+    }
+    if args.arxiv_recall && !args.hybrid_arxiv && !sources.contains(&Source::Arxiv) {
+        return Err(anyhow::anyhow!(
+            "--arxiv-recall requires --source arxiv or --hybrid-arxiv"
+        ));
+    }
+    if let (Some(since), Some(until)) = (args.since, args.until) {
+        if since.cmp_common(&until) == std::cmp::Ordering::Greater {
+            return Err(anyhow::anyhow!(
+                "--since {} is after --until {}",
+                since,
+                until
+            ));
+        }
+    }
+    if (args.output_http_resolved.is_some() || args.output_arxiv_http_resolved.is_some())
+        && args.http_fallback.is_empty()
+    {
+        return Err(anyhow::anyhow!(
+            "--output-http-resolved and --output-arxiv-http-resolved require --http-fallback"
+        ));
+    }
+    if args.output_arxiv_http_resolved.is_some()
+        && !HttpFallbackSource::enabled(&args.http_fallback).1
+    {
+        return Err(anyhow::anyhow!(
+            "--output-arxiv-http-resolved requires --http-fallback datacite (arXiv DOIs are DataCite DOIs)"
+        ));
     }
-    if let Some(ref path) = args.save_datacite_index {
-        if let Some(ref index) = indexes.datacite {
-            save_index_to_parquet(index, path)?;
-        }
+    if args.output_arxiv_http_resolved.is_some() && !sources.contains(&Source::Arxiv) {
+        return Err(anyhow::anyhow!(
+            "--output-arxiv-http-resolved requires --source arxiv"
+        ));
     }
-
-    // Cleanup temp directory if needed
-    if cleanup_temp {
-        info!("Cleaning up temp directory: {}", partition_dir.display());
-        if let Err(e) = std::fs::remove_dir_all(&partition_dir) {
-            warn!("Failed to cleanup temp directory: {}", e);
-        }
+    if args.arxiv_categories.is_some() && !sources.contains(&Source::Arxiv) {
+        return Err(anyhow::anyhow!(
+            "--arxiv-categories requires --source arxiv"
+        ));
     }
 
+    for source in sources {
+        validate_source_args(args, source)?;
+    }
     Ok(())
 }
 
-fn validate_args(args: &PipelineArgs) -> Result<()> {
-    match args.source {
+/// Check the outputs and records required by a single resolved source
+fn validate_source_args(args: &PipelineArgs, source: Source) -> Result<()> {
+    match source {
         Source::All => {
             if args.output_crossref.is_none() || args.output_datacite.is_none() {
                 return Err(anyhow::anyhow!(
@@ -581,9 +2783,13 @@ fn validate_args(args: &PipelineArgs) -> Result<()> {
             if args.output_arxiv.is_none() {
                 return Err(anyhow::anyhow!("Source 'arxiv' requires --output-arxiv"));
             }
-            if args.datacite_records.is_none() && args.load_datacite_index.is_none() {
+            if args.datacite_records.is_none()
+                && args.load_datacite_index.is_none()
+                && args.arxiv_metadata.is_none()
+            {
                 return Err(anyhow::anyhow!(
-                    "Source 'arxiv' requires --datacite-records or --load-datacite-index"
+                    "Source 'arxiv' requires --datacite-records, --load-datacite-index or \
+                     --arxiv-metadata"
                 ));
             }
         }
@@ -594,33 +2800,114 @@ fn validate_args(args: &PipelineArgs) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::cli::PipelineArgs;
+    use crate::cli::{HttpClientArgs, PipelineArgs};
+    use crate::extract::{
+        DEFAULT_ARXIV_RECALL_WINDOW, DEFAULT_MAX_DOI_LENGTH, DEFAULT_MAX_PREFIX_DIGITS,
+        DEFAULT_MIN_PREFIX_DIGITS,
+    };
+    use crate::streaming::DEFAULT_COMPACT_TARGET_ROWS;
+    use clap::Parser;
 
     fn default_args() -> PipelineArgs {
         PipelineArgs {
             input: "test.tar.gz".to_string(),
             datacite_records: None,
-            source: Source::All,
+            source: vec![Source::All],
             output_crossref: None,
             output_datacite: None,
             output_arxiv: None,
             output_crossref_failed: None,
             output_datacite_failed: None,
             output_arxiv_failed: None,
+            output_http_resolved: None,
+            output_arxiv_http_resolved: None,
+            output_citing: None,
+            output_sync_every: 0,
+            output_shard_size: None,
+            shard_records: None,
+            collision_report: None,
             http_fallback: vec![],
             load_crossref_index: None,
             save_crossref_index: None,
             load_datacite_index: None,
             save_datacite_index: None,
+            datacite_prefix_only: false,
             log_level: "INFO".to_string(),
             concurrency: 50,
             timeout: 5,
+            http_checkpoint: None,
+            http_resolver_file: None,
+            bad_prefix_threshold: 0,
+            reference_corpus: None,
+            unmatched_refs_output: None,
+            context_chars: 0,
+            citing_license_funder: false,
+            unattributed_doi_provenance: UnattributedPolicy::Mined,
+            strip_markup: false,
+            max_ref_json_bytes: None,
+            hybrid_arxiv: false,
+            arxiv_recall: false,
+            arxiv_recall_window: DEFAULT_ARXIV_RECALL_WINDOW,
+            arxiv_legacy_categories: None,
+            referenced_by_comparison: None,
+            baseline_snapshot: None,
+            snapshot_comparison: None,
+            link_published_dois: false,
+            annotate_publishers: false,
+            publisher_names: None,
+            arxiv_categories: None,
+            arxiv_metadata: None,
+            leaderboard: None,
+            leaderboard_size: 25,
+            only_dois: None,
+            since: None,
+            until: None,
+            manifest: None,
+            lock_file: None,
+            skip_if_unchanged: false,
+            compact_partitions: false,
+            compact_target_rows: DEFAULT_COMPACT_TARGET_ROWS,
+            stats_history: None,
+            snapshot_id: None,
+            skip_records: 0,
+            limit_records: None,
             keep_intermediates: false,
             temp_dir: None,
+            retry_failed_partitions: false,
+            resume_extraction: false,
+            from_intermediates: None,
+            resume_phase: None,
+            stats_interval: 30,
+            adaptive_concurrency: false,
+            min_concurrency: 4,
+            max_concurrency: 500,
+            index_shards: None,
+            registration_agency_lookup: false,
+            parse_workers: 1,
             batch_size: 5000000,
+            memory_limit: None,
+            memory_warn_percent: 10,
+            match_cache_size: 0,
+            min_prefix_digits: DEFAULT_MIN_PREFIX_DIGITS,
+            max_prefix_digits: DEFAULT_MAX_PREFIX_DIGITS,
+            max_doi_length: DEFAULT_MAX_DOI_LENGTH,
+            doi_spacing_tolerant: false,
+            trim_glued_author_year: false,
+            http_client: HttpClientArgs::default(),
         }
     }
 
+    #[test]
+    fn test_run_pipeline_future_is_send() {
+        // Async services spawn the pipeline onto their own runtime
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&run_pipeline(default_args()));
+        assert_send(&run_pipeline_with_cancel(
+            default_args(),
+            CancellationToken::new(),
+        ));
+    }
+
     #[test]
     fn test_validate_args_all_requires_both_outputs() {
         let args = default_args();
@@ -641,10 +2928,70 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_validate_args_collision_report_requires_built_index() {
+        let mut args = default_args();
+        args.source = vec![Source::Arxiv];
+        args.output_arxiv = Some("arxiv.jsonl".to_string());
+        args.collision_report = Some("collisions.jsonl".to_string());
+        let err = validate_args(&args).unwrap_err();
+        assert!(err.to_string().contains("--collision-report"));
+
+        args.datacite_records = Some("datacite.jsonl.gz".to_string());
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_validate_args_skip_if_unchanged_requires_manifest() {
+        let mut args = default_args();
+        args.output_crossref = Some("-".to_string());
+        args.output_datacite = Some("-".to_string());
+        args.skip_if_unchanged = true;
+        let err = validate_args(&args).unwrap_err();
+        assert!(err.to_string().contains("--skip-if-unchanged"));
+
+        args.manifest = Some("manifest.json".to_string());
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_http_fallback_sources_parse() {
+        let parse = |value: &str| {
+            PipelineArgs::try_parse_from([
+                "pipeline",
+                "--input",
+                "in.tar.gz",
+                "--http-fallback",
+                value,
+            ])
+            .map(|args| HttpFallbackSource::enabled(&args.http_fallback))
+        };
+        assert_eq!(parse("crossref").unwrap(), (true, false));
+        assert_eq!(parse("crossref,datacite").unwrap(), (true, true));
+        assert_eq!(parse("all").unwrap(), (true, true));
+        assert!(parse("crosref").is_err());
+        assert!(parse("crossref,").is_err());
+    }
+
+    #[test]
+    fn test_validate_args_arxiv_http_resolved_requires_datacite_fallback() {
+        let mut args = default_args();
+        args.source = vec![Source::Arxiv];
+        args.output_arxiv = Some("-".to_string());
+        args.datacite_records = Some("records.jsonl.gz".to_string());
+        args.output_arxiv_http_resolved = Some("http.jsonl".to_string());
+        args.http_fallback = vec![HttpFallbackSource::Crossref];
+        let err = validate_args(&args).unwrap_err();
+        assert!(err.to_string().contains("--http-fallback datacite"));
+
+        args.http_fallback = vec![HttpFallbackSource::Datacite];
+        assert!(validate_args(&args).is_ok());
+    }
+
     #[test]
     fn test_validate_args_crossref_requires_output() {
         let mut args = default_args();
-        args.source = Source::Crossref;
+        args.source = vec![Source::Crossref];
         let result = validate_args(&args);
         assert!(result.is_err());
         assert!(result
@@ -653,10 +3000,30 @@ mod tests {
             .contains("--output-crossref"));
     }
 
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_validate_args_referenced_by_comparison_requires_doi_source() {
+        let mut args = default_args();
+        args.source = vec![Source::Arxiv];
+        args.output_arxiv = Some("arxiv.jsonl".to_string());
+        args.referenced_by_comparison = Some("comparison.parquet".to_string());
+        let result = validate_args(&args);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("--referenced-by-comparison"));
+
+        args.source = vec![Source::Crossref, Source::Arxiv];
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.datacite_records = Some("records.jsonl.gz".to_string());
+        assert!(validate_args(&args).is_ok());
+    }
+
     #[test]
     fn test_validate_args_crossref_with_output() {
         let mut args = default_args();
-        args.source = Source::Crossref;
+        args.source = vec![Source::Crossref];
         args.output_crossref = Some("crossref.jsonl".to_string());
         let result = validate_args(&args);
         assert!(result.is_ok());
@@ -665,7 +3032,7 @@ mod tests {
     #[test]
     fn test_validate_args_datacite_requires_output() {
         let mut args = default_args();
-        args.source = Source::Datacite;
+        args.source = vec![Source::Datacite];
         args.datacite_records = Some("records.jsonl.gz".to_string());
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -678,7 +3045,7 @@ mod tests {
     #[test]
     fn test_validate_args_datacite_requires_records() {
         let mut args = default_args();
-        args.source = Source::Datacite;
+        args.source = vec![Source::Datacite];
         args.output_datacite = Some("datacite.jsonl".to_string());
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -691,7 +3058,7 @@ mod tests {
     #[test]
     fn test_validate_args_datacite_with_records_file() {
         let mut args = default_args();
-        args.source = Source::Datacite;
+        args.source = vec![Source::Datacite];
         args.output_datacite = Some("datacite.jsonl".to_string());
         args.datacite_records = Some("records.jsonl.gz".to_string());
         let result = validate_args(&args);
@@ -699,9 +3066,21 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "parquet"))]
+    fn test_validate_args_rejects_parquet_options() {
+        let mut args = default_args();
+        args.leaderboard = Some("top.md".to_string());
+        let err = validate_args(&args).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("--leaderboard requires Parquet support"));
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
     fn test_validate_args_datacite_with_index() {
         let mut args = default_args();
-        args.source = Source::Datacite;
+        args.source = vec![Source::Datacite];
         args.output_datacite = Some("datacite.jsonl".to_string());
         args.load_datacite_index = Some("index.parquet".to_string());
         let result = validate_args(&args);
@@ -711,7 +3090,7 @@ mod tests {
     #[test]
     fn test_validate_args_arxiv_requires_output() {
         let mut args = default_args();
-        args.source = Source::Arxiv;
+        args.source = vec![Source::Arxiv];
         args.datacite_records = Some("records.jsonl.gz".to_string());
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -721,7 +3100,7 @@ mod tests {
     #[test]
     fn test_validate_args_arxiv_requires_records() {
         let mut args = default_args();
-        args.source = Source::Arxiv;
+        args.source = vec![Source::Arxiv];
         args.output_arxiv = Some("arxiv.jsonl".to_string());
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -731,16 +3110,478 @@ mod tests {
             .contains("--datacite-records"));
     }
 
+    #[test]
+    fn test_validate_args_arxiv_accepts_metadata() {
+        let mut args = default_args();
+        args.source = vec![Source::Arxiv];
+        args.output_arxiv = Some("arxiv.jsonl".to_string());
+        args.arxiv_metadata = Some("arxiv-metadata.json.gz".to_string());
+        assert!(validate_args(&args).is_ok());
+
+        // DataCite validation still needs the DataCite records
+        args.source = vec![Source::Datacite, Source::Arxiv];
+        args.output_datacite = Some("datacite.jsonl".to_string());
+        assert!(validate_args(&args).is_err());
+    }
+
     #[test]
     fn test_validate_args_arxiv_with_all_required() {
         let mut args = default_args();
-        args.source = Source::Arxiv;
+        args.source = vec![Source::Arxiv];
         args.output_arxiv = Some("arxiv.jsonl".to_string());
         args.datacite_records = Some("records.jsonl.gz".to_string());
         let result = validate_args(&args);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_validate_args_arxiv_categories_requires_arxiv_source() {
+        let mut args = default_args();
+        args.source = vec![Source::Crossref];
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.arxiv_categories = Some("categories.jsonl".to_string());
+        let result = validate_args(&args);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("--source arxiv"));
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_validate_args_retry_failed_partitions() {
+        let mut args = default_args();
+        args.source = vec![Source::Crossref];
+        args.output_crossref = Some("out.jsonl".to_string());
+        args.retry_failed_partitions = true;
+        assert!(validate_args(&args).is_err());
+
+        args.temp_dir = Some("/tmp/partitions".to_string());
+        assert!(validate_args(&args).is_err());
+
+        args.load_crossref_index = Some("crossref_index.parquet".to_string());
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_validate_args_resume_extraction() {
+        let mut args = default_args();
+        args.source = vec![Source::Datacite];
+        args.output_datacite = Some("out.jsonl".to_string());
+        args.datacite_records = Some("datacite.jsonl.gz".to_string());
+        args.resume_extraction = true;
+        assert!(validate_args(&args)
+            .unwrap_err()
+            .to_string()
+            .contains("--temp-dir"));
+
+        args.temp_dir = Some("/tmp/partitions".to_string());
+        assert!(validate_args(&args).is_ok());
+
+        args.unmatched_refs_output = Some("unmatched.jsonl".to_string());
+        assert!(validate_args(&args)
+            .unwrap_err()
+            .to_string()
+            .contains("--unmatched-refs-output"));
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_validate_args_from_intermediates() {
+        let mut args = default_args();
+        args.source = vec![Source::Crossref];
+        args.output_crossref = Some("out.jsonl".to_string());
+        args.from_intermediates = Some("/tmp/partitions".to_string());
+        let result = validate_args(&args);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("--load-crossref-index"));
+
+        args.load_crossref_index = Some("crossref_index.parquet".to_string());
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_resume_phase_defaults_to_inverted_outputs() {
+        use crate::streaming::intermediates::IntermediateTarget;
+
+        let target = |source: &str, inverted: bool| IntermediateTarget {
+            source: source.to_string(),
+            partition_dir: PathBuf::from(source),
+            partitions: Vec::new(),
+            inverted: inverted.then(|| PathBuf::from(INVERTED_FILE)),
+        };
+        let manifest = IntermediatesManifest {
+            version: 1,
+            run_id: "pipeline-1".to_string(),
+            checkpoint: PathBuf::from(CHECKPOINT_FILE),
+            targets: vec![target("all", true), target("arxiv", false)],
+            sidecars: Vec::new(),
+        };
+        let mut args = default_args();
+        let phase =
+            |args: &PipelineArgs, sources: &[Source]| resume_phase(args, &manifest, sources).ok();
+
+        assert_eq!(phase(&args, &[Source::All]), Some(ResumePhase::Validate));
+        assert_eq!(
+            phase(&args, &[Source::All, Source::Arxiv]),
+            Some(ResumePhase::Aggregate)
+        );
+        args.resume_phase = Some(ResumePhase::Validate);
+        assert_eq!(phase(&args, &[Source::All, Source::Arxiv]), None);
+        args.resume_phase = Some(ResumePhase::Aggregate);
+        assert_eq!(phase(&args, &[Source::All]), Some(ResumePhase::Aggregate));
+    }
+
+    #[test]
+    fn test_inverted_jsonl_path_avoids_synced_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = SourceTarget {
+            source: Source::Crossref,
+            partition_dir: dir.path().join("crossref"),
+        };
+        std::fs::create_dir(&target.partition_dir).unwrap();
+        let output = dir.path().join("crossref.jsonl");
+        let mut args = default_args();
+        args.output_crossref = Some(output.to_string_lossy().to_string());
+
+        assert_eq!(inverted_jsonl_path(&args, &target), output);
+
+        // A synced output keeps its high-water mark for the rerun, so the inverted
+        // works go elsewhere and are copied over only when nothing validates them
+        args.output_sync_every = 10;
+        let inverted = inverted_jsonl_path(&args, &target);
+        assert_eq!(inverted, target.partition_dir.join("inverted.jsonl"));
+        std::fs::write(&inverted, "{\"doi\":\"10.1234/a\"}\n").unwrap();
+        std::fs::write(HighWaterMark::path_for(&output), "{}").unwrap();
+        publish_unvalidated(&args, &target).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "{\"doi\":\"10.1234/a\"}\n"
+        );
+        assert!(HighWaterMark::load(&output).unwrap().unwrap().complete);
+
+        // A sharded output is written through its sink, never in place
+        args.output_sync_every = 0;
+        args.shard_records = Some(1);
+        assert_eq!(inverted_jsonl_path(&args, &target), inverted);
+        std::fs::write(
+            &inverted,
+            "{\"doi\":\"10.1234/a\"}\n{\"doi\":\"10.1234/b\"}\n",
+        )
+        .unwrap();
+        publish_unvalidated(&args, &target).unwrap();
+        assert_eq!(ShardManifest::load(&output).unwrap().unwrap().records, 2);
+        assert!(dir.path().join("crossref_00002.jsonl").exists());
+        args.shard_records = None;
+        assert!(!output_options(&args).restart);
+        args.http_fallback = vec![HttpFallbackSource::Crossref];
+        assert!(output_options(&args).restart);
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_validate_args_snapshot_comparison_requires_baseline() {
+        let mut args = default_args();
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.output_datacite = Some("datacite.jsonl".to_string());
+        args.snapshot_comparison = Some("growth.parquet".to_string());
+        assert!(validate_args(&args).is_err());
+
+        args.baseline_snapshot = Some("2023.tar.gz".to_string());
+        assert!(validate_args(&args).is_ok());
+
+        args.snapshot_comparison = None;
+        assert!(validate_args(&args).is_err());
+    }
+
+    #[test]
+    fn test_validate_args_multiple_sources() {
+        let mut args = default_args();
+        args.source = vec![Source::Crossref, Source::Arxiv];
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.datacite_records = Some("records.jsonl.gz".to_string());
+        let result = validate_args(&args);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("--output-arxiv"));
+
+        args.output_arxiv = Some("arxiv.jsonl".to_string());
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_sources() {
+        assert_eq!(resolve_sources(&[Source::All]), vec![Source::All]);
+        assert_eq!(
+            resolve_sources(&[Source::Crossref, Source::Datacite]),
+            vec![Source::All]
+        );
+        assert_eq!(
+            resolve_sources(&[Source::Arxiv, Source::Crossref]),
+            vec![Source::Crossref, Source::Arxiv]
+        );
+        assert_eq!(
+            resolve_sources(&[Source::All, Source::Datacite, Source::Arxiv]),
+            vec![Source::All, Source::Arxiv]
+        );
+        assert!(resolve_sources(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_per_source_path() {
+        assert_eq!(
+            per_source_path("out/top.md", Source::Arxiv, false),
+            PathBuf::from("out/top.md")
+        );
+        assert_eq!(
+            per_source_path("out/top.md", Source::Arxiv, true),
+            PathBuf::from("out/top_arxiv.md")
+        );
+        assert_eq!(
+            per_source_path("top", Source::Crossref, true),
+            PathBuf::from("top_crossref")
+        );
+    }
+
+    #[test]
+    fn test_manifest_path() {
+        let mut args = default_args();
+        assert_eq!(manifest_path(&args), None);
+
+        args.output_crossref = Some(STDOUT_PATH.to_string());
+        args.output_datacite = Some("out/datacite.jsonl".to_string());
+        assert_eq!(
+            manifest_path(&args),
+            Some(PathBuf::from("out/manifest.json"))
+        );
+
+        args.manifest = Some("release/manifest.json".to_string());
+        assert_eq!(
+            manifest_path(&args),
+            Some(PathBuf::from("release/manifest.json"))
+        );
+    }
+
+    #[test]
+    fn test_unmatched_hint() {
+        let text = "Smith J. Preprint, arXiv 2403. DOI pending ";
+        assert_eq!(
+            unmatched_hint(Source::Arxiv, text),
+            Some(UnmatchedHint::Arxiv)
+        );
+        assert_eq!(
+            unmatched_hint(Source::Crossref, text),
+            Some(UnmatchedHint::Doi)
+        );
+        assert_eq!(unmatched_hint(Source::All, "Smith J. Doing science."), None);
+    }
+
+    #[test]
+    fn test_hybrid_arxiv_matches() {
+        use serde_json::json;
+
+        let reference = json!({
+            "DOI": "10.48550/arXiv.2403.03542v2",
+            "doi-asserted-by": "publisher",
+            "unstructured": "Smith, arXiv:2403.03542 and arXiv:hep-ph/9901234; doi:10.1234/abc",
+        });
+        let search_text = format!(
+            "{} {}",
+            reference["DOI"].as_str().unwrap(),
+            reference["unstructured"].as_str().unwrap()
+        );
+        let extract = |hybrid| {
+            let mut matches = extract_matches(
+                Source::All,
+                &reference,
+                &search_text,
+                0,
+                UnattributedPolicy::Mined,
+                hybrid,
+                &mut MatchCache::new(0),
+            );
+            matches.sort_by(|a, b| a.cited_id.cmp(&b.cited_id));
+            matches
+        };
+
+        let dois: Vec<String> = extract(false).into_iter().map(|m| m.cited_id).collect();
+        assert_eq!(dois, ["10.1234/abc", "10.48550/arxiv.2403.03542v2"]);
+
+        let hybrid = extract(true);
+        let dois: Vec<&str> = hybrid.iter().map(|m| m.cited_id.as_str()).collect();
+        assert_eq!(
+            dois,
+            [
+                "10.1234/abc",
+                "10.48550/arxiv.2403.03542",
+                "10.48550/arxiv.hep-ph/9901234"
+            ]
+        );
+        // The versioned DOI field is still the publisher's assertion
+        assert_eq!(hybrid[1].provenance, Provenance::PublisherAsserted);
+        assert_eq!(hybrid[2].provenance, Provenance::Mined);
+        assert_eq!(hybrid[2].raw_match, "arXiv:hep-ph/9901234");
+    }
+
+    #[test]
+    fn test_validate_args_since_after_until() {
+        let mut args = default_args();
+        args.source = vec![Source::Crossref];
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.since = Some("2021".parse().unwrap());
+        args.until = Some("2020-12".parse().unwrap());
+        assert!(validate_args(&args).is_err());
+
+        args.until = Some("2021-03".parse().unwrap());
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_validate_args_hybrid_arxiv_requires_datacite_target() {
+        let mut args = default_args();
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.output_datacite = Some("datacite.jsonl".to_string());
+        args.hybrid_arxiv = true;
+        assert!(validate_args(&args).is_ok());
+
+        args.source = vec![Source::Crossref];
+        let err = validate_args(&args).unwrap_err().to_string();
+        assert!(err.contains("--hybrid-arxiv"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_args_arxiv_recall_requires_arxiv_matching() {
+        let mut args = default_args();
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.output_datacite = Some("datacite.jsonl".to_string());
+        args.arxiv_recall = true;
+        let err = validate_args(&args).unwrap_err().to_string();
+        assert!(err.contains("--arxiv-recall"), "{}", err);
+
+        args.hybrid_arxiv = true;
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_load_indexes_keeps_arxiv_metadata_apart_from_datacite() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = dir.path().join("arxiv-metadata.json");
+        std::fs::write(&metadata, "{\"id\": \"2403.12345\"}\n").unwrap();
+
+        let mut args = default_args();
+        args.source = vec![Source::All, Source::Arxiv];
+        args.arxiv_metadata = Some(metadata.to_str().unwrap().to_string());
+        let indexes = load_indexes(&args).unwrap();
+        assert!(indexes.datacite.is_none());
+        assert!(indexes
+            .arxiv
+            .as_ref()
+            .unwrap()
+            .contains("10.48550/arxiv.2403.12345"));
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_validate_args_save_datacite_index_requires_full_index() {
+        let mut args = default_args();
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.output_datacite = Some("datacite.jsonl".to_string());
+        args.save_datacite_index = Some("datacite.parquet".to_string());
+        assert!(validate_args(&args).is_ok());
+
+        args.datacite_prefix_only = true;
+        let err = validate_args(&args).unwrap_err().to_string();
+        assert!(err.contains("--datacite-prefix-only"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_args_prefix_digit_range() {
+        let mut args = default_args();
+        args.output_crossref = Some("crossref.jsonl".to_string());
+        args.output_datacite = Some("datacite.jsonl".to_string());
+        args.min_prefix_digits = 6;
+        args.max_prefix_digits = 5;
+        let err = validate_args(&args).unwrap_err().to_string();
+        assert!(err.contains("--min-prefix-digits"), "{}", err);
+
+        args.max_prefix_digits = 6;
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_truncate_reference() {
+        use serde_json::json;
+
+        let small = json!({"key": "r1", "unstructured": "Smith. doi:10.1234/abc"});
+        assert!(truncate_reference(&small, 1000).is_none());
+
+        let reference = json!({
+            "key": "r2",
+            "DOI": "10.1234/abc",
+            "unstructured": format!("Smith, Näture {}", "x".repeat(5000)),
+            "journal-title": "é".repeat(100),
+        });
+        let truncated = truncate_reference(&reference, 300).unwrap();
+        assert!(truncated.to_string().len() <= 300);
+        assert_eq!(truncated["truncated"], true);
+        assert_eq!(truncated["key"], "r2");
+        assert_eq!(truncated["DOI"], "10.1234/abc");
+        assert!(truncated["unstructured"]
+            .as_str()
+            .unwrap()
+            .starts_with("Smith, Nä"));
+        // The longest field absorbs the cut; shorter ones are kept whole when possible
+        assert_eq!(truncated["journal-title"], reference["journal-title"]);
+
+        // Even a giant key is kept whole, so the match maps back to the reference
+        let giant_key = json!({"key": "k".repeat(500), "unstructured": "y".repeat(400)});
+        let truncated = truncate_reference(&giant_key, 600).unwrap();
+        assert_eq!(truncated["key"], giant_key["key"]);
+    }
+
+    #[test]
+    fn test_strip_reference_markup() {
+        use serde_json::json;
+
+        let plain = json!({"key": "r1", "unstructured": "Smith. doi:10.1234/abc"});
+        assert!(strip_reference_markup(&plain).is_none());
+
+        let reference = json!({
+            "key": "r2",
+            "unstructured": "Smith, <italic>Nature</italic>, doi:10.1234/<b>abc</b>",
+        });
+        let stripped = strip_reference_markup(&reference).unwrap();
+        assert_eq!(stripped["unstructured"], "Smith, Nature, doi:10.1234/abc");
+        assert_eq!(stripped["key"], "r2");
+
+        let matches = extract_matches(
+            Source::Crossref,
+            &stripped,
+            stripped["unstructured"].as_str().unwrap(),
+            20,
+            UnattributedPolicy::Mined,
+            false,
+            &mut MatchCache::new(0),
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].cited_id, "10.1234/abc");
+    }
+
+    #[test]
+    fn test_duplicate_references() {
+        use serde_json::json;
+
+        let references = vec![
+            json!({"key": "r1", "DOI": "10.5678/a"}),
+            json!({"key": "r2", "unstructured": "Smith 2020"}),
+            json!({"key": "r1", "DOI": "10.5678/a"}),
+            json!({"key": "r1", "DOI": "10.5678/a", "year": "2020"}),
+            json!({"key": "r2", "unstructured": "Smith 2020"}),
+        ];
+        let duplicates = duplicate_references(&references);
+        assert_eq!(duplicates, HashSet::from([2, 4]));
+        assert!(duplicate_references(&[]).is_empty());
+    }
+
     #[test]
     fn test_should_include_citation() {
         assert!(should_include_citation("10.1234/a", "10.5678/b"));
@@ -748,6 +3589,84 @@ mod tests {
         assert!(!should_include_citation("10.1234/A", "10.1234/a")); // Case insensitive
     }
 
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_build_corpus_row() {
+        use serde_json::json;
+
+        // Asserted DOI field labels the row
+        let asserted = json!({
+            "DOI": "10.5678/ASSERTED",
+            "doi-asserted-by": "publisher",
+            "unstructured": "Smith J. A paper. 2020"
+        });
+        let row = build_corpus_row("10.1234/a", 3, &asserted, UnattributedPolicy::Mined).unwrap();
+        assert_eq!(row.ref_index, 3);
+        assert_eq!(row.doi.as_deref(), Some("10.5678/asserted"));
+        assert_eq!(row.provenance, Some(Provenance::PublisherAsserted));
+
+        // DOI mined from the unstructured text
+        let mined = json!({"unstructured": "Doe J. Title. doi:10.5678/mined."});
+        let row = build_corpus_row("10.1234/a", 0, &mined, UnattributedPolicy::Mined).unwrap();
+        assert_eq!(row.doi.as_deref(), Some("10.5678/mined"));
+        assert_eq!(row.provenance, Some(Provenance::Mined));
+
+        // Unlabeled reference string
+        let unlabeled = json!({"unstructured": "Doe J. A book. Publisher, 1999"});
+        let row = build_corpus_row("10.1234/a", 1, &unlabeled, UnattributedPolicy::Mined).unwrap();
+        assert!(row.doi.is_none());
+        assert!(row.provenance.is_none());
+
+        // No unstructured text, nothing to export
+        assert!(build_corpus_row(
+            "10.1234/a",
+            2,
+            &json!({"DOI": "10.5678/x"}),
+            UnattributedPolicy::Mined
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_match_context() {
+        use serde_json::json;
+
+        let reference = json!({
+            "DOI": "10.1234/asserted",
+            "doi-asserted-by": "publisher",
+            "unstructured": "Smith J. Title. doi:10.5678/mined. Journal 2020"
+        });
+
+        assert_eq!(
+            match_context(&reference, "10.5678/mined.", Provenance::Mined, 6),
+            Some(". doi:10.5678/mined. Journ".to_string())
+        );
+        // Asserted matches and disabled capture produce no context
+        assert_eq!(
+            match_context(
+                &reference,
+                "10.1234/asserted",
+                Provenance::PublisherAsserted,
+                6
+            ),
+            None
+        );
+        assert_eq!(
+            match_context(&reference, "10.5678/mined.", Provenance::Mined, 0),
+            None
+        );
+        // Matches only found outside the unstructured text have no context
+        assert_eq!(
+            match_context(
+                &json!({"URL": "https://doi.org/10.5678/x"}),
+                "10.5678/x",
+                Provenance::Mined,
+                6
+            ),
+            None
+        );
+    }
+
     #[test]
     fn test_determine_provenance() {
         use crate::extract::Provenance;
@@ -756,28 +3675,47 @@ mod tests {
         // Publisher asserted
         let ref_publisher = json!({"DOI": "10.1234/test", "doi-asserted-by": "publisher"});
         assert_eq!(
-            determine_provenance(&ref_publisher, "10.1234/test"),
-            Provenance::Publisher
+            determine_provenance(&ref_publisher, "10.1234/test", UnattributedPolicy::Mined),
+            Provenance::PublisherAsserted
         );
 
         // Crossref asserted
         let ref_crossref = json!({"DOI": "10.1234/test", "doi-asserted-by": "crossref"});
         assert_eq!(
-            determine_provenance(&ref_crossref, "10.1234/test"),
-            Provenance::Crossref
+            determine_provenance(&ref_crossref, "10.1234/test", UnattributedPolicy::Mined),
+            Provenance::CrossrefMatched
         );
 
         // DOI present but no doi-asserted-by
         let ref_no_assertion = json!({"DOI": "10.1234/test"});
         assert_eq!(
-            determine_provenance(&ref_no_assertion, "10.1234/test"),
+            determine_provenance(&ref_no_assertion, "10.1234/test", UnattributedPolicy::Mined),
             Provenance::Mined
         );
 
+        // Unattributed or unknown assertions follow the policy
+        let ref_other = json!({"DOI": "10.1234/TEST", "doi-asserted-by": "author"});
+        assert_eq!(
+            determine_provenance(&ref_other, "10.1234/test", UnattributedPolicy::Mined),
+            Provenance::Mined
+        );
+        assert_eq!(
+            determine_provenance(&ref_other, "10.1234/test", UnattributedPolicy::Structured),
+            Provenance::StructuredUnattributed
+        );
+        assert_eq!(
+            determine_provenance(
+                &ref_no_assertion,
+                "10.1234/test",
+                UnattributedPolicy::Crossref
+            ),
+            Provenance::CrossrefMatched
+        );
+
         // Mined from unstructured (DOI not in DOI field)
         let ref_unstructured = json!({"unstructured": "See doi:10.1234/test"});
         assert_eq!(
-            determine_provenance(&ref_unstructured, "10.1234/test"),
+            determine_provenance(&ref_unstructured, "10.1234/test", UnattributedPolicy::Mined),
             Provenance::Mined
         );
     }
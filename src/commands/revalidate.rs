@@ -0,0 +1,531 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{RevalidateArgs, Source};
+use crate::common::{
+    run_blocking, setup_logging, CancellationToken, CitationRecord, OutputOptions, ShardLimit,
+    ShardManifest,
+};
+use crate::extract::DoiConstraints;
+use crate::validation::{
+    lookup_registration_agencies, open_resolver, read_record_lines, split_off_http_resolved,
+    validate_citations, write_arxiv_validation_results_with_split, write_http_resolved_results,
+    write_validation_results_with_split, ConcurrencyBounds, HttpFallback, ValidationResults,
+};
+
+use super::validate::{check_validation_sources, load_validation_indexes, output_exists};
+
+pub async fn run_revalidate(args: RevalidateArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+
+    let output_failed = args.output_failed.as_deref().unwrap_or(&args.input);
+
+    info!("Starting re-validation of failed citations");
+    info!("Input: {}", args.input);
+    info!("Valid output: {}", args.valid);
+    info!("Source: {}", args.source);
+
+    if !output_exists(&args.input) {
+        return Err(anyhow::anyhow!("Input file does not exist: {}", args.input));
+    }
+    if !output_exists(&args.valid) {
+        return Err(anyhow::anyhow!(
+            "Valid output file does not exist: {}",
+            args.valid
+        ));
+    }
+
+    if args.arxiv_metadata.is_some() && args.source != Source::Arxiv {
+        return Err(anyhow::anyhow!("--arxiv-metadata requires --source arxiv"));
+    }
+
+    if args.output_http_resolved.is_some() && !args.http_fallback {
+        return Err(anyhow::anyhow!(
+            "--output-http-resolved requires --http-fallback"
+        ));
+    }
+
+    let output_options = OutputOptions {
+        shard: ShardLimit::from_size_or_records(args.output_shard_size, args.shard_records),
+        ..OutputOptions::default()
+    };
+    let rewritten = [args.valid.as_str(), output_failed];
+    for path in rewritten
+        .into_iter()
+        .chain(args.output_http_resolved.as_deref())
+    {
+        check_rewrite(path, &output_options)?;
+    }
+
+    let (crossref_index, datacite_index) = run_blocking(|| {
+        load_validation_indexes(
+            args.crossref_index.as_deref(),
+            args.index_backend,
+            &args.index_shard_ids,
+            args.datacite_records.as_deref(),
+            args.datacite_prefix_only,
+            args.arxiv_metadata.as_deref(),
+        )
+    })?;
+    check_validation_sources(
+        args.source,
+        crossref_index.as_ref(),
+        datacite_index.as_ref(),
+        args.http_fallback,
+    )?;
+
+    let resolver = open_resolver(args.http_resolver_file.as_deref())?;
+    let http_fallback = args.http_fallback.then(|| HttpFallback {
+        concurrency: args.concurrency,
+        timeout_secs: args.timeout,
+        checkpoint: args.http_checkpoint.as_ref().map(PathBuf::from),
+        adaptive: args.adaptive_concurrency.then_some(ConcurrencyBounds {
+            min: args.min_concurrency,
+            max: args.max_concurrency,
+        }),
+        crossref: true,
+        datacite: true,
+        bad_prefix_threshold: args.bad_prefix_threshold,
+        resolver,
+        client: args.http_client.config(),
+    });
+
+    // Re-run only the previously failed records
+    let mut results = validate_citations(
+        &args.input,
+        crossref_index.as_ref(),
+        datacite_index.as_ref(),
+        args.source,
+        http_fallback.as_ref(),
+        DoiConstraints::default(),
+        &CancellationToken::new(),
+    )
+    .await?;
+
+    if args.registration_agency_lookup && !results.failed.is_empty() {
+        lookup_registration_agencies(
+            &mut results.failed,
+            args.concurrency,
+            args.timeout,
+            &args.http_client.config(),
+            &CancellationToken::new(),
+        )
+        .await?;
+    }
+
+    // Newly HTTP-resolved records are merged into their own output when one is given
+    if let Some(ref path) = args.output_http_resolved {
+        let http_resolved = split_off_http_resolved(&mut results);
+        let added = run_blocking(|| -> Result<usize> {
+            let existing = if output_exists(path) {
+                read_citation_records(path)?
+            } else {
+                Vec::new()
+            };
+            let (merged, added) = merge_valid_records(existing, http_resolved, args.source);
+            write_http_resolved_results(&merged, path, args.source, &output_options)?;
+            Ok(added)
+        })?;
+        info!("Newly HTTP-resolved: {} (separate output)", added);
+    }
+
+    let newly_valid = results.valid.len();
+    let existing = run_blocking(|| read_citation_records(&args.valid))?;
+    let existing_count = existing.len();
+    let (valid, added) = merge_valid_records(existing, results.valid, args.source);
+
+    // Records of shards not loaded stay in the failed output for the jobs holding them
+    let outside_shards = results.outside_shards.len();
+    let mut failed = results.failed;
+    failed.extend(
+        results
+            .outside_shards
+            .into_iter()
+            .map(|record| (record, args.source)),
+    );
+    let merged = ValidationResults {
+        valid,
+        failed,
+        outside_shards: Vec::new(),
+        stats: results.stats,
+    };
+
+    // Rewrite valid output (and its splits) with the merged records, and replace the
+    // failed output with the records that still fail or were not checked
+    run_blocking(|| match args.source {
+        Source::Arxiv => write_arxiv_validation_results_with_split(
+            &merged,
+            &args.valid,
+            Some(output_failed),
+            &output_options,
+        ),
+        _ => write_validation_results_with_split(
+            &merged.valid,
+            &merged.failed,
+            &args.valid,
+            Some(output_failed),
+            &output_options,
+        ),
+    })?;
+
+    info!("================== RE-VALIDATION COMPLETE ===================");
+    info!("Previously failed: {}", merged.stats.total_records);
+    info!("Newly valid: {}", newly_valid);
+    if added < newly_valid {
+        info!(
+            "  Skipped (already in valid output): {}",
+            newly_valid - added
+        );
+    }
+    info!("Still failed: {}", merged.failed.len() - outside_shards);
+    if outside_shards > 0 {
+        info!(
+            "  Kept unchecked (index shard not loaded): {}",
+            outside_shards
+        );
+    }
+    info!(
+        "Valid output: {} records ({} before)",
+        merged.valid.len(),
+        existing_count
+    );
+    info!("Output failed: {}", output_failed);
+    info!("=============================================================");
+
+    Ok(())
+}
+
+/// Refuse to rewrite a sharded output unsharded
+///
+/// Readers go by the shard manifest, so a plain file written next to it would be
+/// ignored in favour of the old shards.
+fn check_rewrite(path: &str, options: &OutputOptions) -> Result<()> {
+    if options.shard.is_none() && ShardManifest::path_for(Path::new(path)).exists() {
+        return Err(anyhow::anyhow!(
+            "{} was written sharded; pass --output-shard-size or --shard-records to rewrite it",
+            path
+        ));
+    }
+    Ok(())
+}
+
+/// Read citation records from a validation output, in any format its sink writes
+fn read_citation_records(path: &str) -> Result<Vec<CitationRecord>> {
+    let mut records = Vec::new();
+    for (line_num, line_result) in read_record_lines(Path::new(path))?.enumerate() {
+        let line = line_result?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: CitationRecord = serde_json::from_str(&line).with_context(|| {
+            format!(
+                "Failed to parse record at line {} of {}",
+                line_num + 1,
+                path
+            )
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Append newly valid records to the existing valid records
+///
+/// Records whose DOI is already present are skipped so repeated runs stay idempotent.
+/// Returns the merged records and the number of records added.
+fn merge_valid_records(
+    existing: Vec<CitationRecord>,
+    newly_valid: Vec<(CitationRecord, Source)>,
+    source: Source,
+) -> (Vec<(CitationRecord, Source)>, usize) {
+    let mut seen: HashSet<String> = existing.iter().map(|r| r.doi.to_lowercase()).collect();
+    let mut merged: Vec<(CitationRecord, Source)> =
+        existing.into_iter().map(|r| (r, source)).collect();
+
+    let before = merged.len();
+    for (record, found_source) in newly_valid {
+        if seen.insert(record.doi.to_lowercase()) {
+            merged.push((record, found_source));
+        }
+    }
+    let added = merged.len() - before;
+
+    (merged, added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn record(doi: &str) -> CitationRecord {
+        CitationRecord {
+            doi: doi.to_string(),
+            arxiv_id: None,
+            id_type: None,
+            reference_count: 1,
+            citation_count: 1,
+            cited_by: vec![serde_json::json!({"doi": "10.1234/citing", "provenance": "mined"})],
+            published_doi: None,
+            registration_agency: None,
+            http_error: None,
+            validation_method: None,
+            validation_status: None,
+            asserted_citation_count: None,
+            cited_publisher: None,
+            provenance_counts: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_valid_records_skips_existing() {
+        let existing = vec![record("10.5678/a"), record("10.5678/b")];
+        let newly_valid = vec![
+            (record("10.5678/B"), Source::Crossref),
+            (record("10.5678/c"), Source::Crossref),
+        ];
+
+        let (merged, added) = merge_valid_records(existing, newly_valid, Source::Crossref);
+
+        assert_eq!(added, 1);
+        let dois: Vec<_> = merged.iter().map(|(r, _)| r.doi.as_str()).collect();
+        assert_eq!(dois, vec!["10.5678/a", "10.5678/b", "10.5678/c"]);
+    }
+
+    #[test]
+    fn test_read_citation_records() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"doi": "10.5678/a", "reference_count": 2, "citation_count": 1, "cited_by": []}}"#
+        )
+        .unwrap();
+        writeln!(file).unwrap();
+        writeln!(
+            file,
+            r#"{{"arxiv_doi": "10.48550/arXiv.2403.12345", "arxiv_id": "2403.12345", "reference_count": 1, "citation_count": 1, "cited_by": []}}"#
+        )
+        .unwrap();
+
+        let records = read_citation_records(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].doi, "10.48550/arXiv.2403.12345");
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_revalidate_keeps_records_of_other_shards() {
+        use crate::index::{save_sharded_index, shard_of, DoiIndex};
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let index_dir = dir.path().join("index");
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/found");
+        save_sharded_index(&index, index_dir.to_str().unwrap(), 8).unwrap();
+        let loaded = shard_of("10.1234/found", 8);
+        let other = (1..100)
+            .map(|i| format!("10.{}/x", 5000 + i))
+            .find(|doi| shard_of(doi, 8) != loaded)
+            .unwrap();
+
+        let failed = dir.path().join("failed.jsonl");
+        let valid = dir.path().join("valid.jsonl");
+        let mut other_record = record(&other);
+        other_record.validation_status = Some(crate::common::ValidationStatus::PrefixUnknown);
+        let lines: Vec<String> = [record("10.1234/found"), other_record.clone()]
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect();
+        std::fs::write(&failed, lines.join("\n") + "\n").unwrap();
+        std::fs::write(&valid, "").unwrap();
+
+        let args = RevalidateArgs::parse_from([
+            "revalidate",
+            "--input",
+            failed.to_str().unwrap(),
+            "--valid",
+            valid.to_str().unwrap(),
+            "--crossref-index",
+            index_dir.to_str().unwrap(),
+            "--index-shard-ids",
+            &loaded.to_string(),
+            "--source",
+            "crossref",
+        ]);
+        run_revalidate(args).await.unwrap();
+
+        let valid = read_citation_records(valid.to_str().unwrap()).unwrap();
+        assert_eq!(valid.len(), 1);
+        assert_eq!(valid[0].doi, "10.1234/found");
+        let still_failed = read_citation_records(failed.to_str().unwrap()).unwrap();
+        assert_eq!(still_failed.len(), 1);
+        assert_eq!(still_failed[0].doi, other);
+        assert_eq!(
+            still_failed[0].validation_status,
+            other_record.validation_status
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_revalidate_reads_gzip_outputs_of_validate() {
+        use crate::cli::ValidateArgs;
+        use crate::commands::validate::run_validate;
+        use crate::index::{save_index_to_parquet, DoiIndex};
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let input: Vec<String> = ["10.1234/found", "10.1234/later"]
+            .iter()
+            .map(|doi| serde_json::to_string(&record(doi)).unwrap())
+            .collect();
+        std::fs::write(path("input.jsonl"), input.join("\n") + "\n").unwrap();
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/found");
+        save_index_to_parquet(&index, &path("index.parquet")).unwrap();
+
+        run_validate(ValidateArgs::parse_from([
+            "validate",
+            "--input",
+            &path("input.jsonl"),
+            "--source",
+            "crossref",
+            "--crossref-index",
+            &path("index.parquet"),
+            "--output-valid",
+            &path("valid.jsonl.gz"),
+            "--output-failed",
+            &path("failed.jsonl.gz"),
+        ]))
+        .await
+        .unwrap();
+
+        // The DOI missing from the first index is in the second
+        index.insert("10.1234/later");
+        save_index_to_parquet(&index, &path("index.parquet")).unwrap();
+        run_revalidate(RevalidateArgs::parse_from([
+            "revalidate",
+            "--input",
+            &path("failed.jsonl.gz"),
+            "--valid",
+            &path("valid.jsonl.gz"),
+            "--crossref-index",
+            &path("index.parquet"),
+            "--source",
+            "crossref",
+        ]))
+        .await
+        .unwrap();
+
+        let dois = |name: &str| -> Vec<String> {
+            read_citation_records(&path(name))
+                .unwrap()
+                .into_iter()
+                .map(|record| record.doi)
+                .collect()
+        };
+        assert_eq!(dois("valid.jsonl.gz"), ["10.1234/found", "10.1234/later"]);
+        assert!(dois("failed.jsonl.gz").is_empty());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_revalidate_rewrites_sharded_outputs() {
+        use crate::cli::ValidateArgs;
+        use crate::commands::validate::run_validate;
+        use crate::index::{save_index_to_parquet, DoiIndex};
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let input: Vec<String> = ["10.1234/a", "10.1234/b", "10.1234/c", "10.1234/d"]
+            .iter()
+            .map(|doi| serde_json::to_string(&record(doi)).unwrap())
+            .collect();
+        std::fs::write(path("input.jsonl"), input.join("\n") + "\n").unwrap();
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/a");
+        save_index_to_parquet(&index, &path("index.parquet")).unwrap();
+
+        run_validate(ValidateArgs::parse_from([
+            "validate",
+            "--input",
+            &path("input.jsonl"),
+            "--source",
+            "crossref",
+            "--crossref-index",
+            &path("index.parquet"),
+            "--output-valid",
+            &path("valid.jsonl"),
+            "--output-failed",
+            &path("failed.jsonl"),
+            "--shard-records",
+            "2",
+        ]))
+        .await
+        .unwrap();
+        assert!(!Path::new(&path("valid.jsonl")).exists());
+
+        index.insert("10.1234/c");
+        save_index_to_parquet(&index, &path("index.parquet")).unwrap();
+        let revalidate = |shard_records: Option<&str>| {
+            let mut argv = vec![
+                "revalidate".to_string(),
+                "--input".to_string(),
+                path("failed.jsonl"),
+                "--valid".to_string(),
+                path("valid.jsonl"),
+                "--crossref-index".to_string(),
+                path("index.parquet"),
+                "--source".to_string(),
+                "crossref".to_string(),
+            ];
+            if let Some(records) = shard_records {
+                argv.extend(["--shard-records".to_string(), records.to_string()]);
+            }
+            run_revalidate(RevalidateArgs::parse_from(argv))
+        };
+
+        // Unsharded, the new files would sit behind the old shard manifests
+        let error = revalidate(None).await.unwrap_err();
+        assert!(error.to_string().contains("--shard-records"));
+
+        revalidate(Some("1")).await.unwrap();
+        let dois = |name: &str| -> Vec<String> {
+            read_citation_records(&path(name))
+                .unwrap()
+                .into_iter()
+                .map(|record| record.doi)
+                .collect()
+        };
+        assert_eq!(dois("valid.jsonl"), ["10.1234/a", "10.1234/c"]);
+        assert_eq!(dois("failed.jsonl"), ["10.1234/b", "10.1234/d"]);
+        let manifest = ShardManifest::load(Path::new(&path("failed.jsonl")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(manifest.shards.len(), 2);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_read_citation_records_columnar() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["valid.parquet", "valid.arrow"] {
+            let output = dir.path().join(name);
+            let mut sink = crate::common::open_sink(&output).unwrap();
+            sink.write_record(&serde_json::to_value(record("10.5678/a")).unwrap())
+                .unwrap();
+            sink.finish().unwrap();
+
+            let records = read_citation_records(output.to_str().unwrap()).unwrap();
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].doi, "10.5678/a");
+            assert_eq!(records[0].cited_by, record("10.5678/a").cited_by);
+        }
+    }
+}
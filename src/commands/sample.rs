@@ -0,0 +1,28 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::cli::SampleArgs;
+use crate::common::setup_logging;
+use crate::streaming::{sample_records, SampleOptions};
+
+pub fn run_sample(args: SampleArgs) -> Result<()> {
+    setup_logging(&args.log_level)?;
+
+    for input in &args.input {
+        if !Path::new(input).exists() {
+            return Err(anyhow::anyhow!("Input file does not exist: {}", input));
+        }
+    }
+    if args.size == 0 {
+        return Err(anyhow::anyhow!("--size must be at least 1"));
+    }
+
+    let inputs: Vec<PathBuf> = args.input.iter().map(PathBuf::from).collect();
+    let options = SampleOptions {
+        size: args.size,
+        strata: args.stratify,
+        seed: args.seed,
+    };
+    sample_records(&inputs, Path::new(&args.output), &options)?;
+    Ok(())
+}
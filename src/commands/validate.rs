@@ -1,109 +1,568 @@
 use anyhow::Result;
 use log::info;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
 
 use crate::cli::{Source, ValidateArgs};
-use crate::common::setup_logging;
-use crate::index::{build_index_from_jsonl_gz, load_index_from_parquet, DoiIndex};
+use crate::common::{
+    open_sink, run_blocking, setup_logging, CancellationToken, CitationRecord, OutputOptions,
+    ShardLimit, ShardManifest,
+};
+use crate::extract::DoiConstraints;
+use crate::index::{
+    build_arxiv_index_from_metadata, build_index_from_jsonl_gz, build_prefix_index_from_jsonl_gz,
+    DoiIndex, IndexBackend, PublisherNames,
+};
+#[cfg(feature = "parquet")]
+use crate::index::{is_sharded_index, load_index_with_backend, load_sharded_index};
 use crate::validation::{
-    validate_citations, write_arxiv_validation_results_with_split,
-    write_validation_results_with_split,
+    annotate_publishers, link_published_dois, log_validation_stats, lookup_registration_agencies,
+    merge_two_pass_results, open_resolver, split_off_http_resolved, validate_citations,
+    write_arxiv_validation_results_with_split, write_http_resolved_results,
+    write_split_validation_results, write_validation_results_with_split, CitingWorks,
+    ConcurrencyBounds, HttpFallback, ValidationResults,
 };
 
-pub fn run_validate(args: ValidateArgs) -> Result<()> {
-    let rt = tokio::runtime::Runtime::new()?;
-    rt.block_on(run_validate_async(args))
-}
-
-pub async fn run_validate_async(args: ValidateArgs) -> Result<()> {
+pub async fn run_validate(args: ValidateArgs) -> Result<()> {
     setup_logging(&args.log_level)?;
 
     info!("Starting standalone validation");
     info!("Input: {}", args.input);
     info!("Source: {}", args.source);
 
-    if !Path::new(&args.input).exists() {
-        return Err(anyhow::anyhow!("Input file does not exist: {}", args.input));
-    }
+    check_validate_args(&args)?;
+
+    let resolver = open_resolver(args.http_resolver_file.as_deref())?;
+    let http_fallback = args.http_fallback.then(|| HttpFallback {
+        concurrency: args.concurrency,
+        timeout_secs: args.timeout,
+        checkpoint: args.http_checkpoint.as_ref().map(PathBuf::from),
+        adaptive: args.adaptive_concurrency.then_some(ConcurrencyBounds {
+            min: args.min_concurrency,
+            max: args.max_concurrency,
+        }),
+        crossref: true,
+        datacite: true,
+        bad_prefix_threshold: args.bad_prefix_threshold,
+        resolver,
+        client: args.http_client.config(),
+    });
 
-    // Load indexes based on source
-    let crossref_index: Option<DoiIndex> = if let Some(ref path) = args.crossref_index {
-        info!("Loading Crossref index from: {}", path);
-        Some(load_index_from_parquet(path)?)
+    // Run validation
+    let mut results = if args.two_pass {
+        validate_two_pass(&args, http_fallback.as_ref()).await?
     } else {
-        None
+        let (crossref_index, datacite_index) = run_blocking(|| {
+            load_validation_indexes(
+                args.crossref_index.as_deref(),
+                args.index_backend,
+                &args.index_shard_ids,
+                args.datacite_records.as_deref(),
+                args.datacite_prefix_only,
+                args.arxiv_metadata.as_deref(),
+            )
+        })?;
+        check_validation_sources(
+            args.source,
+            crossref_index.as_ref(),
+            datacite_index.as_ref(),
+            args.http_fallback,
+        )?;
+
+        validate_citations(
+            &args.input,
+            crossref_index.as_ref(),
+            datacite_index.as_ref(),
+            args.source,
+            http_fallback.as_ref(),
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await?
     };
+    log_validation_stats(
+        &results,
+        args.source,
+        args.datacite_prefix_only,
+        http_fallback.is_some(),
+    );
 
-    let datacite_index: Option<DoiIndex> = if let Some(ref path) = args.datacite_records {
-        info!("Building DataCite index from: {}", path);
-        Some(build_index_from_jsonl_gz(path, "id")?)
-    } else {
-        None
+    if args.registration_agency_lookup && !results.failed.is_empty() {
+        lookup_registration_agencies(
+            &mut results.failed,
+            args.concurrency,
+            args.timeout,
+            &args.http_client.config(),
+            &CancellationToken::new(),
+        )
+        .await?;
+    }
+
+    let http_resolved = run_blocking(|| write_validate_outputs(&args, &mut results))?;
+
+    info!("==================== VALIDATION COMPLETE ====================");
+    info!("Total records: {}", results.stats.total_records);
+    info!("Valid: {}", results.valid.len());
+    if let Some(ref http_resolved) = http_resolved {
+        info!("HTTP-resolved (separate output): {}", http_resolved.len());
+    }
+    info!("Failed: {}", results.failed.len());
+    info!("Output valid: {}", args.output_valid);
+    info!("Output failed: {}", args.output_failed);
+    info!("=============================================================");
+
+    Ok(())
+}
+
+/// Annotate the validation results and write every requested output
+///
+/// Returns the HTTP-resolved records when they went to their own output.
+fn write_validate_outputs(
+    args: &ValidateArgs,
+    results: &mut ValidationResults,
+) -> Result<Option<Vec<(CitationRecord, Source)>>> {
+    if args.link_published_dois {
+        link_published_dois(results, args.datacite_records.as_ref().unwrap())?;
+    }
+
+    if args.annotate_publishers {
+        let publishers = PublisherNames::load(args.publisher_names.as_deref())?;
+        annotate_publishers(results, &publishers);
+    }
+
+    let output_options = OutputOptions {
+        sync_every: args.output_sync_every,
+        shard: ShardLimit::from_size_or_records(args.output_shard_size, args.shard_records),
+        // HTTP outcomes can change between runs, so a rerun's records may not line up
+        restart: args.http_fallback,
+    };
+
+    // HTTP-resolved records go to their own output when one is given
+    let http_resolved = match args.output_http_resolved {
+        Some(ref path) => {
+            let http_resolved = split_off_http_resolved(results);
+            write_http_resolved_results(&http_resolved, path, args.source, &output_options)?;
+            Some(http_resolved)
+        }
+        None => None,
     };
 
-    // Validate required indexes
+    // Write results with provenance split, and per source in all mode
+    if args.source == Source::All && has_source_outputs(args) {
+        let (crossref_written, datacite_written) = write_split_validation_results(
+            results,
+            args.output_crossref.as_deref(),
+            args.output_datacite.as_deref(),
+            args.output_crossref_failed.as_deref(),
+            args.output_datacite_failed.as_deref(),
+            &output_options,
+        )?;
+        info!(
+            "Per-source outputs written: {} Crossref, {} DataCite",
+            crossref_written, datacite_written
+        );
+    }
     match args.source {
+        Source::Arxiv => {
+            write_arxiv_validation_results_with_split(
+                results,
+                &args.output_valid,
+                Some(&args.output_failed),
+                &output_options,
+            )?;
+        }
+        _ => {
+            write_validation_results_with_split(
+                &results.valid,
+                &results.failed,
+                &args.output_valid,
+                Some(&args.output_failed),
+                &output_options,
+            )?;
+        }
+    }
+
+    if let Some(ref path) = args.output_citing {
+        let mut citing = CitingWorks::default();
+        citing.add(
+            results.valid.iter().chain(http_resolved.iter().flatten()),
+            args.source,
+        );
+        citing.write(path, &output_options)?;
+    }
+
+    Ok(http_resolved)
+}
+
+/// Validate in all mode holding one index at a time (--two-pass)
+///
+/// The first pass looks every record up in the Crossref index. The records it does not
+/// hold are written to a temporary file, and the index is dropped, keeping only its
+/// prefixes for attribution. The second pass validates that file in all mode against
+/// the DataCite index, with HTTP fallback, so peak memory is that of the larger index
+/// rather than of both. Records found in Crossref come first in the valid output.
+async fn validate_two_pass(
+    args: &ValidateArgs,
+    http_fallback: Option<&HttpFallback>,
+) -> Result<ValidationResults> {
+    let cancel = CancellationToken::new();
+
+    let (crossref_index, _) = run_blocking(|| {
+        load_validation_indexes(
+            args.crossref_index.as_deref(),
+            args.index_backend,
+            &args.index_shard_ids,
+            None,
+            false,
+            None,
+        )
+    })?;
+    let crossref_index = crossref_index.expect("--two-pass requires --crossref-index");
+    info!("Pass 1: Crossref index lookup");
+    let mut crossref_pass = validate_citations(
+        &args.input,
+        Some(&crossref_index),
+        None,
+        Source::Crossref,
+        None,
+        DoiConstraints::default(),
+        &cancel,
+    )
+    .await?;
+    let crossref_prefixes = crossref_index.to_prefix_only();
+    drop(crossref_index);
+
+    let rest_path = RemoveOnDrop(std::env::temp_dir().join(format!(
+        "crossref-citation-extraction-two-pass-{}.jsonl",
+        Uuid::new_v4()
+    )));
+    let rest_records = run_blocking(|| -> Result<usize> {
+        let mut sink = open_sink(&rest_path.0)?;
+        for (record, _) in std::mem::take(&mut crossref_pass.failed) {
+            sink.write_record(&serde_json::to_value(record)?)?;
+        }
+        Ok(sink.finish()?)
+    })?;
+
+    let (_, datacite_index) = run_blocking(|| {
+        load_validation_indexes(
+            None,
+            args.index_backend,
+            &[],
+            args.datacite_records.as_deref(),
+            args.datacite_prefix_only,
+            None,
+        )
+    })?;
+    info!(
+        "Pass 2: validating {} records not in the Crossref index",
+        rest_records
+    );
+    let rest = validate_citations(
+        &rest_path.0,
+        Some(&crossref_prefixes),
+        datacite_index.as_ref(),
+        Source::All,
+        http_fallback,
+        DoiConstraints::default(),
+        &cancel,
+    )
+    .await?;
+
+    Ok(merge_two_pass_results(crossref_pass, rest))
+}
+
+/// File removed when dropped, on success and error paths alike
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Whether an output exists, as a file or as the shard manifest of a sharded one
+pub(crate) fn output_exists(path: &str) -> bool {
+    Path::new(path).exists() || ShardManifest::path_for(Path::new(path)).exists()
+}
+
+/// Reject option combinations the standalone validation cannot honour
+fn check_validate_args(args: &ValidateArgs) -> Result<()> {
+    if !output_exists(&args.input) {
+        return Err(anyhow::anyhow!("Input file does not exist: {}", args.input));
+    }
+    if args.arxiv_metadata.is_some() && args.source != Source::Arxiv {
+        return Err(anyhow::anyhow!("--arxiv-metadata requires --source arxiv"));
+    }
+    if args.link_published_dois && (args.source != Source::Arxiv || args.datacite_records.is_none())
+    {
+        return Err(anyhow::anyhow!(
+            "--link-published-dois requires --source arxiv and --datacite-records"
+        ));
+    }
+
+    if args.two_pass
+        && (args.source != Source::All
+            || args.crossref_index.is_none()
+            || args.datacite_records.is_none())
+    {
+        return Err(anyhow::anyhow!(
+            "--two-pass requires --source all, --crossref-index and --datacite-records"
+        ));
+    }
+
+    if args.output_http_resolved.is_some() && !args.http_fallback {
+        return Err(anyhow::anyhow!(
+            "--output-http-resolved requires --http-fallback"
+        ));
+    }
+
+    if has_source_outputs(args) && args.source != Source::All {
+        return Err(anyhow::anyhow!(
+            "--output-crossref, --output-datacite and their failed outputs require --source all"
+        ));
+    }
+    Ok(())
+}
+
+/// Whether any per-source output of all mode was requested
+fn has_source_outputs(args: &ValidateArgs) -> bool {
+    args.output_crossref.is_some()
+        || args.output_datacite.is_some()
+        || args.output_crossref_failed.is_some()
+        || args.output_datacite_failed.is_some()
+}
+
+/// Load the Crossref index and build the DataCite index used by standalone validation
+///
+/// Without DataCite records, the DataCite index holds the arXiv DOIs of `arxiv_metadata`.
+#[cfg_attr(not(feature = "parquet"), allow(unused_variables))]
+pub(crate) fn load_validation_indexes(
+    crossref_index: Option<&str>,
+    index_backend: IndexBackend,
+    index_shard_ids: &[usize],
+    datacite_records: Option<&str>,
+    datacite_prefix_only: bool,
+    arxiv_metadata: Option<&str>,
+) -> Result<(Option<DoiIndex>, Option<DoiIndex>)> {
+    let crossref = match crossref_index {
+        #[cfg(not(feature = "parquet"))]
+        Some(_) => {
+            return Err(crate::error::Error::requires_parquet("--crossref-index").into());
+        }
+        #[cfg(feature = "parquet")]
+        Some(path) if is_sharded_index(path) => {
+            Some(load_sharded_index(path, index_shard_ids, index_backend)?)
+        }
+        #[cfg(feature = "parquet")]
+        Some(_) if !index_shard_ids.is_empty() => {
+            return Err(anyhow::anyhow!(
+                "--index-shard-ids requires a sharded --crossref-index directory"
+            ));
+        }
+        #[cfg(feature = "parquet")]
+        Some(path) => {
+            info!("Loading Crossref index ({}) from: {}", index_backend, path);
+            Some(load_index_with_backend(path, index_backend)?)
+        }
+        None => None,
+    };
+
+    let datacite = match datacite_records {
+        Some(path) if datacite_prefix_only => {
+            info!("Building DataCite prefix index from: {}", path);
+            Some(build_prefix_index_from_jsonl_gz(path, "id")?)
+        }
+        Some(path) => {
+            info!("Building DataCite index from: {}", path);
+            Some(build_index_from_jsonl_gz(path, "id")?)
+        }
+        None => match arxiv_metadata {
+            Some(path) => Some(build_arxiv_index_from_metadata(path)?),
+            None => None,
+        },
+    };
+
+    Ok((crossref, datacite))
+}
+
+/// Ensure the source can be validated with the available indexes or HTTP fallback
+pub(crate) fn check_validation_sources(
+    source: Source,
+    crossref_index: Option<&DoiIndex>,
+    datacite_index: Option<&DoiIndex>,
+    http_fallback: bool,
+) -> Result<()> {
+    match source {
         Source::Crossref => {
-            if crossref_index.is_none() && !args.http_fallback {
+            if crossref_index.is_none() && !http_fallback {
                 return Err(anyhow::anyhow!(
                     "Crossref validation requires --crossref-index or --http-fallback"
                 ));
             }
         }
         Source::Datacite | Source::Arxiv => {
-            if datacite_index.is_none() && !args.http_fallback {
+            if datacite_index.is_none() && !http_fallback {
                 return Err(anyhow::anyhow!(
-                    "DataCite/arXiv validation requires --datacite-records or --http-fallback"
+                    "DataCite/arXiv validation requires --datacite-records, --arxiv-metadata (arXiv) \
+                     or --http-fallback"
                 ));
             }
         }
         Source::All => {
-            if crossref_index.is_none() && datacite_index.is_none() && !args.http_fallback {
+            if crossref_index.is_none() && datacite_index.is_none() && !http_fallback {
                 return Err(anyhow::anyhow!(
                     "Validation requires at least one index or --http-fallback"
                 ));
             }
         }
     }
+    Ok(())
+}
 
-    // Run validation
-    let results = validate_citations(
-        &args.input,
-        crossref_index.as_ref(),
-        datacite_index.as_ref(),
-        args.source,
-        args.http_fallback,
-        args.concurrency,
-        args.timeout,
-    )
-    .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
 
-    // Write results with provenance split
-    match args.source {
-        Source::Arxiv => {
-            write_arxiv_validation_results_with_split(
-                &results,
-                &args.output_valid,
-                Some(&args.output_failed),
-            )?;
-        }
-        _ => {
-            write_validation_results_with_split(
-                &results.valid,
-                &results.failed,
-                &args.output_valid,
-                Some(&args.output_failed),
-            )?;
-        }
+    fn parse(input: &str, extra: &[&str]) -> ValidateArgs {
+        let base = [
+            "validate",
+            "--input",
+            input,
+            "--output-valid",
+            "valid.jsonl",
+            "--output-failed",
+            "failed.jsonl",
+        ];
+        ValidateArgs::parse_from(base.iter().chain(extra))
     }
 
-    info!("==================== VALIDATION COMPLETE ====================");
-    info!("Total records: {}", results.stats.total_records);
-    info!("Valid: {}", results.valid.len());
-    info!("Failed: {}", results.failed.len());
-    info!("Output valid: {}", args.output_valid);
-    info!("Output failed: {}", args.output_failed);
-    info!("=============================================================");
+    #[test]
+    fn test_check_validate_args_source_outputs() {
+        let input = NamedTempFile::new().unwrap();
+        let input = input.path().to_str().unwrap();
 
-    Ok(())
+        let args = parse(
+            input,
+            &["--source", "all", "--output-crossref", "crossref.jsonl"],
+        );
+        assert!(check_validate_args(&args).is_ok());
+
+        let args = parse(
+            input,
+            &[
+                "--source",
+                "crossref",
+                "--output-datacite-failed",
+                "f.jsonl",
+            ],
+        );
+        let err = check_validate_args(&args).unwrap_err();
+        assert!(err.to_string().contains("require --source all"));
+
+        let args = parse("missing.jsonl", &["--source", "all"]);
+        assert!(check_validate_args(&args).is_err());
+    }
+
+    #[test]
+    fn test_check_validate_args_two_pass() {
+        let input = NamedTempFile::new().unwrap();
+        let input = input.path().to_str().unwrap();
+        let indexes = [
+            "--crossref-index",
+            "crossref.parquet",
+            "--datacite-records",
+            "datacite.jsonl.gz",
+            "--two-pass",
+        ];
+
+        let args = parse(input, &[&["--source", "all"][..], &indexes].concat());
+        assert!(check_validate_args(&args).is_ok());
+
+        let args = parse(input, &[&["--source", "crossref"][..], &indexes].concat());
+        let err = check_validate_args(&args).unwrap_err();
+        assert!(err.to_string().contains("--two-pass requires"));
+
+        let args = parse(input, &["--source", "all", "--two-pass"]);
+        assert!(check_validate_args(&args).is_err());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_two_pass_matches_single_pass() {
+        use crate::index::save_index_to_parquet;
+        use serde_json::{json, Value};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+
+        let mut crossref_index = DoiIndex::new();
+        crossref_index.insert("10.1234/crossref");
+        crossref_index.insert("10.1234/dash-1");
+        save_index_to_parquet(&crossref_index, &path("crossref.parquet")).unwrap();
+        std::fs::write(path("datacite.jsonl"), "{\"id\": \"10.5678/datacite\"}\n").unwrap();
+
+        let input: Vec<String> = [
+            "10.1234/crossref",
+            "10.5678/datacite",
+            "10.1234/dash\u{2010}1",
+            "10.1234/missing",
+            "10.5678/missing",
+            "10.9999/unknown",
+        ]
+        .iter()
+        .map(|doi| {
+            json!({"doi": doi, "reference_count": 1, "citation_count": 1, "cited_by": []})
+                .to_string()
+        })
+        .collect();
+        std::fs::write(path("input.jsonl"), input.join("\n") + "\n").unwrap();
+
+        let run = |prefix: &str, two_pass: bool| {
+            let (valid, failed) = (
+                path(&format!("{}_valid.jsonl", prefix)),
+                path(&format!("{}_failed.jsonl", prefix)),
+            );
+            let mut argv = vec![
+                "validate".to_string(),
+                "--input".to_string(),
+                path("input.jsonl"),
+                "--source".to_string(),
+                "all".to_string(),
+                "--crossref-index".to_string(),
+                path("crossref.parquet"),
+                "--datacite-records".to_string(),
+                path("datacite.jsonl"),
+                "--output-valid".to_string(),
+                valid.clone(),
+                "--output-failed".to_string(),
+                failed.clone(),
+            ];
+            if two_pass {
+                argv.push("--two-pass".to_string());
+            }
+            (ValidateArgs::parse_from(argv), valid, failed)
+        };
+        let records = |path: &str| {
+            let mut records: Vec<Value> = std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            records.sort_by_key(|record| record["doi"].as_str().unwrap().to_string());
+            records
+        };
+
+        let (args, single_valid, single_failed) = run("single", false);
+        run_validate(args).await.unwrap();
+        let (args, valid, failed) = run("two_pass", true);
+        run_validate(args).await.unwrap();
+
+        assert_eq!(records(&valid).len(), 3);
+        assert_eq!(records(&valid), records(&single_valid));
+        assert_eq!(records(&failed).len(), 3);
+        assert_eq!(records(&failed), records(&single_failed));
+    }
 }
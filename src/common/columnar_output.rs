@@ -0,0 +1,755 @@
+use crate::error::{Context, Error, Result};
+use polars::chunked_array::builder::get_list_builder;
+use polars::prelude::*;
+use polars_arrow::io::ipc::write::{StreamWriter, WriteOptions};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::output::OutputSink;
+
+/// Records per Parquet row group or Arrow record batch written by a columnar sink
+const COLUMNAR_BATCH_SIZE: usize = 100_000;
+
+/// Appended to an output path to name the file its records are spilled to
+const SPILL_SUFFIX: &str = ".records.tmp";
+
+/// Parquet file with one column per top-level field
+///
+/// Integer, string and boolean fields become typed columns; nested values (such as
+/// `cited_by`) are stored as JSON strings. A field's type depends on every record, so
+/// records are spilled to a JSON Lines file next to the output while their field types
+/// are collected, and `finish` reads them back in row groups of
+/// [`COLUMNAR_BATCH_SIZE`] records, so only one row group is held in memory at a time.
+pub struct ParquetSink {
+    path: PathBuf,
+    spill: RecordSpill,
+    batch_size: usize,
+}
+
+impl ParquetSink {
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            spill: RecordSpill::create(path, false)?,
+            batch_size: COLUMNAR_BATCH_SIZE,
+        })
+    }
+
+    #[cfg(test)]
+    fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Read the spilled records back and write them in row groups
+    fn write_row_groups(&mut self) -> Result<()> {
+        let file = File::create(&self.path)
+            .with_context(|| format!("Failed to create: {:?}", self.path))?;
+        let writer = ParquetWriter::new(file).with_compression(ParquetCompression::Zstd(None));
+        if self.spill.fields.is_empty() {
+            let mut df = DataFrame::new(vec![]).context("Failed to build output DataFrame")?;
+            writer
+                .finish(&mut df)
+                .with_context(|| format!("Failed to write: {:?}", self.path))?;
+            return Ok(());
+        }
+
+        let mut writer = writer
+            .batched(&fields_schema(&self.spill.fields))
+            .with_context(|| format!("Failed to write: {:?}", self.path))?;
+        self.spill.for_each_batch(self.batch_size, |df| {
+            writer
+                .write_batch(df)
+                .with_context(|| format!("Failed to write: {:?}", self.path))
+        })?;
+        writer
+            .finish()
+            .with_context(|| format!("Failed to write: {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+impl OutputSink for ParquetSink {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        self.spill.push(record)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<usize> {
+        self.write_row_groups()?;
+        Ok(self.spill.records)
+    }
+}
+
+/// Records spilled to a JSON Lines file next to an output while their field kinds are
+/// collected, so a columnar file can be typed by every record without holding them
+struct RecordSpill {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    fields: Vec<(String, ColumnKind)>,
+    /// Arrays of objects become list-of-struct columns rather than JSON strings
+    nested: bool,
+    records: usize,
+}
+
+impl RecordSpill {
+    fn create(output: &Path, nested: bool) -> Result<Self> {
+        // Fail early on an unwritable path rather than after spilling everything
+        File::create(output).with_context(|| format!("Failed to create: {:?}", output))?;
+        let mut path = output.as_os_str().to_owned();
+        path.push(SPILL_SUFFIX);
+        let path = PathBuf::from(path);
+        let file = File::create(&path).with_context(|| format!("Failed to create: {:?}", path))?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+            fields: Vec::new(),
+            nested,
+            records: 0,
+        })
+    }
+
+    fn push(&mut self, record: &Value) -> Result<()> {
+        observe_fields(&mut self.fields, record, self.nested);
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Read the records back as DataFrames of up to `batch_size` rows, typed by the
+    /// fields of every record
+    fn for_each_batch(
+        &mut self,
+        batch_size: usize,
+        mut write: impl FnMut(&DataFrame) -> Result<()>,
+    ) -> Result<()> {
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write: {:?}", self.path))?;
+        let file =
+            File::open(&self.path).with_context(|| format!("Failed to open: {:?}", self.path))?;
+        let mut batch = Vec::with_capacity(batch_size.min(self.records));
+        for line in BufReader::new(file).lines() {
+            batch.push(serde_json::from_str::<Value>(&line?)?);
+            if batch.len() >= batch_size {
+                write(&records_to_dataframe(&batch, &self.fields)?)?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            write(&records_to_dataframe(&batch, &self.fields)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RecordSpill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Type of a column, widened as records are seen
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ColumnKind {
+    /// Only nulls so far
+    Null,
+    UInt,
+    Int,
+    Bool,
+    /// Strings, and anything else written as JSON text
+    String,
+    /// Arrays of objects, as a list of structs with these fields
+    Records(Vec<(String, ColumnKind)>),
+}
+
+impl ColumnKind {
+    /// Kind of a value; with `nested`, an array of objects is `Records` and anything
+    /// nested deeper is JSON text
+    fn of(value: &Value, nested: bool) -> Self {
+        match value {
+            Value::Null => ColumnKind::Null,
+            Value::Bool(_) => ColumnKind::Bool,
+            v if v.is_u64() => ColumnKind::UInt,
+            v if v.is_i64() => ColumnKind::Int,
+            Value::Array(items) if nested && items.iter().all(Value::is_object) => {
+                let mut fields = Vec::new();
+                for item in items {
+                    observe_fields(&mut fields, item, false);
+                }
+                ColumnKind::Records(fields)
+            }
+            _ => ColumnKind::String,
+        }
+    }
+
+    /// The narrowest kind holding values of both
+    fn widen(&self, other: &Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a.clone(),
+            (ColumnKind::Null, kind) | (kind, ColumnKind::Null) => kind.clone(),
+            (ColumnKind::UInt, ColumnKind::Int) | (ColumnKind::Int, ColumnKind::UInt) => {
+                ColumnKind::Int
+            }
+            (ColumnKind::Records(seen), ColumnKind::Records(other)) => {
+                let mut fields = seen.clone();
+                for (name, kind) in other {
+                    observe_field(&mut fields, name, kind);
+                }
+                ColumnKind::Records(fields)
+            }
+            _ => ColumnKind::String,
+        }
+    }
+
+    /// Whether a column of this kind can store values of `kind` as they are
+    fn holds(&self, kind: &Self) -> bool {
+        match (self, kind) {
+            (_, ColumnKind::Null) => true,
+            (column, _) if column.dtype() == DataType::String => true,
+            (ColumnKind::Int, ColumnKind::UInt) => true,
+            (ColumnKind::Records(fields), ColumnKind::Records(other)) => {
+                other.iter().all(|(name, kind)| {
+                    fields
+                        .iter()
+                        .any(|(field, column)| field == name && column.holds(kind))
+                })
+            }
+            (column, kind) => column == kind,
+        }
+    }
+
+    /// Column type; a column of nulls only, or of arrays of empty objects, is a string
+    /// column
+    fn dtype(&self) -> DataType {
+        match self {
+            ColumnKind::UInt => DataType::UInt64,
+            ColumnKind::Int => DataType::Int64,
+            ColumnKind::Bool => DataType::Boolean,
+            ColumnKind::Records(fields) if !fields.is_empty() => {
+                DataType::List(Box::new(DataType::Struct(
+                    fields
+                        .iter()
+                        .map(|(name, kind)| Field::new(name.into(), kind.dtype()))
+                        .collect(),
+                )))
+            }
+            ColumnKind::Null | ColumnKind::String | ColumnKind::Records(_) => DataType::String,
+        }
+    }
+}
+
+/// Widen the kinds of `fields` by a record's values, adding its new fields at the end
+fn observe_fields(fields: &mut Vec<(String, ColumnKind)>, record: &Value, nested: bool) {
+    let Value::Object(map) = record else {
+        return;
+    };
+    for (key, value) in map {
+        observe_field(fields, key, &ColumnKind::of(value, nested));
+    }
+}
+
+fn observe_field(fields: &mut Vec<(String, ColumnKind)>, name: &str, kind: &ColumnKind) {
+    match fields.iter_mut().find(|(field, _)| field == name) {
+        Some((_, seen)) => *seen = seen.widen(kind),
+        None => fields.push((name.to_string(), kind.clone())),
+    }
+}
+
+/// Schema with one column per field, typed by its kind
+fn fields_schema(fields: &[(String, ColumnKind)]) -> Schema {
+    Schema::from_iter(
+        fields
+            .iter()
+            .map(|(name, kind)| Field::new(name.into(), kind.dtype())),
+    )
+}
+
+/// DataFrame of the records with one column per field, typed by its kind
+fn records_to_dataframe(records: &[Value], fields: &[(String, ColumnKind)]) -> Result<DataFrame> {
+    let records: Vec<&Value> = records.iter().collect();
+    DataFrame::new(field_columns(&records, fields)?).context("Failed to build output DataFrame")
+}
+
+fn field_columns(records: &[&Value], fields: &[(String, ColumnKind)]) -> Result<Vec<Column>> {
+    fields
+        .iter()
+        .map(|(field, kind)| {
+            let values: Vec<&Value> = records
+                .iter()
+                .map(|record| record.get(field).unwrap_or(&Value::Null))
+                .collect();
+            json_column(field, kind, &values)
+        })
+        .collect()
+}
+
+/// Fields of the records with their kinds, in first-seen order
+fn record_fields(records: &[Value], nested: bool) -> Vec<(String, ColumnKind)> {
+    let mut fields = Vec::new();
+    for record in records {
+        observe_fields(&mut fields, record, nested);
+    }
+    fields
+}
+
+/// Build a column of the given kind from JSON values
+fn json_column(name: &str, kind: &ColumnKind, values: &[&Value]) -> Result<Column> {
+    let column = match kind {
+        ColumnKind::UInt => {
+            let data: Vec<Option<u64>> = values.iter().map(|v| v.as_u64()).collect();
+            Column::new(name.into(), data)
+        }
+        ColumnKind::Int => {
+            let data: Vec<Option<i64>> = values.iter().map(|v| v.as_i64()).collect();
+            Column::new(name.into(), data)
+        }
+        ColumnKind::Bool => {
+            let data: Vec<Option<bool>> = values.iter().map(|v| v.as_bool()).collect();
+            Column::new(name.into(), data)
+        }
+        ColumnKind::Records(fields) if !fields.is_empty() => records_column(name, fields, values)?,
+        ColumnKind::Null | ColumnKind::String | ColumnKind::Records(_) => {
+            let data: Vec<Option<String>> = values
+                .iter()
+                .map(|v| match v {
+                    Value::Null => None,
+                    Value::String(s) => Some(s.clone()),
+                    other => Some(other.to_string()),
+                })
+                .collect();
+            Column::new(name.into(), data)
+        }
+    };
+    Ok(column)
+}
+
+/// List-of-struct column from arrays of objects, with a struct field per object field
+fn records_column(
+    name: &str,
+    fields: &[(String, ColumnKind)],
+    values: &[&Value],
+) -> Result<Column> {
+    let items: Vec<&Value> = values
+        .iter()
+        .filter_map(|v| v.as_array())
+        .flatten()
+        .collect();
+    let structs =
+        StructChunked::from_columns(name.into(), items.len(), &field_columns(&items, fields)?)
+            .context("Failed to build output DataFrame")?
+            .into_series();
+
+    let mut lists = get_list_builder(structs.dtype(), items.len(), values.len(), name.into());
+    let mut offset = 0;
+    for value in values {
+        match value.as_array() {
+            Some(array) => {
+                lists
+                    .append_series(&structs.slice(offset as i64, array.len()))
+                    .context("Failed to build output DataFrame")?;
+                offset += array.len();
+            }
+            None => lists.append_null(),
+        }
+    }
+    Ok(lists.finish().into_column())
+}
+
+/// Arrow IPC file (Feather v2) or stream with one column per top-level field
+///
+/// Typed like a `ParquetSink`, except that arrays of objects (such as `cited_by`) are
+/// list-of-struct columns. Written uncompressed, so pyarrow, polars or R arrow can
+/// memory-map an IPC file instead of decoding it. A file is typed by every record, so
+/// its records are spilled as for Parquet and written in record batches at `finish`. A
+/// stream writes a record batch every [`COLUMNAR_BATCH_SIZE`] records, typed by the
+/// first batch; a later record whose fields do not fit that schema is an error.
+pub struct ArrowIpcSink {
+    path: PathBuf,
+    batch_size: usize,
+    format: ArrowIpcFormat,
+}
+
+enum ArrowIpcFormat {
+    File(RecordSpill),
+    Stream(ArrowStream),
+}
+
+/// Arrow IPC stream written a record batch at a time
+struct ArrowStream {
+    /// Unbuffered, so each batch reaches readers when it is written; batches are large
+    writer: StreamWriter<File>,
+    /// Set by the first batch, when the stream's schema is written
+    fields: Option<Vec<(String, ColumnKind)>>,
+    pending: Vec<Value>,
+    records: usize,
+}
+
+impl ArrowIpcSink {
+    /// Arrow IPC file, which readers can memory-map
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            batch_size: COLUMNAR_BATCH_SIZE,
+            format: ArrowIpcFormat::File(RecordSpill::create(path, true)?),
+        })
+    }
+
+    /// Arrow IPC stream, for readers consuming record batches as they arrive
+    pub fn create_stream(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create: {:?}", path))?;
+        let stream = ArrowStream {
+            writer: StreamWriter::new(file, WriteOptions { compression: None }),
+            fields: None,
+            pending: Vec::new(),
+            records: 0,
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            batch_size: COLUMNAR_BATCH_SIZE,
+            format: ArrowIpcFormat::Stream(stream),
+        })
+    }
+
+    #[cfg(test)]
+    fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+/// Write the spilled records of an IPC file in record batches
+fn write_ipc_file(path: &Path, spill: &mut RecordSpill, batch_size: usize) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create: {:?}", path))?;
+    let mut writer = IpcWriter::new(file)
+        .batched(&fields_schema(&spill.fields))
+        .with_context(|| format!("Failed to write: {:?}", path))?;
+    spill.for_each_batch(batch_size, |df| {
+        writer
+            .write_batch(df)
+            .with_context(|| format!("Failed to write: {:?}", path))
+    })?;
+    writer
+        .finish()
+        .with_context(|| format!("Failed to write: {:?}", path))?;
+    Ok(())
+}
+
+impl ArrowStream {
+    /// Write the pending records as a record batch, starting the stream with their
+    /// schema if this is the first
+    fn write_pending(&mut self, path: &Path) -> Result<()> {
+        let fields = match &self.fields {
+            Some(fields) => {
+                let first = self.records - self.pending.len();
+                for (row, record) in self.pending.iter().enumerate() {
+                    check_stream_fields(fields, record).map_err(|field| {
+                        Error::invalid(format!(
+                            "Cannot write {:?}: field {:?} of record {} does not fit the \
+                             Arrow stream schema set by the first batch; write .arrow or \
+                             .parquet, which are typed by every record",
+                            path,
+                            field,
+                            first + row + 1
+                        ))
+                    })?;
+                }
+                fields
+            }
+            None => {
+                let fields = record_fields(&self.pending, true);
+                let schema = fields_schema(&fields).to_arrow(CompatLevel::newest());
+                self.writer
+                    .start(&schema, None)
+                    .with_context(|| format!("Failed to write: {:?}", path))?;
+                self.fields.insert(fields)
+            }
+        };
+
+        let df = records_to_dataframe(&self.pending, fields)?;
+        for batch in df.iter_chunks(CompatLevel::newest(), true) {
+            self.writer
+                .write(&batch, None)
+                .with_context(|| format!("Failed to write: {:?}", path))?;
+        }
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// Check a record's fields against a stream's schema, returning the first that does
+/// not fit
+fn check_stream_fields(
+    fields: &[(String, ColumnKind)],
+    record: &Value,
+) -> std::result::Result<(), String> {
+    let Value::Object(map) = record else {
+        return Ok(());
+    };
+    for (key, value) in map {
+        let kind = ColumnKind::of(value, true);
+        let fits = fields
+            .iter()
+            .any(|(name, column)| name == key && column.holds(&kind));
+        if !fits {
+            return Err(key.clone());
+        }
+    }
+    Ok(())
+}
+
+impl OutputSink for ArrowIpcSink {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        match &mut self.format {
+            ArrowIpcFormat::File(spill) => spill.push(record),
+            ArrowIpcFormat::Stream(stream) => {
+                stream.pending.push(record.clone());
+                stream.records += 1;
+                if stream.pending.len() >= self.batch_size {
+                    stream.write_pending(&self.path)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Result<usize> {
+        let Self {
+            path,
+            batch_size,
+            format,
+        } = *self;
+        match format {
+            ArrowIpcFormat::File(mut spill) => {
+                write_ipc_file(&path, &mut spill, batch_size)?;
+                Ok(spill.records)
+            }
+            ArrowIpcFormat::Stream(mut stream) => {
+                // An empty stream still starts with its (empty) schema
+                if !stream.pending.is_empty() || stream.fields.is_none() {
+                    stream.write_pending(&path)?;
+                }
+                stream
+                    .writer
+                    .finish()
+                    .with_context(|| format!("Failed to write: {:?}", path))?;
+                Ok(stream.records)
+            }
+        }
+    }
+}
+
+/// Read back the records a `ParquetSink` or `ArrowIpcSink` wrote, as JSON objects
+///
+/// The format follows the extension, as for the sinks. String cells holding a JSON
+/// array or object, which is how the sinks store nested values other than Arrow
+/// list-of-struct columns, are parsed back, and null cells are left out of their
+/// record, as are null struct fields.
+pub fn read_columnar_records(path: &Path) -> Result<Vec<Value>> {
+    let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+    let df = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("parquet") => ParquetReader::new(file).finish(),
+        Some("arrows") => IpcStreamReader::new(file).finish(),
+        _ => IpcReader::new(file).finish(),
+    }
+    .with_context(|| format!("Failed to read: {:?}", path))?;
+
+    let columns = df.get_columns();
+    (0..df.height())
+        .map(|row| {
+            let mut record = serde_json::Map::with_capacity(columns.len());
+            for column in columns {
+                let value = json_value(column.get(row)?);
+                if !value.is_null() {
+                    record.insert(column.name().to_string(), value);
+                }
+            }
+            Ok(Value::Object(record))
+        })
+        .collect()
+}
+
+/// JSON value of a cell written by `json_column`
+fn json_value(cell: AnyValue) -> Value {
+    let string = |s: &str| match s.as_bytes().first() {
+        Some(b'[' | b'{') => serde_json::from_str(s).unwrap_or_else(|_| Value::from(s)),
+        _ => Value::from(s),
+    };
+    match cell {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(b) => Value::from(b),
+        AnyValue::UInt64(n) => Value::from(n),
+        AnyValue::Int64(n) => Value::from(n),
+        AnyValue::String(s) => string(s),
+        AnyValue::StringOwned(s) => string(&s),
+        AnyValue::List(items) => Value::Array(items.iter().map(json_value).collect()),
+        AnyValue::StructOwned(payload) => {
+            let (values, fields) = *payload;
+            json_object(fields.iter().zip(values))
+        }
+        cell @ AnyValue::Struct(_, _, fields) => {
+            json_object(fields.iter().zip(cell._iter_struct_av()))
+        }
+        other => other
+            .extract::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|| Value::from(other.to_string())),
+    }
+}
+
+/// JSON object of a struct cell's non-null fields
+fn json_object<'a>(fields: impl Iterator<Item = (&'a Field, AnyValue<'a>)>) -> Value {
+    Value::Object(
+        fields
+            .map(|(field, value)| (field.name().to_string(), json_value(value)))
+            .filter(|(_, value)| !value.is_null())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::open_sink;
+
+    #[test]
+    fn test_parquet_sink_writes_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.parquet");
+        // A field missing from the first row group is typed by the later records
+        let records: Vec<Value> = (0..5u64)
+            .map(|i| match i {
+                4 => serde_json::json!({"doi": format!("10.1/{}", i), "http_error": "timeout"}),
+                _ => serde_json::json!({"doi": format!("10.1/{}", i), "citation_count": i}),
+            })
+            .collect();
+
+        let mut sink = Box::new(ParquetSink::create(&path).unwrap().with_batch_size(2));
+        for record in &records {
+            sink.write_record(record).unwrap();
+        }
+        assert_eq!(sink.finish().unwrap(), 5);
+
+        let mut reader = ParquetReader::new(File::open(&path).unwrap());
+        assert_eq!(reader.get_metadata().unwrap().row_groups.len(), 3);
+        let df = reader.finish().unwrap();
+        assert_eq!(
+            df.column("citation_count").unwrap().dtype(),
+            &DataType::UInt64
+        );
+        assert_eq!(read_columnar_records(&path).unwrap(), records);
+        assert!(!dir.path().join("out.parquet.records.tmp").exists());
+    }
+
+    #[test]
+    fn test_open_sink_columnar_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let records = [
+            serde_json::json!({"doi": "10.1/a", "citation_count": 2, "cited_by": [{"doi": "10.9/x"}]}),
+            serde_json::json!({"doi": "10.1/b", "citation_count": 1, "cited_by": []}),
+        ];
+        let write_all = |path: &Path| {
+            let mut sink = open_sink(path).unwrap();
+            for record in &records {
+                sink.write_record(record).unwrap();
+            }
+            assert_eq!(sink.finish().unwrap(), 2);
+        };
+
+        let parquet = dir.path().join("out.parquet");
+        write_all(&parquet);
+        let df = LazyFrame::scan_parquet(&parquet, Default::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        assert_eq!(df.height(), 2);
+        let counts = df.column("citation_count").unwrap().u64().unwrap();
+        assert_eq!(counts.get(0), Some(2));
+        let cited_by = df.column("cited_by").unwrap().str().unwrap();
+        assert_eq!(cited_by.get(0), Some(r#"[{"doi":"10.9/x"}]"#));
+
+        // Arrow has the same columns, with `cited_by` as a list of structs
+        let cited_by_dtype = DataType::List(Box::new(DataType::Struct(vec![Field::new(
+            "doi".into(),
+            DataType::String,
+        )])));
+        let flat = df.drop("cited_by").unwrap();
+        let check_ipc = |ipc: DataFrame| {
+            assert!(ipc.drop("cited_by").unwrap().equals_missing(&flat));
+            let cited_by = ipc.column("cited_by").unwrap();
+            assert_eq!(cited_by.dtype(), &cited_by_dtype);
+            let lengths: Vec<_> = cited_by.list().unwrap().lst_lengths().into_iter().collect();
+            assert_eq!(lengths, [Some(1), Some(0)]);
+        };
+
+        let arrow = dir.path().join("out.arrow");
+        write_all(&arrow);
+        check_ipc(
+            IpcReader::new(File::open(&arrow).unwrap())
+                .memory_mapped(Some(arrow.clone()))
+                .finish()
+                .unwrap(),
+        );
+        assert!(!dir.path().join("out.arrow.records.tmp").exists());
+
+        let stream = dir.path().join("out.arrows");
+        write_all(&stream);
+        check_ipc(
+            IpcStreamReader::new(File::open(&stream).unwrap())
+                .finish()
+                .unwrap(),
+        );
+
+        // Each format reads back as the records written
+        for path in [&parquet, &arrow, &stream] {
+            assert_eq!(read_columnar_records(path).unwrap(), records);
+        }
+    }
+
+    #[test]
+    fn test_arrow_stream_writes_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.arrows");
+        let records: Vec<Value> = (0..5u64)
+            .map(|i| {
+                serde_json::json!({
+                    "doi": format!("10.1/{}", i),
+                    "citation_count": i,
+                    "cited_by": [{"doi": format!("10.9/{}", i), "provenance": "mined"}],
+                })
+            })
+            .collect();
+
+        let mut sink = Box::new(
+            ArrowIpcSink::create_stream(&path)
+                .unwrap()
+                .with_batch_size(2),
+        );
+        for record in &records {
+            sink.write_record(record).unwrap();
+        }
+        // Two full batches are written before the stream is finished
+        let written = fs::metadata(&path).unwrap().len();
+        assert!(written > 0);
+        assert_eq!(sink.finish().unwrap(), 5);
+        assert!(fs::metadata(&path).unwrap().len() > written);
+        assert_eq!(read_columnar_records(&path).unwrap(), records);
+
+        // A field that does not fit the schema of the first batch is an error
+        let path = dir.path().join("mismatch.arrows");
+        let mut sink = ArrowIpcSink::create_stream(&path)
+            .unwrap()
+            .with_batch_size(2);
+        for record in &records[..2] {
+            sink.write_record(record).unwrap();
+        }
+        sink.write_record(&serde_json::json!({"doi": "10.1/x", "citation_count": "many"}))
+            .unwrap();
+        let err = sink
+            .write_record(&serde_json::json!({"doi": "10.1/y"}))
+            .unwrap_err();
+        assert!(err.to_string().contains("\"citation_count\" of record 3"));
+    }
+}
@@ -0,0 +1,184 @@
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// A date known to the year, month or day, as in Crossref `date-parts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialDate {
+    pub year: i32,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+
+impl PartialDate {
+    /// Compare at the precision both dates have
+    ///
+    /// A work issued in 2020 with no month is neither before nor after 2020-06, so a
+    /// range bound never excludes a work only because its date is less precise.
+    pub fn cmp_common(&self, other: &PartialDate) -> Ordering {
+        let month = match (self.month, other.month) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => Ordering::Equal,
+        };
+        let day = match (self.day, other.day) {
+            (Some(a), Some(b)) if month == Ordering::Equal => a.cmp(&b),
+            _ => Ordering::Equal,
+        };
+        self.year.cmp(&other.year).then(month).then(day)
+    }
+
+    /// Read a `{"date-parts": [[year, month, day]]}` object, as in an item's `issued`
+    ///
+    /// Month and day are optional; a missing or null year gives None.
+    pub fn from_date_parts(date: &Value) -> Option<Self> {
+        let parts = date.get("date-parts")?.as_array()?.first()?.as_array()?;
+        let part = |i: usize| parts.get(i).and_then(Value::as_i64);
+        let year = i32::try_from(part(0)?).ok()?;
+        let month = part(1)
+            .and_then(|m| u8::try_from(m).ok())
+            .filter(|m| (1..=12).contains(m));
+        let day = month
+            .and(part(2))
+            .and_then(|d| u8::try_from(d).ok())
+            .filter(|d| (1..=31).contains(d));
+        Some(Self { year, month, day })
+    }
+}
+
+impl FromStr for PartialDate {
+    type Err = String;
+
+    /// Parse `YYYY`, `YYYY-MM` or `YYYY-MM-DD`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid date: {}. Expected YYYY, YYYY-MM or YYYY-MM-DD", s);
+        let mut parts = s.trim().split('-');
+        let year = parts
+            .next()
+            .filter(|y| y.len() == 4)
+            .and_then(|y| y.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut next = |max: u8| -> Result<Option<u8>, String> {
+            match parts.next() {
+                None => Ok(None),
+                Some(p) => match p.parse::<u8>() {
+                    Ok(n) if p.len() == 2 && (1..=max).contains(&n) => Ok(Some(n)),
+                    _ => Err(invalid()),
+                },
+            }
+        };
+        let month = next(12)?;
+        let day = next(31)?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self { year, month, day })
+    }
+}
+
+impl std::fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{:02}", month)?;
+        }
+        if let Some(day) = self.day {
+            write!(f, "-{:02}", day)?;
+        }
+        Ok(())
+    }
+}
+
+/// Inclusive publication date range for citing works (--since / --until)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub since: Option<PartialDate>,
+    pub until: Option<PartialDate>,
+}
+
+impl DateRange {
+    /// Whether either bound is set
+    pub fn is_bounded(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    /// Whether a work's `issued` date falls in the range
+    ///
+    /// Works without an issued year are outside any bounded range.
+    pub fn contains_item(&self, item: &Value) -> bool {
+        if !self.is_bounded() {
+            return true;
+        }
+        match item.get("issued").and_then(PartialDate::from_date_parts) {
+            Some(issued) => self.contains(&issued),
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, date: &PartialDate) -> bool {
+        self.since
+            .is_none_or(|since| date.cmp_common(&since) != Ordering::Less)
+            && self
+                .until
+                .is_none_or(|until| date.cmp_common(&until) != Ordering::Greater)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn date(s: &str) -> PartialDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_partial_date() {
+        assert_eq!(
+            date("2020-06-15"),
+            PartialDate {
+                year: 2020,
+                month: Some(6),
+                day: Some(15)
+            }
+        );
+        assert_eq!(date("2020").month, None);
+        assert_eq!(date("2020-06").to_string(), "2020-06");
+        for invalid in [
+            "20",
+            "2020-6",
+            "2020-13",
+            "2020-06-32",
+            "2020-06-15-01",
+            "june",
+        ] {
+            assert!(invalid.parse::<PartialDate>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_from_date_parts() {
+        let parts = |v: Value| PartialDate::from_date_parts(&json!({ "date-parts": v }));
+        assert_eq!(parts(json!([[2019, 3, 7]])), Some(date("2019-03-07")));
+        assert_eq!(parts(json!([[2019]])), Some(date("2019")));
+        assert_eq!(parts(json!([[null]])), None);
+        assert_eq!(parts(json!([[2019, 13]])), Some(date("2019")));
+    }
+
+    #[test]
+    fn test_date_range() {
+        let range = DateRange {
+            since: Some(date("2020-06")),
+            until: Some(date("2021")),
+        };
+        let issued = |parts: Value| json!({ "issued": { "date-parts": [parts] } });
+        assert!(range.contains_item(&issued(json!([2020, 6, 1]))));
+        assert!(range.contains_item(&issued(json!([2021, 12, 31]))));
+        assert!(!range.contains_item(&issued(json!([2020, 5, 31]))));
+        assert!(!range.contains_item(&issued(json!([2022]))));
+        // Year-only dates are compared at year precision
+        assert!(range.contains_item(&issued(json!([2020]))));
+        assert!(!range.contains_item(&json!({ "DOI": "10.1234/undated" })));
+        assert!(DateRange::default().contains_item(&json!({})));
+    }
+}
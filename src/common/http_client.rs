@@ -0,0 +1,236 @@
+use crate::error::{Context, Error, Result};
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy, Url};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How HTTP clients identify themselves and connect, shared by the doi.org fallback,
+/// the registration agency lookup and the Crossref API commands
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpClientConfig {
+    /// User-Agent product (default: this crate's name and version)
+    pub user_agent: Option<String>,
+    /// Contact address added to the User-Agent, and sent to the Crossref API to route
+    /// requests to its polite pool
+    pub mailto: Option<String>,
+    /// Proxy for all requests (`http://` or `https://` URL); without it
+    /// the `HTTP_PROXY`/`HTTPS_PROXY` environment variables apply
+    pub proxy: Option<String>,
+    /// Hosts reached without `proxy`, comma-separated in the `NO_PROXY` format
+    /// (`doi.org, .internal, 10.0.0.0/8`); `*` disables every proxy, including
+    /// those from the environment
+    pub no_proxy: Option<String>,
+    /// PEM file of root certificates to trust besides the bundled ones, such as an
+    /// internal CA's
+    pub ca_bundle: Option<PathBuf>,
+    /// TCP keepalive interval, also how long idle pooled connections are kept
+    pub keepalive: Option<Duration>,
+    /// Idle connections kept open per host
+    pub max_idle_per_host: Option<usize>,
+    /// Requests in flight to any one host, on top of the caller's own concurrency
+    pub max_requests_per_host: Option<usize>,
+}
+
+impl HttpClientConfig {
+    /// User-Agent header: the product, then the contact address when given
+    pub fn user_agent(&self) -> String {
+        let mut user_agent = self
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+        if let Some(ref mailto) = self.mailto {
+            user_agent.push_str(&format!(" (mailto:{})", mailto));
+        }
+        user_agent
+    }
+
+    /// Client builder with these settings applied, for callers adding their own
+    /// (such as a redirect policy)
+    pub fn builder(&self) -> Result<ClientBuilder> {
+        let mut builder = Client::builder().user_agent(self.user_agent());
+        match (&self.proxy, self.no_proxy.as_deref().map(str::trim)) {
+            (_, Some("*")) => builder = builder.no_proxy(),
+            (Some(proxy), no_proxy) => {
+                let proxy = Proxy::all(proxy)
+                    .with_context(|| format!("Invalid proxy URL: {}", proxy))?
+                    .no_proxy(no_proxy.and_then(NoProxy::from_string));
+                builder = builder.proxy(proxy);
+            }
+            (None, Some(_)) => {
+                return Err(Error::invalid(
+                    "Hosts to reach without a proxy need a proxy; environment proxies \
+                     read NO_PROXY instead",
+                ))
+            }
+            (None, None) => {}
+        }
+        if let Some(ref path) = self.ca_bundle {
+            let pem = std::fs::read(path).with_context(|| format!("Failed to read: {:?}", path))?;
+            for certificate in Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid PEM certificates: {:?}", path))?
+            {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some(keepalive) = self.keepalive {
+            builder = builder
+                .tcp_keepalive(keepalive)
+                .pool_idle_timeout(keepalive);
+        }
+        if let Some(max_idle) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        Ok(builder)
+    }
+
+    /// Client with these settings
+    pub fn build(&self) -> Result<Client> {
+        Ok(self.builder()?.build()?)
+    }
+
+    /// `client` behind the per-host request limit of these settings
+    pub fn limit_per_host(&self, client: Client) -> HttpClient {
+        HttpClient {
+            client,
+            max_per_host: self.max_requests_per_host.map(|max| max.max(1)),
+            hosts: Arc::default(),
+        }
+    }
+}
+
+/// Client that holds at most `max_requests_per_host` requests in flight to each host
+///
+/// Callers take a slot for a URL before sending and keep it until the response has
+/// been read. Clones share their slots.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    max_per_host: Option<usize>,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl HttpClient {
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Wait for a request slot on the host of `url`
+    ///
+    /// Returns None without waiting when there is no per-host limit or the URL has no
+    /// host.
+    pub async fn slot(&self, url: &str) -> Option<OwnedSemaphorePermit> {
+        let max = self.max_per_host?;
+        let host = Url::parse(url).ok()?.host_str()?.to_lowercase();
+        let semaphore = {
+            let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+            Arc::clone(
+                hosts
+                    .entry(host)
+                    .or_insert_with(|| Arc::new(Semaphore::new(max))),
+            )
+        };
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_agent() {
+        let default = HttpClientConfig::default();
+        assert_eq!(
+            default.user_agent(),
+            format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        );
+
+        let config = HttpClientConfig {
+            user_agent: Some("citation-bot/2".to_string()),
+            mailto: Some("team@example.org".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.user_agent(),
+            "citation-bot/2 (mailto:team@example.org)"
+        );
+    }
+
+    #[test]
+    fn test_build_client() {
+        let config = HttpClientConfig {
+            proxy: Some("http://127.0.0.1:3128".to_string()),
+            keepalive: Some(Duration::from_secs(30)),
+            max_idle_per_host: Some(4),
+            ..Default::default()
+        };
+        assert!(config.build().is_ok());
+
+        let bad_proxy = HttpClientConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_proxy.build().is_err());
+
+        let bypassed = HttpClientConfig {
+            no_proxy: Some("doi.org, .internal".to_string()),
+            ..config.clone()
+        };
+        assert!(bypassed.build().is_ok());
+
+        let no_proxy_at_all = HttpClientConfig {
+            no_proxy: Some("*".to_string()),
+            ..Default::default()
+        };
+        assert!(no_proxy_at_all.build().is_ok());
+
+        let bypass_without_proxy = HttpClientConfig {
+            no_proxy: Some("doi.org".to_string()),
+            ..Default::default()
+        };
+        assert!(bypass_without_proxy.build().is_err());
+
+        let missing_ca = HttpClientConfig {
+            ca_bundle: Some(PathBuf::from("/nonexistent/ca.pem")),
+            ..Default::default()
+        };
+        assert!(missing_ca.build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_requests_per_host_are_limited() {
+        let config = HttpClientConfig {
+            max_requests_per_host: Some(2),
+            ..Default::default()
+        };
+        let client = config.limit_per_host(config.build().unwrap());
+
+        let first = client.slot("https://doi.org/10.1234/a").await;
+        let second = client.slot("https://DOI.org/ra/10.1234/b").await;
+        assert!(first.is_some() && second.is_some());
+        // A third request to the host waits until one of the two is done
+        let third = client.slot("https://doi.org/10.1234/c");
+        tokio::pin!(third);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), third.as_mut())
+                .await
+                .is_err()
+        );
+        // Other hosts have their own slots, and clones share them
+        assert!(client
+            .clone()
+            .slot("https://api.crossref.org/works")
+            .await
+            .is_some());
+        drop(first);
+        assert!(third.await.is_some());
+
+        let unlimited = HttpClientConfig::default().limit_per_host(Client::new());
+        let slots: Vec<_> =
+            futures::future::join_all((0..8).map(|_| unlimited.slot("https://doi.org/10.1234/a")))
+                .await;
+        assert!(slots.iter().all(Option::is_none));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::error::{Context, Error, Result};
+use log::info;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Exclusive lock held for the duration of a run (--lock-file)
+///
+/// Uses an advisory OS file lock, which is released when the process exits however it
+/// exits, so a crashed run never leaves a stale lock behind. The file holds the PID of
+/// the run holding it, for the error message of a run turned away.
+#[derive(Debug)]
+pub struct RunLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl RunLock {
+    /// Take the lock, failing at once if another process holds it
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file: {:?}", path))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                return Err(Error::invalid(format!(
+                    "Another run holds the lock file {:?} (pid {})",
+                    path,
+                    holder.trim()
+                )));
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(Error::from(e).context(format!("Failed to lock: {:?}", path)));
+            }
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+        info!("Acquired lock file: {:?}", path);
+
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // Empty the file so a later reader does not see an old PID; the OS drops the lock
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+        log::debug!("Released lock file: {:?}", self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_lock_is_exclusive() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pipeline.lock");
+
+        let lock = RunLock::acquire(&path).unwrap();
+        let pid = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(pid.trim(), std::process::id().to_string());
+
+        let err = RunLock::acquire(&path).unwrap_err();
+        assert!(err.to_string().contains("Another run holds the lock file"));
+
+        drop(lock);
+        assert!(RunLock::acquire(&path).is_ok());
+    }
+}
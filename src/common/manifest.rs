@@ -0,0 +1,325 @@
+use crate::error::{Context, Error, Result};
+use flate2::read::MultiGzDecoder;
+use log::{info, warn};
+#[cfg(feature = "parquet")]
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest written next to the outputs by default
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// One produced file (or, under `inputs`, one input file) in the manifest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the manifest's directory, or as given when outside it
+    pub path: String,
+    /// JSONL lines, CSV rows (without the header), or Parquet or Arrow IPC rows; None
+    /// for other formats
+    pub records: Option<usize>,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    pipeline_version: String,
+    arguments: Vec<String>,
+    /// Input files the outputs were produced from, when recorded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    inputs: Vec<ManifestEntry>,
+    files: Vec<ManifestEntry>,
+}
+
+/// How records are counted for a file, from its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordFormat {
+    Lines,
+    GzipLines,
+    Csv,
+    Parquet,
+    ArrowIpc,
+    ArrowStream,
+    Other,
+}
+
+impl RecordFormat {
+    fn of(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".jsonl.gz") {
+            RecordFormat::GzipLines
+        } else if name.ends_with(".jsonl") {
+            RecordFormat::Lines
+        } else if name.ends_with(".csv") {
+            RecordFormat::Csv
+        } else if name.ends_with(".parquet") {
+            RecordFormat::Parquet
+        } else if name.ends_with(".arrow") || name.ends_with(".feather") {
+            RecordFormat::ArrowIpc
+        } else if name.ends_with(".arrows") {
+            RecordFormat::ArrowStream
+        } else {
+            RecordFormat::Other
+        }
+    }
+}
+
+/// Write a manifest of `files` with their record counts, sizes and SHA-256 digests
+///
+/// Files that do not exist are left out. The pipeline version, the process's
+/// command-line arguments and `inputs` (see [`describe_inputs`]) are recorded
+/// alongside. Returns the number of files listed.
+pub fn write_manifest(
+    manifest_path: &Path,
+    files: &[PathBuf],
+    inputs: &[ManifestEntry],
+) -> Result<usize> {
+    let base = manifest_path.parent().unwrap_or(Path::new(""));
+    let mut entries = Vec::new();
+    for path in files {
+        if path.is_file() {
+            entries.push(describe_file(path, base)?);
+        }
+    }
+
+    let manifest = Manifest {
+        pipeline_version: env!("CARGO_PKG_VERSION").to_string(),
+        arguments: std::env::args().skip(1).collect(),
+        inputs: inputs.to_vec(),
+        files: entries,
+    };
+    let file = File::create(manifest_path)
+        .with_context(|| format!("Failed to create manifest: {:?}", manifest_path))?;
+    let mut writer = io::BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, &manifest)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    info!(
+        "Wrote manifest of {} files to: {:?}",
+        manifest.files.len(),
+        manifest_path
+    );
+    Ok(manifest.files.len())
+}
+
+/// Size and SHA-256 of input files, as recorded under `inputs` in the manifest
+///
+/// Paths are kept as given and records are not counted. Hashing reads each file in
+/// full, which for a snapshot takes minutes.
+pub fn describe_inputs(paths: &[&Path]) -> Result<Vec<ManifestEntry>> {
+    paths
+        .iter()
+        .map(|path| {
+            info!("Computing checksum of input: {:?}", path);
+            let (bytes, sha256, _) = hash_file(path)?;
+            Ok(ManifestEntry {
+                path: path.to_string_lossy().to_string(),
+                records: None,
+                bytes,
+                sha256,
+            })
+        })
+        .collect()
+}
+
+/// Whether the manifest at `manifest_path` records a completed run on these inputs
+///
+/// True when the manifest exists and has the same pipeline version, command-line
+/// arguments and input checksums, and every file it lists is still present with its
+/// recorded size. A manifest without inputs never matches.
+pub fn manifest_is_current(manifest_path: &Path, inputs: &[ManifestEntry]) -> Result<bool> {
+    let contents = match std::fs::read_to_string(manifest_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => {
+            return Err(Error::from(e).context(format!("Failed to read: {:?}", manifest_path)))
+        }
+    };
+    let manifest: Manifest = match serde_json::from_str(&contents) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            warn!("Ignoring unreadable manifest {:?}: {}", manifest_path, e);
+            return Ok(false);
+        }
+    };
+
+    let base = manifest_path.parent().unwrap_or(Path::new(""));
+    let files_present = manifest.files.iter().all(|entry| {
+        std::fs::metadata(base.join(&entry.path)).is_ok_and(|meta| meta.len() == entry.bytes)
+    });
+    Ok(!inputs.is_empty()
+        && manifest.inputs == inputs
+        && manifest.pipeline_version == env!("CARGO_PKG_VERSION")
+        && manifest.arguments == std::env::args().skip(1).collect::<Vec<_>>()
+        && files_present)
+}
+
+/// Size and SHA-256 of a file, with its newline count, in one pass
+fn hash_file(path: &Path) -> Result<(u64, String, usize)> {
+    let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut bytes = 0u64;
+    let mut newlines = 0usize;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .with_context(|| format!("Failed to read: {:?}", path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        bytes += n as u64;
+        newlines += buf[..n].iter().filter(|&&b| b == b'\n').count();
+    }
+    Ok((bytes, format!("{:x}", hasher.finalize()), newlines))
+}
+
+/// Hash and size a file in one pass, counting its records
+fn describe_file(path: &Path, base: &Path) -> Result<ManifestEntry> {
+    let (bytes, sha256, newlines) = hash_file(path)?;
+    let records = match RecordFormat::of(path) {
+        RecordFormat::Lines => Some(newlines),
+        RecordFormat::Csv => Some(newlines.saturating_sub(1)),
+        RecordFormat::GzipLines => Some(count_gzip_lines(path)?),
+        #[cfg(feature = "parquet")]
+        RecordFormat::Parquet => Some(
+            ParquetReader::new(File::open(path)?)
+                .num_rows()
+                .with_context(|| format!("Failed to read row count: {:?}", path))?,
+        ),
+        #[cfg(feature = "parquet")]
+        RecordFormat::ArrowIpc => Some(
+            IpcReader::new(File::open(path)?)
+                .memory_mapped(Some(path.to_path_buf()))
+                .finish()
+                .with_context(|| format!("Failed to read Arrow IPC file: {:?}", path))?
+                .height(),
+        ),
+        #[cfg(feature = "parquet")]
+        RecordFormat::ArrowStream => Some(
+            IpcStreamReader::new(File::open(path)?)
+                .finish()
+                .with_context(|| format!("Failed to read Arrow IPC stream: {:?}", path))?
+                .height(),
+        ),
+        // Counting columnar rows needs the Parquet reader
+        #[cfg(not(feature = "parquet"))]
+        RecordFormat::Parquet | RecordFormat::ArrowIpc | RecordFormat::ArrowStream => None,
+        RecordFormat::Other => None,
+    };
+
+    Ok(ManifestEntry {
+        path: path
+            .strip_prefix(base)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string(),
+        records,
+        bytes,
+        sha256,
+    })
+}
+
+fn count_gzip_lines(path: &Path) -> Result<usize> {
+    let mut decoder = MultiGzDecoder::new(BufReader::new(File::open(path)?));
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut newlines = 0;
+    loop {
+        let n = decoder
+            .read(&mut buf)
+            .with_context(|| format!("Failed to decompress: {:?}", path))?;
+        if n == 0 {
+            return Ok(newlines);
+        }
+        newlines += buf[..n].iter().filter(|&&b| b == b'\n').count();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use serde_json::Value;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_manifest() {
+        let dir = tempdir().unwrap();
+        let jsonl = dir.path().join("out.jsonl");
+        std::fs::write(&jsonl, "{\"a\":1}\n{\"a\":2}\n").unwrap();
+        let gz = dir.path().join("out_failed.jsonl.gz");
+        let mut encoder = GzEncoder::new(File::create(&gz).unwrap(), Compression::default());
+        encoder.write_all(b"{}\n{}\n{}\n").unwrap();
+        encoder.finish().unwrap();
+        let report = dir.path().join("top.md");
+        std::fs::write(&report, "# Top\n").unwrap();
+        let arrow = dir.path().join("out.arrow");
+        #[cfg(feature = "parquet")]
+        IpcWriter::new(File::create(&arrow).unwrap())
+            .finish(&mut df!("doi" => ["10.1/a", "10.1/b"]).unwrap())
+            .unwrap();
+        #[cfg(not(feature = "parquet"))]
+        std::fs::write(&arrow, b"ARROW1").unwrap();
+
+        let manifest_path = dir.path().join(MANIFEST_FILE);
+        let files = vec![jsonl, gz, report, arrow, dir.path().join("missing.jsonl")];
+        assert_eq!(write_manifest(&manifest_path, &files, &[]).unwrap(), 4);
+
+        let manifest: Value =
+            serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(manifest["pipeline_version"], env!("CARGO_PKG_VERSION"));
+        let files = manifest["files"].as_array().unwrap();
+        assert_eq!(files[0]["path"], "out.jsonl");
+        assert_eq!(files[0]["records"], 2);
+        assert_eq!(files[0]["bytes"], 16);
+        assert_eq!(
+            files[0]["sha256"],
+            format!("{:x}", Sha256::digest(b"{\"a\":1}\n{\"a\":2}\n"))
+        );
+        assert_eq!(files[1]["records"], 3);
+        assert!(files[2]["records"].is_null());
+        // Counting Arrow rows needs the Parquet feature's reader
+        let arrow_records = if cfg!(feature = "parquet") {
+            Value::from(2)
+        } else {
+            Value::Null
+        };
+        assert_eq!(files[3]["records"], arrow_records);
+        assert!(manifest.get("inputs").is_none());
+    }
+
+    #[test]
+    fn test_manifest_is_current() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("snapshot.tar.gz");
+        std::fs::write(&input, b"snapshot").unwrap();
+        let output = dir.path().join("out.jsonl");
+        std::fs::write(&output, "{}\n").unwrap();
+        let manifest_path = dir.path().join(MANIFEST_FILE);
+
+        let inputs = describe_inputs(&[input.as_path()]).unwrap();
+        assert_eq!(inputs[0].bytes, 8);
+        assert!(!manifest_is_current(&manifest_path, &inputs).unwrap());
+
+        write_manifest(&manifest_path, std::slice::from_ref(&output), &inputs).unwrap();
+        assert!(manifest_is_current(&manifest_path, &inputs).unwrap());
+        assert!(!manifest_is_current(&manifest_path, &[]).unwrap());
+
+        // A changed input or a missing output means the run must be repeated
+        std::fs::write(&input, b"snapshot2").unwrap();
+        let changed = describe_inputs(&[input.as_path()]).unwrap();
+        assert!(!manifest_is_current(&manifest_path, &changed).unwrap());
+        std::fs::remove_file(&output).unwrap();
+        assert!(!manifest_is_current(&manifest_path, &inputs).unwrap());
+    }
+}
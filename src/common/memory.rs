@@ -0,0 +1,152 @@
+/// Estimated in-memory size of one buffered partition row (citing DOI, ref JSON, match, ID)
+const ESTIMATED_ROW_BYTES: usize = 1024;
+/// Share of the budget the partition writer may hold in buffers (percent)
+const PARTITION_BUFFER_SHARE: u64 = 40;
+/// Share of the budget reserved for concurrent partition inversion (percent)
+const INVERT_SHARE: u64 = 50;
+/// Estimated peak memory for inverting a single partition
+const ESTIMATED_INVERT_PARTITION_BYTES: u64 = 512 * 1024 * 1024;
+/// Bounds for the derived Polars streaming chunk size (rows)
+const MIN_STREAMING_CHUNK_SIZE: usize = 10_000;
+const MAX_STREAMING_CHUNK_SIZE: usize = 500_000;
+const VALID_UNITS: &str = "B, KB, MB, GB, TB, KiB, MiB, GiB, TiB";
+
+/// Parse a human-readable byte size such as "32GB", "512MiB", "1.5G" or "1048576"
+///
+/// SI suffixes (KB, MB, GB, TB) are powers of 1000, IEC suffixes (KiB, MiB, GiB, TiB)
+/// and bare single letters (K, M, G, T) are powers of 1024. Case-insensitive.
+pub fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid byte size: {}", s))?;
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        other => {
+            return Err(format!(
+                "Invalid byte size unit '{}' in {}. Valid units: {}",
+                other, s, VALID_UNITS
+            ))
+        }
+    };
+
+    let bytes = (value * multiplier as f64).round();
+    if bytes < 1.0 {
+        return Err(format!("Byte size must be positive: {}", s));
+    }
+    Ok(bytes as u64)
+}
+
+/// Format a byte count with binary units for logging (e.g. "1.50 GiB")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+/// Memory budget used to derive buffer sizes and concurrency from a byte limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    total_bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(total_bytes: u64) -> Self {
+        Self { total_bytes }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Bytes the partition writer may keep buffered before forcing flushes
+    pub fn partition_buffer_bytes(&self) -> usize {
+        (self.total_bytes * PARTITION_BUFFER_SHARE / 100) as usize
+    }
+
+    /// Per-partition flush threshold (rows), sized so a handful of hot partitions fit the buffer
+    pub fn flush_threshold_rows(&self) -> usize {
+        (self.partition_buffer_bytes() / ESTIMATED_ROW_BYTES / 16).max(1_000)
+    }
+
+    /// Number of partitions to invert concurrently
+    pub fn invert_concurrency(&self) -> usize {
+        let by_memory =
+            (self.total_bytes * INVERT_SHARE / 100 / ESTIMATED_INVERT_PARTITION_BYTES) as usize;
+        by_memory.clamp(1, num_cpus::get().max(1))
+    }
+
+    /// Row count per Polars streaming chunk
+    pub fn streaming_chunk_size(&self) -> usize {
+        let per_thread =
+            self.total_bytes as usize / num_cpus::get().max(1) / ESTIMATED_ROW_BYTES / 64;
+        per_thread.clamp(MIN_STREAMING_CHUNK_SIZE, MAX_STREAMING_CHUNK_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_byte_size_units() {
+        assert_eq!(parse_byte_size("1024").unwrap(), 1024);
+        assert_eq!(parse_byte_size("10B").unwrap(), 10);
+        assert_eq!(parse_byte_size("32GB").unwrap(), 32_000_000_000);
+        assert_eq!(parse_byte_size("32gib").unwrap(), 32 * (1 << 30));
+        assert_eq!(parse_byte_size("512M").unwrap(), 512 * (1 << 20));
+        assert_eq!(parse_byte_size("1.5G").unwrap(), 3 * (1 << 29));
+        assert_eq!(parse_byte_size(" 2 TiB ").unwrap(), 2 * (1 << 40));
+    }
+
+    #[test]
+    fn test_parse_byte_size_invalid() {
+        assert!(parse_byte_size("").is_err());
+        assert!(parse_byte_size("GB").is_err());
+        assert!(parse_byte_size("12XB").is_err());
+        assert!(parse_byte_size("0").is_err());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(3 * (1 << 30)), "3.00 GiB");
+    }
+
+    #[test]
+    fn test_memory_budget_derivation() {
+        let small = MemoryBudget::new(1 << 30);
+        let large = MemoryBudget::new(64 * (1 << 30));
+
+        assert_eq!(small.partition_buffer_bytes(), (1usize << 30) * 40 / 100);
+        assert!(large.flush_threshold_rows() > small.flush_threshold_rows());
+        assert_eq!(small.invert_concurrency(), 1);
+        assert!(large.invert_concurrency() >= small.invert_concurrency());
+        assert!(small.streaming_chunk_size() >= MIN_STREAMING_CHUNK_SIZE);
+        assert!(large.streaming_chunk_size() <= MAX_STREAMING_CHUNK_SIZE);
+    }
+}
@@ -0,0 +1,214 @@
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::format_bytes;
+
+/// How often the monitor samples RSS; short enough to catch a fast-growing buffer
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+/// cgroup v1 reports "no limit" as a page-rounded i64::MAX; anything above this is none
+const CGROUP_V1_UNLIMITED: u64 = 1 << 60;
+
+/// Memory state shared between the monitor thread and the pipeline
+#[derive(Debug, Default)]
+pub struct MemoryPressure {
+    peak_rss_bytes: AtomicU64,
+    flush_requested: AtomicBool,
+}
+
+impl MemoryPressure {
+    /// Highest resident set size sampled so far (0 before the first sample or off Linux)
+    pub fn peak_rss_bytes(&self) -> u64 {
+        self.peak_rss_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Consume a pending emergency flush request
+    ///
+    /// Cheap enough to call per item: a single atomic swap.
+    pub fn take_flush_request(&self) -> bool {
+        self.flush_requested.swap(false, Ordering::Relaxed)
+    }
+
+    fn record(&self, rss: u64) {
+        self.peak_rss_bytes.fetch_max(rss, Ordering::Relaxed);
+    }
+}
+
+/// Background thread watching process RSS against the memory limit
+///
+/// The limit is the lower of --memory-limit and the cgroup (container) limit. Once
+/// RSS comes within `warn_percent` of it the monitor warns and asks the extraction
+/// loop to flush its partition buffers, asking again whenever RSS grows further
+/// while it stays there. RSS is logged every `log_interval` (never when zero). Stops
+/// when dropped.
+pub struct MemoryMonitor {
+    pressure: Arc<MemoryPressure>,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MemoryMonitor {
+    pub fn spawn(memory_limit: Option<u64>, warn_percent: u8, log_interval: Duration) -> Self {
+        let limit = effective_limit(memory_limit, cgroup_memory_limit());
+        match limit {
+            Some(limit) => info!(
+                "Memory monitor: limit {}, warning and flushing within {}%",
+                format_bytes(limit),
+                warn_percent
+            ),
+            None => info!("Memory monitor: no memory or cgroup limit, logging RSS only"),
+        }
+        let threshold = limit.map(|limit| warn_threshold(limit, warn_percent));
+
+        let pressure = Arc::new(MemoryPressure::default());
+        let (stop, stopped) = bounded::<()>(0);
+        let shared = Arc::clone(&pressure);
+        let handle = thread::spawn(move || {
+            let mut last_log = Instant::now();
+            // RSS at the last flush request while near the limit
+            let mut requested_at: Option<u64> = None;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(SAMPLE_INTERVAL) {
+                let Some(rss) = current_rss() else {
+                    continue;
+                };
+                shared.record(rss);
+
+                let near_limit = threshold.is_some_and(|threshold| rss >= threshold);
+                if !near_limit {
+                    requested_at = None;
+                } else if requested_at.is_none_or(|previous| rss > previous) {
+                    if requested_at.is_none() {
+                        warn!(
+                            "RSS {} is within {}% of the {} memory limit; flushing partition buffers",
+                            format_bytes(rss),
+                            warn_percent,
+                            format_bytes(limit.unwrap_or_default())
+                        );
+                    }
+                    shared.flush_requested.store(true, Ordering::Relaxed);
+                    requested_at = Some(rss);
+                }
+
+                if !log_interval.is_zero() && last_log.elapsed() >= log_interval {
+                    last_log = Instant::now();
+                    match limit {
+                        Some(limit) => info!(
+                            "Memory: RSS {} ({:.0}% of {})",
+                            format_bytes(rss),
+                            rss as f64 * 100.0 / limit as f64,
+                            format_bytes(limit)
+                        ),
+                        None => info!("Memory: RSS {}", format_bytes(rss)),
+                    }
+                }
+            }
+        });
+
+        Self {
+            pressure,
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+
+    pub fn pressure(&self) -> &Arc<MemoryPressure> {
+        &self.pressure
+    }
+}
+
+impl Drop for MemoryMonitor {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread immediately
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The tighter of the configured and cgroup limits
+fn effective_limit(memory_limit: Option<u64>, cgroup_limit: Option<u64>) -> Option<u64> {
+    match (memory_limit, cgroup_limit) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// RSS at which the monitor warns: `warn_percent` below the limit
+fn warn_threshold(limit: u64, warn_percent: u8) -> u64 {
+    limit / 100 * u64::from(100 - warn_percent.min(100))
+}
+
+/// Resident set size of this process, from /proc/self/status (Linux only)
+pub fn current_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Memory limit of the cgroup this process runs in, if any (v2, then v1)
+pub fn cgroup_memory_limit() -> Option<u64> {
+    [
+        "/sys/fs/cgroup/memory.max",
+        "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+    ]
+    .iter()
+    .find_map(|path| parse_cgroup_limit(&std::fs::read_to_string(path).ok()?))
+}
+
+fn parse_cgroup_limit(contents: &str) -> Option<u64> {
+    contents
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|&limit| limit < CGROUP_V1_UNLIMITED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vm_rss() {
+        let status = "Name:\tcrossref\nVmPeak:\t  9000 kB\nVmRSS:\t  2048 kB\nThreads:\t4\n";
+        assert_eq!(parse_vm_rss(status), Some(2 * 1024 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tx\n"), None);
+    }
+
+    #[test]
+    fn test_parse_cgroup_limit() {
+        assert_eq!(parse_cgroup_limit("8589934592\n"), Some(8 << 30));
+        assert_eq!(parse_cgroup_limit("max\n"), None);
+        assert_eq!(parse_cgroup_limit("9223372036854771712\n"), None);
+    }
+
+    #[test]
+    fn test_limits_and_threshold() {
+        assert_eq!(effective_limit(Some(100), Some(50)), Some(50));
+        assert_eq!(effective_limit(None, Some(50)), Some(50));
+        assert_eq!(effective_limit(Some(100), None), Some(100));
+        assert_eq!(effective_limit(None, None), None);
+        assert_eq!(warn_threshold(1000, 10), 900);
+        assert_eq!(warn_threshold(1000, 0), 1000);
+    }
+
+    #[test]
+    fn test_monitor_flush_request() {
+        // A 1-byte limit is always exceeded, so the first sample requests a flush
+        let monitor = MemoryMonitor::spawn(Some(1), 10, Duration::ZERO);
+        let start = Instant::now();
+        while !monitor.pressure().take_flush_request() && cfg!(target_os = "linux") {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(50));
+        }
+        drop(monitor);
+    }
+}
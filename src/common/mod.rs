@@ -1,11 +1,58 @@
+#[cfg(feature = "parquet")]
+pub mod columnar_output;
+pub mod date;
+pub mod http_client;
+pub mod lock;
 pub mod logging;
+pub mod manifest;
+pub mod memory;
+pub mod memory_monitor;
 pub mod output;
 pub mod progress;
+pub mod rng;
+pub mod runtime;
+pub mod shutdown;
+#[cfg(feature = "parquet")]
+pub mod stats_history;
+pub mod temp_dirs;
 pub mod types;
 pub mod utils;
 
+#[cfg(feature = "parquet")]
+pub use columnar_output::read_columnar_records;
+pub use date::{DateRange, PartialDate};
+pub use http_client::{HttpClient, HttpClientConfig};
+pub use lock::RunLock;
 pub use logging::*;
-pub use output::SplitOutputPaths;
+pub use manifest::{describe_inputs, manifest_is_current, write_manifest, MANIFEST_FILE};
+pub use memory::*;
+pub use memory_monitor::{MemoryMonitor, MemoryPressure};
+pub use output::{
+    open_sink, open_sink_with, OutputOptions, ShardLimit, ShardManifest, SplitOutputPaths,
+    STDOUT_PATH,
+};
+pub use progress::{ExtractionProgress, StatsLogger};
+pub use rng::SplitMix64;
+pub use runtime::run_blocking;
+pub use shutdown::{
+    install_signal_handlers, is_interrupted, CancellationToken, Interrupted, INTERRUPTED_EXIT_CODE,
+};
+#[cfg(feature = "parquet")]
+pub use stats_history::{append_stats_history, StatsRow};
+pub use temp_dirs::{find_stale_temp_dirs, TempDirMarker, DEFAULT_STALE_AGE, TEMP_DIR_PREFIX};
+
+// Re-export sinks for library users implementing their own outputs
+#[cfg(feature = "parquet")]
+#[allow(unused_imports)]
+pub use columnar_output::{ArrowIpcSink, ParquetSink};
+#[allow(unused_imports)]
+pub use output::{
+    shard_path, HighWaterMark, JsonlSink, OutputSink, ShardEntry, ShardedSink, SyncedJsonlSink,
+};
+
+// Re-export the stale temp directory entry for library users
+#[allow(unused_imports)]
+pub use temp_dirs::StaleTempDir;
 pub use types::*;
 pub use utils::*;
 
@@ -1,5 +1,57 @@
+#[cfg(feature = "parquet")]
+use super::columnar_output::{ArrowIpcSink, ParquetSink};
+use crate::error::{Context, Error, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
+/// Output path that writes JSONL to stdout instead of a file
+pub const STDOUT_PATH: &str = "-";
+
+/// Appended to an output path to name its high-water mark file
+pub const HIGH_WATER_MARK_SUFFIX: &str = ".hwm";
+
+/// Appended to an output path to name the manifest of its shards
+pub const SHARD_MANIFEST_SUFFIX: &str = ".shards.json";
+
+/// How output files are written
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputOptions {
+    /// Fsync plain JSONL outputs and record their high-water mark every this many
+    /// records (0: write without syncing)
+    pub sync_every: usize,
+    /// Write each output as numbered shards of at most this size (see [`ShardedSink`])
+    pub shard: Option<ShardLimit>,
+    /// Rewrite synced outputs from the start instead of resuming after an incomplete
+    /// high-water mark, for runs whose records may differ from the interrupted one's
+    pub restart: bool,
+}
+
+/// Largest shard of a sharded output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardLimit {
+    /// Bytes of JSON Lines per shard, before any compression
+    Bytes(u64),
+    /// Records per shard
+    Records(usize),
+}
+
+impl ShardLimit {
+    /// Limit from a shard size or record count, whichever is given
+    pub fn from_size_or_records(bytes: Option<u64>, records: Option<usize>) -> Option<Self> {
+        match (bytes, records) {
+            (Some(bytes), _) => Some(Self::Bytes(bytes.max(1))),
+            (None, Some(records)) => Some(Self::Records(records.max(1))),
+            (None, None) => None,
+        }
+    }
+}
+
 /// Paths for split output files (all, asserted, mined)
 #[derive(Debug, Clone)]
 pub struct SplitOutputPaths {
@@ -11,28 +63,490 @@ pub struct SplitOutputPaths {
 impl SplitOutputPaths {
     /// Generate split paths from a base path
     /// "results.jsonl" -> "results.jsonl", "results_asserted.jsonl", "results_mined.jsonl"
+    /// "results.jsonl.gz" -> "results_asserted.jsonl.gz", "results_mined.jsonl.gz"
     pub fn from_base<P: AsRef<Path>>(base: P) -> Self {
         let base = base.as_ref();
-        let stem = base.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-        let extension = base.extension().and_then(|s| s.to_str());
-        let parent = base.parent();
-
-        let make_path = |suffix: &str| -> PathBuf {
-            let filename = match extension {
-                Some(ext) => format!("{}_{}.{}", stem, suffix, ext),
-                None => format!("{}_{}", stem, suffix),
-            };
-            match parent {
-                Some(p) if !p.as_os_str().is_empty() => p.join(filename),
-                _ => PathBuf::from(filename),
+        Self {
+            all: base.to_path_buf(),
+            asserted: suffixed_path(base, "asserted"),
+            mined: suffixed_path(base, "mined"),
+        }
+    }
+}
+
+/// `base` with `_<suffix>` added to its file stem, keeping a `.jsonl.gz` style
+/// double extension together
+fn suffixed_path(base: &Path, suffix: &str) -> PathBuf {
+    let mut stem = base
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string();
+    let mut extension = base
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(str::to_string);
+    if extension.as_deref() == Some("gz") {
+        let inner = Path::new(&stem);
+        if let (Some(inner_stem), Some(inner_ext)) = (
+            inner.file_stem().and_then(|s| s.to_str()),
+            inner.extension().and_then(|s| s.to_str()),
+        ) {
+            extension = Some(format!("{}.gz", inner_ext));
+            stem = inner_stem.to_string();
+        }
+    }
+    let filename = match &extension {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext),
+        None => format!("{}_{}", stem, suffix),
+    };
+    match base.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.join(filename),
+        _ => PathBuf::from(filename),
+    }
+}
+
+/// Path of the `index`th shard (from 1) of an output:
+/// "results.jsonl" -> "results_00001.jsonl"
+pub fn shard_path(base: &Path, index: usize) -> PathBuf {
+    suffixed_path(base, &format!("{:05}", index))
+}
+
+/// Destination for JSON output records (validation results and similar reports)
+///
+/// Implementations receive one record at a time and must not assume the output is
+/// complete until `finish` returns. Other backends (S3, a database) are not built in,
+/// but can implement this trait and be used wherever the file sinks are.
+pub trait OutputSink {
+    /// Write a single record
+    fn write_record(&mut self, record: &Value) -> Result<()>;
+
+    /// Flush buffered output and finalize the destination, returning records written
+    fn finish(self: Box<Self>) -> Result<usize>;
+}
+
+/// One JSON object per line, to any writer
+///
+/// Object keys are written in sorted order at every depth, whatever order a record was
+/// built in, so reruns on the same inputs produce byte-identical lines. This relies on
+/// serde_json's default sorted map; its `preserve_order` feature must stay disabled
+/// across the dependency graph.
+pub struct JsonlSink<W: Write> {
+    writer: W,
+    records_written: usize,
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            records_written: 0,
+        }
+    }
+}
+
+impl JsonlSink<BufWriter<File>> {
+    /// Plain JSONL file
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create: {:?}", path))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl JsonlSink<BufWriter<GzEncoder<File>>> {
+    /// Gzip-compressed JSONL file
+    pub fn create_gzip(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create: {:?}", path))?;
+        Ok(Self::new(BufWriter::new(GzEncoder::new(
+            file,
+            Compression::default(),
+        ))))
+    }
+}
+
+impl<W: Write> OutputSink for JsonlSink<W> {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.records_written += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<usize> {
+        self.writer.flush()?;
+        Ok(self.records_written)
+    }
+}
+
+/// Durable prefix of a synced JSONL output, kept next to it in `<output>.hwm`
+///
+/// The first `records` lines (`bytes` bytes) of the output have been fsynced, so a
+/// consumer tailing the output can read up to `bytes` safely. `complete` is set once
+/// the whole output has been written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighWaterMark {
+    pub records: usize,
+    pub bytes: u64,
+    pub complete: bool,
+}
+
+impl HighWaterMark {
+    /// High-water mark file of an output
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(HIGH_WATER_MARK_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// Load the high-water mark of an output, if it has one
+    pub fn load(output: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(output);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read: {:?}", path))?;
+        let mark = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse high-water mark: {:?}", path))?;
+        Ok(Some(mark))
+    }
+
+    /// Written to a temporary file and renamed, so readers never see a partial mark
+    fn save(&self, output: &Path) -> Result<()> {
+        let path = Self::path_for(output);
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        fs::write(&tmp_path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write: {:?}", tmp_path))?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))
+    }
+}
+
+/// Plain JSONL file fsynced every `sync_every` records, with its high-water mark
+///
+/// When the output has an incomplete high-water mark from an interrupted run, the
+/// file is truncated to the mark and writing resumes after it: the first
+/// `records` records given are taken to be the ones already written and skipped.
+/// This relies on reruns producing the same records in the same order, as the
+/// validation outputs do for the same inputs without HTTP fallback; `restart`
+/// ignores the mark and rewrites the output.
+pub struct SyncedJsonlSink {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    sync_every: usize,
+    records_written: usize,
+    bytes_written: u64,
+    to_skip: usize,
+}
+
+impl SyncedJsonlSink {
+    /// Create the output, or resume it after its high-water mark unless `restart`
+    pub fn open(path: &Path, sync_every: usize, restart: bool) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to create: {:?}", path))?;
+        let len = file.metadata()?.len();
+        let mark = match HighWaterMark::load(path)? {
+            Some(mark) if restart && !mark.complete => {
+                info!("Rewriting {:?} instead of resuming it", path);
+                HighWaterMark::default()
             }
+            Some(mark) if !mark.complete && mark.bytes <= len => mark,
+            _ => HighWaterMark::default(),
         };
+        if mark.records > 0 {
+            info!(
+                "Resuming {:?} after {} records already written",
+                path, mark.records
+            );
+        }
+        file.set_len(mark.bytes)?;
+        file.seek(SeekFrom::End(0))?;
 
-        Self {
-            all: base.to_path_buf(),
-            asserted: make_path("asserted"),
-            mined: make_path("mined"),
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            sync_every: sync_every.max(1),
+            records_written: mark.records,
+            bytes_written: mark.bytes,
+            to_skip: mark.records,
+        })
+    }
+
+    /// Fsync what has been written and record it as the high-water mark
+    fn sync(&mut self, complete: bool) -> Result<()> {
+        self.writer.flush()?;
+        self.writer
+            .get_ref()
+            .sync_data()
+            .with_context(|| format!("Failed to sync: {:?}", self.path))?;
+        HighWaterMark {
+            records: self.records_written,
+            bytes: self.bytes_written,
+            complete,
+        }
+        .save(&self.path)
+    }
+}
+
+impl OutputSink for SyncedJsonlSink {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        if self.to_skip > 0 {
+            self.to_skip -= 1;
+            return Ok(());
+        }
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.records_written += 1;
+        self.bytes_written += line.len() as u64;
+        if self.records_written.is_multiple_of(self.sync_every) {
+            self.sync(false)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<usize> {
+        if self.to_skip > 0 {
+            return Err(Error::invalid(format!(
+                "{:?} was resumed after {} records but this run produced {}; delete its \
+                 high-water mark to rewrite it",
+                self.path,
+                self.records_written,
+                self.records_written - self.to_skip
+            )));
+        }
+        self.sync(true)?;
+        Ok(self.records_written)
+    }
+}
+
+/// The shards of a sharded output, in order, kept in `<output>.shards.json`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardManifest {
+    /// Records across all shards
+    pub records: usize,
+    pub shards: Vec<ShardEntry>,
+}
+
+/// One shard of a sharded output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardEntry {
+    /// File name, in the directory of the output
+    pub file: String,
+    pub records: usize,
+    /// Bytes of JSON Lines written to the shard, before any compression
+    pub bytes: u64,
+}
+
+impl ShardManifest {
+    /// Manifest file of an output
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(SHARD_MANIFEST_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// Load the shard manifest of an output, if it has one
+    pub fn load(output: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(output);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read: {:?}", path))?;
+        let manifest = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse shard manifest: {:?}", path))?;
+        Ok(Some(manifest))
+    }
+
+    /// Files making up an output: its shards and shard manifest when it was written
+    /// sharded, otherwise the output itself
+    pub fn files_of(output: &Path) -> Vec<PathBuf> {
+        match Self::load(output) {
+            Ok(Some(manifest)) => {
+                let directory = output.parent().unwrap_or(Path::new(""));
+                manifest
+                    .shards
+                    .iter()
+                    .map(|shard| directory.join(&shard.file))
+                    .chain([Self::path_for(output)])
+                    .collect()
+            }
+            _ => vec![output.to_path_buf()],
+        }
+    }
+
+    /// Written to a temporary file and renamed, so readers never see a partial manifest
+    fn save(&self, output: &Path) -> Result<()> {
+        let path = Self::path_for(output);
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write: {:?}", tmp_path))?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))
+    }
+}
+
+/// Output split into numbered shards, each written by the sink its extension implies
+///
+/// Records go to `<stem>_00001.<ext>` until it reaches the [`ShardLimit`], then to
+/// `<stem>_00002.<ext>` and so on; nothing is written at the output path itself.
+/// Byte limits count JSON Lines bytes before compression, whatever the format. On
+/// `finish` the shards are listed in `<output>.shards.json`, and shards of an earlier
+/// run beyond the new last one are removed.
+pub struct ShardedSink {
+    base: PathBuf,
+    limit: ShardLimit,
+    /// Options each shard is opened with
+    shard_options: OutputOptions,
+    current: Option<Box<dyn OutputSink>>,
+    manifest: ShardManifest,
+}
+
+impl ShardedSink {
+    pub fn open(base: &Path, limit: ShardLimit, options: &OutputOptions) -> Result<Self> {
+        Ok(Self {
+            base: base.to_path_buf(),
+            limit,
+            shard_options: OutputOptions {
+                shard: None,
+                ..*options
+            },
+            current: None,
+            manifest: ShardManifest::default(),
+        })
+    }
+
+    /// Finish the open shard, if any
+    fn close_shard(&mut self) -> Result<()> {
+        if let Some(sink) = self.current.take() {
+            sink.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputSink for ShardedSink {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let line_bytes = match self.limit {
+            ShardLimit::Bytes(_) => serde_json::to_vec(record)?.len() as u64 + 1,
+            ShardLimit::Records(_) => 0,
+        };
+        let full = self
+            .manifest
+            .shards
+            .last()
+            .is_some_and(|shard| match self.limit {
+                ShardLimit::Bytes(max) => shard.bytes > 0 && shard.bytes + line_bytes > max,
+                ShardLimit::Records(max) => shard.records >= max,
+            });
+        if full {
+            self.close_shard()?;
+        }
+        if self.current.is_none() {
+            let path = shard_path(&self.base, self.manifest.shards.len() + 1);
+            self.current = Some(open_sink_with(&path, &self.shard_options)?);
+            self.manifest.shards.push(ShardEntry {
+                file: path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                records: 0,
+                bytes: 0,
+            });
+        }
+
+        if let Some(sink) = self.current.as_mut() {
+            sink.write_record(record)?;
+        }
+        if let Some(shard) = self.manifest.shards.last_mut() {
+            shard.records += 1;
+            shard.bytes += line_bytes;
+        }
+        self.manifest.records += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<usize> {
+        self.close_shard()?;
+        if let Some(previous) = ShardManifest::load(&self.base)? {
+            let directory = self.base.parent().unwrap_or(Path::new(""));
+            for stale in previous.shards.iter().skip(self.manifest.shards.len()) {
+                let path = directory.join(&stale.file);
+                if path.exists() {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove: {:?}", path))?;
+                }
+            }
+        }
+        self.manifest.save(&self.base)?;
+        info!(
+            "Wrote {} records to {} shards of {:?}",
+            self.manifest.records,
+            self.manifest.shards.len(),
+            self.base
+        );
+        Ok(self.manifest.records)
+    }
+}
+
+/// Open the sink implied by an output path
+///
+/// `-` writes JSONL to stdout, `.gz` gzip-compressed JSONL, `.parquet` Parquet,
+/// `.arrow` or `.feather` an Arrow IPC file, `.arrows` an Arrow IPC stream, and anything
+/// else plain JSONL.
+pub fn open_sink(path: &Path) -> Result<Box<dyn OutputSink>> {
+    open_sink_with(path, &OutputOptions::default())
+}
+
+/// Open the sink implied by an output path, as [`open_sink`]
+///
+/// With `sync_every` set, plain JSONL files are written through a [`SyncedJsonlSink`];
+/// other formats ignore it. With `shard` set, any format is written as numbered shards
+/// through a [`ShardedSink`]; stdout is never sharded. Object store URLs such as
+/// `s3://bucket/key` are rejected.
+pub fn open_sink_with(path: &Path, options: &OutputOptions) -> Result<Box<dyn OutputSink>> {
+    if path.as_os_str() == STDOUT_PATH {
+        return Ok(Box::new(JsonlSink::new(std::io::stdout().lock())));
+    }
+    // No object store client is built in: a URL would otherwise become a local path
+    if let Some((scheme, _)) = path.to_str().and_then(|p| p.split_once("://")) {
+        return Err(Error::invalid(format!(
+            "Cannot write {:?}: {}:// outputs are not supported; write to a local path and \
+             upload it, or implement OutputSink for the store",
+            path, scheme
+        )));
+    }
+    if let Some(limit) = options.shard {
+        return Ok(Box::new(ShardedSink::open(path, limit, options)?));
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => Ok(Box::new(JsonlSink::create_gzip(path)?)),
+        #[cfg(feature = "parquet")]
+        Some("parquet") => Ok(Box::new(ParquetSink::create(path)?)),
+        #[cfg(feature = "parquet")]
+        Some("arrow" | "feather") => Ok(Box::new(ArrowIpcSink::create(path)?)),
+        #[cfg(feature = "parquet")]
+        Some("arrows") => Ok(Box::new(ArrowIpcSink::create_stream(path)?)),
+        #[cfg(not(feature = "parquet"))]
+        Some(ext @ ("parquet" | "arrow" | "feather" | "arrows")) => {
+            Err(Error::requires_parquet(format!("Writing .{} output", ext)))
         }
+        _ if options.sync_every > 0 => Ok(Box::new(SyncedJsonlSink::open(
+            path,
+            options.sync_every,
+            options.restart,
+        )?)),
+        _ => Ok(Box::new(JsonlSink::create(path)?)),
     }
 }
 
@@ -66,4 +580,202 @@ mod tests {
         assert_eq!(paths.asserted, PathBuf::from("results_asserted"));
         assert_eq!(paths.mined, PathBuf::from("results_mined"));
     }
+
+    #[test]
+    fn test_split_path_gzip() {
+        let paths = SplitOutputPaths::from_base("out/results.jsonl.gz");
+        assert_eq!(paths.all, PathBuf::from("out/results.jsonl.gz"));
+        assert_eq!(
+            paths.asserted,
+            PathBuf::from("out/results_asserted.jsonl.gz")
+        );
+        assert_eq!(paths.mined, PathBuf::from("out/results_mined.jsonl.gz"));
+    }
+
+    #[test]
+    fn test_shard_path() {
+        assert_eq!(
+            shard_path(Path::new("out/results.jsonl"), 1),
+            PathBuf::from("out/results_00001.jsonl")
+        );
+        assert_eq!(
+            shard_path(Path::new("results.jsonl.gz"), 12),
+            PathBuf::from("results_00012.jsonl.gz")
+        );
+    }
+
+    #[test]
+    fn test_jsonl_keys_in_canonical_order() {
+        let mut built_forward = serde_json::Map::new();
+        built_forward.insert("doi".to_string(), "10.1/a".into());
+        built_forward.insert("citation_count".to_string(), 1.into());
+        built_forward.insert(
+            "cited_by".to_string(),
+            serde_json::json!([{"provenance": "mined", "doi": "10.9/x"}]),
+        );
+        let mut built_backward = serde_json::Map::new();
+        built_backward.insert(
+            "cited_by".to_string(),
+            serde_json::json!([{"doi": "10.9/x", "provenance": "mined"}]),
+        );
+        built_backward.insert("citation_count".to_string(), 1.into());
+        built_backward.insert("doi".to_string(), "10.1/a".into());
+
+        let mut sink = JsonlSink::new(Vec::new());
+        sink.write_record(&Value::Object(built_forward)).unwrap();
+        sink.write_record(&Value::Object(built_backward)).unwrap();
+        let content = String::from_utf8(sink.writer).unwrap();
+        let expected = r#"{"citation_count":1,"cited_by":[{"doi":"10.9/x","provenance":"mined"}],"doi":"10.1/a"}"#;
+        assert_eq!(content, format!("{}\n{}\n", expected, expected));
+    }
+
+    #[test]
+    fn test_open_sink_by_extension() {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let records = [
+            serde_json::json!({"doi": "10.1/a", "citation_count": 2, "cited_by": [{"doi": "10.9/x"}]}),
+            serde_json::json!({"doi": "10.1/b", "citation_count": 1, "cited_by": []}),
+        ];
+        let write_all = |path: &Path| {
+            let mut sink = open_sink(path).unwrap();
+            for record in &records {
+                sink.write_record(record).unwrap();
+            }
+            assert_eq!(sink.finish().unwrap(), 2);
+        };
+
+        let jsonl = dir.path().join("out.jsonl");
+        write_all(&jsonl);
+        let content = std::fs::read_to_string(&jsonl).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        let first: Value = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(first, records[0]);
+
+        let gz = dir.path().join("out.jsonl.gz");
+        write_all(&gz);
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(File::open(&gz).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, content);
+    }
+
+    #[test]
+    fn test_open_sink_rejects_object_store_urls() {
+        let err = open_sink(Path::new("s3://bucket/results.jsonl"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("s3:// outputs are not supported"), "{}", err);
+    }
+
+    #[test]
+    fn test_synced_jsonl_sink_resumes_after_high_water_mark() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.jsonl");
+        let records: Vec<Value> = (0..5)
+            .map(|i| serde_json::json!({"doi": format!("10.1/{}", i)}))
+            .collect();
+        let options = OutputOptions {
+            sync_every: 2,
+            ..Default::default()
+        };
+
+        // Interrupted after 3 records: only the first 2 are below the mark
+        let mut sink = open_sink_with(&path, &options).unwrap();
+        for record in &records[..3] {
+            sink.write_record(record).unwrap();
+        }
+        drop(sink);
+        let mark = HighWaterMark::load(&path).unwrap().unwrap();
+        assert_eq!(mark.records, 2);
+        assert!(!mark.complete);
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            mark.bytes as usize,
+            content.lines().take(2).map(|l| l.len() + 1).sum::<usize>()
+        );
+
+        // The rerun skips the 2 synced records and rewrites from the third
+        let mut sink = open_sink_with(&path, &options).unwrap();
+        for record in &records {
+            sink.write_record(record).unwrap();
+        }
+        assert_eq!(sink.finish().unwrap(), 5);
+        let content = std::fs::read_to_string(&path).unwrap();
+        let written: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(written, records);
+        let mark = HighWaterMark::load(&path).unwrap().unwrap();
+        assert_eq!((mark.records, mark.complete), (5, true));
+        assert_eq!(mark.bytes, content.len() as u64);
+
+        // A complete output is rewritten from scratch
+        let mut sink = open_sink_with(&path, &options).unwrap();
+        sink.write_record(&records[0]).unwrap();
+        assert_eq!(sink.finish().unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        // So is an interrupted one when restarting
+        let mut sink = open_sink_with(&path, &options).unwrap();
+        for record in &records[..3] {
+            sink.write_record(record).unwrap();
+        }
+        drop(sink);
+        let restart = OutputOptions {
+            restart: true,
+            ..options
+        };
+        let mut sink = open_sink_with(&path, &restart).unwrap();
+        sink.write_record(&records[4]).unwrap();
+        assert_eq!(sink.finish().unwrap(), 1);
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(content.trim_end()).unwrap(),
+            records[4]
+        );
+    }
+
+    #[test]
+    fn test_sharded_sink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.jsonl");
+        let records: Vec<Value> = (0..5)
+            .map(|i| serde_json::json!({"doi": format!("10.1/{}", i)}))
+            .collect();
+        let write = |limit: ShardLimit, records: &[Value]| {
+            let options = OutputOptions {
+                shard: Some(limit),
+                ..Default::default()
+            };
+            let mut sink = open_sink_with(&path, &options).unwrap();
+            for record in records {
+                sink.write_record(record).unwrap();
+            }
+            sink.finish().unwrap()
+        };
+
+        assert_eq!(write(ShardLimit::Records(2), &records), 5);
+        assert!(!path.exists());
+        let manifest = ShardManifest::load(&path).unwrap().unwrap();
+        assert_eq!(manifest.records, 5);
+        let files: Vec<&str> = manifest.shards.iter().map(|s| s.file.as_str()).collect();
+        assert_eq!(
+            files,
+            ["out_00001.jsonl", "out_00002.jsonl", "out_00003.jsonl"]
+        );
+        let third = std::fs::read_to_string(dir.path().join("out_00003.jsonl")).unwrap();
+        assert_eq!(third, "{\"doi\":\"10.1/4\"}\n");
+
+        // Each line is 17 bytes, so two fit under 40; the stale third shard goes
+        assert_eq!(write(ShardLimit::Bytes(40), &records[..4]), 4);
+        let manifest = ShardManifest::load(&path).unwrap().unwrap();
+        assert_eq!(manifest.shards.len(), 2);
+        assert_eq!(manifest.shards[0].bytes, 34);
+        assert!(!dir.path().join("out_00003.jsonl").exists());
+    }
 }
@@ -1,4 +1,10 @@
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
 use indicatif::{ProgressBar, ProgressStyle};
+use log::info;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 #[allow(dead_code)]
 pub fn create_bytes_progress_bar(total_bytes: u64) -> ProgressBar {
@@ -23,3 +29,98 @@ pub fn create_count_progress_bar(total_items: u64) -> ProgressBar {
     );
     pb
 }
+
+/// Extraction counters shared with a `StatsLogger` thread
+#[derive(Debug, Default)]
+pub struct ExtractionProgress {
+    pub files: AtomicUsize,
+    pub items: AtomicUsize,
+    pub matches: AtomicUsize,
+    /// Rows held in partition buffers, not yet flushed
+    pub rows_buffered: AtomicUsize,
+    pub partitions: AtomicUsize,
+}
+
+impl ExtractionProgress {
+    /// One-line summary, with the item rate since `elapsed` began
+    pub fn summary(&self, elapsed: Duration) -> String {
+        let items = self.items.load(Ordering::Relaxed);
+        let secs = elapsed.as_secs_f64();
+        let rate = if secs > 0.0 { items as f64 / secs } else { 0.0 };
+        format!(
+            "Progress: {} files, {} items ({:.0}/s), {} matches, {} rows buffered in {} partitions",
+            self.files.load(Ordering::Relaxed),
+            items,
+            rate,
+            self.matches.load(Ordering::Relaxed),
+            self.rows_buffered.load(Ordering::Relaxed),
+            self.partitions.load(Ordering::Relaxed)
+        )
+    }
+}
+
+/// Background thread logging `ExtractionProgress` at a fixed interval
+///
+/// Logs on a timer rather than per file, so small files do not flood the log and a
+/// single huge file does not leave it silent. Stops when dropped.
+pub struct StatsLogger {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StatsLogger {
+    pub fn spawn(progress: Arc<ExtractionProgress>, interval: Duration) -> Self {
+        let (stop, stopped) = bounded::<()>(0);
+        let start = Instant::now();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                info!("{}", progress.summary(start.elapsed()));
+            }
+        });
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for StatsLogger {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread immediately
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extraction_progress_summary() {
+        let progress = ExtractionProgress::default();
+        progress.files.store(3, Ordering::Relaxed);
+        progress.items.store(500, Ordering::Relaxed);
+        progress.matches.store(42, Ordering::Relaxed);
+        progress.rows_buffered.store(40, Ordering::Relaxed);
+        progress.partitions.store(7, Ordering::Relaxed);
+
+        assert_eq!(
+            progress.summary(Duration::from_secs(10)),
+            "Progress: 3 files, 500 items (50/s), 42 matches, 40 rows buffered in 7 partitions"
+        );
+    }
+
+    #[test]
+    fn test_stats_logger_stops_on_drop() {
+        let logger = StatsLogger::spawn(
+            Arc::new(ExtractionProgress::default()),
+            Duration::from_secs(3600),
+        );
+        let start = Instant::now();
+        drop(logger);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
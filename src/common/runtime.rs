@@ -0,0 +1,53 @@
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// Run CPU-bound or blocking work from async code without stalling other tasks
+///
+/// On a multi-threaded runtime the work runs through `block_in_place`, which hands the
+/// worker's other tasks to another thread for the duration, so a service that spawns a
+/// pipeline keeps all its workers. A current-thread runtime cannot give its only thread
+/// away, and outside a runtime there is nothing to hand over; there the work runs inline.
+pub fn run_blocking<T>(work: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(work)
+        }
+        _ => work(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_blocking_frees_the_worker() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_time()
+            .build()
+            .unwrap();
+        let done = Arc::new(AtomicBool::new(false));
+
+        runtime.block_on(async {
+            let blocking_done = done.clone();
+            let blocking = tokio::spawn(async move {
+                run_blocking(|| std::thread::sleep(Duration::from_millis(300)));
+                blocking_done.store(true, Ordering::SeqCst);
+            });
+            // Runs on the only worker while the blocking task holds it
+            let other = tokio::spawn(async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            });
+            other.await.unwrap();
+            assert!(!done.load(Ordering::SeqCst));
+            blocking.await.unwrap();
+        });
+        assert!(done.load(Ordering::SeqCst));
+
+        // Inline outside a runtime
+        assert_eq!(run_blocking(|| 7), 7);
+    }
+}
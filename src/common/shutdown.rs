@@ -0,0 +1,168 @@
+use crate::error::{Context, Error, ErrorKind, Result};
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Exit status for a run stopped by SIGINT/SIGTERM after flushing its outputs
+/// (EX_TEMPFAIL: intermediate files and checkpoint are intact, so it can be rerun)
+pub const INTERRUPTED_EXIT_CODE: i32 = 75;
+/// Exit status when a second signal aborts without flushing (128 + SIGINT)
+const ABORTED_EXIT_CODE: i32 = 130;
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Error returned when a phase stops early because shutdown was requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrupted; outputs flushed and checkpoint saved")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Whether an error, or any error it wraps, is an interruption
+///
+/// Interruptions reach the CLI either directly or as a library [`Error`] of kind
+/// `Interrupted`, possibly under added context.
+pub fn is_interrupted(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.is::<Interrupted>()
+            || e.downcast_ref::<Error>()
+                .is_some_and(|e| e.kind() == ErrorKind::Interrupted)
+    })
+}
+
+/// Whether SIGINT/SIGTERM has been received
+///
+/// Long-running loops check this at points where stopping leaves consistent files.
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Cooperative cancellation for long operations embedded in another process
+///
+/// Extraction, inversion and validation check the token at the same safe points
+/// they check for SIGINT/SIGTERM. On cancellation they flush what they have written
+/// (partitions, checkpoint, HTTP checkpoint) and fail with `Interrupted`, so a later
+/// run with the same intermediates resumes from the partial results. A token also
+/// reports cancelled once a signal has been received. An embedding process builds the
+/// token from a flag it shares (`CancellationToken::from`) and cancels by setting it.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the shared flag was set or shutdown was requested by a signal
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst) || shutdown_requested()
+    }
+
+    /// Fail with `Interrupted` if cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Interrupted.into());
+        }
+        Ok(())
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    /// Share an existing flag: storing `true` in it cancels
+    fn from(cancelled: Arc<AtomicBool>) -> Self {
+        Self { cancelled }
+    }
+}
+
+/// Trap SIGINT and SIGTERM on a background thread
+///
+/// The first signal only sets the shutdown flag, so the current phase can flush its
+/// writers and stop at the next safe point. A second signal exits immediately.
+pub fn install_signal_handlers() -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to build signal handling runtime")?;
+
+    std::thread::Builder::new()
+        .name("signal-handler".to_string())
+        .spawn(move || {
+            runtime.block_on(async {
+                loop {
+                    if let Err(e) = wait_for_signal().await {
+                        warn!("Signal handling unavailable: {}", e);
+                        return;
+                    }
+                    if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+                        eprintln!("Second interrupt received, aborting without flushing");
+                        std::process::exit(ABORTED_EXIT_CODE);
+                    }
+                    warn!(
+                        "Interrupt received; flushing outputs and saving checkpoint \
+                         (interrupt again to abort immediately)"
+                    );
+                }
+            });
+        })
+        .context("Failed to spawn signal handling thread")?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = interrupt.recv() => {}
+        _ = terminate.recv() => {}
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupted_is_detectable_through_anyhow() {
+        let err: anyhow::Error = Interrupted.into();
+        let err = err.context("Failed to invert partition");
+        assert!(is_interrupted(&err));
+        assert_eq!(err.downcast_ref::<Interrupted>(), Some(&Interrupted));
+
+        let err: anyhow::Error = Error::from(Interrupted)
+            .context("Inverting partitions")
+            .into();
+        assert!(is_interrupted(&err));
+        assert!(!is_interrupted(&anyhow::anyhow!("Failed to open file")));
+    }
+
+    #[test]
+    fn test_cancellation_token() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let token = CancellationToken::from(Arc::clone(&flag));
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        assert!(clone.check().is_ok());
+
+        flag.store(true, Ordering::SeqCst);
+        assert!(clone.is_cancelled());
+        assert_eq!(clone.check().unwrap_err().kind(), ErrorKind::Interrupted);
+        assert!(!CancellationToken::new().is_cancelled());
+    }
+}
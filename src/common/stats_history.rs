@@ -0,0 +1,212 @@
+use crate::error::{Context, Error, Result};
+use log::info;
+use polars::prelude::*;
+use std::fs::File;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::MultiValidateStats;
+
+/// Counts of one source in one run, a row of the --stats-history table
+///
+/// Extraction counts are the run's totals, repeated on the row of every source, and
+/// are unset when extraction was skipped (--retry-failed-partitions). Validation counts
+/// are unset when the source was not validated (no index, or nothing cited).
+#[derive(Debug, Clone, Default)]
+pub struct StatsRow {
+    pub source: String,
+    pub items_processed: Option<usize>,
+    pub refs_with_matches: Option<usize>,
+    pub total_matches: Option<usize>,
+    pub unique_cited_works: usize,
+    pub total_citations: usize,
+    pub validation: Option<MultiValidateStats>,
+}
+
+/// Append the rows of a run to the Parquet stats history at `path`, returning its rows
+///
+/// Every row gets the same `run_at` (UTC) and `snapshot_id`. The file is created when
+/// missing; otherwise the existing rows are read, the new ones appended, and the whole
+/// table written to a temporary file and renamed, so an interrupted run never loses
+/// earlier history.
+pub fn append_stats_history(path: &Path, snapshot_id: &str, rows: &[StatsRow]) -> Result<usize> {
+    let run_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or_default();
+    let new = stats_frame(run_at, snapshot_id, rows)?;
+
+    let mut history = if path.exists() {
+        let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+        let mut existing = ParquetReader::new(file)
+            .finish()
+            .with_context(|| format!("Failed to read stats history: {:?}", path))?;
+        if existing.schema() != new.schema() {
+            return Err(Error::invalid(format!(
+                "Stats history {:?} has different columns than this version writes; \
+                 start a new file",
+                path
+            )));
+        }
+        existing.vstack_mut(&new)?;
+        existing
+    } else {
+        new
+    };
+
+    let tmp_path = path.with_extension("parquet.tmp");
+    let file =
+        File::create(&tmp_path).with_context(|| format!("Failed to create: {:?}", tmp_path))?;
+    ParquetWriter::new(file)
+        .with_compression(ParquetCompression::Zstd(None))
+        .finish(&mut history)
+        .with_context(|| format!("Failed to write stats history: {:?}", tmp_path))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))?;
+
+    info!(
+        "Appended {} rows to stats history {:?} ({} rows)",
+        rows.len(),
+        path,
+        history.height()
+    );
+    Ok(history.height())
+}
+
+fn stats_frame(run_at: i64, snapshot_id: &str, rows: &[StatsRow]) -> Result<DataFrame> {
+    let count = |name: &str, value: fn(&StatsRow) -> Option<usize>| {
+        let values: Vec<Option<u64>> = rows
+            .iter()
+            .map(|row| value(row).map(|v| v as u64))
+            .collect();
+        Column::new(name.into(), values)
+    };
+    let validated = |name: &str, value: fn(&MultiValidateStats) -> usize| {
+        let values: Vec<Option<u64>> = rows
+            .iter()
+            .map(|row| row.validation.as_ref().map(|stats| value(stats) as u64))
+            .collect();
+        Column::new(name.into(), values)
+    };
+
+    let run_at = Column::new("run_at".into(), vec![run_at; rows.len()]).cast(
+        &DataType::Datetime(TimeUnit::Milliseconds, Some("UTC".into())),
+    )?;
+    let columns = vec![
+        run_at,
+        Column::new("snapshot_id".into(), vec![snapshot_id; rows.len()]),
+        Column::new(
+            "source".into(),
+            rows.iter()
+                .map(|row| row.source.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        count("items_processed", |row| row.items_processed),
+        count("refs_with_matches", |row| row.refs_with_matches),
+        count("total_matches", |row| row.total_matches),
+        count("unique_cited_works", |row| Some(row.unique_cited_works)),
+        count("total_citations", |row| Some(row.total_citations)),
+        validated("validated_records", |stats| stats.total_records),
+        validated("crossref_matched", |stats| stats.crossref_matched),
+        validated("crossref_http_resolved", |stats| {
+            stats.crossref_http_resolved
+        }),
+        validated("crossref_failed", |stats| stats.crossref_failed),
+        validated("datacite_matched", |stats| stats.datacite_matched),
+        validated("datacite_http_resolved", |stats| {
+            stats.datacite_http_resolved
+        }),
+        validated("datacite_failed", |stats| stats.datacite_failed),
+        validated("datacite_prefix_matched", |stats| {
+            stats.datacite_prefix_matched
+        }),
+        validated("fuzzy_rescued", |stats| stats.fuzzy_rescued),
+        validated("failed_prefix_known", |stats| stats.failed_prefix_known),
+        validated("failed_prefix_unknown", |stats| stats.failed_prefix_unknown),
+        validated("failed_malformed", |stats| stats.failed_malformed),
+    ];
+    Ok(DataFrame::new(columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_append_stats_history() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stats.parquet");
+        let validated = StatsRow {
+            source: "crossref".to_string(),
+            items_processed: Some(100),
+            refs_with_matches: Some(40),
+            total_matches: Some(50),
+            unique_cited_works: 30,
+            total_citations: 45,
+            validation: Some(MultiValidateStats {
+                total_records: 30,
+                crossref_matched: 25,
+                crossref_failed: 5,
+                ..Default::default()
+            }),
+        };
+        let retried = StatsRow {
+            source: "arxiv".to_string(),
+            unique_cited_works: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            append_stats_history(&path, "2024-04", std::slice::from_ref(&validated)).unwrap(),
+            1
+        );
+        assert_eq!(
+            append_stats_history(&path, "2025-04", &[validated, retried]).unwrap(),
+            3
+        );
+        assert!(!path.with_extension("parquet.tmp").exists());
+
+        let history = ParquetReader::new(File::open(&path).unwrap())
+            .finish()
+            .unwrap();
+        let snapshots: Vec<_> = history
+            .column("snapshot_id")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(snapshots, ["2024-04", "2025-04", "2025-04"]);
+        let matched = history.column("crossref_matched").unwrap().u64().unwrap();
+        assert_eq!(matched.get(1), Some(25));
+        assert_eq!(matched.get(2), None);
+        assert_eq!(
+            history
+                .column("items_processed")
+                .unwrap()
+                .u64()
+                .unwrap()
+                .get(2),
+            None
+        );
+        assert!(matches!(
+            history.column("run_at").unwrap().dtype(),
+            DataType::Datetime(TimeUnit::Milliseconds, _)
+        ));
+    }
+
+    #[test]
+    fn test_append_stats_history_rejects_other_columns() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stats.parquet");
+        let mut other = df!("run_at" => [1i64]).unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .finish(&mut other)
+            .unwrap();
+
+        let err = append_stats_history(&path, "2025-04", &[StatsRow::default()]).unwrap_err();
+        assert!(err.to_string().contains("different columns"));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::error::{Context, Error, Result};
+use log::debug;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Name prefix of the partition directories a run creates in the system temp directory
+pub const TEMP_DIR_PREFIX: &str = "crossref-extract-";
+
+/// Marker file in a run's temp directory, locked for as long as the run lives
+pub const TEMP_MARKER_FILE: &str = ".crossref-extract-run";
+
+/// Marker file of a temp directory kept on purpose, which is never collected
+pub const TEMP_KEPT_FILE: &str = ".crossref-extract-kept";
+
+/// Age after which an unlocked temp directory is considered orphaned
+pub const DEFAULT_STALE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Marker of a live run in its temp directory
+///
+/// The marker file holds the run's PID under an advisory OS lock, which is released
+/// however the process exits. A directory whose marker nobody holds belongs to a run
+/// that crashed or was interrupted.
+#[derive(Debug)]
+pub struct TempDirMarker {
+    file: File,
+    path: PathBuf,
+}
+
+impl TempDirMarker {
+    /// Create (or take over) the marker of `dir`, failing if another run holds it
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let path = dir.join(TEMP_MARKER_FILE);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open temp directory marker: {:?}", path))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(Error::invalid(format!(
+                    "Another run is using the temp directory {:?}",
+                    dir
+                )));
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(Error::from(e).context(format!("Failed to lock: {:?}", path)));
+            }
+        }
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+        Ok(Self { file, path })
+    }
+
+    /// Mark a directory kept on purpose, so it is never collected
+    ///
+    /// The run marker is replaced by [`TEMP_KEPT_FILE`]: without either, the directory
+    /// would be judged by its age like one from a build that predates the marker.
+    pub fn release(self) -> Result<()> {
+        let kept = self.path.with_file_name(TEMP_KEPT_FILE);
+        fs::write(&kept, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write temp directory marker: {:?}", kept))?;
+        let _ = self.file.unlock();
+        fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove temp directory marker: {:?}", self.path))
+    }
+}
+
+/// A temp directory left behind by an earlier run
+#[derive(Debug, Clone)]
+pub struct StaleTempDir {
+    pub path: PathBuf,
+    /// Time since the run started, or since the directory last changed when it has
+    /// no marker (directories from versions before the marker)
+    pub age: Duration,
+}
+
+impl StaleTempDir {
+    /// Bytes of the files in the directory
+    pub fn size(&self) -> u64 {
+        dir_size(&self.path)
+    }
+}
+
+/// Temp directories under `base` whose run is gone and that are older than `min_age`
+///
+/// Only directories named with [`TEMP_DIR_PREFIX`] are considered, and one whose
+/// marker is locked by a live run, or that was kept on purpose, is never returned,
+/// whatever its age.
+pub fn find_stale_temp_dirs(base: &Path, min_age: Duration) -> Result<Vec<StaleTempDir>> {
+    let now = SystemTime::now();
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(base).with_context(|| format!("Failed to read: {:?}", base))? {
+        let entry = entry?;
+        let is_run_dir = entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(TEMP_DIR_PREFIX)
+            && entry.file_type().is_ok_and(|t| t.is_dir());
+        if !is_run_dir {
+            continue;
+        }
+        let path = entry.path();
+        if path.join(TEMP_KEPT_FILE).exists() {
+            debug!("Temp directory kept on purpose: {:?}", path);
+            continue;
+        }
+        let marker = path.join(TEMP_MARKER_FILE);
+        if marker.exists() && is_locked(&marker) {
+            debug!("Temp directory in use by a live run: {:?}", path);
+            continue;
+        }
+        // The marker is written when the run starts; without one, use the directory
+        let started = if marker.exists() { &marker } else { &path };
+        let age = fs::metadata(started)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age >= min_age {
+            stale.push(StaleTempDir { path, age });
+        }
+    }
+    stale.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(stale)
+}
+
+/// Whether another process holds the lock on a marker file
+fn is_locked(marker: &Path) -> bool {
+    match File::open(marker).map(|file| file.try_lock()) {
+        Ok(Err(TryLockError::WouldBlock)) => true,
+        // Opening or locking failed some other way: treat as not in use
+        Ok(Ok(())) | Ok(Err(TryLockError::Error(_))) | Err(_) => false,
+    }
+}
+
+/// Total bytes of the files under `path`, skipping anything unreadable
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_stale_temp_dirs() {
+        let base = tempdir().unwrap();
+        let crashed = base.path().join(format!("{}crashed", TEMP_DIR_PREFIX));
+        let live = base.path().join(format!("{}live", TEMP_DIR_PREFIX));
+        let unmarked = base.path().join(format!("{}unmarked", TEMP_DIR_PREFIX));
+        let other = base.path().join("other-tool-dir");
+        for dir in [&crashed, &live, &unmarked, &other] {
+            fs::create_dir(dir).unwrap();
+        }
+        fs::write(crashed.join("10.1234.parquet"), b"12345").unwrap();
+
+        // A crashed run leaves its marker unlocked
+        drop(TempDirMarker::acquire(&crashed).unwrap());
+        let _live = TempDirMarker::acquire(&live).unwrap();
+        assert!(TempDirMarker::acquire(&live).is_err());
+
+        let stale = find_stale_temp_dirs(base.path(), Duration::ZERO).unwrap();
+        let paths: Vec<_> = stale.iter().map(|dir| dir.path.clone()).collect();
+        assert_eq!(paths, [crashed.clone(), unmarked]);
+        assert!(stale[0].size() >= 5);
+
+        // Younger than the threshold
+        assert!(find_stale_temp_dirs(base.path(), DEFAULT_STALE_AGE)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_released_marker_is_removed() {
+        let dir = tempdir().unwrap();
+        let marker = TempDirMarker::acquire(dir.path()).unwrap();
+        assert!(dir.path().join(TEMP_MARKER_FILE).exists());
+        marker.release().unwrap();
+        assert!(!dir.path().join(TEMP_MARKER_FILE).exists());
+        assert!(dir.path().join(TEMP_KEPT_FILE).exists());
+    }
+
+    #[test]
+    fn test_released_dir_is_not_stale() {
+        let base = tempdir().unwrap();
+        let kept = base.path().join(format!("{}kept", TEMP_DIR_PREFIX));
+        fs::create_dir(&kept).unwrap();
+        fs::write(kept.join("intermediates.json"), b"{}").unwrap();
+        TempDirMarker::acquire(&kept).unwrap().release().unwrap();
+
+        assert!(find_stale_temp_dirs(base.path(), Duration::ZERO)
+            .unwrap()
+            .is_empty());
+    }
+}
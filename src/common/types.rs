@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::extract::IdType;
 
 /// Represents a single arXiv match with normalized ID, raw matched text, and constructed DOI
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,9 +57,133 @@ pub struct CitationRecord {
     pub doi: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub arxiv_id: Option<String>,
+    /// Identifier family of the cited ID, from inversion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_type: Option<IdType>,
     pub reference_count: usize,
     pub citation_count: usize,
     pub cited_by: Vec<Value>,
+    /// Published journal DOI of a cited arXiv preprint, from DataCite relatedIdentifiers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_doi: Option<String>,
+    /// Registration agency doi.org reports for a failed DOI (--registration-agency-lookup)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_agency: Option<String>,
+    /// How a valid record's DOI was confirmed; unset on failed records
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_method: Option<ValidationMethod>,
+    /// Outcome of validation, set on valid and failed records alike
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_status: Option<ValidationStatus>,
+    /// Why the doi.org check of a failed record did not resolve its DOI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_error: Option<HttpError>,
+    /// Citing works whose citation was asserted (publisher or crossref), from inversion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asserted_citation_count: Option<usize>,
+    /// Publisher of the cited DOI's prefix (--annotate-publishers)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cited_publisher: Option<String>,
+    /// Citation count broken down by provenance, from inversion
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    pub provenance_counts: Option<ProvenanceCounts>,
+}
+
+/// Citing works of a cited work by the provenance of their citation
+///
+/// The four counts add up to `citation_count`. Written as separate top-level fields of
+/// a record; records inverted before the counts existed have none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceCounts {
+    pub publisher_citation_count: usize,
+    pub crossref_citation_count: usize,
+    pub structured_citation_count: usize,
+    pub mined_citation_count: usize,
+}
+
+/// How a cited DOI was confirmed to exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMethod {
+    /// Found in the Crossref or DataCite index
+    Index,
+    /// Resolved via doi.org
+    Http,
+    /// Only its prefix is in a prefix-only DataCite index (probable, not confirmed)
+    Prefix,
+}
+
+/// Outcome of validating a cited DOI
+///
+/// Valid records are matched in an index, by prefix alone in a prefix-only index
+/// (probable, not confirmed), or via doi.org. Failed records are told apart by why they failed: a DOI that cannot be
+/// one (an extraction or OCR error), one under a prefix no loaded index knows (a
+/// coverage gap or another agency), or one under a known prefix that did not resolve (a
+/// typo, or an HTTP check that failed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValidationStatus {
+    MatchedIndex,
+    MatchedPrefix,
+    MatchedHttp,
+    PrefixKnownUnresolved,
+    PrefixUnknown,
+    Malformed,
+}
+
+impl ValidationStatus {
+    /// Status of a valid record confirmed by `method`
+    pub fn matched(method: ValidationMethod) -> Self {
+        match method {
+            ValidationMethod::Index => ValidationStatus::MatchedIndex,
+            ValidationMethod::Prefix => ValidationStatus::MatchedPrefix,
+            ValidationMethod::Http => ValidationStatus::MatchedHttp,
+        }
+    }
+}
+
+/// Why a doi.org check did not resolve a DOI
+///
+/// A 404 means doi.org does not know the DOI, a coverage gap or typo that a rerun will
+/// not change. Throttling, server errors, timeouts and connection failures are
+/// transient and worth checking again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HttpError {
+    /// 404: doi.org does not know the DOI
+    NotFound,
+    /// Any other 4xx other than 429
+    ClientError,
+    /// 429 or 503
+    Throttled,
+    /// Any other 5xx
+    ServerError,
+    /// No response within --timeout
+    Timeout,
+    /// Connection refused or reset
+    Connection,
+    /// Any other failure sending the request or reading the response
+    Request,
+}
+
+impl HttpError {
+    /// Whether checking the DOI again may resolve it
+    pub fn is_transient(self) -> bool {
+        !matches!(self, HttpError::NotFound | HttpError::ClientError)
+    }
+
+    /// Name as written in records and logs
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HttpError::NotFound => "not-found",
+            HttpError::ClientError => "client-error",
+            HttpError::Throttled => "throttled",
+            HttpError::ServerError => "server-error",
+            HttpError::Timeout => "timeout",
+            HttpError::Connection => "connection",
+            HttpError::Request => "request",
+        }
+    }
 }
 
 /// Statistics from multi-source validation
@@ -69,4 +196,30 @@ pub struct MultiValidateStats {
     pub datacite_matched: usize,
     pub datacite_http_resolved: usize,
     pub datacite_failed: usize,
+    /// Failed in all mode under a prefix neither index knows, so attributed to neither
+    pub unknown_prefix_failed: usize,
+    /// Accepted as probable DataCite DOIs by prefix-only lookup
+    pub datacite_prefix_matched: usize,
+    /// Prefix-only matches checked via HTTP
+    pub datacite_prefix_sampled: usize,
+    /// Sampled prefix-only matches that resolved
+    pub datacite_prefix_confirmed: usize,
+    /// Prefixes whose sample resolved too rarely to accept their other DOIs unchecked
+    pub datacite_prefixes_unconfirmed: usize,
+    /// HTTP results taken from the checkpoint instead of re-requested
+    pub http_checkpoint_reused: usize,
+    /// HTTP checks skipped because their prefix only had failing DOIs
+    pub http_prefix_skipped: usize,
+    /// Unmatched DOIs found after removing whitespace and mapping unicode dashes
+    pub fuzzy_rescued: usize,
+    /// Records skipped because their DOI belongs to an index shard not loaded
+    pub outside_shards: usize,
+    /// Failed records whose prefix is in a loaded index
+    pub failed_prefix_known: usize,
+    /// Failed records whose prefix no loaded index knows
+    pub failed_prefix_unknown: usize,
+    /// Failed records whose DOI is not syntactically a DOI
+    pub failed_malformed: usize,
+    /// Failed records by why their doi.org check did not resolve
+    pub http_errors: BTreeMap<HttpError, usize>,
 }
@@ -0,0 +1,201 @@
+use std::fmt::Display;
+
+use crate::common::Interrupted;
+
+/// Result of the library layers (extract, index, validation, streaming)
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error returned by the library layers
+///
+/// The CLI commands keep using anyhow and receive these through `?`. Library users can
+/// match on [`Error::kind`], which looks through any context added on the way up.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Reading or writing a file or stream failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// JSON, XML or other text input could not be parsed
+    #[error(transparent)]
+    Parse(Box<dyn std::error::Error + Send + Sync>),
+
+    /// Building, reading or writing a DataFrame or Parquet file failed
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Data(#[from] polars::error::PolarsError),
+
+    /// An HTTP request failed before a response was received
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    /// Input or arguments are not what the operation requires (wrong file format,
+    /// unsupported schema version, inconsistent options)
+    #[error("{0}")]
+    Invalid(String),
+
+    /// Cancelled by a shutdown signal; outputs were flushed first
+    #[error(transparent)]
+    Interrupted(#[from] Interrupted),
+
+    /// The operation failed for a reason other than its input: a worker thread
+    /// panicked, a lock was poisoned, or every unit of work failed
+    #[error("{0}")]
+    Internal(String),
+
+    /// Another error with a description of what was being done
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+/// Category of an [`Error`], for callers that handle failures differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Io,
+    Parse,
+    #[cfg(feature = "parquet")]
+    Data,
+    Http,
+    Invalid,
+    Interrupted,
+    Internal,
+}
+
+impl Error {
+    /// An [`Error::Invalid`] with the given message
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Error::Invalid(message.into())
+    }
+
+    /// An [`Error::Internal`] with the given message
+    pub fn internal(message: impl Into<String>) -> Self {
+        Error::Internal(message.into())
+    }
+
+    /// An [`Error::Invalid`] for an option or file format that needs Parquet support,
+    /// in a build without the `parquet` feature
+    #[cfg_attr(feature = "parquet", allow(dead_code))]
+    pub fn requires_parquet(what: impl Display) -> Self {
+        Error::Invalid(format!(
+            "{} requires Parquet support; rebuild with the `parquet` feature",
+            what
+        ))
+    }
+
+    /// Wrap the error with a description of what was being done
+    pub fn context(self, context: impl Display) -> Self {
+        Error::Context {
+            context: context.to_string(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Category of the underlying error, below any added context
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Io(_) => ErrorKind::Io,
+            Error::Parse(_) => ErrorKind::Parse,
+            #[cfg(feature = "parquet")]
+            Error::Data(_) => ErrorKind::Data,
+            Error::Http(_) => ErrorKind::Http,
+            Error::Invalid(_) => ErrorKind::Invalid,
+            Error::Interrupted(_) => ErrorKind::Interrupted,
+            Error::Internal(_) => ErrorKind::Internal,
+            Error::Context { source, .. } => source.kind(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        // serde_json reports I/O failures of the underlying reader or writer too
+        match e.io_error_kind() {
+            Some(_) => Error::Io(e.into()),
+            None => Error::Parse(Box::new(e)),
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(e: std::num::ParseIntError) -> Self {
+        Error::Parse(Box::new(e))
+    }
+}
+
+impl From<quick_xml::Error> for Error {
+    fn from(e: quick_xml::Error) -> Self {
+        Error::Parse(Box::new(e))
+    }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for Error {
+    fn from(e: quick_xml::events::attributes::AttrError) -> Self {
+        Error::Parse(Box::new(e))
+    }
+}
+
+/// Add context to errors, as `anyhow::Context` does, keeping their [`ErrorKind`]
+///
+/// On `Option`, `None` becomes an [`Error::Invalid`] with the context as message.
+pub trait Context<T> {
+    fn context<C: Display>(self, context: C) -> Result<T>;
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|e| e.into().context(context))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|e| e.into().context(f()))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.ok_or_else(|| Error::invalid(context.to_string()))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.ok_or_else(|| Error::invalid(f().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_looks_through_context() {
+        let io: Result<()> = Err(std::io::Error::other("disk full").into());
+        let err = io.context("Failed to write").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(err.to_string(), "Failed to write");
+
+        let json = serde_json::from_str::<serde_json::Value>("{").context("Bad record");
+        assert_eq!(json.unwrap_err().kind(), ErrorKind::Parse);
+
+        let missing: Option<u8> = None;
+        let err = missing.context("Missing column").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Invalid);
+
+        let err = Error::internal("Writer thread panicked").context("Inverting partitions");
+        assert_eq!(err.kind(), ErrorKind::Internal);
+    }
+
+    #[test]
+    fn test_context_chain_reaches_anyhow() {
+        let err = Error::from(Interrupted).context("Inverting partitions");
+        let err: anyhow::Error = err.into();
+        assert!(crate::common::is_interrupted(&err));
+        assert_eq!(
+            format!("{:#}", err),
+            "Inverting partitions: Interrupted; outputs flushed and checkpoint saved"
+        );
+    }
+}
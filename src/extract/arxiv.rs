@@ -1,7 +1,11 @@
 use lazy_static::lazy_static;
-use regex::Regex;
+use regex::{Regex, RegexSet};
 use std::collections::HashMap;
 
+#[cfg(test)]
+use super::legacy_categories::bundled_categories;
+use super::legacy_categories::LegacyArxivCategories;
+use super::prescreen::may_contain_arxiv;
 use crate::common::ArxivMatch;
 
 lazy_static! {
@@ -28,8 +32,33 @@ lazy_static! {
     pub static ref ARXIV_URL_PATTERN: Regex = Regex::new(
         r"(?i)(arxiv\.org/(?:abs|pdf)/(\d{4}\.\d{4,6}(?:v\d+)?|[a-z][a-z0-9.-]*/\d{7}(?:v\d+)?))"
     ).unwrap();
+
+    // The ID patterns above, in the order their matches take precedence
+    pub static ref ARXIV_ID_PATTERNS: [&'static Regex; 4] = [
+        &*ARXIV_MODERN_PATTERN,
+        &*ARXIV_OLD_FORMAT_PATTERN,
+        &*ARXIV_DOI_PATTERN,
+        &*ARXIV_URL_PATTERN,
+    ];
+
+    // The ID patterns in one automaton, to find in a single scan which have matches
+    static ref ARXIV_PATTERN_SET: RegexSet = RegexSet::new(
+        ARXIV_ID_PATTERNS.iter().map(|pattern| pattern.as_str())
+    ).unwrap();
+
+    // A word naming arXiv, for the proximity matcher: "arXiv preprint", "[arXiv", "arXiv."
+    pub static ref ARXIV_MENTION_PATTERN: Regex = Regex::new(r"(?i)\barxiv\b").unwrap();
+
+    // arXiv ID with no "arXiv" before it, for the proximity matcher: YYMM.NNNNN or
+    // category/YYMMNNN. The characters around it are checked by the matcher.
+    pub static ref ARXIV_BARE_ID_PATTERN: Regex = Regex::new(
+        r"(?i)\b(\d{4}\.\d{4,5}(?:v\d+)?|[a-z][a-z-]*(?:\.[a-z]{2})?/\d{7}(?:v\d+)?)\b"
+    ).unwrap();
 }
 
+/// Default number of words allowed between a bare arXiv ID and an arXiv mention
+pub const DEFAULT_ARXIV_RECALL_WINDOW: usize = 3;
+
 /// Normalize an arXiv ID by converting to lowercase, removing whitespace, and stripping version
 pub fn normalize_arxiv_id(id: &str) -> String {
     let mut id = id.to_lowercase();
@@ -44,47 +73,161 @@ pub fn normalize_arxiv_id(id: &str) -> String {
     id
 }
 
+/// Category embedded in an old-format arXiv ID (e.g. "hep-ph/9901234" -> "hep-ph")
+///
+/// Modern IDs (YYMM.NNNNN) carry no category and return None.
+pub fn arxiv_id_category(id: &str) -> Option<String> {
+    id.split_once('/')
+        .map(|(category, _)| category.trim().to_lowercase())
+        .filter(|category| !category.is_empty())
+}
+
 /// Extract arXiv matches from text using all pattern types
+///
+/// The text is scanned once by the combined pattern set, and captures are taken only
+/// from the patterns that matched. When several patterns find one ID, the raw match
+/// comes from the first of them in `ARXIV_ID_PATTERNS` order. Old-format IDs need a
+/// category from the bundled [`LegacyArxivCategories`].
+#[cfg(test)]
 pub fn extract_arxiv_matches_from_text(text: &str) -> Vec<ArxivMatch> {
+    extract_arxiv_matches_within(text, bundled_categories())
+}
+
+/// Extract arXiv matches from text, keeping old-format IDs whose category is in
+/// `categories`
+pub fn extract_arxiv_matches_within(
+    text: &str,
+    categories: &LegacyArxivCategories,
+) -> Vec<ArxivMatch> {
+    if !may_contain_arxiv(text) {
+        return Vec::new();
+    }
     let mut matches: HashMap<String, ArxivMatch> = HashMap::new();
 
-    for cap in ARXIV_MODERN_PATTERN.captures_iter(text) {
-        if let (Some(raw), Some(id)) = (cap.get(1), cap.get(2)) {
-            let normalized = normalize_arxiv_id(id.as_str());
-            matches
-                .entry(normalized.clone())
-                .or_insert_with(|| ArxivMatch::new(normalized, raw.as_str().to_string()));
+    for index in ARXIV_PATTERN_SET.matches(text).iter() {
+        for cap in ARXIV_ID_PATTERNS[index].captures_iter(text) {
+            if let (Some(raw), Some(id)) = (cap.get(1), cap.get(2)) {
+                let normalized = normalize_arxiv_id(id.as_str());
+                if !categories.accepts(&normalized) {
+                    continue;
+                }
+                matches
+                    .entry(normalized.clone())
+                    .or_insert_with(|| ArxivMatch::new(normalized, raw.as_str().to_string()));
+            }
         }
     }
 
-    for cap in ARXIV_OLD_FORMAT_PATTERN.captures_iter(text) {
-        if let (Some(raw), Some(id)) = (cap.get(1), cap.get(2)) {
-            let normalized = normalize_arxiv_id(id.as_str());
-            matches
-                .entry(normalized.clone())
-                .or_insert_with(|| ArxivMatch::new(normalized, raw.as_str().to_string()));
-        }
+    matches.into_values().collect()
+}
+
+/// Extract arXiv matches, adding bare IDs within `window` words of an arXiv mention
+///
+/// Recall mode (--arxiv-recall) for references that name arXiv away from the ID, as in
+/// "arXiv preprint 2403.12345" or "(2023), 2403.12345, arXiv". Words are counted
+/// between the ID and the nearest mention, ignoring punctuation. A bare ID must have
+/// the shape of a real identifier (see [`is_plausible_bare_arxiv_id`]) and not be part
+/// of a DOI, URL path or longer number. Its raw match is the ID as written.
+#[cfg(test)]
+pub fn extract_arxiv_matches_near_mentions(text: &str, window: usize) -> Vec<ArxivMatch> {
+    extract_arxiv_matches_near_mentions_within(text, window, bundled_categories())
+}
+
+/// Recall-mode extraction keeping old-format IDs whose category is in `categories`
+pub fn extract_arxiv_matches_near_mentions_within(
+    text: &str,
+    window: usize,
+    categories: &LegacyArxivCategories,
+) -> Vec<ArxivMatch> {
+    let mut matches = extract_arxiv_matches_within(text, categories);
+    let mentions: Vec<(usize, usize)> = ARXIV_MENTION_PATTERN
+        .find_iter(text)
+        .map(|m| (m.start(), m.end()))
+        .collect();
+    if mentions.is_empty() {
+        return matches;
     }
 
-    for cap in ARXIV_DOI_PATTERN.captures_iter(text) {
-        if let (Some(raw), Some(id)) = (cap.get(1), cap.get(2)) {
-            let normalized = normalize_arxiv_id(id.as_str());
-            matches
-                .entry(normalized.clone())
-                .or_insert_with(|| ArxivMatch::new(normalized, raw.as_str().to_string()));
+    for bare in ARXIV_BARE_ID_PATTERN.find_iter(text) {
+        let before = text[..bare.start()].chars().next_back();
+        let after = text[bare.end()..].chars().next();
+        if before.is_some_and(|c| c.is_alphanumeric() || matches!(c, '/' | '.' | '-' | ':'))
+            || after.is_some_and(|c| matches!(c, '/' | '-') || c.is_alphanumeric())
+            || (after == Some('.')
+                && text[bare.end() + 1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            continue;
+        }
+        if !is_plausible_bare_arxiv_id(bare.as_str()) {
+            continue;
+        }
+        let near = mentions.iter().any(|&(start, end)| {
+            let between = if end <= bare.start() {
+                &text[end..bare.start()]
+            } else if bare.end() <= start {
+                &text[bare.end()..start]
+            } else {
+                return false;
+            };
+            words_in(between) <= window
+        });
+        if !near {
+            continue;
+        }
+        let id = normalize_arxiv_id(bare.as_str());
+        if !categories.accepts(&id) {
+            continue;
+        }
+        if !matches.iter().any(|m| m.id == id) {
+            matches.push(ArxivMatch::new(id, bare.as_str().to_string()));
         }
     }
+    matches
+}
 
-    for cap in ARXIV_URL_PATTERN.captures_iter(text) {
-        if let (Some(raw), Some(id)) = (cap.get(1), cap.get(2)) {
-            let normalized = normalize_arxiv_id(id.as_str());
-            matches
-                .entry(normalized.clone())
-                .or_insert_with(|| ArxivMatch::new(normalized, raw.as_str().to_string()));
+/// Whether a bare ID has the shape of an arXiv identifier
+///
+/// Modern IDs start in April 2007 (0704) and have 4 digits after the point up to
+/// December 2014, 5 since. Old IDs have a month of 1991 to March 2007 after the
+/// category. Rules out years, page ranges and version numbers that happen to match.
+pub fn is_plausible_bare_arxiv_id(id: &str) -> bool {
+    let id = id.to_lowercase();
+    let id = match id.rfind('v') {
+        Some(pos) if pos > 0 && id[pos + 1..].chars().all(|c| c.is_ascii_digit()) => &id[..pos],
+        _ => id.as_str(),
+    };
+    let year_month = |digits: &str| -> Option<(u32, u32)> {
+        Some((
+            digits.get(..2)?.parse().ok()?,
+            digits.get(2..4)?.parse().ok()?,
+        ))
+    };
+    match id.split_once('/') {
+        Some((_, number)) => match year_month(number) {
+            Some((year, month)) if (1..=12).contains(&month) => {
+                year >= 91 || (year, month) <= (7, 3)
+            }
+            _ => false,
+        },
+        None => {
+            let Some((date, number)) = id.split_once('.') else {
+                return false;
+            };
+            match year_month(date) {
+                Some((year, month)) if (1..=12).contains(&month) && (year, month) >= (7, 4) => {
+                    number.len() == if year >= 15 { 5 } else { 4 }
+                }
+                _ => false,
+            }
         }
     }
+}
 
-    matches.into_values().collect()
+/// Words in `text`, not counting tokens that are only punctuation
+fn words_in(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|token| token.chars().any(char::is_alphanumeric))
+        .count()
 }
 
 #[cfg(test)]
@@ -101,6 +244,19 @@ mod tests {
         assert!(matches[0].raw.contains("2403.03542"));
     }
 
+    #[test]
+    fn test_arxiv_id_category() {
+        assert_eq!(
+            arxiv_id_category("hep-ph/9901234"),
+            Some("hep-ph".to_string())
+        );
+        assert_eq!(
+            arxiv_id_category("cs.DM/9910013"),
+            Some("cs.dm".to_string())
+        );
+        assert_eq!(arxiv_id_category("2403.03542"), None);
+    }
+
     #[test]
     fn test_extract_arxiv_with_version() {
         let text = "arXiv:2403.03542v2";
@@ -135,6 +291,38 @@ mod tests {
         assert_eq!(matches[0].id, "cs.dm/9910013"); // Whitespace removed
     }
 
+    #[test]
+    fn test_extract_arxiv_old_format_requires_known_category() {
+        for text in [
+            "arXiv:proc/1234567",
+            "arxiv.org/abs/proc/1234567",
+            "arXiv:math.ZZ/0101001",
+        ] {
+            assert!(extract_arxiv_matches_from_text(text).is_empty(), "{}", text);
+        }
+        assert!(extract_arxiv_matches_near_mentions("arXiv preprint phys/9901234", 3).is_empty());
+
+        let text = "arXiv:proc/1234567; arXiv:hep-ph/9901234";
+        let ids: Vec<_> = extract_arxiv_matches_from_text(text)
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, ["hep-ph/9901234"]);
+    }
+
+    #[test]
+    fn test_extract_arxiv_within_categories() {
+        let categories = LegacyArxivCategories::from_list("proc\n").unwrap();
+
+        let text = "arXiv:proc/1234567; arXiv:hep-ph/9901234; arXiv:2403.03542";
+        let mut ids: Vec<_> = extract_arxiv_matches_within(text, &categories)
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["2403.03542", "proc/1234567"]);
+    }
+
     #[test]
     fn test_extract_arxiv_six_digit_decimal() {
         let text = "ArXiv. 2206.153252";
@@ -159,6 +347,29 @@ mod tests {
         assert_eq!(matches[0].id, "2403.03542");
     }
 
+    #[test]
+    fn test_combined_scan_keeps_pattern_precedence() {
+        // The modern pattern also matches inside the arXiv DOI and takes precedence
+        let matches = extract_arxiv_matches_from_text("10.48550/arXiv.2403.03542");
+        assert_eq!(matches[0].raw, "arXiv.2403.03542");
+
+        let text = "arxiv.org/abs/hep-ph/9901234; 10.48550/arXiv.2101.00001v2; arXiv:2403.03542";
+        let mut matches = extract_arxiv_matches_from_text(text);
+        matches.sort_by(|a, b| a.id.cmp(&b.id));
+        let found: Vec<_> = matches
+            .iter()
+            .map(|m| (m.id.as_str(), m.raw.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("2101.00001", "arXiv.2101.00001v2"),
+                ("2403.03542", "arXiv:2403.03542"),
+                ("hep-ph/9901234", "arxiv.org/abs/hep-ph/9901234"),
+            ]
+        );
+    }
+
     #[test]
     fn test_no_match_without_arxiv_context() {
         let text = "Some paper 2403.03542";
@@ -166,6 +377,118 @@ mod tests {
         assert!(matches.is_empty());
     }
 
+    #[test]
+    fn test_extract_arxiv_near_mentions() {
+        let ids = |text: &str| {
+            let mut ids: Vec<_> = extract_arxiv_matches_near_mentions(text, 3)
+                .into_iter()
+                .map(|m| m.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            ids("Smith J. arXiv preprint 2403.12345 (2024)"),
+            ["2403.12345"]
+        );
+        assert_eq!(ids("Smith J. (2023), 2403.12345v2, arXiv"), ["2403.12345"]);
+        assert_eq!(ids("[arXiv 2403.12345]"), ["2403.12345"]);
+        assert_eq!(ids("arXiv e-prints, hep-th/9901234"), ["hep-th/9901234"]);
+        // Strict matches are kept, and each ID is reported once
+        assert_eq!(
+            ids("arXiv:2403.12345, arXiv preprint 2403.12345"),
+            ["2403.12345"]
+        );
+
+        // Too far from the mention, or no mention at all
+        assert!(ids("arXiv preprint, submitted to Phys. Rev. D, 2403.12345").is_empty());
+        assert!(ids("Some paper 2403.12345").is_empty());
+        // Part of a DOI or URL, or a longer number
+        assert!(ids("arXiv version: doi:10.1103/2403.12345").is_empty());
+        assert!(ids("arXiv 12403.12345").is_empty());
+        assert!(ids("arXiv, 2403.12345.6").is_empty());
+
+        let m = &extract_arxiv_matches_near_mentions("arXiv preprint 2403.12345", 3)[0];
+        assert_eq!(m.raw, "2403.12345");
+    }
+
+    #[test]
+    fn test_is_plausible_bare_arxiv_id() {
+        assert!(is_plausible_bare_arxiv_id("0704.0001"));
+        assert!(is_plausible_bare_arxiv_id("1412.6980"));
+        assert!(is_plausible_bare_arxiv_id("2403.12345v3"));
+        assert!(is_plausible_bare_arxiv_id("hep-ph/9901234"));
+        assert!(is_plausible_bare_arxiv_id("math.AG/0703001"));
+
+        assert!(!is_plausible_bare_arxiv_id("0703.0001")); // before modern IDs
+        assert!(!is_plausible_bare_arxiv_id("2413.12345")); // month 13
+        assert!(!is_plausible_bare_arxiv_id("1412.69801")); // 5 digits before 2015
+        assert!(!is_plausible_bare_arxiv_id("2403.1234")); // 4 digits since 2015
+        assert!(!is_plausible_bare_arxiv_id("hep-ph/0804001")); // old format after March 2007
+    }
+
+    /// Precision and recall of strict and recall mode on labelled reference strings
+    #[test]
+    fn test_arxiv_recall_mode_precision_recall() {
+        let labelled: [(&str, &[&str]); 12] = [
+            ("Vaswani A. et al. arXiv:1706.03762", &["1706.03762"]),
+            (
+                "Kingma D., Ba J. Adam. arXiv preprint 1412.6980, 2014",
+                &["1412.6980"],
+            ),
+            (
+                "Brown T. Language models. [arXiv 2005.14165]",
+                &["2005.14165"],
+            ),
+            ("He K. (2015), 1512.03385, arXiv", &["1512.03385"]),
+            (
+                "Maldacena J. arXiv e-print hep-th/9711200",
+                &["hep-th/9711200"],
+            ),
+            ("See https://arxiv.org/abs/2106.09685", &["2106.09685"]),
+            ("Doe J. Phys. Rev. D 2015.1234, pp. 1203.1299", &[]),
+            ("Roe R. Nature 2403.12345 (2024)", &[]),
+            (
+                "Smith A. arXiv preprint, in press, J. Chem. Phys. 2011.12345",
+                &[],
+            ),
+            ("Lee K. doi:10.1016/2403.12345 arXiv version available", &[]),
+            ("Kim S. (2019) Proc. 1904.1234 arXiv", &[]),
+            ("Park H. arXiv 2019, 2403.12345.2", &[]),
+        ];
+
+        let score = |extract: &dyn Fn(&str) -> Vec<ArxivMatch>| {
+            let (mut true_pos, mut false_pos, mut false_neg) = (0, 0, 0);
+            for (text, expected) in labelled {
+                let found: Vec<String> = extract(text).into_iter().map(|m| m.id).collect();
+                true_pos += found
+                    .iter()
+                    .filter(|id| expected.contains(&id.as_str()))
+                    .count();
+                false_pos += found
+                    .iter()
+                    .filter(|id| !expected.contains(&id.as_str()))
+                    .count();
+                false_neg += expected
+                    .iter()
+                    .filter(|id| !found.iter().any(|f| f == *id))
+                    .count();
+            }
+            let precision = true_pos as f64 / (true_pos + false_pos).max(1) as f64;
+            let recall = true_pos as f64 / (true_pos + false_neg).max(1) as f64;
+            (precision, recall)
+        };
+
+        let (strict_precision, strict_recall) = score(&extract_arxiv_matches_from_text);
+        let (recall_precision, recall_recall) = score(&|text: &str| {
+            extract_arxiv_matches_near_mentions(text, DEFAULT_ARXIV_RECALL_WINDOW)
+        });
+        assert_eq!(strict_precision, 1.0);
+        assert!(strict_recall < 0.7);
+        assert_eq!(recall_precision, 1.0);
+        assert_eq!(recall_recall, 1.0);
+    }
+
     #[test]
     fn test_normalize_arxiv_id() {
         assert_eq!(normalize_arxiv_id("2403.03542"), "2403.03542");
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{
+    extract_arxiv_matches_near_mentions_within, extract_arxiv_matches_within,
+    extract_doi_matches_within, extract_spaced_doi_matches_within, may_contain_arxiv,
+    may_contain_doi, may_contain_spaced_doi, DoiConstraints, DoiMatch, LegacyArxivCategories,
+};
+use crate::common::ArxivMatch;
+
+/// Regex matches found in one search text, filled in per pattern as they are needed
+#[derive(Debug, Default)]
+struct CachedMatches {
+    dois: Option<Vec<DoiMatch>>,
+    arxiv: Option<Vec<ArxivMatch>>,
+}
+
+/// Cache of DOI and arXiv regex matches keyed by reference search text
+///
+/// Standard textbooks, software and data citations repeat the same reference text
+/// across millions of works; this skips the regexes for repeats. Eviction approximates
+/// LRU with two generations: entries go into the current one, which becomes the
+/// previous one (dropping the old previous) once it holds half the capacity, and a hit
+/// in the previous generation moves the entry back. A capacity of 0 disables caching.
+///
+/// Texts the prescreen rules out (no "10." or "arxiv") are answered before the cache,
+/// so they take no entry and count as neither hits nor misses.
+#[derive(Debug)]
+pub struct MatchCache {
+    entries: Generations,
+    /// Words allowed between a bare arXiv ID and an arXiv mention, in recall mode
+    arxiv_recall: Option<usize>,
+    /// Categories accepted in old-format arXiv IDs
+    legacy_arxiv_categories: Arc<LegacyArxivCategories>,
+    doi_constraints: DoiConstraints,
+    /// Also read DOIs with whitespace around the prefix dot or slash
+    doi_spacing_tolerant: bool,
+    hits: u64,
+    misses: u64,
+    screened: u64,
+}
+
+#[derive(Debug)]
+struct Generations {
+    capacity: usize,
+    current: HashMap<Box<str>, CachedMatches>,
+    previous: HashMap<Box<str>, CachedMatches>,
+}
+
+impl MatchCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Generations {
+                capacity,
+                current: HashMap::new(),
+                previous: HashMap::new(),
+            },
+            arxiv_recall: None,
+            legacy_arxiv_categories: Arc::new(LegacyArxivCategories::bundled()),
+            doi_constraints: DoiConstraints::default(),
+            doi_spacing_tolerant: false,
+            hits: 0,
+            misses: 0,
+            screened: 0,
+        }
+    }
+
+    /// Also match bare arXiv IDs within `window` words of an arXiv mention
+    /// (see [`extract_arxiv_matches_near_mentions_within`])
+    pub fn with_arxiv_recall(mut self, window: usize) -> Self {
+        self.arxiv_recall = Some(window);
+        self
+    }
+
+    /// Accept old-format arXiv IDs in `categories` instead of the bundled list
+    pub fn with_legacy_arxiv_categories(mut self, categories: LegacyArxivCategories) -> Self {
+        self.legacy_arxiv_categories = Arc::new(categories);
+        self
+    }
+
+    /// Keep only DOIs within `constraints` instead of the defaults
+    pub fn with_doi_constraints(mut self, constraints: DoiConstraints) -> Self {
+        self.doi_constraints = constraints;
+        self
+    }
+
+    /// Also match DOIs with whitespace around the prefix dot or slash
+    /// (see [`extract_spaced_doi_matches_within`])
+    pub fn with_doi_spacing_tolerance(mut self) -> Self {
+        self.doi_spacing_tolerant = true;
+        self
+    }
+
+    /// DOI matches in `text`, from the cache when the text was seen before
+    pub fn doi_matches(&mut self, text: &str) -> Vec<DoiMatch> {
+        let tolerant = self.doi_spacing_tolerant;
+        let candidate = if tolerant {
+            may_contain_spaced_doi(text)
+        } else {
+            may_contain_doi(text)
+        };
+        if !candidate {
+            self.screened += 1;
+            return Vec::new();
+        }
+        let constraints = self.doi_constraints;
+        self.lookup(
+            text,
+            |entry| &mut entry.dois,
+            |text| {
+                if tolerant {
+                    extract_spaced_doi_matches_within(text, &constraints)
+                } else {
+                    extract_doi_matches_within(text, &constraints)
+                }
+            },
+        )
+    }
+
+    /// arXiv matches in `text`, from the cache when the text was seen before
+    pub fn arxiv_matches(&mut self, text: &str) -> Vec<ArxivMatch> {
+        if !may_contain_arxiv(text) {
+            self.screened += 1;
+            return Vec::new();
+        }
+        let recall = self.arxiv_recall;
+        let categories = Arc::clone(&self.legacy_arxiv_categories);
+        self.lookup(
+            text,
+            |entry| &mut entry.arxiv,
+            |text| match recall {
+                Some(window) => {
+                    extract_arxiv_matches_near_mentions_within(text, window, &categories)
+                }
+                None => extract_arxiv_matches_within(text, &categories),
+            },
+        )
+    }
+
+    /// Lookups answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups that ran the regexes
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Lookups skipped because the text cannot hold a match
+    pub fn screened(&self) -> u64 {
+        self.screened
+    }
+
+    /// Share of lookups answered from the cache, 0.0 before any lookup
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+
+    fn lookup<T: Clone>(
+        &mut self,
+        text: &str,
+        slot: fn(&mut CachedMatches) -> &mut Option<Vec<T>>,
+        extract: impl FnOnce(&str) -> Vec<T>,
+    ) -> Vec<T> {
+        if self.entries.capacity == 0 {
+            return extract(text);
+        }
+        let slot = slot(self.entries.entry(text));
+        match slot {
+            Some(matches) => {
+                self.hits += 1;
+                matches.clone()
+            }
+            None => {
+                self.misses += 1;
+                slot.insert(extract(text)).clone()
+            }
+        }
+    }
+}
+
+impl Generations {
+    /// Entry for `text` in the current generation, moving or creating it as needed
+    fn entry(&mut self, text: &str) -> &mut CachedMatches {
+        if !self.current.contains_key(text) {
+            let entry = self.previous.remove(text).unwrap_or_default();
+            if self.current.len() >= self.capacity.div_ceil(2) {
+                self.previous = std::mem::take(&mut self.current);
+            }
+            self.current.insert(Box::from(text), entry);
+        }
+        self.current.get_mut(text).expect("entry was just inserted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::extract_doi_matches_from_text;
+
+    #[test]
+    fn test_match_cache_hits() {
+        let mut cache = MatchCache::new(10);
+        let text = "Smith (2020) doi:10.1234/abc arXiv:2403.12345";
+
+        assert_eq!(cache.doi_matches(text), extract_doi_matches_from_text(text));
+        assert_eq!(cache.doi_matches(text)[0].doi, "10.1234/abc");
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // arXiv matches are cached separately for the same text
+        assert_eq!(cache.arxiv_matches(text)[0].id, "2403.12345");
+        assert_eq!(cache.arxiv_matches(text).len(), 1);
+        assert_eq!((cache.hits(), cache.misses()), (2, 2));
+        assert_eq!(cache.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_match_cache_arxiv_recall() {
+        let text = "Kingma D. arXiv preprint 1412.6980";
+        assert!(MatchCache::new(10).arxiv_matches(text).is_empty());
+
+        let mut cache = MatchCache::new(10).with_arxiv_recall(3);
+        assert_eq!(cache.arxiv_matches(text)[0].id, "1412.6980");
+        assert_eq!(cache.arxiv_matches(text).len(), 1);
+        assert_eq!(cache.hits(), 1);
+    }
+
+    #[test]
+    fn test_match_cache_legacy_arxiv_categories() {
+        let text = "arXiv:proc/1234567";
+        assert!(MatchCache::new(10).arxiv_matches(text).is_empty());
+
+        let categories = LegacyArxivCategories::from_list("proc\n").unwrap();
+        let mut cache = MatchCache::new(10).with_legacy_arxiv_categories(categories);
+        assert_eq!(cache.arxiv_matches(text)[0].id, "proc/1234567");
+    }
+
+    #[test]
+    fn test_match_cache_doi_constraints() {
+        let text = "doi:10.123/abc";
+        assert!(MatchCache::new(10).doi_matches(text).is_empty());
+
+        let mut cache = MatchCache::new(10).with_doi_constraints(DoiConstraints {
+            min_prefix_digits: 3,
+            ..DoiConstraints::default()
+        });
+        assert_eq!(cache.doi_matches(text)[0].doi, "10.123/abc");
+    }
+
+    #[test]
+    fn test_match_cache_doi_spacing_tolerance() {
+        let text = "Smith J. J. Comput. Phys. 10 .1016/j.jcp.2020.109";
+        assert!(MatchCache::new(10).doi_matches(text).is_empty());
+
+        let mut cache = MatchCache::new(10).with_doi_spacing_tolerance();
+        assert_eq!(cache.doi_matches(text)[0].doi, "10.1016/j.jcp.2020.109");
+        assert_eq!(cache.screened(), 0);
+    }
+
+    #[test]
+    fn test_match_cache_eviction() {
+        let mut cache = MatchCache::new(4);
+        for i in 0..3 {
+            cache.doi_matches(&format!("doi:10.1234/{}", i));
+        }
+        // 0 and 1 moved to the previous generation; a hit brings 0 back
+        cache.doi_matches("doi:10.1234/0");
+        assert_eq!(cache.hits(), 1);
+        for i in 3..6 {
+            cache.doi_matches(&format!("doi:10.1234/{}", i));
+        }
+        assert!(cache.entries.current.len() + cache.entries.previous.len() <= 4);
+
+        let misses = cache.misses();
+        cache.doi_matches("doi:10.1234/1");
+        assert_eq!(cache.misses(), misses + 1);
+    }
+
+    #[test]
+    fn test_match_cache_skips_screened_text() {
+        let mut cache = MatchCache::new(10);
+        let text = "Smith J. (2020) A book. Publisher, Berlin";
+        assert!(cache.doi_matches(text).is_empty());
+        assert!(cache.arxiv_matches(text).is_empty());
+        assert_eq!((cache.hits(), cache.misses(), cache.screened()), (0, 0, 2));
+        assert!(cache.entries.current.is_empty());
+    }
+
+    #[test]
+    fn test_match_cache_disabled() {
+        let mut cache = MatchCache::new(0);
+        assert_eq!(cache.doi_matches("doi:10.1234/abc").len(), 1);
+        assert_eq!(cache.doi_matches("doi:10.1234/abc").len(), 1);
+        assert_eq!((cache.hits(), cache.misses()), (0, 0));
+        assert_eq!(cache.hit_rate(), 0.0);
+    }
+}
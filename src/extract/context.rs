@@ -0,0 +1,65 @@
+/// Extract a window of up to `chars` characters on each side of `raw` within `text`
+///
+/// Returns None if `chars` is zero or `raw` does not occur in `text`. The window is
+/// measured in characters (not bytes) so multi-byte text is never split mid-character,
+/// and surrounding whitespace is trimmed.
+pub fn extract_context(text: &str, raw: &str, chars: usize) -> Option<String> {
+    if chars == 0 || raw.is_empty() {
+        return None;
+    }
+
+    let match_start = text.find(raw)?;
+    let match_end = match_start + raw.len();
+
+    let start = text[..match_start]
+        .char_indices()
+        .rev()
+        .nth(chars - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let end = text[match_end..]
+        .char_indices()
+        .nth(chars)
+        .map(|(i, _)| match_end + i)
+        .unwrap_or(text.len());
+
+    Some(text[start..end].trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_context_window() {
+        let text = "Smith J. Some title. doi:10.1234/abc. Nature 2020";
+        assert_eq!(
+            extract_context(text, "10.1234/abc.", 5),
+            Some("doi:10.1234/abc. Natu".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_context_clamped_to_text() {
+        let text = "doi:10.1234/abc";
+        assert_eq!(
+            extract_context(text, "10.1234/abc", 100),
+            Some("doi:10.1234/abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_context_multibyte() {
+        let text = "Müller é 10.1234/abc ü";
+        assert_eq!(
+            extract_context(text, "10.1234/abc", 3),
+            Some("é 10.1234/abc ü".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_context_disabled_or_missing() {
+        assert_eq!(extract_context("doi:10.1234/abc", "10.1234/abc", 0), None);
+        assert_eq!(extract_context("no identifiers", "10.1234/abc", 10), None);
+    }
+}
@@ -2,14 +2,95 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashSet;
 
+use super::prescreen::may_contain_doi;
+use super::suffix::trim_doi_suffix;
 use super::Provenance;
 
 lazy_static! {
     /// DOI pattern - captures DOI from various formats
     /// Matches: bare DOI, doi:prefix, URL forms
+    /// Brackets are captured and balanced in normalize_doi; `;` only as part of a SICI
+    /// check character (".CO;2-X"). Registrant codes of any length are captured and
+    /// held to [`DoiConstraints`] after normalization.
     pub static ref DOI_PATTERN: Regex = Regex::new(
-        r#"(?i)(?:doi[:\s]*|(?:https?://)?(?:dx\.)?doi\.org/)?(10\.\d{4,}/[^\s\]\)>,;"']+)"#
+        r#"(?i)(?:doi[:\s]*|(?:https?://)?(?:dx\.)?doi\.org/)?(10\.\d+/(?:\.co;2-|[^\s,;"'])+)"#
     ).unwrap();
+
+    /// DOI with up to two whitespace characters on either side of the prefix dot or
+    /// slash, as OCR leaves them ("10. 1016/j.jcp", "10.1016 /j.jcp"); the gaps are
+    /// captured so matches without any can be told apart
+    static ref SPACED_DOI_PATTERN: Regex = Regex::new(
+        r#"(?i)10(\s{0,2})\.(\s{0,2})(\d+)(\s{0,2})/(\s{0,2})((?:\.co;2-|[^\s,;"'])+)"#
+    ).unwrap();
+
+    /// URLs in reference text, up to whitespace, quotes or markup; `<` and `>` before a
+    /// digit are kept, as in the SICI DOI of a doi.org URL
+    /// (`https://doi.org/10.1002/(SICI)...37:2<282::AID-JBM17>3.0.CO;2-H`)
+    static ref URL_PATTERN: Regex =
+        Regex::new(r#"(?i)\bhttps?://(?:[^\s"'<>]|[<>]\d)+"#).unwrap();
+
+    /// A whole DOI: "10." registrant code (dot-separated digits), '/', and a suffix
+    /// without whitespace or control characters; [`DoiConstraints`] bound the digits
+    static ref WELL_FORMED_DOI: Regex =
+        Regex::new(r"^10\.\d+(?:\.\d+)*/[^\s\p{Cc}]+$").unwrap();
+}
+
+/// Default fewest digits in a registrant code (`10.1234`)
+pub const DEFAULT_MIN_PREFIX_DIGITS: usize = 4;
+
+/// Default most digits in a registrant code; assigned codes have four or five
+pub const DEFAULT_MAX_PREFIX_DIGITS: usize = 9;
+
+/// Default longest DOI, in bytes after normalization
+pub const DEFAULT_MAX_DOI_LENGTH: usize = 300;
+
+/// Limits on the shape of an extracted DOI
+///
+/// Text mining picks up `10.1`–`10.999` from page ranges and version numbers, and long
+/// digit runs from tables and identifiers; neither is a DOI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoiConstraints {
+    pub min_prefix_digits: usize,
+    pub max_prefix_digits: usize,
+    pub max_length: usize,
+    /// Also trim a name and year glued onto the suffix (see [`trim_doi_suffix`])
+    pub trim_glued_author_year: bool,
+}
+
+impl Default for DoiConstraints {
+    fn default() -> Self {
+        Self {
+            min_prefix_digits: DEFAULT_MIN_PREFIX_DIGITS,
+            max_prefix_digits: DEFAULT_MAX_PREFIX_DIGITS,
+            max_length: DEFAULT_MAX_DOI_LENGTH,
+            trim_glued_author_year: false,
+        }
+    }
+}
+
+impl DoiConstraints {
+    /// Whether a normalized DOI is within the limits
+    pub fn allows(&self, doi: &str) -> bool {
+        let digits = doi.strip_prefix("10.").map_or(0, |rest| {
+            rest.bytes().take_while(u8::is_ascii_digit).count()
+        });
+        (self.min_prefix_digits..=self.max_prefix_digits).contains(&digits)
+            && doi.len() <= self.max_length
+    }
+
+    /// Check whether a string is syntactically a DOI within the limits
+    ///
+    /// Extraction only emits strings that start out as DOIs, so this flags what went
+    /// wrong later: embedded whitespace, stray control characters, or a mangled
+    /// registrant code (`10.1OOO`).
+    pub fn is_well_formed(&self, doi: &str) -> bool {
+        WELL_FORMED_DOI.is_match(doi) && self.allows(doi)
+    }
+
+    /// Trim citation text glued onto a mined DOI, before normalization
+    fn trim<'a>(&self, raw: &'a str) -> &'a str {
+        trim_doi_suffix(raw, self.trim_glued_author_year)
+    }
 }
 
 /// Represents a matched DOI with raw match text, normalized form, and provenance
@@ -36,8 +117,9 @@ impl DoiMatch {
 }
 
 /// Clean up a captured DOI string
-/// - Strip trailing punctuation
 /// - Decode URL-encoded characters
+/// - Cut at the first unbalanced closing bracket
+/// - Strip trailing punctuation
 /// - Normalize to lowercase
 pub fn normalize_doi(doi: &str) -> String {
     let mut result = doi.to_string();
@@ -55,8 +137,10 @@ pub fn normalize_doi(doi: &str) -> String {
         .replace("%3E", ">")
         .replace("%3e", ">");
 
+    result.truncate(balanced_len(&result));
+
     // Strip trailing punctuation that's likely not part of the DOI
-    let trailing_chars: &[char] = &['.', ',', ';', ':', ')', ']', '>', '"', '\'', ' '];
+    let trailing_chars: &[char] = &['.', ',', ';', ':', '"', '\'', ' '];
     while result.ends_with(trailing_chars) {
         result.pop();
     }
@@ -71,24 +155,197 @@ pub fn normalize_doi(doi: &str) -> String {
     result.to_lowercase()
 }
 
+/// Length of the longest prefix of `doi` without an unbalanced closing bracket
+///
+/// Suffixes may contain balanced brackets, as in SICI DOIs such as
+/// `10.1002/(SICI)1097-4571(199806)49:8<693::AID-ASI4>3.0.CO;2-0`, while a DOI quoted in
+/// parentheses or brackets picks up the closing one. `<` only opens a bracket before a
+/// digit, as in SICI; otherwise it starts markup (`</a>`, `<br>`) and ends the DOI.
+fn balanced_len(doi: &str) -> usize {
+    let mut open: Vec<char> = Vec::new();
+    let mut chars = doi.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let expected = match c {
+            '(' | '[' => {
+                open.push(c);
+                continue;
+            }
+            '<' if chars.peek().is_some_and(|(_, next)| next.is_ascii_digit()) => {
+                open.push(c);
+                continue;
+            }
+            '<' => return i,
+            ')' => '(',
+            ']' => '[',
+            '>' => '<',
+            _ => continue,
+        };
+        if open.pop() != Some(expected) {
+            return i;
+        }
+    }
+    doi.len()
+}
+
 /// Extract DOI matches from text
+///
+/// DOIs in URLs are read from the URL's path and query parameters, percent-decoded
+/// and cut at the end of the path or parameter, so `?doi=10.1234%2Fabc&rfr=x` gives
+/// `10.1234/abc`. The raw match of such a DOI is its encoded form in the URL.
+///
+/// DOIs outside the default [`DoiConstraints`] are dropped.
 pub fn extract_doi_matches_from_text(text: &str) -> Vec<DoiMatch> {
+    extract_doi_matches_within(text, &DoiConstraints::default())
+}
+
+/// Extract DOI matches from text, keeping those within `constraints`
+pub fn extract_doi_matches_within(text: &str, constraints: &DoiConstraints) -> Vec<DoiMatch> {
+    if !may_contain_doi(text) {
+        return Vec::new();
+    }
     let mut seen: HashSet<String> = HashSet::new();
     let mut matches = Vec::new();
+    let mut push = |normalized: String, raw: &str| {
+        if !constraints.allows(&normalized) {
+            return;
+        }
+        // Skip if we've already seen this normalized DOI
+        if seen.insert(normalized.clone()) {
+            matches.push(DoiMatch::mined(normalized, raw.to_string()));
+        }
+    };
+
+    let mut text_start = 0;
+    for url in URL_PATTERN.find_iter(text) {
+        let url_dois = dois_in_url(url.as_str(), constraints);
+        if url_dois.is_empty() {
+            continue;
+        }
+        for cap in DOI_PATTERN.captures_iter(&text[text_start..url.start()]) {
+            let raw = &cap[1];
+            push(normalize_doi(constraints.trim(raw)), raw);
+        }
+        for (doi, raw) in url_dois {
+            push(doi, raw);
+        }
+        text_start = url.end();
+    }
+    for cap in DOI_PATTERN.captures_iter(&text[text_start..]) {
+        let raw = &cap[1];
+        push(normalize_doi(constraints.trim(raw)), raw);
+    }
+
+    matches
+}
+
+/// Extract DOI matches from text, also reading DOIs with whitespace around the prefix
+/// dot or slash
+///
+/// Tolerant mode (--doi-spacing-tolerant) for OCR'd references such as
+/// "10. 1016/j.jcp.2020.109" or "10.1016 /j.jcp.2020.109". The whitespace is dropped
+/// before normalization, and the raw match is the DOI as written. A spaced DOI must
+/// start a word (not `2010. 1234/...` or `1.10. 1234/...`) and have a suffix that is
+/// not only digits, which rules out years and page ranges such as "Table 10. 2013/14".
+/// Text without such gaps gives the same matches as [`extract_doi_matches_within`].
+pub fn extract_spaced_doi_matches_within(
+    text: &str,
+    constraints: &DoiConstraints,
+) -> Vec<DoiMatch> {
+    let mut matches = extract_doi_matches_within(text, constraints);
+    let mut seen: HashSet<String> = matches.iter().map(|m| m.doi.clone()).collect();
+    for cap in SPACED_DOI_PATTERN.captures_iter(text) {
+        let whole = cap.get(0).unwrap();
+        let spaced = [1, 2, 4, 5].iter().any(|&gap| !cap[gap].is_empty());
+        let starts_word = text[..whole.start()]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric() && c != '.');
+        if !spaced || !starts_word {
+            continue;
+        }
+        let collapsed = format!("10.{}/{}", &cap[3], &cap[6]);
+        let normalized = normalize_doi(constraints.trim(&collapsed));
+        let suffix_is_number = normalized
+            .split_once('/')
+            .is_some_and(|(_, suffix)| suffix.bytes().all(|b| b.is_ascii_digit()));
+        if suffix_is_number || !constraints.allows(&normalized) {
+            continue;
+        }
+        if seen.insert(normalized.clone()) {
+            matches.push(DoiMatch::mined(normalized, whole.as_str().to_string()));
+        }
+    }
+    matches
+}
+
+/// DOIs in a URL's path and query parameters, with their encoded forms
+///
+/// The fragment is dropped; each part is percent-decoded before matching, and the
+/// raw form runs from the `10.` of the DOI to the end of its path or parameter.
+fn dois_in_url<'a>(url: &'a str, constraints: &DoiConstraints) -> Vec<(String, &'a str)> {
+    let url = url.split('#').next().unwrap_or(url);
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    // Skip the scheme and host: "10." there is an IP address, not a DOI
+    let path = path
+        .split_once("://")
+        .and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..]))
+        .unwrap_or("");
 
-    for cap in DOI_PATTERN.captures_iter(text) {
-        if let Some(doi_match) = cap.get(1) {
-            let raw = doi_match.as_str().to_string();
-            let normalized = normalize_doi(&raw);
+    let parameters = query.split('&').map(|parameter| {
+        parameter
+            .split_once('=')
+            .map_or(parameter, |(_, value)| value)
+    });
+    std::iter::once(path)
+        .chain(parameters)
+        .filter_map(|part| {
+            let raw = &part[part.find("10.")?..];
+            let decoded = percent_decode(raw);
+            let doi = DOI_PATTERN.captures(&decoded)?.get(1)?.as_str().to_string();
+            Some((normalize_doi(constraints.trim(&doi)), raw))
+        })
+        .collect()
+}
 
-            // Skip if we've already seen this normalized DOI
-            if seen.insert(normalized.clone()) {
-                matches.push(DoiMatch::mined(normalized, raw));
+/// Decode `%XX` escapes, leaving malformed ones as they are
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
             }
         }
     }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
 
-    matches
+/// Lowercase a DOI, dropping any doi.org URL or "doi:" prefix
+pub(crate) fn strip_doi_prefix(doi: &str) -> String {
+    let doi = doi.trim().to_lowercase();
+    [
+        "https://doi.org/",
+        "http://doi.org/",
+        "https://dx.doi.org/",
+        "http://dx.doi.org/",
+        "doi:",
+    ]
+    .iter()
+    .find_map(|prefix| doi.strip_prefix(prefix))
+    .map(str::to_string)
+    .unwrap_or(doi)
 }
 
 /// Extract DOI prefix (registrant code) from a DOI
@@ -101,10 +358,133 @@ pub fn doi_prefix(doi: &str) -> Option<String> {
     }
 }
 
+/// Check whether two DOIs share a registrant prefix, e.g. a publisher citing itself
+///
+/// Anything without a "10." prefix (such as an arXiv ID) never matches.
+pub fn same_prefix(a: &str, b: &str) -> bool {
+    match (doi_prefix(a), doi_prefix(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_well_formed() {
+        let defaults = DoiConstraints::default();
+        assert!(defaults.is_well_formed("10.1234/example"));
+        assert!(defaults.is_well_formed("10.1000.10/abc(1)"));
+        assert!(defaults.is_well_formed("10.48550/arxiv.2301.12345"));
+        assert!(!defaults.is_well_formed("10.1234/exa mple"));
+        assert!(!defaults.is_well_formed("10.1ooo/example"));
+        assert!(!defaults.is_well_formed("10.123/example"));
+        assert!(!defaults.is_well_formed("10.1234/"));
+        assert!(!defaults.is_well_formed("10.1234/a\u{0007}b"));
+
+        let short_prefixes = DoiConstraints {
+            min_prefix_digits: 3,
+            ..DoiConstraints::default()
+        };
+        assert!(short_prefixes.is_well_formed("10.123/example"));
+        assert!(!short_prefixes.is_well_formed("10.12/example"));
+    }
+
+    #[test]
+    fn test_doi_constraints() {
+        let defaults = DoiConstraints::default();
+        assert!(defaults.allows("10.1234/example"));
+        assert!(defaults.allows("10.48550/arxiv.2301.12345"));
+        assert!(!defaults.allows("10.123/example"));
+        assert!(!defaults.allows("10.1234567890/example"));
+        assert!(!defaults.allows(&format!("10.1234/{}", "x".repeat(300))));
+
+        let text = "pp. 10.12/34, doi:10.123456789012345678901234567890/x and 10.1234/ok";
+        let dois: Vec<_> = extract_doi_matches_from_text(text)
+            .into_iter()
+            .map(|m| m.doi)
+            .collect();
+        assert_eq!(dois, ["10.1234/ok"]);
+
+        let loose = DoiConstraints {
+            min_prefix_digits: 2,
+            max_prefix_digits: 40,
+            max_length: 20,
+            ..DoiConstraints::default()
+        };
+        let dois: Vec<_> = extract_doi_matches_within(text, &loose)
+            .into_iter()
+            .map(|m| m.doi)
+            .collect();
+        assert_eq!(dois, ["10.12/34", "10.1234/ok"]);
+    }
+
+    #[test]
+    fn test_extract_spaced_doi() {
+        let constraints = DoiConstraints::default();
+        for (text, raw) in [
+            (
+                "J. Comput. Phys. 10. 1016/j.jcp.2020.109",
+                "10. 1016/j.jcp.2020.109",
+            ),
+            (
+                "J. Comput. Phys. 10.1016 /j.jcp.2020.109.",
+                "10.1016 /j.jcp.2020.109.",
+            ),
+            (
+                "doi: 10 . 1016 / j.jcp.2020.109",
+                "10 . 1016 / j.jcp.2020.109",
+            ),
+            ("(10.1016/\nj.jcp.2020.109)", "10.1016/\nj.jcp.2020.109)"),
+        ] {
+            let matches = extract_spaced_doi_matches_within(text, &constraints);
+            assert_eq!(matches.len(), 1, "{}", text);
+            assert_eq!(matches[0].doi, "10.1016/j.jcp.2020.109", "{}", text);
+            assert_eq!(matches[0].raw, raw, "{}", text);
+            assert!(extract_doi_matches_within(text, &constraints).is_empty());
+        }
+    }
+
+    /// Tolerant mode finds nothing strict mode does not on clean references, and
+    /// nothing in spaced numbers that are not DOIs
+    #[test]
+    fn test_spaced_doi_precision() {
+        let constraints = DoiConstraints::default();
+        let clean = [
+            "Smith J. Nature 2020. doi:10.1038/s41586-020-2649-2",
+            "https://doi.org/10.1016/j.cell.2020.01.001, accessed 2021.",
+            "Doe J. (2010) 10.1002/(SICI)1097-4571(199806)49:8<693::AID-ASI4>3.0.CO;2-0",
+            "Vol. 10, pp. 1234-1250. 10.1234/abc; 10.5678/def",
+            "10.1000.10/abc and https://example.org/resolve?doi=10.1234%2Fxyz",
+            "Roe R. Phys. Rev. 10, 1016 (1998).",
+        ];
+        for text in clean {
+            assert_eq!(
+                extract_spaced_doi_matches_within(text, &constraints),
+                extract_doi_matches_within(text, &constraints),
+                "{}",
+                text
+            );
+        }
+
+        let not_dois = [
+            "Table 10. 2013/2014 enrolment",
+            "Season 2010. 1234/abc",
+            "Section 1.10. 1234/abc",
+            "pp. 10 .123/abc",
+            "ratio 10. 1016 / 2020",
+        ];
+        for text in not_dois {
+            assert!(
+                extract_spaced_doi_matches_within(text, &constraints).is_empty(),
+                "{}",
+                text
+            );
+        }
+    }
+
     #[test]
     fn test_extract_bare_doi() {
         let text = "See 10.1234/example.paper for details";
@@ -137,6 +517,151 @@ mod tests {
         assert_eq!(matches[0].doi, "10.1234/example");
     }
 
+    #[test]
+    fn test_extract_doi_with_glued_citation_text() {
+        let text = "Cell 180, 10.1016/j.cell.2020.01.001Smith J (2020)";
+        let matches = extract_doi_matches_from_text(text);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].doi, "10.1016/j.cell.2020.01.001");
+        assert_eq!(matches[0].raw, "10.1016/j.cell.2020.01.001Smith");
+
+        // A name and year glued onto another suffix is trimmed only on request
+        let text = "PLoS One 10.1371/journal.pone.0123456Nguyen2019a";
+        let matches = extract_doi_matches_from_text(text);
+        assert_eq!(matches[0].doi, "10.1371/journal.pone.0123456nguyen2019a");
+        let constraints = DoiConstraints {
+            trim_glued_author_year: true,
+            ..DoiConstraints::default()
+        };
+        let matches = extract_doi_matches_within(text, &constraints);
+        assert_eq!(matches[0].doi, "10.1371/journal.pone.0123456");
+    }
+
+    #[test]
+    fn test_extract_sici_doi() {
+        // Wiley back-catalog SICI DOIs
+        let text = "Am. Soc. Inf. Sci. doi:10.1002/(SICI)1097-4571(199806)49:8<693::AID-ASI4>3.0.CO;2-0; see also";
+        let matches = extract_doi_matches_from_text(text);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].doi,
+            "10.1002/(sici)1097-4571(199806)49:8<693::aid-asi4>3.0.co;2-0"
+        );
+
+        let text =
+            "(J. Biomed. Mater. Res. 10.1002/(SICI)1097-4636(199707)35:1<1::AID-JBM1>3.0.CO;2-D)";
+        let matches = extract_doi_matches_from_text(text);
+        assert_eq!(
+            matches[0].doi,
+            "10.1002/(sici)1097-4636(199707)35:1<1::aid-jbm1>3.0.co;2-d"
+        );
+
+        let text = "[10.1002/(SICI)1099-1085(19960315)10:3<281::AID-HYP339>3.0.CO;2-6].";
+        let matches = extract_doi_matches_from_text(text);
+        assert_eq!(
+            matches[0].doi,
+            "10.1002/(sici)1099-1085(19960315)10:3<281::aid-hyp339>3.0.co;2-6"
+        );
+    }
+
+    #[test]
+    fn test_extract_sici_doi_url() {
+        let text = "J. Biomed. Mater. Res. \
+                    https://doi.org/10.1002/(SICI)1097-4636(199711)37:2<282::AID-JBM17>3.0.CO;2-H \
+                    and https://doi.org/10.1234/abc</a>";
+        let dois: Vec<String> = extract_doi_matches_from_text(text)
+            .into_iter()
+            .map(|m| m.doi)
+            .collect();
+        assert_eq!(
+            dois,
+            [
+                "10.1002/(sici)1097-4636(199711)37:2<282::aid-jbm17>3.0.co;2-h",
+                "10.1234/abc"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_doi_in_brackets() {
+        let text = "(see 10.1016/S0140-6736(20)30183-5) and [10.1234/abc], <10.1234/def>";
+        let dois: Vec<String> = extract_doi_matches_from_text(text)
+            .into_iter()
+            .map(|m| m.doi)
+            .collect();
+        assert_eq!(
+            dois,
+            vec![
+                "10.1016/s0140-6736(20)30183-5",
+                "10.1234/abc",
+                "10.1234/def"
+            ]
+        );
+
+        let matches = extract_doi_matches_from_text("<a>10.1234/ghi</a>; 10.1234/a;10.1234/b");
+        let dois: Vec<&str> = matches.iter().map(|m| m.doi.as_str()).collect();
+        assert_eq!(dois, vec!["10.1234/ghi", "10.1234/a", "10.1234/b"]);
+    }
+
+    #[test]
+    fn test_extract_doi_from_link_resolver_urls() {
+        let cases = [
+            // SFX OpenURL from Google Scholar
+            (
+                "https://sfx.example.edu/sfx_local?sid=google&aulast=Smith&title=Nature&doi=10.1038%2Fnature12373&rfr_id=info%3Asid%2Fgoogle",
+                "10.1038/nature12373",
+                "10.1038%2Fnature12373",
+            ),
+            // OpenURL 1.0 rft_id
+            (
+                "http://resolver.example.org/openurl?url_ver=Z39.88-2004&rft_id=info:doi/10.1126%2Fscience.1234567&rft.atitle=Example",
+                "10.1126/science.1234567",
+                "10.1126%2Fscience.1234567",
+            ),
+            // Scholar lookup with an unencoded DOI
+            (
+                "https://scholar.google.com/scholar_lookup?title=Cells&doi=10.1016/j.cell.2020.01.001&publication_year=2020",
+                "10.1016/j.cell.2020.01.001",
+                "10.1016/j.cell.2020.01.001",
+            ),
+            // Publisher landing page with tracking parameters
+            (
+                "https://onlinelibrary.wiley.com/doi/10.1002/anie.201915678?af=R",
+                "10.1002/anie.201915678",
+                "10.1002/anie.201915678",
+            ),
+            // Encoded slash and a fragment on doi.org
+            (
+                "https://doi.org/10.1000%2F182#section-2",
+                "10.1000/182",
+                "10.1000%2F182",
+            ),
+        ];
+        for (url, doi, raw) in cases {
+            let text = format!(
+                "Smith J (2020) Example. Available at {} (accessed 2021)",
+                url
+            );
+            let matches = extract_doi_matches_from_text(&text);
+            assert_eq!(matches.len(), 1, "{}", url);
+            assert_eq!(matches[0].doi, doi);
+            assert_eq!(matches[0].raw, raw);
+            assert!(text.contains(&matches[0].raw));
+        }
+    }
+
+    #[test]
+    fn test_extract_doi_around_urls() {
+        let text = "doi:10.1234/first. See http://10.0.0.1/report?id=7 and \
+                    https://example.org/?doi=10.5678%2Fsecond&x=1; also 10.9999/third";
+        let dois: Vec<_> = extract_doi_matches_from_text(text)
+            .into_iter()
+            .map(|m| m.doi)
+            .collect();
+        assert_eq!(dois, ["10.1234/first", "10.5678/second", "10.9999/third"]);
+        assert_eq!(percent_decode("10.1234%2fa%zz%"), "10.1234/a%zz%");
+    }
+
     #[test]
     fn test_normalize_trailing_punctuation() {
         assert_eq!(normalize_doi("10.1234/test."), "10.1234/test");
@@ -184,10 +709,10 @@ mod tests {
         let m = DoiMatch::new(
             "10.1234/test".to_string(),
             "10.1234/test".to_string(),
-            Provenance::Publisher,
+            Provenance::PublisherAsserted,
         );
         assert_eq!(m.doi, "10.1234/test");
-        assert_eq!(m.provenance, Provenance::Publisher);
+        assert_eq!(m.provenance, Provenance::PublisherAsserted);
     }
 
     #[test]
@@ -195,4 +720,12 @@ mod tests {
         let m = DoiMatch::mined("10.1234/test".to_string(), "10.1234/test".to_string());
         assert_eq!(m.provenance, Provenance::Mined);
     }
+
+    #[test]
+    fn test_same_prefix() {
+        assert!(same_prefix("10.1016/j.a.1", "10.1016/J.B.2"));
+        assert!(!same_prefix("10.1016/j.a.1", "10.1103/physrevd.1"));
+        assert!(!same_prefix("10.1016/j.a.1", "2403.12345"));
+        assert!(!same_prefix("10.1016/j.a.1", "hep-ph/9901234"));
+    }
 }
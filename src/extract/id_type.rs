@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// Identifier family of a cited ID
+///
+/// Each extracted edge records the family of its cited ID, in partition files as the
+/// `id_type` column and in inverted records as the `id_type` field, so consumers can
+/// tell DOIs from arXiv IDs without parsing them. It also decides how IDs are routed to
+/// partitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdType {
+    /// DOI, including arXiv DOIs (10.48550/arXiv.*)
+    Doi,
+    /// Bare arXiv ID, modern (2403.12345) or old-style (hep-ph/9901234)
+    Arxiv,
+}
+
+impl IdType {
+    /// Family of a normalized cited ID: anything starting with a DOI prefix is a DOI
+    pub fn of(id: &str) -> Self {
+        if id.starts_with("10.") {
+            IdType::Doi
+        } else {
+            IdType::Arxiv
+        }
+    }
+
+    /// Name of the family, as written to partition files and outputs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdType::Doi => "doi",
+            IdType::Arxiv => "arxiv",
+        }
+    }
+
+    /// Partition key of an ID of this family
+    ///
+    /// DOIs are partitioned by their lowercased prefix and arXiv IDs by their first
+    /// four characters. A DOI without a slash falls back to the arXiv rule.
+    pub fn partition_key(&self, id: &str) -> String {
+        if *self == IdType::Doi {
+            if let Some(slash_pos) = id.find('/') {
+                return id[..slash_pos].to_lowercase();
+            }
+        }
+        id.to_lowercase()
+            .chars()
+            .take(4)
+            .map(|c| if c == '/' { '_' } else { c })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id_type_of() {
+        assert_eq!(IdType::of("10.1234/example"), IdType::Doi);
+        assert_eq!(IdType::of("10.48550/arXiv.2403.12345"), IdType::Doi);
+        assert_eq!(IdType::of("2403.12345"), IdType::Arxiv);
+        assert_eq!(IdType::of("hep-ph/9901234"), IdType::Arxiv);
+    }
+
+    #[test]
+    fn test_id_type_serialization() {
+        assert_eq!(serde_json::to_string(&IdType::Doi).unwrap(), "\"doi\"");
+        assert_eq!(
+            serde_json::from_str::<IdType>("\"arxiv\"").unwrap(),
+            IdType::Arxiv
+        );
+        for id_type in [IdType::Doi, IdType::Arxiv] {
+            assert_eq!(
+                serde_json::to_string(&id_type).unwrap(),
+                format!("\"{}\"", id_type.as_str())
+            );
+        }
+    }
+}
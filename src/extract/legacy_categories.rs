@@ -0,0 +1,142 @@
+use lazy_static::lazy_static;
+use log::info;
+use std::collections::HashSet;
+use std::fs;
+
+use super::arxiv_id_category;
+use crate::error::{Context, Error, Result};
+
+/// Category list shipped with the binary (`src/extract/legacy_arxiv_categories.txt`)
+const BUNDLED_CATEGORIES: &str = include_str!("legacy_arxiv_categories.txt");
+
+lazy_static! {
+    static ref BUNDLED: LegacyArxivCategories = {
+        let mut categories = LegacyArxivCategories::default();
+        categories
+            .extend_from_list(BUNDLED_CATEGORIES)
+            .expect("bundled arXiv category list is well-formed");
+        categories
+    };
+}
+
+/// Categories accepted in old-format arXiv IDs (category/YYMMNNN)
+///
+/// The old-format patterns take any word before the slash, so text like
+/// "arXiv:proc/1234567" would otherwise read as an ID. The bundled list holds the
+/// archives and subject classes arXiv used until March 2007; a file in the same format
+/// (one category per line, `#` comments) replaces it.
+#[derive(Debug, Clone, Default)]
+pub struct LegacyArxivCategories {
+    /// Lowercase categories
+    categories: HashSet<String>,
+}
+
+impl LegacyArxivCategories {
+    /// The bundled list
+    pub fn bundled() -> Self {
+        BUNDLED.clone()
+    }
+
+    /// The list in `path` when given, the bundled one otherwise
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::bundled());
+        };
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path))?;
+        let mut categories = Self::default();
+        let added = categories
+            .extend_from_list(&content)
+            .map_err(|e| e.context(format!("Invalid arXiv category list {}", path)))?;
+        info!("Loaded {} legacy arXiv categories from: {}", added, path);
+        Ok(categories)
+    }
+
+    /// The categories of a one-per-line list, as a file for [`Self::load`] holds them
+    #[cfg(test)]
+    pub(super) fn from_list(content: &str) -> Result<Self> {
+        let mut categories = Self::default();
+        categories.extend_from_list(content)?;
+        Ok(categories)
+    }
+
+    /// Add the categories of a one-per-line list, returning how many were read
+    fn extend_from_list(&mut self, content: &str) -> Result<usize> {
+        let mut added = 0;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.contains(|c: char| c.is_whitespace() || c == '/') {
+                return Err(Error::invalid(format!(
+                    "line {}: expected one category, as in hep-ph or math.ag",
+                    i + 1
+                )));
+            }
+            self.categories.insert(line.to_lowercase());
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Whether an arXiv ID may be real: modern IDs always, old-format IDs when their
+    /// category is listed
+    pub fn accepts(&self, id: &str) -> bool {
+        match arxiv_id_category(id) {
+            Some(category) => self.categories.contains(&category),
+            None => true,
+        }
+    }
+
+    /// Number of categories listed
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.categories.len()
+    }
+}
+
+/// The bundled list, without cloning it
+#[cfg(test)]
+pub(super) fn bundled_categories() -> &'static LegacyArxivCategories {
+    &BUNDLED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_categories() {
+        let categories = LegacyArxivCategories::bundled();
+        for id in [
+            "hep-ph/9901234",
+            "astro-ph/0601001",
+            "cs.dm/9910013",
+            "math.ag/0101001",
+            "cond-mat.str-el/0501001",
+            "2403.03542",
+        ] {
+            assert!(categories.accepts(id), "{}", id);
+        }
+        for id in ["proc/1234567", "phys/9901234", "math.zz/0101001"] {
+            assert!(!categories.accepts(id), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_list_replaces_bundled_list() {
+        let categories =
+            LegacyArxivCategories::from_list("# local list\nproc\n\nHEP-TH\n").unwrap();
+        assert_eq!(categories.len(), 2);
+        assert!(categories.accepts("proc/1234567"));
+        assert!(categories.accepts("hep-th/9711200"));
+        assert!(!categories.accepts("hep-ph/9901234"));
+
+        let error = LegacyArxivCategories::from_list("hep-ph\nhep-ph/9901234\n").unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+
+        let bundled = LegacyArxivCategories::load(None).unwrap();
+        assert_eq!(bundled.len(), bundled_categories().len());
+    }
+}
@@ -0,0 +1,101 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+
+lazy_static! {
+    /// Character and named entity references, e.g. `&lt;`, `&#x2F;`, `&#47;`
+    static ref ENTITY: Regex = Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]{2,8});").unwrap();
+
+    /// HTML/JATS tags; `<` followed by a digit or space (e.g. `p<0.05`) is not a tag
+    static ref TAG: Regex = Regex::new(r#"</?[A-Za-z][\w:.-]*(?:\s[^<>]*)?/?>"#).unwrap();
+
+    /// Link target of an `href` or `xlink:href` attribute
+    static ref HREF: Regex = Regex::new(r#"(?i)\bhref\s*=\s*["']([^"']+)["']"#).unwrap();
+}
+
+/// Strip HTML/JATS markup from reference text before pattern matching
+///
+/// Entities are decoded first, twice, so escaped markup (`&lt;italic&gt;`) and
+/// double-escaped ampersands become plain text. Tags are then removed without
+/// adding whitespace, which rejoins a DOI split by inline markup
+/// (`10.1234/<italic>abc</italic>`). A tag's `href` target is kept, surrounded by
+/// spaces, since `<ext-link>` often carries the DOI only in its link. Text
+/// without `<` or `&` is returned borrowed.
+pub fn strip_markup(text: &str) -> Cow<'_, str> {
+    if !text.contains(['<', '&']) {
+        return Cow::Borrowed(text);
+    }
+
+    let decoded = decode_entities(&decode_entities(text)).into_owned();
+    let stripped = TAG.replace_all(&decoded, |caps: &Captures| match HREF.captures(&caps[0]) {
+        Some(href) => format!(" {} ", &href[1]),
+        None => String::new(),
+    });
+    Cow::Owned(stripped.into_owned())
+}
+
+fn decode_entities(text: &str) -> Cow<'_, str> {
+    ENTITY.replace_all(text, |caps: &Captures| {
+        let name = &caps[1];
+        let decoded = match name.strip_prefix('#') {
+            Some(code) => {
+                let value = match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code.parse().ok(),
+                };
+                value.and_then(char::from_u32)
+            }
+            None => match name {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "ndash" => Some('\u{2013}'),
+                "mdash" => Some('\u{2014}'),
+                _ => None,
+            },
+        };
+        match decoded {
+            Some(c) => c.to_string(),
+            None => caps[0].to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_is_borrowed() {
+        let text = "Smith J (2020) Nature. doi:10.1234/abc";
+        assert!(matches!(strip_markup(text), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_strip_tags_and_entities() {
+        assert_eq!(
+            strip_markup("Smith, <italic>Nature</italic>, doi:10.1234/<b>abc</b>."),
+            "Smith, Nature, doi:10.1234/abc."
+        );
+        assert_eq!(
+            strip_markup(
+                "&lt;italic&gt;Cell&lt;/italic&gt; 10.1016/j.cell.2020.01.001&lt;/ext-link&gt;"
+            ),
+            "Cell 10.1016/j.cell.2020.01.001"
+        );
+        assert_eq!(
+            strip_markup("A &amp;amp; B &#x2F; C &#47; D"),
+            "A & B / C / D"
+        );
+        assert_eq!(strip_markup("p<0.05 &unknown;"), "p<0.05 &unknown;");
+    }
+
+    #[test]
+    fn test_href_target_is_kept() {
+        let text = r#"See <ext-link ext-link-type="uri" xlink:href="https://doi.org/10.5678/xyz">link</ext-link>."#;
+        assert_eq!(strip_markup(text), "See  https://doi.org/10.5678/xyz link.");
+    }
+}
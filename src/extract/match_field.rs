@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Reference field a raw match was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchField {
+    /// The reference's explicit DOI field
+    Doi,
+    /// The URL field
+    Url,
+    /// The article-title field
+    ArticleTitle,
+    /// The journal-title field
+    JournalTitle,
+    /// The unstructured citation string
+    Unstructured,
+}
+
+impl MatchField {
+    /// Fields searched for matches, in the order they are concatenated for extraction
+    pub const SEARCH_ORDER: [MatchField; 5] = [
+        MatchField::Doi,
+        MatchField::Url,
+        MatchField::ArticleTitle,
+        MatchField::JournalTitle,
+        MatchField::Unstructured,
+    ];
+
+    /// Get string representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchField::Doi => "doi",
+            MatchField::Url => "url",
+            MatchField::ArticleTitle => "article-title",
+            MatchField::JournalTitle => "journal-title",
+            MatchField::Unstructured => "unstructured",
+        }
+    }
+
+    /// Key of this field in a Crossref reference object
+    pub fn json_key(&self) -> &'static str {
+        match self {
+            MatchField::Doi => "DOI",
+            MatchField::Url => "URL",
+            MatchField::ArticleTitle => "article-title",
+            MatchField::JournalTitle => "journal-title",
+            MatchField::Unstructured => "unstructured",
+        }
+    }
+
+    /// Find the first searched field of `reference` containing `raw_match`, and where
+    pub fn locate(reference: &Value, raw_match: &str) -> Option<(MatchField, MatchSpan)> {
+        Self::SEARCH_ORDER.into_iter().find_map(|field| {
+            let text = reference.get(field.json_key())?.as_str()?;
+            let start = text.find(raw_match)?;
+            Some((
+                field,
+                MatchSpan::from_byte_range(text, start, raw_match.len()),
+            ))
+        })
+    }
+}
+
+/// Position of a raw match within its reference field
+///
+/// Offsets count Unicode characters (code points) from the start of the field, end
+/// exclusive, so `field.chars().skip(start).take(end - start)` is the raw match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchSpan {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl MatchSpan {
+    /// Span of the `len` bytes at byte offset `start` of `text`
+    fn from_byte_range(text: &str, start: usize, len: usize) -> Self {
+        let start_chars = text[..start].chars().count();
+        let len_chars = text[start..start + len].chars().count();
+        Self {
+            start: start_chars as u32,
+            end: (start_chars + len_chars) as u32,
+        }
+    }
+}
+
+impl std::fmt::Display for MatchField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_match_field_serialization() {
+        assert_eq!(
+            serde_json::to_string(&MatchField::ArticleTitle).unwrap(),
+            "\"article-title\""
+        );
+        assert_eq!(
+            serde_json::from_str::<MatchField>("\"unstructured\"").unwrap(),
+            MatchField::Unstructured
+        );
+        for field in MatchField::SEARCH_ORDER {
+            assert_eq!(
+                serde_json::to_string(&field).unwrap(),
+                format!("\"{}\"", field.as_str())
+            );
+        }
+    }
+
+    #[test]
+    fn test_locate_match_field() {
+        let reference = json!({
+            "DOI": "10.1234/asserted",
+            "URL": "https://doi.org/10.1234/from-url",
+            "unstructured": "Smith. doi:10.1234/mined. See also 10.1234/asserted"
+        });
+
+        // DOI field takes precedence when the match appears in several fields
+        let field = |raw: &str| MatchField::locate(&reference, raw).map(|(field, _)| field);
+        assert_eq!(field("10.1234/asserted"), Some(MatchField::Doi));
+        assert_eq!(field("10.1234/from-url"), Some(MatchField::Url));
+        assert_eq!(field("10.1234/mined."), Some(MatchField::Unstructured));
+        assert_eq!(MatchField::locate(&reference, "10.1234/absent"), None);
+    }
+
+    #[test]
+    fn test_locate_span_counts_characters() {
+        let reference = json!({"unstructured": "Müller, Ł. (2020) doi:10.1234/ü-1"});
+        let (field, span) = MatchField::locate(&reference, "10.1234/ü-1").unwrap();
+        assert_eq!(field, MatchField::Unstructured);
+        assert_eq!(span, MatchSpan { start: 22, end: 33 });
+
+        let text = reference["unstructured"].as_str().unwrap();
+        let located: String = text
+            .chars()
+            .skip(span.start as usize)
+            .take((span.end - span.start) as usize)
+            .collect();
+        assert_eq!(located, "10.1234/ü-1");
+    }
+}
@@ -1,7 +1,27 @@
 pub mod arxiv;
+mod cache;
+mod context;
 pub mod doi;
+mod id_type;
+mod legacy_categories;
+mod markup;
+mod match_field;
+mod prescreen;
 mod provenance;
+mod suffix;
 
 pub use arxiv::*;
+pub use cache::MatchCache;
+pub use context::extract_context;
 pub use doi::*;
-pub use provenance::Provenance;
+pub use id_type::IdType;
+pub use legacy_categories::LegacyArxivCategories;
+pub use markup::strip_markup;
+pub use match_field::{MatchField, MatchSpan};
+pub use prescreen::{may_contain_arxiv, may_contain_doi, may_contain_spaced_doi};
+#[cfg(feature = "parquet")]
+pub use provenance::ASSERTED_PROVENANCES;
+pub use provenance::{Provenance, UnattributedPolicy};
+// Re-export trim_doi_suffix for library users
+#[allow(unused_imports)]
+pub use suffix::trim_doi_suffix;
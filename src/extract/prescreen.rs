@@ -0,0 +1,103 @@
+use lazy_static::lazy_static;
+use memchr::memmem::Finder;
+
+lazy_static! {
+    /// Start of every DOI: DOI_PATTERN, URL DOIs and arXiv DOIs all need "10."
+    static ref DOI_START: Finder<'static> = Finder::new("10.");
+}
+
+/// Whether `text` can hold a DOI match
+///
+/// A SIMD substring search for "10.", which every DOI pattern requires literally
+/// (percent-encoded URL DOIs are decoded only after their "10." is found). Most
+/// reference texts have no candidate, and for those this skips the regexes.
+pub fn may_contain_doi(text: &str) -> bool {
+    DOI_START.find(text.as_bytes()).is_some()
+}
+
+/// Whether `text` can hold a DOI match in tolerant mode
+///
+/// As [`may_contain_doi`], but also passes "10" followed by whitespace and a dot, the
+/// start of a spaced DOI such as "10 .1016/j.jcp".
+pub fn may_contain_spaced_doi(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    may_contain_doi(text)
+        || memchr::memmem::find_iter(bytes, "10").any(|i| {
+            bytes[i + 2..]
+                .iter()
+                .find(|b| !b.is_ascii_whitespace())
+                .is_some_and(|&b| b == b'.')
+        })
+}
+
+/// Whether `text` can hold an arXiv match
+///
+/// Every arXiv pattern, the recall mode's mention included, needs "arxiv" in some
+/// ASCII case. Candidates for its first letter are found with memchr and the rest is
+/// compared in place.
+pub fn may_contain_arxiv(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    memchr::memchr2_iter(b'a', b'A', bytes)
+        .any(|i| bytes[i..].len() >= 5 && bytes[i + 1..i + 5].eq_ignore_ascii_case(b"rxiv"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::arxiv::{
+        ARXIV_DOI_PATTERN, ARXIV_MENTION_PATTERN, ARXIV_MODERN_PATTERN, ARXIV_OLD_FORMAT_PATTERN,
+        ARXIV_URL_PATTERN,
+    };
+    use crate::extract::doi::DOI_PATTERN;
+
+    #[test]
+    fn test_prescreen_passes_every_regex_match() {
+        let texts = [
+            "See 10.1234/example.paper for details",
+            "https://example.org/resolve?doi=10.1234%2Fabc",
+            "arXiv:hep-ph/9901234",
+            "https://ARXIV.org/abs/2403.12345",
+            "Kingma D. ArXiv preprint 1412.6980",
+            "10.48550/arXiv.2403.12345",
+        ];
+        let arxiv_patterns = [
+            &*ARXIV_MODERN_PATTERN,
+            &*ARXIV_OLD_FORMAT_PATTERN,
+            &*ARXIV_DOI_PATTERN,
+            &*ARXIV_URL_PATTERN,
+            &*ARXIV_MENTION_PATTERN,
+        ];
+        for text in texts {
+            assert!(
+                may_contain_doi(text) || !DOI_PATTERN.is_match(text),
+                "{}",
+                text
+            );
+            assert!(
+                may_contain_arxiv(text) || !arxiv_patterns.iter().any(|p| p.is_match(text)),
+                "{}",
+                text
+            );
+        }
+        // URL DOIs are matched after decoding, so their "10." must be literal too
+        let url = "https://example.org/resolve?doi=10.1234%2Fabc";
+        assert!(!crate::extract::extract_doi_matches_from_text(url).is_empty());
+    }
+
+    #[test]
+    fn test_prescreen_rejects_text_without_candidates() {
+        let text = "Smith J. (2020) Proc. 10th Conf., pp. 1-9. Academic Press";
+        assert!(!may_contain_doi(text));
+        assert!(!may_contain_arxiv(text));
+        assert!(!may_contain_arxiv("ends with arxi"));
+    }
+
+    #[test]
+    fn test_prescreen_spaced_doi() {
+        assert!(may_contain_spaced_doi("See 10.1234/x"));
+        assert!(may_contain_spaced_doi("See 10 .1234/x"));
+        assert!(!may_contain_doi("See 10 .1234/x"));
+        assert!(!may_contain_spaced_doi("Proc. 10th Conf., pp. 10 - 19"));
+        assert!(!may_contain_spaced_doi("ends with 10"));
+    }
+}
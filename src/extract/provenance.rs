@@ -1,26 +1,64 @@
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 /// Provenance of a DOI reference - how it was obtained
+///
+/// Serialized names are unchanged from the original three-way model (publisher,
+/// crossref, mined); `structured` is only produced under
+/// `UnattributedPolicy::Structured`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
 pub enum Provenance {
     /// Mined from unstructured text (lowest quality)
+    #[serde(rename = "mined")]
     Mined = 0,
+    /// Structured DOI field whose `doi-asserted-by` is missing or unrecognized
+    #[serde(rename = "structured")]
+    StructuredUnattributed = 1,
     /// Matched by Crossref
-    Crossref = 1,
+    #[serde(rename = "crossref")]
+    CrossrefMatched = 2,
     /// Explicitly provided by publisher (highest quality)
-    Publisher = 2,
+    #[serde(rename = "publisher")]
+    PublisherAsserted = 3,
 }
 
+/// Serialized provenances counted as asserted in `_asserted`/`_mined` splits and counts
+pub const ASSERTED_PROVENANCES: [&str; 2] = ["publisher", "crossref"];
+
 impl Provenance {
     /// Get string representation
     pub fn as_str(&self) -> &'static str {
         match self {
-            Provenance::Publisher => "publisher",
-            Provenance::Crossref => "crossref",
+            Provenance::PublisherAsserted => "publisher",
+            Provenance::CrossrefMatched => "crossref",
+            Provenance::StructuredUnattributed => "structured",
             Provenance::Mined => "mined",
         }
     }
+
+    /// Provenance of a DOI taken from a reference's structured DOI field
+    ///
+    /// `doi-asserted-by` is compared case-insensitively; a missing or unrecognized
+    /// value is mapped by `policy`.
+    pub fn from_asserted_by(asserted_by: Option<&str>, policy: UnattributedPolicy) -> Self {
+        match asserted_by
+            .map(|value| value.trim().to_lowercase())
+            .as_deref()
+        {
+            Some("publisher") => Provenance::PublisherAsserted,
+            Some("crossref") => Provenance::CrossrefMatched,
+            _ => match policy {
+                UnattributedPolicy::Mined => Provenance::Mined,
+                UnattributedPolicy::Structured => Provenance::StructuredUnattributed,
+                UnattributedPolicy::Crossref => Provenance::CrossrefMatched,
+            },
+        }
+    }
+
+    /// Whether a serialized provenance counts as asserted (publisher or Crossref)
+    pub fn is_asserted_str(provenance: &str) -> bool {
+        ASSERTED_PROVENANCES.contains(&provenance)
+    }
 }
 
 impl std::fmt::Display for Provenance {
@@ -29,6 +67,44 @@ impl std::fmt::Display for Provenance {
     }
 }
 
+/// How to label a structured DOI whose `doi-asserted-by` is missing or unrecognized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnattributedPolicy {
+    /// Label it mined, like a DOI found in free text (the original behavior)
+    #[default]
+    Mined,
+    /// Label it `structured`, kept apart from both asserted and mined DOIs
+    Structured,
+    /// Treat it as Crossref-matched
+    Crossref,
+}
+
+impl FromStr for UnattributedPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mined" => Ok(UnattributedPolicy::Mined),
+            "structured" => Ok(UnattributedPolicy::Structured),
+            "crossref" => Ok(UnattributedPolicy::Crossref),
+            _ => Err(format!(
+                "Invalid unattributed DOI policy: {}. Valid options: mined, structured, crossref",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for UnattributedPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnattributedPolicy::Mined => write!(f, "mined"),
+            UnattributedPolicy::Structured => write!(f, "structured"),
+            UnattributedPolicy::Crossref => write!(f, "crossref"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,11 +112,11 @@ mod tests {
     #[test]
     fn test_provenance_serialization() {
         assert_eq!(
-            serde_json::to_string(&Provenance::Publisher).unwrap(),
+            serde_json::to_string(&Provenance::PublisherAsserted).unwrap(),
             "\"publisher\""
         );
         assert_eq!(
-            serde_json::to_string(&Provenance::Crossref).unwrap(),
+            serde_json::to_string(&Provenance::CrossrefMatched).unwrap(),
             "\"crossref\""
         );
         assert_eq!(
@@ -53,11 +129,11 @@ mod tests {
     fn test_provenance_deserialization() {
         assert_eq!(
             serde_json::from_str::<Provenance>("\"publisher\"").unwrap(),
-            Provenance::Publisher
+            Provenance::PublisherAsserted
         );
         assert_eq!(
             serde_json::from_str::<Provenance>("\"crossref\"").unwrap(),
-            Provenance::Crossref
+            Provenance::CrossrefMatched
         );
         assert_eq!(
             serde_json::from_str::<Provenance>("\"mined\"").unwrap(),
@@ -67,15 +143,46 @@ mod tests {
 
     #[test]
     fn test_provenance_ordering() {
-        // Publisher > Crossref > Mined (for deduplication preference)
-        assert!(Provenance::Publisher > Provenance::Crossref);
-        assert!(Provenance::Crossref > Provenance::Mined);
+        // Publisher > Crossref > Structured > Mined (for deduplication preference)
+        assert!(Provenance::PublisherAsserted > Provenance::CrossrefMatched);
+        assert!(Provenance::CrossrefMatched > Provenance::StructuredUnattributed);
+        assert!(Provenance::StructuredUnattributed > Provenance::Mined);
     }
 
     #[test]
     fn test_provenance_to_string() {
-        assert_eq!(Provenance::Publisher.as_str(), "publisher");
-        assert_eq!(Provenance::Crossref.as_str(), "crossref");
+        assert_eq!(Provenance::PublisherAsserted.as_str(), "publisher");
+        assert_eq!(Provenance::CrossrefMatched.as_str(), "crossref");
         assert_eq!(Provenance::Mined.as_str(), "mined");
+        assert_eq!(Provenance::StructuredUnattributed.as_str(), "structured");
+        assert_eq!(
+            serde_json::to_string(&Provenance::StructuredUnattributed).unwrap(),
+            "\"structured\""
+        );
+    }
+
+    #[test]
+    fn test_provenance_from_asserted_by() {
+        use UnattributedPolicy::*;
+
+        assert_eq!(
+            Provenance::from_asserted_by(Some("Publisher"), Mined),
+            Provenance::PublisherAsserted
+        );
+        assert_eq!(
+            Provenance::from_asserted_by(Some("crossref"), Structured),
+            Provenance::CrossrefMatched
+        );
+        assert_eq!(Provenance::from_asserted_by(None, Mined), Provenance::Mined);
+        assert_eq!(
+            Provenance::from_asserted_by(Some("author"), Structured),
+            Provenance::StructuredUnattributed
+        );
+        assert_eq!(
+            Provenance::from_asserted_by(None, Crossref),
+            Provenance::CrossrefMatched
+        );
+        assert_eq!("Structured".parse(), Ok(Structured));
+        assert!("other".parse::<UnattributedPolicy>().is_err());
     }
 }
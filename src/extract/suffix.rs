@@ -0,0 +1,186 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// Suffix shapes of high-volume publishers; text glued after a complete suffix is
+    /// citation text rather than part of the DOI
+    static ref PUBLISHER_SUFFIXES: Vec<Regex> = [
+        // Elsevier journal articles: 10.1016/j.cell.2020.01.001
+        r"(?i)^10\.1016/j\.[a-z0-9]+\.\d{4}\.\d{2}\.\d{3}",
+        // Springer Nature journal articles: 10.1007/s00134-020-05991-x
+        r"(?i)^10\.1007/s\d{5}-\d{3}-\d{4,5}-[\dx]",
+        // Springer books and chapters: 10.1007/978-3-030-58452-8_13
+        r"(?i)^10\.1007/978-\d{1,5}-\d{1,7}-\d{1,7}-[\dx](?:_\d+)?",
+        // IEEE: 10.1109/TPAMI.2019.2913372, 10.1109/CVPR42600.2020.01234, 10.1109/5.771073
+        r"(?i)^10\.1109/(?:[a-z]+\d*\.\d{4}|\d+)\.\d+",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect();
+
+    /// An author name and year glued to the end of a suffix: "...0123456Smith2020"
+    static ref GLUED_AUTHOR_YEAR: Regex =
+        Regex::new(r"[a-z0-9]([A-Z][a-z]{2,}(?:19|20)\d{2}[a-z]?)$").unwrap();
+}
+
+/// Trim citation text glued onto the end of a mined DOI
+///
+/// For Elsevier, Springer and IEEE DOIs, anything after a complete suffix that starts
+/// with a letter (optionally after punctuation) is dropped. With `glued_author_year`
+/// (--trim-glued-author-year), a capitalized name and year appended to a lowercase or
+/// numeric suffix of any other DOI is dropped too; free-form suffixes can end that way,
+/// so it is off by default (see `suffix_labels.tsv` for the measured rates). Case
+/// matters for the second rule, so this runs before normalization.
+pub fn trim_doi_suffix(raw: &str, glued_author_year: bool) -> &str {
+    for pattern in PUBLISHER_SUFFIXES.iter() {
+        if let Some(m) = pattern.find(raw) {
+            let rest = raw[m.end()..].trim_start_matches(['.', ',', ':', ';']);
+            if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return &raw[..m.end()];
+            }
+            return raw;
+        }
+    }
+    if !glued_author_year {
+        return raw;
+    }
+
+    match GLUED_AUTHOR_YEAR.captures(raw).and_then(|cap| cap.get(1)) {
+        Some(glued) => &raw[..glued.start()],
+        None => raw,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_publisher_suffixes() {
+        for glued_author_year in [false, true] {
+            let trim = |raw| trim_doi_suffix(raw, glued_author_year);
+            assert_eq!(
+                trim("10.1016/j.cell.2020.01.001Smith"),
+                "10.1016/j.cell.2020.01.001"
+            );
+            assert_eq!(
+                trim("10.1016/J.JHEP.2019.12.020.Accessed"),
+                "10.1016/J.JHEP.2019.12.020"
+            );
+            assert_eq!(
+                trim("10.1007/s00134-020-05991-xIntensive"),
+                "10.1007/s00134-020-05991-x"
+            );
+            assert_eq!(
+                trim("10.1007/978-3-030-58452-8_13Lecture"),
+                "10.1007/978-3-030-58452-8_13"
+            );
+            assert_eq!(
+                trim("10.1109/TPAMI.2019.2913372IEEE"),
+                "10.1109/TPAMI.2019.2913372"
+            );
+            assert_eq!(trim("10.1109/5.771073Proceedings"), "10.1109/5.771073");
+        }
+    }
+
+    #[test]
+    fn test_keep_complete_suffixes() {
+        for doi in [
+            "10.1016/j.cell.2020.01.001",
+            "10.1016/S0140-6736",
+            "10.1007/s00134-020-05991-x",
+            "10.1109/CVPR42600.2020.01234",
+            "10.1103/PhysRevLett.123.456",
+            "10.1371/journal.pone.0123456",
+            "10.1093/nar/gkz2020",
+        ] {
+            assert_eq!(trim_doi_suffix(doi, true), doi);
+        }
+        // Not a recognized shape, so nothing after it is trimmed
+        assert_eq!(
+            trim_doi_suffix("10.1016/j.cell.2020.1Smith", true),
+            "10.1016/j.cell.2020.1Smith"
+        );
+    }
+
+    #[test]
+    fn test_trim_glued_author_year() {
+        assert_eq!(trim_doi_suffix("10.1000/xyzSmith2020", true), "10.1000/xyz");
+        assert_eq!(
+            trim_doi_suffix("10.1371/journal.pone.0123456Nguyen2019a", true),
+            "10.1371/journal.pone.0123456"
+        );
+        assert_eq!(
+            trim_doi_suffix("10.1000/Smith2020", true),
+            "10.1000/Smith2020"
+        );
+        // Off by default
+        assert_eq!(
+            trim_doi_suffix("10.1000/xyzSmith2020", false),
+            "10.1000/xyzSmith2020"
+        );
+    }
+
+    /// Trims made and trims gone wrong over the labeled fixture
+    #[derive(Debug, Default, PartialEq)]
+    struct TrimCounts {
+        /// Labeled DOIs
+        labeled: usize,
+        /// DOIs with glued text to trim
+        glued: usize,
+        /// Trimmed to the labeled DOI
+        correct_trims: usize,
+        /// Trimmed although the DOI was complete, or trimmed to the wrong length
+        false_trims: usize,
+        /// Glued text left in place
+        missed: usize,
+    }
+
+    fn measure(glued_author_year: bool) -> TrimCounts {
+        let mut counts = TrimCounts::default();
+        let labels = include_str!("suffix_labels.tsv")
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in labels {
+            let (raw, expected) = line.split_once('\t').unwrap();
+            let trimmed = trim_doi_suffix(raw, glued_author_year);
+            counts.labeled += 1;
+            counts.glued += usize::from(expected != raw);
+            if trimmed == raw {
+                counts.missed += usize::from(expected != raw);
+            } else if trimmed == expected {
+                counts.correct_trims += 1;
+            } else {
+                counts.false_trims += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn test_trim_rates_on_labeled_suffixes() {
+        // The publisher rules trim every glued publisher DOI and nothing else
+        assert_eq!(
+            measure(false),
+            TrimCounts {
+                labeled: 37,
+                glued: 17,
+                correct_trims: 10,
+                false_trims: 0,
+                missed: 7,
+            }
+        );
+        // The author-year rule catches the other 7 but also cuts 3 of the 20 complete
+        // suffixes, so 3 of its 10 trims are wrong
+        assert_eq!(
+            measure(true),
+            TrimCounts {
+                labeled: 37,
+                glued: 17,
+                correct_trims: 17,
+                false_trims: 3,
+                missed: 0,
+            }
+        );
+    }
+}
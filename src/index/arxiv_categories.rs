@@ -0,0 +1,239 @@
+use crate::error::{Context, Result};
+use log::info;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+use super::read_arxiv_metadata;
+use crate::common::{format_elapsed, CitationRecord};
+use crate::extract::{arxiv_id_category, normalize_arxiv_id};
+
+/// Category label for arXiv IDs whose category cannot be determined
+pub const UNKNOWN_CATEGORY: &str = "unknown";
+
+/// Primary arXiv category per arXiv ID, from an arXiv metadata file
+#[derive(Debug, Clone, Default)]
+pub struct ArxivCategories {
+    /// Normalized arXiv ID -> primary category, cased as the metadata writes it
+    categories: HashMap<String, String>,
+}
+
+impl ArxivCategories {
+    /// Load from an arXiv metadata file, JSONL or OAI-PMH XML (see
+    /// [`read_arxiv_metadata`])
+    ///
+    /// The first listed category of each paper is its primary one.
+    pub fn load(path: &str) -> Result<Self> {
+        info!("Loading arXiv categories from: {}", path);
+        let start = Instant::now();
+
+        let mut categories = HashMap::new();
+        let lines_failed = read_arxiv_metadata(path, |id, listed| {
+            if let Some(primary) = listed.and_then(|c| c.split_whitespace().next()) {
+                categories.insert(normalize_arxiv_id(id), primary.to_string());
+            }
+        })?;
+
+        info!(
+            "Loaded categories for {} arXiv IDs in {}",
+            categories.len(),
+            format_elapsed(start.elapsed())
+        );
+        if lines_failed > 0 {
+            info!("  ({} records failed to parse)", lines_failed);
+        }
+
+        Ok(Self { categories })
+    }
+
+    /// Primary category of an arXiv ID: from metadata, else from an old-format ID
+    /// in arXiv's canonical case
+    pub fn category_of(&self, arxiv_id: &str) -> Option<String> {
+        let id = normalize_arxiv_id(arxiv_id);
+        self.categories
+            .get(&id)
+            .cloned()
+            .or_else(|| arxiv_id_category(&id).map(|category| canonical_case(&category)))
+    }
+
+    /// Number of arXiv IDs with a known category
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.categories.len()
+    }
+}
+
+/// A lowercase category in arXiv's case: subject classes are uppercase (math.AG,
+/// cs.DM) except in cond-mat and physics (cond-mat.str-el, physics.optics)
+fn canonical_case(category: &str) -> String {
+    match category.split_once('.') {
+        Some((archive, _)) if archive == "cond-mat" || archive == "physics" => category.to_string(),
+        Some((archive, subject)) => format!("{}.{}", archive, subject.to_uppercase()),
+        None => category.to_string(),
+    }
+}
+
+/// Citation totals for one arXiv category
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CategoryCount {
+    pub category: String,
+    /// Distinct cited arXiv works in the category
+    pub cited_works: usize,
+    /// Sum of citation counts across those works
+    pub citations: usize,
+}
+
+/// Aggregate arXiv citation records by primary category, most cited first
+///
+/// Without metadata, only old-format IDs (e.g. hep-ph/9901234) have a category; the
+/// rest are counted under `UNKNOWN_CATEGORY`. Categories are compared ignoring case
+/// and reported as the first record in each spells them.
+pub fn aggregate_by_category<'a>(
+    records: impl IntoIterator<Item = &'a CitationRecord>,
+    categories: &ArxivCategories,
+) -> Vec<CategoryCount> {
+    let mut totals: HashMap<String, (String, usize, usize)> = HashMap::new();
+    for record in records {
+        let category = record
+            .arxiv_id
+            .as_deref()
+            .and_then(|id| categories.category_of(id))
+            .unwrap_or_else(|| UNKNOWN_CATEGORY.to_string());
+        let entry = totals
+            .entry(category.to_lowercase())
+            .or_insert_with(|| (category, 0, 0));
+        entry.1 += 1;
+        entry.2 += record.citation_count;
+    }
+
+    let mut counts: Vec<CategoryCount> = totals
+        .into_values()
+        .map(|(category, cited_works, citations)| CategoryCount {
+            category,
+            cited_works,
+            citations,
+        })
+        .collect();
+    counts.sort_by(|a, b| {
+        b.citations
+            .cmp(&a.citations)
+            .then_with(|| a.category.cmp(&b.category))
+    });
+    counts
+}
+
+/// Write category counts as JSONL, one category per line
+pub fn write_category_counts(counts: &[CategoryCount], path: &str) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create: {}", path))?;
+    let mut writer = BufWriter::new(file);
+    for count in counts {
+        writeln!(writer, "{}", serde_json::to_string(count)?)?;
+    }
+    writer.flush()?;
+
+    info!(
+        "Wrote citation counts for {} arXiv categories to {}",
+        counts.len(),
+        path
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn record(arxiv_id: &str, citation_count: usize) -> CitationRecord {
+        CitationRecord {
+            doi: format!("10.48550/arXiv.{}", arxiv_id),
+            arxiv_id: Some(arxiv_id.to_string()),
+            id_type: None,
+            reference_count: citation_count,
+            citation_count,
+            cited_by: vec![],
+            published_doi: None,
+            registration_agency: None,
+            http_error: None,
+            validation_method: None,
+            validation_status: None,
+            asserted_citation_count: None,
+            cited_publisher: None,
+            provenance_counts: None,
+        }
+    }
+
+    #[test]
+    fn test_load_arxiv_categories() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"id": "2403.03542", "categories": "cs.LG stat.ML"}}"#
+        )
+        .unwrap();
+        writeln!(file, r#"{{"id": "0704.0001", "categories": "hep-ph"}}"#).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        let categories = ArxivCategories::load(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(categories.len(), 2);
+        assert_eq!(
+            categories.category_of("2403.03542v2"),
+            Some("cs.LG".to_string())
+        );
+        assert_eq!(
+            categories.category_of("0704.0001"),
+            Some("hep-ph".to_string())
+        );
+        // Old-format IDs fall back to the category embedded in the ID
+        assert_eq!(
+            categories.category_of("math.co/0112001"),
+            Some("math.CO".to_string())
+        );
+        assert_eq!(
+            categories.category_of("cond-mat.str-el/0501001"),
+            Some("cond-mat.str-el".to_string())
+        );
+        assert_eq!(categories.category_of("2501.00001"), None);
+    }
+
+    #[test]
+    fn test_aggregate_by_category() {
+        let records = vec![
+            record("hep-ph/9901234", 5),
+            record("hep-ph/9905678", 2),
+            record("cs.dm/9910013", 3),
+            record("2403.03542", 4),
+            record("0704.0001", 2),
+        ];
+        // The category of an old-format ID, from metadata for a modern one
+        let mut categories = ArxivCategories::default();
+        categories
+            .categories
+            .insert("0704.0001".to_string(), "cs.DM".to_string());
+
+        let counts = aggregate_by_category(&records, &categories);
+        assert_eq!(
+            counts,
+            vec![
+                CategoryCount {
+                    category: "hep-ph".to_string(),
+                    cited_works: 2,
+                    citations: 7,
+                },
+                CategoryCount {
+                    category: "cs.DM".to_string(),
+                    cited_works: 2,
+                    citations: 5,
+                },
+                CategoryCount {
+                    category: UNKNOWN_CATEGORY.to_string(),
+                    cited_works: 1,
+                    citations: 4,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use crate::error::{Context, Result};
+use log::info;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::common::{format_elapsed, CitationRecord};
+use crate::extract::{normalize_arxiv_id, strip_doi_prefix};
+use crate::streaming::open_compressed_reader;
+
+/// DOI prefix of arXiv's DataCite records (10.48550/arXiv.<id>)
+const ARXIV_DOI_PREFIX: &str = "10.48550/arxiv.";
+/// relatedIdentifier relation types that point from a preprint to its published version
+const PUBLISHED_VERSION_RELATIONS: [&str; 3] =
+    ["IsVersionOf", "IsPreviousVersionOf", "IsPreprintOf"];
+
+/// Published journal DOI per arXiv ID, from DataCite relatedIdentifiers
+#[derive(Debug, Clone, Default)]
+pub struct ArxivPublishedDois {
+    /// Normalized arXiv ID -> lowercase published DOI
+    links: HashMap<String, String>,
+}
+
+impl ArxivPublishedDois {
+    /// Load links from a DataCite records JSONL file (gzip, bzip2, zstd or plain)
+    ///
+    /// Only arXiv records are considered. Their `relatedIdentifiers` may sit at the top
+    /// level or under `attributes`; the first DOI related by a published-version
+    /// relation outside arXiv is used.
+    pub fn load_from_jsonl_gz(path: &str) -> Result<Self> {
+        info!("Loading arXiv published DOI links from: {}", path);
+        let start = Instant::now();
+
+        let reader = BufReader::new(open_compressed_reader(Path::new(path))?);
+        let mut links = HashMap::new();
+        let mut arxiv_records = 0;
+        let mut lines_failed = 0;
+
+        for line_result in reader.lines() {
+            let line = line_result.context("Failed to read line")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Cheap pre-filter: most DataCite records are not arXiv's
+            if !line.contains("10.48550") {
+                continue;
+            }
+
+            match serde_json::from_str::<Value>(&line) {
+                Ok(record) => {
+                    let Some(arxiv_id) = record_arxiv_id(&record) else {
+                        continue;
+                    };
+                    arxiv_records += 1;
+                    if let Some(doi) = published_doi(&record) {
+                        links.entry(arxiv_id).or_insert(doi);
+                    }
+                }
+                Err(_) => lines_failed += 1,
+            }
+        }
+
+        info!(
+            "Linked {} of {} arXiv records to published DOIs in {}",
+            links.len(),
+            arxiv_records,
+            format_elapsed(start.elapsed())
+        );
+        if lines_failed > 0 {
+            info!("  ({} records failed to parse)", lines_failed);
+        }
+
+        Ok(Self { links })
+    }
+
+    /// Published DOI for an arXiv ID, if DataCite records one
+    pub fn published_doi(&self, arxiv_id: &str) -> Option<&str> {
+        self.links
+            .get(&normalize_arxiv_id(arxiv_id))
+            .map(String::as_str)
+    }
+
+    /// Set `published_doi` on records with a known link, returning how many were linked
+    pub fn annotate<'a>(&self, records: impl IntoIterator<Item = &'a mut CitationRecord>) -> usize {
+        let mut linked = 0;
+        for record in records {
+            let arxiv_id = record
+                .arxiv_id
+                .clone()
+                .or_else(|| arxiv_id_from_doi(&record.doi.to_lowercase()));
+            if let Some(doi) = arxiv_id.as_deref().and_then(|id| self.published_doi(id)) {
+                record.published_doi = Some(doi.to_string());
+                linked += 1;
+            }
+        }
+        linked
+    }
+
+    /// Number of arXiv IDs with a published DOI
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.links.len()
+    }
+}
+
+/// Normalized arXiv ID of a DataCite record, if it is an arXiv DOI
+fn record_arxiv_id(record: &Value) -> Option<String> {
+    let id = record.get("id").and_then(|v| v.as_str())?;
+    arxiv_id_from_doi(&id.to_lowercase())
+}
+
+fn arxiv_id_from_doi(doi: &str) -> Option<String> {
+    doi.strip_prefix(ARXIV_DOI_PREFIX)
+        .filter(|id| !id.is_empty())
+        .map(normalize_arxiv_id)
+}
+
+/// First non-arXiv DOI related to the record as its published version
+fn published_doi(record: &Value) -> Option<String> {
+    let related = record
+        .get("relatedIdentifiers")
+        .or_else(|| record.get("attributes")?.get("relatedIdentifiers"))?
+        .as_array()?;
+
+    related.iter().find_map(|rel| {
+        let relation = rel.get("relationType")?.as_str()?;
+        let id_type = rel.get("relatedIdentifierType")?.as_str()?;
+        if !PUBLISHED_VERSION_RELATIONS.contains(&relation) || !id_type.eq_ignore_ascii_case("DOI")
+        {
+            return None;
+        }
+        let doi = strip_doi_prefix(rel.get("relatedIdentifier")?.as_str()?);
+        (doi.starts_with("10.") && !doi.starts_with(ARXIV_DOI_PREFIX)).then_some(doi)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_load_published_doi_links() {
+        let file = NamedTempFile::new().unwrap();
+        let mut encoder = GzEncoder::new(file.reopen().unwrap(), Compression::default());
+        let lines = [
+            // Nested attributes, DOI given as a URL
+            r#"{"id": "10.48550/arXiv.2403.03542", "attributes": {"relatedIdentifiers": [{"relationType": "IsVersionOf", "relatedIdentifierType": "DOI", "relatedIdentifier": "https://doi.org/10.1103/PhysRevD.1.2"}]}}"#,
+            // Flat record; arXiv-internal versions and non-DOI identifiers are skipped
+            r#"{"id": "10.48550/arxiv.hep-ph/9901234", "relatedIdentifiers": [{"relationType": "IsVersionOf", "relatedIdentifierType": "DOI", "relatedIdentifier": "10.48550/arXiv.hep-ph/9901234v2"}, {"relationType": "IsPreviousVersionOf", "relatedIdentifierType": "URL", "relatedIdentifier": "https://example.org"}, {"relationType": "IsPreviousVersionOf", "relatedIdentifierType": "DOI", "relatedIdentifier": "10.1016/J.X.1999"}]}"#,
+            r#"{"id": "10.48550/arXiv.2501.00001", "relatedIdentifiers": [{"relationType": "References", "relatedIdentifierType": "DOI", "relatedIdentifier": "10.1/cited"}]}"#,
+            r#"{"id": "10.5061/dryad.123", "relatedIdentifiers": [{"relationType": "IsVersionOf", "relatedIdentifierType": "DOI", "relatedIdentifier": "10.1/other"}]}"#,
+        ];
+        for line in lines {
+            writeln!(encoder, "{}", line).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let links = ArxivPublishedDois::load_from_jsonl_gz(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            links.published_doi("2403.03542v3"),
+            Some("10.1103/physrevd.1.2")
+        );
+        assert_eq!(
+            links.published_doi("hep-ph/9901234"),
+            Some("10.1016/j.x.1999")
+        );
+        assert_eq!(links.published_doi("2501.00001"), None);
+
+        let mut records = [
+            CitationRecord {
+                doi: "10.48550/arXiv.2403.03542".to_string(),
+                arxiv_id: None,
+                id_type: None,
+                reference_count: 1,
+                citation_count: 1,
+                cited_by: vec![],
+                published_doi: None,
+                registration_agency: None,
+                http_error: None,
+                validation_method: None,
+                validation_status: None,
+                asserted_citation_count: None,
+                cited_publisher: None,
+                provenance_counts: None,
+            },
+            CitationRecord {
+                doi: "10.48550/arXiv.2501.00001".to_string(),
+                arxiv_id: Some("2501.00001".to_string()),
+                id_type: None,
+                reference_count: 1,
+                citation_count: 1,
+                cited_by: vec![],
+                published_doi: None,
+                registration_agency: None,
+                http_error: None,
+                validation_method: None,
+                validation_status: None,
+                asserted_citation_count: None,
+                cited_publisher: None,
+                provenance_counts: None,
+            },
+        ];
+        assert_eq!(links.annotate(records.iter_mut()), 1);
+        assert_eq!(
+            records[0].published_doi.as_deref(),
+            Some("10.1103/physrevd.1.2")
+        );
+        assert_eq!(records[1].published_doi, None);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{Context, Result};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::streaming::open_compressed_reader;
+
+/// Read the papers listed in an arXiv metadata file (gzip, bzip2, zstd or plain)
+///
+/// Accepts JSONL with an "id" and a space-separated "categories" field per record, as
+/// in the arXiv OAI snapshot on Kaggle, and OAI-PMH `ListRecords` XML in the `arXiv` or
+/// `arXivRaw` metadata format, as harvested from export.arxiv.org/oai2; harvested
+/// pages may be concatenated into one file. The format is detected from the first
+/// non-blank byte. Calls `visit` with each paper's ID and categories, and returns how
+/// many JSONL lines failed to parse. Deleted OAI records carry no metadata and are
+/// skipped.
+pub fn read_arxiv_metadata(path: &str, visit: impl FnMut(&str, Option<&str>)) -> Result<usize> {
+    let mut input = BufReader::new(open_compressed_reader(Path::new(path))?);
+    let is_xml = loop {
+        let buf = input.fill_buf().context("Failed to read arXiv metadata")?;
+        match buf.iter().position(|byte| !byte.is_ascii_whitespace()) {
+            Some(start) => {
+                let is_xml = buf[start] == b'<';
+                input.consume(start);
+                break is_xml;
+            }
+            None if buf.is_empty() => break false,
+            None => {
+                let len = buf.len();
+                input.consume(len);
+            }
+        }
+    };
+
+    if is_xml {
+        read_oai_xml(input, visit)?;
+        Ok(0)
+    } else {
+        read_jsonl(input, visit)
+    }
+}
+
+fn read_jsonl<R: BufRead>(input: R, mut visit: impl FnMut(&str, Option<&str>)) -> Result<usize> {
+    let mut lines_failed = 0;
+    for line_result in input.lines() {
+        let line = line_result.context("Failed to read line")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(record) => {
+                if let Some(id) = record.get("id").and_then(|v| v.as_str()) {
+                    visit(id, record.get("categories").and_then(|v| v.as_str()));
+                }
+            }
+            Err(_) => lines_failed += 1,
+        }
+    }
+    Ok(lines_failed)
+}
+
+/// Stream `<id>` and `<categories>` out of the `arXiv` or `arXivRaw` metadata of each
+/// OAI-PMH `<record>`, without holding more than one record
+fn read_oai_xml<R: BufRead>(input: R, mut visit: impl FnMut(&str, Option<&str>)) -> Result<()> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    // Local names of the open elements
+    let mut open: Vec<Vec<u8>> = Vec::new();
+    let mut id = String::new();
+    let mut categories = String::new();
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .with_context(|| format!("Invalid XML at byte {}", reader.buffer_position()))?;
+        match event {
+            Event::Start(start) => open.push(start.local_name().as_ref().to_vec()),
+            Event::End(end) => {
+                if end.local_name().as_ref() == b"record" {
+                    let id = std::mem::take(&mut id);
+                    let categories = std::mem::take(&mut categories);
+                    let id = id.trim();
+                    if !id.is_empty() {
+                        visit(id, Some(categories.trim()).filter(|c| !c.is_empty()));
+                    }
+                }
+                open.pop();
+            }
+            Event::Text(text) => {
+                if let Some(field) = metadata_field(&open, &mut id, &mut categories) {
+                    field.push_str(&text.unescape().context("Invalid XML text")?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(field) = metadata_field(&open, &mut id, &mut categories) {
+                    field.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+/// The field that text inside the innermost open element belongs to, if any
+fn metadata_field<'a>(
+    open: &[Vec<u8>],
+    id: &'a mut String,
+    categories: &'a mut String,
+) -> Option<&'a mut String> {
+    let [.., format, field] = open else {
+        return None;
+    };
+    if format != b"arXiv" && format != b"arXivRaw" {
+        return None;
+    }
+    match field.as_slice() {
+        b"id" => Some(id),
+        b"categories" => Some(categories),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn read_all(content: &str) -> (Vec<(String, Option<String>)>, usize) {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        let mut papers = Vec::new();
+        let failed = read_arxiv_metadata(file.path().to_str().unwrap(), |id, categories| {
+            papers.push((id.to_string(), categories.map(str::to_string)))
+        })
+        .unwrap();
+        (papers, failed)
+    }
+
+    #[test]
+    fn test_read_arxiv_metadata_jsonl() {
+        let (papers, failed) = read_all(concat!(
+            "{\"id\": \"2301.12345\", \"categories\": \"cs.CL cs.LG\"}\n",
+            "\n",
+            "{\"id\": \"hep-ph/9901234v2\"}\n",
+            "not json\n",
+        ));
+        assert_eq!(
+            papers,
+            [
+                ("2301.12345".to_string(), Some("cs.CL cs.LG".to_string())),
+                ("hep-ph/9901234v2".to_string(), None),
+            ]
+        );
+        assert_eq!(failed, 1);
+    }
+
+    #[test]
+    fn test_read_arxiv_metadata_oai_xml() {
+        let page = |records: &str| {
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+<ListRecords>{records}<resumptionToken cursor="0">6961524|1001</resumptionToken></ListRecords>
+</OAI-PMH>
+"#
+            )
+        };
+        let xml = page(
+            r#"<record><header><identifier>oai:arXiv.org:0704.0001</identifier>
+<setSpec>physics:hep-ph</setSpec></header>
+<metadata><arXiv xmlns="http://arxiv.org/OAI/arXiv/"><id>0704.0001</id>
+<title>Calculation of prompt diphoton production</title><categories>hep-ph</categories>
+</arXiv></metadata></record>
+<record><header status="deleted"><identifier>oai:arXiv.org:0704.0002</identifier></header></record>"#,
+        ) + &page(
+            r#"<record><header><identifier>oai:arXiv.org:math/0112001</identifier></header>
+<metadata><arXivRaw xmlns="http://arxiv.org/OAI/arXivRaw/"><id>math/0112001</id>
+<version version="v1"><date>Mon, 3 Dec 2001</date></version>
+<categories>math.CO math.AG</categories></arXivRaw></metadata></record>"#,
+        );
+
+        let (papers, failed) = read_all(&format!("\n  {}", xml));
+        assert_eq!(
+            papers,
+            [
+                ("0704.0001".to_string(), Some("hep-ph".to_string())),
+                (
+                    "math/0112001".to_string(),
+                    Some("math.CO math.AG".to_string())
+                ),
+            ]
+        );
+        assert_eq!(failed, 0);
+    }
+
+    #[test]
+    fn test_read_arxiv_metadata_invalid_xml() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "<OAI-PMH><record><metadata></arXiv>").unwrap();
+        assert!(read_arxiv_metadata(file.path().to_str().unwrap(), |_, _| {}).is_err());
+    }
+}
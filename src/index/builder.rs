@@ -1,24 +1,187 @@
-use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
+use crate::error::{Context, Result};
 use log::info;
+#[cfg(feature = "parquet")]
+use log::{debug, warn};
+#[cfg(feature = "parquet")]
+use serde::Deserialize;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 use std::time::Instant;
 
-use super::DoiIndex;
+use super::{read_arxiv_metadata, DoiIndex};
 use crate::common::format_elapsed;
+use crate::extract::{normalize_arxiv_id, strip_doi_prefix};
+use crate::streaming::open_compressed_reader;
+#[cfg(feature = "parquet")]
+use crate::streaming::{open_gzip_reader, parse_deposit_xml};
 
-/// Build a DOI index from a gzipped JSONL file containing records with "id" field
+/// Build a DOI index from a JSONL file (gzip, bzip2, zstd or plain) containing records
+/// with "id" field
 pub fn build_index_from_jsonl_gz(path: &str, id_field: &str) -> Result<DoiIndex> {
-    info!("Building DOI index from: {}", path);
+    build_index_from_jsonl_gz_into(path, id_field, DoiIndex::with_capacity(10_000_000, 100_000))
+}
+
+/// Build a prefix-only index from a JSONL file, discarding the DOIs themselves
+pub fn build_prefix_index_from_jsonl_gz(path: &str, id_field: &str) -> Result<DoiIndex> {
+    build_index_from_jsonl_gz_into(path, id_field, DoiIndex::prefix_only())
+}
+
+/// Build a DOI index from a text file with one DOI per line
+///
+/// DOIs may be given as doi.org URLs or with a "doi:" prefix. Blank lines and lines
+/// starting with `#` are skipped.
+pub fn build_index_from_doi_list(path: &str) -> Result<DoiIndex> {
+    info!("Loading DOI list from: {}", path);
+    let file = File::open(path).with_context(|| format!("Failed to open DOI list: {}", path))?;
+
+    let mut index = DoiIndex::new();
+    for line_result in BufReader::new(file).lines() {
+        let line = line_result.context("Failed to read line")?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        index.insert(&strip_doi_prefix(line));
+    }
+
+    info!(
+        "Loaded {} DOIs ({} prefixes) from DOI list",
+        index.len(),
+        index.prefix_count()
+    );
+    Ok(index)
+}
+
+/// Build the arXiv DOI index from arXiv metadata, JSONL or OAI-PMH XML (plain or
+/// compressed)
+///
+/// Reads the arXiv OAI snapshot from Kaggle or an OAI-PMH harvest (see
+/// [`read_arxiv_metadata`]) and indexes the DataCite DOI arXiv registers for each paper
+/// (`10.48550/arxiv.<id>`, version stripped). Validates arXiv citations like an index
+/// built from the DataCite dump, without reading that dump.
+pub fn build_arxiv_index_from_metadata(path: &str) -> Result<DoiIndex> {
+    info!("Building arXiv DOI index from metadata: {}", path);
     let start = Instant::now();
 
-    let file = File::open(path).with_context(|| format!("Failed to open file: {}", path))?;
+    let mut index = DoiIndex::with_capacity(3_000_000, 1);
+    let lines_failed = read_arxiv_metadata(path, |id, _| {
+        index.insert(&format!("10.48550/arxiv.{}", normalize_arxiv_id(id)));
+    })?;
 
-    let decoder = GzDecoder::new(file);
+    info!(
+        "Built arXiv index with {} DOIs in {}",
+        index.len(),
+        format_elapsed(start.elapsed())
+    );
+    if lines_failed > 0 {
+        info!("  ({} records failed to parse)", lines_failed);
+    }
+
+    Ok(index)
+}
+
+/// A Crossref snapshot file, read only for the DOIs of its items
+#[cfg(feature = "parquet")]
+#[derive(Deserialize)]
+struct SnapshotFile {
+    #[serde(default)]
+    items: Vec<SnapshotItem>,
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Deserialize)]
+struct SnapshotItem {
+    #[serde(rename = "DOI")]
+    doi: Option<String>,
+}
+
+/// Build the Crossref DOI index from a snapshot tar.gz without extracting references
+///
+/// Reads the same entries as the pipeline (JSON item files and deposit XML), but
+/// deserializes only each item's `DOI` and skips everything else, which makes it
+/// several times faster than a pipeline run when only the index is needed. DOIs are
+/// inserted as registered, like the index the pipeline builds.
+#[cfg(feature = "parquet")]
+pub fn build_crossref_index_from_snapshot(path: &str, mut index: DoiIndex) -> Result<DoiIndex> {
+    info!("Building Crossref DOI index from snapshot: {}", path);
+    let start = Instant::now();
+
+    let mut archive = tar::Archive::new(open_gzip_reader(Path::new(path))?);
+    let mut files_read = 0;
+    let mut items_read = 0;
+
+    for entry_result in archive.entries().context("Failed to read archive")? {
+        let entry = entry_result.context("Failed to read tar entry")?;
+        let entry_path = entry.path()?.to_string_lossy().into_owned();
+
+        let dois: Vec<String> = if entry_path.ends_with(".json") {
+            debug!("Reading DOIs from: {}", entry_path);
+            match serde_json::from_reader::<_, SnapshotFile>(BufReader::new(entry)) {
+                Ok(file) => file.items.into_iter().filter_map(|item| item.doi).collect(),
+                Err(e) => {
+                    warn!("Failed to parse JSON in {}: {}", entry_path, e);
+                    continue;
+                }
+            }
+        } else if entry_path.ends_with(".xml") {
+            debug!("Reading DOIs from deposit XML: {}", entry_path);
+            match parse_deposit_xml(BufReader::new(entry)) {
+                Ok(items) => items
+                    .iter()
+                    .filter_map(|item| item.get("DOI").and_then(|v| v.as_str()))
+                    .map(String::from)
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to parse XML in {}: {:#}", entry_path, e);
+                    continue;
+                }
+            }
+        } else {
+            continue;
+        };
+
+        files_read += 1;
+        items_read += dois.len();
+        for doi in &dois {
+            index.insert(doi);
+        }
+        if files_read % 1_000 == 0 {
+            info!(
+                "  Read {} files, {} DOIs indexed...",
+                files_read,
+                index.len()
+            );
+        }
+    }
+
+    info!(
+        "Built index with {} DOIs ({} prefixes) from {} items in {} files in {}",
+        index.len(),
+        index.prefix_count(),
+        items_read,
+        files_read,
+        format_elapsed(start.elapsed())
+    );
+
+    Ok(index)
+}
+
+/// Build a DOI index from a JSONL file into `index`
+///
+/// Lets the caller choose the index's capacity or collision tracking. The file may be
+/// compressed with gzip, bzip2 or zstd (see [`open_compressed_reader`]).
+pub fn build_index_from_jsonl_gz_into(
+    path: &str,
+    id_field: &str,
+    mut index: DoiIndex,
+) -> Result<DoiIndex> {
+    info!("Building DOI index from: {}", path);
+    let start = Instant::now();
+
+    let decoder = open_compressed_reader(Path::new(path))?;
     let reader = BufReader::new(decoder);
 
-    let mut index = DoiIndex::with_capacity(10_000_000, 100_000);
     let mut lines_processed = 0;
     let mut lines_failed = 0;
 
@@ -102,4 +265,93 @@ mod tests {
         assert!(index.contains("10.5678/other"));
         assert_eq!(index.prefix_count(), 2);
     }
+
+    #[test]
+    fn test_build_prefix_index_from_jsonl_gz() {
+        let file = create_test_jsonl_gz(&[
+            r#"{"id": "10.5438/example1"}"#,
+            r#"{"id": "10.5438/example2"}"#,
+            r#"{"id": "10.48550/arXiv.2403.12345"}"#,
+        ]);
+
+        let index = build_prefix_index_from_jsonl_gz(file.path().to_str().unwrap(), "id").unwrap();
+
+        assert!(index.prefix_only);
+        assert!(index.is_empty());
+        assert_eq!(index.prefix_count(), 2);
+        assert!(index.has_prefix("10.48550"));
+    }
+
+    #[test]
+    fn test_build_index_from_doi_list() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "# Journal of Examples").unwrap();
+        writeln!(file, "10.1234/Example1").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "  https://doi.org/10.1234/example2  ").unwrap();
+        writeln!(file, "doi:10.5678/other").unwrap();
+
+        let index = build_index_from_doi_list(file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(index.len(), 3);
+        assert!(index.contains("10.1234/example1"));
+        assert!(index.contains("10.1234/example2"));
+        assert!(index.contains("10.5678/other"));
+    }
+
+    #[test]
+    fn test_build_arxiv_index_from_metadata() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"id": "2301.12345", "categories": "cs.CL cs.LG"}}"#
+        )
+        .unwrap();
+        writeln!(file, r#"{{"id": "hep-ph/9901234v2"}}"#).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        let index = build_arxiv_index_from_metadata(file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(index.len(), 2);
+        assert!(index.contains("10.48550/arXiv.2301.12345"));
+        assert!(index.contains("10.48550/arxiv.hep-ph/9901234"));
+        assert!(!index.contains("10.48550/arxiv.2301.99999"));
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_build_crossref_index_from_snapshot() {
+        let file = NamedTempFile::new().unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            file.reopen().unwrap(),
+            Compression::default(),
+        ));
+        let entries = [
+            (
+                "snapshot/0.json",
+                r#"{"items": [{"DOI": "10.1234/A", "reference": [{"DOI": "10.9999/x"}]}, {"type": "book"}]}"#,
+            ),
+            ("snapshot/1.json", r#"{"items": [{"DOI": "10.5678/b"}]}"#),
+            ("snapshot/README", "not a snapshot file"),
+        ];
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_path(path).unwrap();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let index =
+            build_crossref_index_from_snapshot(file.path().to_str().unwrap(), DoiIndex::new())
+                .unwrap();
+
+        assert_eq!(index.len(), 2);
+        assert!(index.contains("10.1234/a"));
+        assert!(index.contains("10.5678/b"));
+        // Cited DOIs are not indexed
+        assert!(!index.contains("10.9999/x"));
+    }
 }
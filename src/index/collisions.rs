@@ -0,0 +1,225 @@
+use crate::error::Result;
+use log::info;
+use serde_json::json;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{BuildHasher, RandomState};
+use std::path::Path;
+
+use crate::common::{open_sink, CitationRecord};
+use crate::validation::fuzzy_normalize_doi;
+
+/// Form under which DOIs collide: lowercase, with whitespace and invisible characters
+/// removed and unicode dashes mapped to '-'
+pub fn collision_key(doi: &str) -> String {
+    let lower = doi.to_lowercase();
+    fuzzy_normalize_doi(&lower).unwrap_or(lower)
+}
+
+/// DOIs added to an index that differ only by case, whitespace or dashes
+///
+/// The index stores DOIs lowercased, so variants differing in case are merged into
+/// one entry and variants differing in whitespace or dashes become separate entries
+/// that a cited DOI can match either of. Tracking keeps a hash of every key seen, plus
+/// the original form when it is not the key itself: about 32 bytes per DOI.
+#[derive(Debug, Clone, Default)]
+pub struct DoiCollisions {
+    hasher: RandomState,
+    first_forms: HashMap<u64, Option<Box<str>>>,
+    groups: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DoiCollisions {
+    /// Record a DOI as it appears in the source records
+    pub fn observe(&mut self, doi: &str) {
+        let key = collision_key(doi);
+        let form = (doi != key).then(|| Box::from(doi));
+        match self.first_forms.entry(self.hasher.hash_one(&key)) {
+            Entry::Vacant(entry) => {
+                entry.insert(form);
+            }
+            Entry::Occupied(entry) => {
+                let first = entry.get().as_deref().unwrap_or(&key);
+                // Equal hashes of different keys are not collisions of the DOIs
+                if first != doi && collision_key(first) == key {
+                    let first = first.to_string();
+                    let variants = self.groups.entry(key).or_default();
+                    variants.insert(first);
+                    variants.insert(doi.to_string());
+                }
+            }
+        }
+    }
+
+    /// Number of keys with more than one form
+    pub(crate) fn len(&self) -> usize {
+        self.groups.len()
+    }
+}
+
+/// Collisions found in the indexes of a run, with the cited DOIs that hit them
+#[derive(Debug, Default)]
+pub struct CollisionReport {
+    groups: BTreeMap<String, CollisionGroup>,
+}
+
+#[derive(Debug, Default)]
+struct CollisionGroup {
+    indexes: BTreeSet<String>,
+    variants: BTreeSet<String>,
+    cited_as: BTreeSet<String>,
+    citations: usize,
+}
+
+impl CollisionGroup {
+    /// Distinct entries the variants occupy in a lowercasing index
+    fn index_entries(&self) -> usize {
+        self.variants
+            .iter()
+            .map(|variant| variant.to_lowercase())
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+}
+
+impl CollisionReport {
+    /// Add the collisions found while building an index
+    pub fn add_index(&mut self, index: &str, collisions: &DoiCollisions) {
+        for (key, variants) in &collisions.groups {
+            let group = self.groups.entry(key.clone()).or_default();
+            group.indexes.insert(index.to_string());
+            group.variants.extend(variants.iter().cloned());
+        }
+    }
+
+    /// Count the validated records whose cited DOI falls in a collision group
+    pub fn add_cited<'a>(&mut self, records: impl IntoIterator<Item = &'a CitationRecord>) {
+        if self.groups.is_empty() {
+            return;
+        }
+        for record in records {
+            if let Some(group) = self.groups.get_mut(&collision_key(&record.doi)) {
+                group.cited_as.insert(record.doi.clone());
+                group.citations += record.citation_count;
+            }
+        }
+    }
+
+    /// Write one record per collision group, ordered by key, returning records written
+    ///
+    /// `kind` is `case` when the variants differ only by case, `whitespace` otherwise.
+    /// `ambiguous` groups occupy more than one index entry, so a cited DOI in them
+    /// matches several entries after normalization.
+    pub fn write(&self, path: &str) -> Result<usize> {
+        let ambiguous_cited = self
+            .groups
+            .values()
+            .filter(|group| group.index_entries() > 1 && group.citations > 0)
+            .count();
+        info!(
+            "Writing {} DOI collisions ({} ambiguous and cited) to: {}",
+            self.groups.len(),
+            ambiguous_cited,
+            path
+        );
+        let mut sink = open_sink(Path::new(path))?;
+        for (key, group) in &self.groups {
+            let index_entries = group.index_entries();
+            let kind = if index_entries == 1 {
+                "case"
+            } else {
+                "whitespace"
+            };
+            sink.write_record(&json!({
+                "key": key,
+                "kind": kind,
+                "indexes": group.indexes,
+                "variants": group.variants,
+                "index_entries": index_entries,
+                "ambiguous": index_entries > 1,
+                "cited_as": group.cited_as,
+                "citations": group.citations,
+            }))?;
+        }
+        sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::io::BufRead;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_doi_collisions() {
+        let mut collisions = DoiCollisions::default();
+        for doi in [
+            "10.1234/abc",
+            "10.1234/ABC",
+            "10.1234/abc",
+            "10.5678/x y",
+            "10.5678/xy",
+            "10.5678/x\u{2010}1",
+            "10.9999/unique",
+        ] {
+            collisions.observe(doi);
+        }
+        assert_eq!(collisions.len(), 2);
+        assert_eq!(
+            collisions.groups["10.1234/abc"],
+            BTreeSet::from(["10.1234/ABC".to_string(), "10.1234/abc".to_string()])
+        );
+        assert_eq!(collisions.groups["10.5678/xy"].len(), 2);
+        assert_eq!(collision_key("10.5678/X\u{2010}1"), "10.5678/x-1");
+    }
+
+    #[test]
+    fn test_collision_report() {
+        let mut crossref = DoiCollisions::default();
+        crossref.observe("10.1234/ABC");
+        crossref.observe("10.1234/abc");
+        let mut datacite = DoiCollisions::default();
+        datacite.observe("10.5678/x y");
+        datacite.observe("10.5678/xy");
+
+        let mut report = CollisionReport::default();
+        report.add_index("crossref", &crossref);
+        report.add_index("datacite", &datacite);
+        let cited = CitationRecord {
+            doi: "10.5678/x y".to_string(),
+            arxiv_id: None,
+            id_type: None,
+            reference_count: 3,
+            citation_count: 3,
+            cited_by: Vec::new(),
+            published_doi: None,
+            registration_agency: None,
+            http_error: None,
+            validation_method: None,
+            validation_status: None,
+            asserted_citation_count: None,
+            cited_publisher: None,
+            provenance_counts: None,
+        };
+        report.add_cited([&cited]);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("collisions.jsonl");
+        assert_eq!(report.write(path.to_str().unwrap()).unwrap(), 2);
+
+        let lines: Vec<Value> = std::io::BufReader::new(std::fs::File::open(&path).unwrap())
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(lines[0]["key"], "10.1234/abc");
+        assert_eq!(lines[0]["kind"], "case");
+        assert_eq!(lines[0]["ambiguous"], false);
+        assert_eq!(lines[1]["kind"], "whitespace");
+        assert_eq!(lines[1]["indexes"], json!(["datacite"]));
+        assert_eq!(lines[1]["index_entries"], 2);
+        assert_eq!(lines[1]["cited_as"], json!(["10.5678/x y"]));
+        assert_eq!(lines[1]["citations"], 3);
+    }
+}
@@ -0,0 +1,448 @@
+#[cfg(feature = "parquet")]
+use crate::error::{Context, Error, Result};
+#[cfg(feature = "parquet")]
+use memmap2::Mmap;
+#[cfg(feature = "parquet")]
+use std::fs::File;
+#[cfg(feature = "parquet")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "parquet")]
+use std::ops::Range;
+#[cfg(feature = "parquet")]
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+/// Entries per front-coded block; the first entry of each block is stored whole
+#[cfg(feature = "parquet")]
+const BLOCK_SIZE: usize = 16;
+
+/// First bytes of a front-coded set file
+#[cfg(feature = "parquet")]
+const FILE_MAGIC: &[u8; 8] = b"DOIFCS01";
+/// Magic, entry count and block count
+#[cfg(feature = "parquet")]
+const FILE_HEADER_LEN: usize = 24;
+
+/// In-memory representation of the DOIs in a loaded index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexBackend {
+    /// HashSet of DOI strings; supports inserts and merges
+    #[default]
+    Hash,
+    /// Read-only sorted front-coded blocks searched by binary search, several
+    /// times smaller than the HashSet
+    Fst,
+    /// The fst blocks saved next to the index file and memory-mapped: loading only
+    /// maps the file, and pages are read from disk as lookups touch them
+    Mmap,
+}
+
+impl FromStr for IndexBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hash" => Ok(IndexBackend::Hash),
+            "fst" => Ok(IndexBackend::Fst),
+            "mmap" => Ok(IndexBackend::Mmap),
+            _ => Err(format!(
+                "Invalid index backend: {}. Valid options: hash, fst, mmap",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for IndexBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexBackend::Hash => write!(f, "hash"),
+            IndexBackend::Fst => write!(f, "fst"),
+            IndexBackend::Mmap => write!(f, "mmap"),
+        }
+    }
+}
+
+/// Read-only set of strings stored as sorted, front-coded blocks
+///
+/// Each block holds up to `BLOCK_SIZE` strings. The first is stored as a length and
+/// its bytes; each following string as the length of the prefix it shares with its
+/// predecessor, the suffix length, and the suffix bytes. Lookups binary search the
+/// blocks by first string and scan one block.
+///
+/// A set can be saved to a file and memory-mapped back, which costs no parsing: the
+/// file is the header, the block offsets as little-endian u64s, then the blocks.
+#[derive(Debug, Clone, Default)]
+pub struct FrontCodedSet {
+    storage: Storage,
+    len: usize,
+}
+
+/// Blocks and their offsets, built on the heap or mapped from a file
+#[derive(Debug, Clone)]
+enum Storage {
+    Heap {
+        bytes: Vec<u8>,
+        block_offsets: Vec<usize>,
+    },
+    #[cfg(feature = "parquet")]
+    Mapped {
+        map: Arc<Mmap>,
+        /// Byte range of the block offset table
+        offsets: Range<usize>,
+        /// Byte range of the blocks
+        blocks: Range<usize>,
+    },
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage::Heap {
+            bytes: Vec::new(),
+            block_offsets: Vec::new(),
+        }
+    }
+}
+
+impl FrontCodedSet {
+    /// Build from strings in ascending byte order; duplicates are skipped
+    ///
+    /// Panics if the input is not sorted.
+    #[cfg(feature = "parquet")]
+    pub fn from_sorted<'a>(strings: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut bytes = Vec::new();
+        let mut block_offsets = Vec::new();
+        let mut len = 0;
+        let mut previous: &[u8] = &[];
+
+        for s in strings {
+            let s = s.as_bytes();
+            if len > 0 {
+                assert!(s >= previous, "FrontCodedSet input is not sorted");
+                if s == previous {
+                    continue;
+                }
+            }
+
+            if len % BLOCK_SIZE == 0 {
+                block_offsets.push(bytes.len());
+                write_varint(&mut bytes, s.len());
+                bytes.extend_from_slice(s);
+            } else {
+                let shared = shared_prefix_len(previous, s);
+                write_varint(&mut bytes, shared);
+                write_varint(&mut bytes, s.len() - shared);
+                bytes.extend_from_slice(&s[shared..]);
+            }
+            previous = s;
+            len += 1;
+        }
+
+        bytes.shrink_to_fit();
+        block_offsets.shrink_to_fit();
+        Self {
+            storage: Storage::Heap {
+                bytes,
+                block_offsets,
+            },
+            len,
+        }
+    }
+
+    /// Save the set to a file that `open_mapped` can map
+    ///
+    /// Written to a temporary file and renamed, so a mapped file is never rewritten
+    /// in place.
+    #[cfg(feature = "parquet")]
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("fcs.tmp");
+        let file =
+            File::create(&tmp_path).with_context(|| format!("Failed to create: {:?}", tmp_path))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&(self.len as u64).to_le_bytes())?;
+        writer.write_all(&(self.block_count() as u64).to_le_bytes())?;
+        for block in 0..self.block_count() {
+            writer.write_all(&(self.block_offset(block) as u64).to_le_bytes())?;
+        }
+        writer.write_all(self.blocks())?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()
+            .with_context(|| format!("Failed to write: {:?}", tmp_path))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))
+    }
+
+    /// Memory-map a set saved with `write_to`
+    ///
+    /// Only the header is read; blocks are paged in by lookups.
+    #[cfg(feature = "parquet")]
+    pub fn open_mapped(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+        // SAFETY: the map is read-only, and `write_to` replaces set files by rename
+        // rather than rewriting them, so the mapped bytes do not change under us
+        // unless another process truncates the file, which nothing here does.
+        let map = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to memory-map: {:?}", path))?;
+
+        let invalid = || Error::invalid(format!("Not a front-coded set file: {:?}", path));
+        if map.len() < FILE_HEADER_LEN || &map[..8] != FILE_MAGIC {
+            return Err(invalid());
+        }
+        let read_u64 = |at: usize| u64::from_le_bytes(map[at..at + 8].try_into().unwrap());
+        let len = read_u64(8) as usize;
+        let block_count = read_u64(16) as usize;
+        let blocks_start = block_count
+            .checked_mul(8)
+            .and_then(|table| table.checked_add(FILE_HEADER_LEN))
+            .filter(|&start| start <= map.len())
+            .ok_or_else(invalid)?;
+        if block_count != len.div_ceil(BLOCK_SIZE) {
+            return Err(invalid());
+        }
+
+        let blocks = blocks_start..map.len();
+        Ok(Self {
+            storage: Storage::Mapped {
+                map: Arc::new(map),
+                offsets: FILE_HEADER_LEN..blocks_start,
+                blocks,
+            },
+            len,
+        })
+    }
+
+    pub fn contains(&self, s: &str) -> bool {
+        let target = s.as_bytes();
+        // Last block whose first string is <= target
+        let (mut low, mut high) = (0, self.block_count());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.first_in_block(self.block_offset(mid)).cmp(target) {
+                std::cmp::Ordering::Equal => return true,
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+        let block = match low {
+            0 => return false,
+            next => next - 1,
+        };
+
+        let bytes = self.blocks();
+        let (mut pos, end) = self.block_range(block);
+
+        let first_len = read_varint(bytes, &mut pos);
+        let mut current = bytes[pos..pos + first_len].to_vec();
+        pos += first_len;
+
+        while pos < end {
+            let shared = read_varint(bytes, &mut pos);
+            let suffix_len = read_varint(bytes, &mut pos);
+            current.truncate(shared);
+            current.extend_from_slice(&bytes[pos..pos + suffix_len]);
+            pos += suffix_len;
+
+            match current.as_slice().cmp(target) {
+                std::cmp::Ordering::Equal => return true,
+                std::cmp::Ordering::Greater => return false,
+                std::cmp::Ordering::Less => {}
+            }
+        }
+        false
+    }
+
+    /// The strings in ascending byte order
+    #[cfg(feature = "parquet")]
+    pub fn iter(&self) -> impl Iterator<Item = String> + '_ {
+        (0..self.block_count()).flat_map(|block| self.block_strings(block))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Heap bytes held by the set; a mapped set holds none
+    #[cfg(feature = "parquet")]
+    pub fn heap_size(&self) -> usize {
+        match &self.storage {
+            Storage::Heap {
+                bytes,
+                block_offsets,
+            } => bytes.capacity() + block_offsets.capacity() * std::mem::size_of::<usize>(),
+            Storage::Mapped { .. } => 0,
+        }
+    }
+
+    /// Size of the mapped file, or 0 for a set on the heap
+    #[cfg(feature = "parquet")]
+    pub fn mapped_size(&self) -> usize {
+        match &self.storage {
+            Storage::Heap { .. } => 0,
+            Storage::Mapped { map, .. } => map.len(),
+        }
+    }
+
+    fn blocks(&self) -> &[u8] {
+        match &self.storage {
+            Storage::Heap { bytes, .. } => bytes,
+            #[cfg(feature = "parquet")]
+            Storage::Mapped { map, blocks, .. } => &map[blocks.clone()],
+        }
+    }
+
+    fn block_count(&self) -> usize {
+        match &self.storage {
+            Storage::Heap { block_offsets, .. } => block_offsets.len(),
+            #[cfg(feature = "parquet")]
+            Storage::Mapped { offsets, .. } => offsets.len() / 8,
+        }
+    }
+
+    fn block_offset(&self, block: usize) -> usize {
+        match &self.storage {
+            Storage::Heap { block_offsets, .. } => block_offsets[block],
+            #[cfg(feature = "parquet")]
+            Storage::Mapped { map, offsets, .. } => {
+                let at = offsets.start + block * 8;
+                u64::from_le_bytes(map[at..at + 8].try_into().unwrap()) as usize
+            }
+        }
+    }
+
+    /// Start and end of a block within `blocks()`
+    fn block_range(&self, block: usize) -> (usize, usize) {
+        let end = if block + 1 < self.block_count() {
+            self.block_offset(block + 1)
+        } else {
+            self.blocks().len()
+        };
+        (self.block_offset(block), end)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn block_strings(&self, block: usize) -> Vec<String> {
+        let bytes = self.blocks();
+        let (mut pos, end) = self.block_range(block);
+        let mut strings = Vec::with_capacity(BLOCK_SIZE);
+        let mut current = Vec::new();
+        let mut shared = 0;
+        while pos < end {
+            if !strings.is_empty() {
+                shared = read_varint(bytes, &mut pos);
+            }
+            let suffix_len = read_varint(bytes, &mut pos);
+            current.truncate(shared);
+            current.extend_from_slice(&bytes[pos..pos + suffix_len]);
+            pos += suffix_len;
+            strings.push(String::from_utf8_lossy(&current).into_owned());
+        }
+        strings
+    }
+
+    fn first_in_block(&self, offset: usize) -> &[u8] {
+        let bytes = self.blocks();
+        let mut pos = offset;
+        let len = read_varint(bytes, &mut pos);
+        &bytes[pos..pos + len]
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn shared_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// LEB128 unsigned varint
+#[cfg(feature = "parquet")]
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(all(test, feature = "parquet"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_coded_set_lookup() {
+        let mut dois: Vec<String> = (0..1000)
+            .map(|i| format!("10.{}/item.{}", 1000 + i % 7, i))
+            .collect();
+        // A long entry exercises multi-byte varints
+        dois.push(format!("10.9999/{}", "x".repeat(300)));
+        dois.sort();
+        let mut with_duplicates = dois.clone();
+        with_duplicates.extend(dois.iter().take(10).cloned());
+        with_duplicates.sort();
+
+        let set = FrontCodedSet::from_sorted(with_duplicates.iter().map(String::as_str));
+        assert_eq!(set.len(), dois.len());
+        assert!(dois.iter().all(|doi| set.contains(doi)));
+
+        for missing in ["", "10.0/a", "10.1000/item", "10.1003/item.99", "zzz"] {
+            assert!(!set.contains(missing), "{} should be absent", missing);
+        }
+        assert!(!set.contains(&format!("10.9999/{}", "x".repeat(299))));
+
+        assert!(set.iter().eq(dois.iter().cloned()));
+
+        let raw: usize = dois.iter().map(String::len).sum();
+        assert!(set.heap_size() < raw);
+    }
+
+    #[test]
+    fn test_front_coded_set_mapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.parquet.fcs");
+        let dois: Vec<String> = (0..100).map(|i| format!("10.1234/{:03}", i)).collect();
+        let set = FrontCodedSet::from_sorted(dois.iter().map(String::as_str));
+        set.write_to(&path).unwrap();
+
+        let mapped = FrontCodedSet::open_mapped(&path).unwrap();
+        assert_eq!(mapped.len(), 100);
+        assert_eq!(mapped.heap_size(), 0);
+        assert!(mapped.mapped_size() > 0);
+        assert!(dois.iter().all(|doi| mapped.contains(doi)));
+        assert!(!mapped.contains("10.1234/100"));
+        assert!(!mapped.contains("10.0/a"));
+        assert!(mapped.iter().eq(dois.iter().cloned()));
+
+        let empty_path = dir.path().join("empty.fcs");
+        FrontCodedSet::default().write_to(&empty_path).unwrap();
+        let empty = FrontCodedSet::open_mapped(&empty_path).unwrap();
+        assert!(empty.len() == 0 && !empty.contains("10.1234/000"));
+
+        std::fs::write(&empty_path, b"not a set").unwrap();
+        assert!(FrontCodedSet::open_mapped(&empty_path).is_err());
+    }
+
+    #[test]
+    fn test_index_backend_parse() {
+        assert_eq!("fst".parse::<IndexBackend>(), Ok(IndexBackend::Fst));
+        assert_eq!("mmap".parse::<IndexBackend>(), Ok(IndexBackend::Mmap));
+        assert_eq!("HASH".parse::<IndexBackend>(), Ok(IndexBackend::Hash));
+        assert!("btree".parse::<IndexBackend>().is_err());
+    }
+}
@@ -1,18 +1,46 @@
+pub mod arxiv_categories;
+pub mod arxiv_links;
+pub mod arxiv_metadata;
 pub mod builder;
+pub mod collisions;
+pub mod compact;
+#[cfg(feature = "parquet")]
 pub mod persistence;
+pub mod publishers;
+pub mod shards;
 
+pub use arxiv_categories::*;
+pub use arxiv_links::ArxivPublishedDois;
+pub use arxiv_metadata::read_arxiv_metadata;
 pub use builder::*;
+pub use collisions::{CollisionReport, DoiCollisions};
+pub use compact::{FrontCodedSet, IndexBackend};
+#[cfg(feature = "parquet")]
 pub use persistence::*;
+pub use publishers::PublisherNames;
+pub use shards::*;
 
+#[cfg(feature = "parquet")]
+use std::borrow::Cow;
 use std::collections::HashSet;
 
 /// DOI index containing DOIs and their prefixes for fast lookup
 #[derive(Debug, Clone, Default)]
 pub struct DoiIndex {
-    /// Set of all DOIs (lowercase)
-    pub dois: HashSet<String>,
+    /// Set of all DOIs (lowercase), empty when `compact` holds them; read through
+    /// [`DoiIndex::dois`]
+    dois: HashSet<String>,
     /// Set of all DOI prefixes (e.g., "10.1234")
     pub prefixes: HashSet<String>,
+    /// Only prefixes are tracked; DOI membership is unknown
+    pub prefix_only: bool,
+    /// Read-only compact DOI storage used instead of `dois` by the fst and mmap
+    /// backends: one set per loaded file (a DOI is in at most one)
+    pub compact: Vec<FrontCodedSet>,
+    /// Shards held when only part of a sharded index was loaded
+    pub shards: Option<ShardSelection>,
+    /// DOIs inserted that differ only by case or whitespace, when tracked
+    pub collisions: Option<DoiCollisions>,
 }
 
 impl DoiIndex {
@@ -24,21 +52,74 @@ impl DoiIndex {
         Self {
             dois: HashSet::with_capacity(doi_capacity),
             prefixes: HashSet::with_capacity(prefix_capacity),
+            prefix_only: false,
+            compact: Vec::new(),
+            shards: None,
+            collisions: None,
+        }
+    }
+
+    /// Track DOIs inserted from now on that collide by case or whitespace
+    pub fn with_collision_tracking(mut self) -> Self {
+        self.collisions = Some(DoiCollisions::default());
+        self
+    }
+
+    /// Create an index that tracks prefixes only, for cheap source routing
+    pub fn prefix_only() -> Self {
+        Self {
+            prefix_only: true,
+            ..Self::default()
+        }
+    }
+
+    /// Copy of the prefixes alone, to route DOIs once the index itself is dropped
+    pub fn to_prefix_only(&self) -> Self {
+        Self {
+            prefixes: self.prefixes.clone(),
+            ..Self::prefix_only()
         }
     }
 
     /// Add a DOI to the index, also tracking its prefix
+    ///
+    /// Prefix-only indexes record the prefix and discard the DOI.
     pub fn insert(&mut self, doi: &str) {
+        if let Some(ref mut collisions) = self.collisions {
+            collisions.observe(doi);
+        }
         let doi_lower = doi.to_lowercase();
         if let Some(prefix) = crate::extract::doi_prefix(&doi_lower) {
             self.prefixes.insert(prefix);
         }
-        self.dois.insert(doi_lower);
+        if !self.prefix_only {
+            self.dois.insert(doi_lower);
+        }
     }
 
     /// Check if a DOI exists in the index
     pub fn contains(&self, doi: &str) -> bool {
-        self.dois.contains(&doi.to_lowercase())
+        let doi_lower = doi.to_lowercase();
+        if self.compact.is_empty() {
+            self.dois.contains(&doi_lower)
+        } else {
+            self.compact.iter().any(|set| set.contains(&doi_lower))
+        }
+    }
+
+    /// Every DOI in the index, lowercase, whichever backend holds them
+    #[cfg(feature = "parquet")]
+    pub fn dois(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+        if self.compact.is_empty() {
+            Box::new(self.dois.iter().map(|doi| Cow::Borrowed(doi.as_str())))
+        } else {
+            Box::new(
+                self.compact
+                    .iter()
+                    .flat_map(FrontCodedSet::iter)
+                    .map(Cow::Owned),
+            )
+        }
     }
 
     /// Check if a prefix exists in the index
@@ -49,13 +130,17 @@ impl DoiIndex {
 
     /// Get count of DOIs
     pub fn len(&self) -> usize {
-        self.dois.len()
+        if self.compact.is_empty() {
+            self.dois.len()
+        } else {
+            self.compact.iter().map(FrontCodedSet::len).sum()
+        }
     }
 
     /// Check if empty
     #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
-        self.dois.is_empty()
+        self.len() == 0
     }
 
     /// Get count of unique prefixes
@@ -63,11 +148,25 @@ impl DoiIndex {
         self.prefixes.len()
     }
 
+    /// Check if a DOI's prefix exists in the index
+    pub fn contains_prefix_of(&self, doi: &str) -> bool {
+        crate::extract::doi_prefix(doi).is_some_and(|prefix| self.prefixes.contains(&prefix))
+    }
+
+    /// Whether the index can answer for this DOI: false when the DOI belongs to a
+    /// shard that was not loaded
+    pub fn covers(&self, doi: &str) -> bool {
+        self.shards
+            .as_ref()
+            .is_none_or(|selection| selection.covers(doi))
+    }
+
     /// Merge another index into this one
     #[allow(dead_code)]
     pub fn merge(&mut self, other: DoiIndex) {
         self.dois.extend(other.dois);
         self.prefixes.extend(other.prefixes);
+        self.compact.extend(other.compact);
     }
 }
 
@@ -98,6 +197,29 @@ mod tests {
         assert_eq!(index.prefix_count(), 2);
     }
 
+    #[test]
+    fn test_prefix_only_index() {
+        let mut index = DoiIndex::prefix_only();
+        index.insert("10.5438/example");
+
+        assert!(index.is_empty());
+        assert!(!index.contains("10.5438/example"));
+        assert!(index.has_prefix("10.5438"));
+        assert!(index.contains_prefix_of("10.5438/other"));
+        assert!(!index.contains_prefix_of("10.1234/other"));
+    }
+
+    #[test]
+    fn test_to_prefix_only() {
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/example");
+
+        let prefixes = index.to_prefix_only();
+        assert!(prefixes.prefix_only);
+        assert!(!prefixes.contains("10.1234/example"));
+        assert!(prefixes.has_prefix("10.1234"));
+    }
+
     #[test]
     fn test_doi_index_merge() {
         let mut index1 = DoiIndex::new();
@@ -112,4 +234,22 @@ mod tests {
         assert!(index1.contains("10.5678/b"));
         assert_eq!(index1.len(), 2);
     }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_dois_of_every_backend() {
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/B");
+        index.insert("10.1234/a");
+        let mut dois: Vec<_> = index.dois().collect();
+        dois.sort();
+        assert_eq!(dois, ["10.1234/a", "10.1234/b"]);
+
+        let compact = DoiIndex {
+            compact: vec![FrontCodedSet::from_sorted(["10.1234/a", "10.1234/b"])],
+            ..DoiIndex::new()
+        };
+        assert!(compact.dois.is_empty());
+        assert!(compact.dois().eq(["10.1234/a", "10.1234/b"]));
+    }
 }
@@ -1,10 +1,12 @@
-use anyhow::{Context, Result};
-use log::info;
+use crate::error::{Context, Result};
+use log::{info, warn};
 use polars::prelude::*;
+use std::borrow::Cow;
 use std::fs::File;
+use std::path::Path;
 use std::time::Instant;
 
-use super::DoiIndex;
+use super::{DoiIndex, FrontCodedSet, IndexBackend};
 use crate::common::format_elapsed;
 
 /// Save a DOI index to a Parquet file
@@ -12,7 +14,8 @@ pub fn save_index_to_parquet(index: &DoiIndex, path: &str) -> Result<()> {
     info!("Saving DOI index to: {}", path);
     let start = Instant::now();
 
-    let dois: Vec<&str> = index.dois.iter().map(|s| s.as_str()).collect();
+    let dois: Vec<Cow<'_, str>> = index.dois().collect();
+    let dois: Vec<&str> = dois.iter().map(|doi| doi.as_ref()).collect();
     let prefixes: Vec<&str> = index.prefixes.iter().map(|s| s.as_str()).collect();
 
     // Create two dataframes and save to same file using row groups
@@ -97,6 +100,170 @@ pub fn load_index_from_parquet(path: &str) -> Result<DoiIndex> {
     Ok(index)
 }
 
+/// Load a DOI index from a Parquet file into the given backend
+pub fn load_index_with_backend(path: &str, backend: IndexBackend) -> Result<DoiIndex> {
+    match backend {
+        IndexBackend::Hash => load_index_from_parquet(path),
+        IndexBackend::Fst => load_compact_index_from_parquet(path),
+        IndexBackend::Mmap => load_mapped_index_from_parquet(path),
+    }
+}
+
+/// Path of the memory-mapped sidecar built next to a Parquet index
+pub fn mapped_index_path(path: &str) -> String {
+    format!("{}.fcs", path)
+}
+
+/// Load a DOI index from a Parquet file, memory-mapping its front-coded DOIs
+///
+/// The DOIs live in a `<path>.fcs` sidecar that is built on first use and rebuilt
+/// whenever the Parquet file is newer, so later runs start in the time it takes to
+/// read the prefixes and the OS pages DOI blocks in as lookups touch them. If the
+/// sidecar cannot be written the index is kept on the heap as with the fst backend.
+pub fn load_mapped_index_from_parquet(path: &str) -> Result<DoiIndex> {
+    let start = Instant::now();
+    let sidecar = mapped_index_path(path);
+
+    let index = if sidecar_is_fresh(path, &sidecar) {
+        info!("Mapping DOI index from: {}", sidecar);
+        let mapped = FrontCodedSet::open_mapped(Path::new(&sidecar))?;
+        let mut index = load_prefix_index_from_parquet(path)?;
+        index.prefix_only = false;
+        index.compact = vec![mapped];
+        index
+    } else {
+        let mut index = load_compact_index_from_parquet(path)?;
+        info!("Writing memory-mapped DOI index to: {}", sidecar);
+        let written = index
+            .compact
+            .first()
+            .map_or(Ok(()), |set| set.write_to(Path::new(&sidecar)));
+        if let Err(e) = written {
+            warn!("Keeping DOI index in memory: {:#}", e);
+            return Ok(index);
+        }
+        index.compact = vec![FrontCodedSet::open_mapped(Path::new(&sidecar))?];
+        index
+    };
+
+    info!(
+        "Mapped {} DOIs ({} MB) and {} prefixes in {}",
+        index.len(),
+        index
+            .compact
+            .iter()
+            .map(FrontCodedSet::mapped_size)
+            .sum::<usize>()
+            / (1024 * 1024),
+        index.prefix_count(),
+        format_elapsed(start.elapsed())
+    );
+
+    Ok(index)
+}
+
+/// Whether the sidecar exists and is at least as new as the Parquet index
+fn sidecar_is_fresh(path: &str, sidecar: &str) -> bool {
+    let modified = |p: &str| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (modified(path), modified(sidecar)) {
+        (Some(index), Some(sidecar)) => sidecar >= index,
+        _ => false,
+    }
+}
+
+/// Load a DOI index from a Parquet file into read-only front-coded storage
+///
+/// Lookups are slower than the HashSet index but the DOIs take a fraction of the
+/// memory. The result cannot be extended or saved.
+pub fn load_compact_index_from_parquet(path: &str) -> Result<DoiIndex> {
+    info!("Loading compact DOI index from: {}", path);
+    let start = Instant::now();
+
+    let dois_df = LazyFrame::scan_parquet(path, Default::default())
+        .with_context(|| format!("Failed to scan parquet: {}", path))?
+        .select([col("doi")])
+        .collect()
+        .context("Failed to collect DOIs dataframe")?;
+
+    let mut dois: Vec<&str> = dois_df
+        .column("doi")?
+        .str()?
+        .into_iter()
+        .flatten()
+        .collect();
+    dois.sort_unstable();
+    let compact = FrontCodedSet::from_sorted(dois.iter().copied());
+
+    let prefix_path = format!("{}.prefixes", path);
+    let mut index = if std::path::Path::new(&prefix_path).exists() {
+        load_prefix_index_from_parquet(path)?
+    } else {
+        // Rebuild prefixes from DOIs if prefix file missing
+        let mut index = DoiIndex::prefix_only();
+        for doi in &dois {
+            index.insert(doi);
+        }
+        index
+    };
+    index.prefix_only = false;
+    index.compact = vec![compact];
+
+    info!(
+        "Loaded {} DOIs ({} MB) and {} prefixes in {}",
+        index.len(),
+        index
+            .compact
+            .iter()
+            .map(FrontCodedSet::heap_size)
+            .sum::<usize>()
+            / (1024 * 1024),
+        index.prefix_count(),
+        format_elapsed(start.elapsed())
+    );
+
+    Ok(index)
+}
+
+/// Load only the prefixes of a saved DOI index, producing a prefix-only index
+pub fn load_prefix_index_from_parquet(path: &str) -> Result<DoiIndex> {
+    info!("Loading DOI prefixes from: {}", path);
+    let start = Instant::now();
+
+    let mut index = DoiIndex::prefix_only();
+
+    let prefix_path = format!("{}.prefixes", path);
+    if std::path::Path::new(&prefix_path).exists() {
+        let prefixes_df = LazyFrame::scan_parquet(&prefix_path, Default::default())
+            .with_context(|| format!("Failed to scan prefix parquet: {}", prefix_path))?
+            .collect()
+            .context("Failed to collect prefixes dataframe")?;
+
+        let prefixes_col = prefixes_df.column("prefix")?.str()?;
+        for prefix in prefixes_col.into_iter().flatten() {
+            index.prefixes.insert(prefix.to_string());
+        }
+    } else {
+        // Derive prefixes from the DOI column without keeping the DOIs
+        let dois_df = LazyFrame::scan_parquet(path, Default::default())
+            .with_context(|| format!("Failed to scan parquet: {}", path))?
+            .collect()
+            .context("Failed to collect DOIs dataframe")?;
+
+        let dois_col = dois_df.column("doi")?.str()?;
+        for doi in dois_col.into_iter().flatten() {
+            index.insert(doi);
+        }
+    }
+
+    info!(
+        "Loaded {} prefixes in {}",
+        index.prefix_count(),
+        format_elapsed(start.elapsed())
+    );
+
+    Ok(index)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,4 +290,95 @@ mod tests {
         assert_eq!(loaded.prefix_count(), 2);
         assert!(loaded.has_prefix("10.1234"));
     }
+
+    #[test]
+    fn test_load_prefix_index() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_index.parquet");
+        let path_str = path.to_str().unwrap();
+
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/example1");
+        index.insert("10.5678/other");
+        save_index_to_parquet(&index, path_str).unwrap();
+
+        let loaded = load_prefix_index_from_parquet(path_str).unwrap();
+
+        assert!(loaded.prefix_only);
+        assert!(loaded.is_empty());
+        assert_eq!(loaded.prefix_count(), 2);
+
+        // Falls back to deriving prefixes when the prefix file is missing
+        std::fs::remove_file(format!("{}.prefixes", path_str)).unwrap();
+        let derived = load_prefix_index_from_parquet(path_str).unwrap();
+        assert!(derived.is_empty());
+        assert!(derived.has_prefix("10.5678"));
+    }
+
+    #[test]
+    fn test_load_compact_index() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_index.parquet");
+        let path_str = path.to_str().unwrap();
+
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/example1");
+        index.insert("10.1234/example2");
+        index.insert("10.5678/other");
+        save_index_to_parquet(&index, path_str).unwrap();
+
+        let loaded = load_index_with_backend(path_str, IndexBackend::Fst).unwrap();
+        assert!(!loaded.compact.is_empty() && loaded.dois.is_empty());
+        assert_eq!(loaded.len(), 3);
+        assert!(loaded.contains("10.1234/EXAMPLE1"));
+        assert!(!loaded.contains("10.1234/example3"));
+        assert!(loaded.contains_prefix_of("10.5678/new"));
+        assert_eq!(loaded.prefix_count(), 2);
+
+        // Saving a compact index writes the DOIs it holds
+        let resaved = dir.path().join("resaved.parquet");
+        save_index_to_parquet(&loaded, resaved.to_str().unwrap()).unwrap();
+        assert_eq!(
+            load_index_from_parquet(resaved.to_str().unwrap())
+                .unwrap()
+                .len(),
+            3
+        );
+
+        std::fs::remove_file(format!("{}.prefixes", path_str)).unwrap();
+        let derived = load_compact_index_from_parquet(path_str).unwrap();
+        assert!(derived.has_prefix("10.1234"));
+        assert!(derived.contains("10.5678/other"));
+    }
+
+    #[test]
+    fn test_load_mapped_index() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test_index.parquet");
+        let path_str = path.to_str().unwrap();
+
+        let mut index = DoiIndex::new();
+        index.insert("10.1234/example1");
+        index.insert("10.5678/other");
+        save_index_to_parquet(&index, path_str).unwrap();
+
+        // First load builds the sidecar, the second maps it as-is
+        for _ in 0..2 {
+            let loaded = load_index_with_backend(path_str, IndexBackend::Mmap).unwrap();
+            assert!(Path::new(&mapped_index_path(path_str)).exists());
+            assert_eq!(loaded.compact[0].heap_size(), 0);
+            assert_eq!(loaded.len(), 2);
+            assert!(loaded.contains("10.1234/EXAMPLE1"));
+            assert!(!loaded.contains("10.1234/example2"));
+            assert!(loaded.has_prefix("10.5678"));
+        }
+
+        // A rebuilt index makes the sidecar stale
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        index.insert("10.1234/example2");
+        save_index_to_parquet(&index, path_str).unwrap();
+        let reloaded = load_index_with_backend(path_str, IndexBackend::Mmap).unwrap();
+        assert_eq!(reloaded.len(), 3);
+        assert!(reloaded.contains("10.1234/example2"));
+    }
 }
@@ -0,0 +1,231 @@
+use crate::error::{Context, Error, Result};
+use log::info;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+#[cfg(feature = "parquet")]
+use std::path::Path;
+
+use crate::common::CitationRecord;
+use crate::extract::doi_prefix;
+#[cfg(feature = "parquet")]
+use crate::streaming::read_members_table;
+
+/// Prefix -> publisher table shipped with the binary (`src/index/publishers.tsv`)
+const BUNDLED_PUBLISHERS: &str = include_str!("publishers.tsv");
+
+/// Publisher name per DOI prefix
+///
+/// The bundled table covers the largest Crossref members; a file in the same format
+/// (one `prefix<TAB>name` per line, `#` comments) or a members table from
+/// `members sync` adds prefixes or renames them.
+#[derive(Debug, Clone, Default)]
+pub struct PublisherNames {
+    /// Lowercase DOI prefix -> publisher name
+    names: HashMap<String, String>,
+}
+
+impl PublisherNames {
+    /// The bundled table
+    pub fn bundled() -> Self {
+        let mut publishers = Self::default();
+        publishers
+            .extend_from_tsv(BUNDLED_PUBLISHERS)
+            .expect("bundled publisher table is well-formed");
+        publishers
+    }
+
+    /// The bundled table, extended and overridden by the entries of `path` when given
+    ///
+    /// A `.parquet` file is read as a members table, naming each prefix after its
+    /// member; anything else as a `prefix<TAB>name` table.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let mut publishers = Self::bundled();
+        if let Some(path) = path {
+            let added = if path.ends_with(".parquet") {
+                publishers.extend_from_members_table(path)?
+            } else {
+                let content = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read: {}", path))?;
+                publishers
+                    .extend_from_tsv(&content)
+                    .map_err(|e| e.context(format!("Invalid publisher table {}", path)))?
+            };
+            info!("Loaded {} publisher names from: {}", added, path);
+        }
+        Ok(publishers)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn extend_from_members_table(&mut self, path: &str) -> Result<usize> {
+        let rows = read_members_table(Path::new(path))?;
+        let added = rows.len();
+        self.names.extend(
+            rows.into_iter()
+                .map(|row| (row.prefix.to_lowercase(), row.member_name)),
+        );
+        Ok(added)
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    fn extend_from_members_table(&mut self, _path: &str) -> Result<usize> {
+        Err(Error::requires_parquet("Reading a members table"))
+    }
+
+    /// Add the entries of a `prefix<TAB>name` table, returning how many were read
+    fn extend_from_tsv(&mut self, content: &str) -> Result<usize> {
+        let mut added = 0;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (prefix, name) = line
+                .split_once('\t')
+                .map(|(prefix, name)| (prefix.trim(), name.trim()))
+                .filter(|(prefix, name)| prefix.starts_with("10.") && !name.is_empty())
+                .ok_or_else(|| {
+                    Error::invalid(format!(
+                        "line {}: expected a DOI prefix, a tab and a publisher name",
+                        i + 1
+                    ))
+                })?;
+            self.names.insert(prefix.to_lowercase(), name.to_string());
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Publisher of a DOI, by its prefix
+    pub fn publisher_of(&self, doi: &str) -> Option<&str> {
+        self.names.get(&doi_prefix(doi)?).map(String::as_str)
+    }
+
+    /// Set `cited_publisher` on records and `citing_publisher` on their cited_by
+    /// entries where the prefix is known, returning how many records were annotated
+    pub fn annotate<'a>(&self, records: impl IntoIterator<Item = &'a mut CitationRecord>) -> usize {
+        let mut annotated = 0;
+        for record in records {
+            record.cited_publisher = self.publisher_of(&record.doi).map(str::to_string);
+            annotated += usize::from(record.cited_publisher.is_some());
+            for entry in &mut record.cited_by {
+                let publisher = entry
+                    .get("doi")
+                    .and_then(Value::as_str)
+                    .and_then(|doi| self.publisher_of(doi));
+                if let (Some(publisher), Value::Object(entry)) = (publisher, &mut *entry) {
+                    entry.insert("citing_publisher".to_string(), Value::from(publisher));
+                }
+            }
+        }
+        annotated
+    }
+
+    /// Number of prefixes with a publisher name
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_publishers() {
+        let publishers = PublisherNames::bundled();
+        assert!(publishers.len() > 50);
+        assert_eq!(
+            publishers.publisher_of("10.1016/j.cell.2020.01.001"),
+            Some("Elsevier BV")
+        );
+        assert_eq!(
+            publishers.publisher_of("10.48550/arXiv.2403.12345"),
+            Some("arXiv")
+        );
+        assert_eq!(publishers.publisher_of("10.99999/unknown"), None);
+        assert_eq!(publishers.publisher_of("not a doi"), None);
+    }
+
+    #[test]
+    fn test_load_publishers_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("publishers.tsv");
+        fs::write(
+            &path,
+            "# custom\n10.99999\tExample Press\n10.1016\tElsevier\n\n",
+        )
+        .unwrap();
+        let publishers = PublisherNames::load(path.to_str()).unwrap();
+        assert_eq!(
+            publishers.publisher_of("10.99999/abc"),
+            Some("Example Press")
+        );
+        assert_eq!(publishers.publisher_of("10.1016/abc"), Some("Elsevier"));
+        assert_eq!(
+            publishers.publisher_of("10.1038/abc"),
+            Some("Springer Science and Business Media LLC")
+        );
+
+        fs::write(&path, "10.99999 Example Press\n").unwrap();
+        assert!(PublisherNames::load(path.to_str()).is_err());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_load_members_table() {
+        use polars::prelude::*;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("members.parquet");
+        let mut df = df!(
+            "prefix" => ["10.99999"],
+            "member_id" => [7u64],
+            "member_name" => ["Example Press"],
+            "prefix_name" => [None::<&str>],
+        )
+        .unwrap();
+        ParquetWriter::new(fs::File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        let publishers = PublisherNames::load(path.to_str()).unwrap();
+        assert_eq!(
+            publishers.publisher_of("10.99999/abc"),
+            Some("Example Press")
+        );
+        assert_eq!(publishers.publisher_of("10.1016/abc"), Some("Elsevier BV"));
+    }
+
+    #[test]
+    fn test_annotate_publishers() {
+        let mut record = CitationRecord {
+            doi: "10.1016/j.cell.2020.01.001".to_string(),
+            arxiv_id: None,
+            id_type: None,
+            reference_count: 2,
+            citation_count: 2,
+            cited_by: vec![
+                serde_json::json!({"doi": "10.1038/s41586-020-2649-2"}),
+                serde_json::json!({"doi": "10.99999/unknown"}),
+            ],
+            published_doi: None,
+            registration_agency: None,
+            validation_method: None,
+            validation_status: None,
+            http_error: None,
+            asserted_citation_count: None,
+            cited_publisher: None,
+            provenance_counts: None,
+        };
+        let annotated = PublisherNames::bundled().annotate([&mut record]);
+        assert_eq!(annotated, 1);
+        assert_eq!(record.cited_publisher.as_deref(), Some("Elsevier BV"));
+        assert_eq!(
+            record.cited_by[0]["citing_publisher"],
+            "Springer Science and Business Media LLC"
+        );
+        assert!(record.cited_by[1].get("citing_publisher").is_none());
+    }
+}
@@ -0,0 +1,297 @@
+#[cfg(feature = "parquet")]
+use crate::error::{Context, Error, Result};
+#[cfg(feature = "parquet")]
+use log::info;
+#[cfg(feature = "parquet")]
+use polars::prelude::*;
+#[cfg(feature = "parquet")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "parquet")]
+use std::fs::File;
+#[cfg(feature = "parquet")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "parquet")]
+use std::time::Instant;
+
+#[cfg(feature = "parquet")]
+use super::{
+    load_index_with_backend, load_prefix_index_from_parquet, save_index_to_parquet, DoiIndex,
+    FrontCodedSet, IndexBackend,
+};
+#[cfg(feature = "parquet")]
+use crate::common::format_elapsed;
+
+#[cfg(feature = "parquet")]
+/// File in a sharded index directory recording the shard count
+pub const SHARDS_FILE: &str = "shards.json";
+
+#[cfg(feature = "parquet")]
+#[derive(Debug, Serialize, Deserialize)]
+struct ShardsManifest {
+    shards: usize,
+    dois: Vec<usize>,
+}
+
+/// Shards of a sharded index held by a loaded `DoiIndex`
+///
+/// DOIs whose shard is not loaded are outside the index's coverage: their absence
+/// says nothing about whether they exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardSelection {
+    pub count: usize,
+    pub loaded: Vec<usize>,
+}
+
+impl ShardSelection {
+    /// Whether the DOI's shard is loaded
+    pub fn covers(&self, doi: &str) -> bool {
+        self.loaded.contains(&shard_of(doi, self.count))
+    }
+}
+
+/// Shard holding a DOI: a stable FNV-1a hash of its lowercase prefix, modulo `shards`
+///
+/// DOIs without a prefix hash as a whole. The hash must never change, or saved
+/// shards stop matching the DOIs routed to them.
+pub fn shard_of(doi: &str, shards: usize) -> usize {
+    let doi = doi.to_lowercase();
+    let key = crate::extract::doi_prefix(&doi).unwrap_or(doi);
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    (hash % shards as u64) as usize
+}
+
+#[cfg(feature = "parquet")]
+/// Path of one shard's Parquet file within a sharded index directory
+pub fn shard_path(dir: &Path, shard: usize, shards: usize) -> PathBuf {
+    dir.join(format!("shard-{:04}-of-{:04}.parquet", shard, shards))
+}
+
+#[cfg(feature = "parquet")]
+/// Whether a path is a sharded index directory rather than a single index file
+pub fn is_sharded_index(path: &str) -> bool {
+    Path::new(path).join(SHARDS_FILE).is_file()
+}
+
+#[cfg(feature = "parquet")]
+/// Save a DOI index as `shards` prefix-hashed Parquet files in a directory
+///
+/// Each shard is a regular index file (with its `.prefixes` companion), so one can
+/// also be loaded on its own with `load_index_from_parquet`.
+pub fn save_sharded_index(index: &DoiIndex, dir: &str, shards: usize) -> Result<()> {
+    if shards == 0 {
+        return Err(Error::invalid("--index-shards must be at least 1"));
+    }
+    info!("Saving DOI index as {} shards to: {}", shards, dir);
+    let start = Instant::now();
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create index directory: {:?}", dir))?;
+
+    let mut parts: Vec<DoiIndex> = (0..shards).map(|_| DoiIndex::new()).collect();
+    for doi in index.dois() {
+        parts[shard_of(&doi, shards)].insert(&doi);
+    }
+    for prefix in &index.prefixes {
+        parts[shard_of(prefix, shards)]
+            .prefixes
+            .insert(prefix.clone());
+    }
+
+    for (shard, part) in parts.iter().enumerate() {
+        let path = shard_path(dir, shard, shards);
+        save_index_to_parquet(part, &path.to_string_lossy())?;
+    }
+
+    let manifest = ShardsManifest {
+        shards,
+        dois: parts.iter().map(DoiIndex::len).collect(),
+    };
+    let manifest_path = dir.join(SHARDS_FILE);
+    let file = File::create(&manifest_path)
+        .with_context(|| format!("Failed to create: {:?}", manifest_path))?;
+    serde_json::to_writer_pretty(file, &manifest)?;
+
+    info!(
+        "Saved {} DOIs in {} shards in {}",
+        index.len(),
+        shards,
+        format_elapsed(start.elapsed())
+    );
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+/// Load a sharded index directory, or only the listed shards of it
+///
+/// With a subset of shards the index records the selection, so validation can
+/// restrict itself to the DOIs those shards cover.
+pub fn load_sharded_index(dir: &str, only: &[usize], backend: IndexBackend) -> Result<DoiIndex> {
+    let manifest = read_shards_manifest(dir)?;
+
+    let selected: Vec<usize> = if only.is_empty() {
+        (0..manifest.shards).collect()
+    } else {
+        only.to_vec()
+    };
+    if let Some(&bad) = selected.iter().find(|&&s| s >= manifest.shards) {
+        return Err(Error::invalid(format!(
+            "Shard {} does not exist in {} ({} shards)",
+            bad, dir, manifest.shards
+        )));
+    }
+    info!(
+        "Loading {} of {} index shards from: {}",
+        selected.len(),
+        manifest.shards,
+        dir
+    );
+
+    let paths: Vec<PathBuf> = selected
+        .iter()
+        .map(|&s| shard_path(Path::new(dir), s, manifest.shards))
+        .collect();
+    let mut index = match (backend, paths.as_slice()) {
+        (_, [path]) => load_index_with_backend(&path.to_string_lossy(), backend)?,
+        // Mapped shards stay separate sets, so combining them copies nothing
+        (IndexBackend::Hash | IndexBackend::Mmap, _) => {
+            let mut index = DoiIndex::new();
+            for path in &paths {
+                index.merge(load_index_with_backend(&path.to_string_lossy(), backend)?);
+            }
+            index
+        }
+        (IndexBackend::Fst, _) => load_compact_shards(&paths)?,
+    };
+
+    if selected.len() < manifest.shards {
+        index.shards = Some(ShardSelection {
+            count: manifest.shards,
+            loaded: selected,
+        });
+    }
+    Ok(index)
+}
+
+#[cfg(feature = "parquet")]
+/// Load the prefixes of every shard of a sharded index, producing a prefix-only index
+pub fn load_sharded_prefix_index(dir: &str) -> Result<DoiIndex> {
+    let manifest = read_shards_manifest(dir)?;
+    let mut index = DoiIndex::prefix_only();
+    for shard in 0..manifest.shards {
+        let path = shard_path(Path::new(dir), shard, manifest.shards);
+        index.merge(load_prefix_index_from_parquet(&path.to_string_lossy())?);
+    }
+    Ok(index)
+}
+
+#[cfg(feature = "parquet")]
+fn read_shards_manifest(dir: &str) -> Result<ShardsManifest> {
+    let path = Path::new(dir).join(SHARDS_FILE);
+    let file = File::open(&path).with_context(|| format!("Failed to open: {:?}", path))?;
+    serde_json::from_reader(file).with_context(|| format!("Failed to parse: {:?}", path))
+}
+
+#[cfg(feature = "parquet")]
+/// Load several shards into one compact index
+fn load_compact_shards(paths: &[PathBuf]) -> Result<DoiIndex> {
+    let mut dois: Vec<String> = Vec::new();
+    let mut index = DoiIndex::prefix_only();
+    for path in paths {
+        let df = LazyFrame::scan_parquet(path, Default::default())
+            .with_context(|| format!("Failed to scan parquet: {:?}", path))?
+            .select([col("doi")])
+            .collect()
+            .context("Failed to collect DOIs dataframe")?;
+        dois.extend(
+            df.column("doi")?
+                .str()?
+                .into_iter()
+                .flatten()
+                .map(str::to_string),
+        );
+        index.merge(load_prefix_index_from_parquet(&path.to_string_lossy())?);
+    }
+    dois.sort_unstable();
+    index.prefix_only = false;
+    index.compact = vec![FrontCodedSet::from_sorted(dois.iter().map(String::as_str))];
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "parquet")]
+    use tempfile::tempdir;
+
+    #[cfg(feature = "parquet")]
+    fn sample_index() -> DoiIndex {
+        let mut index = DoiIndex::new();
+        for prefix in ["10.1000", "10.1001", "10.1002", "10.1003", "10.1004"] {
+            index.insert(&format!("{}/a", prefix));
+            index.insert(&format!("{}/b", prefix));
+        }
+        index
+    }
+
+    #[test]
+    fn test_shard_of_is_stable_per_prefix() {
+        assert_eq!(shard_of("10.1016/j.a", 16), shard_of("10.1016/J.B", 16));
+        assert!(shard_of("10.1016/j.a", 16) < 16);
+        assert_eq!(shard_of("anything", 1), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_save_and_load_all_shards() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("crossref_index");
+        let path_str = path.to_str().unwrap();
+        save_sharded_index(&sample_index(), path_str, 4).unwrap();
+
+        assert!(is_sharded_index(path_str));
+        assert!(shard_path(&path, 3, 4).is_file());
+
+        for backend in [IndexBackend::Hash, IndexBackend::Fst, IndexBackend::Mmap] {
+            let loaded = load_sharded_index(path_str, &[], backend).unwrap();
+            assert_eq!(loaded.len(), 10);
+            assert_eq!(loaded.prefix_count(), 5);
+            assert!(loaded.contains("10.1003/b"));
+            assert!(loaded.shards.is_none());
+
+            // Resharding a compact index keeps its DOIs
+            let resharded = dir.path().join(format!("resharded_{}", backend));
+            let resharded = resharded.to_str().unwrap();
+            save_sharded_index(&loaded, resharded, 2).unwrap();
+            let reloaded = load_sharded_index(resharded, &[], IndexBackend::Hash).unwrap();
+            assert_eq!(reloaded.len(), 10);
+        }
+
+        let prefixes = load_sharded_prefix_index(path_str).unwrap();
+        assert!(prefixes.prefix_only && prefixes.is_empty());
+        assert_eq!(prefixes.prefix_count(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_load_selected_shards() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("crossref_index");
+        let path_str = path.to_str().unwrap();
+        save_sharded_index(&sample_index(), path_str, 4).unwrap();
+
+        let shard = shard_of("10.1002/a", 4);
+        let loaded = load_sharded_index(path_str, &[shard], IndexBackend::Hash).unwrap();
+        assert!(loaded.contains("10.1002/a"));
+        let selection = loaded.shards.as_ref().unwrap();
+        assert!(selection.covers("10.1002/zzz"));
+        for doi in ["10.1000/a", "10.1001/a", "10.1003/a", "10.1004/a"] {
+            assert_eq!(loaded.contains(doi), selection.covers(doi));
+        }
+
+        assert!(load_sharded_index(path_str, &[4], IndexBackend::Hash).is_err());
+    }
+}
@@ -1,6 +1,9 @@
 pub mod cli;
+pub mod commands;
 pub mod common;
+pub mod error;
 pub mod extract;
 pub mod index;
 pub mod streaming;
+pub mod synthetic;
 pub mod validation;
@@ -1,28 +1,49 @@
-mod cli;
-mod commands;
-mod common;
-mod extract;
-mod index;
-mod streaming;
-mod validation;
-
 use anyhow::Result;
 use clap::Parser;
 
-use cli::{Cli, Commands};
-use commands::{run_pipeline, run_validate};
+// The binary is a thin CLI over the library crate
+use crossref_citation_extraction::cli::{Cli, Commands};
+use crossref_citation_extraction::commands::{
+    run_cleanup, run_export_graph, run_harvest, run_inspect, run_pipeline, run_revalidate,
+    run_sample, run_validate,
+};
+#[cfg(feature = "parquet")]
+use crossref_citation_extraction::commands::{run_compact_partitions, run_index, run_members};
+use crossref_citation_extraction::common::{is_interrupted, INTERRUPTED_EXIT_CODE};
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Pipeline(args) => {
-            run_pipeline(*args)?;
-        }
-        Commands::Validate(args) => {
-            run_validate(args)?;
+    // Every command shares this runtime; none starts one of its own
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(run_command(cli.command));
+
+    // A clean interruption gets its own status so wrappers can tell it from a failure
+    if let Err(e) = &result {
+        if is_interrupted(e) {
+            eprintln!("{}", e);
+            std::process::exit(INTERRUPTED_EXIT_CODE);
         }
     }
 
-    Ok(())
+    result
+}
+
+async fn run_command(command: Commands) -> Result<()> {
+    match command {
+        Commands::Pipeline(args) => run_pipeline(*args).await,
+        Commands::Validate(args) => run_validate(args).await,
+        Commands::Revalidate(args) => run_revalidate(args).await,
+        Commands::Inspect(args) => run_inspect(args),
+        Commands::ExportGraph(args) => run_export_graph(args),
+        Commands::Sample(args) => run_sample(args),
+        Commands::Harvest(args) => run_harvest(args).await,
+        Commands::Cleanup(args) => run_cleanup(args),
+        #[cfg(feature = "parquet")]
+        Commands::CompactPartitions(args) => run_compact_partitions(args),
+        #[cfg(feature = "parquet")]
+        Commands::Index(command) => run_index(command),
+        #[cfg(feature = "parquet")]
+        Commands::Members(command) => run_members(command).await,
+    }
 }
@@ -0,0 +1,334 @@
+use crate::common::{CancellationToken, HttpClientConfig, Interrupted, PartialDate};
+use crate::error::{Context, Error, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Crossref REST API works route
+pub const CROSSREF_WORKS_URL: &str = "https://api.crossref.org/works";
+
+/// Largest page the works route returns
+pub const MAX_HARVEST_ROWS: usize = 1000;
+
+/// Attempts per page before a throttled or failing request gives up
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Header carrying a Metadata Plus API token
+const PLUS_TOKEN_HEADER: &str = "Crossref-Plus-API-Token";
+
+/// What to harvest from the Crossref REST API, and how
+#[derive(Debug, Clone)]
+pub struct HarvestOptions {
+    /// Works updated on or after this date
+    pub from_update_date: PartialDate,
+    /// Works updated on or before this date
+    pub until_update_date: Option<PartialDate>,
+    /// Client settings; its contact address routes requests to the polite pool
+    pub client: HttpClientConfig,
+    /// Metadata Plus token, for the Plus pool and its higher rate limits
+    pub plus_token: Option<String>,
+    /// Works per page, at most [`MAX_HARVEST_ROWS`]
+    pub rows: usize,
+    /// Timeout of each page request
+    pub timeout: Duration,
+}
+
+/// Statistics from a harvest
+#[derive(Debug, Clone, Default)]
+pub struct HarvestStats {
+    pub pages: usize,
+    pub works: usize,
+    /// Page requests repeated after throttling or a failure
+    pub retries: usize,
+}
+
+/// Harvest works updated in a date range into a snapshot-shaped tar.gz
+///
+/// Pages are fetched with a deep-paging cursor and each is written as a
+/// `harvest/page-NNNNNN.json` entry holding an `items` array, the layout of the
+/// monthly snapshot, so the archive is read by the same extraction as a snapshot.
+/// The archive is written under a `.partial` name and renamed when the last page is
+/// in. Cursors expire after a few minutes, so an interrupted harvest cannot be
+/// resumed; the pages harvested so far are left, readable, in the partial archive.
+pub async fn harvest_works(
+    options: &HarvestOptions,
+    output: &Path,
+    cancel: &CancellationToken,
+) -> Result<HarvestStats> {
+    let client = options.client.build()?;
+    let partial = partial_path(output);
+    let file = File::create(&partial)
+        .with_context(|| format!("Failed to create harvest archive: {:?}", partial))?;
+    let mut archive =
+        tar::Builder::new(GzEncoder::new(BufWriter::new(file), Compression::default()));
+
+    let mut stats = HarvestStats::default();
+    let mut cursor = "*".to_string();
+    let mut interrupted = false;
+    loop {
+        if cancel.is_cancelled() {
+            interrupted = true;
+            break;
+        }
+        let params = query_params(options, &cursor);
+        let body = fetch_api_page(
+            &client,
+            CROSSREF_WORKS_URL,
+            &params,
+            options.plus_token.as_deref(),
+            options.timeout,
+            &mut stats.retries,
+        )
+        .await?;
+        let (items, next_cursor) = parse_list_page(body)?;
+        if items.is_empty() {
+            break;
+        }
+
+        stats.pages += 1;
+        stats.works += items.len();
+        append_page(&mut archive, stats.pages, items)
+            .with_context(|| format!("Failed to write harvest archive: {:?}", partial))?;
+        info!("Harvested page {} ({} works)", stats.pages, stats.works);
+
+        match next_cursor {
+            Some(next) => cursor = next,
+            None => break,
+        }
+    }
+
+    archive
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .and_then(|mut writer| std::io::Write::flush(&mut writer))
+        .with_context(|| format!("Failed to finish harvest archive: {:?}", partial))?;
+    if interrupted {
+        warn!(
+            "Harvest interrupted after {} pages ({} works); they are kept in {}",
+            stats.pages,
+            stats.works,
+            partial.display()
+        );
+        return Err(Interrupted.into());
+    }
+    std::fs::rename(&partial, output)
+        .with_context(|| format!("Failed to rename {:?} to {:?}", partial, output))?;
+    Ok(stats)
+}
+
+/// Append a page of works as a snapshot JSON entry
+fn append_page<W: std::io::Write>(
+    archive: &mut tar::Builder<W>,
+    page_number: usize,
+    items: Vec<Value>,
+) -> Result<()> {
+    let page = serde_json::to_vec(&json!({ "items": items }))?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(page.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(
+        &mut header,
+        format!("harvest/page-{:06}.json", page_number),
+        page.as_slice(),
+    )?;
+    Ok(())
+}
+
+/// Fetch one page of a Crossref API route, retrying throttled (429), unavailable
+/// (5xx) and failed requests with exponential backoff and counting them in `retries`
+pub(super) async fn fetch_api_page(
+    client: &Client,
+    url: &str,
+    params: &[(&'static str, String)],
+    plus_token: Option<&str>,
+    timeout: Duration,
+    retries: &mut usize,
+) -> Result<Value> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let mut request = client.get(url).query(params).timeout(timeout);
+        if let Some(token) = plus_token {
+            request = request.header(PLUS_TOKEN_HEADER, format!("Bearer {}", token));
+        }
+
+        let failure = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                let text = response.text().await?;
+                return Ok(serde_json::from_str(&text)?);
+            }
+            Ok(response) => {
+                let status = response.status();
+                if status != StatusCode::TOO_MANY_REQUESTS && !status.is_server_error() {
+                    return Err(Error::invalid(format!(
+                        "Crossref API returned {}: {}",
+                        status,
+                        response.text().await.unwrap_or_default()
+                    )));
+                }
+                status.to_string()
+            }
+            Err(e) if attempt < MAX_ATTEMPTS => e.to_string(),
+            Err(e) => return Err(Error::from(e).context("Crossref API request failed")),
+        };
+        if attempt >= MAX_ATTEMPTS {
+            return Err(Error::invalid(format!(
+                "Crossref API still returned {} after {} attempts",
+                failure, MAX_ATTEMPTS
+            )));
+        }
+
+        let delay = Duration::from_secs(1 << attempt);
+        warn!(
+            "Crossref API request failed ({}); retrying in {}s",
+            failure,
+            delay.as_secs()
+        );
+        *retries += 1;
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Query parameters of a works page
+fn query_params(options: &HarvestOptions, cursor: &str) -> Vec<(&'static str, String)> {
+    let mut filter = format!("from-update-date:{}", options.from_update_date);
+    if let Some(until) = options.until_update_date {
+        filter.push_str(&format!(",until-update-date:{}", until));
+    }
+    let mut params = vec![
+        ("filter", filter),
+        ("rows", options.rows.clamp(1, MAX_HARVEST_ROWS).to_string()),
+        ("cursor", cursor.to_string()),
+    ];
+    if let Some(ref mailto) = options.client.mailto {
+        params.push(("mailto", mailto.clone()));
+    }
+    params
+}
+
+/// Items and next cursor of a list response (works, members)
+///
+/// The last page repeats the cursor with no items, so an empty page ends the listing.
+pub(super) fn parse_list_page(mut body: Value) -> Result<(Vec<Value>, Option<String>)> {
+    if body.get("status").and_then(Value::as_str) != Some("ok") {
+        return Err(Error::invalid(format!(
+            "Unexpected Crossref API response: {}",
+            body
+        )));
+    }
+    let next_cursor = body
+        .pointer("/message/next-cursor")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let items = match body.pointer_mut("/message/items").map(Value::take) {
+        Some(Value::Array(items)) => items,
+        _ => {
+            return Err(Error::invalid(
+                "Crossref API response has no message.items array",
+            ))
+        }
+    };
+    Ok((items, next_cursor))
+}
+
+fn partial_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".partial");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> HarvestOptions {
+        HarvestOptions {
+            from_update_date: "2024-01-15".parse().unwrap(),
+            until_update_date: None,
+            client: HttpClientConfig::default(),
+            plus_token: None,
+            rows: 5000,
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_query_params() {
+        let params = query_params(&options(), "*");
+        assert_eq!(
+            params,
+            [
+                ("filter", "from-update-date:2024-01-15".to_string()),
+                ("rows", "1000".to_string()),
+                ("cursor", "*".to_string()),
+            ]
+        );
+
+        let options = HarvestOptions {
+            until_update_date: Some("2024-02".parse().unwrap()),
+            client: HttpClientConfig {
+                mailto: Some("team@example.org".to_string()),
+                ..Default::default()
+            },
+            ..options()
+        };
+        let params = query_params(&options, "AoJ+abc=");
+        assert_eq!(
+            params[0].1,
+            "from-update-date:2024-01-15,until-update-date:2024-02"
+        );
+        assert_eq!(params[2].1, "AoJ+abc=");
+        assert_eq!(params[3], ("mailto", "team@example.org".to_string()));
+    }
+
+    #[test]
+    fn test_parse_works_page() {
+        let body = json!({
+            "status": "ok",
+            "message": {
+                "next-cursor": "AoJ+abc=",
+                "items": [{"DOI": "10.1234/a"}, {"DOI": "10.1234/b"}]
+            }
+        });
+        let (items, cursor) = parse_list_page(body).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1]["DOI"], "10.1234/b");
+        assert_eq!(cursor.as_deref(), Some("AoJ+abc="));
+
+        let error = json!({"status": "failed", "message": [{"type": "validation-failure"}]});
+        assert!(parse_list_page(error).is_err());
+    }
+
+    #[test]
+    fn test_harvest_pages_read_as_snapshot() {
+        let mut archive = tar::Builder::new(Vec::new());
+        let items = vec![json!({"DOI": "10.1234/a", "reference": [{"DOI": "10.5678/x"}]})];
+        append_page(&mut archive, 1, items).unwrap();
+        let bytes = archive.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(bytes.as_slice());
+        let mut entries = archive.entries().unwrap();
+        let entry = entries.next().unwrap().unwrap();
+        assert_eq!(
+            entry.path().unwrap().to_str(),
+            Some("harvest/page-000001.json")
+        );
+        let items = crate::streaming::parse_snapshot_json(entry).unwrap();
+        assert_eq!(items[0]["reference"][0]["DOI"], "10.5678/x");
+    }
+
+    #[test]
+    fn test_partial_path() {
+        assert_eq!(
+            partial_path(Path::new("/data/harvest.tar.gz")),
+            PathBuf::from("/data/harvest.tar.gz.partial")
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use crate::common::{CancellationToken, HttpClientConfig, Interrupted};
+use crate::error::{Context, Error, Result};
+use log::info;
+use polars::prelude::*;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use super::api_harvest::{fetch_api_page, parse_list_page};
+
+/// Crossref REST API members route
+pub const CROSSREF_MEMBERS_URL: &str = "https://api.crossref.org/members";
+
+/// Largest page the members route returns
+pub const MAX_MEMBER_ROWS: usize = 1000;
+
+/// How to list Crossref members
+#[derive(Debug, Clone)]
+pub struct MembersOptions {
+    /// Client settings; its contact address routes requests to the polite pool
+    pub client: HttpClientConfig,
+    /// Metadata Plus token, for the Plus pool and its higher rate limits
+    pub plus_token: Option<String>,
+    /// Members per page, at most [`MAX_MEMBER_ROWS`]
+    pub rows: usize,
+    /// Timeout of each page request
+    pub timeout: Duration,
+}
+
+/// A DOI prefix and the Crossref member it belongs to, a row of the members table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberPrefix {
+    /// Lowercase DOI prefix (`10.1016`)
+    pub prefix: String,
+    pub member_id: u64,
+    /// The member's primary name
+    pub member_name: String,
+    /// Name the prefix is registered under, when the member lists one
+    pub prefix_name: Option<String>,
+}
+
+/// Statistics from a members sync
+#[derive(Debug, Clone, Default)]
+pub struct MembersSyncStats {
+    pub pages: usize,
+    pub members: usize,
+    pub prefixes: usize,
+    /// Page requests repeated after throttling or a failure
+    pub retries: usize,
+}
+
+/// Whether the members table at `path` was written less than `max_age` ago
+pub fn members_table_is_fresh(path: &Path, max_age: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < max_age)
+}
+
+/// List every Crossref member and write the prefix to member table as Parquet
+///
+/// Pages are fetched with a deep-paging cursor. The table, one row per prefix sorted
+/// by prefix, is written to a temporary file and renamed once the last page is in,
+/// so an interrupted or failed sync leaves an earlier table in place.
+pub async fn sync_members(
+    options: &MembersOptions,
+    output: &Path,
+    cancel: &CancellationToken,
+) -> Result<MembersSyncStats> {
+    let client = options.client.build()?;
+    let mut stats = MembersSyncStats::default();
+    let mut prefixes: BTreeMap<String, MemberPrefix> = BTreeMap::new();
+    let mut cursor = "*".to_string();
+    loop {
+        if cancel.is_cancelled() {
+            return Err(Interrupted.into());
+        }
+        let params = query_params(options, &cursor);
+        let body = fetch_api_page(
+            &client,
+            CROSSREF_MEMBERS_URL,
+            &params,
+            options.plus_token.as_deref(),
+            options.timeout,
+            &mut stats.retries,
+        )
+        .await?;
+        let (items, next_cursor) = parse_list_page(body)?;
+        if items.is_empty() {
+            break;
+        }
+
+        stats.pages += 1;
+        stats.members += items.len();
+        for row in items.iter().flat_map(member_prefixes) {
+            prefixes.entry(row.prefix.clone()).or_insert(row);
+        }
+        info!(
+            "Listed page {} ({} members, {} prefixes)",
+            stats.pages,
+            stats.members,
+            prefixes.len()
+        );
+
+        match next_cursor {
+            Some(next) => cursor = next,
+            None => break,
+        }
+    }
+
+    let rows: Vec<MemberPrefix> = prefixes.into_values().collect();
+    stats.prefixes = rows.len();
+    write_members_table(&rows, output)?;
+    Ok(stats)
+}
+
+/// Query parameters of a members page
+fn query_params(options: &MembersOptions, cursor: &str) -> Vec<(&'static str, String)> {
+    let mut params = vec![
+        ("rows", options.rows.clamp(1, MAX_MEMBER_ROWS).to_string()),
+        ("cursor", cursor.to_string()),
+    ];
+    if let Some(ref mailto) = options.client.mailto {
+        params.push(("mailto", mailto.clone()));
+    }
+    params
+}
+
+/// Prefixes of a member record
+///
+/// The `prefix` list carries a name per prefix; members without it only have the
+/// plain `prefixes` list.
+fn member_prefixes(member: &Value) -> Vec<MemberPrefix> {
+    let (Some(member_id), Some(member_name)) = (
+        member.get("id").and_then(Value::as_u64),
+        member.get("primary-name").and_then(Value::as_str),
+    ) else {
+        return Vec::new();
+    };
+    let row = |prefix: &str, prefix_name: Option<&str>| MemberPrefix {
+        prefix: prefix.trim().to_lowercase(),
+        member_id,
+        member_name: member_name.to_string(),
+        prefix_name: prefix_name.map(str::to_string),
+    };
+
+    match member.get("prefix").and_then(Value::as_array) {
+        Some(named) if !named.is_empty() => named
+            .iter()
+            .filter_map(|entry| {
+                let prefix = entry.get("value").and_then(Value::as_str)?;
+                Some(row(prefix, entry.get("name").and_then(Value::as_str)))
+            })
+            .collect(),
+        _ => member
+            .get("prefixes")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(|prefix| row(prefix, None))
+            .collect(),
+    }
+}
+
+fn write_members_table(rows: &[MemberPrefix], path: &Path) -> Result<()> {
+    let mut df = DataFrame::new(vec![
+        Column::new(
+            "prefix".into(),
+            rows.iter().map(|r| r.prefix.as_str()).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "member_id".into(),
+            rows.iter().map(|r| r.member_id).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "member_name".into(),
+            rows.iter()
+                .map(|r| r.member_name.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "prefix_name".into(),
+            rows.iter()
+                .map(|r| r.prefix_name.as_deref())
+                .collect::<Vec<_>>(),
+        ),
+    ])?;
+
+    let tmp_path = path.with_extension("parquet.tmp");
+    let file =
+        File::create(&tmp_path).with_context(|| format!("Failed to create: {:?}", tmp_path))?;
+    ParquetWriter::new(file)
+        .with_compression(ParquetCompression::Zstd(None))
+        .finish(&mut df)
+        .with_context(|| format!("Failed to write members table: {:?}", tmp_path))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))
+}
+
+/// Read a members table written by [`sync_members`]
+pub fn read_members_table(path: &Path) -> Result<Vec<MemberPrefix>> {
+    let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+    let df = ParquetReader::new(file)
+        .finish()
+        .with_context(|| format!("Failed to read members table: {:?}", path))?;
+    let column = |name: &str| {
+        df.column(name)
+            .map_err(|_| Error::invalid(format!("{:?} has no {} column", path, name)))
+    };
+    let prefix = column("prefix")?.str()?;
+    let member_id = column("member_id")?.u64()?;
+    let member_name = column("member_name")?.str()?;
+    let prefix_name = column("prefix_name")?.str()?;
+
+    Ok((0..df.height())
+        .filter_map(|i| {
+            Some(MemberPrefix {
+                prefix: prefix.get(i)?.to_string(),
+                member_id: member_id.get(i)?,
+                member_name: member_name.get(i)?.to_string(),
+                prefix_name: prefix_name.get(i).map(str::to_string),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_member_prefixes() {
+        let member = json!({
+            "id": 78,
+            "primary-name": "Elsevier BV",
+            "prefixes": ["10.1016", "10.2139"],
+            "prefix": [
+                {"value": "10.1016", "name": "Elsevier BV", "public-references": true},
+                {"value": "10.2139", "name": "SSRN Electronic Journal"}
+            ]
+        });
+        let rows = member_prefixes(&member);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].prefix, "10.2139");
+        assert_eq!(rows[1].member_id, 78);
+        assert_eq!(rows[1].member_name, "Elsevier BV");
+        assert_eq!(
+            rows[1].prefix_name.as_deref(),
+            Some("SSRN Electronic Journal")
+        );
+
+        let plain = json!({"id": 1, "primary-name": "Example", "prefixes": ["10.9999"]});
+        let rows = member_prefixes(&plain);
+        assert_eq!(rows[0].prefix, "10.9999");
+        assert_eq!(rows[0].prefix_name, None);
+
+        assert!(member_prefixes(&json!({"prefixes": ["10.9999"]})).is_empty());
+    }
+
+    #[test]
+    fn test_members_table_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("members.parquet");
+        let rows = vec![
+            MemberPrefix {
+                prefix: "10.1016".to_string(),
+                member_id: 78,
+                member_name: "Elsevier BV".to_string(),
+                prefix_name: Some("Elsevier BV".to_string()),
+            },
+            MemberPrefix {
+                prefix: "10.9999".to_string(),
+                member_id: 1,
+                member_name: "Example".to_string(),
+                prefix_name: None,
+            },
+        ];
+        write_members_table(&rows, &path).unwrap();
+        assert_eq!(read_members_table(&path).unwrap(), rows);
+        assert!(members_table_is_fresh(&path, Duration::from_secs(3600)));
+        assert!(!members_table_is_fresh(&path, Duration::ZERO));
+        assert!(!members_table_is_fresh(
+            &dir.path().join("missing.parquet"),
+            Duration::from_secs(3600)
+        ));
+    }
+
+    #[test]
+    fn test_members_query_params() {
+        let options = MembersOptions {
+            client: HttpClientConfig {
+                mailto: Some("team@example.org".to_string()),
+                ..Default::default()
+            },
+            plus_token: None,
+            rows: 5000,
+            timeout: Duration::from_secs(60),
+        };
+        assert_eq!(
+            query_params(&options, "*"),
+            [
+                ("rows", "1000".to_string()),
+                ("cursor", "*".to_string()),
+                ("mailto", "team@example.org".to_string()),
+            ]
+        );
+    }
+}
@@ -1,8 +1,8 @@
-use anyhow::{Context, Result};
+use crate::error::{Context, Error, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Pipeline phase for checkpoint tracking
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -11,6 +11,8 @@ pub enum PipelinePhase {
     ConvertExtract,
     /// Phase 2: Inverting partitions
     Invert,
+    /// Phase 3: Validating inverted citations
+    Validate,
     /// Pipeline completed successfully
     Complete,
 }
@@ -24,12 +26,25 @@ pub struct Checkpoint {
     pub phase: PipelinePhase,
     /// Tar entries processed (for resume in phase 1)
     pub tar_entries_processed: usize,
+    /// Name of the last tar entry processed, which extraction resumes after
+    #[serde(default)]
+    pub last_tar_entry: Option<String>,
+    /// Snapshot the entries were read from
+    #[serde(default)]
+    pub input: Option<String>,
     /// Partitions that have been fully written (phase 1 complete marker)
     pub partitions_written: HashSet<String>,
     /// Partitions that have been inverted (phase 2 progress)
     pub partitions_inverted: HashSet<String>,
+    /// Partitions that failed to invert, by path relative to the temp dir
+    /// (`<target>/<file>`), with their error (retried on request)
+    #[serde(default)]
+    pub partitions_failed: BTreeMap<String, String>,
     /// Statistics collected during processing
     pub stats: CheckpointStats,
+    /// Sidecar file holding HTTP fallback results (phase 3 progress)
+    #[serde(default)]
+    pub http_checkpoint: Option<PathBuf>,
 }
 
 /// Statistics tracked in checkpoint
@@ -40,6 +55,8 @@ pub struct CheckpointStats {
     pub total_references: usize,
     pub references_with_matches: usize,
     pub total_arxiv_ids_extracted: usize,
+    #[serde(default)]
+    pub total_matches: usize,
 }
 
 impl Checkpoint {
@@ -49,14 +66,17 @@ impl Checkpoint {
             run_id: run_id.to_string(),
             phase: PipelinePhase::ConvertExtract,
             tar_entries_processed: 0,
+            last_tar_entry: None,
+            input: None,
             partitions_written: HashSet::new(),
             partitions_inverted: HashSet::new(),
+            partitions_failed: BTreeMap::new(),
             stats: CheckpointStats::default(),
+            http_checkpoint: None,
         }
     }
 
     /// Save checkpoint to file
-    #[allow(dead_code)]
     pub fn save(&self, path: &Path) -> Result<()> {
         let json = serde_json::to_string_pretty(self).context("Failed to serialize checkpoint")?;
         fs::write(path, json)
@@ -65,7 +85,6 @@ impl Checkpoint {
     }
 
     /// Load checkpoint from file, returning None if file doesn't exist
-    #[allow(dead_code)]
     pub fn load(path: &Path) -> Result<Option<Self>> {
         if !path.exists() {
             return Ok(None);
@@ -77,6 +96,26 @@ impl Checkpoint {
         Ok(Some(checkpoint))
     }
 
+    /// Tar entries an interrupted extraction of `input` got through, with the name of
+    /// the last one; None when extraction finished or never recorded an entry
+    pub fn extraction_watermark(&self, input: &str) -> Result<Option<(usize, &str)>> {
+        if self.phase != PipelinePhase::ConvertExtract {
+            return Ok(None);
+        }
+        if let Some(ref recorded) = self.input {
+            if recorded != input {
+                return Err(Error::invalid(format!(
+                    "The checkpoint is for an extraction of {}, not {}",
+                    recorded, input
+                )));
+            }
+        }
+        Ok(self
+            .last_tar_entry
+            .as_deref()
+            .map(|name| (self.tar_entries_processed, name)))
+    }
+
     /// Mark a partition as inverted (phase 2 complete for this partition)
     pub fn mark_partition_inverted(&mut self, partition: &str) {
         self.partitions_inverted.insert(partition.to_string());
@@ -87,19 +126,60 @@ impl Checkpoint {
         self.partitions_inverted.contains(partition)
     }
 
+    /// Record a partition that failed to invert, replacing any earlier error
+    pub fn mark_partition_failed(&mut self, partition_path: &Path, error: String) {
+        self.partitions_failed
+            .insert(partition_key(partition_path), error);
+    }
+
+    /// Forget an earlier failure of a partition
+    pub fn clear_partition_failed(&mut self, partition_path: &Path) {
+        self.partitions_failed
+            .remove(&partition_key(partition_path));
+    }
+
+    /// Failed partitions located in the given partition directory
+    ///
+    /// Failures are matched by the directory's name, so they are found after the temp
+    /// dir was moved.
+    pub fn failed_partitions_in(&self, partition_dir: &Path) -> Vec<PathBuf> {
+        self.partitions_failed
+            .keys()
+            .map(Path::new)
+            .filter(|key| key.parent().and_then(Path::file_name) == partition_dir.file_name())
+            .filter_map(|key| Some(partition_dir.join(key.file_name()?)))
+            .collect()
+    }
+
     /// Transition to invert phase
-    #[allow(dead_code)]
     pub fn start_invert_phase(&mut self) {
         self.phase = PipelinePhase::Invert;
     }
 
+    /// Transition to validate phase, recording where HTTP results are persisted
+    pub fn start_validate_phase(&mut self, http_checkpoint: Option<PathBuf>) {
+        self.phase = PipelinePhase::Validate;
+        self.http_checkpoint = http_checkpoint;
+    }
+
     /// Mark pipeline as complete
-    #[allow(dead_code)]
     pub fn mark_complete(&mut self) {
         self.phase = PipelinePhase::Complete;
     }
 }
 
+/// Name a failed partition is recorded under: its target directory and file name,
+/// which is its path relative to the temp dir
+fn partition_key(partition_path: &Path) -> String {
+    let file_name = partition_path.file_name().unwrap_or_default();
+    match partition_path.parent().and_then(Path::file_name) {
+        Some(dir) => Path::new(dir).join(file_name),
+        None => PathBuf::from(file_name),
+    }
+    .to_string_lossy()
+    .into_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,6 +210,38 @@ mod tests {
         assert_eq!(loaded.stats.total_references, 1000);
     }
 
+    #[test]
+    fn test_extraction_watermark() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+
+        // Checkpoints written before the watermark was recorded still load
+        fs::write(
+            &path,
+            r#"{"run_id": "old", "phase": "ConvertExtract", "tar_entries_processed": 3,
+                "partitions_written": [], "partitions_inverted": [],
+                "stats": {"json_files_processed": 3, "total_records": 10,
+                          "total_references": 0, "references_with_matches": 4,
+                          "total_arxiv_ids_extracted": 0}}"#,
+        )
+        .unwrap();
+        let old = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(old.extraction_watermark("snapshot.tar.gz").unwrap(), None);
+
+        let mut cp = Checkpoint::new("run");
+        cp.tar_entries_processed = 3;
+        cp.last_tar_entry = Some("snapshot/2.json".to_string());
+        cp.input = Some("snapshot.tar.gz".to_string());
+        assert_eq!(
+            cp.extraction_watermark("snapshot.tar.gz").unwrap(),
+            Some((3, "snapshot/2.json"))
+        );
+        assert!(cp.extraction_watermark("other.tar.gz").is_err());
+
+        cp.start_invert_phase();
+        assert_eq!(cp.extraction_watermark("snapshot.tar.gz").unwrap(), None);
+    }
+
     #[test]
     fn test_checkpoint_load_nonexistent() {
         let result = Checkpoint::load(Path::new("/nonexistent/path.json")).unwrap();
@@ -147,4 +259,54 @@ mod tests {
         assert!(cp.is_partition_inverted("2403"));
         assert!(!cp.is_partition_inverted("2404"));
     }
+
+    #[test]
+    fn test_failed_partition_tracking() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        let doi_dir = dir.path().join("all");
+
+        let mut cp = Checkpoint::new("run123");
+        cp.mark_partition_failed(&doi_dir.join("10.1234.parquet"), "bad footer".to_string());
+        cp.mark_partition_failed(&dir.path().join("arxiv/2403.parquet"), "bad".to_string());
+        cp.save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(
+            loaded.failed_partitions_in(&doi_dir),
+            vec![doi_dir.join("10.1234.parquet")]
+        );
+        assert_eq!(
+            loaded.partitions_failed["all/10.1234.parquet"],
+            "bad footer"
+        );
+
+        // Found again after the temp dir moved
+        let moved = tempdir().unwrap();
+        assert_eq!(
+            loaded.failed_partitions_in(&moved.path().join("all")),
+            vec![moved.path().join("all/10.1234.parquet")]
+        );
+
+        let mut loaded = loaded;
+        loaded.clear_partition_failed(&moved.path().join("arxiv/2403.parquet"));
+        assert_eq!(loaded.partitions_failed.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_phase_records_http_checkpoint() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+
+        let mut cp = Checkpoint::new("run123");
+        cp.start_validate_phase(Some(dir.path().join("http_checkpoint.jsonl")));
+        cp.save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.phase, PipelinePhase::Validate);
+        assert_eq!(
+            loaded.http_checkpoint,
+            Some(dir.path().join("http_checkpoint.jsonl"))
+        );
+    }
 }
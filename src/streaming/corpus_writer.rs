@@ -0,0 +1,102 @@
+use crate::common::{open_sink_with, OutputOptions, OutputSink};
+use crate::error::Result;
+use log::info;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+use crate::extract::Provenance;
+
+/// A single unstructured reference string paired with its DOI label (if any)
+#[derive(Debug, Clone)]
+pub struct CorpusRow {
+    pub citing_doi: String,
+    pub ref_index: u32,
+    pub unstructured: String,
+    pub doi: Option<String>,
+    pub provenance: Option<Provenance>,
+}
+
+/// Streams unstructured reference strings and their DOI labels to a corpus file
+///
+/// Columns: citing_doi, ref_index, unstructured, doi (nullable), provenance (nullable).
+/// Written through the sink the path implies (Parquet for `.parquet`), with the shard
+/// options of the other outputs.
+pub struct ReferenceCorpusWriter {
+    path: PathBuf,
+    sink: Box<dyn OutputSink>,
+}
+
+impl ReferenceCorpusWriter {
+    /// Create the corpus output
+    pub fn new(path: &Path, options: &OutputOptions) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            sink: open_sink_with(path, options)?,
+        })
+    }
+
+    pub fn write(&mut self, row: CorpusRow) -> Result<()> {
+        self.sink.write_record(&json!({
+            "citing_doi": row.citing_doi,
+            "ref_index": row.ref_index,
+            "unstructured": row.unstructured,
+            "doi": row.doi,
+            "provenance": row.provenance.map(|p| p.as_str()),
+        }))
+    }
+
+    /// Finish the corpus output, returning rows written
+    pub fn finish(self) -> Result<usize> {
+        let rows_written = self.sink.finish()?;
+        info!(
+            "Wrote {} reference corpus rows to: {:?}",
+            rows_written, self.path
+        );
+        Ok(rows_written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_corpus_writer_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("corpus.parquet");
+
+        let mut writer = ReferenceCorpusWriter::new(&path, &OutputOptions::default()).unwrap();
+        writer
+            .write(CorpusRow {
+                citing_doi: "10.1234/a".to_string(),
+                ref_index: 0,
+                unstructured: "Smith J. Some title. doi:10.5678/x".to_string(),
+                doi: Some("10.5678/x".to_string()),
+                provenance: Some(Provenance::Mined),
+            })
+            .unwrap();
+        writer
+            .write(CorpusRow {
+                citing_doi: "10.1234/a".to_string(),
+                ref_index: 1,
+                unstructured: "Doe J. A book without identifiers".to_string(),
+                doi: None,
+                provenance: None,
+            })
+            .unwrap();
+        assert_eq!(writer.finish().unwrap(), 2);
+
+        let df = LazyFrame::scan_parquet(&path, Default::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        assert_eq!(df.height(), 2);
+        let dois = df.column("doi").unwrap().str().unwrap();
+        assert_eq!(dois.get(0), Some("10.5678/x"));
+        assert_eq!(dois.get(1), None);
+        let provenances = df.column("provenance").unwrap().str().unwrap();
+        assert_eq!(provenances.get(0), Some("mined"));
+    }
+}
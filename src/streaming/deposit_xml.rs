@@ -0,0 +1,389 @@
+use crate::error::{Context, Result};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde_json::{json, Map, Value};
+use std::io::BufRead;
+
+/// Citation child elements and the Crossref API reference keys they map to
+const CITATION_FIELDS: [(&str, &str); 17] = [
+    ("doi", "DOI"),
+    ("unstructured_citation", "unstructured"),
+    ("journal_title", "journal-title"),
+    ("article_title", "article-title"),
+    ("author", "author"),
+    ("cYear", "year"),
+    ("volume", "volume"),
+    ("issue", "issue"),
+    ("first_page", "first-page"),
+    ("series_title", "series-title"),
+    ("volume_title", "volume-title"),
+    ("edition_number", "edition"),
+    ("component_number", "component"),
+    ("std_designator", "standard-designator"),
+    ("standards_body_name", "standards-body"),
+    ("isbn", "ISBN"),
+    ("issn", "ISSN"),
+];
+
+/// Parsed XML element: local name, attributes and children
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// Element at a `/`-separated path of child names
+    fn path(&self, path: &str) -> Option<&Element> {
+        path.split('/')
+            .try_fold(self, |element, name| element.child(name))
+    }
+
+    /// Text of the element and its descendants with whitespace runs collapsed
+    fn text(&self) -> String {
+        let mut raw = String::new();
+        self.push_text(&mut raw);
+        raw.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn push_text(&self, out: &mut String) {
+        for child in &self.children {
+            match child {
+                Node::Element(element) => element.push_text(out),
+                Node::Text(text) => out.push_str(text),
+            }
+        }
+    }
+}
+
+/// Parse Crossref deposit XML into works shaped like Crossref API items
+///
+/// Accepts deposit files (`doi_batch`) and UNIXREF/UNIXSD query responses
+/// (`doi_records`), which share the work schema. Every element with a `doi_data`
+/// child becomes an item with `DOI`, `type`, `container-title` when known, and a
+/// `reference` array built from its `citation_list`, using the keys the JSON
+/// snapshot uses so extraction treats both inputs alike.
+pub fn parse_deposit_xml<R: BufRead>(reader: R) -> Result<Vec<Value>> {
+    let root = read_tree(reader)?;
+    let mut items = Vec::new();
+    collect_works(&root, None, &mut items);
+    Ok(items)
+}
+
+/// Read a whole XML document into an element tree under a nameless root
+fn read_tree<R: BufRead>(reader: R) -> Result<Element> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut stack = vec![Element::default()];
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .with_context(|| format!("Invalid XML at byte {}", reader.buffer_position()))?;
+        match event {
+            Event::Start(start) => stack.push(start_element(&start)?),
+            Event::Empty(start) => {
+                let element = start_element(&start)?;
+                push_child(&mut stack, Node::Element(element));
+            }
+            Event::End(_) if stack.len() > 1 => {
+                let element = stack.pop().expect("stack holds the root");
+                push_child(&mut stack, Node::Element(element));
+            }
+            Event::Text(text) => {
+                let text = text.unescape().context("Invalid XML text")?;
+                push_child(&mut stack, Node::Text(text.into_owned()));
+            }
+            Event::CData(data) => {
+                let text = String::from_utf8_lossy(&data.into_inner()).into_owned();
+                push_child(&mut stack, Node::Text(text));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    // Close elements left open by a truncated document
+    while stack.len() > 1 {
+        let element = stack.pop().expect("stack holds the root");
+        push_child(&mut stack, Node::Element(element));
+    }
+    Ok(stack.pop().expect("stack holds the root"))
+}
+
+fn start_element(start: &quick_xml::events::BytesStart) -> Result<Element> {
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.context("Invalid XML attribute")?;
+        let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+        let value = attribute
+            .unescape_value()
+            .context("Invalid XML attribute value")?;
+        attributes.push((key, value.into_owned()));
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+        attributes,
+        children: Vec::new(),
+    })
+}
+
+fn push_child(stack: &mut [Element], node: Node) {
+    stack
+        .last_mut()
+        .expect("stack holds the root")
+        .children
+        .push(node);
+}
+
+/// Walk the tree, emitting an item for each work and tracking the enclosing
+/// journal, proceedings or book title
+fn collect_works(element: &Element, container: Option<&str>, items: &mut Vec<Value>) {
+    let title = container_title(element);
+    let container = title.as_deref().or(container);
+
+    if let Some(doi) = element.path("doi_data/doi").map(Element::text) {
+        if !doi.is_empty() {
+            items.push(work_item(element, &doi, container));
+        }
+    }
+    for child in element.elements() {
+        collect_works(child, container, items);
+    }
+}
+
+/// Title a container element gives the works inside it
+fn container_title(element: &Element) -> Option<String> {
+    let title = match element.name.as_str() {
+        "journal" => element.path("journal_metadata/full_title"),
+        "conference" => element
+            .path("proceedings_metadata/proceedings_title")
+            .or_else(|| element.path("proceedings_series_metadata/proceedings_title")),
+        "book" => element
+            .path("book_metadata/titles/title")
+            .or_else(|| element.path("book_series_metadata/titles/title"))
+            .or_else(|| element.path("book_set_metadata/titles/title")),
+        _ => None,
+    }?;
+    Some(title.text()).filter(|title| !title.is_empty())
+}
+
+fn work_item(element: &Element, doi: &str, container: Option<&str>) -> Value {
+    let mut item = Map::new();
+    let work_type = work_type(element);
+    // Journals, proceedings and books are not their own containers
+    let is_container = matches!(work_type.as_str(), "journal" | "proceedings" | "book");
+    item.insert("DOI".to_string(), json!(doi));
+    item.insert("type".to_string(), json!(work_type));
+    if let Some(container) = container.filter(|_| !is_container) {
+        item.insert("container-title".to_string(), json!([container]));
+    }
+    if let Some(date_parts) = publication_date(element) {
+        item.insert("issued".to_string(), json!({ "date-parts": [date_parts] }));
+    }
+    if let Some(citations) = element.child("citation_list") {
+        let references: Vec<Value> = citations
+            .elements()
+            .filter(|citation| citation.name == "citation")
+            .map(citation_reference)
+            .collect();
+        item.insert("reference-count".to_string(), json!(references.len()));
+        item.insert("reference".to_string(), Value::Array(references));
+    }
+    Value::Object(item)
+}
+
+/// `[year, month, day]` of a work's first `publication_date`, as far as it is known
+///
+/// Book chapters and proceedings papers often carry no date of their own, so the
+/// work's metadata element (e.g. `book_metadata`) is used when the work has none.
+fn publication_date(element: &Element) -> Option<Vec<i64>> {
+    let date = element.child("publication_date").or_else(|| {
+        element
+            .elements()
+            .filter(|child| child.name.ends_with("_metadata"))
+            .find_map(|metadata| metadata.child("publication_date"))
+    })?;
+    let part = |name: &str| date.child(name).and_then(|e| e.text().parse::<i64>().ok());
+    let mut parts = vec![part("year")?];
+    if let Some(month) = part("month") {
+        parts.push(month);
+        parts.extend(part("day"));
+    }
+    Some(parts)
+}
+
+/// Crossref API work type for a work element
+fn work_type(element: &Element) -> String {
+    let work_type = match element.name.as_str() {
+        "journal_article" => "journal-article",
+        "journal_issue" => "journal-issue",
+        "journal_metadata" => "journal",
+        "conference_paper" => "proceedings-article",
+        "proceedings_metadata" => "proceedings",
+        "content_item" => match element.attribute("component_type") {
+            Some("section") => "book-section",
+            Some("part") => "book-part",
+            Some("track") => "book-track",
+            Some("reference_entry") => "reference-entry",
+            _ => "book-chapter",
+        },
+        "book_metadata" | "book_series_metadata" | "book_set_metadata" => "book",
+        "report-paper_metadata" => "report",
+        "standard_metadata" => "standard",
+        "posted_content" => "posted-content",
+        "sa_component" | "component" => "component",
+        "peer_review" => "peer-review",
+        "database_metadata" => "database",
+        other => return other.replace('_', "-"),
+    };
+    work_type.to_string()
+}
+
+/// Crossref API reference object for a `citation` element
+fn citation_reference(citation: &Element) -> Value {
+    let mut reference = Map::new();
+    if let Some(key) = citation.attribute("key") {
+        reference.insert("key".to_string(), json!(key));
+    }
+    for (element_name, key) in CITATION_FIELDS {
+        let Some(element) = citation.child(element_name) else {
+            continue;
+        };
+        let text = element.text();
+        if text.is_empty() {
+            continue;
+        }
+        if key == "DOI" {
+            // UNIXSD marks DOIs Crossref matched with provider="crossref"; deposited
+            // DOIs are the publisher's
+            let asserted_by = match element.attribute("provider") {
+                Some("crossref") => "crossref",
+                _ => "publisher",
+            };
+            reference.insert("doi-asserted-by".to_string(), json!(asserted_by));
+        }
+        reference.insert(key.to_string(), json!(text));
+    }
+    Value::Object(reference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPOSIT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<doi_batch xmlns="http://www.crossref.org/schema/5.3.1" version="5.3.1">
+  <head><doi_batch_id>batch-1</doi_batch_id></head>
+  <body>
+    <journal>
+      <journal_metadata><full_title>Journal of  Synthetic
+        Results</full_title></journal_metadata>
+      <journal_article publication_type="full_text">
+        <titles><title>An article</title></titles>
+        <publication_date media_type="online"><month>03</month><year>2021</year></publication_date>
+        <doi_data><doi>10.1234/ABC.1</doi><resource>https://example.org/1</resource></doi_data>
+        <citation_list>
+          <citation key="ref1">
+            <journal_title>Nature</journal_title>
+            <author>Smith</author>
+            <cYear>2020</cYear>
+            <doi>10.5678/x</doi>
+          </citation>
+          <citation key="ref2">
+            <unstructured_citation>Jones, A. (2019). Data &amp; more. <i>J. Test</i>
+              doi:10.9999/y</unstructured_citation>
+          </citation>
+          <citation key="ref3"><![CDATA[ignored]]></citation>
+        </citation_list>
+      </journal_article>
+    </journal>
+  </body>
+</doi_batch>"#;
+
+    #[test]
+    fn test_parse_deposit() {
+        let items = parse_deposit_xml(DEPOSIT.as_bytes()).unwrap();
+        assert_eq!(items.len(), 1);
+
+        let item = &items[0];
+        assert_eq!(item["DOI"], "10.1234/ABC.1");
+        assert_eq!(item["type"], "journal-article");
+        assert_eq!(item["container-title"][0], "Journal of Synthetic Results");
+        assert_eq!(item["reference-count"], 3);
+        assert_eq!(item["issued"], json!({"date-parts": [[2021, 3]]}));
+
+        let references = item["reference"].as_array().unwrap();
+        assert_eq!(
+            references[0],
+            json!({
+                "key": "ref1",
+                "journal-title": "Nature",
+                "author": "Smith",
+                "year": "2020",
+                "DOI": "10.5678/x",
+                "doi-asserted-by": "publisher",
+            })
+        );
+        assert_eq!(
+            references[1]["unstructured"],
+            "Jones, A. (2019). Data & more. J. Test doi:10.9999/y"
+        );
+        assert_eq!(references[2], json!({"key": "ref3"}));
+    }
+
+    #[test]
+    fn test_parse_unixsd_book() {
+        let xml = r#"<crossref_result><query_result><body><query status="resolved">
+<doi_record><crossref><book book_type="edited_book">
+  <book_metadata><titles><title>A Book</title></titles>
+    <doi_data><doi>10.1000/book</doi></doi_data></book_metadata>
+  <content_item component_type="chapter">
+    <doi_data><doi>10.1000/book.ch1</doi></doi_data>
+    <citation_list><citation key="c1"><doi provider="crossref">10.2000/z</doi></citation></citation_list>
+  </content_item>
+</book></crossref></doi_record></query></body></query_result></crossref_result>"#;
+
+        let items = parse_deposit_xml(xml.as_bytes()).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["type"], "book");
+        assert!(items[0].get("container-title").is_none());
+        assert!(items[0].get("reference").is_none());
+        assert!(items[0].get("issued").is_none());
+
+        assert_eq!(items[1]["type"], "book-chapter");
+        assert_eq!(items[1]["container-title"][0], "A Book");
+        assert_eq!(items[1]["reference"][0]["doi-asserted-by"], "crossref");
+    }
+
+    #[test]
+    fn test_parse_invalid_xml() {
+        assert!(parse_deposit_xml("<a><b></a>".as_bytes()).is_err());
+    }
+}
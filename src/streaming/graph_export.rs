@@ -0,0 +1,519 @@
+use crate::error::{Context, Result};
+use log::info;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::open_compressed_reader;
+use crate::common::{shard_path, CitationRecord, ShardLimit};
+use crate::error::Error;
+
+/// Node file of a Neo4j export
+pub const NEO4J_NODES_FILE: &str = "works.csv";
+/// Relationship file of a Neo4j export
+pub const NEO4J_RELATIONSHIPS_FILE: &str = "cites.csv";
+
+/// Layout of an exported citation graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Node and relationship CSVs for `neo4j-admin database import`
+    Neo4j,
+    /// A single GraphML document
+    Graphml,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "neo4j" => Ok(GraphFormat::Neo4j),
+            "graphml" => Ok(GraphFormat::Graphml),
+            _ => Err(format!(
+                "Invalid graph format: {}. Valid options: neo4j, graphml",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphFormat::Neo4j => write!(f, "neo4j"),
+            GraphFormat::Graphml => write!(f, "graphml"),
+        }
+    }
+}
+
+/// Counts from a graph export
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphExportStats {
+    pub records_read: usize,
+    pub works: usize,
+    pub citations: usize,
+}
+
+/// One CITES relationship, from the citing work to the cited work
+struct Citation<'a> {
+    citing: &'a str,
+    cited: &'a str,
+    provenance: &'a str,
+    same_prefix: Option<bool>,
+}
+
+/// Write the citations in validated JSONL outputs (plain or compressed) as a graph
+///
+/// Works are nodes keyed by DOI (arXiv DOI for arXiv outputs); cited works carry their
+/// `citation_count`. Each `cited_by` entry becomes a CITES relationship with its
+/// provenance and, when present, its `same_prefix` flag. Neo4j output is a directory
+/// holding `works.csv` and `cites.csv`, or with a `shard` limit a header file and
+/// numbered data files for each (see [`CsvOutput`]); GraphML output is a single file
+/// and cannot be sharded.
+pub fn export_graph(
+    inputs: &[PathBuf],
+    format: GraphFormat,
+    output: &Path,
+    shard: Option<ShardLimit>,
+) -> Result<GraphExportStats> {
+    if format == GraphFormat::Graphml && shard.is_some() {
+        return Err(Error::invalid(
+            "GraphML is a single document and cannot be sharded; use --format neo4j",
+        ));
+    }
+    info!("Exporting citation graph ({}) to: {:?}", format, output);
+
+    let mut stats = GraphExportStats::default();
+    // DOI -> citation_count, known only for cited works
+    let mut works: BTreeMap<String, Option<usize>> = BTreeMap::new();
+
+    // Relationships stream to disk; nodes are written once every input has been read
+    let mut edges = match format {
+        GraphFormat::Neo4j => {
+            fs::create_dir_all(output)
+                .with_context(|| format!("Failed to create output directory: {:?}", output))?;
+            CsvOutput::create(
+                &output.join(NEO4J_RELATIONSHIPS_FILE),
+                Some(":START_ID(Work),:END_ID(Work),provenance,same_prefix:boolean,:TYPE"),
+                shard,
+            )?
+        }
+        GraphFormat::Graphml => CsvOutput::create(&output.with_extension("edges.tmp"), None, None)?,
+    };
+    let mut row = Vec::new();
+
+    for input in inputs {
+        let reader = open_jsonl(input)?;
+        for (line_num, line_result) in reader.lines().enumerate() {
+            let line = line_result.with_context(|| format!("Failed to read: {:?}", input))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: CitationRecord = serde_json::from_str(&line).with_context(|| {
+                format!(
+                    "Failed to parse record at line {} of {:?}",
+                    line_num + 1,
+                    input
+                )
+            })?;
+            stats.records_read += 1;
+
+            works.insert(record.doi.clone(), Some(record.citation_count));
+            for entry in &record.cited_by {
+                let Some(citing) = entry.get("doi").and_then(Value::as_str) else {
+                    continue;
+                };
+                works.entry(citing.to_string()).or_insert(None);
+                let citation = Citation {
+                    citing,
+                    cited: &record.doi,
+                    provenance: entry
+                        .get("provenance")
+                        .and_then(Value::as_str)
+                        .unwrap_or("mined"),
+                    same_prefix: entry.get("same_prefix").and_then(Value::as_bool),
+                };
+                row.clear();
+                match format {
+                    GraphFormat::Neo4j => write_neo4j_relationship(&mut row, &citation)?,
+                    GraphFormat::Graphml => write_graphml_edge(&mut row, &citation)?,
+                }
+                edges.write_row(&row)?;
+                stats.citations += 1;
+            }
+        }
+    }
+    let edge_files = edges.finish()?;
+    stats.works = works.len();
+
+    match format {
+        GraphFormat::Neo4j => {
+            let node_files = write_neo4j_nodes(&output.join(NEO4J_NODES_FILE), &works, shard)?;
+            info!(
+                "Import with: neo4j-admin database import full --nodes={} --relationships={}",
+                file_list(&node_files),
+                file_list(&edge_files)
+            );
+        }
+        GraphFormat::Graphml => {
+            let edges_path = &edge_files[0];
+            let result = write_graphml(output, &works, edges_path);
+            let _ = fs::remove_file(edges_path);
+            result?;
+        }
+    }
+
+    info!(
+        "Exported {} works and {} citations from {} records",
+        stats.works, stats.citations, stats.records_read
+    );
+    Ok(stats)
+}
+
+/// Reader over a JSONL file, plain or compressed
+pub(super) fn open_jsonl(path: &Path) -> Result<Box<dyn BufRead>> {
+    Ok(Box::new(BufReader::new(open_compressed_reader(path)?)))
+}
+
+/// Quote a CSV field, doubling embedded quotes
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn write_neo4j_relationship(writer: &mut impl Write, citation: &Citation) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{},{},{},{},CITES",
+        csv_field(citation.citing),
+        csv_field(citation.cited),
+        csv_field(citation.provenance),
+        citation
+            .same_prefix
+            .map_or(String::new(), |b| b.to_string())
+    )
+}
+
+fn write_neo4j_nodes(
+    path: &Path,
+    works: &BTreeMap<String, Option<usize>>,
+    shard: Option<ShardLimit>,
+) -> Result<Vec<PathBuf>> {
+    let mut nodes =
+        CsvOutput::create(path, Some("doi:ID(Work),citation_count:long,:LABEL"), shard)?;
+    let mut row = Vec::new();
+    for (doi, citation_count) in works {
+        row.clear();
+        writeln!(
+            row,
+            "{},{},Work",
+            csv_field(doi),
+            citation_count.map_or(String::new(), |count| count.to_string())
+        )?;
+        nodes.write_row(&row)?;
+    }
+    nodes.finish()
+}
+
+/// Comma-separated paths, as `neo4j-admin` takes the files of one input
+fn file_list(files: &[PathBuf]) -> String {
+    files
+        .iter()
+        .map(|file| file.display().to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A CSV file, or with a [`ShardLimit`] a header file and numbered data files
+///
+/// Sharded, `cites.csv` becomes `cites_header.csv` holding only the header and
+/// `cites_00001.csv`, `cites_00002.csv`, ... holding the rows, which `neo4j-admin`
+/// imports as one input when given in that order. Byte limits count the CSV rows.
+/// Data files of an earlier export beyond the new last one are removed.
+struct CsvOutput {
+    base: PathBuf,
+    shard: Option<ShardLimit>,
+    writer: BufWriter<File>,
+    /// Files written so far, in import order
+    files: Vec<PathBuf>,
+    /// Data files written so far
+    shards: usize,
+    /// Rows and bytes in the current data file
+    rows: usize,
+    bytes: u64,
+}
+
+impl CsvOutput {
+    fn create(base: &Path, header: Option<&str>, shard: Option<ShardLimit>) -> Result<Self> {
+        let mut files = Vec::new();
+        if shard.is_some() {
+            if let Some(header) = header {
+                let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+                let header_path = base.with_file_name(format!("{}_header.csv", stem));
+                fs::write(&header_path, format!("{}\n", header))
+                    .with_context(|| format!("Failed to write: {:?}", header_path))?;
+                files.push(header_path);
+            }
+        }
+        let path = match shard {
+            Some(_) => shard_path(base, 1),
+            None => base.to_path_buf(),
+        };
+        let mut writer = Self::open(&path)?;
+        if let (None, Some(header)) = (shard, header) {
+            writeln!(writer, "{}", header)?;
+        }
+        files.push(path);
+        Ok(Self {
+            base: base.to_path_buf(),
+            shard,
+            writer,
+            files,
+            shards: 1,
+            rows: 0,
+            bytes: 0,
+        })
+    }
+
+    fn open(path: &Path) -> Result<BufWriter<File>> {
+        Ok(BufWriter::new(File::create(path).with_context(|| {
+            format!("Failed to create: {:?}", path)
+        })?))
+    }
+
+    fn write_row(&mut self, row: &[u8]) -> Result<()> {
+        let full = self.shard.is_some_and(|limit| match limit {
+            ShardLimit::Bytes(max) => self.bytes > 0 && self.bytes + row.len() as u64 > max,
+            ShardLimit::Records(max) => self.rows >= max,
+        });
+        if full {
+            self.flush()?;
+            self.shards += 1;
+            let path = shard_path(&self.base, self.shards);
+            self.writer = Self::open(&path)?;
+            self.files.push(path);
+            self.rows = 0;
+            self.bytes = 0;
+        }
+        self.writer.write_all(row)?;
+        self.rows += 1;
+        self.bytes += row.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let path = self.files.last().expect("a file is open");
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write: {:?}", path))
+    }
+
+    /// Flush the open file, returning every file written in import order
+    fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.flush()?;
+        if self.shard.is_some() {
+            let mut stale = self.shards + 1;
+            while shard_path(&self.base, stale).exists() {
+                let path = shard_path(&self.base, stale);
+                fs::remove_file(&path).with_context(|| format!("Failed to remove: {:?}", path))?;
+                stale += 1;
+            }
+        }
+        Ok(self.files)
+    }
+}
+
+/// Escape text for an XML attribute or element
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_graphml_edge(writer: &mut impl Write, citation: &Citation) -> io::Result<()> {
+    write!(
+        writer,
+        "    <edge source=\"{}\" target=\"{}\"><data key=\"provenance\">{}</data>",
+        xml_escape(citation.citing),
+        xml_escape(citation.cited),
+        xml_escape(citation.provenance)
+    )?;
+    if let Some(same_prefix) = citation.same_prefix {
+        write!(writer, "<data key=\"same_prefix\">{}</data>", same_prefix)?;
+    }
+    writeln!(writer, "</edge>")
+}
+
+/// Assemble the GraphML document: keys, nodes, then the streamed edges
+fn write_graphml(
+    path: &Path,
+    works: &BTreeMap<String, Option<usize>>,
+    edges_path: &Path,
+) -> Result<()> {
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create: {:?}", path))?,
+    );
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="citation_count" for="node" attr.name="citation_count" attr.type="long"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="provenance" for="edge" attr.name="provenance" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="same_prefix" for="edge" attr.name="same_prefix" attr.type="boolean"/>"#
+    )?;
+    writeln!(writer, r#"  <graph id="citations" edgedefault="directed">"#)?;
+
+    for (doi, citation_count) in works {
+        match citation_count {
+            Some(count) => writeln!(
+                writer,
+                "    <node id=\"{}\"><data key=\"citation_count\">{}</data></node>",
+                xml_escape(doi),
+                count
+            )?,
+            None => writeln!(writer, "    <node id=\"{}\"/>", xml_escape(doi))?,
+        }
+    }
+
+    let mut edges = File::open(edges_path)
+        .with_context(|| format!("Failed to reopen edges: {:?}", edges_path))?;
+    io::copy(&mut edges, &mut writer)?;
+
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")?;
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_outputs(dir: &Path) -> PathBuf {
+        let path = dir.join("valid.jsonl");
+        let lines = [
+            r#"{"doi": "10.1234/cited", "reference_count": 2, "citation_count": 2, "cited_by": [{"doi": "10.1234/a", "provenance": "publisher", "same_prefix": true}, {"doi": "10.5678/\"b\"", "provenance": "mined", "same_prefix": false}]}"#,
+            r#"{"doi": "10.1234/a", "reference_count": 1, "citation_count": 1, "cited_by": [{"doi": "10.9999/c&d", "provenance": "crossref"}]}"#,
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_export_neo4j() {
+        let dir = tempdir().unwrap();
+        let input = write_outputs(dir.path());
+        let output = dir.path().join("graph");
+
+        let stats = export_graph(&[input], GraphFormat::Neo4j, &output, None).unwrap();
+        assert_eq!(
+            stats,
+            GraphExportStats {
+                records_read: 2,
+                works: 4,
+                citations: 3,
+            }
+        );
+
+        let nodes = fs::read_to_string(output.join(NEO4J_NODES_FILE)).unwrap();
+        let nodes: Vec<&str> = nodes.lines().collect();
+        assert_eq!(nodes[0], "doi:ID(Work),citation_count:long,:LABEL");
+        assert_eq!(nodes[1], r#""10.1234/a",1,Work"#);
+        assert!(nodes.contains(&r#""10.5678/""b""",,Work"#));
+
+        let rels = fs::read_to_string(output.join(NEO4J_RELATIONSHIPS_FILE)).unwrap();
+        let rels: Vec<&str> = rels.lines().collect();
+        assert_eq!(rels.len(), 4);
+        assert_eq!(
+            rels[1],
+            r#""10.1234/a","10.1234/cited","publisher",true,CITES"#
+        );
+        assert_eq!(rels[3], r#""10.9999/c&d","10.1234/a","crossref",,CITES"#);
+    }
+
+    #[test]
+    fn test_export_neo4j_sharded() {
+        let dir = tempdir().unwrap();
+        let input = write_outputs(dir.path());
+        let output = dir.path().join("graph");
+        fs::create_dir(&output).unwrap();
+        // Left by an earlier export with more shards
+        fs::write(output.join("cites_00009.csv"), "").unwrap();
+        fs::write(output.join("cites_00003.csv"), "").unwrap();
+
+        let shard = Some(ShardLimit::Records(2));
+        let stats = export_graph(&[input], GraphFormat::Neo4j, &output, shard).unwrap();
+        assert_eq!(stats.citations, 3);
+
+        assert!(!output.join(NEO4J_RELATIONSHIPS_FILE).exists());
+        let header = fs::read_to_string(output.join("cites_header.csv")).unwrap();
+        assert!(header.starts_with(":START_ID(Work)"));
+        let first = fs::read_to_string(output.join("cites_00001.csv")).unwrap();
+        assert_eq!(first.lines().count(), 2);
+        assert!(first.starts_with(r#""10.1234/a","10.1234/cited""#));
+        let second = fs::read_to_string(output.join("cites_00002.csv")).unwrap();
+        assert_eq!(second.lines().count(), 1);
+        assert!(!output.join("cites_00003.csv").exists());
+
+        // Four works in two node files
+        assert!(output.join("works_header.csv").exists());
+        assert!(output.join("works_00002.csv").exists());
+        assert!(!output.join("works_00003.csv").exists());
+
+        let graphml = dir.path().join("graph.graphml");
+        let input = dir.path().join("valid.jsonl");
+        assert!(export_graph(&[input], GraphFormat::Graphml, &graphml, shard).is_err());
+    }
+
+    #[test]
+    fn test_export_graphml() {
+        let dir = tempdir().unwrap();
+        let input = write_outputs(dir.path());
+        let output = dir.path().join("graph.graphml");
+
+        let stats = export_graph(&[input], GraphFormat::Graphml, &output, None).unwrap();
+        assert_eq!(stats.citations, 3);
+        assert!(!output.with_extension("edges.tmp").exists());
+
+        let xml = fs::read_to_string(&output).unwrap();
+        assert!(
+            xml.contains(r#"<node id="10.1234/cited"><data key="citation_count">2</data></node>"#)
+        );
+        assert!(xml.contains(r#"<node id="10.9999/c&amp;d"/>"#));
+        assert!(xml.contains(
+            r#"<edge source="10.5678/&quot;b&quot;" target="10.1234/cited"><data key="provenance">mined</data><data key="same_prefix">false</data></edge>"#
+        ));
+        // Nodes precede edges
+        assert!(xml.find("<node").unwrap() < xml.find("<edge").unwrap());
+        assert!(xml.trim_end().ends_with("</graphml>"));
+    }
+
+    #[test]
+    fn test_graph_format_parse() {
+        assert_eq!("Neo4j".parse::<GraphFormat>(), Ok(GraphFormat::Neo4j));
+        assert_eq!("graphml".parse::<GraphFormat>(), Ok(GraphFormat::Graphml));
+        assert!("gexf".parse::<GraphFormat>().is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use crate::error::{Context, Result};
+use bzip2::read::MultiBzDecoder;
+use crossbeam_channel::{bounded, Receiver};
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+
+/// Decompressed bytes handed to the consumer per channel message
+pub const READAHEAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// Decompressed chunks the background thread may run ahead of the consumer
+pub const READAHEAD_DEPTH: usize = 8;
+/// Read buffer for the compressed input file
+const COMPRESSED_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Leading bytes of a gzip member
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// Leading bytes of a bzip2 stream ("BZh")
+const BZIP2_MAGIC: &[u8] = b"BZh";
+/// Leading bytes of a zstd frame
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Whether leading bytes start a gzip, bzip2 or zstd stream that
+/// [`open_compressed_reader`] decompresses
+pub fn is_compressed(head: &[u8]) -> bool {
+    [GZIP_MAGIC, BZIP2_MAGIC, ZSTD_MAGIC]
+        .iter()
+        .any(|magic| head.starts_with(magic))
+}
+
+/// Reader that drains an inner reader on a background thread
+///
+/// Wrapping a gzip decoder moves decompression off the consuming thread, so tar/JSON
+/// parsing and inflate run concurrently instead of taking turns.
+pub struct ReadaheadReader {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    handle: Option<JoinHandle<()>>,
+    current: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl ReadaheadReader {
+    /// Spawn the background reader, buffering up to `depth` chunks of `chunk_size` bytes
+    pub fn new<R: Read + Send + 'static>(mut inner: R, chunk_size: usize, depth: usize) -> Self {
+        let (sender, receiver) = bounded(depth.max(1));
+        let chunk_size = chunk_size.max(1);
+
+        let handle = thread::spawn(move || loop {
+            match fill_chunk(&mut inner, chunk_size) {
+                Ok(chunk) if chunk.is_empty() => break,
+                Ok(chunk) => {
+                    // Consumer dropped the reader; stop decompressing
+                    if sender.send(Ok(chunk)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    break;
+                }
+            }
+        });
+
+        Self {
+            receiver,
+            handle: Some(handle),
+            current: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+/// Read until `chunk_size` bytes are collected or the inner reader hits EOF
+fn fill_chunk<R: Read>(reader: &mut R, chunk_size: usize) -> io::Result<Vec<u8>> {
+    let mut chunk = vec![0u8; chunk_size];
+    let mut filled = 0;
+    while filled < chunk_size {
+        match reader.read(&mut chunk[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    chunk.truncate(filled);
+    Ok(chunk)
+}
+
+impl Read for ReadaheadReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.pos >= self.current.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.receiver.recv() {
+                Ok(Ok(chunk)) => {
+                    self.current = chunk;
+                    self.pos = 0;
+                }
+                Ok(Err(e)) => {
+                    self.done = true;
+                    return Err(e);
+                }
+                Err(_) => {
+                    // Channel closed: either clean EOF or the reader thread panicked
+                    self.done = true;
+                    if let Some(handle) = self.handle.take() {
+                        if handle.join().is_err() {
+                            return Err(io::Error::other("readahead thread panicked"));
+                        }
+                    }
+                    return Ok(0);
+                }
+            }
+        }
+
+        let n = buf.len().min(self.current.len() - self.pos);
+        buf[..n].copy_from_slice(&self.current[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Open a gzip file (single or multi-member, e.g. pigz output) for streaming reads
+///
+/// With the `parallel-gzip` feature, decompression runs on a dedicated readahead thread.
+#[cfg(feature = "parquet")]
+pub fn open_gzip_reader(path: &Path) -> Result<Box<dyn Read>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open input file: {:?}", path))?;
+    let decoder = MultiGzDecoder::new(BufReader::with_capacity(COMPRESSED_BUFFER_SIZE, file));
+    Ok(with_readahead(decoder))
+}
+
+/// Open a file for streaming reads, decompressing gzip, bzip2 or zstd
+///
+/// The format is detected from the file's leading bytes rather than its extension, so
+/// `.jsonl.bz2` and `.jsonl.zst` mirrors of a dump read like the `.jsonl.gz` original,
+/// and anything else is read as it is. Concatenated members, streams and frames are all
+/// read. Decompression runs on a readahead thread as for [`open_gzip_reader`].
+pub fn open_compressed_reader(path: &Path) -> Result<Box<dyn Read>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open input file: {:?}", path))?;
+    let mut input = BufReader::with_capacity(COMPRESSED_BUFFER_SIZE, file);
+    let head = input
+        .fill_buf()
+        .with_context(|| format!("Failed to read input file: {:?}", path))?;
+
+    Ok(if head.starts_with(GZIP_MAGIC) {
+        with_readahead(MultiGzDecoder::new(input))
+    } else if head.starts_with(BZIP2_MAGIC) {
+        with_readahead(MultiBzDecoder::new(input))
+    } else if head.starts_with(ZSTD_MAGIC) {
+        let decoder = zstd::stream::read::Decoder::with_buffer(input)
+            .with_context(|| format!("Failed to open zstd input: {:?}", path))?;
+        with_readahead(decoder)
+    } else {
+        Box::new(input)
+    })
+}
+
+/// A decoder, on a readahead thread with the `parallel-gzip` feature
+fn with_readahead<R: Read + Send + 'static>(decoder: R) -> Box<dyn Read> {
+    if cfg!(feature = "parallel-gzip") {
+        Box::new(ReadaheadReader::new(
+            decoder,
+            READAHEAD_CHUNK_SIZE,
+            READAHEAD_DEPTH,
+        ))
+    } else {
+        Box::new(decoder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    struct FailingReader {
+        remaining: usize,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.remaining == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt"));
+            }
+            let n = buf.len().min(self.remaining);
+            buf[..n].fill(b'x');
+            self.remaining -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_readahead_roundtrip() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let mut reader = ReadaheadReader::new(io::Cursor::new(data.clone()), 4096, 2);
+
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_readahead_propagates_errors() {
+        let mut reader = ReadaheadReader::new(FailingReader { remaining: 10_000 }, 4096, 2);
+
+        let mut out = Vec::new();
+        let err = reader.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_readahead_early_drop() {
+        let data = vec![7u8; 1_000_000];
+        let mut reader = ReadaheadReader::new(io::Cursor::new(data), 1024, 1);
+
+        let mut buf = [0u8; 10];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [7u8; 10]);
+        drop(reader);
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_open_gzip_reader_multi_member() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("multi.gz");
+
+        // Two concatenated gzip members, as produced by pigz or `cat a.gz b.gz`
+        let mut file = File::create(&path).unwrap();
+        for part in [b"first member\n".as_slice(), b"second member\n".as_slice()] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(part).unwrap();
+            file.write_all(&encoder.finish().unwrap()).unwrap();
+        }
+        drop(file);
+
+        let mut out = String::new();
+        open_gzip_reader(&path)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "first member\nsecond member\n");
+    }
+
+    #[test]
+    fn test_open_compressed_reader_detects_format() {
+        let dir = tempdir().unwrap();
+        let data = b"{\"id\": \"10.1234/a\"}\n{\"id\": \"10.1234/b\"}\n";
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(data).unwrap();
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(data).unwrap();
+        let encoded = [
+            ("dump.jsonl.gz", gzip.finish().unwrap()),
+            ("dump.jsonl.bz2", bzip2.finish().unwrap()),
+            ("dump.jsonl.zst", zstd::encode_all(&data[..], 0).unwrap()),
+            // The extension is not what decides
+            ("dump.jsonl", data.to_vec()),
+            ("misnamed.jsonl.gz", data.to_vec()),
+            ("empty.jsonl", Vec::new()),
+        ];
+        for (name, bytes) in encoded {
+            let path = dir.path().join(name);
+            std::fs::write(&path, &bytes).unwrap();
+            let mut out = Vec::new();
+            open_compressed_reader(&path)
+                .unwrap()
+                .read_to_end(&mut out)
+                .unwrap();
+            let expected: &[u8] = if bytes.is_empty() { b"" } else { data };
+            assert_eq!(out, expected, "{}", name);
+        }
+    }
+}
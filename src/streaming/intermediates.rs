@@ -0,0 +1,224 @@
+use crate::error::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use super::inversion::{INVERTED_FILE, PARTITION_EXTENSION};
+
+/// Manifest of the intermediate files in a kept partition directory
+pub const INTERMEDIATES_FILE: &str = "intermediates.json";
+
+/// Layout version written to new intermediates manifests
+pub const INTERMEDIATES_VERSION: u32 = 1;
+
+/// Intermediate files a run left in its partition directory
+///
+/// Every path is relative to the directory holding the manifest, so the directory can
+/// be moved or copied to another machine and still be loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntermediatesManifest {
+    pub version: u32,
+    /// Run ID of the checkpoint
+    pub run_id: String,
+    pub checkpoint: PathBuf,
+    /// One entry per source target, in the order they were processed
+    pub targets: Vec<IntermediateTarget>,
+    /// Other files in the directory (HTTP checkpoint, is-referenced-by counts)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sidecars: Vec<PathBuf>,
+}
+
+/// Partition files and inverted output of one source target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntermediateTarget {
+    /// Source the target was extracted for (all, crossref, datacite or arxiv)
+    pub source: String,
+    /// Directory of the target's partitions; empty when it is the manifest's directory
+    pub partition_dir: PathBuf,
+    /// Partition file names, sorted
+    pub partitions: Vec<PathBuf>,
+    /// Inverted output, when inversion completed
+    pub inverted: Option<PathBuf>,
+}
+
+impl IntermediatesManifest {
+    /// Describe the intermediate files in `dir`
+    ///
+    /// `targets` pairs each source name with its partition directory. `checkpoint` and
+    /// the `sidecars` that exist must be inside `dir`; a sidecar with the partition
+    /// extension is not listed as a partition.
+    pub fn collect(
+        dir: &Path,
+        run_id: &str,
+        checkpoint: &Path,
+        targets: &[(String, PathBuf)],
+        sidecars: &[PathBuf],
+    ) -> Result<Self> {
+        let relative = |path: &Path| -> Result<PathBuf> {
+            path.strip_prefix(dir)
+                .map(Path::to_path_buf)
+                .map_err(|_| Error::invalid(format!("{:?} is not inside {:?}", path, dir)))
+        };
+
+        let mut manifest_targets = Vec::with_capacity(targets.len());
+        for (source, partition_dir) in targets {
+            let mut partitions: Vec<PathBuf> = fs::read_dir(partition_dir)
+                .with_context(|| format!("Failed to read: {:?}", partition_dir))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| PathBuf::from(entry.file_name()))
+                .filter(|name| {
+                    name.extension()
+                        .is_some_and(|ext| ext == PARTITION_EXTENSION)
+                        && name != Path::new(INVERTED_FILE)
+                        && !sidecars.contains(&partition_dir.join(name))
+                })
+                .collect();
+            partitions.sort();
+            let inverted = partition_dir.join(INVERTED_FILE);
+            manifest_targets.push(IntermediateTarget {
+                source: source.clone(),
+                partition_dir: relative(partition_dir)?,
+                partitions,
+                inverted: inverted.exists().then(|| PathBuf::from(INVERTED_FILE)),
+            });
+        }
+
+        Ok(Self {
+            version: INTERMEDIATES_VERSION,
+            run_id: run_id.to_string(),
+            checkpoint: relative(checkpoint)?,
+            targets: manifest_targets,
+            sidecars: sidecars
+                .iter()
+                .filter(|path| path.exists())
+                .map(|path| relative(path))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Write the manifest into `dir`
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(INTERMEDIATES_FILE);
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create intermediates manifest: {:?}", path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Load the manifest of `dir`, checking that every file it lists is present
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(INTERMEDIATES_FILE);
+        let file = File::open(&path).with_context(|| {
+            format!(
+                "No intermediates manifest in {:?}; was the run started with \
+                 --keep-intermediates or --temp-dir?",
+                dir
+            )
+        })?;
+        let manifest: Self = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("Failed to parse intermediates manifest: {:?}", path))?;
+        if manifest.version > INTERMEDIATES_VERSION {
+            return Err(Error::invalid(format!(
+                "Intermediates manifest {:?} has version {}, newer than this build supports ({})",
+                path, manifest.version, INTERMEDIATES_VERSION
+            )));
+        }
+
+        let missing: Vec<PathBuf> = manifest
+            .files()
+            .map(|file| dir.join(file))
+            .filter(|path| !path.exists())
+            .collect();
+        if let Some(first) = missing.first() {
+            return Err(Error::invalid(format!(
+                "{} files listed in {:?} are missing, e.g. {:?}",
+                missing.len(),
+                path,
+                first
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// The target extracted for `source`
+    pub fn target(&self, source: &str) -> Option<&IntermediateTarget> {
+        self.targets.iter().find(|target| target.source == source)
+    }
+
+    /// Every listed file, relative to the manifest's directory
+    fn files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        let target_files = self.targets.iter().flat_map(|target| {
+            target
+                .partitions
+                .iter()
+                .chain(&target.inverted)
+                .map(|file| target.partition_dir.join(file))
+        });
+        std::iter::once(self.checkpoint.clone())
+            .chain(target_files)
+            .chain(self.sidecars.iter().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_intermediates_manifest_roundtrip() {
+        let dir = tempdir().unwrap();
+        let crossref = dir.path().join("crossref");
+        let arxiv = dir.path().join("arxiv");
+        for (target, partition) in [(&crossref, "10.1234"), (&arxiv, "2403")] {
+            fs::create_dir(target).unwrap();
+            fs::write(
+                target.join(format!("{}.{}", partition, PARTITION_EXTENSION)),
+                b"",
+            )
+            .unwrap();
+        }
+        fs::write(crossref.join(INVERTED_FILE), b"").unwrap();
+        fs::write(crossref.join("inverted.jsonl"), b"").unwrap();
+        let checkpoint = dir.path().join("checkpoint.json");
+        fs::write(&checkpoint, b"{}").unwrap();
+
+        let manifest = IntermediatesManifest::collect(
+            dir.path(),
+            "pipeline-1",
+            &checkpoint,
+            &[
+                ("crossref".to_string(), crossref.clone()),
+                ("arxiv".to_string(), arxiv.clone()),
+            ],
+            &[dir.path().join("http_checkpoint.jsonl")],
+        )
+        .unwrap();
+        let target = manifest.target("crossref").unwrap();
+        assert_eq!(target.partition_dir, PathBuf::from("crossref"));
+        assert_eq!(
+            target.partitions,
+            [PathBuf::from(format!("10.1234.{}", PARTITION_EXTENSION))]
+        );
+        assert_eq!(target.inverted, Some(PathBuf::from(INVERTED_FILE)));
+        assert_eq!(manifest.target("arxiv").unwrap().inverted, None);
+        assert!(manifest.target("datacite").is_none());
+        // Absent sidecars are left out
+        assert!(manifest.sidecars.is_empty());
+
+        manifest.write(dir.path()).unwrap();
+        assert_eq!(IntermediatesManifest::load(dir.path()).unwrap(), manifest);
+
+        fs::remove_file(crossref.join(INVERTED_FILE)).unwrap();
+        let error = IntermediatesManifest::load(dir.path()).unwrap_err();
+        assert!(error.to_string().contains("missing"));
+    }
+
+    #[test]
+    fn test_load_without_manifest() {
+        let dir = tempdir().unwrap();
+        let error = IntermediatesManifest::load(dir.path()).unwrap_err();
+        assert!(error.to_string().contains("--keep-intermediates"));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::common::ProvenanceCounts;
+use crate::error::{Context, Result};
+use crate::extract::{IdType, Provenance};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::ExplodedRow;
+
+/// Field of a match object repeating the reference's `key`, the citing work's own
+/// label for it (the JATS/deposit `key` attribute)
+pub const REFERENCE_KEY_FIELD: &str = "reference_key";
+
+/// Inverted records within a target's partition directory, kept for retries
+#[cfg(feature = "parquet")]
+pub const INVERTED_FILE: &str = "inverted.parquet";
+/// Inverted records within a target's partition directory, kept for retries
+#[cfg(not(feature = "parquet"))]
+pub const INVERTED_FILE: &str = "inverted.jsonl";
+
+/// Extension of the partition files extraction writes
+#[cfg(feature = "parquet")]
+pub const PARTITION_EXTENSION: &str = "parquet";
+/// Extension of the partition files extraction writes, distinct from the `.jsonl`
+/// outputs and sidecars that can share the partition directory
+#[cfg(not(feature = "parquet"))]
+pub const PARTITION_EXTENSION: &str = "ndjson";
+
+/// Output mode for inverted data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// arXiv-specific output with arxiv_doi and arxiv_id fields
+    #[default]
+    Arxiv,
+    /// Generic DOI output with just doi field
+    Generic,
+}
+
+/// Statistics from inverting partitions
+#[derive(Debug, Clone, Default)]
+pub struct InvertStats {
+    pub partitions_processed: usize,
+    /// Partitions that failed to invert and were recorded in the checkpoint
+    pub partitions_failed: usize,
+    pub unique_cited_works: usize,
+    pub total_citations: usize,
+    /// Citations by provenance; they add up to `total_citations`
+    pub provenance_counts: ProvenanceCounts,
+}
+
+/// Provenance counted by each per-record citation count column
+pub(super) const PROVENANCE_COUNT_COLUMNS: [(&str, &str); 4] = [
+    ("publisher", "publisher_citation_count"),
+    ("crossref", "crossref_citation_count"),
+    ("structured", "structured_citation_count"),
+    ("mined", "mined_citation_count"),
+];
+
+/// Per-record count of asserted citations (publisher and crossref together)
+pub(super) const ASSERTED_COUNT_COLUMN: &str = "asserted_citation_count";
+
+/// Move an inverted output aside before retried partitions are merged into it
+///
+/// Returns the earlier output to merge from: the output just moved, or one left
+/// aside by a retry that stopped before replacing it. Pass the merge's result to
+/// [`restore_set_aside`].
+pub(super) fn set_aside_output(output: &Path) -> Result<Option<PathBuf>> {
+    let mut previous = output.as_os_str().to_owned();
+    previous.push(".previous");
+    let previous = PathBuf::from(previous);
+    if output.exists() {
+        fs::rename(output, &previous)
+            .with_context(|| format!("Failed to move aside inverted output: {:?}", output))?;
+    }
+    Ok(previous.exists().then_some(previous))
+}
+
+/// Finish a merge from an output moved aside by [`set_aside_output`]: remove it once
+/// the merged output is written, or put it back when the merge failed
+pub(super) fn restore_set_aside<T>(
+    previous: Option<&Path>,
+    output: &Path,
+    merged: Result<T>,
+) -> Result<T> {
+    let Some(previous) = previous else {
+        return merged;
+    };
+    match merged {
+        Ok(_) => fs::remove_file(previous)
+            .with_context(|| format!("Failed to remove earlier inverted output: {:?}", previous))?,
+        Err(_) => fs::rename(previous, output)
+            .with_context(|| format!("Failed to restore inverted output: {:?}", output))?,
+    }
+    merged
+}
+
+/// Add a reference to a cited_by match object, with its key as a top-level field
+pub(super) fn add_reference(match_obj: &mut Value, reference: Value) {
+    if let Some(key) = reference.get("key").filter(|key| key.is_string()) {
+        match_obj[REFERENCE_KEY_FIELD] = key.clone();
+    }
+    match_obj["reference"] = reference;
+}
+
+/// A partition row as JSONL partition files hold it, keyed like the columns of a
+/// Parquet partition
+#[derive(Debug, Deserialize)]
+pub(super) struct PartitionRow {
+    citing_doi: String,
+    citing_type: Option<String>,
+    citing_container_title: Option<String>,
+    citing_licenses: Option<Vec<String>>,
+    citing_funders: Option<Vec<String>>,
+    ref_json: String,
+    raw_match: String,
+    cited_id: String,
+    /// Missing from JSONL partitions written before the column existed
+    #[serde(default)]
+    id_type: Option<String>,
+    provenance: String,
+    match_field: Option<String>,
+    match_start: Option<u32>,
+    match_end: Option<u32>,
+    context: Option<String>,
+    same_prefix: bool,
+}
+
+impl From<ExplodedRow> for PartitionRow {
+    fn from(row: ExplodedRow) -> Self {
+        Self {
+            citing_doi: row.citing_doi.to_string(),
+            citing_type: row.citing_type.map(|t| t.to_string()),
+            citing_container_title: row.citing_container_title.map(|t| t.to_string()),
+            citing_licenses: row.citing_licenses.map(|l| l.to_vec()),
+            citing_funders: row.citing_funders.map(|f| f.to_vec()),
+            ref_json: row.ref_json.to_string(),
+            raw_match: row.raw_match,
+            cited_id: row.cited_id,
+            id_type: Some(row.id_type.as_str().to_string()),
+            provenance: row.provenance.as_str().to_string(),
+            match_field: row.match_field.map(|field| field.as_str().to_string()),
+            match_start: row.match_span.map(|span| span.start),
+            match_end: row.match_span.map(|span| span.end),
+            context: row.context,
+            same_prefix: row.same_prefix,
+        }
+    }
+}
+
+/// Partition rows grouped by cited ID, in first-cited order
+///
+/// Keeps the first row of each (citing_doi, cited_id) pair and drops self-citations,
+/// as Parquet inversion does, so every cited_by entry holds one match.
+#[derive(Default)]
+pub(super) struct RowGroups {
+    seen: HashSet<(String, String)>,
+    group_of: HashMap<String, usize>,
+    groups: Vec<(String, Vec<PartitionRow>)>,
+}
+
+impl RowGroups {
+    pub(super) fn push(&mut self, row: PartitionRow) {
+        if row.citing_doi == row.cited_id
+            || !self
+                .seen
+                .insert((row.citing_doi.clone(), row.cited_id.clone()))
+        {
+            return;
+        }
+        let groups = &mut self.groups;
+        let group = *self
+            .group_of
+            .entry(row.cited_id.clone())
+            .or_insert_with(|| {
+                groups.push((row.cited_id.clone(), Vec::new()));
+                groups.len() - 1
+            });
+        self.groups[group].1.push(row);
+    }
+
+    /// Output record of each cited work
+    pub(super) fn into_records(self, output_mode: OutputMode) -> Vec<Value> {
+        self.groups
+            .into_iter()
+            .map(|(cited_id, rows)| inverted_record(&cited_id, rows, output_mode))
+            .collect()
+    }
+}
+
+/// Invert extracted rows held in memory, most cited first as in the inverted outputs
+///
+/// Produces the records Parquet or JSONL partitions of the same rows invert to,
+/// without writing a partition.
+pub fn invert_exploded_rows(
+    rows: impl IntoIterator<Item = ExplodedRow>,
+    output_mode: OutputMode,
+) -> Vec<Value> {
+    let mut groups = RowGroups::default();
+    for row in rows {
+        groups.push(row.into());
+    }
+    let mut records = groups.into_records(output_mode);
+    records.sort_by_key(|record| std::cmp::Reverse(record["citation_count"].as_u64()));
+    records
+}
+
+/// Output record of a cited work from its deduplicated citing rows
+fn inverted_record(cited_id: &str, rows: Vec<PartitionRow>, output_mode: OutputMode) -> Value {
+    let mut record = match output_mode {
+        OutputMode::Arxiv => json!({
+            "arxiv_doi": format!("10.48550/arXiv.{}", cited_id),
+            "arxiv_id": cited_id,
+        }),
+        OutputMode::Generic => json!({ "doi": cited_id }),
+    };
+    record["id_type"] = match rows.first().and_then(|row| row.id_type.as_deref()) {
+        Some(id_type) => Value::from(id_type),
+        None => Value::from(IdType::of(cited_id).as_str()),
+    };
+    record["reference_count"] = Value::from(rows.len());
+    record["citation_count"] = Value::from(rows.len());
+    for (provenance, name) in PROVENANCE_COUNT_COLUMNS {
+        let count = rows
+            .iter()
+            .filter(|row| row.provenance == provenance)
+            .count();
+        record[name] = Value::from(count);
+    }
+    let asserted = rows
+        .iter()
+        .filter(|row| Provenance::is_asserted_str(&row.provenance))
+        .count();
+    record[ASSERTED_COUNT_COLUMN] = Value::from(asserted);
+    record["cited_by"] = rows.into_iter().map(citing_entry).collect();
+    record
+}
+
+/// cited_by entry of a citing work, with its single match
+fn citing_entry(row: PartitionRow) -> Value {
+    let reference: Value = serde_json::from_str(&row.ref_json).unwrap_or(Value::Null);
+    let mut match_obj = json!({
+        "raw_match": row.raw_match,
+        "provenance": row.provenance,
+    });
+    add_reference(&mut match_obj, reference);
+    if let Some(match_field) = row.match_field {
+        match_obj["match_field"] = Value::String(match_field);
+    }
+    if let (Some(start), Some(end)) = (row.match_start, row.match_end) {
+        match_obj["match_start"] = Value::from(start);
+        match_obj["match_end"] = Value::from(end);
+    }
+    if let Some(context) = row.context {
+        match_obj["context"] = Value::String(context);
+    }
+
+    let mut citing = json!({
+        "doi": row.citing_doi,
+        "provenance": row.provenance,
+        "same_prefix": row.same_prefix,
+    });
+    if let Some(work_type) = row.citing_type {
+        citing["type"] = Value::String(work_type);
+    }
+    if let Some(container_title) = row.citing_container_title {
+        citing["container_title"] = Value::String(container_title);
+    }
+    if let Some(licenses) = row.citing_licenses {
+        citing["licenses"] = json!(licenses);
+    }
+    if let Some(funders) = row.citing_funders {
+        citing["funders"] = json!(funders);
+    }
+    citing["matches"] = json!([match_obj]);
+    citing
+}
@@ -0,0 +1,496 @@
+use crate::error::{Context, Error, Result};
+use log::{debug, info, warn};
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::inversion::{
+    restore_set_aside, set_aside_output, InvertStats, OutputMode, PartitionRow, RowGroups,
+    PARTITION_EXTENSION,
+};
+use super::Checkpoint;
+use crate::common::{CancellationToken, ProvenanceCounts};
+
+/// Invert a single JSONL partition file into output records, in first-cited order
+fn invert_single_partition(partition_path: &Path, output_mode: OutputMode) -> Result<Vec<Value>> {
+    debug!("Inverting partition: {:?}", partition_path);
+
+    let file = File::open(partition_path)
+        .with_context(|| format!("Failed to open partition: {:?}", partition_path))?;
+    let mut groups = RowGroups::default();
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read: {:?}", partition_path))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row: PartitionRow = serde_json::from_str(&line).with_context(|| {
+            format!(
+                "Invalid partition row at {:?}:{}",
+                partition_path,
+                line_number + 1
+            )
+        })?;
+        groups.push(row);
+    }
+    Ok(groups.into_records(output_mode))
+}
+
+/// Invert all JSONL partition files in parallel
+///
+/// The build without Parquet support inverts in memory, a partition at a time, and
+/// writes the records only as JSONL: to `output`, kept for retries, and to
+/// `output_jsonl` when given. Failures are recorded in the checkpoint as with Parquet
+/// partitions.
+pub fn invert_partitions(
+    partition_dir: &Path,
+    output: &Path,
+    output_jsonl: Option<&Path>,
+    checkpoint: &mut Checkpoint,
+    output_mode: OutputMode,
+    cancel: &CancellationToken,
+) -> Result<InvertStats> {
+    let partition_files: Vec<_> = fs::read_dir(partition_dir)
+        .with_context(|| format!("Failed to read partition directory: {:?}", partition_dir))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == PARTITION_EXTENSION)
+        })
+        .filter(|path| {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            !checkpoint.is_partition_inverted(name)
+        })
+        .collect();
+
+    info!("Inverting {} partitions in parallel", partition_files.len());
+
+    let (inverted, partitions_processed) =
+        invert_partition_files(&partition_files, checkpoint, output_mode, cancel)?;
+    let partitions_failed = partition_files.len() - partitions_processed;
+    if partitions_processed == 0 && partitions_failed > 0 {
+        return Err(Error::internal(format!(
+            "All {} partitions failed to invert",
+            partitions_failed
+        )));
+    }
+
+    if partitions_processed == 0 {
+        info!("No partitions to invert (all already processed or none found)");
+        return Ok(InvertStats::default());
+    }
+
+    let stats = write_inverted(None, inverted, output, output_jsonl)?;
+
+    Ok(InvertStats {
+        partitions_processed,
+        partitions_failed,
+        ..stats
+    })
+}
+
+/// Re-invert the partitions in a directory that failed in an earlier run
+///
+/// Partitions hold disjoint cited IDs, so the retried records are merged into the
+/// existing `output` as it is read a line at a time, and both outputs are rewritten.
+pub fn retry_failed_partitions(
+    partition_dir: &Path,
+    output: &Path,
+    output_jsonl: Option<&Path>,
+    checkpoint: &mut Checkpoint,
+    output_mode: OutputMode,
+    cancel: &CancellationToken,
+) -> Result<InvertStats> {
+    let partition_files = checkpoint.failed_partitions_in(partition_dir);
+    info!("Retrying {} failed partitions", partition_files.len());
+
+    let (retried, partitions_processed) =
+        invert_partition_files(&partition_files, checkpoint, output_mode, cancel)?;
+    let partitions_failed = partition_files.len() - partitions_processed;
+
+    let previous = set_aside_output(output)?;
+    if previous.is_none() && partitions_processed == 0 {
+        return Err(Error::internal(format!(
+            "All {} retried partitions failed to invert",
+            partitions_failed
+        )));
+    }
+
+    let stats = restore_set_aside(
+        previous.as_deref(),
+        output,
+        write_inverted(previous.as_deref(), retried, output, output_jsonl),
+    )?;
+
+    Ok(InvertStats {
+        partitions_processed,
+        partitions_failed,
+        ..stats
+    })
+}
+
+/// Write the JSONL copy of an existing inverted output
+///
+/// Lets a kept partition directory be validated again without re-inverting it. The
+/// inverted output is only read, a line at a time, and copied to `output_jsonl`
+/// unless that is the output itself.
+pub fn export_inverted_jsonl(output: &Path, output_jsonl: &Path) -> Result<InvertStats> {
+    let file = File::open(output)
+        .with_context(|| format!("Failed to open inverted output: {:?}", output))?;
+    let mut writer = None;
+    if output_jsonl != output {
+        info!("Writing JSONL output: {:?}", output_jsonl);
+        let file = File::create(output_jsonl)
+            .with_context(|| format!("Failed to create JSONL output: {:?}", output_jsonl))?;
+        writer = Some(BufWriter::new(file));
+    }
+
+    let mut stats = InvertStats::default();
+    for line in BufReader::new(file).lines() {
+        let line = line.with_context(|| format!("Failed to read inverted output: {:?}", output))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        add_to_totals(&mut stats, &serde_json::from_str(&line)?);
+        if let Some(writer) = writer.as_mut() {
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    if let Some(mut writer) = writer {
+        writer
+            .flush()
+            .with_context(|| format!("Failed to write JSONL output: {:?}", output_jsonl))?;
+    }
+    Ok(stats)
+}
+
+/// Invert partition files in parallel, returning the records of the partitions that
+/// succeeded and their number
+///
+/// Each outcome is recorded in the checkpoint: successes as inverted (clearing any
+/// earlier failure), failures with their error.
+fn invert_partition_files(
+    partition_files: &[PathBuf],
+    checkpoint: &mut Checkpoint,
+    output_mode: OutputMode,
+    cancel: &CancellationToken,
+) -> Result<(Vec<Value>, usize)> {
+    const BATCH_SIZE: usize = 500;
+    let mut records = Vec::new();
+    let mut succeeded = 0;
+
+    for batch in partition_files.chunks(BATCH_SIZE) {
+        // Partitions inverted so far are recorded in the checkpoint
+        cancel.check()?;
+
+        let results: Vec<(&PathBuf, Result<Vec<Value>>)> = batch
+            .par_iter()
+            .map(|path| (path, invert_single_partition(path, output_mode)))
+            .collect();
+
+        for (path, result) in results {
+            match result {
+                Ok(inverted) => {
+                    let name = path
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("unknown");
+                    checkpoint.mark_partition_inverted(name);
+                    checkpoint.clear_partition_failed(path);
+                    records.extend(inverted);
+                    succeeded += 1;
+                }
+                Err(e) => {
+                    warn!("Failed to invert partition {:?}: {:#}", path, e);
+                    checkpoint.mark_partition_failed(path, format!("{:#}", e));
+                }
+            }
+        }
+    }
+
+    Ok((records, succeeded))
+}
+
+/// Count an inverted record in the cited works and citation totals
+fn add_to_totals(stats: &mut InvertStats, record: &Value) {
+    stats.unique_cited_works += 1;
+    stats.total_citations += record["citation_count"].as_u64().unwrap_or(0) as usize;
+    let counts = ProvenanceCounts::deserialize(record).unwrap_or_default();
+    let totals = &mut stats.provenance_counts;
+    totals.publisher_citation_count += counts.publisher_citation_count;
+    totals.crossref_citation_count += counts.crossref_citation_count;
+    totals.structured_citation_count += counts.structured_citation_count;
+    totals.mined_citation_count += counts.mined_citation_count;
+}
+
+/// Sort inverted records by citation count and write the outputs
+///
+/// With `previous`, an earlier output already in that order, the records are merged
+/// into its lines as they are read; on equal counts its records come first. Returns
+/// the number of unique cited works and the citation totals; the partition counts are
+/// left to the caller.
+fn write_inverted(
+    previous: Option<&Path>,
+    mut records: Vec<Value>,
+    output: &Path,
+    output_jsonl: Option<&Path>,
+) -> Result<InvertStats> {
+    let citation_count = |record: &Value| record["citation_count"].as_u64().unwrap_or(0);
+    records.sort_by_key(|record| std::cmp::Reverse(citation_count(record)));
+    let mut records = records.into_iter().peekable();
+
+    let file = File::create(output)
+        .with_context(|| format!("Failed to create output file: {:?}", output))?;
+    let mut writer = BufWriter::new(file);
+    let mut stats = InvertStats::default();
+    let mut write_record = |writer: &mut BufWriter<File>, record: &Value| -> Result<()> {
+        add_to_totals(&mut stats, record);
+        serde_json::to_writer(&mut *writer, record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    };
+    if let Some(previous) = previous {
+        let file = File::open(previous)
+            .with_context(|| format!("Failed to open inverted output: {:?}", previous))?;
+        for line in BufReader::new(file).lines() {
+            let line =
+                line.with_context(|| format!("Failed to read inverted output: {:?}", previous))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Value = serde_json::from_str(&line)?;
+            let count = citation_count(&record);
+            while let Some(retried) = records.next_if(|retried| citation_count(retried) > count) {
+                write_record(&mut writer, &retried)?;
+            }
+            write_record(&mut writer, &record)?;
+        }
+    }
+    for record in records {
+        write_record(&mut writer, &record)?;
+    }
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write inverted output: {:?}", output))?;
+    info!(
+        "Wrote inverted output: {} unique cited works",
+        stats.unique_cited_works
+    );
+
+    if let Some(jsonl_path) = output_jsonl.filter(|path| *path != output) {
+        info!("Writing JSONL output: {:?}", jsonl_path);
+        fs::copy(output, jsonl_path)
+            .with_context(|| format!("Failed to write JSONL output: {:?}", jsonl_path))?;
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::Provenance;
+    use crate::streaming::INVERTED_FILE;
+    use crate::streaming::{CitingWork, ExtractedMatch, PartitionWriter};
+    use serde_json::json;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    /// Records of an inverted JSONL output
+    fn read_inverted(path: &Path) -> Result<Vec<Value>> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open inverted output: {:?}", path))?;
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line =
+                line.with_context(|| format!("Failed to read inverted output: {:?}", path))?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(records)
+    }
+
+    fn write_ref(
+        writer: &mut PartitionWriter,
+        citing: &str,
+        cited: &[&str],
+        provenance: Provenance,
+    ) {
+        let citing = CitingWork {
+            doi: Arc::from(citing),
+            work_type: Some(Arc::from("journal-article")),
+            container_title: None,
+            licenses: None,
+            funders: None,
+        };
+        let matches: Vec<ExtractedMatch> = cited
+            .iter()
+            .map(|cited| ExtractedMatch {
+                raw_match: cited.to_string(),
+                cited_id: cited.to_string(),
+                provenance,
+                match_field: None,
+                match_span: None,
+                context: None,
+            })
+            .collect();
+        writer
+            .write_extracted_ref(&citing, 0, &Arc::from(r#"{"key":"ref1"}"#), &matches)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_invert_jsonl_partitions() {
+        let dir = tempdir().unwrap();
+        let mut writer = PartitionWriter::new(dir.path(), 2).unwrap();
+        write_ref(
+            &mut writer,
+            "10.9/x",
+            &["10.1234/a", "10.1234/a"],
+            Provenance::Mined,
+        );
+        write_ref(
+            &mut writer,
+            "10.9/y",
+            &["10.1234/a"],
+            Provenance::PublisherAsserted,
+        );
+        write_ref(
+            &mut writer,
+            "10.9/x",
+            &["10.5678/b", "10.9/x"],
+            Provenance::CrossrefMatched,
+        );
+        writer.flush_all().unwrap();
+
+        let output = dir.path().join(INVERTED_FILE);
+        let out_dir = tempdir().unwrap();
+        let jsonl = out_dir.path().join("out.jsonl");
+        let mut checkpoint = Checkpoint::new("test");
+        let stats = invert_partitions(
+            dir.path(),
+            &output,
+            Some(&jsonl),
+            &mut checkpoint,
+            OutputMode::Generic,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+
+        assert_eq!(stats.partitions_processed, 3);
+        assert_eq!(stats.unique_cited_works, 2);
+        assert_eq!(stats.total_citations, 3);
+        assert_eq!(stats.provenance_counts.mined_citation_count, 1);
+        assert_eq!(stats.provenance_counts.publisher_citation_count, 1);
+        assert!(checkpoint.is_partition_inverted("10.1234"));
+
+        let records = read_inverted(&jsonl).unwrap();
+        assert_eq!(records, read_inverted(&output).unwrap());
+        assert_eq!(records[0]["doi"], "10.1234/a");
+        assert_eq!(records[0]["citation_count"], 2);
+        assert_eq!(records[0]["cited_by"][0]["doi"], "10.9/x");
+        assert_eq!(records[0]["cited_by"][0]["type"], "journal-article");
+        assert_eq!(
+            records[0]["cited_by"][0]["matches"][0]["reference"]["key"],
+            "ref1"
+        );
+        assert_eq!(
+            records[0]["cited_by"][0]["matches"][0]["reference_key"],
+            "ref1"
+        );
+        assert_eq!(records[1]["crossref_citation_count"], 1);
+        assert_eq!(records[1]["asserted_citation_count"], 1);
+
+        // Exporting copies the kept output and totals it without rewriting it
+        let exported = out_dir.path().join("exported.jsonl");
+        let kept = fs::read(&output).unwrap();
+        let export_stats = export_inverted_jsonl(&output, &exported).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), kept);
+        assert_eq!(fs::read(&exported).unwrap(), kept);
+        assert_eq!(export_stats.unique_cited_works, 2);
+        assert_eq!(export_stats.total_citations, 3);
+        assert_eq!(export_stats.provenance_counts, stats.provenance_counts);
+
+        // A rerun finds every partition inverted, and never reads its own output
+        fs::write(dir.path().join("http_checkpoint.jsonl"), "{}\n").unwrap();
+        let stats = invert_partitions(
+            dir.path(),
+            &output,
+            None,
+            &mut checkpoint,
+            OutputMode::Generic,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        assert_eq!(stats.partitions_processed, 0);
+    }
+
+    #[test]
+    fn test_retry_failed_jsonl_partitions() {
+        let dir = tempdir().unwrap();
+        let mut writer = PartitionWriter::new(dir.path(), 10).unwrap();
+        write_ref(&mut writer, "10.9/x", &["2403.12345"], Provenance::Mined);
+        writer.flush_all().unwrap();
+        let broken = dir.path().join(format!("2404.{}", PARTITION_EXTENSION));
+        fs::write(&broken, "not json\n").unwrap();
+
+        let output = dir.path().join(INVERTED_FILE);
+        let mut checkpoint = Checkpoint::new("test");
+        let cancel = CancellationToken::new();
+        let stats = invert_partitions(
+            dir.path(),
+            &output,
+            None,
+            &mut checkpoint,
+            OutputMode::Arxiv,
+            &cancel,
+        )
+        .unwrap();
+        assert_eq!(stats.partitions_failed, 1);
+        assert_eq!(
+            checkpoint.failed_partitions_in(dir.path()),
+            vec![broken.clone()]
+        );
+
+        let row = json!({
+            "citing_doi": "10.9/y", "citing_type": null, "citing_container_title": null,
+            "citing_licenses": null, "citing_funders": null, "ref_index": 0, "ref_json": "{}",
+            "raw_match": "2404.00001", "cited_id": "2404.00001", "provenance": "mined",
+            "match_field": null, "match_start": null, "match_end": null, "context": null,
+            "same_prefix": false,
+        });
+        let mut second = row.clone();
+        second["citing_doi"] = json!("10.9/z");
+        fs::write(&broken, format!("{}\n{}\n", row, second)).unwrap();
+        let stats = retry_failed_partitions(
+            dir.path(),
+            &output,
+            None,
+            &mut checkpoint,
+            OutputMode::Arxiv,
+            &cancel,
+        )
+        .unwrap();
+        assert_eq!(stats.partitions_processed, 1);
+        assert_eq!(stats.unique_cited_works, 2);
+        assert!(checkpoint.partitions_failed.is_empty());
+
+        assert!(!dir
+            .path()
+            .join(format!("{}.previous", INVERTED_FILE))
+            .exists());
+
+        // Merged into the earlier output by citation count
+        let records = read_inverted(&output).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["arxiv_doi"], "10.48550/arXiv.2404.00001");
+        assert_eq!(records[0]["arxiv_id"], "2404.00001");
+        assert_eq!(records[0]["citation_count"], 2);
+        assert_eq!(records[1]["arxiv_id"], "2403.12345");
+    }
+}
@@ -0,0 +1,403 @@
+use crate::common::{open_sink_with, OutputOptions};
+use crate::error::{Context, Result};
+use log::info;
+use polars::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::inversion::PROVENANCE_COUNT_COLUMNS;
+
+/// Report format, chosen from the output file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardFormat {
+    Json,
+    Markdown,
+    /// One record per entry, written like the other record outputs
+    Records,
+}
+
+impl LeaderboardFormat {
+    /// `.md`/`.markdown` files get Markdown; `.jsonl`, `.gz`, Parquet and Arrow files get
+    /// a record per entry; anything else gets JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown") => {
+                LeaderboardFormat::Markdown
+            }
+            Some("jsonl" | "gz" | "parquet" | "arrow" | "feather" | "arrows") => {
+                LeaderboardFormat::Records
+            }
+            _ => LeaderboardFormat::Json,
+        }
+    }
+}
+
+/// One cited work in the top-N leaderboard
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    /// Cited DOI or arXiv ID
+    pub id: String,
+    pub citation_count: u32,
+    pub reference_count: u32,
+    /// Citing works by provenance (publisher, crossref, structured, mined)
+    pub provenance: BTreeMap<String, usize>,
+}
+
+/// Top-N most cited works from an inverted Parquet file
+#[derive(Debug, Clone, Serialize)]
+pub struct Leaderboard {
+    pub top_n: usize,
+    pub unique_cited_works: usize,
+    pub total_citations: usize,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    /// Read the `top_n` most cited works from the inverted output
+    ///
+    /// Only the ID and count columns are read, and the top works are picked with a
+    /// top-k rather than a full sort. Files written before the provenance counts were
+    /// stored fall back to the `cited_by` lists of the top works alone.
+    pub fn from_inverted_parquet(path: &Path, top_n: usize) -> Result<Self> {
+        let mut lf = LazyFrame::scan_parquet(path, Default::default())
+            .with_context(|| format!("Failed to scan inverted output: {:?}", path))?;
+        let schema = lf
+            .collect_schema()
+            .with_context(|| format!("Failed to read inverted schema: {:?}", path))?;
+
+        let totals = lf
+            .clone()
+            .select([
+                len().cast(DataType::UInt64).alias("unique_cited_works"),
+                col("citation_count")
+                    .cast(DataType::UInt64)
+                    .sum()
+                    .alias("total_citations"),
+            ])
+            .collect()
+            .context("Failed to compute leaderboard totals")?;
+        let unique_cited_works = totals.column("unique_cited_works")?.u64()?.get(0);
+        let total_citations = totals.column("total_citations")?.u64()?.get(0);
+
+        let stored_counts = PROVENANCE_COUNT_COLUMNS
+            .iter()
+            .all(|(_, name)| schema.contains(name));
+        let mut columns = vec![
+            col("cited_id"),
+            col("citation_count"),
+            col("reference_count"),
+        ];
+        if stored_counts {
+            columns.extend(PROVENANCE_COUNT_COLUMNS.map(|(_, name)| col(name)));
+        } else {
+            columns.push(col("cited_by"));
+        }
+        let top = lf
+            .select(columns)
+            .top_k(
+                top_n as IdxSize,
+                [col("citation_count")],
+                SortMultipleOptions::default(),
+            )
+            .collect()
+            .context("Failed to collect top cited works")?;
+
+        let ids = top.column("cited_id")?.str()?;
+        let citation_counts = top.column("citation_count")?.u32()?;
+        let reference_counts = top.column("reference_count")?.u32()?;
+
+        let mut entries = Vec::with_capacity(top.height());
+        for row in 0..top.height() {
+            let provenance = if stored_counts {
+                let mut provenance = BTreeMap::new();
+                for (value, name) in PROVENANCE_COUNT_COLUMNS {
+                    let count = top.column(name)?.cast(&DataType::UInt32)?;
+                    let count = count.u32()?.get(row).unwrap_or(0) as usize;
+                    if count > 0 {
+                        provenance.insert(value.to_string(), count);
+                    }
+                }
+                provenance
+            } else {
+                cited_by_provenance(top.column("cited_by")?.list()?, row)?
+            };
+
+            entries.push(LeaderboardEntry {
+                rank: row + 1,
+                id: ids.get(row).unwrap_or("").to_string(),
+                citation_count: citation_counts.get(row).unwrap_or(0),
+                reference_count: reference_counts.get(row).unwrap_or(0),
+                provenance,
+            });
+        }
+
+        Ok(Self {
+            top_n,
+            unique_cited_works: unique_cited_works.unwrap_or(0) as usize,
+            total_citations: total_citations.unwrap_or(0) as usize,
+            entries,
+        })
+    }
+
+    /// Write the report, in the format implied by the file extension
+    ///
+    /// Entries written as records go through the output sink, with `options`; a JSON
+    /// or Markdown report is a single document.
+    pub fn write(&self, path: &Path, options: &OutputOptions) -> Result<()> {
+        let format = LeaderboardFormat::from_path(path);
+        if format == LeaderboardFormat::Records {
+            let mut sink = open_sink_with(path, options)?;
+            for entry in &self.entries {
+                sink.write_record(&serde_json::to_value(entry)?)?;
+            }
+            sink.finish()?;
+        } else {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create leaderboard report: {:?}", path))?;
+            let mut writer = BufWriter::new(file);
+            if format == LeaderboardFormat::Markdown {
+                writer.write_all(self.to_markdown().as_bytes())?;
+            } else {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writeln!(writer)?;
+            }
+            writer.flush()?;
+        }
+
+        info!(
+            "Leaderboard report written: {:?} (top {} of {} cited works)",
+            path,
+            self.entries.len(),
+            self.unique_cited_works
+        );
+        Ok(())
+    }
+
+    /// Render the report as a Markdown table
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Top {} cited works\n\n{} unique cited works, {} total citations (before validation)\n\n",
+            self.top_n, self.unique_cited_works, self.total_citations
+        );
+        out.push_str(
+            "| Rank | ID | Citations | References | Publisher | Crossref | Structured | Mined |\n",
+        );
+        out.push_str("|---:|---|---:|---:|---:|---:|---:|---:|\n");
+        for entry in &self.entries {
+            let count = |key: &str| entry.provenance.get(key).copied().unwrap_or(0);
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} | {} | {} |\n",
+                entry.rank,
+                entry.id,
+                entry.citation_count,
+                entry.reference_count,
+                count("publisher"),
+                count("crossref"),
+                count("structured"),
+                count("mined")
+            ));
+        }
+        out
+    }
+}
+
+/// Citing works of a `cited_by` list by provenance, each counted once under the
+/// provenance of its first entry, so the counts add up to the citation count
+fn cited_by_provenance(cited_by: &ListChunked, row: usize) -> Result<BTreeMap<String, usize>> {
+    let mut provenance = BTreeMap::new();
+    let Some(series) = cited_by.get_as_series(row) else {
+        return Ok(provenance);
+    };
+    let entries = series.struct_()?;
+    let dois = entries.field_by_name("doi")?;
+    let provenances = entries.field_by_name("provenance")?;
+    let mut seen = HashSet::new();
+    for (doi, value) in dois.str()?.into_iter().zip(provenances.str()?) {
+        if seen.insert(doi) {
+            *provenance
+                .entry(value.unwrap_or("mined").to_string())
+                .or_insert(0) += 1;
+        }
+    }
+    Ok(provenance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Write a minimal inverted file: (cited_id, provenance of each citing work)
+    fn create_inverted_parquet(path: &Path, works: &[(&str, &[&str])]) {
+        let mut cited_ids = Vec::new();
+        let mut citing_dois = Vec::new();
+        let mut provenances = Vec::new();
+        for (cited_id, provs) in works {
+            for (i, prov) in provs.iter().enumerate() {
+                cited_ids.push(cited_id.to_string());
+                citing_dois.push(format!("10.1/citing-{}", i));
+                provenances.push(prov.to_string());
+            }
+        }
+
+        let mut df = df!(
+            "cited_id" => cited_ids,
+            "citing_doi" => citing_dois,
+            "provenance" => provenances,
+        )
+        .unwrap()
+        .lazy()
+        .group_by([col("cited_id")])
+        .agg([
+            col("citing_doi").n_unique().alias("citation_count"),
+            col("citing_doi").count().alias("reference_count"),
+            as_struct(vec![col("citing_doi").alias("doi"), col("provenance")]).alias("cited_by"),
+        ])
+        .collect()
+        .unwrap();
+
+        ParquetWriter::new(File::create(path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_leaderboard_from_inverted_parquet() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("inverted.parquet");
+        create_inverted_parquet(
+            &path,
+            &[
+                ("10.1234/small", &["mined"]),
+                ("10.1234/big", &["publisher", "mined", "mined"]),
+                ("10.1234/mid", &["crossref", "publisher"]),
+            ],
+        );
+
+        let board = Leaderboard::from_inverted_parquet(&path, 2).unwrap();
+        assert_eq!(board.unique_cited_works, 3);
+        assert_eq!(board.total_citations, 6);
+        assert_eq!(board.entries.len(), 2);
+
+        let first = &board.entries[0];
+        assert_eq!(first.rank, 1);
+        assert_eq!(first.id, "10.1234/big");
+        assert_eq!(first.citation_count, 3);
+        assert_eq!(first.provenance.get("mined"), Some(&2));
+        assert_eq!(first.provenance.get("publisher"), Some(&1));
+        assert_eq!(board.entries[1].id, "10.1234/mid");
+    }
+
+    #[test]
+    fn test_leaderboard_counts_each_citing_work_once() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("inverted.parquet");
+        // One work cites 10.1234/a from two references, found as publisher and mined
+        let mut df = df!(
+            "cited_id" => ["10.1234/a", "10.1234/a", "10.1234/a"],
+            "citing_doi" => ["10.1/x", "10.1/x", "10.1/y"],
+            "provenance" => ["publisher", "mined", "mined"],
+        )
+        .unwrap()
+        .lazy()
+        .group_by([col("cited_id")])
+        .agg([
+            col("citing_doi").n_unique().alias("citation_count"),
+            col("citing_doi").count().alias("reference_count"),
+            as_struct(vec![col("citing_doi").alias("doi"), col("provenance")]).alias("cited_by"),
+        ])
+        .collect()
+        .unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        let board = Leaderboard::from_inverted_parquet(&path, 1).unwrap();
+        let entry = &board.entries[0];
+        assert_eq!(entry.citation_count, 2);
+        assert_eq!(entry.provenance.values().sum::<usize>(), 2);
+        assert_eq!(entry.provenance.get("publisher"), Some(&1));
+        assert_eq!(entry.provenance.get("mined"), Some(&1));
+    }
+
+    #[test]
+    fn test_leaderboard_reads_stored_provenance_counts() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("inverted.parquet");
+        // No cited_by column: the stored counts are all that is read
+        let mut df = df!(
+            "cited_id" => ["10.1234/a", "10.1234/b"],
+            "citation_count" => [3u32, 5],
+            "reference_count" => [3u32, 6],
+            "publisher_citation_count" => [1u32, 0],
+            "crossref_citation_count" => [0u32, 2],
+            "structured_citation_count" => [0u32, 0],
+            "mined_citation_count" => [2u32, 3],
+        )
+        .unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        let board = Leaderboard::from_inverted_parquet(&path, 1).unwrap();
+        assert_eq!(board.total_citations, 8);
+        assert_eq!(board.entries.len(), 1);
+        let entry = &board.entries[0];
+        assert_eq!(entry.id, "10.1234/b");
+        assert_eq!(entry.provenance.get("crossref"), Some(&2));
+        assert_eq!(entry.provenance.get("mined"), Some(&3));
+        assert_eq!(entry.provenance.get("publisher"), None);
+    }
+
+    #[test]
+    fn test_leaderboard_write_formats() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("inverted.parquet");
+        create_inverted_parquet(&path, &[("10.1234/a", &["publisher", "mined"])]);
+        let board = Leaderboard::from_inverted_parquet(&path, 10).unwrap();
+
+        let options = OutputOptions::default();
+        let json_path = dir.path().join("top.json");
+        board.write(&json_path, &options).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+        assert_eq!(json["entries"][0]["id"], "10.1234/a");
+        assert_eq!(json["entries"][0]["provenance"]["publisher"], 1);
+
+        let md_path = dir.path().join("top.md");
+        board.write(&md_path, &options).unwrap();
+        let markdown = std::fs::read_to_string(&md_path).unwrap();
+        assert!(markdown.contains("| 1 | 10.1234/a | 2 | 2 | 1 | 0 | 0 | 1 |"));
+
+        let jsonl_path = dir.path().join("top.jsonl");
+        board.write(&jsonl_path, &options).unwrap();
+        let entry: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&jsonl_path).unwrap().trim()).unwrap();
+        assert_eq!(entry["rank"], 1);
+        assert_eq!(entry["provenance"]["mined"], 1);
+    }
+
+    #[test]
+    fn test_leaderboard_format_from_path() {
+        assert_eq!(
+            LeaderboardFormat::from_path(Path::new("top.MD")),
+            LeaderboardFormat::Markdown
+        );
+        assert_eq!(
+            LeaderboardFormat::from_path(Path::new("top.json")),
+            LeaderboardFormat::Json
+        );
+        assert_eq!(
+            LeaderboardFormat::from_path(Path::new("top")),
+            LeaderboardFormat::Json
+        );
+        assert_eq!(
+            LeaderboardFormat::from_path(Path::new("top.parquet")),
+            LeaderboardFormat::Records
+        );
+    }
+}
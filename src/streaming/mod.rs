@@ -1,38 +1,103 @@
+pub mod api_harvest;
+#[cfg(feature = "parquet")]
+pub mod api_members;
 pub mod checkpoint;
+#[cfg(feature = "parquet")]
+pub mod corpus_writer;
+pub mod deposit_xml;
+pub mod graph_export;
+pub mod gzip_reader;
+pub mod intermediates;
+pub mod inversion;
+#[cfg(not(feature = "parquet"))]
+pub mod jsonl_invert;
+#[cfg(feature = "parquet")]
+pub mod leaderboard;
+#[cfg(feature = "parquet")]
+pub mod partition_compact;
+#[cfg(feature = "parquet")]
 pub mod partition_invert;
+#[cfg(feature = "parquet")]
+pub mod partition_schema;
 pub mod partition_writer;
+pub mod record_sample;
+#[cfg(feature = "parquet")]
+pub mod referenced_by;
+#[cfg(feature = "parquet")]
+pub mod snapshot_comparison;
+pub mod snapshot_entries;
+pub mod snapshot_json;
+pub mod unmatched_refs;
 
+pub use api_harvest::{harvest_works, HarvestOptions};
+#[cfg(feature = "parquet")]
+pub use api_members::{members_table_is_fresh, read_members_table, sync_members, MembersOptions};
 pub use checkpoint::*;
-pub use partition_invert::{invert_partitions, OutputMode};
+#[cfg(feature = "parquet")]
+pub use corpus_writer::{CorpusRow, ReferenceCorpusWriter};
+pub use deposit_xml::parse_deposit_xml;
+pub use graph_export::{export_graph, GraphFormat};
+pub use gzip_reader::open_compressed_reader;
+#[cfg(feature = "parquet")]
+pub use gzip_reader::open_gzip_reader;
+pub use intermediates::{IntermediatesManifest, INTERMEDIATES_FILE};
+pub use inversion::{invert_exploded_rows, InvertStats, OutputMode, INVERTED_FILE};
+#[cfg(not(feature = "parquet"))]
+pub use jsonl_invert::{export_inverted_jsonl, invert_partitions, retry_failed_partitions};
+#[cfg(feature = "parquet")]
+pub use leaderboard::Leaderboard;
+#[cfg(feature = "parquet")]
+pub use partition_compact::{compact_partitions, plan_compaction, CompactStep};
+#[cfg(feature = "parquet")]
+pub use partition_invert::{export_inverted_jsonl, invert_partitions, retry_failed_partitions};
 pub use partition_writer::*;
+pub use record_sample::{sample_records, SampleOptions, SampleStrata};
+#[cfg(feature = "parquet")]
+pub use referenced_by::{compare_referenced_by_counts, ReferencedByWriter};
+#[cfg(feature = "parquet")]
+pub use snapshot_comparison::compare_snapshots;
+pub use snapshot_entries::{SnapshotEntry, SnapshotFormat, SnapshotInput};
+pub use snapshot_json::parse_snapshot_json;
+pub use unmatched_refs::{UnmatchedHint, UnmatchedRefsWriter};
 
-// Re-export InvertStats for library users
+// Re-export partition schema versioning for library users
+#[cfg(feature = "parquet")]
 #[allow(unused_imports)]
-pub use partition_invert::InvertStats;
+pub use partition_schema::{partition_schema_version, scan_partition, PARTITION_SCHEMA_VERSION};
 
-/// Extract partition key from a DOI or arXiv ID.
-/// For DOIs: uses prefix (e.g., "10.1234" -> "10.1234")
-/// For arXiv IDs: uses first 4 chars (existing behavior)
-pub fn partition_key(id: &str) -> String {
-    // Check if it looks like a DOI (starts with 10.)
-    if id.starts_with("10.") {
-        // Use the DOI prefix as partition key
-        if let Some(slash_pos) = id.find('/') {
-            return id[..slash_pos].to_lowercase();
-        }
-    }
+// Re-export compaction statistics for library users
+#[cfg(feature = "parquet")]
+#[allow(unused_imports)]
+pub use partition_compact::CompactStats;
 
-    // Fall back to first 4 chars for arXiv IDs
-    id.to_lowercase()
-        .chars()
-        .take(4)
-        .map(|c| if c == '/' { '_' } else { c })
-        .collect()
-}
+// Re-export the partition file extension for library users
+#[allow(unused_imports)]
+pub use inversion::PARTITION_EXTENSION;
+
+// Re-export harvest, members sync and sampling statistics for library users
+#[allow(unused_imports)]
+pub use api_harvest::HarvestStats;
+#[cfg(feature = "parquet")]
+#[allow(unused_imports)]
+pub use api_members::{MemberPrefix, MembersSyncStats};
+#[allow(unused_imports)]
+pub use record_sample::SampleStats;
+
+// Re-export ReadaheadReader for library users
+#[allow(unused_imports)]
+pub use gzip_reader::ReadaheadReader;
+
+/// Default rows per compacted partition file, half the out-of-core inversion threshold
+pub const DEFAULT_COMPACT_TARGET_ROWS: usize = 1_000_000;
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use crate::extract::IdType;
+
+    /// Partition key a row with this cited ID is routed to
+    fn partition_key(id: &str) -> String {
+        IdType::of(id).partition_key(id)
+    }
 
     #[test]
     fn test_partition_key_modern_format() {
@@ -0,0 +1,536 @@
+use crate::common::CancellationToken;
+use crate::error::{Context, Result};
+use log::{debug, info, warn};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use super::partition_invert::parquet_row_count;
+use super::partition_schema::{scan_partition, write_partition_file};
+
+/// Extension of compacted files while they are written, so inversion never picks them up
+const COMPACTING_EXTENSION: &str = "compacting";
+
+/// Journal of the step being swapped in, replayed by `recover_compaction` after a crash
+const JOURNAL_FILE: &str = "compact-journal.json";
+
+/// One change to a partition directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompactStep {
+    /// Small partitions combined into one file
+    Merge { inputs: Vec<PathBuf>, rows: usize },
+    /// An oversized partition divided into `pieces` files by cited_id range
+    Split {
+        input: PathBuf,
+        rows: usize,
+        pieces: usize,
+    },
+}
+
+/// Statistics from compacting a partition directory
+#[derive(Debug, Clone, Default)]
+pub struct CompactStats {
+    pub partitions_before: usize,
+    pub partitions_after: usize,
+    /// Small partitions merged into others
+    pub partitions_merged: usize,
+    /// Oversized partitions split
+    pub partitions_split: usize,
+}
+
+/// Plan the compaction of the partition files in `dir`
+///
+/// Partitions under half of `target_rows` are merged in name order into files of at
+/// most `target_rows`; partitions over twice `target_rows` are split into pieces of
+/// about `target_rows`. Partitions in between are left alone, as are Parquet files that
+/// are not partitions (such as `inverted.parquet`).
+pub fn plan_compaction(dir: &Path, target_rows: usize) -> Result<Vec<CompactStep>> {
+    let target_rows = target_rows.max(1);
+    let mut steps = Vec::new();
+    let mut bucket: Vec<PathBuf> = Vec::new();
+    let mut bucket_rows = 0;
+    let close_bucket = |bucket: &mut Vec<PathBuf>, rows: usize, steps: &mut Vec<_>| {
+        // A bucket of one file would only rename it
+        if bucket.len() > 1 {
+            steps.push(CompactStep::Merge {
+                inputs: std::mem::take(bucket),
+                rows,
+            });
+        }
+        bucket.clear();
+    };
+
+    for (path, rows) in partition_files(dir)? {
+        if rows > target_rows * 2 {
+            steps.push(CompactStep::Split {
+                input: path,
+                rows,
+                pieces: rows.div_ceil(target_rows),
+            });
+        } else if rows < target_rows / 2 {
+            if bucket_rows + rows > target_rows {
+                close_bucket(&mut bucket, bucket_rows, &mut steps);
+                bucket_rows = 0;
+            }
+            bucket.push(path);
+            bucket_rows += rows;
+        }
+    }
+    close_bucket(&mut bucket, bucket_rows, &mut steps);
+    Ok(steps)
+}
+
+/// Merge small partitions and split oversized ones, as planned by `plan_compaction`
+///
+/// Every cited_id stays in a single file, so the directory inverts to the same output
+/// with more evenly sized work per partition. Compacted files are named
+/// `compact-NNNNN.parquet` and written with the current partition schema. Each step
+/// writes its files under a temporary extension and journals the swap before renaming
+/// them and removing its inputs, so a step interrupted part way is finished by
+/// `recover_compaction`; cancellation is checked between steps. Run it between
+/// extraction and inversion: partitions recorded in a checkpoint are referred to by
+/// their file names.
+pub fn compact_partitions(
+    dir: &Path,
+    target_rows: usize,
+    cancel: &CancellationToken,
+) -> Result<CompactStats> {
+    recover_compaction(dir)?;
+    let partitions_before = partition_files(dir)?.len();
+    let steps = plan_compaction(dir, target_rows)?;
+    let mut names = NameAllocator::new(dir)?;
+    let mut stats = CompactStats {
+        partitions_before,
+        ..Default::default()
+    };
+
+    for step in &steps {
+        cancel.check()?;
+        match step {
+            CompactStep::Merge { inputs, rows } => {
+                debug!("Merging {} partitions ({} rows)", inputs.len(), rows);
+                let frames = inputs
+                    .iter()
+                    .map(|path| scan_partition(path))
+                    .collect::<Result<Vec<_>>>()?;
+                let merged = concat(frames, UnionArgs::default())?
+                    .collect()
+                    .context("Failed to merge partitions")?;
+                replace(inputs, &[merged], &mut names)?;
+                stats.partitions_merged += inputs.len();
+            }
+            CompactStep::Split {
+                input,
+                rows,
+                pieces,
+            } => {
+                debug!("Splitting {:?} ({} rows) into {}", input, rows, pieces);
+                let bounds = split_bounds(input, rows.div_ceil(*pieces))?;
+                let mut parts = Vec::with_capacity(bounds.len() + 1);
+                for i in 0..=bounds.len() {
+                    let mut filter = lit(true);
+                    if let Some(lower) = i.checked_sub(1).map(|i| &bounds[i]) {
+                        filter = filter.and(col("cited_id").gt_eq(lit(lower.as_str())));
+                    }
+                    if let Some(upper) = bounds.get(i) {
+                        filter = filter.and(col("cited_id").lt(lit(upper.as_str())));
+                    }
+                    parts.push(
+                        scan_partition(input)?
+                            .filter(filter)
+                            .collect()
+                            .with_context(|| format!("Failed to split partition: {:?}", input))?,
+                    );
+                }
+                replace(std::slice::from_ref(input), &parts, &mut names)?;
+                stats.partitions_split += 1;
+            }
+        }
+    }
+
+    stats.partitions_after = partition_files(dir)?.len();
+    info!(
+        "Compacted {:?}: {} partitions -> {} ({} merged, {} split)",
+        dir,
+        stats.partitions_before,
+        stats.partitions_after,
+        stats.partitions_merged,
+        stats.partitions_split
+    );
+    Ok(stats)
+}
+
+/// Partition files in `dir` with their row counts, in name order
+fn partition_files(dir: &Path) -> Result<Vec<(PathBuf, usize)>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read partition directory: {:?}", dir))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "parquet"))
+        .collect();
+    paths.sort();
+
+    let mut partitions = Vec::with_capacity(paths.len());
+    for path in paths {
+        if is_partition(&path)? {
+            let rows = parquet_row_count(&path)?;
+            partitions.push((path, rows));
+        }
+    }
+    Ok(partitions)
+}
+
+/// Whether a Parquet file holds partition rows rather than, say, inverted output
+fn is_partition(path: &Path) -> Result<bool> {
+    let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+    let schema = ParquetReader::new(file)
+        .schema()
+        .with_context(|| format!("Failed to read parquet schema: {:?}", path))?;
+    Ok(schema.contains("citing_doi") && schema.contains("cited_id"))
+}
+
+/// cited_ids starting each piece after the first, cutting about every `piece_rows` rows
+/// without dividing the rows of one cited_id
+fn split_bounds(path: &Path, piece_rows: usize) -> Result<Vec<String>> {
+    let counts = scan_partition(path)?
+        .group_by([col("cited_id")])
+        .agg([len().alias("rows")])
+        .sort(["cited_id"], Default::default())
+        .collect()
+        .with_context(|| format!("Failed to count cited IDs: {:?}", path))?;
+    let ids = counts.column("cited_id")?.str()?;
+    let rows = counts.column("rows")?.cast(&DataType::UInt64)?;
+    let rows = rows.u64()?;
+
+    let mut bounds = Vec::new();
+    let mut piece = 0;
+    for (id, count) in ids.into_iter().zip(rows) {
+        let (Some(id), Some(count)) = (id, count) else {
+            continue;
+        };
+        if piece >= piece_rows {
+            bounds.push(id.to_string());
+            piece = 0;
+        }
+        piece += count as usize;
+    }
+    Ok(bounds)
+}
+
+/// Write `parts` as new partition files, then remove `inputs`
+///
+/// The renames and removals are journaled first, so a crash between them is finished
+/// on the next start instead of leaving the rows in both the inputs and the new files.
+fn replace(inputs: &[PathBuf], parts: &[DataFrame], names: &mut NameAllocator) -> Result<()> {
+    let mut journal = CompactJournal::default();
+    for part in parts.iter().filter(|part| part.height() > 0) {
+        let path = names.next();
+        let tmp_path = path.with_extension(COMPACTING_EXTENSION);
+        write_partition_file(part, &tmp_path)?;
+        journal
+            .renames
+            .push((file_name(&tmp_path), file_name(&path)));
+    }
+    journal.removes = inputs.iter().map(|input| file_name(input)).collect();
+
+    journal.write(&names.dir)?;
+    journal.apply(&names.dir)
+}
+
+/// Finish a compaction step interrupted after its swap was journaled
+///
+/// A journaled step is completed: its remaining files are renamed and its inputs
+/// removed. Files still under the temporary extension belong to a step that never
+/// reached its journal and are deleted, leaving that step's inputs in place. Run
+/// before compacting or inverting a partition directory.
+pub fn recover_compaction(dir: &Path) -> Result<()> {
+    let journal_path = dir.join(JOURNAL_FILE);
+    if journal_path.exists() {
+        warn!("Finishing interrupted compaction step in {:?}", dir);
+        let data = fs::read_to_string(&journal_path)
+            .with_context(|| format!("Failed to read compaction journal: {:?}", journal_path))?;
+        let journal: CompactJournal = serde_json::from_str(&data)
+            .with_context(|| format!("Failed to parse compaction journal: {:?}", journal_path))?;
+        journal.apply(dir)?;
+    }
+
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)
+        .with_context(|| format!("Failed to read partition directory: {:?}", dir))?
+        .filter_map(|entry| entry.ok())
+    {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == COMPACTING_EXTENSION)
+        {
+            debug!("Removing unfinished compacted file {:?}", path);
+            fs::remove_file(&path).with_context(|| format!("Failed to remove: {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
+/// The renames and removals that swap one step's files in, by name within the directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CompactJournal {
+    renames: Vec<(String, String)>,
+    removes: Vec<String>,
+}
+
+impl CompactJournal {
+    /// Write the journal atomically, so a crash leaves either all of it or none
+    fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(JOURNAL_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(self)?)
+            .with_context(|| format!("Failed to write compaction journal: {:?}", tmp_path))?;
+        File::open(&tmp_path)
+            .and_then(|file| file.sync_all())
+            .with_context(|| format!("Failed to sync compaction journal: {:?}", tmp_path))?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))
+    }
+
+    /// Carry out the swap, skipping what was already done, then drop the journal
+    fn apply(&self, dir: &Path) -> Result<()> {
+        for (tmp_name, name) in &self.renames {
+            let (tmp_path, path) = (dir.join(tmp_name), dir.join(name));
+            if tmp_path.exists() {
+                fs::rename(&tmp_path, &path)
+                    .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))?;
+            }
+        }
+        for name in &self.removes {
+            let input = dir.join(name);
+            if input.exists() {
+                fs::remove_file(&input)
+                    .with_context(|| format!("Failed to remove: {:?}", input))?;
+            }
+        }
+        let path = dir.join(JOURNAL_FILE);
+        fs::remove_file(&path).with_context(|| format!("Failed to remove: {:?}", path))
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Hands out `compact-NNNNN.parquet` names not yet used in the directory
+struct NameAllocator {
+    dir: PathBuf,
+    taken: HashSet<String>,
+    next: usize,
+}
+
+impl NameAllocator {
+    fn new(dir: &Path) -> Result<Self> {
+        let taken = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read partition directory: {:?}", dir))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            taken,
+            next: 0,
+        })
+    }
+
+    fn next(&mut self) -> PathBuf {
+        loop {
+            let name = format!("compact-{:05}.parquet", self.next);
+            self.next += 1;
+            if self.taken.insert(name.clone()) {
+                return self.dir.join(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_test_partition(dir: &Path, name: &str, cited_ids: &[&str]) {
+        let n = cited_ids.len();
+        let df = df!(
+            "citing_doi" => vec!["10.9999/citing"; n],
+            "ref_index" => (0..n as u32).collect::<Vec<_>>(),
+            "ref_json" => vec!["{}"; n],
+            "raw_match" => cited_ids,
+            "cited_id" => cited_ids,
+        )
+        .unwrap();
+        write_partition_file(&df, &dir.join(format!("{}.parquet", name))).unwrap();
+    }
+
+    fn cited_ids_by_file(dir: &Path) -> Vec<(String, Vec<String>)> {
+        partition_files(dir)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| {
+                let df = scan_partition(&path).unwrap().collect().unwrap();
+                let mut ids: Vec<String> = df
+                    .column("cited_id")
+                    .unwrap()
+                    .str()
+                    .unwrap()
+                    .into_iter()
+                    .flatten()
+                    .map(String::from)
+                    .collect();
+                ids.sort();
+                let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+                (name, ids)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_compaction() {
+        let dir = tempdir().unwrap();
+        write_test_partition(dir.path(), "10.1111", &["10.1111/a"]);
+        write_test_partition(dir.path(), "10.2222", &["10.2222/a"]);
+        write_test_partition(dir.path(), "10.3333", &["10.3333/a"; 5]);
+        write_test_partition(dir.path(), "10.4444", &["10.4444/a"; 12]);
+        let mut inverted = df!("cited_id" => ["10.1111/a"]).unwrap();
+        ParquetWriter::new(File::create(dir.path().join("inverted.parquet")).unwrap())
+            .finish(&mut inverted)
+            .unwrap();
+
+        let steps = plan_compaction(dir.path(), 5).unwrap();
+        assert_eq!(
+            steps,
+            [
+                CompactStep::Split {
+                    input: dir.path().join("10.4444.parquet"),
+                    rows: 12,
+                    pieces: 3,
+                },
+                CompactStep::Merge {
+                    inputs: vec![
+                        dir.path().join("10.1111.parquet"),
+                        dir.path().join("10.2222.parquet")
+                    ],
+                    rows: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compact_partitions_keeps_cited_ids_together() {
+        let dir = tempdir().unwrap();
+        write_test_partition(dir.path(), "10.1111", &["10.1111/a"]);
+        write_test_partition(dir.path(), "10.2222", &["10.2222/a"]);
+        write_test_partition(
+            dir.path(),
+            "10.4444",
+            &[
+                "10.4444/a",
+                "10.4444/a",
+                "10.4444/a",
+                "10.4444/b",
+                "10.4444/c",
+                "10.4444/c",
+                "10.4444/d",
+                "10.4444/e",
+                "10.4444/e",
+                "10.4444/e",
+                "10.4444/f",
+            ],
+        );
+
+        let stats = compact_partitions(dir.path(), 4, &CancellationToken::new()).unwrap();
+        assert_eq!(stats.partitions_before, 3);
+        assert_eq!(stats.partitions_merged, 2);
+        assert_eq!(stats.partitions_split, 1);
+        assert_eq!(stats.partitions_after, 4);
+
+        let files = cited_ids_by_file(dir.path());
+        assert!(files.iter().all(|(name, _)| name.starts_with("compact-")));
+        assert_eq!(
+            files[0].1,
+            ["10.4444/a", "10.4444/a", "10.4444/a", "10.4444/b"]
+        );
+        assert_eq!(
+            files[1].1,
+            [
+                "10.4444/c",
+                "10.4444/c",
+                "10.4444/d",
+                "10.4444/e",
+                "10.4444/e",
+                "10.4444/e"
+            ]
+        );
+        assert_eq!(files[2].1, ["10.4444/f"]);
+        assert_eq!(files[3].1, ["10.1111/a", "10.2222/a"]);
+        assert!(fs::read_dir(dir.path()).unwrap().all(|entry| entry
+            .unwrap()
+            .path()
+            .extension()
+            .unwrap()
+            == "parquet"));
+    }
+
+    #[test]
+    fn test_recover_compaction_finishes_journaled_step() {
+        let dir = tempdir().unwrap();
+        write_test_partition(dir.path(), "10.1111", &["10.1111/a"]);
+        write_test_partition(dir.path(), "10.2222", &["10.2222/a"]);
+        write_test_partition(dir.path(), "10.3333", &["10.3333/a"]);
+
+        // Crash after the journal and the first rename, before the inputs were removed
+        let merged = scan_partition(&dir.path().join("10.1111.parquet"))
+            .unwrap()
+            .collect()
+            .unwrap();
+        write_partition_file(&merged, &dir.path().join("compact-00000.parquet")).unwrap();
+        write_partition_file(&merged, &dir.path().join("compact-00001.compacting")).unwrap();
+        let journal = CompactJournal {
+            renames: vec![
+                (
+                    "compact-00000.compacting".into(),
+                    "compact-00000.parquet".into(),
+                ),
+                (
+                    "compact-00001.compacting".into(),
+                    "compact-00001.parquet".into(),
+                ),
+            ],
+            removes: vec!["10.1111.parquet".into(), "10.2222.parquet".into()],
+        };
+        journal.write(dir.path()).unwrap();
+        // And a step that never reached its journal
+        write_partition_file(&merged, &dir.path().join("compact-00002.compacting")).unwrap();
+
+        recover_compaction(dir.path()).unwrap();
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "10.3333.parquet",
+                "compact-00000.parquet",
+                "compact-00001.parquet"
+            ]
+        );
+
+        // Nothing left to replay
+        recover_compaction(dir.path()).unwrap();
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+}
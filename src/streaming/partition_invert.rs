@@ -1,45 +1,92 @@
-use anyhow::{Context, Result};
-use log::{debug, info};
+use crate::error::{Context, Error, Result};
+use crossbeam_channel::{bounded, Sender};
+use log::{debug, info, warn};
+use polars::io::parquet::write::BatchedWriter;
 use polars::prelude::*;
 use rayon::prelude::*;
 use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{BufWriter, Write};
-use std::path::Path;
-
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use super::inversion::{
+    add_reference, restore_set_aside, set_aside_output, InvertStats, OutputMode,
+    ASSERTED_COUNT_COLUMN, PROVENANCE_COUNT_COLUMNS,
+};
+use super::partition_compact::recover_compaction;
+use super::partition_schema::{id_type_of, scan_partition};
 use super::Checkpoint;
+use crate::common::{CancellationToken, ProvenanceCounts};
+use crate::extract::ASSERTED_PROVENANCES;
+
+/// Partitions with more rows than this are inverted out-of-core
+const OUT_OF_CORE_ROW_THRESHOLD: usize = 2_000_000;
+/// Rows grouped at a time when inverting a partition out-of-core
+const OUT_OF_CORE_SLICE_ROWS: usize = 500_000;
+
+/// An inverted partition, held in memory or, for partitions inverted out-of-core, on disk
+enum InvertedPartition {
+    Frame(DataFrame),
+    File(InvertedFile),
+    /// An earlier inverted output that retried partitions are merged into
+    Output(PathBuf),
+}
 
-/// Output mode for inverted data
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub enum OutputMode {
-    /// arXiv-specific output with arxiv_doi and arxiv_id fields
-    #[default]
-    Arxiv,
-    /// Generic DOI output with just doi field
-    Generic,
+impl InvertedPartition {
+    fn lazy(&self) -> Result<LazyFrame> {
+        match self {
+            InvertedPartition::Frame(df) => Ok(df.clone().lazy()),
+            InvertedPartition::File(file) => LazyFrame::scan_parquet(&file.0, Default::default())
+                .with_context(|| format!("Failed to scan inverted partition: {:?}", file.0)),
+            InvertedPartition::Output(path) => scan_inverted(path),
+        }
+    }
 }
 
-/// Statistics from inverting partitions
-#[derive(Debug, Clone, Default)]
-pub struct InvertStats {
-    pub partitions_processed: usize,
-    pub unique_cited_works: usize,
-    pub total_citations: usize,
+/// A partition's inverted rows written to a temporary Parquet file, removed on drop
+struct InvertedFile(PathBuf);
+
+impl Drop for InvertedFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
 }
 
 /// Invert a single partition file
 ///
-/// Each partition file contains rows with (citing_doi, ref_index, ref_json, raw_match, cited_id).
+/// Each partition file contains rows with (citing_doi, citing_type, citing_container_title,
+/// citing_licenses, citing_funders, ref_index, ref_json, raw_match, cited_id, provenance,
+/// match_field, context, same_prefix).
 /// This function groups by cited_id and aggregates to produce the inverted index.
-fn invert_single_partition(partition_path: &Path, output_mode: OutputMode) -> Result<DataFrame> {
+/// Partitions larger than `OUT_OF_CORE_ROW_THRESHOLD` rows are inverted out-of-core.
+fn invert_single_partition(
+    partition_path: &Path,
+    output_mode: OutputMode,
+) -> Result<InvertedPartition> {
     debug!("Inverting partition: {:?}", partition_path);
 
-    let lf = LazyFrame::scan_parquet(partition_path, Default::default())
-        .with_context(|| format!("Failed to scan partition: {:?}", partition_path))?;
+    let rows = parquet_row_count(partition_path)?;
+    if rows > OUT_OF_CORE_ROW_THRESHOLD {
+        info!(
+            "Inverting large partition out-of-core: {:?} ({} rows)",
+            partition_path, rows
+        );
+        return invert_partition_out_of_core(partition_path, output_mode, OUT_OF_CORE_SLICE_ROWS)
+            .map(InvertedPartition::File);
+    }
 
-    // Group by cited_id, aggregating citations
-    // Note: rows are already exploded (one row per cited_id per reference)
-    let inverted = lf
+    let lf = scan_partition(partition_path)?;
+
+    aggregate_citations(dedup_citations(lf), output_mode)
+        .collect()
+        .map(InvertedPartition::Frame)
+        .with_context(|| format!("Failed to collect inverted partition: {:?}", partition_path))
+}
+
+/// Deduplicate citations and drop self-citations
+fn dedup_citations(lf: LazyFrame) -> LazyFrame {
+    lf
         // Deduplicate (same citing_doi + cited_id should only count once)
         .unique(
             Some(vec!["citing_doi".into(), "cited_id".into()]),
@@ -47,42 +94,197 @@ fn invert_single_partition(partition_path: &Path, output_mode: OutputMode) -> Re
         )
         // Filter out any self-citations that slipped through
         .filter(col("citing_doi").neq(col("cited_id")))
-        .group_by([col("cited_id")])
-        .agg([
-            col("citing_doi").n_unique().alias("citation_count"),
-            col("citing_doi").count().alias("reference_count"),
-            as_struct(vec![
-                col("citing_doi").alias("doi"),
-                col("raw_match"),
-                col("ref_json").alias("reference"),
-                col("provenance"),
-            ])
-            .alias("cited_by"),
-        ]);
+}
+
+/// Group citation rows by cited_id, aggregating citations
+///
+/// Rows are deduplicated to one per citing work, so the per-provenance counts add up
+/// to the citation count.
+fn aggregate_citations(lf: LazyFrame, output_mode: OutputMode) -> LazyFrame {
+    // Note: rows are already exploded (one row per cited_id per reference)
+    let mut aggs = vec![
+        col("id_type").first(),
+        col("citing_doi").n_unique().alias("citation_count"),
+        col("citing_doi").count().alias("reference_count"),
+    ];
+    aggs.extend(PROVENANCE_COUNT_COLUMNS.map(|(provenance, name)| {
+        col("provenance")
+            .eq(lit(provenance))
+            .sum()
+            .cast(DataType::UInt32)
+            .alias(name)
+    }));
+    aggs.push(
+        is_asserted(col("provenance"))
+            .sum()
+            .cast(DataType::UInt32)
+            .alias(ASSERTED_COUNT_COLUMN),
+    );
+    aggs.push(
+        as_struct(vec![
+            col("citing_doi").alias("doi"),
+            col("raw_match"),
+            col("ref_json").alias("reference"),
+            col("provenance"),
+            col("match_field"),
+            col("match_start"),
+            col("match_end"),
+            col("context"),
+            col("same_prefix"),
+            col("citing_type"),
+            col("citing_container_title"),
+            col("citing_licenses"),
+            col("citing_funders"),
+        ])
+        .alias("cited_by"),
+    );
+    let inverted = lf.group_by([col("cited_id")]).agg(aggs);
 
     // Add arxiv_doi column only for Arxiv output mode
-    let inverted = match output_mode {
+    match output_mode {
         OutputMode::Arxiv => {
             inverted.with_columns([
                 concat_str([lit("10.48550/arXiv."), col("cited_id")], "", true).alias("arxiv_doi"),
             ])
         }
         OutputMode::Generic => inverted,
+    }
+}
+
+/// Number of rows in a Parquet file, read from its metadata
+pub(super) fn parquet_row_count(path: &Path) -> Result<usize> {
+    let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+    ParquetReader::new(file)
+        .num_rows()
+        .with_context(|| format!("Failed to read row count: {:?}", path))
+}
+
+/// Invert a partition too large to group in memory
+///
+/// Polars' streaming engine cannot aggregate into list columns, so the streamable part
+/// (deduplication and a spill-to-disk sort by cited_id) is sunk to a temporary file first.
+/// The sorted rows are then grouped in slices of about `slice_rows`, with slice boundaries
+/// moved to cited_id changes so no cited work is split across slices, and each slice's
+/// aggregate is appended to a temporary inverted file as it is produced.
+fn invert_partition_out_of_core(
+    partition_path: &Path,
+    output_mode: OutputMode,
+    slice_rows: usize,
+) -> Result<InvertedFile> {
+    // Not a .parquet extension, so an interrupted run never mistakes it for a partition
+    let sorted_path = partition_path.with_extension("sorted");
+
+    let lf = scan_partition(partition_path)?;
+    dedup_citations(lf)
+        .sort(["cited_id"], SortMultipleOptions::default())
+        .with_streaming(true)
+        .sink_parquet(&sorted_path, ParquetWriteOptions::default(), None)
+        .with_context(|| format!("Failed to sort partition: {:?}", partition_path))?;
+
+    let inverted = InvertedFile(partition_path.with_extension("inverted"));
+    let result = invert_sorted_slices(&sorted_path, &inverted.0, output_mode, slice_rows);
+    let _ = fs::remove_file(&sorted_path);
+    result.map(|()| inverted)
+}
+
+/// Group a cited_id-sorted Parquet file slice by slice, writing one row group per slice
+fn invert_sorted_slices(
+    sorted_path: &Path,
+    inverted_path: &Path,
+    output_mode: OutputMode,
+    slice_rows: usize,
+) -> Result<()> {
+    let total_rows = parquet_row_count(sorted_path)?;
+    let mut writer: Option<BatchedWriter<File>> = None;
+    let mut offset = 0;
+
+    while offset < total_rows {
+        // Grow the slice until it ends on a cited_id boundary (or the end of the file)
+        let mut len = slice_rows.max(1);
+        let (slice, cut) = loop {
+            let slice = LazyFrame::scan_parquet(sorted_path, Default::default())?
+                .slice(offset as i64, len as IdxSize)
+                .collect()
+                .context("Failed to read sorted partition slice")?;
+
+            if offset + slice.height() >= total_rows {
+                let height = slice.height();
+                break (slice, height);
+            }
+            let cut = last_group_start(&slice)?;
+            if cut > 0 {
+                break (slice, cut);
+            }
+            len *= 2;
+        };
+
+        let chunk = aggregate_citations(slice.slice(0, cut).lazy(), output_mode)
+            .collect()
+            .context("Failed to aggregate sorted partition slice")?;
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => writer.insert(inverted_writer(inverted_path, chunk.schema())?),
+        };
+        writer
+            .write_batch(&chunk)
+            .context("Failed to write inverted partition slice")?;
+        offset += cut;
+    }
+
+    let writer = match writer {
+        Some(writer) => writer,
+        None => {
+            let empty = aggregate_citations(
+                LazyFrame::scan_parquet(sorted_path, Default::default())?,
+                output_mode,
+            )
+            .collect()
+            .context("Failed to aggregate empty partition")?;
+            inverted_writer(inverted_path, empty.schema())?
+        }
     };
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finish inverted partition: {:?}", inverted_path))?;
+    Ok(())
+}
 
-    inverted
-        .collect()
-        .with_context(|| format!("Failed to collect inverted partition: {:?}", partition_path))
+/// Parquet writer for a partition's inverted rows
+fn inverted_writer(path: &Path, schema: &Schema) -> Result<BatchedWriter<File>> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create inverted partition: {:?}", path))?;
+    ParquetWriter::new(file)
+        .with_compression(ParquetCompression::Zstd(None))
+        .batched(schema)
+        .with_context(|| format!("Failed to start inverted partition: {:?}", path))
+}
+
+/// Index of the first row sharing the last row's cited_id in a sorted slice
+fn last_group_start(slice: &DataFrame) -> Result<usize> {
+    let cited_ids = slice.column("cited_id")?.str()?;
+    let last = cited_ids.get(slice.height() - 1);
+    let mut start = slice.height() - 1;
+    while start > 0 && cited_ids.get(start - 1) == last {
+        start -= 1;
+    }
+    Ok(start)
 }
 
 /// Invert all partition files in parallel
+///
+/// A partition that fails to invert is logged and recorded in the checkpoint, and the
+/// remaining partitions are still inverted; only an interruption or every partition
+/// failing stops the run. Failed partitions can be retried with `retry_failed_partitions`.
 pub fn invert_partitions(
     partition_dir: &Path,
     output_parquet: &Path,
     output_jsonl: Option<&Path>,
     checkpoint: &mut Checkpoint,
     output_mode: OutputMode,
+    cancel: &CancellationToken,
 ) -> Result<InvertStats> {
+    recover_compaction(partition_dir)?;
+
     // Find all partition files
     let partition_files: Vec<_> = fs::read_dir(partition_dir)
         .with_context(|| format!("Failed to read partition directory: {:?}", partition_dir))?
@@ -98,12 +300,230 @@ pub fn invert_partitions(
 
     info!("Inverting {} partitions in parallel", partition_files.len());
 
+    let dfs = invert_partition_files(&partition_files, checkpoint, output_mode, cancel)?;
+    let partitions_failed = partition_files.len() - dfs.len();
+    if dfs.is_empty() && partitions_failed > 0 {
+        return Err(Error::internal(format!(
+            "All {} partitions failed to invert",
+            partitions_failed
+        )));
+    }
+
+    if dfs.is_empty() {
+        info!("No partitions to invert (all already processed or none found)");
+        return Ok(InvertStats::default());
+    }
+
+    let partitions_processed = dfs.len();
+    let stats = write_inverted(dfs, output_parquet, output_jsonl, output_mode)?;
+
+    Ok(InvertStats {
+        partitions_processed,
+        partitions_failed,
+        ..stats
+    })
+}
+
+/// Re-invert the partitions in a directory that failed in an earlier run
+///
+/// Partitions hold disjoint cited IDs, so the retried rows are merged with a scan of
+/// the existing inverted Parquet output, which is never loaded whole, and both outputs
+/// are rewritten.
+pub fn retry_failed_partitions(
+    partition_dir: &Path,
+    output_parquet: &Path,
+    output_jsonl: Option<&Path>,
+    checkpoint: &mut Checkpoint,
+    output_mode: OutputMode,
+    cancel: &CancellationToken,
+) -> Result<InvertStats> {
+    recover_compaction(partition_dir)?;
+    let partition_files = checkpoint.failed_partitions_in(partition_dir);
+    info!("Retrying {} failed partitions", partition_files.len());
+
+    let retried = invert_partition_files(&partition_files, checkpoint, output_mode, cancel)?;
+    let partitions_processed = retried.len();
+    let partitions_failed = partition_files.len() - partitions_processed;
+
+    let previous = set_aside_output(output_parquet)?;
+    let mut dfs = Vec::with_capacity(retried.len() + 1);
+    dfs.extend(previous.clone().map(InvertedPartition::Output));
+    dfs.extend(retried);
+
+    if dfs.is_empty() {
+        return Err(Error::internal(format!(
+            "All {} retried partitions failed to invert",
+            partitions_failed
+        )));
+    }
+
+    let stats = restore_set_aside(
+        previous.as_deref(),
+        output_parquet,
+        write_inverted(dfs, output_parquet, output_jsonl, output_mode),
+    )?;
+
+    Ok(InvertStats {
+        partitions_processed,
+        partitions_failed,
+        ..stats
+    })
+}
+
+/// Write the JSONL of an existing inverted output, as inversion wrote it
+///
+/// Lets a kept partition directory be validated again without re-inverting it. The
+/// Parquet output is only read, a slice at a time; outputs written before the id_type
+/// or count columns existed gain them on the way to the JSONL.
+pub fn export_inverted_jsonl(
+    output_parquet: &Path,
+    output_jsonl: &Path,
+    output_mode: OutputMode,
+) -> Result<InvertStats> {
+    let inverted = scan_inverted(output_parquet)?;
+    let stats = inverted_totals(inverted.clone())?;
+    write_inverted_jsonl(
+        &inverted,
+        stats.unique_cited_works,
+        output_jsonl,
+        output_mode,
+    )?;
+    Ok(stats)
+}
+
+/// Scan an inverted Parquet output, upgraded to the current columns
+fn scan_inverted(path: &Path) -> Result<LazyFrame> {
+    let inverted = LazyFrame::scan_parquet(path, Default::default())
+        .with_context(|| format!("Failed to scan inverted output: {:?}", path))?;
+    with_provenance_counts(with_id_type(inverted)?)
+}
+
+/// The number of cited works and the citation totals of an inverted output
+fn inverted_totals(inverted: LazyFrame) -> Result<InvertStats> {
+    let totals = inverted
+        .select(
+            ["citation_count"]
+                .into_iter()
+                .chain(PROVENANCE_COUNT_COLUMNS.map(|(_, name)| name))
+                .map(|name| col(name).cast(DataType::UInt64).sum())
+                .chain([len().cast(DataType::UInt64).alias("rows")])
+                .collect::<Vec<_>>(),
+        )
+        .collect()
+        .context("Failed to total inverted output")?;
+    let column_total = |name: &str| -> Result<usize> {
+        Ok(totals.column(name)?.u64()?.get(0).unwrap_or(0) as usize)
+    };
+    Ok(InvertStats {
+        unique_cited_works: column_total("rows")?,
+        total_citations: column_total("citation_count")?,
+        provenance_counts: ProvenanceCounts {
+            publisher_citation_count: column_total("publisher_citation_count")?,
+            crossref_citation_count: column_total("crossref_citation_count")?,
+            structured_citation_count: column_total("structured_citation_count")?,
+            mined_citation_count: column_total("mined_citation_count")?,
+        },
+        ..Default::default()
+    })
+}
+
+/// Whether a provenance column holds an asserted provenance (publisher or crossref)
+fn is_asserted(provenance: Expr) -> Expr {
+    ASSERTED_PROVENANCES
+        .map(|asserted| provenance.clone().eq(lit(asserted)))
+        .into_iter()
+        .reduce(Expr::or)
+        .expect("there are asserted provenances")
+}
+
+/// Add the id_type column, after cited_id, to an inverted output written without it
+fn with_id_type(mut inverted: LazyFrame) -> Result<LazyFrame> {
+    let schema = inverted
+        .collect_schema()
+        .context("Failed to read inverted output schema")?;
+    if schema.contains("id_type") {
+        return Ok(inverted);
+    }
+    let mut order = vec![col("cited_id"), col("id_type")];
+    order.extend(
+        schema
+            .iter_names()
+            .filter(|name| name.as_str() != "cited_id")
+            .map(|name| col(name.clone())),
+    );
+    Ok(inverted
+        .with_column(id_type_of(col("cited_id")).alias("id_type"))
+        .select(order))
+}
+
+/// Add the count columns to an inverted output written without some of them
+///
+/// Counts the provenances in cited_by, which holds one entry per citing work, and
+/// places the columns where inversion does, before cited_by.
+fn with_provenance_counts(mut inverted: LazyFrame) -> Result<LazyFrame> {
+    let schema = inverted
+        .collect_schema()
+        .context("Failed to read inverted output schema")?;
+    let provenance = || col("").struct_().field_by_name("provenance");
+    let count_in_cited_by = |predicate: Expr, name: &'static str| {
+        col("cited_by")
+            .list()
+            .eval(predicate, false)
+            .list()
+            .sum()
+            .cast(DataType::UInt32)
+            .alias(name)
+    };
+    let mut counts: Vec<(&str, Expr)> = PROVENANCE_COUNT_COLUMNS
+        .iter()
+        .map(|&(value, name)| (name, count_in_cited_by(provenance().eq(lit(value)), name)))
+        .collect();
+    counts.push((
+        ASSERTED_COUNT_COLUMN,
+        count_in_cited_by(is_asserted(provenance()), ASSERTED_COUNT_COLUMN),
+    ));
+    counts.retain(|(name, _)| !schema.contains(name));
+    if counts.is_empty() {
+        return Ok(inverted);
+    }
+
+    let count_names: Vec<&str> = PROVENANCE_COUNT_COLUMNS
+        .iter()
+        .map(|&(_, name)| name)
+        .chain([ASSERTED_COUNT_COLUMN])
+        .collect();
+    let mut order: Vec<Expr> = Vec::new();
+    for name in schema.iter_names() {
+        if name.as_str() == "cited_by" {
+            order.extend(count_names.iter().map(|&count| col(count)));
+        } else if count_names.contains(&name.as_str()) {
+            continue;
+        }
+        order.push(col(name.clone()));
+    }
+    let counts: Vec<Expr> = counts.into_iter().map(|(_, expr)| expr).collect();
+    Ok(inverted.with_columns(counts).select(order))
+}
+
+/// Invert partition files in parallel batches, returning the partitions that succeeded
+///
+/// Each outcome is recorded in the checkpoint: successes as inverted (clearing any earlier
+/// failure), failures with their error.
+fn invert_partition_files(
+    partition_files: &[PathBuf],
+    checkpoint: &mut Checkpoint,
+    output_mode: OutputMode,
+    cancel: &CancellationToken,
+) -> Result<Vec<InvertedPartition>> {
     // Process partitions in batches to avoid stack overflow from nested parallelism
     // (Polars uses rayon internally, so processing too many partitions at once causes issues)
     const BATCH_SIZE: usize = 500;
     let mut dfs = Vec::new();
 
     for (batch_idx, batch) in partition_files.chunks(BATCH_SIZE).enumerate() {
+        // Partitions inverted so far are recorded in the checkpoint
+        cancel.check()?;
+
         debug!(
             "Processing partition batch {}/{} ({} partitions)",
             batch_idx + 1,
@@ -111,183 +531,322 @@ pub fn invert_partitions(
             batch.len()
         );
 
-        let results: Vec<Result<(String, DataFrame)>> = batch
+        let results: Vec<(&PathBuf, Result<InvertedPartition>)> = batch
             .par_iter()
-            .map(|path| {
-                let df = invert_single_partition(path, output_mode)?;
-                let name = path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("unknown")
-                    .to_string();
-                Ok((name, df))
-            })
+            .map(|path| (path, invert_single_partition(path, output_mode)))
             .collect();
 
-        // Collect successful results and track which partitions completed
-        for result in results {
+        // Collect successful results and track which partitions completed or failed
+        for (path, result) in results {
             match result {
-                Ok((name, df)) => {
-                    checkpoint.mark_partition_inverted(&name);
-                    dfs.push(df);
+                Ok(inverted) => {
+                    let name = path
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("unknown");
+                    checkpoint.mark_partition_inverted(name);
+                    checkpoint.clear_partition_failed(path);
+                    dfs.push(inverted);
                 }
                 Err(e) => {
-                    return Err(e.context("Failed to invert partition"));
+                    warn!("Failed to invert partition {:?}: {:#}", path, e);
+                    checkpoint.mark_partition_failed(path, format!("{:#}", e));
                 }
             }
         }
     }
 
-    if dfs.is_empty() {
-        info!("No partitions to invert (all already processed or none found)");
-        return Ok(InvertStats::default());
-    }
-
-    info!("Concatenating {} inverted partitions", dfs.len());
+    Ok(dfs)
+}
 
-    // Concatenate in batches to avoid stack overflow from deep recursive plans
-    // Polars concat builds a tree of Union nodes; too deep causes stack overflow
-    const CONCAT_BATCH_SIZE: usize = 500;
-    let mut combined = if dfs.len() <= CONCAT_BATCH_SIZE {
-        // Small enough to concat directly
-        let lazy_dfs: Vec<LazyFrame> = dfs.into_iter().map(|df| df.lazy()).collect();
-        concat(&lazy_dfs, UnionArgs::default())
-            .context("Failed to concatenate inverted partitions")?
-            .collect()
-            .context("Failed to collect combined dataframe")?
-    } else {
-        // Batch concatenation to limit tree depth
-        let mut batched_dfs: Vec<DataFrame> = Vec::new();
-        for chunk in dfs.chunks(CONCAT_BATCH_SIZE) {
-            let lazy_chunk: Vec<LazyFrame> = chunk.iter().map(|df| df.clone().lazy()).collect();
-            let batch_df = concat(&lazy_chunk, UnionArgs::default())
-                .context("Failed to concatenate batch")?
-                .collect()
-                .context("Failed to collect batch")?;
-            batched_dfs.push(batch_df);
-        }
-        // Final concat of batched results
-        let lazy_batched: Vec<LazyFrame> = batched_dfs.into_iter().map(|df| df.lazy()).collect();
-        concat(&lazy_batched, UnionArgs::default())
-            .context("Failed to concatenate batched results")?
-            .collect()
-            .context("Failed to collect final result")?
-    };
+/// Concatenate inverted partitions, sort by citation count, and write the outputs
+///
+/// The sort streams into the Parquet output, spilling to disk as needed, and the JSONL
+/// is written from the sorted Parquet a slice at a time, so partitions inverted
+/// out-of-core are never loaded whole. Returns the number of unique cited works and
+/// the citation totals; the partition counts are left to the caller.
+fn write_inverted(
+    parts: Vec<InvertedPartition>,
+    output_parquet: &Path,
+    output_jsonl: Option<&Path>,
+    output_mode: OutputMode,
+) -> Result<InvertStats> {
+    info!("Concatenating {} inverted partitions", parts.len());
+    let (frames, files): (Vec<_>, Vec<_>) = parts
+        .into_iter()
+        .partition(|part| matches!(part, InvertedPartition::Frame(_)));
+
+    let mut lazy_parts = Vec::with_capacity(files.len() + 1);
+    if !frames.is_empty() {
+        lazy_parts.push(concat_frames(frames)?.lazy());
+    }
+    for file in &files {
+        lazy_parts.push(file.lazy()?);
+    }
+    let combined = concat(&lazy_parts, UnionArgs::default())
+        .context("Failed to concatenate inverted partitions")?;
 
     // Sort by citation count descending
-    combined = combined
-        .lazy()
+    combined
         .sort(
             ["citation_count"],
             SortMultipleOptions::default().with_order_descending(true),
         )
-        .collect()
-        .context("Failed to sort combined dataframe")?;
+        .with_streaming(true)
+        .sink_parquet(
+            &output_parquet,
+            ParquetWriteOptions {
+                compression: ParquetCompression::Zstd(None),
+                row_group_size: Some(250_000),
+                ..Default::default()
+            },
+            None,
+        )
+        .context("Failed to write output parquet")?;
+    // The partitions inverted out-of-core are in the output now
+    drop(files);
 
-    let unique_cited_works = combined.height();
-    let total_citations: u32 = combined.column("citation_count")?.u32()?.sum().unwrap_or(0);
+    let stats = inverted_totals(LazyFrame::scan_parquet(output_parquet, Default::default())?)?;
 
     info!(
-        "Writing inverted output: {} unique cited works",
-        unique_cited_works
+        "Wrote inverted output: {} unique cited works",
+        stats.unique_cited_works
     );
 
-    // Write Parquet output
-    let file = File::create(output_parquet)
-        .with_context(|| format!("Failed to create output file: {:?}", output_parquet))?;
-
-    ParquetWriter::new(file)
-        .with_compression(ParquetCompression::Zstd(None))
-        .with_row_group_size(Some(250_000))
-        .finish(&mut combined)
-        .context("Failed to write output parquet")?;
-
     // Write JSONL output if requested
     if let Some(jsonl_path) = output_jsonl {
-        match output_mode {
-            OutputMode::Arxiv => write_arxiv_jsonl_output(&combined, jsonl_path)?,
-            OutputMode::Generic => write_generic_jsonl_output(&combined, jsonl_path)?,
-        }
+        let inverted = LazyFrame::scan_parquet(output_parquet, Default::default())?;
+        write_inverted_jsonl(&inverted, stats.unique_cited_works, jsonl_path, output_mode)?;
     }
 
-    let stats = InvertStats {
-        partitions_processed: partition_files.len(),
-        unique_cited_works,
-        total_citations: total_citations as usize,
-    };
-
     Ok(stats)
 }
 
-/// Write DataFrame to JSONL format for arXiv-specific output
-fn write_arxiv_jsonl_output(df: &DataFrame, path: &Path) -> Result<()> {
-    info!("Writing arXiv JSONL output: {:?}", path);
+/// Concatenate in-memory inverted partitions into one DataFrame
+fn concat_frames(frames: Vec<InvertedPartition>) -> Result<DataFrame> {
+    let dfs: Vec<DataFrame> = frames
+        .into_iter()
+        .filter_map(|part| match part {
+            InvertedPartition::Frame(df) => Some(df),
+            InvertedPartition::File(_) | InvertedPartition::Output(_) => None,
+        })
+        .collect();
+    // Concatenate in batches to avoid stack overflow from deep recursive plans
+    // Polars concat builds a tree of Union nodes; too deep causes stack overflow
+    const CONCAT_BATCH_SIZE: usize = 500;
+    if dfs.len() <= CONCAT_BATCH_SIZE {
+        // Small enough to concat directly
+        let lazy_dfs: Vec<LazyFrame> = dfs.into_iter().map(|df| df.lazy()).collect();
+        return concat(&lazy_dfs, UnionArgs::default())
+            .context("Failed to concatenate inverted partitions")?
+            .collect()
+            .context("Failed to collect combined dataframe");
+    }
+
+    // Batch concatenation to limit tree depth
+    let mut batched_dfs: Vec<DataFrame> = Vec::new();
+    for chunk in dfs.chunks(CONCAT_BATCH_SIZE) {
+        let lazy_chunk: Vec<LazyFrame> = chunk.iter().map(|df| df.clone().lazy()).collect();
+        let batch_df = concat(&lazy_chunk, UnionArgs::default())
+            .context("Failed to concatenate batch")?
+            .collect()
+            .context("Failed to collect batch")?;
+        batched_dfs.push(batch_df);
+    }
+    // Final concat of batched results
+    let lazy_batched: Vec<LazyFrame> = batched_dfs.into_iter().map(|df| df.lazy()).collect();
+    concat(&lazy_batched, UnionArgs::default())
+        .context("Failed to concatenate batched results")?
+        .collect()
+        .context("Failed to collect final result")
+}
+
+/// Rows serialized in parallel per chunk handed to the JSONL writer thread
+const JSONL_CHUNK_ROWS: usize = 10_000;
+/// Serialized chunks that may wait for the writer thread
+const JSONL_WRITE_DEPTH: usize = 4;
+/// Rows of the sorted inverted output read at a time to write its JSONL
+const JSONL_SLICE_ROWS: usize = 250_000;
+
+/// Write the JSONL of a sorted inverted output, a slice at a time
+fn write_inverted_jsonl(
+    inverted: &LazyFrame,
+    rows: usize,
+    path: &Path,
+    output_mode: OutputMode,
+) -> Result<()> {
+    match output_mode {
+        OutputMode::Arxiv => info!("Writing arXiv JSONL output: {:?}", path),
+        OutputMode::Generic => info!("Writing generic JSONL output: {:?}", path),
+    }
 
-    let file =
-        File::create(path).with_context(|| format!("Failed to create JSONL file: {:?}", path))?;
-    let mut writer = BufWriter::new(file);
+    let mut writer = ParallelJsonlWriter::create(path)?;
+    let mut offset = 0;
+    while offset < rows {
+        let slice = inverted
+            .clone()
+            .slice(offset as i64, JSONL_SLICE_ROWS as IdxSize)
+            .collect()
+            .context("Failed to read inverted output slice")?;
+        if slice.height() == 0 {
+            break;
+        }
+        match output_mode {
+            OutputMode::Arxiv => write_arxiv_jsonl_rows(&slice, &mut writer)?,
+            OutputMode::Generic => write_generic_jsonl_rows(&slice, &mut writer)?,
+        }
+        offset += slice.height();
+    }
+    writer.finish()
+}
 
+/// Write DataFrame rows as arXiv-specific JSONL records
+fn write_arxiv_jsonl_rows(df: &DataFrame, writer: &mut ParallelJsonlWriter) -> Result<()> {
     let arxiv_doi = df.column("arxiv_doi")?.str()?;
     let cited_id = df.column("cited_id")?.str()?;
+    let id_type = df.column("id_type")?.str()?;
     let reference_count = df.column("reference_count")?.u32()?;
     let citation_count = df.column("citation_count")?.u32()?;
+    let provenance_counts = provenance_count_columns(df)?;
     let cited_by = df.column("cited_by")?;
 
-    for i in 0..df.height() {
-        let doi = arxiv_doi.get(i).unwrap_or("");
-        let id = cited_id.get(i).unwrap_or("");
-        let ref_count = reference_count.get(i).unwrap_or(0);
-        let cit_count = citation_count.get(i).unwrap_or(0);
-
-        let cited_by_json = build_cited_by_json(cited_by, i)?;
-
-        let json_line = serde_json::json!({
-            "arxiv_doi": doi,
-            "arxiv_id": id,
-            "reference_count": ref_count,
-            "citation_count": cit_count,
-            "cited_by": cited_by_json
+    writer.write_rows(df.height(), |i| {
+        let mut row = serde_json::json!({
+            "arxiv_doi": arxiv_doi.get(i).unwrap_or(""),
+            "arxiv_id": cited_id.get(i).unwrap_or(""),
+            "id_type": id_type.get(i),
+            "reference_count": reference_count.get(i).unwrap_or(0),
+            "citation_count": citation_count.get(i).unwrap_or(0),
         });
-
-        writeln!(writer, "{}", json_line)?;
-    }
-
-    writer.flush()?;
-    Ok(())
+        add_provenance_counts(&mut row, &provenance_counts, i);
+        row["cited_by"] = build_cited_by_json(cited_by, i)?;
+        Ok(row)
+    })
 }
 
-/// Write DataFrame to JSONL format for generic DOI citations
-fn write_generic_jsonl_output(df: &DataFrame, path: &Path) -> Result<()> {
-    info!("Writing generic JSONL output: {:?}", path);
-
-    let file =
-        File::create(path).with_context(|| format!("Failed to create JSONL file: {:?}", path))?;
-    let mut writer = BufWriter::new(file);
-
+/// Write DataFrame rows as generic DOI citation JSONL records
+fn write_generic_jsonl_rows(df: &DataFrame, writer: &mut ParallelJsonlWriter) -> Result<()> {
     let cited_id = df.column("cited_id")?.str()?;
+    let id_type = df.column("id_type")?.str()?;
     let reference_count = df.column("reference_count")?.u32()?;
     let citation_count = df.column("citation_count")?.u32()?;
+    let provenance_counts = provenance_count_columns(df)?;
     let cited_by = df.column("cited_by")?;
 
-    for i in 0..df.height() {
-        let doi = cited_id.get(i).unwrap_or("");
-        let ref_count = reference_count.get(i).unwrap_or(0);
-        let cit_count = citation_count.get(i).unwrap_or(0);
+    writer.write_rows(df.height(), |i| {
+        let mut row = serde_json::json!({
+            "doi": cited_id.get(i).unwrap_or(""),
+            "id_type": id_type.get(i),
+            "reference_count": reference_count.get(i).unwrap_or(0),
+            "citation_count": citation_count.get(i).unwrap_or(0),
+        });
+        add_provenance_counts(&mut row, &provenance_counts, i);
+        row["cited_by"] = build_cited_by_json(cited_by, i)?;
+        Ok(row)
+    })
+}
 
-        let cited_by_json = build_cited_by_json(cited_by, i)?;
+/// The count columns of an inverted DataFrame (per provenance, then asserted), with
+/// their names
+fn provenance_count_columns(df: &DataFrame) -> Result<Vec<(&'static str, &UInt32Chunked)>> {
+    PROVENANCE_COUNT_COLUMNS
+        .iter()
+        .map(|&(_, name)| name)
+        .chain([ASSERTED_COUNT_COLUMN])
+        .map(|name| Ok((name, df.column(name)?.u32()?)))
+        .collect()
+}
 
-        let json_line = serde_json::json!({
-            "doi": doi,
-            "reference_count": ref_count,
-            "citation_count": cit_count,
-            "cited_by": cited_by_json
+/// Set the counts of row `i` on its JSON record
+fn add_provenance_counts(
+    row: &mut serde_json::Value,
+    columns: &[(&'static str, &UInt32Chunked)],
+    i: usize,
+) {
+    for (name, column) in columns {
+        row[*name] = serde_json::Value::from(column.get(i).unwrap_or(0));
+    }
+}
+
+/// JSON lines written in order by a dedicated writer thread
+///
+/// Rows are serialized in parallel a chunk at a time on the current rayon pool and
+/// handed, in order, to the writer thread, so building the next chunk overlaps with
+/// writing the previous one.
+struct ParallelJsonlWriter {
+    path: PathBuf,
+    sender: Sender<Vec<u8>>,
+    writer: thread::JoinHandle<Result<()>>,
+}
+
+impl ParallelJsonlWriter {
+    fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create JSONL file: {:?}", path))?;
+        let (sender, receiver) = bounded::<Vec<u8>>(JSONL_WRITE_DEPTH);
+
+        let writer_path = path.to_path_buf();
+        let writer = thread::spawn(move || -> Result<()> {
+            let mut writer = BufWriter::new(file);
+            for chunk in receiver {
+                writer
+                    .write_all(&chunk)
+                    .with_context(|| format!("Failed to write JSONL file: {:?}", writer_path))?;
+            }
+            writer
+                .flush()
+                .with_context(|| format!("Failed to flush JSONL file: {:?}", writer_path))
         });
 
-        writeln!(writer, "{}", json_line)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            sender,
+            writer,
+        })
+    }
+
+    /// Write `rows` JSON lines built by `build_row`, in row order
+    fn write_rows<F>(&mut self, rows: usize, build_row: F) -> Result<()>
+    where
+        F: Fn(usize) -> Result<serde_json::Value> + Sync,
+    {
+        for start in (0..rows).step_by(JSONL_CHUNK_ROWS) {
+            let end = (start + JSONL_CHUNK_ROWS).min(rows);
+            let chunk = (start..end)
+                .into_par_iter()
+                .map(|i| {
+                    let mut line = serde_json::to_vec(&build_row(i)?)?;
+                    line.push(b'\n');
+                    Ok(line)
+                })
+                .collect::<Result<Vec<Vec<u8>>>>()?
+                .concat();
+            // The writer thread stopped on an error, which finishing reports
+            if self.sender.send(chunk).is_err() {
+                break;
+            }
+        }
+        Ok(())
     }
 
-    writer.flush()?;
-    Ok(())
+    /// Wait for the writer thread to write and flush every row
+    fn finish(self) -> Result<()> {
+        drop(self.sender);
+        self.writer.join().map_err(|_| {
+            Error::internal(format!("JSONL writer thread panicked: {:?}", self.path))
+        })?
+    }
+}
+
+/// Matches from one citing work, with the work's flags and metadata
+#[derive(Default)]
+struct CitingEntry {
+    same_prefix: bool,
+    work_type: Option<String>,
+    container_title: Option<String>,
+    licenses: Option<Vec<String>>,
+    funders: Option<Vec<String>>,
+    matches: Vec<serde_json::Value>,
 }
 
 /// Build cited_by JSON array from struct column
@@ -302,13 +861,31 @@ fn build_cited_by_json(cited_by_col: &Column, row_idx: usize) -> Result<serde_js
             let raw_match_field = structs.field_by_name("raw_match")?;
             let ref_field = structs.field_by_name("reference")?;
             let provenance_field = structs.field_by_name("provenance")?;
+            let match_field_field = structs.field_by_name("match_field")?;
+            let match_start_field = structs.field_by_name("match_start")?;
+            let match_end_field = structs.field_by_name("match_end")?;
+            let context_field = structs.field_by_name("context")?;
+            let same_prefix_field = structs.field_by_name("same_prefix")?;
+            let type_field = structs.field_by_name("citing_type")?;
+            let container_field = structs.field_by_name("citing_container_title")?;
+            let licenses_field = structs.field_by_name("citing_licenses")?;
+            let funders_field = structs.field_by_name("citing_funders")?;
 
             let dois = doi_field.str()?;
             let raw_matches = raw_match_field.str()?;
             let refs = ref_field.str()?;
             let provenances = provenance_field.str()?;
-
-            let mut doi_matches: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
+            let match_fields = match_field_field.str()?;
+            let match_starts = match_start_field.u32()?;
+            let match_ends = match_end_field.u32()?;
+            let contexts = context_field.str()?;
+            let same_prefixes = same_prefix_field.bool()?;
+            let types = type_field.str()?;
+            let containers = container_field.str()?;
+            let licenses = licenses_field.list()?;
+            let funders = funders_field.list()?;
+
+            let mut doi_matches: HashMap<String, CitingEntry> = HashMap::new();
 
             for j in 0..series.len() {
                 let doi = dois.get(j).unwrap_or("").to_string();
@@ -319,34 +896,74 @@ fn build_cited_by_json(cited_by_col: &Column, row_idx: usize) -> Result<serde_js
                 let reference: serde_json::Value =
                     serde_json::from_str(ref_json_str).unwrap_or(serde_json::Value::Null);
 
-                let match_obj = serde_json::json!({
+                let mut match_obj = serde_json::json!({
                     "raw_match": raw_match,
-                    "reference": reference,
                     "provenance": provenance
                 });
+                add_reference(&mut match_obj, reference);
+                if let Some(match_field) = match_fields.get(j) {
+                    match_obj["match_field"] = serde_json::Value::String(match_field.to_string());
+                }
+                if let (Some(start), Some(end)) = (match_starts.get(j), match_ends.get(j)) {
+                    match_obj["match_start"] = serde_json::Value::from(start);
+                    match_obj["match_end"] = serde_json::Value::from(end);
+                }
+                if let Some(context) = contexts.get(j) {
+                    match_obj["context"] = serde_json::Value::String(context.to_string());
+                }
 
-                doi_matches.entry(doi).or_default().push(match_obj);
+                let entry = doi_matches.entry(doi).or_default();
+                entry.same_prefix |= same_prefixes.get(j).unwrap_or(false);
+                if entry.work_type.is_none() {
+                    entry.work_type = types.get(j).map(String::from);
+                }
+                if entry.container_title.is_none() {
+                    entry.container_title = containers.get(j).map(String::from);
+                }
+                if entry.licenses.is_none() {
+                    entry.licenses = string_list(licenses, j)?;
+                }
+                if entry.funders.is_none() {
+                    entry.funders = string_list(funders, j)?;
+                }
+                entry.matches.push(match_obj);
             }
 
             let cited_by_arr: Vec<serde_json::Value> = doi_matches
                 .into_iter()
-                .map(|(doi, matches)| {
+                .map(|(doi, entry)| {
                     // Determine overall provenance for this citing DOI (best available)
-                    let best_provenance = matches
+                    let best_provenance = entry
+                        .matches
                         .iter()
                         .filter_map(|m| m.get("provenance").and_then(|p| p.as_str()))
                         .max_by_key(|p| match *p {
-                            "publisher" => 2,
-                            "crossref" => 1,
+                            "publisher" => 3,
+                            "crossref" => 2,
+                            "structured" => 1,
                             _ => 0,
                         })
                         .unwrap_or("mined");
 
-                    serde_json::json!({
+                    let mut citing = serde_json::json!({
                         "doi": doi,
                         "provenance": best_provenance,
-                        "matches": matches
-                    })
+                        "same_prefix": entry.same_prefix,
+                    });
+                    if let Some(work_type) = entry.work_type {
+                        citing["type"] = serde_json::Value::String(work_type);
+                    }
+                    if let Some(container_title) = entry.container_title {
+                        citing["container_title"] = serde_json::Value::String(container_title);
+                    }
+                    if let Some(licenses) = entry.licenses {
+                        citing["licenses"] = serde_json::json!(licenses);
+                    }
+                    if let Some(funders) = entry.funders {
+                        citing["funders"] = serde_json::json!(funders);
+                    }
+                    citing["matches"] = serde_json::Value::Array(entry.matches);
+                    citing
                 })
                 .collect();
 
@@ -356,11 +973,35 @@ fn build_cited_by_json(cited_by_col: &Column, row_idx: usize) -> Result<serde_js
     }
 }
 
+/// Values of a list-of-strings cell, or None when it is null
+fn string_list(lists: &ListChunked, idx: usize) -> Result<Option<Vec<String>>> {
+    match lists.get_as_series(idx) {
+        Some(series) => Ok(Some(
+            series
+                .str()?
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect(),
+        )),
+        None => Ok(None),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::extract::{same_prefix, MatchField, MatchSpan, Provenance};
+    use crate::streaming::{CitingWork, ExtractedMatch, PartitionWriter};
+    use std::sync::Arc;
     use tempfile::tempdir;
 
+    /// Invert a partition and load the result, wherever it was written
+    fn invert_to_frame(path: &Path, output_mode: OutputMode) -> Result<DataFrame> {
+        let inverted = invert_single_partition(path, output_mode)?;
+        Ok(inverted.lazy()?.collect()?)
+    }
+
     fn create_test_partition(
         dir: &Path,
         name: &str,
@@ -378,6 +1019,17 @@ mod tests {
         dir: &Path,
         name: &str,
         rows: Vec<(&str, u32, &str, &str, &str, &str)>,
+    ) -> Result<()> {
+        let details = vec![(None, None); rows.len()];
+        create_test_partition_with_details(dir, name, rows, details)
+    }
+
+    /// Create a partition with per-row (match_field, context) values
+    fn create_test_partition_with_details(
+        dir: &Path,
+        name: &str,
+        rows: Vec<(&str, u32, &str, &str, &str, &str)>,
+        details: Vec<(Option<&str>, Option<&str>)>,
     ) -> Result<()> {
         let citing_dois: Vec<String> = rows.iter().map(|r| r.0.to_string()).collect();
         let ref_indices: Vec<u32> = rows.iter().map(|r| r.1).collect();
@@ -385,14 +1037,24 @@ mod tests {
         let raw_matches: Vec<String> = rows.iter().map(|r| r.3.to_string()).collect();
         let cited_ids: Vec<String> = rows.iter().map(|r| r.4.to_string()).collect();
         let provenances: Vec<String> = rows.iter().map(|r| r.5.to_string()).collect();
+        let match_fields: Vec<Option<&str>> = details.iter().map(|d| d.0).collect();
+        let contexts: Vec<Option<&str>> = details.iter().map(|d| d.1).collect();
+        let same_prefixes: Vec<bool> = rows.iter().map(|r| same_prefix(r.0, r.4)).collect();
+
+        let no_metadata: Vec<Option<&str>> = vec![None; rows.len()];
 
         let mut df = DataFrame::new(vec![
             Column::new("citing_doi".into(), &citing_dois),
+            Column::new("citing_type".into(), &no_metadata),
+            Column::new("citing_container_title".into(), &no_metadata),
             Column::new("ref_index".into(), &ref_indices),
             Column::new("ref_json".into(), &ref_jsons),
             Column::new("raw_match".into(), &raw_matches),
             Column::new("cited_id".into(), &cited_ids),
             Column::new("provenance".into(), &provenances),
+            Column::new("match_field".into(), &match_fields),
+            Column::new("context".into(), &contexts),
+            Column::new("same_prefix".into(), &same_prefixes),
         ])?;
 
         let file = File::create(dir.join(format!("{}.parquet", name)))?;
@@ -415,8 +1077,7 @@ mod tests {
         )
         .unwrap();
 
-        let df =
-            invert_single_partition(&dir.path().join("2403.parquet"), OutputMode::Arxiv).unwrap();
+        let df = invert_to_frame(&dir.path().join("2403.parquet"), OutputMode::Arxiv).unwrap();
 
         assert_eq!(df.height(), 2); // Two unique cited_ids
 
@@ -451,8 +1112,7 @@ mod tests {
         )
         .unwrap();
 
-        let df = invert_single_partition(&dir.path().join("10.1234.parquet"), OutputMode::Generic)
-            .unwrap();
+        let df = invert_to_frame(&dir.path().join("10.1234.parquet"), OutputMode::Generic).unwrap();
 
         assert_eq!(df.height(), 2); // Two unique cited_ids
 
@@ -502,8 +1162,7 @@ mod tests {
         .unwrap();
 
         let result =
-            invert_single_partition(&dir.path().join("10.5678.parquet"), OutputMode::Generic)
-                .unwrap();
+            invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
 
         // Verify we have one cited work with two citations
         assert_eq!(result.height(), 1);
@@ -531,6 +1190,136 @@ mod tests {
         assert!(prov_values.contains(&"mined".to_string()));
     }
 
+    #[test]
+    fn test_invert_partition_counts_provenances() {
+        let dir = tempdir().unwrap();
+        create_test_partition_with_provenance(
+            dir.path(),
+            "10.5678",
+            vec![
+                ("10.1234/a", 0, "{}", "10.5678/x", "10.5678/x", "publisher"),
+                ("10.1234/b", 0, "{}", "10.5678/x", "10.5678/x", "mined"),
+                ("10.1234/c", 0, "{}", "10.5678/x", "10.5678/x", "mined"),
+                // A second reference from the same work counts once
+                ("10.1234/c", 1, "{}", "10.5678/x", "10.5678/x", "mined"),
+            ],
+        )
+        .unwrap();
+
+        let df = invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
+        let count = |name: &str| df.column(name).unwrap().u32().unwrap().get(0);
+        assert_eq!(count("citation_count"), Some(3));
+        assert_eq!(count("publisher_citation_count"), Some(1));
+        assert_eq!(count("crossref_citation_count"), Some(0));
+        assert_eq!(count("structured_citation_count"), Some(0));
+        assert_eq!(count("mined_citation_count"), Some(2));
+        assert_eq!(count("asserted_citation_count"), Some(1));
+    }
+
+    #[test]
+    fn test_with_provenance_counts_upgrades_old_output() {
+        let dir = tempdir().unwrap();
+        create_test_partition_with_provenance(
+            dir.path(),
+            "10.5678",
+            vec![
+                ("10.1234/a", 0, "{}", "10.5678/x", "10.5678/x", "crossref"),
+                ("10.1234/b", 0, "{}", "10.5678/x", "10.5678/x", "mined"),
+            ],
+        )
+        .unwrap();
+        let current =
+            invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
+        let names: Vec<&str> = PROVENANCE_COUNT_COLUMNS
+            .iter()
+            .map(|(_, name)| *name)
+            .chain([ASSERTED_COUNT_COLUMN])
+            .collect();
+        let count = |df: &DataFrame, name: &str| df.column(name).unwrap().u32().unwrap().get(0);
+        let upgrade = |df: DataFrame| {
+            with_provenance_counts(df.lazy())
+                .unwrap()
+                .collect()
+                .unwrap()
+        };
+
+        let old = current.clone().drop_many(names);
+        let upgraded = upgrade(old);
+        assert_eq!(upgraded.get_column_names(), current.get_column_names());
+        assert_eq!(count(&upgraded, "crossref_citation_count"), Some(1));
+        assert_eq!(count(&upgraded, "asserted_citation_count"), Some(1));
+
+        // Outputs with the per-provenance counts only gain the asserted one
+        let without_asserted = current.clone().drop_many([ASSERTED_COUNT_COLUMN]);
+        let upgraded = upgrade(without_asserted);
+        assert_eq!(upgraded.get_column_names(), current.get_column_names());
+        assert_eq!(count(&upgraded, "asserted_citation_count"), Some(1));
+    }
+
+    #[test]
+    fn test_export_inverted_jsonl_leaves_parquet_alone() {
+        let dir = tempdir().unwrap();
+        create_test_partition_with_provenance(
+            dir.path(),
+            "10.5678",
+            vec![
+                ("10.1234/a", 0, "{}", "10.5678/x", "10.5678/x", "crossref"),
+                ("10.1234/b", 0, "{}", "10.5678/x", "10.5678/x", "mined"),
+                ("10.1234/b", 1, "{}", "10.5678/y", "10.5678/y", "mined"),
+            ],
+        )
+        .unwrap();
+        let output_parquet = dir.path().join("inverted.parquet");
+        let output_jsonl = dir.path().join("inverted.jsonl");
+        let inverted = invert_partitions(
+            dir.path(),
+            &output_parquet,
+            Some(&output_jsonl),
+            &mut Checkpoint::new("test"),
+            OutputMode::Generic,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+
+        // An output from before id_type and the count columns
+        let mut old = LazyFrame::scan_parquet(&output_parquet, Default::default())
+            .unwrap()
+            .collect()
+            .unwrap()
+            .drop_many(
+                ["id_type"]
+                    .into_iter()
+                    .chain(PROVENANCE_COUNT_COLUMNS.map(|(_, name)| name))
+                    .chain([ASSERTED_COUNT_COLUMN]),
+            );
+        let old_parquet = dir.path().join("old.parquet");
+        ParquetWriter::new(File::create(&old_parquet).unwrap())
+            .finish(&mut old)
+            .unwrap();
+        let old_bytes = fs::read(&old_parquet).unwrap();
+
+        let exported = dir.path().join("exported.jsonl");
+        let stats = export_inverted_jsonl(&old_parquet, &exported, OutputMode::Generic).unwrap();
+        assert_eq!(fs::read(&old_parquet).unwrap(), old_bytes);
+        // cited_by entries come out in no particular order
+        let records = |path: &Path| -> Vec<serde_json::Value> {
+            fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let mut record: serde_json::Value = serde_json::from_str(line).unwrap();
+                    let cited_by = record["cited_by"].as_array_mut().unwrap();
+                    cited_by.sort_by_key(|citing| citing["doi"].to_string());
+                    record
+                })
+                .collect()
+        };
+        assert_eq!(records(&exported), records(&output_jsonl));
+        assert_eq!(stats.unique_cited_works, inverted.unique_cited_works);
+        assert_eq!(stats.total_citations, inverted.total_citations);
+        assert_eq!(stats.provenance_counts, inverted.provenance_counts);
+    }
+
     #[test]
     fn test_build_cited_by_json_with_provenance() {
         let dir = tempdir().unwrap();
@@ -572,8 +1361,7 @@ mod tests {
         .unwrap();
 
         let result =
-            invert_single_partition(&dir.path().join("10.5678.parquet"), OutputMode::Generic)
-                .unwrap();
+            invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
 
         let cited_by_col = result.column("cited_by").unwrap();
         let json = build_cited_by_json(cited_by_col, 0).unwrap();
@@ -588,6 +1376,7 @@ mod tests {
             .find(|e| e["doi"] == "10.1234/a")
             .expect("Should have entry for 10.1234/a");
         assert_eq!(entry_a["provenance"], "publisher");
+        assert_eq!(entry_a["same_prefix"], false);
 
         // Entry should have one match with provenance
         let matches_a = entry_a["matches"].as_array().unwrap();
@@ -608,4 +1397,522 @@ mod tests {
             .expect("Should have entry for 10.1234/c");
         assert_eq!(entry_c["provenance"], "mined");
     }
+
+    #[test]
+    fn test_build_cited_by_json_includes_match_details() {
+        let dir = tempdir().unwrap();
+
+        create_test_partition_with_details(
+            dir.path(),
+            "10.5678",
+            vec![
+                (
+                    "10.1234/a",
+                    0,
+                    r#"{"unstructured": "Smith. doi:10.5678/cited. 2020"}"#,
+                    "10.5678/cited.",
+                    "10.5678/cited",
+                    "mined",
+                ),
+                (
+                    "10.1234/b",
+                    0,
+                    r#"{"DOI": "10.5678/cited"}"#,
+                    "10.5678/cited",
+                    "10.5678/cited",
+                    "publisher",
+                ),
+            ],
+            vec![
+                (Some("unstructured"), Some("Smith. doi:10.5678/cited. 2020")),
+                (Some("doi"), None),
+            ],
+        )
+        .unwrap();
+
+        let result =
+            invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
+        let json = build_cited_by_json(result.column("cited_by").unwrap(), 0).unwrap();
+        let arr = json.as_array().unwrap();
+
+        let entry_a = arr.iter().find(|e| e["doi"] == "10.1234/a").unwrap();
+        assert_eq!(entry_a["matches"][0]["match_field"], "unstructured");
+        assert_eq!(
+            entry_a["matches"][0]["context"],
+            "Smith. doi:10.5678/cited. 2020"
+        );
+
+        // Asserted matches carry no context
+        let entry_b = arr.iter().find(|e| e["doi"] == "10.1234/b").unwrap();
+        assert_eq!(entry_b["matches"][0]["match_field"], "doi");
+        assert!(entry_b["matches"][0].get("context").is_none());
+        // Partitions written before offsets were recorded have none
+        assert!(entry_b["matches"][0].get("match_start").is_none());
+    }
+
+    #[test]
+    fn test_build_cited_by_json_includes_reference_key() {
+        let dir = tempdir().unwrap();
+
+        create_test_partition(
+            dir.path(),
+            "10.5678",
+            vec![
+                (
+                    "10.1234/a",
+                    0,
+                    r#"{"key": "ref7", "unstructured": "Smith. doi:10.5678/cited. 2020"}"#,
+                    "10.5678/cited",
+                    "10.5678/cited",
+                ),
+                (
+                    "10.1234/b",
+                    0,
+                    r#"{"DOI": "10.5678/cited"}"#,
+                    "10.5678/cited",
+                    "10.5678/cited",
+                ),
+            ],
+        )
+        .unwrap();
+
+        let result =
+            invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
+        let json = build_cited_by_json(result.column("cited_by").unwrap(), 0).unwrap();
+        let arr = json.as_array().unwrap();
+
+        let entry_a = arr.iter().find(|e| e["doi"] == "10.1234/a").unwrap();
+        assert_eq!(entry_a["matches"][0]["reference_key"], "ref7");
+        assert_eq!(entry_a["matches"][0]["reference"]["key"], "ref7");
+
+        // A reference without a key has no reference_key
+        let entry_b = arr.iter().find(|e| e["doi"] == "10.1234/b").unwrap();
+        assert!(entry_b["matches"][0].get("reference_key").is_none());
+    }
+
+    #[test]
+    fn test_build_cited_by_json_flags_same_prefix() {
+        let dir = tempdir().unwrap();
+
+        create_test_partition(
+            dir.path(),
+            "10.5678",
+            vec![
+                ("10.1234/a", 0, "{}", "10.5678/cited", "10.5678/cited"),
+                ("10.5678/b", 0, "{}", "10.5678/cited", "10.5678/cited"),
+                ("10.5678/B.C", 1, "{}", "10.5678/cited", "10.5678/cited"),
+            ],
+        )
+        .unwrap();
+
+        let result =
+            invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
+        let json = build_cited_by_json(result.column("cited_by").unwrap(), 0).unwrap();
+        let same_prefix = |doi: &str| {
+            json.as_array()
+                .unwrap()
+                .iter()
+                .find(|e| e["doi"] == doi)
+                .unwrap()["same_prefix"]
+                .clone()
+        };
+
+        // A citation from another prefix
+        assert_eq!(same_prefix("10.1234/a"), false);
+        // Citations within one publisher's prefix, whatever the case of the suffix
+        assert_eq!(same_prefix("10.5678/b"), true);
+        assert_eq!(same_prefix("10.5678/B.C"), true);
+    }
+
+    #[test]
+    fn test_build_cited_by_json_includes_work_metadata() {
+        let dir = tempdir().unwrap();
+        let mut writer = PartitionWriter::new(dir.path(), 100).unwrap();
+        let matches = [ExtractedMatch {
+            raw_match: "10.5678/cited".to_string(),
+            cited_id: "10.5678/cited".to_string(),
+            provenance: Provenance::Mined,
+            match_field: Some(MatchField::Unstructured),
+            match_span: Some(MatchSpan { start: 7, end: 20 }),
+            context: None,
+        }];
+        let ref_json: Arc<str> = Arc::from("{}");
+
+        let item = serde_json::json!({
+            "type": "journal-article",
+            "container-title": ["Synth. J."],
+            "license": [
+                {"URL": "http://creativecommons.org/licenses/by/4.0/", "content-version": "vor"},
+                {"URL": "http://creativecommons.org/licenses/by/4.0/", "content-version": "am"},
+            ],
+            "funder": [{"DOI": "10.13039/100000001", "name": "NSF"}, {"name": "No DOI"}],
+        });
+        let article =
+            CitingWork::from_item(Arc::from("10.1234/a"), &item).with_license_funder(&item);
+        let untyped = CitingWork::from_item(Arc::from("10.1234/b"), &serde_json::json!({}));
+        writer
+            .write_extracted_ref(&article, 0, &ref_json, &matches)
+            .unwrap();
+        writer
+            .write_extracted_ref(&untyped, 0, &ref_json, &matches)
+            .unwrap();
+        writer.flush_all().unwrap();
+
+        let result =
+            invert_to_frame(&dir.path().join("10.5678.parquet"), OutputMode::Generic).unwrap();
+        let json = build_cited_by_json(result.column("cited_by").unwrap(), 0).unwrap();
+        let arr = json.as_array().unwrap();
+
+        let entry_a = arr.iter().find(|e| e["doi"] == "10.1234/a").unwrap();
+        assert_eq!(entry_a["type"], "journal-article");
+        assert_eq!(entry_a["container_title"], "Synth. J.");
+        assert_eq!(entry_a["matches"][0]["match_start"], 7);
+        assert_eq!(entry_a["matches"][0]["match_end"], 20);
+        assert_eq!(
+            entry_a["licenses"],
+            serde_json::json!(["http://creativecommons.org/licenses/by/4.0/"])
+        );
+        assert_eq!(
+            entry_a["funders"],
+            serde_json::json!(["10.13039/100000001"])
+        );
+        let entry_b = arr.iter().find(|e| e["doi"] == "10.1234/b").unwrap();
+        assert!(entry_b.get("type").is_none());
+        assert!(entry_b.get("container_title").is_none());
+        assert!(entry_b.get("licenses").is_none());
+        assert!(entry_b.get("funders").is_none());
+    }
+
+    #[test]
+    fn test_invert_partition_out_of_core_matches_in_memory() {
+        let dir = tempdir().unwrap();
+
+        create_test_partition(
+            dir.path(),
+            "10.5678",
+            vec![
+                ("10.1234/a", 0, "{}", "10.5678/x", "10.5678/x"),
+                ("10.1234/b", 0, "{}", "10.5678/x", "10.5678/x"),
+                ("10.1234/c", 0, "{}", "10.5678/x", "10.5678/x"),
+                ("10.1234/a", 1, "{}", "10.5678/y", "10.5678/y"),
+                ("10.1234/a", 2, "{}", "10.5678/y", "10.5678/y"), // duplicate pair
+                ("10.1234/b", 1, "{}", "10.5678/z", "10.5678/z"),
+                ("10.5678/w", 0, "{}", "10.5678/w", "10.5678/w"), // self-citation
+            ],
+        )
+        .unwrap();
+        let path = dir.path().join("10.5678.parquet");
+
+        let summarize = |df: DataFrame| -> Vec<(String, u32, usize)> {
+            let ids = df.column("cited_id").unwrap().str().unwrap().clone();
+            let counts = df.column("citation_count").unwrap().u32().unwrap().clone();
+            let cited_by = df.column("cited_by").unwrap().list().unwrap().clone();
+            let mut rows: Vec<_> = (0..df.height())
+                .map(|i| {
+                    (
+                        ids.get(i).unwrap().to_string(),
+                        counts.get(i).unwrap(),
+                        cited_by.get_as_series(i).unwrap().len(),
+                    )
+                })
+                .collect();
+            rows.sort();
+            rows
+        };
+
+        let in_memory = summarize(invert_to_frame(&path, OutputMode::Generic).unwrap());
+        // Slices of 2 rows force boundary adjustment and slice growth for 10.5678/x
+        let inverted = invert_partition_out_of_core(&path, OutputMode::Generic, 2).unwrap();
+        let inverted_path = inverted.0.clone();
+        assert_eq!(inverted_path, dir.path().join("10.5678.inverted"));
+        // Each slice's aggregate went to disk as its own row group, never stacked in memory
+        let mut reader = ParquetReader::new(File::open(&inverted_path).unwrap());
+        assert_eq!(reader.get_metadata().unwrap().row_groups.len(), 2);
+        let out_of_core = summarize(
+            InvertedPartition::File(inverted)
+                .lazy()
+                .unwrap()
+                .collect()
+                .unwrap(),
+        );
+
+        assert_eq!(
+            in_memory,
+            vec![
+                ("10.5678/x".to_string(), 3, 3),
+                ("10.5678/y".to_string(), 1, 1),
+                ("10.5678/z".to_string(), 1, 1),
+            ]
+        );
+        assert_eq!(out_of_core, in_memory);
+        assert!(!dir.path().join("10.5678.sorted").exists());
+        // Dropping the inverted partition removes its file
+        assert!(!inverted_path.exists());
+    }
+
+    #[test]
+    fn test_write_inverted_streams_partitions_from_disk() {
+        let dir = tempdir().unwrap();
+        create_test_partition(
+            dir.path(),
+            "10.5678",
+            vec![
+                ("10.1234/a", 0, "{}", "10.5678/x", "10.5678/x"),
+                ("10.1234/b", 0, "{}", "10.5678/x", "10.5678/x"),
+                ("10.1234/a", 1, "{}", "10.5678/y", "10.5678/y"),
+            ],
+        )
+        .unwrap();
+        create_test_partition(
+            dir.path(),
+            "10.9999",
+            vec![
+                ("10.1234/a", 0, "{}", "10.9999/p", "10.9999/p"),
+                ("10.1234/b", 0, "{}", "10.9999/p", "10.9999/p"),
+                ("10.1234/c", 0, "{}", "10.9999/p", "10.9999/p"),
+            ],
+        )
+        .unwrap();
+        let on_disk = invert_partition_out_of_core(
+            &dir.path().join("10.5678.parquet"),
+            OutputMode::Generic,
+            1,
+        )
+        .unwrap();
+        let in_memory =
+            invert_single_partition(&dir.path().join("10.9999.parquet"), OutputMode::Generic)
+                .unwrap();
+        let inverted_path = on_disk.0.clone();
+
+        let output_parquet = dir.path().join("inverted.out");
+        let output_jsonl = dir.path().join("inverted.jsonl");
+        let stats = write_inverted(
+            vec![InvertedPartition::File(on_disk), in_memory],
+            &output_parquet,
+            Some(&output_jsonl),
+            OutputMode::Generic,
+        )
+        .unwrap();
+
+        assert_eq!(stats.unique_cited_works, 3);
+        assert_eq!(stats.total_citations, 6);
+        assert_eq!(stats.provenance_counts.mined_citation_count, 6);
+        assert!(!inverted_path.exists());
+        // Sorted by citation count across in-memory and on-disk partitions
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&output_jsonl)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let rows: Vec<(&str, u64)> = lines
+            .iter()
+            .map(|row| {
+                (
+                    row["doi"].as_str().unwrap(),
+                    row["citation_count"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(rows, [("10.9999/p", 3), ("10.5678/x", 2), ("10.5678/y", 1)]);
+        assert_eq!(parquet_row_count(&output_parquet).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_parallel_jsonl_writer_keeps_row_order() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("rows.jsonl");
+        let rows = JSONL_CHUNK_ROWS * 2 + 7;
+
+        // Rows written in two calls continue one another
+        let mut writer = ParallelJsonlWriter::create(&path).unwrap();
+        writer
+            .write_rows(JSONL_CHUNK_ROWS + 3, |i| {
+                Ok(serde_json::json!({ "row": i }))
+            })
+            .unwrap();
+        writer
+            .write_rows(rows - JSONL_CHUNK_ROWS - 3, |i| {
+                Ok(serde_json::json!({ "row": JSONL_CHUNK_ROWS + 3 + i }))
+            })
+            .unwrap();
+        writer.finish().unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), rows);
+        for (i, line) in content.lines().enumerate() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["row"], i);
+        }
+
+        // A row that fails to build fails the whole write
+        let mut writer = ParallelJsonlWriter::create(&path).unwrap();
+        let result = writer.write_rows(rows, |i| {
+            if i == JSONL_CHUNK_ROWS + 1 {
+                return Err(Error::invalid("bad row"));
+            }
+            Ok(serde_json::json!({ "row": i }))
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_id_type_round_trips_to_outputs() {
+        use crate::common::{open_sink, read_columnar_records, CitationRecord};
+        use crate::extract::IdType;
+
+        let dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let mut writer = PartitionWriter::new(dir.path(), 100).unwrap();
+        let citing = CitingWork::from_item(Arc::from("10.1234/citing"), &serde_json::Value::Null);
+        let extracted = |cited_id: &str| ExtractedMatch {
+            raw_match: cited_id.to_string(),
+            cited_id: cited_id.to_string(),
+            provenance: Provenance::Mined,
+            match_field: None,
+            match_span: None,
+            context: None,
+        };
+        let matches = [
+            extracted("10.5678/x"),
+            extracted("10.48550/arxiv.2403.12345"),
+        ];
+        writer
+            .write_extracted_ref(&citing, 0, &Arc::from("{}"), &matches)
+            .unwrap();
+        writer.flush_all().unwrap();
+        let partition = dir.path().join("10.5678.parquet");
+        let id_type = scan_partition(&partition).unwrap().collect().unwrap();
+        let id_type = id_type.column("id_type").unwrap().str().unwrap().get(0);
+        assert_eq!(id_type, Some("doi"));
+
+        let output_jsonl = out.path().join("inverted.jsonl");
+        invert_partitions(
+            dir.path(),
+            &out.path().join("inverted.parquet"),
+            Some(&output_jsonl),
+            &mut Checkpoint::new("test"),
+            OutputMode::Generic,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        let records: Vec<CitationRecord> = fs::read_to_string(&output_jsonl)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert!(records
+            .iter()
+            .all(|record| record.id_type == Some(IdType::Doi)));
+
+        // Validated outputs keep the field, in columnar formats too
+        let output = out.path().join("valid.parquet");
+        let mut sink = open_sink(&output).unwrap();
+        for record in &records {
+            sink.write_record(&serde_json::to_value(record).unwrap())
+                .unwrap();
+        }
+        sink.finish().unwrap();
+        for record in read_columnar_records(&output).unwrap() {
+            assert_eq!(record["id_type"], "doi");
+        }
+    }
+
+    #[test]
+    fn test_cancelled_inversion_stops_before_writing() {
+        let dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let output_parquet = out.path().join("inverted.parquet");
+        create_test_partition(
+            dir.path(),
+            "2403",
+            vec![("10.1234/a", 0, "{}", "arXiv:2403.12345", "2403.12345")],
+        )
+        .unwrap();
+
+        let cancel = CancellationToken::from(Arc::new(std::sync::atomic::AtomicBool::new(true)));
+        let mut checkpoint = Checkpoint::new("test");
+        let err = invert_partitions(
+            dir.path(),
+            &output_parquet,
+            None,
+            &mut checkpoint,
+            OutputMode::Arxiv,
+            &cancel,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Interrupted);
+        assert!(!output_parquet.exists());
+    }
+
+    #[test]
+    fn test_failed_partition_is_isolated_and_retried() {
+        let dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let output_parquet = out.path().join("inverted.parquet");
+        let output_jsonl = out.path().join("inverted.jsonl");
+
+        create_test_partition(
+            dir.path(),
+            "2403",
+            vec![
+                ("10.1234/a", 0, "{}", "arXiv:2403.12345", "2403.12345"),
+                ("10.1234/b", 1, "{}", "arXiv:2403.12345", "2403.12345"),
+            ],
+        )
+        .unwrap();
+        let broken = dir.path().join("2404.parquet");
+        fs::write(&broken, b"not a parquet file").unwrap();
+
+        let mut checkpoint = Checkpoint::new("test");
+        let stats = invert_partitions(
+            dir.path(),
+            &output_parquet,
+            Some(&output_jsonl),
+            &mut checkpoint,
+            OutputMode::Arxiv,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        assert_eq!(stats.partitions_processed, 1);
+        assert_eq!(stats.partitions_failed, 1);
+        assert_eq!(stats.unique_cited_works, 1);
+        assert_eq!(checkpoint.failed_partitions_in(dir.path()), vec![broken]);
+
+        // Move the temp dir, repair the partition, then retry only it
+        let moved = tempdir().unwrap();
+        let moved_dir = moved.path().join(dir.path().file_name().unwrap());
+        fs::rename(dir.path(), &moved_dir).unwrap();
+        create_test_partition(
+            &moved_dir,
+            "2404",
+            vec![("10.1234/c", 0, "{}", "arXiv:2404.00001", "2404.00001")],
+        )
+        .unwrap();
+        let stats = retry_failed_partitions(
+            &moved_dir,
+            &output_parquet,
+            Some(&output_jsonl),
+            &mut checkpoint,
+            OutputMode::Arxiv,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        assert_eq!(stats.partitions_processed, 1);
+        assert_eq!(stats.partitions_failed, 0);
+        assert_eq!(stats.unique_cited_works, 2);
+        assert_eq!(stats.total_citations, 3);
+        assert_eq!(stats.provenance_counts.mined_citation_count, 3);
+        assert!(checkpoint.partitions_failed.is_empty());
+        assert!(checkpoint.is_partition_inverted("2404"));
+        // The earlier output was merged from, then removed
+        assert_eq!(fs::read_dir(out.path()).unwrap().count(), 2);
+
+        let lines = fs::read_to_string(&output_jsonl).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        let first: serde_json::Value = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+        assert_eq!(first["mined_citation_count"], 2);
+        assert_eq!(first["publisher_citation_count"], 0);
+        assert_eq!(first["asserted_citation_count"], 0);
+    }
 }
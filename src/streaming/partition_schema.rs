@@ -0,0 +1,267 @@
+use crate::error::{Context, Error, Result};
+use crate::extract::IdType;
+use polars::prelude::*;
+use polars_parquet::parquet::metadata::KeyValue;
+use std::fs::File;
+use std::path::Path;
+
+/// Schema version written to new partition files
+///
+/// Version 1 is any partition written before the version was recorded. Such files
+/// may lack the provenance, match_field, context, same_prefix, citing_type and
+/// citing_container_title columns. Version 2 lacks the match_start and match_end
+/// columns added in version 3, versions before 4 the citing_licenses and
+/// citing_funders columns, and versions before 5 the id_type column.
+pub const PARTITION_SCHEMA_VERSION: u32 = 5;
+
+/// Parquet key-value metadata key holding the partition schema version
+const SCHEMA_VERSION_KEY: &str = "partition_schema_version";
+
+/// Rows per Parquet row group in partition files
+const ROW_GROUP_SIZE: usize = 100_000;
+
+/// Columns of a current partition file, in order
+const PARTITION_COLUMNS: [&str; 16] = [
+    "citing_doi",
+    "citing_type",
+    "citing_container_title",
+    "citing_licenses",
+    "citing_funders",
+    "ref_index",
+    "ref_json",
+    "raw_match",
+    "cited_id",
+    "id_type",
+    "provenance",
+    "match_field",
+    "match_start",
+    "match_end",
+    "context",
+    "same_prefix",
+];
+
+/// Write a partition file, recording the current schema version in its metadata
+pub fn write_partition_file(df: &DataFrame, path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create partition file: {:?}", path))?;
+    let mut writer = ParquetWriter::new(file)
+        .with_compression(ParquetCompression::Zstd(None))
+        .batched(df.schema())
+        .context("Failed to start partition parquet")?;
+
+    let mut offset = 0;
+    while offset < df.height() {
+        writer
+            .write_batch(&df.slice(offset as i64, ROW_GROUP_SIZE))
+            .context("Failed to write partition parquet")?;
+        offset += ROW_GROUP_SIZE;
+    }
+
+    writer
+        .get_writer()
+        .lock()
+        .map_err(|_| Error::internal(format!("Partition writer lock poisoned: {:?}", path)))?
+        .end(Some(vec![KeyValue {
+            key: SCHEMA_VERSION_KEY.to_string(),
+            value: Some(PARTITION_SCHEMA_VERSION.to_string()),
+        }]))
+        .context("Failed to finish partition parquet")?;
+    Ok(())
+}
+
+/// Schema version of a partition file; 1 when none is recorded
+pub fn partition_schema_version(path: &Path) -> Result<u32> {
+    let file = File::open(path).with_context(|| format!("Failed to open: {:?}", path))?;
+    let mut reader = ParquetReader::new(file);
+    let metadata = reader
+        .get_metadata()
+        .with_context(|| format!("Failed to read parquet metadata: {:?}", path))?;
+
+    let recorded = metadata
+        .key_value_metadata()
+        .iter()
+        .flatten()
+        .find(|kv| kv.key == SCHEMA_VERSION_KEY)
+        .and_then(|kv| kv.value.as_deref());
+    match recorded {
+        Some(version) => version.parse().with_context(|| {
+            format!(
+                "Invalid partition schema version {:?} in {:?}",
+                version, path
+            )
+        }),
+        None => Ok(1),
+    }
+}
+
+/// Scan a partition file, upgrading older schema versions to the current columns
+///
+/// Fails on partitions written by a newer build, whose columns this one may misread.
+pub fn scan_partition(path: &Path) -> Result<LazyFrame> {
+    let version = partition_schema_version(path)?;
+    if version > PARTITION_SCHEMA_VERSION {
+        return Err(Error::invalid(format!(
+            "Partition {:?} has schema version {}, newer than this build supports ({}); \
+             rerun extraction or use a newer build",
+            path, version, PARTITION_SCHEMA_VERSION
+        )));
+    }
+
+    let lf = LazyFrame::scan_parquet(path, Default::default())
+        .with_context(|| format!("Failed to scan partition: {:?}", path))?;
+    if version == PARTITION_SCHEMA_VERSION {
+        return Ok(lf);
+    }
+    upgrade(lf).with_context(|| format!("Failed to upgrade partition: {:?}", path))
+}
+
+/// Add the columns an older partition may lack, with the values extraction would
+/// have written for a mined match without context, offsets or citing work metadata,
+/// and the identifier family its cited ID has
+fn upgrade(mut lf: LazyFrame) -> Result<LazyFrame> {
+    let schema = lf.collect_schema()?;
+    let doi_prefix = |name: &str| {
+        col(name)
+            .str()
+            .to_lowercase()
+            .str()
+            .extract(lit(r"^(10\.[^/]+)/"), 1)
+    };
+
+    let missing: Vec<Expr> = PARTITION_COLUMNS
+        .iter()
+        .filter(|name| !schema.contains(name))
+        .map(|&name| match name {
+            "provenance" => lit("mined").alias(name),
+            "same_prefix" => doi_prefix("citing_doi")
+                .eq(doi_prefix("cited_id"))
+                .fill_null(lit(false))
+                .alias(name),
+            "id_type" => id_type_of(col("cited_id")).alias(name),
+            "match_start" | "match_end" => lit(NULL).cast(DataType::UInt32).alias(name),
+            "citing_licenses" | "citing_funders" => lit(NULL)
+                .cast(DataType::List(Box::new(DataType::String)))
+                .alias(name),
+            _ => lit(NULL).cast(DataType::String).alias(name),
+        })
+        .collect();
+
+    Ok(lf.with_columns(missing).select(PARTITION_COLUMNS.map(col)))
+}
+
+/// Identifier family of cited IDs, as [`IdType::of`] tells it, for rows written
+/// before the id_type column existed
+pub(super) fn id_type_of(cited_id: Expr) -> Expr {
+    when(cited_id.str().starts_with(lit("10.")))
+        .then(lit(IdType::Doi.as_str()))
+        .otherwise(lit(IdType::Arxiv.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_version_recorded_on_write() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("10.1234.parquet");
+        let df = df!(
+            "citing_doi" => ["10.1234/a"],
+            "cited_id" => ["10.1234/b"],
+        )
+        .unwrap();
+
+        write_partition_file(&df, &path).unwrap();
+        assert_eq!(
+            partition_schema_version(&path).unwrap(),
+            PARTITION_SCHEMA_VERSION
+        );
+        let read = scan_partition(&path).unwrap().collect().unwrap();
+        assert!(read.equals(&df));
+    }
+
+    #[test]
+    fn test_upgrade_v1_partition() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("10.5678.parquet");
+        // Columns written before provenance and later fields were added
+        let mut df = df!(
+            "citing_doi" => ["10.5678/a", "10.1234/b"],
+            "ref_index" => [0u32, 1],
+            "ref_json" => ["{}", "{}"],
+            "raw_match" => ["10.5678/X", "10.5678/x"],
+            "cited_id" => ["10.5678/x", "10.5678/x"],
+        )
+        .unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        assert_eq!(partition_schema_version(&path).unwrap(), 1);
+        let upgraded = scan_partition(&path).unwrap().collect().unwrap();
+        let names: Vec<&str> = upgraded
+            .get_column_names()
+            .into_iter()
+            .map(|n| n.as_str())
+            .collect();
+        assert_eq!(names, PARTITION_COLUMNS);
+
+        let provenance = upgraded.column("provenance").unwrap().str().unwrap();
+        assert_eq!(provenance.get(0), Some("mined"));
+        let same_prefix = upgraded.column("same_prefix").unwrap().bool().unwrap();
+        assert_eq!(same_prefix.get(0), Some(true));
+        assert_eq!(same_prefix.get(1), Some(false));
+        let context = upgraded.column("context").unwrap().str().unwrap();
+        assert_eq!(context.get(0), None);
+        let match_start = upgraded.column("match_start").unwrap().u32().unwrap();
+        assert_eq!(match_start.get(0), None);
+        let licenses = upgraded.column("citing_licenses").unwrap().list().unwrap();
+        assert_eq!(licenses.get_as_series(0), None);
+    }
+
+    #[test]
+    fn test_upgrade_fills_id_type() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("mixed.parquet");
+        let mut df = df!(
+            "citing_doi" => ["10.5678/a", "10.5678/b"],
+            "cited_id" => ["10.5678/x", "2403.12345"],
+        )
+        .unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        let upgraded = scan_partition(&path).unwrap().collect().unwrap();
+        let id_type = upgraded.column("id_type").unwrap().str().unwrap();
+        assert_eq!(id_type.get(0), Some("doi"));
+        assert_eq!(id_type.get(1), Some("arxiv"));
+    }
+
+    #[test]
+    fn test_newer_version_rejected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("future.parquet");
+        let df = df!("citing_doi" => ["10.1234/a"]).unwrap();
+        let mut writer = ParquetWriter::new(File::create(&path).unwrap())
+            .batched(df.schema())
+            .unwrap();
+        writer.write_batch(&df).unwrap();
+        writer
+            .get_writer()
+            .lock()
+            .unwrap()
+            .end(Some(vec![KeyValue {
+                key: SCHEMA_VERSION_KEY.to_string(),
+                value: Some((PARTITION_SCHEMA_VERSION + 1).to_string()),
+            }]))
+            .unwrap();
+
+        let err = match scan_partition(&path) {
+            Ok(_) => panic!("a newer partition should be rejected"),
+            Err(e) => e.to_string(),
+        };
+        assert!(err.contains("newer than this build"), "{}", err);
+    }
+}
@@ -1,48 +1,200 @@
-use anyhow::{Context, Result};
+use crate::error::{Context, Error, Result};
 use log::{debug, info};
+#[cfg(feature = "parquet")]
 use polars::prelude::*;
+use serde_json::Value;
 use std::collections::HashMap;
-use std::fs::{self, File};
+use std::fs;
+#[cfg(not(feature = "parquet"))]
+use std::io::{BufWriter, Write};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use super::partition_key;
-use crate::extract::Provenance;
+use super::inversion::PARTITION_EXTENSION;
+#[cfg(feature = "parquet")]
+use super::partition_schema::{scan_partition, write_partition_file};
+use crate::extract::{same_prefix, IdType, MatchField, MatchSpan, Provenance};
+
+/// The work whose references are being extracted
+///
+/// Its DOI and metadata are shared by every row exploded from its references.
+#[derive(Debug, Clone)]
+pub struct CitingWork {
+    pub doi: Arc<str>,
+    /// Crossref work type, e.g. journal-article
+    pub work_type: Option<Arc<str>>,
+    /// First container title (journal, book or proceedings name)
+    pub container_title: Option<Arc<str>>,
+    /// License URLs, when captured (see [`CitingWork::with_license_funder`])
+    pub licenses: Option<Arc<[String]>>,
+    /// Funder DOIs, when captured
+    pub funders: Option<Arc<[String]>>,
+}
+
+impl CitingWork {
+    /// Read the type and first container-title of a Crossref work item
+    pub fn from_item(doi: Arc<str>, item: &Value) -> Self {
+        Self {
+            doi,
+            work_type: item.get("type").and_then(Value::as_str).map(Arc::from),
+            container_title: item
+                .get("container-title")
+                .and_then(Value::as_array)
+                .and_then(|titles| titles.first())
+                .and_then(Value::as_str)
+                .filter(|title| !title.is_empty())
+                .map(Arc::from),
+            licenses: None,
+            funders: None,
+        }
+    }
+
+    /// One row per match of its reference `ref_index`, sharing the work's and the
+    /// reference's allocations
+    pub fn explode<'a>(
+        &'a self,
+        ref_index: u32,
+        ref_json: &'a Arc<str>,
+        matches: &'a [ExtractedMatch],
+    ) -> impl Iterator<Item = ExplodedRow> + 'a {
+        matches.iter().map(move |m| ExplodedRow {
+            citing_doi: Arc::clone(&self.doi),
+            citing_type: self.work_type.clone(),
+            citing_container_title: self.container_title.clone(),
+            citing_licenses: self.licenses.clone(),
+            citing_funders: self.funders.clone(),
+            ref_index,
+            ref_json: Arc::clone(ref_json),
+            raw_match: m.raw_match.clone(),
+            cited_id: m.cited_id.clone(),
+            id_type: IdType::of(&m.cited_id),
+            provenance: m.provenance,
+            match_field: m.match_field,
+            match_span: m.match_span,
+            context: m.context.clone(),
+            same_prefix: same_prefix(&self.doi, &m.cited_id),
+        })
+    }
+
+    /// Also capture the work's `license` URLs and `funder` DOIs
+    ///
+    /// Each list keeps the first occurrence of every value; funders registered without
+    /// a DOI are left out. A work with none of either leaves it unset.
+    pub fn with_license_funder(mut self, item: &Value) -> Self {
+        self.licenses = distinct_field_values(item, "license", "URL");
+        self.funders = distinct_field_values(item, "funder", "DOI");
+        self
+    }
+}
+
+/// Distinct non-empty `field` values of the objects in `item[list]`, in order
+fn distinct_field_values(item: &Value, list: &str, field: &str) -> Option<Arc<[String]>> {
+    let mut values: Vec<String> = Vec::new();
+    for value in item
+        .get(list)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get(field).and_then(Value::as_str))
+    {
+        if !value.is_empty() && !values.iter().any(|v| v == value) {
+            values.push(value.to_string());
+        }
+    }
+    (!values.is_empty()).then(|| Arc::from(values))
+}
 
 /// A single extracted and exploded row ready for partitioning
+///
+/// The citing DOI and reference JSON are shared: every row exploded from the same work
+/// or reference points at one allocation instead of owning a copy.
 #[derive(Debug, Clone)]
 pub struct ExplodedRow {
-    pub citing_doi: String,
+    pub citing_doi: Arc<str>,
+    /// Citing work's type, shared like the citing DOI
+    pub citing_type: Option<Arc<str>>,
+    /// Citing work's container title, shared like the citing DOI
+    pub citing_container_title: Option<Arc<str>>,
+    /// Citing work's license URLs, shared like the citing DOI
+    pub citing_licenses: Option<Arc<[String]>>,
+    /// Citing work's funder DOIs, shared like the citing DOI
+    pub citing_funders: Option<Arc<[String]>>,
     pub ref_index: u32,
-    pub ref_json: String,
+    pub ref_json: Arc<str>,
     pub raw_match: String,
     pub cited_id: String,
+    /// Identifier family of the cited ID
+    pub id_type: IdType,
     pub provenance: Provenance,
+    /// Reference field the raw match was found in
+    pub match_field: Option<MatchField>,
+    /// Position of the raw match within its field
+    pub match_span: Option<MatchSpan>,
+    /// Text surrounding a mined match, when context capture is enabled
+    pub context: Option<String>,
+    /// Citing and cited DOIs share a prefix (publisher self-citation)
+    pub same_prefix: bool,
+}
+
+/// A single match extracted from a reference, before exploding into rows
+#[derive(Debug, Clone)]
+pub struct ExtractedMatch {
+    pub raw_match: String,
+    pub cited_id: String,
+    pub provenance: Provenance,
+    pub match_field: Option<MatchField>,
+    pub match_span: Option<MatchSpan>,
+    pub context: Option<String>,
 }
 
 /// Buffer for a single partition
 struct PartitionBuffer {
-    citing_dois: Vec<String>,
+    citing_dois: Vec<Arc<str>>,
+    citing_types: Vec<Option<Arc<str>>>,
+    citing_container_titles: Vec<Option<Arc<str>>>,
+    citing_licenses: Vec<Option<Arc<[String]>>>,
+    citing_funders: Vec<Option<Arc<[String]>>>,
     ref_indices: Vec<u32>,
-    ref_jsons: Vec<String>,
+    ref_jsons: Vec<Arc<str>>,
     raw_matches: Vec<String>,
     cited_ids: Vec<String>,
-    provenances: Vec<String>,
+    id_types: Vec<&'static str>,
+    provenances: Vec<&'static str>,
+    match_fields: Vec<Option<&'static str>>,
+    match_starts: Vec<Option<u32>>,
+    match_ends: Vec<Option<u32>>,
+    contexts: Vec<Option<String>>,
+    same_prefixes: Vec<bool>,
     file_path: PathBuf,
     rows_written: usize,
+    /// Estimated heap bytes held by the buffered rows
+    buffered_bytes: usize,
 }
 
 impl PartitionBuffer {
     fn new(partition_dir: &Path, partition_name: &str) -> Self {
-        let file_path = partition_dir.join(format!("{}.parquet", partition_name));
+        let file_path = partition_dir.join(format!("{}.{}", partition_name, PARTITION_EXTENSION));
         Self {
             citing_dois: Vec::new(),
+            citing_types: Vec::new(),
+            citing_container_titles: Vec::new(),
+            citing_licenses: Vec::new(),
+            citing_funders: Vec::new(),
             ref_indices: Vec::new(),
             ref_jsons: Vec::new(),
             raw_matches: Vec::new(),
             cited_ids: Vec::new(),
+            id_types: Vec::new(),
             provenances: Vec::new(),
+            match_fields: Vec::new(),
+            match_starts: Vec::new(),
+            match_ends: Vec::new(),
+            contexts: Vec::new(),
+            same_prefixes: Vec::new(),
             file_path,
             rows_written: 0,
+            buffered_bytes: 0,
         }
     }
 
@@ -50,43 +202,305 @@ impl PartitionBuffer {
         self.citing_dois.len()
     }
 
-    fn push(&mut self, row: ExplodedRow) {
+    /// Push a row, returning its estimated heap size in bytes
+    ///
+    /// A shared citing DOI (with the work's metadata) or reference JSON is counted only
+    /// when it differs from the previous row's, since rows from one reference arrive
+    /// consecutively.
+    fn push(&mut self, row: ExplodedRow) -> usize {
+        let citing_bytes = match new_shared_bytes(&self.citing_dois, &row.citing_doi) {
+            0 => 0,
+            doi_bytes => {
+                doi_bytes
+                    + row.citing_type.as_ref().map_or(0, |t| t.len())
+                    + row.citing_container_title.as_ref().map_or(0, |t| t.len())
+                    + shared_list_bytes(&row.citing_licenses)
+                    + shared_list_bytes(&row.citing_funders)
+            }
+        };
+        let row_bytes = estimate_row_bytes(&row)
+            + citing_bytes
+            + new_shared_bytes(&self.ref_jsons, &row.ref_json);
+        self.buffered_bytes += row_bytes;
         self.citing_dois.push(row.citing_doi);
+        self.citing_types.push(row.citing_type);
+        self.citing_container_titles
+            .push(row.citing_container_title);
+        self.citing_licenses.push(row.citing_licenses);
+        self.citing_funders.push(row.citing_funders);
         self.ref_indices.push(row.ref_index);
         self.ref_jsons.push(row.ref_json);
         self.raw_matches.push(row.raw_match);
         self.cited_ids.push(row.cited_id);
-        self.provenances.push(row.provenance.as_str().to_string());
+        self.id_types.push(row.id_type.as_str());
+        self.provenances.push(row.provenance.as_str());
+        self.match_fields
+            .push(row.match_field.map(|field| field.as_str()));
+        self.match_starts
+            .push(row.match_span.map(|span| span.start));
+        self.match_ends.push(row.match_span.map(|span| span.end));
+        self.contexts.push(row.context);
+        self.same_prefixes.push(row.same_prefix);
+        row_bytes
+    }
+
+    /// Append the buffered rows to the partition file, returning the rows written
+    ///
+    /// The combined partition is written to a `.flushing` sibling, synced and renamed
+    /// over the old file, so a kill mid-flush leaves either the old or the new file.
+    #[cfg(feature = "parquet")]
+    fn write_to_file(&self) -> Result<usize> {
+        let mut df = self.to_dataframe()?;
+        let rows_in_batch = df.height();
+
+        // Append to existing file or create new one
+        if self.file_path.exists() {
+            // Read existing, concat, and rewrite
+            // This is simpler than managing append-mode Parquet
+            let existing = scan_partition(&self.file_path)
+                .context("Failed to read existing partition file")?
+                .collect()
+                .context("Failed to collect existing partition data")?;
+
+            df = concat([existing.lazy(), df.lazy()], UnionArgs::default())
+                .context("Failed to concat dataframes")?
+                .collect()
+                .context("Failed to collect concatenated dataframe")?;
+        }
+
+        let tmp_path = self.file_path.with_extension(FLUSHING_EXTENSION);
+        write_partition_file(&df, &tmp_path)?;
+        fs::File::open(&tmp_path)
+            .and_then(|file| file.sync_all())
+            .with_context(|| format!("Failed to sync partition file: {:?}", tmp_path))?;
+        fs::rename(&tmp_path, &self.file_path)
+            .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, self.file_path))?;
+        Ok(rows_in_batch)
+    }
+
+    /// Append the buffered rows to the partition file, returning the rows written
+    ///
+    /// Without Parquet support partitions are JSONL, one object per row keyed by the
+    /// Parquet partition column names, so appending needs no rewrite. A kill mid-append
+    /// can only tear the last line, which [`recover_partition_dir`] cuts off on reopen.
+    #[cfg(not(feature = "parquet"))]
+    fn write_to_file(&self) -> Result<usize> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)
+            .with_context(|| format!("Failed to open partition file: {:?}", self.file_path))?;
+        let mut writer = BufWriter::new(file);
+        for i in 0..self.len() {
+            let row = serde_json::json!({
+                "citing_doi": &*self.citing_dois[i],
+                "citing_type": self.citing_types[i].as_deref(),
+                "citing_container_title": self.citing_container_titles[i].as_deref(),
+                "citing_licenses": self.citing_licenses[i].as_deref(),
+                "citing_funders": self.citing_funders[i].as_deref(),
+                "ref_index": self.ref_indices[i],
+                "ref_json": &*self.ref_jsons[i],
+                "raw_match": self.raw_matches[i],
+                "cited_id": self.cited_ids[i],
+                "id_type": self.id_types[i],
+                "provenance": self.provenances[i],
+                "match_field": self.match_fields[i],
+                "match_start": self.match_starts[i],
+                "match_end": self.match_ends[i],
+                "context": self.contexts[i],
+                "same_prefix": self.same_prefixes[i],
+            });
+            serde_json::to_writer(&mut writer, &row)?;
+            writer.write_all(b"\n")?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|file| file.sync_data())
+            .with_context(|| format!("Failed to write partition file: {:?}", self.file_path))?;
+        Ok(self.len())
     }
 
+    #[cfg(feature = "parquet")]
     fn to_dataframe(&self) -> Result<DataFrame> {
         DataFrame::new(vec![
-            Column::new("citing_doi".into(), &self.citing_dois),
+            shared_str_column("citing_doi", &self.citing_dois),
+            shared_opt_str_column("citing_type", &self.citing_types),
+            shared_opt_str_column("citing_container_title", &self.citing_container_titles),
+            shared_opt_list_column("citing_licenses", &self.citing_licenses),
+            shared_opt_list_column("citing_funders", &self.citing_funders),
             Column::new("ref_index".into(), &self.ref_indices),
-            Column::new("ref_json".into(), &self.ref_jsons),
+            shared_str_column("ref_json", &self.ref_jsons),
             Column::new("raw_match".into(), &self.raw_matches),
             Column::new("cited_id".into(), &self.cited_ids),
+            Column::new("id_type".into(), &self.id_types),
             Column::new("provenance".into(), &self.provenances),
+            Column::new("match_field".into(), &self.match_fields),
+            Column::new("match_start".into(), &self.match_starts),
+            Column::new("match_end".into(), &self.match_ends),
+            Column::new("context".into(), &self.contexts),
+            Column::new("same_prefix".into(), &self.same_prefixes),
         ])
-        .map_err(|e| anyhow::anyhow!("Failed to create DataFrame: {}", e))
+        .context("Failed to create DataFrame")
     }
 
     fn clear(&mut self) {
         self.citing_dois.clear();
+        self.citing_types.clear();
+        self.citing_container_titles.clear();
+        self.citing_licenses.clear();
+        self.citing_funders.clear();
         self.ref_indices.clear();
         self.ref_jsons.clear();
         self.raw_matches.clear();
         self.cited_ids.clear();
+        self.id_types.clear();
         self.provenances.clear();
+        self.match_fields.clear();
+        self.match_starts.clear();
+        self.match_ends.clear();
+        self.contexts.clear();
+        self.same_prefixes.clear();
+        self.buffered_bytes = 0;
     }
 }
 
+#[cfg(feature = "parquet")]
+/// Build a string column from shared strings
+fn shared_str_column(name: &str, values: &[Arc<str>]) -> Column {
+    StringChunked::from_iter_values(name.into(), values.iter().map(|v| v.as_ref())).into_column()
+}
+
+#[cfg(feature = "parquet")]
+/// Build a nullable string column from optional shared strings
+fn shared_opt_str_column(name: &str, values: &[Option<Arc<str>>]) -> Column {
+    StringChunked::from_iter_options(name.into(), values.iter().map(|v| v.as_deref())).into_column()
+}
+
+#[cfg(feature = "parquet")]
+/// Build a nullable list-of-strings column from optional shared string lists
+fn shared_opt_list_column(name: &str, values: &[Option<Arc<[String]>>]) -> Column {
+    let mut builder = ListStringChunkedBuilder::new(name.into(), values.len(), values.len());
+    for value in values {
+        match value {
+            Some(list) => builder.append_values_iter(list.iter().map(String::as_str)),
+            None => builder.append_null(),
+        }
+    }
+    builder.finish().into_column()
+}
+
+/// Payload bytes of a shared string list
+fn shared_list_bytes(values: &Option<Arc<[String]>>) -> usize {
+    values
+        .as_ref()
+        .map_or(0, |list| list.iter().map(String::len).sum())
+}
+
+/// Payload bytes of a shared string, or 0 if it is the column's last value
+fn new_shared_bytes(column: &[Arc<str>], value: &Arc<str>) -> usize {
+    match column.last() {
+        Some(last) if Arc::ptr_eq(last, value) => 0,
+        _ => value.len(),
+    }
+}
+
+/// Estimate the heap footprint of a buffered row's owned strings plus per-column overhead
+///
+/// Shared citing DOI and reference JSON payloads are accounted for separately.
+fn estimate_row_bytes(row: &ExplodedRow) -> usize {
+    const PER_ROW_OVERHEAD: usize = 2 * std::mem::size_of::<Arc<str>>()
+        + 2 * std::mem::size_of::<Option<Arc<str>>>()
+        + 2 * std::mem::size_of::<Option<Arc<[String]>>>()
+        + 3 * std::mem::size_of::<String>()
+        + 2 * std::mem::size_of::<Option<&'static str>>()
+        + std::mem::size_of::<&'static str>()
+        + 2 * std::mem::size_of::<Option<u32>>()
+        + std::mem::size_of::<u32>()
+        + std::mem::size_of::<bool>();
+    row.raw_match.len()
+        + row.cited_id.len()
+        + row.context.as_ref().map_or(0, |c| c.len())
+        + PER_ROW_OVERHEAD
+}
+
+/// Extension of a partition file while a flush rewrites it
+const FLUSHING_EXTENSION: &str = "flushing";
+
+/// Undo what an interrupted flush left in a partition directory, returning how many
+/// files were removed or repaired
+///
+/// Half-written `.flushing` files are removed; the partition they were replacing is
+/// still intact. JSONL partitions whose last line is torn are truncated after their
+/// last complete line.
+pub fn recover_partition_dir(partition_dir: &Path) -> Result<usize> {
+    let entries = fs::read_dir(partition_dir)
+        .with_context(|| format!("Failed to read partition directory: {:?}", partition_dir))?;
+    let mut repaired = 0;
+    for entry in entries {
+        let path = entry?.path();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(FLUSHING_EXTENSION) => {
+                fs::remove_file(&path).with_context(|| format!("Failed to remove: {:?}", path))?;
+                repaired += 1;
+            }
+            Some(PARTITION_EXTENSION) if cfg!(not(feature = "parquet")) => {
+                repaired += usize::from(truncate_torn_line(&path)?);
+            }
+            _ => {}
+        }
+    }
+    Ok(repaired)
+}
+
+/// Cut a JSONL file back to its last complete line, returning whether it was torn
+///
+/// Reads backwards from the end in blocks, so only the torn tail is read.
+fn truncate_torn_line(path: &Path) -> Result<bool> {
+    const BLOCK: u64 = 64 * 1024;
+
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open partition file: {:?}", path))?;
+    let len = file.metadata()?.len();
+    let mut end = len;
+    let mut block = Vec::new();
+    let keep = loop {
+        if end == 0 {
+            break 0;
+        }
+        let start = end.saturating_sub(BLOCK);
+        block.resize((end - start) as usize, 0);
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+        if let Some(newline) = memchr::memrchr(b'\n', &block) {
+            break start + newline as u64 + 1;
+        }
+        end = start;
+    };
+    if keep == len {
+        return Ok(false);
+    }
+    file.set_len(keep)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Failed to truncate torn partition file: {:?}", path))?;
+    Ok(true)
+}
+
 /// Manages writing extracted rows to partitioned Parquet files
 pub struct PartitionWriter {
     partition_dir: PathBuf,
     buffers: HashMap<String, PartitionBuffer>,
     flush_threshold: usize,
     total_rows_written: usize,
+    /// Estimated bytes currently held across all partition buffers
+    buffered_bytes: usize,
+    /// Rows currently held across all partition buffers
+    buffered_rows: usize,
+    /// Optional cap on buffered bytes; exceeding it flushes the largest partitions
+    max_buffered_bytes: Option<usize>,
 }
 
 impl PartitionWriter {
@@ -99,60 +513,97 @@ impl PartitionWriter {
         fs::create_dir_all(partition_dir).with_context(|| {
             format!("Failed to create partition directory: {:?}", partition_dir)
         })?;
+        let repaired = recover_partition_dir(partition_dir)?;
+        if repaired > 0 {
+            info!(
+                "Repaired {} partition files left by an interrupted flush in {:?}",
+                repaired, partition_dir
+            );
+        }
 
         Ok(Self {
             partition_dir: partition_dir.to_path_buf(),
             buffers: HashMap::new(),
             flush_threshold,
             total_rows_written: 0,
+            buffered_bytes: 0,
+            buffered_rows: 0,
+            max_buffered_bytes: None,
         })
     }
 
+    /// Cap the total bytes held in partition buffers
+    ///
+    /// When the estimated buffered size exceeds the cap, the largest partitions are
+    /// flushed until usage drops below half of it.
+    pub fn with_memory_budget(mut self, max_buffered_bytes: usize) -> Self {
+        self.max_buffered_bytes = Some(max_buffered_bytes);
+        self
+    }
+
     /// Write an exploded row to the appropriate partition
     pub fn write(&mut self, row: ExplodedRow) -> Result<()> {
-        let partition = partition_key(&row.cited_id);
+        let partition = row.id_type.partition_key(&row.cited_id);
 
         let buffer = self
             .buffers
             .entry(partition.clone())
             .or_insert_with(|| PartitionBuffer::new(&self.partition_dir, &partition));
 
-        buffer.push(row);
+        self.buffered_bytes += buffer.push(row);
+        self.buffered_rows += 1;
 
         if buffer.len() >= self.flush_threshold {
             self.flush_partition(&partition)?;
         }
 
+        if let Some(max_bytes) = self.max_buffered_bytes {
+            if self.buffered_bytes > max_bytes {
+                self.flush_largest_partitions(max_bytes / 2)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush the largest partition buffers until buffered bytes fall to `target_bytes`
+    fn flush_largest_partitions(&mut self, target_bytes: usize) -> Result<()> {
+        debug!(
+            "Partition buffers hold {} bytes, over budget; flushing largest partitions",
+            self.buffered_bytes
+        );
+
+        let mut by_size: Vec<(String, usize)> = self
+            .buffers
+            .iter()
+            .map(|(name, buffer)| (name.clone(), buffer.buffered_bytes))
+            .collect();
+        by_size.sort_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+
+        for (partition, _) in by_size {
+            if self.buffered_bytes <= target_bytes {
+                break;
+            }
+            self.flush_partition(&partition)?;
+        }
+
         Ok(())
     }
 
     /// Write multiple rows from a reference extraction (handles exploding)
+    ///
+    /// All rows share the caller's citing work and reference JSON allocations.
     pub fn write_extracted_ref(
         &mut self,
-        citing_doi: &str,
+        citing: &CitingWork,
         ref_index: u32,
-        ref_json: &str,
-        raw_matches: &[String],
-        cited_ids: &[String],
-        provenances: &[Provenance],
+        ref_json: &Arc<str>,
+        matches: &[ExtractedMatch],
     ) -> Result<usize> {
-        let mut written = 0;
-        for ((raw_match, cited_id), provenance) in raw_matches
-            .iter()
-            .zip(cited_ids.iter())
-            .zip(provenances.iter())
-        {
-            self.write(ExplodedRow {
-                citing_doi: citing_doi.to_string(),
-                ref_index,
-                ref_json: ref_json.to_string(),
-                raw_match: raw_match.clone(),
-                cited_id: cited_id.clone(),
-                provenance: *provenance,
-            })?;
-            written += 1;
+        for row in citing.explode(ref_index, ref_json, matches) {
+            self.write(row)?;
         }
-        Ok(written)
+        Ok(matches.len())
     }
 
     /// Flush a specific partition to disk
@@ -160,41 +611,18 @@ impl PartitionWriter {
         let buffer = self
             .buffers
             .get_mut(partition)
-            .ok_or_else(|| anyhow::anyhow!("Partition {} not found", partition))?;
+            .ok_or_else(|| Error::invalid(format!("Partition {} not found", partition)))?;
 
         if buffer.len() == 0 {
             return Ok(());
         }
 
-        let mut df = buffer.to_dataframe()?;
-        let rows_in_batch = df.height();
-
-        // Append to existing file or create new one
-        if buffer.file_path.exists() {
-            // Read existing, concat, and rewrite
-            // This is simpler than managing append-mode Parquet
-            let existing = LazyFrame::scan_parquet(&buffer.file_path, Default::default())
-                .context("Failed to read existing partition file")?
-                .collect()
-                .context("Failed to collect existing partition data")?;
-
-            df = concat([existing.lazy(), df.lazy()], UnionArgs::default())
-                .context("Failed to concat dataframes")?
-                .collect()
-                .context("Failed to collect concatenated dataframe")?;
-        }
-
-        let file = File::create(&buffer.file_path)
-            .with_context(|| format!("Failed to create partition file: {:?}", buffer.file_path))?;
-
-        ParquetWriter::new(file)
-            .with_compression(ParquetCompression::Zstd(None))
-            .with_row_group_size(Some(100_000))
-            .finish(&mut df)
-            .context("Failed to write partition parquet")?;
+        let rows_in_batch = buffer.write_to_file()?;
 
         buffer.rows_written += rows_in_batch;
         self.total_rows_written += rows_in_batch;
+        self.buffered_bytes = self.buffered_bytes.saturating_sub(buffer.buffered_bytes);
+        self.buffered_rows -= rows_in_batch;
         buffer.clear();
 
         debug!(
@@ -218,11 +646,26 @@ impl PartitionWriter {
         Ok(())
     }
 
+    /// Rows written to partition files so far
+    pub fn rows_written(&self) -> usize {
+        self.total_rows_written
+    }
+
     /// Get count of unique partitions
-    #[allow(dead_code)]
     pub fn partition_count(&self) -> usize {
         self.buffers.len()
     }
+
+    /// Estimated bytes currently held in partition buffers
+    #[cfg(test)]
+    fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    /// Rows currently held in partition buffers
+    pub fn buffered_rows(&self) -> usize {
+        self.buffered_rows
+    }
 }
 
 #[cfg(test)]
@@ -237,18 +680,30 @@ mod tests {
 
         writer
             .write(ExplodedRow {
-                citing_doi: "10.1234/test".to_string(),
+                citing_doi: Arc::from("10.1234/test"),
+                citing_type: None,
+                citing_container_title: None,
+                citing_licenses: None,
+                citing_funders: None,
                 ref_index: 0,
-                ref_json: "{}".to_string(),
+                ref_json: Arc::from("{}"),
                 raw_match: "arXiv:2403.12345".to_string(),
                 cited_id: "2403.12345".to_string(),
+                id_type: IdType::Arxiv,
                 provenance: Provenance::Mined,
+                match_field: None,
+                match_span: None,
+                context: None,
+                same_prefix: false,
             })
             .unwrap();
 
         writer.flush_all().unwrap();
 
-        assert!(dir.path().join("2403.parquet").exists());
+        assert!(dir
+            .path()
+            .join(format!("2403.{}", PARTITION_EXTENSION))
+            .exists());
     }
 
     #[test]
@@ -259,70 +714,232 @@ mod tests {
         // Modern format
         writer
             .write(ExplodedRow {
-                citing_doi: "10.1234/a".to_string(),
+                citing_doi: Arc::from("10.1234/a"),
+                citing_type: None,
+                citing_container_title: None,
+                citing_licenses: None,
+                citing_funders: None,
                 ref_index: 0,
-                ref_json: "{}".to_string(),
+                ref_json: Arc::from("{}"),
                 raw_match: "arXiv:2403.12345".to_string(),
                 cited_id: "2403.12345".to_string(),
+                id_type: IdType::Arxiv,
                 provenance: Provenance::Mined,
+                match_field: None,
+                match_span: None,
+                context: None,
+                same_prefix: false,
             })
             .unwrap();
 
         // Old format
         writer
             .write(ExplodedRow {
-                citing_doi: "10.1234/b".to_string(),
+                citing_doi: Arc::from("10.1234/b"),
+                citing_type: None,
+                citing_container_title: None,
+                citing_licenses: None,
+                citing_funders: None,
                 ref_index: 1,
-                ref_json: "{}".to_string(),
+                ref_json: Arc::from("{}"),
                 raw_match: "arXiv:hep-ph/9901234".to_string(),
                 cited_id: "hep-ph/9901234".to_string(),
+                id_type: IdType::Arxiv,
                 provenance: Provenance::Mined,
+                match_field: None,
+                match_span: None,
+                context: None,
+                same_prefix: false,
             })
             .unwrap();
 
+        assert_eq!(writer.buffered_rows(), 2);
         writer.flush_all().unwrap();
 
-        assert!(dir.path().join("2403.parquet").exists());
-        assert!(dir.path().join("hep-.parquet").exists());
+        assert!(dir
+            .path()
+            .join(format!("2403.{}", PARTITION_EXTENSION))
+            .exists());
+        assert!(dir
+            .path()
+            .join(format!("hep-.{}", PARTITION_EXTENSION))
+            .exists());
         assert_eq!(writer.partition_count(), 2);
+        assert_eq!(writer.buffered_rows(), 0);
     }
 
     #[test]
+    #[cfg(feature = "parquet")]
     fn test_write_extracted_ref() {
         let dir = tempdir().unwrap();
         let mut writer = PartitionWriter::new(dir.path(), 100).unwrap();
 
+        let citing = CitingWork::from_item(
+            Arc::from("10.1234/test"),
+            &serde_json::json!({"type": "journal-article", "container-title": ["Synth. J."]}),
+        );
         let written = writer
             .write_extracted_ref(
-                "10.1234/test",
+                &citing,
                 0,
-                "{}",
+                &Arc::from("{}"),
                 &[
-                    "arXiv:2403.12345".to_string(),
-                    "arXiv:2403.67890".to_string(),
+                    ExtractedMatch {
+                        raw_match: "arXiv:2403.12345".to_string(),
+                        cited_id: "2403.12345".to_string(),
+                        provenance: Provenance::Mined,
+                        match_field: Some(MatchField::Unstructured),
+                        match_span: Some(MatchSpan { start: 4, end: 20 }),
+                        context: Some("see arXiv:2403.12345".to_string()),
+                    },
+                    ExtractedMatch {
+                        raw_match: "arXiv:2403.67890".to_string(),
+                        cited_id: "2403.67890".to_string(),
+                        provenance: Provenance::Mined,
+                        match_field: Some(MatchField::Url),
+                        match_span: None,
+                        context: None,
+                    },
                 ],
-                &["2403.12345".to_string(), "2403.67890".to_string()],
-                &[Provenance::Mined, Provenance::Mined],
             )
             .unwrap();
 
         assert_eq!(written, 2);
         writer.flush_all().unwrap();
+
+        let df = LazyFrame::scan_parquet(dir.path().join("2403.parquet"), Default::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        let contexts = df.column("context").unwrap().str().unwrap();
+        assert_eq!(contexts.get(0), Some("see arXiv:2403.12345"));
+        assert_eq!(contexts.get(1), None);
+        let match_fields = df.column("match_field").unwrap().str().unwrap();
+        assert_eq!(match_fields.get(0), Some("unstructured"));
+        assert_eq!(match_fields.get(1), Some("url"));
+        let starts = df.column("match_start").unwrap().u32().unwrap();
+        let ends = df.column("match_end").unwrap().u32().unwrap();
+        assert_eq!((starts.get(0), ends.get(0)), (Some(4), Some(20)));
+        assert_eq!((starts.get(1), ends.get(1)), (None, None));
+        let same_prefix = df.column("same_prefix").unwrap().bool().unwrap();
+        assert_eq!(same_prefix.get(0), Some(false));
+        let types = df.column("citing_type").unwrap().str().unwrap();
+        assert_eq!(types.get(1), Some("journal-article"));
+        let containers = df.column("citing_container_title").unwrap().str().unwrap();
+        assert_eq!(containers.get(1), Some("Synth. J."));
+    }
+
+    #[test]
+    fn test_partition_writer_memory_budget_flushes() {
+        let dir = tempdir().unwrap();
+        // Row threshold never reached; only the byte budget triggers flushes
+        let mut writer = PartitionWriter::new(dir.path(), 1_000_000)
+            .unwrap()
+            .with_memory_budget(4096);
+
+        for i in 0..100 {
+            writer
+                .write(ExplodedRow {
+                    citing_doi: Arc::from(format!("10.1234/citing{}", i)),
+                    citing_type: None,
+                    citing_container_title: None,
+                    citing_licenses: None,
+                    citing_funders: None,
+                    ref_index: i,
+                    ref_json: Arc::from("{}"),
+                    raw_match: format!("10.5678/cited{}", i),
+                    cited_id: format!("10.5678/cited{}", i),
+                    id_type: IdType::Doi,
+                    provenance: Provenance::Mined,
+                    match_field: None,
+                    match_span: None,
+                    context: None,
+                    same_prefix: false,
+                })
+                .unwrap();
+            assert!(writer.buffered_bytes() <= 4096);
+        }
+
+        assert!(dir
+            .path()
+            .join(format!("10.5678.{}", PARTITION_EXTENSION))
+            .exists());
+    }
+
+    #[test]
+    fn test_partition_writer_recovers_interrupted_flush() {
+        let dir = tempdir().unwrap();
+        let row = |i: u32| ExplodedRow {
+            citing_doi: Arc::from(format!("10.1234/citing{}", i)),
+            citing_type: None,
+            citing_container_title: None,
+            citing_licenses: None,
+            citing_funders: None,
+            ref_index: i,
+            ref_json: Arc::from("{}"),
+            raw_match: "arXiv:2403.12345".to_string(),
+            cited_id: "2403.12345".to_string(),
+            id_type: IdType::Arxiv,
+            provenance: Provenance::Mined,
+            match_field: None,
+            match_span: None,
+            context: None,
+            same_prefix: false,
+        };
+        let path = dir.path().join(format!("2403.{}", PARTITION_EXTENSION));
+        let stale = dir.path().join(format!("2403.{}", FLUSHING_EXTENSION));
+
+        let mut writer = PartitionWriter::new(dir.path(), 10).unwrap();
+        writer.write(row(0)).unwrap();
+        writer.flush_all().unwrap();
+        assert!(!stale.exists());
+
+        // A flush killed midway leaves a half-written sibling and, for JSONL, a torn line
+        fs::write(&stale, b"PAR1 partial").unwrap();
+        if cfg!(not(feature = "parquet")) {
+            let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+            std::io::Write::write_all(&mut file, b"{\"citing_doi\": \"10.12").unwrap();
+        }
+
+        let mut writer = PartitionWriter::new(dir.path(), 10).unwrap();
+        assert!(!stale.exists());
+        writer.write(row(1)).unwrap();
+        writer.flush_all().unwrap();
+
+        #[cfg(feature = "parquet")]
+        let rows = scan_partition(&path).unwrap().collect().unwrap().height();
+        #[cfg(not(feature = "parquet"))]
+        let rows = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .filter(|line| serde_json::from_str::<Value>(line).is_ok())
+            .count();
+        assert_eq!(rows, 2);
     }
 
     #[test]
+    #[cfg(feature = "parquet")]
     fn test_partition_writer_with_provenance() {
         let dir = tempdir().unwrap();
         let mut writer = PartitionWriter::new(dir.path(), 10).unwrap();
 
         writer
             .write(ExplodedRow {
-                citing_doi: "10.1234/test".to_string(),
+                citing_doi: Arc::from("10.1234/test"),
+                citing_type: None,
+                citing_container_title: None,
+                citing_licenses: None,
+                citing_funders: None,
                 ref_index: 0,
-                ref_json: "{}".to_string(),
+                ref_json: Arc::from("{}"),
                 raw_match: "10.5678/cited".to_string(),
                 cited_id: "10.5678/cited".to_string(),
-                provenance: Provenance::Publisher,
+                id_type: IdType::Doi,
+                provenance: Provenance::PublisherAsserted,
+                match_field: None,
+                match_span: None,
+                context: None,
+                same_prefix: false,
             })
             .unwrap();
 
@@ -339,4 +956,41 @@ mod tests {
         let prov = df.column("provenance").unwrap().str().unwrap();
         assert_eq!(prov.get(0).unwrap(), "publisher");
     }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_shared_strings_counted_once() {
+        let dir = tempdir().unwrap();
+        let mut writer = PartitionWriter::new(dir.path(), 1_000).unwrap();
+
+        let citing = CitingWork::from_item(Arc::from("10.1234/citing"), &Value::Null);
+        let ref_json: Arc<str> = Arc::from("x".repeat(1000));
+        let matches: Vec<ExtractedMatch> = (0..10)
+            .map(|i| ExtractedMatch {
+                raw_match: format!("10.5678/{}", i),
+                cited_id: format!("10.5678/{}", i),
+                provenance: Provenance::Mined,
+                match_field: None,
+                match_span: None,
+                context: None,
+            })
+            .collect();
+
+        writer
+            .write_extracted_ref(&citing, 0, &ref_json, &matches)
+            .unwrap();
+
+        // One shared 1000-byte reference, not ten copies
+        assert!(writer.buffered_bytes() < 5 * 1000);
+        assert_eq!(Arc::strong_count(&ref_json), 11);
+
+        writer.flush_all().unwrap();
+        let df = LazyFrame::scan_parquet(dir.path().join("10.5678.parquet"), Default::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        assert_eq!(df.height(), 10);
+        let citing = df.column("citing_doi").unwrap().str().unwrap();
+        assert!(citing.into_iter().all(|doi| doi == Some("10.1234/citing")));
+    }
 }
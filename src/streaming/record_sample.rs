@@ -0,0 +1,301 @@
+use crate::common::{open_sink, SplitMix64};
+use crate::error::{Context, Result};
+use crate::extract::doi_prefix;
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::graph_export::open_jsonl;
+
+/// How records are grouped before sampling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleStrata {
+    /// DOI prefix of the cited work (`10.48550` for arXiv records)
+    Prefix,
+    /// Power-of-ten bucket of `citation_count`: 0, 1, 2-9, 10-99, ...
+    CitationCount,
+}
+
+impl FromStr for SampleStrata {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "prefix" => Ok(SampleStrata::Prefix),
+            "citation-count" | "citation_count" => Ok(SampleStrata::CitationCount),
+            _ => Err(format!(
+                "Invalid strata: {}. Valid options: prefix, citation-count",
+                s
+            )),
+        }
+    }
+}
+
+/// What to sample
+#[derive(Debug, Clone)]
+pub struct SampleOptions {
+    /// Records to keep, in each stratum when stratifying
+    pub size: usize,
+    pub strata: Option<SampleStrata>,
+    /// Same seed and inputs, same sample
+    pub seed: u64,
+}
+
+/// Statistics from sampling
+#[derive(Debug, Clone, Default)]
+pub struct SampleStats {
+    pub records_read: usize,
+    pub records_sampled: usize,
+    /// Records read and sampled per stratum, in output order
+    pub strata: Vec<(String, usize, usize)>,
+}
+
+/// Fields a record is stratified by
+#[derive(Deserialize)]
+struct StratumFields {
+    #[serde(default, alias = "arxiv_doi")]
+    doi: Option<String>,
+    #[serde(default)]
+    citation_count: Option<u64>,
+}
+
+/// Stratum a record falls in: a sort key and a label
+///
+/// Prefixes sort by name; count buckets by their lower bound. Records missing the field
+/// go to an `unknown` stratum, last.
+type Stratum = (u64, String);
+
+/// Uniform sample of records from one stratum (reservoir sampling, algorithm R)
+struct Reservoir {
+    seen: usize,
+    /// Input position and line of each kept record
+    kept: Vec<(usize, String)>,
+}
+
+/// Draw a uniform random sample of records from JSONL files (plain or compressed)
+///
+/// One pass with reservoir sampling, so memory holds only the sample, however large
+/// the inputs. With `strata`, up to `size` records are kept from each stratum. Sampled
+/// records are written unchanged through [`open_sink`], in input order within each
+/// stratum, strata in order.
+pub fn sample_records(
+    inputs: &[PathBuf],
+    output: &Path,
+    options: &SampleOptions,
+) -> Result<SampleStats> {
+    let mut rng = SplitMix64::new(options.seed);
+    let mut reservoirs: BTreeMap<Stratum, Reservoir> = BTreeMap::new();
+    let mut stats = SampleStats::default();
+
+    for path in inputs {
+        let reader = open_jsonl(path)?;
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read: {:?}", path))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let stratum = match options.strata {
+                Some(strata) => {
+                    let fields: StratumFields = serde_json::from_str(&line).with_context(|| {
+                        format!("Failed to parse {:?} line {}", path, line_number + 1)
+                    })?;
+                    stratum_of(strata, &fields)
+                }
+                None => (0, String::new()),
+            };
+
+            let position = stats.records_read;
+            stats.records_read += 1;
+            let reservoir = reservoirs.entry(stratum).or_insert_with(|| Reservoir {
+                seen: 0,
+                kept: Vec::new(),
+            });
+            reservoir.seen += 1;
+            if reservoir.kept.len() < options.size {
+                reservoir.kept.push((position, line));
+            } else {
+                let slot = rng.below(reservoir.seen as u64) as usize;
+                if slot < options.size {
+                    reservoir.kept[slot] = (position, line);
+                }
+            }
+        }
+    }
+
+    let mut sink = open_sink(output)?;
+    for ((_, label), mut reservoir) in reservoirs {
+        reservoir.kept.sort_by_key(|(position, _)| *position);
+        for (_, line) in &reservoir.kept {
+            let record: Value = serde_json::from_str(line)?;
+            sink.write_record(&record)?;
+        }
+        stats.records_sampled += reservoir.kept.len();
+        if options.strata.is_some() {
+            debug!(
+                "  {}: {} of {}",
+                label,
+                reservoir.kept.len(),
+                reservoir.seen
+            );
+        }
+        stats
+            .strata
+            .push((label, reservoir.seen, reservoir.kept.len()));
+    }
+    sink.finish()?;
+
+    info!(
+        "Sampled {} of {} records{}",
+        stats.records_sampled,
+        stats.records_read,
+        match options.strata {
+            Some(_) => format!(" from {} strata", stats.strata.len()),
+            None => String::new(),
+        }
+    );
+    Ok(stats)
+}
+
+fn stratum_of(strata: SampleStrata, fields: &StratumFields) -> Stratum {
+    let unknown = (u64::MAX, "unknown".to_string());
+    match strata {
+        SampleStrata::Prefix => fields
+            .doi
+            .as_deref()
+            .and_then(doi_prefix)
+            .map_or(unknown, |prefix| (0, prefix)),
+        SampleStrata::CitationCount => fields.citation_count.map_or(unknown, count_bucket),
+    }
+}
+
+/// Power-of-ten bucket of a citation count, 1 on its own
+fn count_bucket(count: u64) -> Stratum {
+    match count {
+        0 | 1 => (count, count.to_string()),
+        2..=9 => (2, "2-9".to_string()),
+        _ => {
+            let low = 10u64.pow(count.ilog10());
+            (low, format!("{}-{}", low, low.saturating_mul(10) - 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_records(path: &Path, count: usize) {
+        let mut file = File::create(path).unwrap();
+        for i in 0..count {
+            let record = json!({
+                "doi": format!("10.{}/{}", 1000 + i % 3, i),
+                "citation_count": i,
+                "cited_by": [],
+            });
+            writeln!(file, "{}", record).unwrap();
+        }
+    }
+
+    fn read_dois(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["doi"].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_records() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("valid.jsonl");
+        write_records(&input, 1000);
+        let options = SampleOptions {
+            size: 10,
+            strata: None,
+            seed: 7,
+        };
+
+        let first = dir.path().join("sample.jsonl");
+        let stats = sample_records(std::slice::from_ref(&input), &first, &options).unwrap();
+        assert_eq!((stats.records_read, stats.records_sampled), (1000, 10));
+        let dois = read_dois(&first);
+        assert_eq!(dois.len(), 10);
+        // Not just the head of the file
+        let positions: Vec<usize> = dois
+            .iter()
+            .map(|doi| {
+                doi.trim_matches('"')
+                    .rsplit('/')
+                    .next()
+                    .unwrap()
+                    .parse()
+                    .unwrap()
+            })
+            .collect();
+        assert!(positions.iter().any(|&position| position >= 10));
+
+        // Same seed, same sample; another seed, another sample
+        let again = dir.path().join("again.jsonl");
+        sample_records(std::slice::from_ref(&input), &again, &options).unwrap();
+        assert_eq!(read_dois(&again), dois);
+        let other = dir.path().join("other.jsonl");
+        let options = SampleOptions { seed: 8, ..options };
+        sample_records(&[input], &other, &options).unwrap();
+        assert_ne!(read_dois(&other), dois);
+    }
+
+    #[test]
+    fn test_sample_records_stratified() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("valid.jsonl");
+        write_records(&input, 300);
+        let output = dir.path().join("sample.jsonl");
+
+        let options = SampleOptions {
+            size: 2,
+            strata: Some(SampleStrata::Prefix),
+            seed: 1,
+        };
+        let stats = sample_records(std::slice::from_ref(&input), &output, &options).unwrap();
+        let labels: Vec<_> = stats.strata.iter().map(|s| s.0.as_str()).collect();
+        assert_eq!(labels, ["10.1000", "10.1001", "10.1002"]);
+        assert!(stats.strata.iter().all(|s| s.1 == 100 && s.2 == 2));
+        assert_eq!(read_dois(&output).len(), 6);
+
+        let options = SampleOptions {
+            strata: Some(SampleStrata::CitationCount),
+            ..options
+        };
+        let stats = sample_records(&[input], &output, &options).unwrap();
+        let strata: Vec<_> = stats
+            .strata
+            .iter()
+            .map(|(label, read, _)| (label.as_str(), *read))
+            .collect();
+        assert_eq!(
+            strata,
+            [
+                ("0", 1),
+                ("1", 1),
+                ("2-9", 8),
+                ("10-99", 90),
+                ("100-999", 200)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sample_strata_from_str() {
+        assert_eq!("prefix".parse(), Ok(SampleStrata::Prefix));
+        assert_eq!("citation-count".parse(), Ok(SampleStrata::CitationCount));
+        assert!("journal".parse::<SampleStrata>().is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::{Context, Result};
+use log::info;
+use polars::io::parquet::write::BatchedWriter;
+use polars::prelude::*;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::extract::ASSERTED_PROVENANCES;
+
+/// Rows buffered before a batch is written to the counts file
+const COUNTS_BATCH_SIZE: usize = 500_000;
+
+/// Streams each Crossref work's `is-referenced-by-count` to Parquet during extraction
+///
+/// Columns: doi, crossref_count
+pub struct ReferencedByWriter {
+    path: PathBuf,
+    writer: BatchedWriter<File>,
+    dois: Vec<String>,
+    counts: Vec<u32>,
+    rows_written: usize,
+}
+
+impl ReferencedByWriter {
+    /// Create the counts file and its Parquet writer
+    pub fn new(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create referenced-by counts file: {:?}", path))?;
+
+        let schema = Schema::from_iter([
+            Field::new("doi".into(), DataType::String),
+            Field::new("crossref_count".into(), DataType::UInt32),
+        ]);
+
+        let writer = ParquetWriter::new(file)
+            .with_compression(ParquetCompression::Zstd(None))
+            .batched(&schema)
+            .context("Failed to create referenced-by counts parquet writer")?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            dois: Vec::new(),
+            counts: Vec::new(),
+            rows_written: 0,
+        })
+    }
+
+    /// Buffer a work's count, writing a batch when the buffer is full
+    pub fn write(&mut self, doi: &str, count: u32) -> Result<()> {
+        self.dois.push(doi.to_string());
+        self.counts.push(count);
+
+        if self.dois.len() >= COUNTS_BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.dois.is_empty() {
+            return Ok(());
+        }
+
+        let df = DataFrame::new(vec![
+            Column::new("doi".into(), &self.dois),
+            Column::new("crossref_count".into(), &self.counts),
+        ])
+        .context("Failed to create referenced-by DataFrame")?;
+
+        self.writer
+            .write_batch(&df)
+            .context("Failed to write referenced-by batch")?;
+
+        self.rows_written += df.height();
+        self.dois.clear();
+        self.counts.clear();
+        Ok(())
+    }
+
+    /// Flush remaining rows and finalize the Parquet file, returning rows written
+    pub fn finish(mut self) -> Result<usize> {
+        self.flush()?;
+        self.writer
+            .finish()
+            .context("Failed to finalize referenced-by counts parquet")?;
+        info!(
+            "Captured is-referenced-by-count for {} Crossref works: {:?}",
+            self.rows_written, self.path
+        );
+        Ok(self.rows_written)
+    }
+}
+
+/// Totals from comparing our citation counts with Crossref's
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferencedByComparison {
+    /// Crossref works cited by us or with a nonzero Crossref count
+    pub works_compared: usize,
+    pub our_citations: usize,
+    pub asserted_citations: usize,
+    pub mined_citations: usize,
+    pub crossref_citations: usize,
+    /// Works where we found more citing works than Crossref reports
+    pub works_above_crossref: usize,
+}
+
+/// Compare inverted citation counts against captured `is-referenced-by-count` values
+///
+/// Writes one row per Crossref work that we cite or that Crossref reports as cited:
+/// doi, citation_count, asserted_count, mined_count, crossref_count, difference
+/// (citation_count - crossref_count). Counts are before validation, but only DOIs
+/// that are Crossref works appear.
+pub fn compare_referenced_by_counts(
+    inverted_parquet: &Path,
+    counts_parquet: &Path,
+    output_path: &Path,
+) -> Result<ReferencedByComparison> {
+    info!("Comparing citation counts with Crossref is-referenced-by-count...");
+
+    let ours = LazyFrame::scan_parquet(inverted_parquet, Default::default())
+        .with_context(|| format!("Failed to scan inverted output: {:?}", inverted_parquet))?
+        .select([
+            col("cited_id").alias("doi"),
+            col("citation_count"),
+            provenance_count(false).alias("asserted_count"),
+            provenance_count(true).alias("mined_count"),
+        ]);
+
+    let crossref = LazyFrame::scan_parquet(counts_parquet, Default::default())
+        .with_context(|| format!("Failed to scan referenced-by counts: {:?}", counts_parquet))?
+        .unique(Some(vec!["doi".into()]), UniqueKeepStrategy::First);
+
+    let mut comparison = crossref
+        .join(
+            ours,
+            [col("doi")],
+            [col("doi")],
+            JoinArgs::new(JoinType::Left),
+        )
+        .with_columns([
+            col("citation_count").fill_null(lit(0u32)),
+            col("asserted_count").fill_null(lit(0u32)),
+            col("mined_count").fill_null(lit(0u32)),
+        ])
+        .filter(
+            col("citation_count")
+                .gt(lit(0u32))
+                .or(col("crossref_count").gt(lit(0u32))),
+        )
+        .with_column(
+            (col("citation_count").cast(DataType::Int64)
+                - col("crossref_count").cast(DataType::Int64))
+            .alias("difference"),
+        )
+        .select([
+            col("doi"),
+            col("citation_count"),
+            col("asserted_count"),
+            col("mined_count"),
+            col("crossref_count"),
+            col("difference"),
+        ])
+        .sort(
+            ["difference"],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
+        .collect()
+        .context("Failed to compare citation counts")?;
+
+    let sum = |name: &str| -> Result<usize> {
+        Ok(comparison
+            .column(name)?
+            .as_materialized_series()
+            .cast(&DataType::UInt64)?
+            .u64()?
+            .sum()
+            .unwrap_or(0) as usize)
+    };
+    let stats = ReferencedByComparison {
+        works_compared: comparison.height(),
+        our_citations: sum("citation_count")?,
+        asserted_citations: sum("asserted_count")?,
+        mined_citations: sum("mined_count")?,
+        crossref_citations: sum("crossref_count")?,
+        works_above_crossref: comparison
+            .column("difference")?
+            .i64()?
+            .into_iter()
+            .filter(|d| d.is_some_and(|d| d > 0))
+            .count(),
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create comparison output: {:?}", output_path))?;
+    ParquetWriter::new(file)
+        .with_compression(ParquetCompression::Zstd(None))
+        .finish(&mut comparison)
+        .context("Failed to write comparison parquet")?;
+
+    info!("Crossref is-referenced-by-count comparison:");
+    info!("  Works compared: {}", stats.works_compared);
+    info!(
+        "  Our citations: {} ({} asserted, {} mined)",
+        stats.our_citations, stats.asserted_citations, stats.mined_citations
+    );
+    info!("  Crossref citations: {}", stats.crossref_citations);
+    info!(
+        "  Works with more citations than Crossref reports: {}",
+        stats.works_above_crossref
+    );
+    info!("  Written to: {:?}", output_path);
+
+    Ok(stats)
+}
+
+/// Whether a provenance string is publisher- or Crossref-asserted
+pub(crate) fn is_asserted(provenance: Expr) -> Expr {
+    let [publisher, crossref] = ASSERTED_PROVENANCES;
+    provenance
+        .clone()
+        .eq(lit(publisher))
+        .or(provenance.eq(lit(crossref)))
+}
+
+/// Count cited_by entries that are mined (or, with `mined` false, asserted)
+///
+/// Anything not publisher- or Crossref-asserted, including `structured`, counts as mined.
+fn provenance_count(mined: bool) -> Expr {
+    let asserted = is_asserted(col("").struct_().field_by_name("provenance"));
+    let predicate = if mined { asserted.not() } else { asserted };
+    col("cited_by")
+        .list()
+        .eval(predicate.cast(DataType::UInt32), false)
+        .list()
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_referenced_by_writer_and_comparison() {
+        let dir = tempdir().unwrap();
+
+        // Inverted output: 10.1/a cited by one asserted and two mined works, 10.1/b by one mined
+        let mut inverted = df!(
+            "cited_id" => ["10.1/a", "10.1/a", "10.1/a", "10.1/b"],
+            "citing_doi" => ["10.9/x", "10.9/y", "10.9/z", "10.9/x"],
+            "provenance" => ["publisher", "mined", "mined", "mined"],
+        )
+        .unwrap()
+        .lazy()
+        .group_by([col("cited_id")])
+        .agg([
+            col("citing_doi").n_unique().alias("citation_count"),
+            as_struct(vec![col("citing_doi").alias("doi"), col("provenance")]).alias("cited_by"),
+        ])
+        .collect()
+        .unwrap();
+        let inverted_path = dir.path().join("inverted.parquet");
+        ParquetWriter::new(File::create(&inverted_path).unwrap())
+            .finish(&mut inverted)
+            .unwrap();
+
+        let counts_path = dir.path().join("counts.parquet");
+        let mut writer = ReferencedByWriter::new(&counts_path).unwrap();
+        writer.write("10.1/a", 1).unwrap();
+        writer.write("10.1/b", 0).unwrap();
+        writer.write("10.1/c", 4).unwrap();
+        writer.write("10.1/uncited", 0).unwrap();
+        assert_eq!(writer.finish().unwrap(), 4);
+
+        let output_path = dir.path().join("comparison.parquet");
+        let stats =
+            compare_referenced_by_counts(&inverted_path, &counts_path, &output_path).unwrap();
+
+        assert_eq!(
+            stats,
+            ReferencedByComparison {
+                works_compared: 3,
+                our_citations: 4,
+                asserted_citations: 1,
+                mined_citations: 3,
+                crossref_citations: 5,
+                works_above_crossref: 2,
+            }
+        );
+
+        let df = LazyFrame::scan_parquet(&output_path, Default::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        let dois = df.column("doi").unwrap().str().unwrap();
+        let differences = df.column("difference").unwrap().i64().unwrap();
+        assert_eq!(dois.get(0), Some("10.1/a"));
+        assert_eq!(differences.get(0), Some(2));
+        assert_eq!(dois.get(2), Some("10.1/c"));
+        assert_eq!(differences.get(2), Some(-4));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::error::{Context, Result};
+use log::info;
+use polars::prelude::*;
+use std::fs::File;
+use std::path::Path;
+
+use super::referenced_by::is_asserted;
+
+/// Totals from comparing the inverted citations of two snapshots
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotComparison {
+    /// Works cited in either snapshot
+    pub works_compared: usize,
+    /// Works cited in the current snapshot but not the baseline
+    pub works_newly_cited: usize,
+    pub citations_before: usize,
+    pub citations_after: usize,
+    /// Citing works found only in the current snapshot, summed over cited works
+    pub new_citing_works: usize,
+    /// Citing works found only in the baseline, summed over cited works
+    pub lost_citing_works: usize,
+    /// Citations mined in the baseline and publisher/Crossref-asserted in the current snapshot
+    pub mined_to_asserted: usize,
+    /// Citations asserted in the baseline and only mined in the current snapshot
+    pub asserted_to_mined: usize,
+}
+
+/// Compare the inverted citations of a baseline snapshot with the current one
+///
+/// Writes one row per work cited in either snapshot: cited_id, citations_before,
+/// citations_after, growth (after - before), new_citing_works, lost_citing_works,
+/// new_citing_dois, asserted/mined counts before and after, and the citing works whose
+/// provenance moved between mined and asserted. Largest growth first. Counts are before
+/// validation.
+pub fn compare_snapshots(
+    baseline_parquet: &Path,
+    current_parquet: &Path,
+    output_path: &Path,
+) -> Result<SnapshotComparison> {
+    info!("Comparing citations with the baseline snapshot...");
+
+    let pairs = citing_pairs(baseline_parquet, "before")?.join(
+        citing_pairs(current_parquet, "after")?,
+        [col("cited_id"), col("citing_doi")],
+        [col("cited_id"), col("citing_doi")],
+        JoinArgs::new(JoinType::Full).with_coalesce(JoinCoalesce::CoalesceColumns),
+    );
+
+    let before = col("asserted_before").is_not_null();
+    let after = col("asserted_after").is_not_null();
+    let count = |predicate: Expr| predicate.cast(DataType::UInt32).sum();
+
+    let mut comparison = pairs
+        .group_by([col("cited_id")])
+        .agg([
+            count(before.clone()).alias("citations_before"),
+            count(after.clone()).alias("citations_after"),
+            count(after.clone().and(before.clone().not())).alias("new_citing_works"),
+            count(before.clone().and(after.clone().not())).alias("lost_citing_works"),
+            col("citing_doi")
+                .filter(after.and(before.not()))
+                .alias("new_citing_dois"),
+            count(col("asserted_before").eq(lit(true))).alias("asserted_before"),
+            count(col("asserted_before").eq(lit(false))).alias("mined_before"),
+            count(col("asserted_after").eq(lit(true))).alias("asserted_after"),
+            count(col("asserted_after").eq(lit(false))).alias("mined_after"),
+            count(
+                col("asserted_before")
+                    .eq(lit(false))
+                    .and(col("asserted_after").eq(lit(true))),
+            )
+            .alias("mined_to_asserted"),
+            count(
+                col("asserted_before")
+                    .eq(lit(true))
+                    .and(col("asserted_after").eq(lit(false))),
+            )
+            .alias("asserted_to_mined"),
+        ])
+        .with_column(
+            (col("citations_after").cast(DataType::Int64)
+                - col("citations_before").cast(DataType::Int64))
+            .alias("growth"),
+        )
+        .select([
+            col("cited_id"),
+            col("citations_before"),
+            col("citations_after"),
+            col("growth"),
+            col("new_citing_works"),
+            col("lost_citing_works"),
+            col("new_citing_dois"),
+            col("asserted_before"),
+            col("mined_before"),
+            col("asserted_after"),
+            col("mined_after"),
+            col("mined_to_asserted"),
+            col("asserted_to_mined"),
+        ])
+        .sort(
+            ["growth", "cited_id"],
+            SortMultipleOptions::default().with_order_descending_multi([true, false]),
+        )
+        .collect()
+        .context("Failed to compare snapshots")?;
+
+    let sum = |name: &str| -> Result<usize> {
+        Ok(comparison
+            .column(name)?
+            .as_materialized_series()
+            .cast(&DataType::UInt64)?
+            .u64()?
+            .sum()
+            .unwrap_or(0) as usize)
+    };
+    let stats = SnapshotComparison {
+        works_compared: comparison.height(),
+        works_newly_cited: comparison
+            .column("citations_before")?
+            .u32()?
+            .into_iter()
+            .filter(|count| *count == Some(0))
+            .count(),
+        citations_before: sum("citations_before")?,
+        citations_after: sum("citations_after")?,
+        new_citing_works: sum("new_citing_works")?,
+        lost_citing_works: sum("lost_citing_works")?,
+        mined_to_asserted: sum("mined_to_asserted")?,
+        asserted_to_mined: sum("asserted_to_mined")?,
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create snapshot comparison: {:?}", output_path))?;
+    ParquetWriter::new(file)
+        .with_compression(ParquetCompression::Zstd(None))
+        .finish(&mut comparison)
+        .context("Failed to write snapshot comparison parquet")?;
+
+    info!("Snapshot comparison:");
+    info!(
+        "  Works compared: {} ({} newly cited)",
+        stats.works_compared, stats.works_newly_cited
+    );
+    info!(
+        "  Citations: {} -> {}",
+        stats.citations_before, stats.citations_after
+    );
+    info!(
+        "  Citing works: {} new, {} lost",
+        stats.new_citing_works, stats.lost_citing_works
+    );
+    info!(
+        "  Provenance shifts: {} mined -> asserted, {} asserted -> mined",
+        stats.mined_to_asserted, stats.asserted_to_mined
+    );
+    info!("  Written to: {:?}", output_path);
+
+    Ok(stats)
+}
+
+/// One row per (cited_id, citing_doi) in an inverted output, with whether the
+/// citation is asserted (publisher or Crossref) rather than mined or structured
+fn citing_pairs(inverted_parquet: &Path, suffix: &str) -> Result<LazyFrame> {
+    let entry = || col("cited_by").struct_();
+    Ok(
+        LazyFrame::scan_parquet(inverted_parquet, Default::default())
+            .with_context(|| format!("Failed to scan inverted output: {:?}", inverted_parquet))?
+            .select([col("cited_id"), col("cited_by")])
+            .explode([col("cited_by")])
+            .select([
+                col("cited_id"),
+                entry().field_by_name("doi").alias("citing_doi"),
+                is_asserted(entry().field_by_name("provenance"))
+                    .alias(format!("asserted_{}", suffix)),
+            ])
+            // An empty cited_by list explodes to a null entry
+            .filter(col("citing_doi").is_not_null()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_inverted(path: &Path, rows: [Vec<&str>; 3]) {
+        let [cited_ids, citing_dois, provenances] = rows;
+        let mut df = df!(
+            "cited_id" => cited_ids,
+            "citing_doi" => citing_dois,
+            "provenance" => provenances,
+        )
+        .unwrap()
+        .lazy()
+        .group_by([col("cited_id")])
+        .agg([
+            col("citing_doi").n_unique().alias("citation_count"),
+            as_struct(vec![col("citing_doi").alias("doi"), col("provenance")]).alias("cited_by"),
+        ])
+        .collect()
+        .unwrap();
+        ParquetWriter::new(File::create(path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_compare_snapshots() {
+        let dir = tempdir().unwrap();
+        let baseline = dir.path().join("baseline.parquet");
+        let current = dir.path().join("current.parquet");
+
+        // 10.1/a: x mined -> publisher, y dropped, z and w new; 10.1/b: unchanged;
+        // 10.1/c: newly cited; 10.1/d: no longer cited
+        write_inverted(
+            &baseline,
+            [
+                vec!["10.1/a", "10.1/a", "10.1/b", "10.1/d"],
+                vec!["10.9/x", "10.9/y", "10.9/x", "10.9/x"],
+                vec!["mined", "crossref", "publisher", "mined"],
+            ],
+        );
+        write_inverted(
+            &current,
+            [
+                vec!["10.1/a", "10.1/a", "10.1/a", "10.1/b", "10.1/c"],
+                vec!["10.9/x", "10.9/z", "10.9/w", "10.9/x", "10.9/z"],
+                vec!["publisher", "mined", "mined", "publisher", "mined"],
+            ],
+        );
+
+        let output = dir.path().join("comparison.parquet");
+        let stats = compare_snapshots(&baseline, &current, &output).unwrap();
+        assert_eq!(
+            stats,
+            SnapshotComparison {
+                works_compared: 4,
+                works_newly_cited: 1,
+                citations_before: 4,
+                citations_after: 5,
+                new_citing_works: 3,
+                lost_citing_works: 2,
+                mined_to_asserted: 1,
+                asserted_to_mined: 0,
+            }
+        );
+
+        let df = LazyFrame::scan_parquet(&output, Default::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        let cited_ids = df.column("cited_id").unwrap().str().unwrap();
+        let growth = df.column("growth").unwrap().i64().unwrap();
+        assert_eq!(cited_ids.get(0), Some("10.1/a"));
+        assert_eq!(growth.get(0), Some(1));
+        assert_eq!(cited_ids.get(3), Some("10.1/d"));
+        assert_eq!(growth.get(3), Some(-1));
+
+        let new_citing = df.column("new_citing_dois").unwrap().list().unwrap();
+        let mut first: Vec<String> = new_citing
+            .get_as_series(0)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .map(String::from)
+            .collect();
+        first.sort();
+        assert_eq!(first, vec!["10.9/w", "10.9/z"]);
+    }
+}
@@ -0,0 +1,466 @@
+use crate::error::{Context, Error, Result};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use log::debug;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use tar::Archive;
+
+use super::gzip_reader::{is_compressed, open_compressed_reader};
+use super::{parse_deposit_xml, parse_snapshot_json};
+
+/// Offset of the `ustar` magic within a tar header block
+const USTAR_MAGIC_OFFSET: usize = 257;
+const USTAR_MAGIC: &[u8] = b"ustar";
+/// Parsed entries each worker may run ahead of the consumer
+const WORKER_READAHEAD: usize = 2;
+
+/// How a Crossref snapshot input is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// Tar compressed with gzip, bzip2 or zstd, read sequentially
+    Compressed,
+    /// Uncompressed tar, whose entries can be read from any offset
+    Tar,
+    /// Directory of an unpacked snapshot's files
+    Directory,
+}
+
+impl SnapshotFormat {
+    /// Detect the format of `path`: a directory, a tar by its `ustar` header magic, or
+    /// a compressed tar by the magic of its compression; anything else is an error
+    pub fn detect(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            return Ok(SnapshotFormat::Directory);
+        }
+        let mut header = Vec::with_capacity(USTAR_MAGIC_OFFSET + USTAR_MAGIC.len());
+        File::open(path)
+            .with_context(|| format!("Failed to open input file: {:?}", path))?
+            .take(header.capacity() as u64)
+            .read_to_end(&mut header)
+            .with_context(|| format!("Failed to read input file: {:?}", path))?;
+        if header.get(USTAR_MAGIC_OFFSET..) == Some(USTAR_MAGIC) {
+            Ok(SnapshotFormat::Tar)
+        } else if is_compressed(&header) {
+            Ok(SnapshotFormat::Compressed)
+        } else {
+            Err(Error::invalid(format!(
+                "{:?} is neither a tar, a gzip, bzip2 or zstd compressed tar, nor a directory",
+                path
+            )))
+        }
+    }
+}
+
+/// An entry of a snapshot input with its work items
+#[derive(Debug)]
+pub struct SnapshotEntry {
+    /// Path within the archive or directory
+    pub path: String,
+    /// Items of a JSON or deposit XML entry; None for other entries and for entries
+    /// passed over unread
+    pub items: Option<Result<Vec<Value>>>,
+}
+
+/// Parse an entry by its extension: snapshot JSON files hold an items array, and
+/// deposit XML files are converted to the same item shape
+fn parse_entry<R: Read>(path: &str, reader: R) -> Option<Result<Vec<Value>>> {
+    if path.ends_with(".json") {
+        debug!("Processing: {}", path);
+        Some(parse_snapshot_json(reader))
+    } else if path.ends_with(".xml") {
+        debug!("Processing deposit XML: {}", path);
+        Some(parse_deposit_xml(BufReader::new(reader)))
+    } else {
+        None
+    }
+}
+
+/// Entries of a tar archive read in order, the first `skip` passed over unparsed
+pub fn archive_entries<R: Read>(
+    archive: &mut Archive<R>,
+    skip: usize,
+) -> Result<impl Iterator<Item = Result<SnapshotEntry>> + '_> {
+    Ok(archive.entries()?.enumerate().map(move |(i, entry)| {
+        let entry = entry.context("Failed to read tar entry")?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let items = if i < skip {
+            None
+        } else {
+            parse_entry(&path, entry)
+        };
+        Ok(SnapshotEntry { path, items })
+    }))
+}
+
+/// A snapshot input opened for reading its entries
+///
+/// A compressed tar is streamed; an uncompressed tar or a directory is read by
+/// [`ParallelEntries`] workers.
+pub struct SnapshotInput {
+    path: PathBuf,
+    format: SnapshotFormat,
+    archive: Option<Archive<Box<dyn Read>>>,
+}
+
+impl SnapshotInput {
+    /// Open `path`, detecting its format
+    pub fn open(path: &Path) -> Result<Self> {
+        let format = SnapshotFormat::detect(path)?;
+        let archive = match format {
+            SnapshotFormat::Compressed => Some(Archive::new(open_compressed_reader(path)?)),
+            SnapshotFormat::Tar | SnapshotFormat::Directory => None,
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            archive,
+        })
+    }
+
+    pub fn format(&self) -> SnapshotFormat {
+        self.format
+    }
+
+    /// Entries in archive order, parsed ahead by `workers` threads unless the input is
+    /// compressed; the first `skip` entries are passed over unparsed
+    pub fn entries(
+        &mut self,
+        workers: usize,
+        skip: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<SnapshotEntry>> + '_>> {
+        Ok(match self.archive {
+            Some(ref mut archive) => Box::new(archive_entries(archive, skip)?),
+            None => Box::new(ParallelEntries::spawn(
+                &self.path,
+                self.format,
+                workers,
+                skip,
+            )?),
+        })
+    }
+}
+
+/// Where an entry's data lies: a byte range of an uncompressed tar, or a whole file of
+/// a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryRange {
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Byte ranges of every entry of an uncompressed tar, in archive order
+///
+/// Only headers are read; the data of each entry is seeked past.
+pub fn index_tar(path: &Path) -> Result<Vec<EntryRange>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open input file: {:?}", path))?;
+    let mut archive = Archive::new(BufReader::new(file));
+    let mut ranges = Vec::new();
+    for entry in archive.entries_with_seek()? {
+        let entry = entry.with_context(|| format!("Failed to read tar entry in {:?}", path))?;
+        ranges.push(EntryRange {
+            path: entry.path()?.to_string_lossy().into_owned(),
+            offset: entry.raw_file_position(),
+            size: entry.size(),
+        });
+    }
+    Ok(ranges)
+}
+
+/// Files under a directory, recursively, in path order
+pub fn index_directory(dir: &Path) -> Result<Vec<EntryRange>> {
+    fn walk(dir: &Path, root: &Path, ranges: &mut Vec<EntryRange>) -> Result<()> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory: {:?}", dir))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?;
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                walk(&path, root, ranges)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                ranges.push(EntryRange {
+                    path: relative.to_string_lossy().replace('\\', "/"),
+                    offset: 0,
+                    size: fs::metadata(&path)?.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    let mut ranges = Vec::new();
+    walk(dir, dir, &mut ranges)?;
+    Ok(ranges)
+}
+
+/// Entries of an uncompressed tar or a directory, read and parsed on worker threads
+/// and yielded in archive order
+///
+/// Entry byte ranges are dealt out to the workers in turn, so each reads every
+/// `workers`-th entry with its own file handle. Each worker runs at most a couple of
+/// parsed entries ahead of the consumer; dropping the iterator stops them.
+pub struct ParallelEntries {
+    receivers: Vec<Receiver<Result<SnapshotEntry>>>,
+    handles: Vec<Option<JoinHandle<()>>>,
+    next: usize,
+    total: usize,
+}
+
+impl ParallelEntries {
+    /// Index `input` and start `workers` threads; the first `skip` entries are passed
+    /// over unread
+    pub fn spawn(
+        input: &Path,
+        format: SnapshotFormat,
+        workers: usize,
+        skip: usize,
+    ) -> Result<Self> {
+        let ranges = match format {
+            SnapshotFormat::Tar => index_tar(input)?,
+            SnapshotFormat::Directory => index_directory(input)?,
+            SnapshotFormat::Compressed => {
+                return Err(Error::invalid(format!(
+                    "{:?} is compressed; only an uncompressed tar or a directory can be \
+                     read in parallel",
+                    input
+                )))
+            }
+        };
+        let total = ranges.len();
+        let workers = workers.clamp(1, total.max(1));
+        debug!("Reading {} entries with {} workers", total, workers);
+
+        let mut receivers = Vec::with_capacity(workers);
+        let mut handles = Vec::with_capacity(workers);
+        for worker in 0..workers {
+            let (sender, receiver) = bounded(WORKER_READAHEAD);
+            let assigned: Vec<(usize, EntryRange)> = ranges
+                .iter()
+                .cloned()
+                .enumerate()
+                .skip(worker)
+                .step_by(workers)
+                .collect();
+            let input = input.to_path_buf();
+            handles.push(Some(thread::spawn(move || {
+                read_entries(&input, format, assigned, skip, sender)
+            })));
+            receivers.push(receiver);
+        }
+
+        Ok(Self {
+            receivers,
+            handles,
+            next: 0,
+            total,
+        })
+    }
+}
+
+/// Read and parse a worker's entries, sending each in turn
+fn read_entries(
+    input: &Path,
+    format: SnapshotFormat,
+    assigned: Vec<(usize, EntryRange)>,
+    skip: usize,
+    sender: Sender<Result<SnapshotEntry>>,
+) {
+    let mut archive: Option<File> = None;
+    for (index, range) in assigned {
+        let entry = read_entry(input, format, &mut archive, &range, index < skip).map(|items| {
+            SnapshotEntry {
+                path: range.path,
+                items,
+            }
+        });
+        let failed = entry.is_err();
+        // The consumer dropped the iterator; stop reading
+        if sender.send(entry).is_err() || failed {
+            break;
+        }
+    }
+}
+
+/// Items of an entry, unless it is passed over or neither JSON nor XML
+fn read_entry(
+    input: &Path,
+    format: SnapshotFormat,
+    archive: &mut Option<File>,
+    range: &EntryRange,
+    pass_over: bool,
+) -> Result<Option<Result<Vec<Value>>>> {
+    if pass_over || !(range.path.ends_with(".json") || range.path.ends_with(".xml")) {
+        return Ok(None);
+    }
+    let items = match format {
+        SnapshotFormat::Directory => {
+            let path = input.join(&range.path);
+            let file = File::open(&path).with_context(|| format!("Failed to open: {:?}", path))?;
+            parse_entry(&range.path, BufReader::new(file))
+        }
+        _ => {
+            let file = match archive {
+                Some(file) => file,
+                None => archive.insert(
+                    File::open(input)
+                        .with_context(|| format!("Failed to open input file: {:?}", input))?,
+                ),
+            };
+            file.seek(SeekFrom::Start(range.offset))
+                .with_context(|| format!("Failed to seek to {} in {:?}", range.path, input))?;
+            parse_entry(&range.path, BufReader::new(file.take(range.size)))
+        }
+    };
+    Ok(items)
+}
+
+impl Iterator for ParallelEntries {
+    type Item = Result<SnapshotEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.total {
+            return None;
+        }
+        let worker = self.next % self.receivers.len();
+        match self.receivers[worker].recv() {
+            Ok(entry) => {
+                self.next += 1;
+                if entry.is_err() {
+                    self.total = self.next;
+                }
+                Some(entry)
+            }
+            Err(_) => {
+                // A worker stopped before its last entry only by panicking
+                self.total = self.next;
+                let panicked = self.handles[worker]
+                    .take()
+                    .is_some_and(|handle| handle.join().is_err());
+                Some(Err(Error::invalid(if panicked {
+                    "Entry reader thread panicked".to_string()
+                } else {
+                    format!("Entry reader stopped before entry {}", self.next + 1)
+                })))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn snapshot_file(name: &str) -> (String, Vec<u8>) {
+        let json = format!(r#"{{"items": [{{"DOI": "10.1234/{}"}}]}}"#, name);
+        (format!("snapshot/{}.json", name), json.into_bytes())
+    }
+
+    fn write_tar(path: &Path, files: &[(String, Vec<u8>)]) {
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, &data[..]).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn dois(entries: impl Iterator<Item = Result<SnapshotEntry>>) -> Vec<(String, Option<String>)> {
+        entries
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let doi = entry
+                    .items
+                    .map(|items| items.unwrap()[0]["DOI"].as_str().unwrap().to_string());
+                (entry.path, doi)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let dir = tempdir().unwrap();
+        let tar_path = dir.path().join("snapshot.tar");
+        write_tar(&tar_path, &[snapshot_file("a")]);
+        let gz_path = dir.path().join("snapshot.tar.gz");
+        fs::write(&gz_path, [0x1f, 0x8b, 0x08, 0x00]).unwrap();
+
+        assert_eq!(
+            SnapshotFormat::detect(&tar_path).unwrap(),
+            SnapshotFormat::Tar
+        );
+        assert_eq!(
+            SnapshotFormat::detect(&gz_path).unwrap(),
+            SnapshotFormat::Compressed
+        );
+        let zst_path = dir.path().join("snapshot.tar.zst");
+        fs::write(&zst_path, [0x28, 0xb5, 0x2f, 0xfd, 0x00]).unwrap();
+        assert_eq!(
+            SnapshotFormat::detect(&zst_path).unwrap(),
+            SnapshotFormat::Compressed
+        );
+
+        // Neither compressed nor a tar, and too short for a tar header
+        let zip_path = dir.path().join("snapshot.zip");
+        fs::write(&zip_path, b"PK\x03\x04").unwrap();
+        assert!(SnapshotFormat::detect(&zip_path).is_err());
+        assert_eq!(
+            SnapshotFormat::detect(dir.path()).unwrap(),
+            SnapshotFormat::Directory
+        );
+    }
+
+    #[test]
+    fn test_parallel_entries_in_archive_order() {
+        let dir = tempdir().unwrap();
+        let files: Vec<_> = ["a", "b", "c", "d", "e"]
+            .into_iter()
+            .map(snapshot_file)
+            .chain([("snapshot/notes.txt".to_string(), b"notes".to_vec())])
+            .collect();
+        let tar_path = dir.path().join("snapshot.tar");
+        write_tar(&tar_path, &files);
+
+        let ranges = index_tar(&tar_path).unwrap();
+        assert_eq!(ranges.len(), 6);
+        assert_eq!(ranges[1].path, "snapshot/b.json");
+
+        let mut sequential = Archive::new(File::open(&tar_path).unwrap());
+        let expected = dois(archive_entries(&mut sequential, 2).unwrap());
+        assert_eq!(expected[0], ("snapshot/a.json".to_string(), None));
+        assert_eq!(
+            expected[2],
+            ("snapshot/c.json".to_string(), Some("10.1234/c".to_string()))
+        );
+        assert_eq!(expected[5], ("snapshot/notes.txt".to_string(), None));
+
+        for workers in [1, 3, 8] {
+            let entries =
+                ParallelEntries::spawn(&tar_path, SnapshotFormat::Tar, workers, 2).unwrap();
+            assert_eq!(dois(entries), expected, "{} workers", workers);
+        }
+
+        // An unpacked copy reads the same, its files in path order
+        let unpacked = dir.path().join("unpacked");
+        Archive::new(File::open(&tar_path).unwrap())
+            .unpack(&unpacked)
+            .unwrap();
+        let entries = ParallelEntries::spawn(&unpacked, SnapshotFormat::Directory, 2, 2).unwrap();
+        assert_eq!(dois(entries), expected);
+    }
+
+    #[test]
+    fn test_parallel_entries_reject_compressed() {
+        let dir = tempdir().unwrap();
+        assert!(ParallelEntries::spawn(dir.path(), SnapshotFormat::Compressed, 2, 0).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::{Context, Result};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+use std::io::Read;
+
+/// Key of the references array, as it appears in the raw item bytes
+const REFERENCE_KEY: &[u8] = b"\"reference\"";
+
+#[derive(Deserialize)]
+struct SnapshotFile<'a> {
+    #[serde(borrow, default)]
+    items: Vec<&'a RawValue>,
+}
+
+/// Fields the pipeline reads from a work without references
+#[derive(Deserialize)]
+struct ItemWithoutReferences {
+    #[serde(rename = "DOI")]
+    doi: Option<Value>,
+    #[serde(rename = "is-referenced-by-count")]
+    referenced_by_count: Option<Value>,
+    issued: Option<Value>,
+    reference: Option<IgnoredAny>,
+}
+
+/// Read the items of a snapshot JSON file, fully parsing only those that may hold
+/// references
+///
+/// About half of Crossref works have no `reference` array. An item whose bytes do not
+/// contain the `"reference"` key is parsed into its DOI, `is-referenced-by-count` and
+/// `issued` alone, skipping the rest of its metadata. Should that parse find the key
+/// after all (written with escapes), the item is parsed in full, so the result is the
+/// same as parsing every item.
+pub fn parse_snapshot_json<R: Read>(mut reader: R) -> Result<Vec<Value>> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .context("Failed to read snapshot JSON")?;
+    let file: SnapshotFile = serde_json::from_slice(&bytes)?;
+
+    file.items
+        .into_iter()
+        .map(|raw| Ok(parse_item(raw.get())?))
+        .collect()
+}
+
+fn parse_item(raw: &str) -> serde_json::Result<Value> {
+    if contains_reference_key(raw.as_bytes()) {
+        return serde_json::from_str(raw);
+    }
+    let item: ItemWithoutReferences = serde_json::from_str(raw)?;
+    if item.reference.is_some() {
+        return serde_json::from_str(raw);
+    }
+
+    let mut fields = Map::new();
+    for (key, value) in [
+        ("DOI", item.doi),
+        ("is-referenced-by-count", item.referenced_by_count),
+        ("issued", item.issued),
+    ] {
+        if let Some(value) = value {
+            fields.insert(key.to_string(), value);
+        }
+    }
+    Ok(Value::Object(fields))
+}
+
+fn contains_reference_key(bytes: &[u8]) -> bool {
+    bytes
+        .windows(REFERENCE_KEY.len())
+        .any(|window| window == REFERENCE_KEY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_snapshot_json() {
+        let file = json!({
+            "items": [
+                {
+                    "DOI": "10.1234/cites",
+                    "title": ["A \"reference\" in a title"],
+                    "reference": [{"key": "r1", "DOI": "10.5678/cited"}]
+                },
+                {
+                    "DOI": "10.1234/alone",
+                    "is-referenced-by-count": 3,
+                    "issued": {"date-parts": [[2024]]},
+                    "reference-count": 0,
+                    "title": ["No references"]
+                }
+            ]
+        });
+        let items = parse_snapshot_json(file.to_string().as_bytes()).unwrap();
+        assert_eq!(items[0], file["items"][0]);
+        assert_eq!(
+            items[1],
+            json!({
+                "DOI": "10.1234/alone",
+                "is-referenced-by-count": 3,
+                "issued": {"date-parts": [[2024]]}
+            })
+        );
+
+        // A key written with escapes is found by the parse and the item kept whole
+        let escaped = r#"{"items": [{"DOI": "10.1/x", "refer\u0065nce": [{"key": "r1"}]}]}"#;
+        let items = parse_snapshot_json(escaped.as_bytes()).unwrap();
+        assert_eq!(items[0]["reference"][0]["key"], "r1");
+
+        assert!(parse_snapshot_json(&b"{}"[..]).unwrap().is_empty());
+        assert!(parse_snapshot_json(&b"{\"items\": [{"[..]).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::common::{open_sink_with, OutputOptions, OutputSink};
+use crate::error::Result;
+use log::info;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Identifier a reference appears to contain but that no pattern extracted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmatchedHint {
+    /// Mentions arXiv
+    Arxiv,
+    /// Mentions "doi" as a word (doi:, doi.org, DOI ...)
+    Doi,
+}
+
+impl UnmatchedHint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnmatchedHint::Arxiv => "arxiv",
+            UnmatchedHint::Doi => "doi",
+        }
+    }
+
+    /// Arxiv hint: "arxiv" anywhere in the text, case-insensitively
+    pub fn arxiv_in(text: &str) -> bool {
+        text.to_ascii_lowercase().contains("arxiv")
+    }
+
+    /// DOI hint: "doi" not inside a longer word, so "doi:" and "dx.doi.org" count but
+    /// "doing" does not
+    pub fn doi_in(text: &str) -> bool {
+        let lower = text.to_ascii_lowercase();
+        let bytes = lower.as_bytes();
+        lower.match_indices("doi").any(|(i, _)| {
+            let before = i.checked_sub(1).map(|j| bytes[j]);
+            let after = bytes.get(i + 3).copied();
+            !before.is_some_and(|b| b.is_ascii_alphabetic())
+                && !after.is_some_and(|b| b.is_ascii_alphabetic())
+        })
+    }
+}
+
+/// Streams references with an identifier hint but no extracted ID to JSONL
+///
+/// Each line: citing_doi, ref_index, hint (arxiv or doi), and the reference object.
+/// Written through the sink the path implies, so `.gz`, Parquet or Arrow paths and the
+/// shard options work as for the validation outputs.
+pub struct UnmatchedRefsWriter {
+    path: PathBuf,
+    sink: Box<dyn OutputSink>,
+}
+
+impl UnmatchedRefsWriter {
+    pub fn new(path: &Path, options: &OutputOptions) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            sink: open_sink_with(path, options)?,
+        })
+    }
+
+    pub fn write(
+        &mut self,
+        citing_doi: &str,
+        ref_index: u32,
+        hint: UnmatchedHint,
+        reference: &Value,
+    ) -> Result<()> {
+        let line = json!({
+            "citing_doi": citing_doi,
+            "ref_index": ref_index,
+            "hint": hint.as_str(),
+            "reference": reference,
+        });
+        self.sink.write_record(&line)
+    }
+
+    /// Finish the output, returning references written
+    pub fn finish(self) -> Result<usize> {
+        let rows_written = self.sink.finish()?;
+        info!(
+            "Wrote {} unmatched references to: {:?}",
+            rows_written, self.path
+        );
+        Ok(rows_written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{ShardLimit, ShardManifest};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hints() {
+        assert!(UnmatchedHint::arxiv_in("Preprint, ArXiv 2403"));
+        assert!(!UnmatchedHint::arxiv_in("Proc. Synth. Conf."));
+
+        assert!(UnmatchedHint::doi_in("DOI: 10.1/x"));
+        assert!(UnmatchedHint::doi_in("https://dx.doi.org/10.1/x"));
+        assert!(UnmatchedHint::doi_in("doi"));
+        assert!(!UnmatchedHint::doi_in("Doing things, Doisneau R."));
+    }
+
+    #[test]
+    fn test_unmatched_refs_writer() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("unmatched.jsonl");
+
+        let mut writer = UnmatchedRefsWriter::new(&path, &OutputOptions::default()).unwrap();
+        writer
+            .write(
+                "10.1234/a",
+                3,
+                UnmatchedHint::Doi,
+                &json!({"unstructured": "Smith. DOI: 10.1/x"}),
+            )
+            .unwrap();
+        assert_eq!(writer.finish().unwrap(), 1);
+
+        let line: Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(line["citing_doi"], "10.1234/a");
+        assert_eq!(line["ref_index"], 3);
+        assert_eq!(line["hint"], "doi");
+        assert_eq!(line["reference"]["unstructured"], "Smith. DOI: 10.1/x");
+    }
+
+    #[test]
+    fn test_unmatched_refs_writer_shards() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("unmatched.jsonl");
+        let options = OutputOptions {
+            shard: Some(ShardLimit::Records(1)),
+            ..Default::default()
+        };
+
+        let mut writer = UnmatchedRefsWriter::new(&path, &options).unwrap();
+        for index in 0..2 {
+            writer
+                .write("10.1234/a", index, UnmatchedHint::Arxiv, &json!({}))
+                .unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), 2);
+
+        assert!(!path.exists());
+        assert_eq!(ShardManifest::load(&path).unwrap().unwrap().shards.len(), 2);
+        assert!(dir.path().join("unmatched_00002.jsonl").exists());
+    }
+}
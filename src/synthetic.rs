@@ -0,0 +1,217 @@
+//! Synthetic Crossref snapshots for benchmarks and tests
+//!
+//! Generates tar.gz archives shaped like the Crossref public data file: JSON files
+//! with an `items` array of works, each with a `reference` list. Output is
+//! deterministic for a given seed.
+
+use crate::common::SplitMix64;
+use crate::error::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::path::Path;
+use tar::{Builder, Header};
+
+/// Shape of a synthetic snapshot
+#[derive(Debug, Clone)]
+pub struct SyntheticSnapshot {
+    /// JSON files in the archive
+    pub files: usize,
+    /// Works per JSON file
+    pub items_per_file: usize,
+    /// References per work
+    pub references_per_item: usize,
+    /// Fraction of references carrying a publisher-asserted DOI field
+    pub asserted_doi_rate: f64,
+    /// Fraction of references with a DOI only in unstructured text
+    pub mined_doi_rate: f64,
+    /// Fraction of references with an arXiv ID in unstructured text
+    pub arxiv_rate: f64,
+    /// Distinct DOI prefixes cited works are spread over (drives partition count)
+    pub prefixes: usize,
+    pub seed: u64,
+}
+
+impl Default for SyntheticSnapshot {
+    fn default() -> Self {
+        Self {
+            files: 10,
+            items_per_file: 100,
+            references_per_item: 20,
+            asserted_doi_rate: 0.4,
+            mined_doi_rate: 0.2,
+            arxiv_rate: 0.05,
+            prefixes: 200,
+            seed: 42,
+        }
+    }
+}
+
+/// Counts describing a generated snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyntheticStats {
+    pub files: usize,
+    pub items: usize,
+    pub references: usize,
+    pub asserted_dois: usize,
+    pub mined_dois: usize,
+    pub arxiv_ids: usize,
+    /// Uncompressed JSON bytes written
+    pub json_bytes: usize,
+}
+
+impl SyntheticSnapshot {
+    /// Total works in the snapshot
+    pub fn items(&self) -> usize {
+        self.files * self.items_per_file
+    }
+
+    /// Write the snapshot as a gzipped tar archive
+    pub fn write_tar_gz(&self, path: &Path) -> Result<SyntheticStats> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create synthetic snapshot: {:?}", path))?;
+        let mut builder = Builder::new(GzEncoder::new(file, Compression::fast()));
+        let mut rng = SplitMix64::new(self.seed);
+        let mut stats = SyntheticStats::default();
+
+        for file_idx in 0..self.files {
+            let items: Vec<Value> = (0..self.items_per_file)
+                .map(|item_idx| self.work(file_idx, item_idx, &mut rng, &mut stats))
+                .collect();
+            let content = serde_json::to_vec(&json!({ "items": items }))?;
+
+            let mut header = Header::new_gnu();
+            header.set_path(format!("synthetic/{:06}.json", file_idx))?;
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, content.as_slice())?;
+
+            stats.files += 1;
+            stats.json_bytes += content.len();
+        }
+
+        builder
+            .into_inner()?
+            .finish()
+            .context("Failed to finish synthetic snapshot")?;
+        Ok(stats)
+    }
+
+    fn work(
+        &self,
+        file_idx: usize,
+        item_idx: usize,
+        rng: &mut SplitMix64,
+        stats: &mut SyntheticStats,
+    ) -> Value {
+        stats.items += 1;
+        let references: Vec<Value> = (0..self.references_per_item)
+            .map(|_| self.reference(rng, stats))
+            .collect();
+        stats.references += references.len();
+
+        json!({
+            "DOI": format!("10.{}/work.{}.{}", 1000 + item_idx % self.prefixes.max(1), file_idx, item_idx),
+            "type": "journal-article",
+            "is-referenced-by-count": rng.below(500),
+            "reference": references,
+        })
+    }
+
+    fn reference(&self, rng: &mut SplitMix64, stats: &mut SyntheticStats) -> Value {
+        let roll = unit(rng);
+        let asserted = self.asserted_doi_rate;
+        let mined = asserted + self.mined_doi_rate;
+        let arxiv = mined + self.arxiv_rate;
+
+        if roll < asserted {
+            stats.asserted_dois += 1;
+            json!({
+                "key": format!("ref{}", rng.below(1000)),
+                "DOI": self.cited_doi(rng),
+                "doi-asserted-by": if unit(rng) < 0.5 { "publisher" } else { "crossref" },
+                "article-title": "A synthetic study of citation graphs",
+            })
+        } else if roll < mined {
+            stats.mined_dois += 1;
+            json!({
+                "unstructured": format!(
+                    "Author A, Author B. A synthetic study. J Synth. 2020;12:34. doi:{}",
+                    self.cited_doi(rng)
+                ),
+            })
+        } else if roll < arxiv {
+            stats.arxiv_ids += 1;
+            json!({
+                "unstructured": format!(
+                    "Author C. Preprint results. arXiv:{:02}{:02}.{:05}",
+                    15 + rng.below(10),
+                    1 + rng.below(12),
+                    rng.below(100_000)
+                ),
+            })
+        } else {
+            json!({
+                "unstructured": "Author D. A reference without identifiers. Proc Synth Conf. 2019.",
+            })
+        }
+    }
+
+    /// Cited DOI skewed towards a small set of highly cited works
+    fn cited_doi(&self, rng: &mut SplitMix64) -> String {
+        let skewed = unit(rng) * unit(rng);
+        let work = (skewed * 100_000.0) as u64;
+        format!(
+            "10.{}/cited.{}",
+            1000 + work % self.prefixes.max(1) as u64,
+            work
+        )
+    }
+}
+
+/// Uniform in [0, 1)
+fn unit(rng: &mut SplitMix64) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use tar::Archive;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_synthetic_snapshot_shape() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("snapshot.tar.gz");
+        let snapshot = SyntheticSnapshot {
+            files: 3,
+            items_per_file: 4,
+            references_per_item: 50,
+            ..Default::default()
+        };
+
+        let stats = snapshot.write_tar_gz(&path).unwrap();
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.items, snapshot.items());
+        assert_eq!(stats.references, 600);
+        assert!(stats.asserted_dois > 0 && stats.mined_dois > 0 && stats.arxiv_ids > 0);
+
+        let mut archive = Archive::new(GzDecoder::new(File::open(&path).unwrap()));
+        let mut items = 0;
+        for entry in archive.entries().unwrap() {
+            let json: Value = serde_json::from_reader(entry.unwrap()).unwrap();
+            let works = json["items"].as_array().unwrap();
+            assert!(works.iter().all(|w| w["DOI"].is_string()));
+            items += works.len();
+        }
+        assert_eq!(items, 12);
+
+        // Same seed, same archive contents
+        let again = dir.path().join("again.tar.gz");
+        assert_eq!(snapshot.write_tar_gz(&again).unwrap(), stats);
+    }
+}
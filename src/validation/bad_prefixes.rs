@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::extract::doi_prefix;
+
+/// Prefixes whose DOIs keep failing to resolve, skipped once they reach a threshold
+///
+/// OCR and extraction noise produces DOIs under prefixes nobody registered (10.0000,
+/// 10.1000/xyz123 mangled into 10.1OOO): thousands of requests that all 404. After
+/// `threshold` DOIs under a prefix have failed and none has resolved, the remaining
+/// DOIs under it are counted unresolved without a request. One resolving DOI clears the
+/// prefix for the rest of the run. Only definite answers (a response that is neither
+/// throttling nor a server error) count, so an outage never marks a prefix bad.
+#[derive(Debug)]
+pub struct BadPrefixes {
+    threshold: usize,
+    prefixes: Mutex<HashMap<String, PrefixState>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PrefixState {
+    /// DOIs failed so far, and DOIs skipped once that reached the threshold
+    Failing { failed: usize, skipped: usize },
+    /// At least one DOI under the prefix resolved
+    Resolves,
+}
+
+impl BadPrefixes {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            prefixes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether to skip the request for `doi`; counts the skip when it is
+    pub fn skip(&self, doi: &str) -> bool {
+        let Some(prefix) = doi_prefix(doi) else {
+            return false;
+        };
+        let mut prefixes = self.prefixes.lock().expect("bad prefix lock poisoned");
+        match prefixes.get_mut(&prefix) {
+            Some(PrefixState::Failing { failed, skipped }) if *failed >= self.threshold => {
+                *skipped += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record the definite result of a request (or a checkpointed one) for `doi`
+    pub fn observe(&self, doi: &str, resolves: bool) {
+        let Some(prefix) = doi_prefix(doi) else {
+            return;
+        };
+        let mut prefixes = self.prefixes.lock().expect("bad prefix lock poisoned");
+        let state = prefixes.entry(prefix).or_insert(PrefixState::Failing {
+            failed: 0,
+            skipped: 0,
+        });
+        if resolves {
+            *state = PrefixState::Resolves;
+        } else if let PrefixState::Failing { failed, .. } = state {
+            *failed += 1;
+        }
+    }
+
+    /// Prefixes that had requests skipped, with the number skipped, most skipped first
+    pub fn skipped(&self) -> Vec<(String, usize)> {
+        let prefixes = self.prefixes.lock().expect("bad prefix lock poisoned");
+        let mut skipped: Vec<(String, usize)> = prefixes
+            .iter()
+            .filter_map(|(prefix, state)| match *state {
+                PrefixState::Failing { skipped, .. } if skipped > 0 => {
+                    Some((prefix.clone(), skipped))
+                }
+                _ => None,
+            })
+            .collect();
+        skipped.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bad_prefixes_skip_after_threshold() {
+        let bad = BadPrefixes::new(2);
+        bad.observe("10.0000/a", false);
+        assert!(!bad.skip("10.0000/b"));
+        bad.observe("10.0000/b", false);
+        assert!(bad.skip("10.0000/c"));
+        assert!(bad.skip("10.0000/D"));
+
+        // A prefix with a resolving DOI is never skipped
+        bad.observe("10.1234/a", false);
+        bad.observe("10.1234/b", true);
+        bad.observe("10.1234/c", false);
+        bad.observe("10.1234/d", false);
+        assert!(!bad.skip("10.1234/e"));
+
+        assert!(!bad.skip("not-a-doi"));
+        assert_eq!(bad.skipped(), [("10.0000".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::error::Result;
+use log::info;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::cli::Source;
+use crate::common::{open_sink_with, CitationRecord, OutputOptions};
+use crate::streaming::inversion::REFERENCE_KEY_FIELD;
+
+/// Resolved citations regrouped by citing work, for the citing-side output
+///
+/// The validated outputs hold one record per cited work. This collects the same
+/// citations keyed by citing DOI, across every source added, so each citing work
+/// can be written with the list of works it cites.
+#[derive(Debug, Default)]
+pub struct CitingWorks {
+    works: BTreeMap<String, CitingWork>,
+}
+
+#[derive(Debug, Default)]
+struct CitingWork {
+    work_type: Option<String>,
+    container_title: Option<String>,
+    publisher: Option<String>,
+    references: Vec<Value>,
+}
+
+impl CitingWorks {
+    /// Add the citations of validated records from one source target
+    ///
+    /// `target` is the pipeline source the records were validated for: arXiv records
+    /// are listed with their arXiv ID; Crossref and DataCite records with the source
+    /// they validated against.
+    pub fn add<'a>(
+        &mut self,
+        records: impl IntoIterator<Item = &'a (CitationRecord, Source)>,
+        target: Source,
+    ) {
+        for (record, source) in records {
+            let source = if target == Source::Arxiv {
+                Source::Arxiv
+            } else {
+                *source
+            };
+            for entry in &record.cited_by {
+                let Some(citing) = entry.get("doi").and_then(Value::as_str) else {
+                    continue;
+                };
+                let work = self.works.entry(citing.to_string()).or_default();
+                if work.work_type.is_none() {
+                    work.work_type = entry.get("type").and_then(Value::as_str).map(String::from);
+                }
+                if work.container_title.is_none() {
+                    work.container_title = entry
+                        .get("container_title")
+                        .and_then(Value::as_str)
+                        .map(String::from);
+                }
+                if work.publisher.is_none() {
+                    work.publisher = entry
+                        .get("citing_publisher")
+                        .and_then(Value::as_str)
+                        .map(String::from);
+                }
+                work.references.push(cited_reference(record, source, entry));
+            }
+        }
+    }
+
+    /// Number of citing works collected
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.works.len()
+    }
+
+    /// Write one record per citing work, ordered by DOI, returning records written
+    ///
+    /// Each record has the citing `doi`, its `type`, `container_title` and
+    /// `citing_publisher` when known, `reference_count` (resolved references only) and
+    /// `references`, sorted by cited DOI.
+    pub fn write(self, path: &str, options: &OutputOptions) -> Result<usize> {
+        info!("Writing {} citing works to: {}", self.works.len(), path);
+        let mut sink = open_sink_with(Path::new(path), options)?;
+        for (doi, mut work) in self.works {
+            work.references
+                .sort_by(|a, b| a["doi"].as_str().cmp(&b["doi"].as_str()));
+            let mut record = json!({
+                "doi": doi,
+                "reference_count": work.references.len(),
+                "references": work.references,
+            });
+            if let Some(work_type) = work.work_type {
+                record["type"] = Value::String(work_type);
+            }
+            if let Some(container_title) = work.container_title {
+                record["container_title"] = Value::String(container_title);
+            }
+            if let Some(publisher) = work.publisher {
+                record["citing_publisher"] = Value::String(publisher);
+            }
+            sink.write_record(&record)?;
+        }
+        sink.finish()
+    }
+}
+
+/// Reference entry for a cited work: its IDs, source, and how it was cited and confirmed
+fn cited_reference(record: &CitationRecord, source: Source, entry: &Value) -> Value {
+    let mut reference = json!({
+        "doi": record.doi,
+        "source": source.to_string(),
+        "provenance": entry.get("provenance").and_then(Value::as_str).unwrap_or("mined"),
+    });
+    if source == Source::Arxiv {
+        let arxiv_id = record.arxiv_id.as_deref().or_else(|| {
+            record
+                .doi
+                .strip_prefix("10.48550/arXiv.")
+                .or_else(|| record.doi.strip_prefix("10.48550/arxiv."))
+        });
+        if let Some(arxiv_id) = arxiv_id {
+            reference["arxiv_id"] = Value::from(arxiv_id);
+        }
+        if let Some(ref published_doi) = record.published_doi {
+            reference["published_doi"] = Value::from(published_doi.as_str());
+        }
+    }
+    if let Some(ref publisher) = record.cited_publisher {
+        reference["cited_publisher"] = Value::from(publisher.as_str());
+    }
+    if let Some(method) = record.validation_method {
+        reference["validation_method"] = json!(method);
+    }
+    if let Some(status) = record.validation_status {
+        reference["validation_status"] = json!(status);
+    }
+    let raw_matches: Vec<&str> = entry
+        .get("matches")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.get("raw_match").and_then(Value::as_str))
+        .collect();
+    if !raw_matches.is_empty() {
+        reference["raw_matches"] = json!(raw_matches);
+    }
+    let mut reference_keys: Vec<&str> = entry
+        .get("matches")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.get(REFERENCE_KEY_FIELD).and_then(Value::as_str))
+        .collect();
+    reference_keys.sort_unstable();
+    reference_keys.dedup();
+    if !reference_keys.is_empty() {
+        reference["reference_keys"] = json!(reference_keys);
+    }
+    reference
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ValidationMethod;
+    use std::io::BufRead;
+    use tempfile::tempdir;
+
+    fn record(doi: &str, cited_by: Vec<Value>) -> CitationRecord {
+        CitationRecord {
+            doi: doi.to_string(),
+            arxiv_id: None,
+            id_type: None,
+            reference_count: cited_by.len(),
+            citation_count: cited_by.len(),
+            cited_by,
+            published_doi: None,
+            registration_agency: None,
+            http_error: None,
+            validation_method: Some(ValidationMethod::Index),
+            validation_status: None,
+            asserted_citation_count: None,
+            cited_publisher: None,
+            provenance_counts: None,
+        }
+    }
+
+    #[test]
+    fn test_citing_works_grouped_by_citing_doi() {
+        let citing_a = json!({
+            "doi": "10.1111/a",
+            "provenance": "publisher",
+            "type": "journal-article",
+            "matches": [
+                {"raw_match": "10.5678/y", "provenance": "publisher", "reference_key": "ref1"}
+            ],
+        });
+        let citing_b = json!({"doi": "10.2222/b", "provenance": "mined", "matches": []});
+
+        let mut works = CitingWorks::default();
+        works.add(
+            &[
+                (
+                    record("10.5678/y", vec![citing_a.clone()]),
+                    Source::Crossref,
+                ),
+                (
+                    record("10.1234/x", vec![citing_a, citing_b.clone()]),
+                    Source::Datacite,
+                ),
+            ],
+            Source::All,
+        );
+        works.add(
+            &[(
+                record("10.48550/arXiv.2403.12345", vec![citing_b]),
+                Source::Datacite,
+            )],
+            Source::Arxiv,
+        );
+        assert_eq!(works.len(), 2);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("citing.jsonl");
+        assert_eq!(
+            works
+                .write(path.to_str().unwrap(), &OutputOptions::default())
+                .unwrap(),
+            2
+        );
+
+        let lines: Vec<Value> = std::io::BufReader::new(std::fs::File::open(&path).unwrap())
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(lines[0]["doi"], "10.1111/a");
+        assert_eq!(lines[0]["type"], "journal-article");
+        assert_eq!(lines[0]["reference_count"], 2);
+        assert_eq!(
+            lines[0]["references"][0],
+            json!({
+                "doi": "10.1234/x",
+                "source": "datacite",
+                "provenance": "publisher",
+                "validation_method": "index",
+                "raw_matches": ["10.5678/y"],
+                "reference_keys": ["ref1"],
+            })
+        );
+        assert_eq!(lines[0]["references"][1]["source"], "crossref");
+
+        assert_eq!(lines[1]["doi"], "10.2222/b");
+        assert!(lines[1].get("type").is_none());
+        let arxiv = &lines[1]["references"][1];
+        assert_eq!(arxiv["source"], "arxiv");
+        assert_eq!(arxiv["arxiv_id"], "2403.12345");
+        assert!(lines[1]["references"][0].get("raw_matches").is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Share of a window's requests that may be throttled or fail before the limit is cut
+const CONGESTION_RATE: f64 = 0.05;
+/// Window latency, relative to the best window seen, above which the limit stops growing
+const LATENCY_TOLERANCE: f64 = 2.0;
+/// Mean latency below which latency never holds the limit back (timer noise)
+const LATENCY_FLOOR: Duration = Duration::from_millis(50);
+/// Weight of the newest window in the smoothed request rate
+const RATE_SMOOTHING: f64 = 0.2;
+
+/// Lower and upper bound on the adaptive HTTP concurrency limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyBounds {
+    pub min: usize,
+    pub max: usize,
+}
+
+/// How one HTTP request ended, as seen by the concurrency controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    /// Any response other than a throttling or server error
+    Completed,
+    /// 429 or 503: the server asked us to slow down
+    Throttled,
+    /// Timeout, connection error or other 5xx
+    Failed,
+}
+
+/// Summary of an adaptive run, for reporting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConcurrencyReport {
+    pub final_limit: usize,
+    pub peak_limit: usize,
+    pub throttled: usize,
+    pub failed: usize,
+    /// Smoothed requests per second over the last windows
+    pub steady_rate: f64,
+}
+
+/// Concurrency limiter that adapts its limit with AIMD
+///
+/// Requests complete in windows of `limit` requests (about one round trip at full
+/// concurrency). At the end of a window the limit is halved when more than 5% of its
+/// requests were throttled or failed, otherwise raised by one while the window's mean
+/// latency stays within twice the best window's. The limit stays within `bounds`.
+pub struct AdaptiveLimiter {
+    bounds: ConcurrencyBounds,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+struct LimiterState {
+    limit: usize,
+    peak_limit: usize,
+    in_flight: usize,
+    window: Window,
+    best_latency: Option<Duration>,
+    throttled: usize,
+    failed: usize,
+    steady_rate: Option<f64>,
+}
+
+struct Window {
+    started: Instant,
+    completed: usize,
+    congested: usize,
+    latency: Duration,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            completed: 0,
+            congested: 0,
+            latency: Duration::ZERO,
+        }
+    }
+}
+
+/// Slot held by one in-flight request; report its outcome with `finish`
+pub struct LimiterPermit<'a> {
+    limiter: &'a AdaptiveLimiter,
+    started: Instant,
+    finished: bool,
+}
+
+impl AdaptiveLimiter {
+    /// Start at `initial`, clamped to `bounds`; a minimum above the maximum is lowered
+    pub fn new(initial: usize, bounds: ConcurrencyBounds) -> Self {
+        let max = bounds.max.max(1);
+        let bounds = ConcurrencyBounds {
+            min: bounds.min.clamp(1, max),
+            max,
+        };
+        let limit = initial.clamp(bounds.min, bounds.max);
+        Self {
+            bounds,
+            state: Mutex::new(LimiterState {
+                limit,
+                peak_limit: limit,
+                in_flight: 0,
+                window: Window::new(),
+                best_latency: None,
+                throttled: 0,
+                failed: 0,
+                steady_rate: None,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Wait until fewer than `limit` requests are in flight
+    pub async fn acquire(&self) -> LimiterPermit<'_> {
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            {
+                let mut state = self.lock();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    return LimiterPermit {
+                        limiter: self,
+                        started: Instant::now(),
+                        finished: false,
+                    };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Current concurrency limit
+    #[cfg(test)]
+    fn limit(&self) -> usize {
+        self.lock().limit
+    }
+
+    pub fn report(&self) -> ConcurrencyReport {
+        let state = self.lock();
+        ConcurrencyReport {
+            final_limit: state.limit,
+            peak_limit: state.peak_limit,
+            throttled: state.throttled,
+            failed: state.failed,
+            steady_rate: state.steady_rate.unwrap_or_else(|| {
+                let elapsed = state.window.started.elapsed().as_secs_f64();
+                if elapsed > 0.0 {
+                    state.window.completed as f64 / elapsed
+                } else {
+                    0.0
+                }
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn release(&self, outcome: RequestOutcome, latency: Duration) {
+        {
+            let mut guard = self.lock();
+            let state = &mut *guard;
+            state.in_flight -= 1;
+            match outcome {
+                RequestOutcome::Completed => {}
+                RequestOutcome::Throttled => state.throttled += 1,
+                RequestOutcome::Failed => state.failed += 1,
+            }
+            let window = &mut state.window;
+            window.completed += 1;
+            window.latency += latency;
+            if outcome != RequestOutcome::Completed {
+                window.congested += 1;
+            }
+            if window.completed >= state.limit {
+                self.end_window(state);
+            }
+        }
+        self.released.notify_waiters();
+    }
+
+    fn end_window(&self, state: &mut LimiterState) {
+        let window = std::mem::replace(&mut state.window, Window::new());
+        let elapsed = window.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            let rate = window.completed as f64 / elapsed;
+            state.steady_rate = Some(match state.steady_rate {
+                Some(smoothed) => smoothed + RATE_SMOOTHING * (rate - smoothed),
+                None => rate,
+            });
+        }
+
+        let mean_latency = window.latency / window.completed as u32;
+        let congested = window.congested as f64 / window.completed as f64 > CONGESTION_RATE;
+        if congested {
+            state.limit = (state.limit / 2).max(self.bounds.min);
+            return;
+        }
+
+        let best = *state.best_latency.get_or_insert(mean_latency);
+        if mean_latency < best {
+            state.best_latency = Some(mean_latency);
+        }
+        if mean_latency <= LATENCY_FLOOR
+            || mean_latency.as_secs_f64() <= best.as_secs_f64() * LATENCY_TOLERANCE
+        {
+            state.limit = (state.limit + 1).min(self.bounds.max);
+            state.peak_limit = state.peak_limit.max(state.limit);
+        }
+    }
+}
+
+impl LimiterPermit<'_> {
+    /// Release the slot, feeding the request's outcome and latency to the controller
+    pub fn finish(mut self, outcome: RequestOutcome) {
+        self.finished = true;
+        self.limiter.release(outcome, self.started.elapsed());
+    }
+}
+
+impl Drop for LimiterPermit<'_> {
+    fn drop(&mut self) {
+        // A request dropped without an outcome (e.g. cancelled) frees its slot only
+        if !self.finished {
+            let mut state = self.limiter.lock();
+            state.in_flight -= 1;
+            drop(state);
+            self.limiter.released.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: ConcurrencyBounds = ConcurrencyBounds { min: 2, max: 8 };
+
+    async fn run_window(limiter: &AdaptiveLimiter, outcome: RequestOutcome) {
+        for _ in 0..limiter.limit() {
+            limiter.acquire().await.finish(outcome);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_additive_increase_up_to_max() {
+        let limiter = AdaptiveLimiter::new(4, BOUNDS);
+        run_window(&limiter, RequestOutcome::Completed).await;
+        assert_eq!(limiter.limit(), 5);
+        for _ in 0..10 {
+            run_window(&limiter, RequestOutcome::Completed).await;
+        }
+        assert_eq!(limiter.limit(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_multiplicative_decrease_down_to_min() {
+        let limiter = AdaptiveLimiter::new(8, BOUNDS);
+        run_window(&limiter, RequestOutcome::Throttled).await;
+        assert_eq!(limiter.limit(), 4);
+        run_window(&limiter, RequestOutcome::Failed).await;
+        run_window(&limiter, RequestOutcome::Failed).await;
+        assert_eq!(limiter.limit(), 2);
+
+        let report = limiter.report();
+        assert_eq!(report.throttled, 8);
+        assert_eq!(report.failed, 6);
+        assert_eq!(report.peak_limit, 8);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_a_free_slot() {
+        let limiter = AdaptiveLimiter::new(1, ConcurrencyBounds { min: 1, max: 1 });
+        let held = limiter.acquire().await;
+        let waiting = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(waiting.is_err());
+
+        drop(held);
+        let next = tokio::time::timeout(Duration::from_secs(1), limiter.acquire()).await;
+        assert!(next.is_ok());
+    }
+
+    #[test]
+    fn test_initial_limit_is_clamped() {
+        assert_eq!(AdaptiveLimiter::new(50, BOUNDS).limit(), 8);
+        assert_eq!(AdaptiveLimiter::new(0, BOUNDS).limit(), 2);
+        let inverted = ConcurrencyBounds { min: 10, max: 5 };
+        assert_eq!(AdaptiveLimiter::new(50, inverted).limit(), 5);
+    }
+}
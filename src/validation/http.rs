@@ -1,37 +1,344 @@
-use log::debug;
-use reqwest::Client;
+use futures::future::BoxFuture;
+use log::{debug, info};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
-/// Check if a DOI resolves via HTTP HEAD request
-pub async fn check_doi_resolves(client: &Client, doi: &str, timeout: Duration) -> bool {
-    let url = format!("https://doi.org/{}", doi);
+use super::http_checkpoint::read_http_results;
+use super::RequestOutcome;
+use crate::common::{HttpClient, HttpClientConfig, HttpError};
+use crate::error::Result;
 
-    match client.head(&url).timeout(timeout).send().await {
-        Ok(resp) => {
-            let status = resp.status();
-            status.is_redirection() || status.is_success()
+/// Resolves DOIs for the HTTP fallback
+///
+/// [`DoiOrgResolver`] asks doi.org. Tests and offline runs plug in another, such as a
+/// [`FileResolver`], through [`HttpFallback::resolver`](super::HttpFallback::resolver).
+pub trait DoiResolver: Debug + Send + Sync {
+    /// Check if a DOI resolves, returning why not when it does not
+    fn check<'a>(&'a self, doi: &'a str) -> BoxFuture<'a, Option<HttpError>>;
+}
+
+/// Resolver sending a HEAD request to doi.org for each DOI
+#[derive(Debug, Clone)]
+pub struct DoiOrgResolver {
+    client: HttpClient,
+    timeout: Duration,
+}
+
+impl DoiOrgResolver {
+    pub fn new(timeout: Duration, config: &HttpClientConfig) -> Result<Self> {
+        Ok(Self {
+            client: create_doi_client(config)?,
+            timeout,
+        })
+    }
+}
+
+impl DoiResolver for DoiOrgResolver {
+    fn check<'a>(&'a self, doi: &'a str) -> BoxFuture<'a, Option<HttpError>> {
+        Box::pin(check_doi(&self.client, doi, self.timeout))
+    }
+}
+
+/// Resolver answering from recorded results, without network access
+///
+/// DOIs it has no result for fail with a connection error, which is transient, so an
+/// HTTP checkpoint does not keep them and a later run against doi.org checks them.
+#[derive(Debug, Default)]
+pub struct FileResolver {
+    results: HashMap<String, Option<HttpError>>,
+}
+
+impl FileResolver {
+    /// Resolver answering with `results`: each DOI and why it does not resolve
+    #[cfg(test)]
+    pub fn new(results: impl IntoIterator<Item = (String, Option<HttpError>)>) -> Self {
+        Self {
+            results: results
+                .into_iter()
+                .map(|(doi, error)| (doi.to_lowercase(), error))
+                .collect(),
         }
+    }
+
+    /// Load results from a file in the HTTP checkpoint format: JSONL lines of `doi`,
+    /// `resolves` and optional `http_error`
+    ///
+    /// A DOI that did not resolve without a recorded error counts as not found.
+    pub fn open(path: &Path) -> Result<Self> {
+        let results = read_http_results(path)?;
+        info!(
+            "Loaded {} HTTP results to resolve from: {:?}",
+            results.len(),
+            path
+        );
+        Ok(Self {
+            results: results
+                .into_iter()
+                .map(|(doi, (resolves, error))| {
+                    (doi, error.or((!resolves).then_some(HttpError::NotFound)))
+                })
+                .collect(),
+        })
+    }
+}
+
+impl DoiResolver for FileResolver {
+    fn check<'a>(&'a self, doi: &'a str) -> BoxFuture<'a, Option<HttpError>> {
+        let error = match self.results.get(&doi.to_lowercase()) {
+            Some(error) => *error,
+            None => Some(HttpError::Connection),
+        };
+        Box::pin(std::future::ready(error))
+    }
+}
+
+/// Resolver for the HTTP fallback: a [`FileResolver`] over `path` when given, otherwise
+/// None to ask doi.org
+pub fn open_resolver(path: Option<&str>) -> Result<Option<Arc<dyn DoiResolver>>> {
+    Ok(match path {
+        Some(path) => Some(Arc::new(FileResolver::open(Path::new(path))?)),
+        None => None,
+    })
+}
+
+/// Check if a DOI resolves, returning why not when it does not
+///
+/// A redirect or success resolves. See [`request_outcome`] for how the error counts
+/// for concurrency control.
+pub async fn check_doi(client: &HttpClient, doi: &str, timeout: Duration) -> Option<HttpError> {
+    let url = format!("https://doi.org/{}", encode_doi_path(doi));
+
+    let _slot = client.slot(&url).await;
+    match client.client().head(&url).timeout(timeout).send().await {
+        Ok(resp) => classify_status(resp.status()),
         Err(e) => {
             debug!("DOI resolution failed for {}: {}", doi, e);
-            false
+            Some(classify_request_error(&e))
+        }
+    }
+}
+
+/// How a check counts for concurrency control
+///
+/// Throttling counts as throttled; server errors, timeouts and connection errors as
+/// failures; a DOI doi.org answered for, resolving or not, as completed.
+pub fn request_outcome(error: Option<HttpError>) -> RequestOutcome {
+    match error {
+        Some(HttpError::Throttled) => RequestOutcome::Throttled,
+        Some(
+            HttpError::ServerError
+            | HttpError::Timeout
+            | HttpError::Connection
+            | HttpError::Request,
+        ) => RequestOutcome::Failed,
+        Some(HttpError::NotFound | HttpError::ClientError) | None => RequestOutcome::Completed,
+    }
+}
+
+/// Error of a doi.org response, None when it resolves the DOI
+fn classify_status(status: StatusCode) -> Option<HttpError> {
+    match status {
+        status if status.is_redirection() || status.is_success() => None,
+        StatusCode::NOT_FOUND => Some(HttpError::NotFound),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+            Some(HttpError::Throttled)
         }
+        status if status.is_server_error() => Some(HttpError::ServerError),
+        _ => Some(HttpError::ClientError),
+    }
+}
+
+fn classify_request_error(error: &reqwest::Error) -> HttpError {
+    if error.is_timeout() {
+        HttpError::Timeout
+    } else if error.is_connect() {
+        HttpError::Connection
+    } else {
+        HttpError::Request
+    }
+}
+
+/// Registration agency of a DOI, from the doi.org RA endpoint
+///
+/// Returns the agency name lowercased (e.g. "crossref", "datacite", "medra"),
+/// "unregistered" when doi.org reports the DOI does not exist, "invalid" for a
+/// malformed DOI, or None when the request fails.
+pub async fn lookup_registration_agency(
+    client: &HttpClient,
+    doi: &str,
+    timeout: Duration,
+) -> Option<String> {
+    let url = format!("https://doi.org/ra/{}", encode_doi_path(doi));
+
+    let _slot = client.slot(&url).await;
+    let text = match client.client().get(&url).timeout(timeout).send().await {
+        Ok(resp) => resp.text().await,
+        Err(e) => Err(e),
+    };
+    match text {
+        Ok(text) => match serde_json::from_str(&text) {
+            Ok(body) => parse_registration_agency(&body),
+            Err(e) => {
+                debug!("Unreadable RA response for {}: {}", doi, e);
+                None
+            }
+        },
+        Err(e) => {
+            debug!("RA lookup failed for {}: {}", doi, e);
+            None
+        }
+    }
+}
+
+/// A DOI as a doi.org URL path, percent-encoding all but unreserved characters and '/'
+///
+/// Sent as is, `#` and `?` would end the path early and `;` would start path
+/// parameters, while spaces, `<`, `>` or non-ASCII characters make the URL invalid.
+fn encode_doi_path(doi: &str) -> String {
+    let mut path = String::with_capacity(doi.len());
+    for byte in doi.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    path
+}
+
+/// Agency from an RA endpoint response: `[{"DOI": ..., "RA": "Crossref"}]`, or
+/// `[{"DOI": ..., "status": "DOI does not exist"}]` for unknown DOIs
+fn parse_registration_agency(body: &Value) -> Option<String> {
+    let entry = body.as_array()?.first()?;
+    if let Some(agency) = entry.get("RA").and_then(Value::as_str) {
+        return Some(agency.to_lowercase());
+    }
+    match entry.get("status").and_then(Value::as_str) {
+        Some("DOI does not exist") => Some("unregistered".to_string()),
+        Some("Invalid DOI") => Some("invalid".to_string()),
+        _ => None,
     }
 }
 
 /// Create an HTTP client configured for DOI resolution
-pub fn create_doi_client() -> reqwest::Result<Client> {
-    Client::builder()
+///
+/// Redirects are not followed: a redirect from doi.org is the answer.
+pub fn create_doi_client(config: &HttpClientConfig) -> Result<HttpClient> {
+    let client = config
+        .builder()?
         .redirect(reqwest::redirect::Policy::none())
-        .build()
+        .build()?;
+    Ok(config.limit_per_host(client))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_encode_doi_path() {
+        assert_eq!(
+            encode_doi_path("10.1234/abc.def-1_2"),
+            "10.1234/abc.def-1_2"
+        );
+        assert_eq!(
+            encode_doi_path("10.1002/(SICI)1097-4636(199708)37:2<282::AID-JBM17>3.0.CO;2-H"),
+            "10.1002/%28SICI%291097-4636%28199708%2937%3A2%3C282%3A%3AAID-JBM17%3E3.0.CO%3B2-H"
+        );
+        assert_eq!(encode_doi_path("10.1234/a#b?c d"), "10.1234/a%23b%3Fc%20d");
+        assert_eq!(encode_doi_path("10.1234/100%é"), "10.1234/100%25%C3%A9");
+    }
+
+    #[test]
+    fn test_parse_registration_agency() {
+        let parse = |body: &str| parse_registration_agency(&serde_json::from_str(body).unwrap());
+        assert_eq!(
+            parse(r#"[{"DOI": "10.1234/x", "RA": "Crossref"}]"#).as_deref(),
+            Some("crossref")
+        );
+        assert_eq!(
+            parse(r#"[{"DOI": "10.1234/x", "status": "DOI does not exist"}]"#).as_deref(),
+            Some("unregistered")
+        );
+        assert_eq!(
+            parse(r#"[{"DOI": "x", "status": "Invalid DOI"}]"#).as_deref(),
+            Some("invalid")
+        );
+        assert_eq!(parse(r#"{"error": "rate limited"}"#), None);
+    }
+
+    #[test]
+    fn test_classify_status() {
+        assert_eq!(classify_status(StatusCode::FOUND), None);
+        assert_eq!(classify_status(StatusCode::OK), None);
+        assert_eq!(
+            classify_status(StatusCode::NOT_FOUND),
+            Some(HttpError::NotFound)
+        );
+        assert_eq!(
+            classify_status(StatusCode::TOO_MANY_REQUESTS),
+            Some(HttpError::Throttled)
+        );
+        assert_eq!(
+            classify_status(StatusCode::BAD_GATEWAY),
+            Some(HttpError::ServerError)
+        );
+        assert_eq!(
+            classify_status(StatusCode::FORBIDDEN),
+            Some(HttpError::ClientError)
+        );
+
+        assert_eq!(request_outcome(None), RequestOutcome::Completed);
+        assert_eq!(
+            request_outcome(Some(HttpError::NotFound)),
+            RequestOutcome::Completed
+        );
+        assert_eq!(
+            request_outcome(Some(HttpError::Throttled)),
+            RequestOutcome::Throttled
+        );
+        assert_eq!(
+            request_outcome(Some(HttpError::Timeout)),
+            RequestOutcome::Failed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_resolver() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.jsonl");
+        std::fs::write(
+            &path,
+            concat!(
+                "{\"doi\":\"10.1234/A\",\"resolves\":true}\n",
+                "{\"doi\":\"10.1234/b\",\"resolves\":false}\n",
+                "{\"doi\":\"10.1234/c\",\"resolves\":false,\"http_error\":\"server-error\"}\n",
+            ),
+        )
+        .unwrap();
+
+        let resolver = FileResolver::open(&path).unwrap();
+        assert_eq!(resolver.check("10.1234/a").await, None);
+        assert_eq!(resolver.check("10.1234/b").await, Some(HttpError::NotFound));
+        assert_eq!(
+            resolver.check("10.1234/c").await,
+            Some(HttpError::ServerError)
+        );
+        // Unknown DOIs fail transiently, to be checked online later
+        assert_eq!(
+            resolver.check("10.1234/d").await,
+            Some(HttpError::Connection)
+        );
+    }
+
     #[test]
     fn test_create_doi_client() {
-        let client = create_doi_client();
+        let client = create_doi_client(&HttpClientConfig::default());
         assert!(client.is_ok());
     }
 }
@@ -0,0 +1,208 @@
+use crate::common::HttpError;
+use crate::error::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Flush the sidecar file after this many new results
+const FLUSH_INTERVAL: usize = 1000;
+
+/// One persisted HTTP resolution result
+#[derive(Debug, Serialize, Deserialize)]
+struct HttpResult {
+    doi: String,
+    resolves: bool,
+    /// Why it did not resolve; absent in checkpoints written before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    http_error: Option<HttpError>,
+}
+
+/// Persisted HTTP fallback results, so an interrupted validation can skip DOIs
+/// that were already checked
+///
+/// Results are appended to a JSONL sidecar file as they arrive; reopening the file
+/// loads them back. A partially written last line (from a crash) is ignored.
+/// Failures with a transient [`HttpError`] (a timeout, throttling) are kept in the file
+/// but not reused, so a rerun checks only those DOIs again.
+pub struct HttpCheckpoint {
+    path: PathBuf,
+    results: HashMap<String, (bool, Option<HttpError>)>,
+    writer: BufWriter<File>,
+    unflushed: usize,
+}
+
+impl HttpCheckpoint {
+    /// Open (or create) a checkpoint file, loading any previously recorded results
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut results = HashMap::new();
+
+        if path.exists() {
+            results = read_http_results(path)?;
+            info!(
+                "Loaded {} HTTP results from checkpoint: {:?}",
+                results.len(),
+                path
+            );
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(path)
+            .with_context(|| format!("Failed to open HTTP checkpoint: {:?}", path))?;
+
+        // Terminate a partially written last line so new results start on their own line
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n")?;
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            results,
+            writer: BufWriter::new(file),
+            unflushed: 0,
+        })
+    }
+
+    /// Previously recorded result for a DOI and why it did not resolve, unless its
+    /// failure was transient
+    pub fn get(&self, doi: &str) -> Option<(bool, Option<HttpError>)> {
+        self.results
+            .get(&doi.to_lowercase())
+            .copied()
+            .filter(|(_, error)| !error.is_some_and(HttpError::is_transient))
+    }
+
+    /// Record a result, flushing to disk periodically
+    pub fn record(
+        &mut self,
+        doi: &str,
+        resolves: bool,
+        http_error: Option<HttpError>,
+    ) -> Result<()> {
+        let result = HttpResult {
+            doi: doi.to_lowercase(),
+            resolves,
+            http_error,
+        };
+        writeln!(self.writer, "{}", serde_json::to_string(&result)?)?;
+        self.results.insert(result.doi, (resolves, http_error));
+
+        self.unflushed += 1;
+        if self.unflushed >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered results to disk
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to flush HTTP checkpoint: {:?}", self.path))?;
+        self.unflushed = 0;
+        Ok(())
+    }
+
+    /// Number of recorded results
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.results.len()
+    }
+}
+
+/// Results in an HTTP checkpoint file, keyed by lowercased DOI
+///
+/// A later line for a DOI replaces an earlier one; malformed lines are skipped with a
+/// warning.
+pub(super) fn read_http_results(path: &Path) -> Result<HashMap<String, (bool, Option<HttpError>)>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open HTTP checkpoint: {:?}", path))?;
+    let mut results = HashMap::new();
+    for (line_num, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<HttpResult>(&line) {
+            Ok(result) => {
+                results.insert(
+                    result.doi.to_lowercase(),
+                    (result.resolves, result.http_error),
+                );
+            }
+            Err(e) => warn!(
+                "Skipping malformed HTTP checkpoint line {} in {:?}: {}",
+                line_num + 1,
+                path,
+                e
+            ),
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_http_checkpoint_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("http.jsonl");
+
+        let mut checkpoint = HttpCheckpoint::open(&path).unwrap();
+        assert_eq!(checkpoint.len(), 0);
+        checkpoint.record("10.1234/A", true, None).unwrap();
+        checkpoint
+            .record("10.1234/b", false, Some(HttpError::NotFound))
+            .unwrap();
+        checkpoint
+            .record("10.1234/t", false, Some(HttpError::Timeout))
+            .unwrap();
+        checkpoint.flush().unwrap();
+        drop(checkpoint);
+
+        let reopened = HttpCheckpoint::open(&path).unwrap();
+        assert_eq!(reopened.len(), 3);
+        assert_eq!(reopened.get("10.1234/a"), Some((true, None)));
+        assert_eq!(
+            reopened.get("10.1234/B"),
+            Some((false, Some(HttpError::NotFound)))
+        );
+        // Timed out: checked again
+        assert_eq!(reopened.get("10.1234/t"), None);
+        assert_eq!(reopened.get("10.1234/c"), None);
+    }
+
+    #[test]
+    fn test_http_checkpoint_truncated_line() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("http.jsonl");
+        std::fs::write(
+            &path,
+            "{\"doi\":\"10.1234/a\",\"resolves\":true}\n{\"doi\":\"10.12",
+        )
+        .unwrap();
+
+        let mut checkpoint = HttpCheckpoint::open(&path).unwrap();
+        assert_eq!(checkpoint.len(), 1);
+        checkpoint.record("10.1234/b", true, None).unwrap();
+        checkpoint.flush().unwrap();
+        drop(checkpoint);
+
+        let reopened = HttpCheckpoint::open(&path).unwrap();
+        assert_eq!(reopened.get("10.1234/a"), Some((true, None)));
+        assert_eq!(reopened.get("10.1234/b"), Some((true, None)));
+    }
+}
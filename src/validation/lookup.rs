@@ -6,6 +6,8 @@ use crate::index::DoiIndex;
 pub enum LookupResult {
     /// DOI found in specified source
     Found(Source),
+    /// DOI prefix belongs to the source, but the index holds prefixes only
+    ProbableByPrefix(Source),
     /// DOI not found in any checked source
     NotFound,
 }
@@ -31,6 +33,9 @@ pub fn lookup_doi(
                 if idx.contains(&doi_lower) {
                     return LookupResult::Found(Source::Datacite);
                 }
+                if idx.prefix_only && idx.contains_prefix_of(&doi_lower) {
+                    return LookupResult::ProbableByPrefix(Source::Datacite);
+                }
             }
             LookupResult::NotFound
         }
@@ -47,12 +52,66 @@ pub fn lookup_doi(
                 if idx.contains(&doi_lower) {
                     return LookupResult::Found(Source::Datacite);
                 }
+                if idx.prefix_only && idx.contains_prefix_of(&doi_lower) {
+                    return LookupResult::ProbableByPrefix(Source::Datacite);
+                }
             }
             LookupResult::NotFound
         }
     }
 }
 
+/// Characters dropped by fuzzy normalization: whitespace plus invisible characters
+/// (zero-width space/joiners, soft hyphen, byte order mark) that survive extraction
+fn is_invisible(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '\u{00AD}' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}'
+        )
+}
+
+/// Unicode hyphens, dashes and minus signs that stand in for an ASCII hyphen
+fn is_unicode_dash(c: char) -> bool {
+    matches!(
+        c,
+        '\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{FE58}' | '\u{FE63}' | '\u{FF0D}'
+    )
+}
+
+/// Looser form of a DOI for a second lookup: whitespace and invisible characters
+/// removed, unicode dashes mapped to '-'
+///
+/// Returns None when the DOI is already in this form, so there is nothing to retry.
+pub fn fuzzy_normalize_doi(doi: &str) -> Option<String> {
+    if !doi.chars().any(|c| is_invisible(c) || is_unicode_dash(c)) {
+        return None;
+    }
+    Some(
+        doi.chars()
+            .filter(|&c| !is_invisible(c))
+            .map(|c| if is_unicode_dash(c) { '-' } else { c })
+            .collect(),
+    )
+}
+
+/// Retry an unmatched DOI in its fuzzy-normalized form
+///
+/// Returns the normalized DOI and the source it was found in. Prefix-only matches do
+/// not count, as the looser form only rescues DOIs the index actually holds.
+pub fn fuzzy_lookup_doi(
+    doi: &str,
+    source: Source,
+    crossref: Option<&DoiIndex>,
+    datacite: Option<&DoiIndex>,
+) -> Option<(String, Source)> {
+    let fuzzy = fuzzy_normalize_doi(doi)?;
+    match lookup_doi(&fuzzy, source, crossref, datacite) {
+        LookupResult::Found(found_source) => Some((fuzzy, found_source)),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,4 +160,78 @@ mod tests {
         );
         assert_eq!(result, LookupResult::NotFound);
     }
+
+    #[test]
+    fn test_lookup_doi_prefix_only_datacite() {
+        let mut crossref = DoiIndex::new();
+        crossref.insert("10.1234/crossref");
+
+        let mut datacite = DoiIndex::prefix_only();
+        datacite.insert("10.5438/anything");
+
+        assert_eq!(
+            lookup_doi("10.5438/other", Source::Datacite, None, Some(&datacite)),
+            LookupResult::ProbableByPrefix(Source::Datacite)
+        );
+        // Exact Crossref matches still take precedence in all mode
+        assert_eq!(
+            lookup_doi(
+                "10.1234/crossref",
+                Source::All,
+                Some(&crossref),
+                Some(&datacite)
+            ),
+            LookupResult::Found(Source::Crossref)
+        );
+        assert_eq!(
+            lookup_doi("10.5438/x", Source::All, Some(&crossref), Some(&datacite)),
+            LookupResult::ProbableByPrefix(Source::Datacite)
+        );
+        assert_eq!(
+            lookup_doi("10.9999/x", Source::All, Some(&crossref), Some(&datacite)),
+            LookupResult::NotFound
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_normalize_doi() {
+        assert_eq!(fuzzy_normalize_doi("10.1234/abc-1"), None);
+        assert_eq!(
+            fuzzy_normalize_doi("10.1234/ abc\u{2010}1\u{200B}"),
+            Some("10.1234/abc-1".to_string())
+        );
+        assert_eq!(
+            fuzzy_normalize_doi("10.1234/x\u{2013}y\u{00A0}z"),
+            Some("10.1234/x-yz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_lookup_doi() {
+        let mut crossref = DoiIndex::new();
+        crossref.insert("10.1234/abc-1");
+
+        assert_eq!(
+            fuzzy_lookup_doi(
+                "10.1234/ABC\u{2212}1",
+                Source::Crossref,
+                Some(&crossref),
+                None
+            ),
+            Some(("10.1234/ABC-1".to_string(), Source::Crossref))
+        );
+        assert_eq!(
+            fuzzy_lookup_doi(
+                "10.1234/abc\u{2010}2",
+                Source::Crossref,
+                Some(&crossref),
+                None
+            ),
+            None
+        );
+        assert_eq!(
+            fuzzy_lookup_doi("10.1234/abc-2", Source::Crossref, Some(&crossref), None),
+            None
+        );
+    }
 }
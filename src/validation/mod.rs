@@ -1,12 +1,22 @@
+pub mod bad_prefixes;
+pub mod citing;
+pub mod concurrency;
 pub mod http;
+pub mod http_checkpoint;
 pub mod lookup;
 pub mod prefix_filter;
 pub mod runner;
 
+pub use bad_prefixes::BadPrefixes;
+pub use citing::CitingWorks;
+pub use concurrency::*;
 pub use http::*;
+pub use http_checkpoint::HttpCheckpoint;
 pub use lookup::*;
 pub use runner::*;
 
+pub use prefix_filter::{attribute_by_prefix, failure_status};
+
 // Re-export prefix_filter for library users
 #[allow(unused_imports)]
 pub use prefix_filter::{has_known_prefix, prefix_source, PrefixMatch};
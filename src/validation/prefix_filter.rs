@@ -1,9 +1,10 @@
-use crate::extract::doi_prefix;
+use crate::cli::Source;
+use crate::common::ValidationStatus;
+use crate::extract::{doi_prefix, DoiConstraints};
 use crate::index::DoiIndex;
 
 /// Fast prefix-based filter for DOIs
 /// Returns true if the DOI's prefix exists in any of the provided indexes
-#[allow(dead_code)]
 pub fn has_known_prefix(
     doi: &str,
     crossref: Option<&DoiIndex>,
@@ -31,7 +32,6 @@ pub fn has_known_prefix(
 
 /// Determine which source(s) might contain a DOI based on prefix
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[allow(dead_code)]
 pub enum PrefixMatch {
     None,
     Crossref,
@@ -39,7 +39,6 @@ pub enum PrefixMatch {
     Both,
 }
 
-#[allow(dead_code)]
 pub fn prefix_source(
     doi: &str,
     crossref: Option<&DoiIndex>,
@@ -61,6 +60,41 @@ pub fn prefix_source(
     }
 }
 
+/// Attribute a DOI missing from the indexes to Crossref or DataCite by its prefix
+///
+/// Prefixes in both indexes go to Crossref, which lookup checks first. Returns
+/// `Source::All` when neither index knows the prefix, so the agency is unknown.
+pub fn attribute_by_prefix(
+    doi: &str,
+    crossref: Option<&DoiIndex>,
+    datacite: Option<&DoiIndex>,
+) -> Source {
+    match prefix_source(doi, crossref, datacite) {
+        PrefixMatch::Crossref | PrefixMatch::Both => Source::Crossref,
+        PrefixMatch::Datacite => Source::Datacite,
+        PrefixMatch::None => Source::All,
+    }
+}
+
+/// Status of a DOI that neither the indexes nor doi.org confirmed
+///
+/// A DOI outside `doi_constraints` is malformed, as extraction under them would not
+/// have produced it.
+pub fn failure_status(
+    doi: &str,
+    crossref: Option<&DoiIndex>,
+    datacite: Option<&DoiIndex>,
+    doi_constraints: DoiConstraints,
+) -> ValidationStatus {
+    if !doi_constraints.is_well_formed(doi) {
+        ValidationStatus::Malformed
+    } else if has_known_prefix(doi, crossref, datacite) {
+        ValidationStatus::PrefixKnownUnresolved
+    } else {
+        ValidationStatus::PrefixUnknown
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,4 +145,58 @@ mod tests {
             PrefixMatch::None
         );
     }
+
+    #[test]
+    fn test_attribute_by_prefix() {
+        let mut crossref = DoiIndex::new();
+        crossref.insert("10.1234/example");
+        crossref.insert("10.5555/shared");
+
+        let mut datacite = DoiIndex::new();
+        datacite.insert("10.5061/dryad.1");
+        datacite.insert("10.5555/other");
+
+        let attribute = |doi| attribute_by_prefix(doi, Some(&crossref), Some(&datacite));
+        assert_eq!(attribute("10.1234/new"), Source::Crossref);
+        assert_eq!(attribute("10.5061/dryad.2"), Source::Datacite);
+        assert_eq!(attribute("10.5555/x"), Source::Crossref);
+        assert_eq!(attribute("10.9999/x"), Source::All);
+        assert_eq!(attribute("not-a-doi"), Source::All);
+    }
+
+    #[test]
+    fn test_failure_status() {
+        let mut crossref = DoiIndex::new();
+        crossref.insert("10.1234/example");
+        let defaults = DoiConstraints::default();
+
+        assert_eq!(
+            failure_status("10.1234/missing", Some(&crossref), None, defaults),
+            ValidationStatus::PrefixKnownUnresolved
+        );
+        assert_eq!(
+            failure_status("10.9999/missing", Some(&crossref), None, defaults),
+            ValidationStatus::PrefixUnknown
+        );
+        assert_eq!(
+            failure_status("10.1234/mis sing", Some(&crossref), None, defaults),
+            ValidationStatus::Malformed
+        );
+        assert_eq!(
+            failure_status("10.1234/missing", None, None, defaults),
+            ValidationStatus::PrefixUnknown
+        );
+        assert_eq!(
+            failure_status("10.123/missing", None, None, defaults),
+            ValidationStatus::Malformed
+        );
+        let short_prefixes = DoiConstraints {
+            min_prefix_digits: 3,
+            ..defaults
+        };
+        assert_eq!(
+            failure_status("10.123/missing", None, None, short_prefixes),
+            ValidationStatus::PrefixUnknown
+        );
+    }
 }
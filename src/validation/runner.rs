@@ -1,153 +1,1088 @@
-use anyhow::{Context, Result};
+use crate::error::{Context, Result};
 use futures::stream::{self, StreamExt};
 use log::info;
-use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use rayon::prelude::*;
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
 
 use crate::cli::Source;
-use crate::common::{format_elapsed, CitationRecord, MultiValidateStats, SplitOutputPaths};
-use crate::index::DoiIndex;
-
-use super::{check_doi_resolves, create_doi_client, lookup_doi, LookupResult};
+#[cfg(feature = "parquet")]
+use crate::common::read_columnar_records;
+use crate::common::{
+    format_elapsed, open_sink_with, run_blocking, CancellationToken, CitationRecord,
+    HttpClientConfig, MultiValidateStats, OutputOptions, ProvenanceCounts, ShardManifest,
+    SplitOutputPaths, ValidationMethod, ValidationStatus, STDOUT_PATH,
+};
+use crate::error::Error;
+use crate::extract::{doi_prefix, DoiConstraints, Provenance};
+use crate::index::{ArxivPublishedDois, DoiIndex, PublisherNames};
+use crate::streaming::open_compressed_reader;
+
+use super::{
+    attribute_by_prefix, create_doi_client, failure_status, fuzzy_lookup_doi, lookup_doi,
+    lookup_registration_agency, request_outcome, AdaptiveLimiter, BadPrefixes, ConcurrencyBounds,
+    DoiOrgResolver, DoiResolver, HttpCheckpoint, LookupResult, RequestOutcome,
+};
 
 /// Multiplier for buffer_unordered capacity relative to concurrency
 const BUFFER_CAPACITY_MULTIPLIER: usize = 2;
+/// Maximum number of prefix-only DataCite matches confirmed via HTTP per prefix
+const PREFIX_CONFIRM_SAMPLE_SIZE: usize = 10;
+/// Share of a prefix's sample that must resolve to accept its other DOIs unchecked
+const PREFIX_CONFIRM_RATE: f64 = 0.9;
+/// Input lines parsed and looked up in parallel at a time during index lookup
+const LOOKUP_CHUNK_LINES: usize = 16_384;
+
+/// Settings for resolving DOIs missing from the indexes via doi.org
+#[derive(Debug, Clone)]
+pub struct HttpFallback {
+    pub concurrency: usize,
+    pub timeout_secs: u64,
+    /// Sidecar file persisting per-DOI results, so a rerun skips DOIs already checked
+    pub checkpoint: Option<PathBuf>,
+    /// Adapt concurrency within these bounds, starting from `concurrency`
+    pub adaptive: Option<ConcurrencyBounds>,
+    /// Resolve unmatched DOIs attributed to Crossref
+    pub crossref: bool,
+    /// Resolve unmatched DOIs attributed to DataCite (including arXiv DOIs) and confirm
+    /// prefix-only matches
+    pub datacite: bool,
+    /// Skip the remaining DOIs of a prefix once this many have failed and none
+    /// resolved (see [`BadPrefixes`]); 0 checks every DOI
+    pub bad_prefix_threshold: usize,
+    /// Resolver answering the checks instead of doi.org, such as a [`FileResolver`]
+    ///
+    /// [`FileResolver`]: super::FileResolver
+    pub resolver: Option<Arc<dyn DoiResolver>>,
+    /// User-Agent, proxy and connection settings of the doi.org client
+    pub client: HttpClientConfig,
+}
+
+impl HttpFallback {
+    /// Whether DOIs attributed to `source` are resolved via doi.org
+    ///
+    /// DOIs with a prefix neither index knows (`Source::All`) are resolved when either
+    /// source is enabled.
+    pub fn applies_to(&self, source: Source) -> bool {
+        match source {
+            Source::Crossref => self.crossref,
+            Source::Datacite | Source::Arxiv => self.datacite,
+            Source::All => self.crossref || self.datacite,
+        }
+    }
+}
 
 /// Results from validation
+///
+/// Each record carries the source it is attributed to. In all mode, records missing
+/// from both indexes are attributed by prefix, with `Source::All` when it is unknown.
 pub struct ValidationResults {
     pub valid: Vec<(CitationRecord, Source)>,
-    pub failed: Vec<CitationRecord>,
+    pub failed: Vec<(CitationRecord, Source)>,
+    /// Records whose DOI belongs to an index shard that was not loaded, left
+    /// unvalidated and unchanged for the job holding that shard
+    pub outside_shards: Vec<CitationRecord>,
     pub stats: MultiValidateStats,
 }
 
-/// Validate citations from a JSONL file against indexes
-pub async fn validate_citations(
-    input_path: &str,
+/// Combine a Crossref-only pass with the all-mode pass over the records it did not
+/// match (validate --two-pass)
+///
+/// The failed records of the first pass were validated again by the second, so only
+/// its matches are kept. Totals and shard skips come from the first pass, which read
+/// the whole input.
+pub fn merge_two_pass_results(
+    crossref_pass: ValidationResults,
+    rest: ValidationResults,
+) -> ValidationResults {
+    let mut stats = rest.stats;
+    stats.total_records = crossref_pass.stats.total_records;
+    stats.outside_shards = crossref_pass.stats.outside_shards;
+    stats.crossref_matched += crossref_pass.stats.crossref_matched;
+    stats.fuzzy_rescued += crossref_pass.stats.fuzzy_rescued;
+
+    let mut valid = crossref_pass.valid;
+    valid.extend(rest.valid);
+    let valid = merge_by_doi(valid);
+    let mut outside_shards = crossref_pass.outside_shards;
+    outside_shards.extend(rest.outside_shards);
+    ValidationResults {
+        valid,
+        failed: rest.failed,
+        outside_shards,
+        stats,
+    }
+}
+
+/// Outcome of the index lookup for one input record
+enum Lookup {
+    /// The DOI belongs to an index shard that is not loaded; the record is unchanged
+    OutsideShards(CitationRecord),
+    /// Found in an index, `fuzzy` when only after whitespace and dash normalization
+    Matched {
+        record: CitationRecord,
+        source: Source,
+        fuzzy: bool,
+    },
+    ProbableByPrefix(CitationRecord),
+    NotFound(CitationRecord),
+}
+
+/// Parse an input line and look its DOI up in the indexes
+fn lookup_record(
+    line: &str,
+    line_number: usize,
+    source: Source,
     crossref_index: Option<&DoiIndex>,
     datacite_index: Option<&DoiIndex>,
-    source: Source,
-    http_fallback: bool,
-    concurrency: usize,
-    timeout_secs: u64,
-) -> Result<ValidationResults> {
-    let start = Instant::now();
-    info!("Validating citations from: {}", input_path);
-
-    let file = File::open(input_path).with_context(|| format!("Failed to open: {}", input_path))?;
-    let reader = BufReader::new(file);
+) -> Result<Lookup> {
+    let record: CitationRecord = serde_json::from_str(line)
+        .with_context(|| format!("Failed to parse record at line {}", line_number))?;
+    Ok(lookup_parsed(
+        record,
+        source,
+        crossref_index,
+        datacite_index,
+    ))
+}
 
-    let mut matched: Vec<(CitationRecord, Source)> = Vec::new();
-    let mut unmatched: Vec<CitationRecord> = Vec::new();
-    let mut stats = MultiValidateStats::default();
+/// Look a record's DOI up in the indexes, clearing any earlier validation outcome
+fn lookup_parsed(
+    mut record: CitationRecord,
+    source: Source,
+    crossref_index: Option<&DoiIndex>,
+    datacite_index: Option<&DoiIndex>,
+) -> Lookup {
+    // Partitioned jobs leave DOIs of other shards to the jobs holding them
+    if [crossref_index, datacite_index]
+        .into_iter()
+        .flatten()
+        .any(|index| !index.covers(&record.doi))
+    {
+        return Lookup::OutsideShards(record);
+    }
 
-    // Phase 1: Index lookup
-    for line_result in reader.lines() {
-        let line = line_result?;
-        if line.trim().is_empty() {
-            continue;
+    record.validation_method = None;
+    record.validation_status = None;
+    record.http_error = None;
+
+    match lookup_doi(&record.doi, source, crossref_index, datacite_index) {
+        LookupResult::Found(found_source) => Lookup::Matched {
+            record,
+            source: found_source,
+            fuzzy: false,
+        },
+        LookupResult::ProbableByPrefix(_) => Lookup::ProbableByPrefix(record),
+        // Embedded whitespace or unicode dashes can survive normalization
+        LookupResult::NotFound => {
+            match fuzzy_lookup_doi(&record.doi, source, crossref_index, datacite_index) {
+                Some((doi, found_source)) => Lookup::Matched {
+                    record: CitationRecord { doi, ..record },
+                    source: found_source,
+                    fuzzy: true,
+                },
+                None => Lookup::NotFound(record),
+            }
         }
+    }
+}
 
-        let record: CitationRecord = serde_json::from_str(&line).with_context(|| {
-            format!("Failed to parse record at line {}", stats.total_records + 1)
-        })?;
-        stats.total_records += 1;
+/// Records sorted by the outcome of their index lookup
+#[derive(Default)]
+struct IndexLookups {
+    matched: Vec<(CitationRecord, Source)>,
+    probable: Vec<CitationRecord>,
+    unmatched: Vec<CitationRecord>,
+    outside_shards: Vec<CitationRecord>,
+    stats: MultiValidateStats,
+}
 
-        match lookup_doi(&record.doi, source, crossref_index, datacite_index) {
-            LookupResult::Found(found_source) => {
+impl IndexLookups {
+    fn add(&mut self, lookup: Lookup) {
+        let stats = &mut self.stats;
+        stats.total_records += 1;
+        match lookup {
+            Lookup::OutsideShards(record) => {
+                stats.outside_shards += 1;
+                self.outside_shards.push(record);
+            }
+            Lookup::Matched {
+                record,
+                source: found_source,
+                fuzzy,
+            } => {
+                if fuzzy {
+                    stats.fuzzy_rescued += 1;
+                }
                 match found_source {
                     Source::Crossref => stats.crossref_matched += 1,
                     Source::Datacite => stats.datacite_matched += 1,
                     _ => {}
                 }
-                matched.push((record, found_source));
+                self.matched.push((record, found_source));
+            }
+            Lookup::ProbableByPrefix(record) => {
+                stats.datacite_prefix_matched += 1;
+                self.probable.push(record);
+            }
+            Lookup::NotFound(record) => self.unmatched.push(record),
+        }
+    }
+}
+
+/// Lines of the JSON records in a validation input or output, whatever sink wrote it
+///
+/// JSONL is read through [`open_compressed_reader`], so gzip (or bzip2 or zstd) outputs
+/// read like plain ones. Parquet and Arrow IPC outputs, by the extensions `open_sink`
+/// writes them at, are read whole and converted back to one JSON line per row. A
+/// sharded output is read shard by shard, in the order its manifest lists them.
+pub fn read_record_lines(path: &Path) -> Result<Box<dyn Iterator<Item = Result<String>>>> {
+    let manifest = ShardManifest::path_for(path);
+    let mut files: Vec<PathBuf> = ShardManifest::files_of(path)
+        .into_iter()
+        .filter(|file| *file != manifest)
+        .collect();
+    if files.len() == 1 && !manifest.exists() {
+        return read_file_lines(&files.remove(0));
+    }
+    Ok(Box::new(files.into_iter().flat_map(|file| {
+        read_file_lines(&file).unwrap_or_else(|e| Box::new(std::iter::once(Err(e))))
+    })))
+}
+
+/// Lines of the JSON records in one output file, as [`read_record_lines`]
+fn read_file_lines(path: &Path) -> Result<Box<dyn Iterator<Item = Result<String>>>> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        #[cfg(feature = "parquet")]
+        Some("parquet" | "arrow" | "feather" | "arrows") => {
+            let lines: Vec<Result<String>> = read_columnar_records(path)?
+                .into_iter()
+                .map(|record| Ok(record.to_string()))
+                .collect();
+            Ok(Box::new(lines.into_iter()))
+        }
+        #[cfg(not(feature = "parquet"))]
+        Some(ext @ ("parquet" | "arrow" | "feather" | "arrows")) => {
+            Err(Error::requires_parquet(format!("Reading .{} input", ext)))
+        }
+        _ => {
+            let reader = BufReader::new(open_compressed_reader(path)?);
+            let path = path.to_path_buf();
+            Ok(Box::new(reader.lines().map(move |line| {
+                line.map_err(|e| Error::from(e).context(format!("Failed to read: {:?}", path)))
+            })))
+        }
+    }
+}
+
+/// Look up each record of an input in the indexes
+///
+/// A chunk of lines at a time is parsed and looked up on the rayon pool, and the
+/// lookups are applied in input order.
+fn lookup_input(
+    input_path: &Path,
+    source: Source,
+    crossref_index: Option<&DoiIndex>,
+    datacite_index: Option<&DoiIndex>,
+) -> Result<IndexLookups> {
+    let mut lookups = IndexLookups::default();
+    let mut lines = read_record_lines(input_path)?.enumerate();
+    let mut chunk: Vec<(usize, String)> = Vec::with_capacity(LOOKUP_CHUNK_LINES);
+    loop {
+        chunk.clear();
+        for (line_idx, line_result) in lines.by_ref() {
+            let line = line_result?;
+            if !line.trim().is_empty() {
+                chunk.push((line_idx + 1, line));
             }
-            LookupResult::NotFound => {
-                unmatched.push(record);
+            if chunk.len() == LOOKUP_CHUNK_LINES {
+                break;
             }
         }
+        if chunk.is_empty() {
+            break;
+        }
+
+        let results: Vec<Result<Lookup>> = chunk
+            .par_iter()
+            .map(|(line_number, line)| {
+                lookup_record(line, *line_number, source, crossref_index, datacite_index)
+            })
+            .collect();
+        for lookup in results {
+            lookups.add(lookup?);
+        }
+    }
+    Ok(lookups)
+}
+
+/// Validate citations from a JSONL file against indexes
+///
+/// Unconfirmed DOIs outside `doi_constraints` fail as malformed.
+pub async fn validate_citations(
+    input_path: impl AsRef<Path>,
+    crossref_index: Option<&DoiIndex>,
+    datacite_index: Option<&DoiIndex>,
+    source: Source,
+    http_fallback: Option<&HttpFallback>,
+    doi_constraints: DoiConstraints,
+    cancel: &CancellationToken,
+) -> Result<ValidationResults> {
+    let start = Instant::now();
+    let input_path = input_path.as_ref();
+    info!("Validating citations from: {}", input_path.display());
+
+    // Phase 1: Index lookup
+    let lookups =
+        run_blocking(|| lookup_input(input_path, source, crossref_index, datacite_index))?;
+
+    let results = resolve_lookups(
+        lookups,
+        crossref_index,
+        datacite_index,
+        source,
+        http_fallback,
+        doi_constraints,
+        cancel,
+    )
+    .await?;
+    info!("Validation complete in {}", format_elapsed(start.elapsed()));
+    Ok(results)
+}
+
+/// Validate citation records held in memory against indexes
+///
+/// Validates as [`validate_citations`] does a JSONL file of the same records, in
+/// their order.
+pub async fn validate_records(
+    records: Vec<CitationRecord>,
+    crossref_index: Option<&DoiIndex>,
+    datacite_index: Option<&DoiIndex>,
+    source: Source,
+    http_fallback: Option<&HttpFallback>,
+    doi_constraints: DoiConstraints,
+    cancel: &CancellationToken,
+) -> Result<ValidationResults> {
+    let start = Instant::now();
+    info!("Validating {} citation records", records.len());
+
+    let mut lookups = IndexLookups::default();
+    let results: Vec<Lookup> = run_blocking(|| {
+        records
+            .into_par_iter()
+            .map(|record| lookup_parsed(record, source, crossref_index, datacite_index))
+            .collect()
+    });
+    for lookup in results {
+        lookups.add(lookup);
     }
 
+    let results = resolve_lookups(
+        lookups,
+        crossref_index,
+        datacite_index,
+        source,
+        http_fallback,
+        doi_constraints,
+        cancel,
+    )
+    .await?;
+    info!("Validation complete in {}", format_elapsed(start.elapsed()));
+    Ok(results)
+}
+
+/// Settle the records the index lookup did not match, via doi.org when enabled, and
+/// set the validation outcome of every record
+async fn resolve_lookups(
+    lookups: IndexLookups,
+    crossref_index: Option<&DoiIndex>,
+    datacite_index: Option<&DoiIndex>,
+    source: Source,
+    http_fallback: Option<&HttpFallback>,
+    doi_constraints: DoiConstraints,
+    cancel: &CancellationToken,
+) -> Result<ValidationResults> {
+    let IndexLookups {
+        mut matched,
+        probable,
+        mut unmatched,
+        outside_shards,
+        mut stats,
+    } = lookups;
+
+    let mut http_checkpoint = match http_fallback.and_then(|http| http.checkpoint.as_deref()) {
+        Some(path) => Some(HttpCheckpoint::open(path)?),
+        None => None,
+    };
+
     info!(
         "Index lookup: {} matched, {} unmatched",
         matched.len(),
         unmatched.len()
     );
+    if stats.fuzzy_rescued > 0 {
+        info!(
+            "  ({} matched only after removing whitespace and mapping unicode dashes)",
+            stats.fuzzy_rescued
+        );
+    }
+    if stats.outside_shards > 0 {
+        info!(
+            "  ({} skipped: DOI belongs to an index shard not loaded)",
+            stats.outside_shards
+        );
+    }
+
+    let mut failed: Vec<(CitationRecord, Source)> = Vec::new();
+
+    // Prefix-only matches are accepted as probable DataCite DOIs. With HTTP fallback
+    // enabled, an evenly spaced sample of each prefix is confirmed; the other DOIs of a
+    // prefix are accepted only when enough of its sample resolved, and checked one by
+    // one otherwise
+    if !probable.is_empty() {
+        info!(
+            "Prefix-only lookup: {} probable DataCite DOIs",
+            probable.len()
+        );
+
+        if let Some(http) = http_fallback.filter(|http| http.applies_to(Source::Datacite)) {
+            let prefix_of = |record: &CitationRecord| doi_prefix(&record.doi).unwrap_or_default();
+            let mut by_prefix: BTreeMap<String, Vec<CitationRecord>> = BTreeMap::new();
+            for record in probable {
+                by_prefix
+                    .entry(prefix_of(&record))
+                    .or_default()
+                    .push(record);
+            }
+            let mut sample = Vec::new();
+            let mut rest: Vec<(String, Vec<CitationRecord>)> = Vec::new();
+            for (prefix, records) in by_prefix {
+                let step = records.len().div_ceil(PREFIX_CONFIRM_SAMPLE_SIZE);
+                let mut unsampled = Vec::new();
+                for (i, record) in records.into_iter().enumerate() {
+                    if i % step == 0 {
+                        sample.push(record);
+                    } else {
+                        unsampled.push(record);
+                    }
+                }
+                rest.push((prefix, unsampled));
+            }
+
+            stats.datacite_prefix_sampled = sample.len();
+            let results =
+                resolve_via_http(sample, http, http_checkpoint.as_mut(), &mut stats, cancel)
+                    .await?;
+            // Confirmed and sampled DOIs per prefix
+            let mut confirmations: HashMap<String, (usize, usize)> = HashMap::new();
+            for (record, resolves) in results {
+                let tally = confirmations.entry(prefix_of(&record)).or_default();
+                tally.1 += 1;
+                if resolves {
+                    tally.0 += 1;
+                    stats.datacite_prefix_confirmed += 1;
+                    let record = CitationRecord {
+                        validation_method: Some(ValidationMethod::Http),
+                        ..record
+                    };
+                    matched.push((record, Source::Datacite));
+                } else {
+                    stats.datacite_failed += 1;
+                    failed.push((record, Source::Datacite));
+                }
+            }
+
+            info!(
+                "Prefix-only sample: {}/{} confirmed via HTTP",
+                stats.datacite_prefix_confirmed, stats.datacite_prefix_sampled
+            );
+
+            let mut unconfirmed = Vec::new();
+            for (prefix, records) in rest {
+                let (confirmed, sampled) = confirmations.get(&prefix).copied().unwrap_or_default();
+                if sampled > 0 && confirmed as f64 >= sampled as f64 * PREFIX_CONFIRM_RATE {
+                    matched.extend(records.into_iter().map(probable_by_prefix));
+                } else {
+                    stats.datacite_prefixes_unconfirmed += 1;
+                    unconfirmed.extend(records);
+                }
+            }
+            if stats.datacite_prefixes_unconfirmed > 0 {
+                info!(
+                    "{} prefixes confirmed by less than {:.0}% of their sample; checking their \
+                     other {} DOIs via HTTP",
+                    stats.datacite_prefixes_unconfirmed,
+                    PREFIX_CONFIRM_RATE * 100.0,
+                    unconfirmed.len()
+                );
+                let results = resolve_via_http(
+                    unconfirmed,
+                    http,
+                    http_checkpoint.as_mut(),
+                    &mut stats,
+                    cancel,
+                )
+                .await?;
+                for (record, resolves) in results {
+                    if resolves {
+                        stats.datacite_http_resolved += 1;
+                        let record = CitationRecord {
+                            validation_method: Some(ValidationMethod::Http),
+                            ..record
+                        };
+                        matched.push((record, Source::Datacite));
+                    } else {
+                        stats.datacite_failed += 1;
+                        failed.push((record, Source::Datacite));
+                    }
+                }
+            }
+        } else {
+            matched.extend(probable.into_iter().map(probable_by_prefix));
+        }
+    }
+
+    // In all mode, DOIs missing from both indexes are attributed by prefix; a prefix
+    // neither index knows stays Source::All and is counted on its own
+    let attribute = |record: &CitationRecord| match source {
+        Source::All => attribute_by_prefix(&record.doi, crossref_index, datacite_index),
+        _ => source,
+    };
 
     // Phase 2: HTTP fallback for unmatched (if enabled)
     let mut http_resolved: Vec<(CitationRecord, Source)> = Vec::new();
-    let mut failed: Vec<CitationRecord> = Vec::new();
 
-    if http_fallback && !unmatched.is_empty() {
+    // Unmatched DOIs of sources without HTTP fallback fail without a request
+    if let Some(http) = http_fallback {
+        let (checked, skipped): (Vec<_>, Vec<_>) = unmatched
+            .into_iter()
+            .partition(|record| http.applies_to(attribute(record)));
+        if !skipped.is_empty() {
+            info!(
+                "HTTP fallback not enabled for the source of {} unmatched DOIs",
+                skipped.len()
+            );
+        }
+        for record in skipped {
+            let attributed = attribute(&record);
+            count_failed(&mut stats, attributed);
+            failed.push((record, attributed));
+        }
+        unmatched = checked;
+    }
+
+    if let Some(http) = http_fallback.filter(|_| !unmatched.is_empty()) {
         info!(
             "Running HTTP fallback for {} unmatched DOIs...",
             unmatched.len()
         );
 
-        let client = create_doi_client()?;
-        let timeout = Duration::from_secs(timeout_secs);
-        let semaphore = Arc::new(Semaphore::new(concurrency));
-
-        let results: Vec<(CitationRecord, bool)> = stream::iter(unmatched.into_iter())
-            .map(|record| {
-                let client = client.clone();
-                let semaphore = semaphore.clone();
-
-                async move {
-                    let _permit = semaphore
-                        .acquire()
-                        .await
-                        .expect("semaphore should never be closed");
-                    let resolves = check_doi_resolves(&client, &record.doi, timeout).await;
-                    (record, resolves)
-                }
-            })
-            .buffer_unordered(concurrency * BUFFER_CAPACITY_MULTIPLIER)
-            .collect()
-            .await;
+        let results = resolve_via_http(
+            unmatched,
+            http,
+            http_checkpoint.as_mut(),
+            &mut stats,
+            cancel,
+        )
+        .await?;
 
         for (record, resolves) in results {
+            let attributed = attribute(&record);
             if resolves {
-                // Determine source based on prefix for stats
-                match source {
+                // A resolving DOI needs one output; an unknown prefix goes to DataCite
+                let resolved_source = match attributed {
+                    Source::All => Source::Datacite,
+                    other => other,
+                };
+                match resolved_source {
                     Source::Crossref => stats.crossref_http_resolved += 1,
-                    Source::Datacite | Source::Arxiv => stats.datacite_http_resolved += 1,
-                    Source::All => stats.datacite_http_resolved += 1, // Default to datacite for all
+                    _ => stats.datacite_http_resolved += 1,
                 }
-                http_resolved.push((record, source));
+                let record = CitationRecord {
+                    validation_method: Some(ValidationMethod::Http),
+                    ..record
+                };
+                http_resolved.push((record, resolved_source));
             } else {
-                match source {
-                    Source::Crossref => stats.crossref_failed += 1,
-                    _ => stats.datacite_failed += 1,
-                }
-                failed.push(record);
+                count_failed(&mut stats, attributed);
+                failed.push((record, attributed));
             }
         }
     } else {
         // All unmatched go to failed
         for record in unmatched {
-            match source {
-                Source::Crossref => stats.crossref_failed += 1,
-                _ => stats.datacite_failed += 1,
-            }
-            failed.push(record);
+            let attributed = attribute(&record);
+            count_failed(&mut stats, attributed);
+            failed.push((record, attributed));
         }
     }
 
     // Combine matched and http_resolved
     matched.extend(http_resolved);
+    let mut matched = merge_by_doi(matched);
+    for (record, _) in &mut matched {
+        let method = *record
+            .validation_method
+            .get_or_insert(ValidationMethod::Index);
+        record.validation_status = Some(ValidationStatus::matched(method));
+    }
+    for (record, _) in &mut failed {
+        let status = failure_status(&record.doi, crossref_index, datacite_index, doi_constraints);
+        match status {
+            ValidationStatus::Malformed => stats.failed_malformed += 1,
+            ValidationStatus::PrefixUnknown => stats.failed_prefix_unknown += 1,
+            _ => stats.failed_prefix_known += 1,
+        }
+        record.validation_status = Some(status);
+        if let Some(http_error) = record.http_error {
+            *stats.http_errors.entry(http_error).or_default() += 1;
+        }
+    }
 
-    info!("Validation complete in {}", format_elapsed(start.elapsed()));
+    if stats.http_checkpoint_reused > 0 {
+        info!(
+            "Reused {} HTTP results from checkpoint",
+            stats.http_checkpoint_reused
+        );
+    }
 
     Ok(ValidationResults {
         valid: matched,
         failed,
+        outside_shards,
         stats,
     })
 }
 
+/// Merge valid records that share a DOI into the first of them
+///
+/// A fuzzy rescue can turn a record's DOI into one another record matched as written,
+/// which would otherwise be output twice with its citing works split between the two.
+/// The first record keeps its place and fields, gains the citing works of the others
+/// it does not already list, and has its counts recomputed.
+fn merge_by_doi(records: Vec<(CitationRecord, Source)>) -> Vec<(CitationRecord, Source)> {
+    let total = records.len();
+    let mut positions: HashMap<String, usize> = HashMap::with_capacity(total);
+    let mut merged: Vec<(CitationRecord, Source)> = Vec::with_capacity(total);
+    for (record, source) in records {
+        match positions.entry(record.doi.to_lowercase()) {
+            Entry::Occupied(position) => absorb_citations(&mut merged[*position.get()].0, record),
+            Entry::Vacant(position) => {
+                position.insert(merged.len());
+                merged.push((record, source));
+            }
+        }
+    }
+    if merged.len() < total {
+        info!(
+            "Merged {} records into the record already matched for their DOI",
+            total - merged.len()
+        );
+    }
+    merged
+}
+
+/// Add the citing works of `other`, a record of the same DOI, to `record`
+fn absorb_citations(record: &mut CitationRecord, other: CitationRecord) {
+    let citing_doi = |entry: &Value| entry.get("doi").and_then(Value::as_str).map(str::to_string);
+    let mut citing: HashSet<String> = record.cited_by.iter().filter_map(citing_doi).collect();
+    let before = record.cited_by.len();
+    for entry in other.cited_by {
+        if citing_doi(&entry).is_none_or(|doi| citing.insert(doi)) {
+            record.cited_by.push(entry);
+        }
+    }
+    let added = record.cited_by.len() - before;
+    record.citation_count += added;
+    record.reference_count += added;
+    record.published_doi = record.published_doi.take().or(other.published_doi);
+    record.cited_publisher = record.cited_publisher.take().or(other.cited_publisher);
+
+    let (counts, asserted) = count_cited_by(&record.cited_by);
+    if record.provenance_counts.is_some() || other.provenance_counts.is_some() {
+        record.provenance_counts = Some(counts);
+    }
+    if record.asserted_citation_count.is_some() || other.asserted_citation_count.is_some() {
+        record.asserted_citation_count = Some(asserted);
+    }
+}
+
+/// The per-provenance counts of a cited_by list, and its asserted citations
+fn count_cited_by(cited_by: &[Value]) -> (ProvenanceCounts, usize) {
+    let provenances: Vec<&str> = cited_by
+        .iter()
+        .map(|entry| {
+            entry
+                .get("provenance")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+        })
+        .collect();
+    let count = |provenance: &str| provenances.iter().filter(|p| **p == provenance).count();
+    let counts = ProvenanceCounts {
+        publisher_citation_count: count("publisher"),
+        crossref_citation_count: count("crossref"),
+        structured_citation_count: count("structured"),
+        mined_citation_count: count("mined"),
+    };
+    let asserted = provenances
+        .iter()
+        .filter(|p| Provenance::is_asserted_str(p))
+        .count();
+    (counts, asserted)
+}
+
+/// Log the index, HTTP and outcome counts of a validation run
+///
+/// `prefix_only` and `http_fallback` add the prefix sample and HTTP lines.
+pub fn log_validation_stats(
+    results: &ValidationResults,
+    source: Source,
+    prefix_only: bool,
+    http_fallback: bool,
+) {
+    let stats = &results.stats;
+    info!("Validation results:");
+    info!("  Total records checked: {}", stats.total_records);
+    info!("  Crossref index matched: {}", stats.crossref_matched);
+    info!("  DataCite index matched: {}", stats.datacite_matched);
+    if stats.fuzzy_rescued > 0 {
+        info!("  Rescued by fuzzy lookup: {}", stats.fuzzy_rescued);
+    }
+    if prefix_only {
+        info!(
+            "  DataCite prefix matched (probable): {}",
+            stats.datacite_prefix_matched
+        );
+        if http_fallback {
+            info!(
+                "  DataCite prefix sample confirmed: {}/{}",
+                stats.datacite_prefix_confirmed, stats.datacite_prefix_sampled
+            );
+            if stats.datacite_prefixes_unconfirmed > 0 {
+                info!(
+                    "  DataCite prefixes checked DOI by DOI: {}",
+                    stats.datacite_prefixes_unconfirmed
+                );
+            }
+        }
+    }
+    if http_fallback {
+        info!(
+            "  HTTP resolved: {} crossref, {} datacite",
+            stats.crossref_http_resolved, stats.datacite_http_resolved
+        );
+        if stats.http_checkpoint_reused > 0 {
+            info!(
+                "  HTTP results reused from checkpoint: {}",
+                stats.http_checkpoint_reused
+            );
+        }
+        if stats.http_prefix_skipped > 0 {
+            info!(
+                "  HTTP checks skipped under failing prefixes: {}",
+                stats.http_prefix_skipped
+            );
+        }
+        if !stats.http_errors.is_empty() {
+            info!(
+                "  HTTP failures: {} ({} transient)",
+                stats
+                    .http_errors
+                    .iter()
+                    .map(|(error, count)| format!("{} {}", count, error.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                stats
+                    .http_errors
+                    .iter()
+                    .filter(|(error, _)| error.is_transient())
+                    .map(|(_, count)| count)
+                    .sum::<usize>()
+            );
+        }
+    }
+    info!("  Valid {} citations: {}", source, results.valid.len());
+    info!(
+        "  Failed (not in {} index): {}",
+        source,
+        results.failed.len()
+    );
+    if !results.failed.is_empty() {
+        info!(
+            "    {} under a known prefix, {} under an unknown prefix, {} malformed",
+            stats.failed_prefix_known, stats.failed_prefix_unknown, stats.failed_malformed
+        );
+    }
+    if stats.unknown_prefix_failed > 0 {
+        info!(
+            "    {} attributed to neither source: their prefix is in neither index",
+            stats.unknown_prefix_failed
+        );
+    }
+}
+
+/// Accept a record matched only by prefix as a probable DataCite DOI
+fn probable_by_prefix(record: CitationRecord) -> (CitationRecord, Source) {
+    let record = CitationRecord {
+        validation_method: Some(ValidationMethod::Prefix),
+        ..record
+    };
+    (record, Source::Datacite)
+}
+
+/// Count a failed record against its attributed source
+fn count_failed(stats: &mut MultiValidateStats, source: Source) {
+    match source {
+        Source::Crossref => stats.crossref_failed += 1,
+        Source::All => stats.unknown_prefix_failed += 1,
+        _ => stats.datacite_failed += 1,
+    }
+}
+
+/// Check each record's DOI against doi.org with bounded concurrency
+///
+/// DOIs already recorded in the checkpoint are not re-requested, except after a
+/// transient failure; new results are appended to it as they arrive so an interrupted
+/// run loses little progress. A record that does not resolve carries its `http_error`.
+/// DOIs under a prefix given up on by `bad_prefix_threshold` count as unresolved
+/// without a request or error and are left out of the checkpoint, so a later run
+/// checks them again. Results come back in the order of `records`, whatever order the
+/// requests complete in.
+async fn resolve_via_http(
+    records: Vec<CitationRecord>,
+    http: &HttpFallback,
+    mut checkpoint: Option<&mut HttpCheckpoint>,
+    stats: &mut MultiValidateStats,
+    cancel: &CancellationToken,
+) -> Result<Vec<(CitationRecord, bool)>> {
+    let bad_prefixes =
+        (http.bad_prefix_threshold > 0).then(|| BadPrefixes::new(http.bad_prefix_threshold));
+    let bad_prefixes = bad_prefixes.as_ref();
+
+    let mut results = Vec::with_capacity(records.len());
+    let mut pending = Vec::new();
+    for (position, record) in records.into_iter().enumerate() {
+        match checkpoint.as_deref().and_then(|cp| cp.get(&record.doi)) {
+            Some((resolves, http_error)) => {
+                stats.http_checkpoint_reused += 1;
+                if let Some(bad_prefixes) = bad_prefixes {
+                    bad_prefixes.observe(&record.doi, resolves);
+                }
+                results.push((
+                    position,
+                    CitationRecord {
+                        http_error,
+                        ..record
+                    },
+                    resolves,
+                ));
+            }
+            None => pending.push((position, record)),
+        }
+    }
+
+    if pending.is_empty() {
+        return Ok(in_input_order(results));
+    }
+
+    let resolver: Arc<dyn DoiResolver> = match &http.resolver {
+        Some(resolver) => resolver.clone(),
+        None => Arc::new(DoiOrgResolver::new(
+            Duration::from_secs(http.timeout_secs),
+            &http.client,
+        )?),
+    };
+    let resolver = resolver.as_ref();
+    let semaphore = Arc::new(Semaphore::new(http.concurrency));
+    let limiter = http
+        .adaptive
+        .map(|bounds| AdaptiveLimiter::new(http.concurrency, bounds));
+    let limiter = limiter.as_ref();
+    let max_concurrency = http
+        .adaptive
+        .map_or(http.concurrency, |bounds| bounds.max.max(http.concurrency));
+
+    let mut resolved = pin!(stream::iter(pending)
+        .map(|(position, record)| {
+            let semaphore = semaphore.clone();
+
+            async move {
+                if bad_prefixes.is_some_and(|bad_prefixes| bad_prefixes.skip(&record.doi)) {
+                    return (position, record, true);
+                }
+                let http_error = match limiter {
+                    Some(limiter) => {
+                        let permit = limiter.acquire().await;
+                        let http_error = resolver.check(&record.doi).await;
+                        permit.finish(request_outcome(http_error));
+                        http_error
+                    }
+                    None => {
+                        let _permit = semaphore
+                            .acquire()
+                            .await
+                            .expect("semaphore should never be closed");
+                        resolver.check(&record.doi).await
+                    }
+                };
+                if let Some(bad_prefixes) = bad_prefixes {
+                    if request_outcome(http_error) == RequestOutcome::Completed {
+                        bad_prefixes.observe(&record.doi, http_error.is_none());
+                    }
+                }
+                (
+                    position,
+                    CitationRecord {
+                        http_error,
+                        ..record
+                    },
+                    false,
+                )
+            }
+        })
+        .buffer_unordered(max_concurrency * BUFFER_CAPACITY_MULTIPLIER));
+
+    while let Some((position, record, skipped)) = resolved.next().await {
+        let resolves = !skipped && record.http_error.is_none();
+        if skipped {
+            stats.http_prefix_skipped += 1;
+        } else if let Some(cp) = checkpoint.as_deref_mut() {
+            cp.record(&record.doi, resolves, record.http_error)?;
+        }
+        results.push((position, record, resolves));
+        if cancel.is_cancelled() {
+            break;
+        }
+    }
+
+    if let Some(cp) = checkpoint {
+        cp.flush()?;
+    }
+    if let Some(bad_prefixes) = bad_prefixes {
+        let skipped = bad_prefixes.skipped();
+        if !skipped.is_empty() {
+            info!(
+                "Skipped {} HTTP checks under {} prefixes with only failing DOIs \
+                 (--bad-prefix-threshold {}), most skipped: {}",
+                stats.http_prefix_skipped,
+                skipped.len(),
+                http.bad_prefix_threshold,
+                skipped
+                    .iter()
+                    .take(5)
+                    .map(|(prefix, count)| format!("{} ({})", prefix, count))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+    if let Some(limiter) = limiter {
+        let report = limiter.report();
+        info!(
+            "Adaptive concurrency: settled at {} (peak {}), {:.1} requests/s steady state, \
+             {} throttled, {} failed",
+            report.final_limit,
+            report.peak_limit,
+            report.steady_rate,
+            report.throttled,
+            report.failed
+        );
+    }
+    cancel.check()?;
+
+    Ok(in_input_order(results))
+}
+
+/// Results tagged with their record's position, sorted back into input order
+fn in_input_order(mut results: Vec<(usize, CitationRecord, bool)>) -> Vec<(CitationRecord, bool)> {
+    results.sort_unstable_by_key(|(position, _, _)| *position);
+    results
+        .into_iter()
+        .map(|(_, record, resolves)| (record, resolves))
+        .collect()
+}
+
+/// Record the registration agency doi.org reports for each failed record's DOI
+///
+/// Tells a Crossref or DataCite index gap (the DOI is registered with that agency)
+/// apart from a DOI registered elsewhere or not at all. Returns record counts per
+/// agency; records whose lookup fails are counted as "unknown" and left unset.
+pub async fn lookup_registration_agencies(
+    failed: &mut [(CitationRecord, Source)],
+    concurrency: usize,
+    timeout_secs: u64,
+    client: &HttpClientConfig,
+    cancel: &CancellationToken,
+) -> Result<BTreeMap<String, usize>> {
+    info!(
+        "Looking up registration agencies for {} failed DOIs...",
+        failed.len()
+    );
+
+    let client = create_doi_client(client)?;
+    let timeout = Duration::from_secs(timeout_secs);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+
+    let dois: Vec<(usize, String)> = failed
+        .iter()
+        .enumerate()
+        .map(|(i, (record, _))| (i, record.doi.clone()))
+        .collect();
+    let mut agencies = pin!(stream::iter(dois)
+        .map(|(i, doi)| {
+            let client = client.clone();
+            let semaphore = semaphore.clone();
+
+            async move {
+                let _permit = semaphore
+                    .acquire()
+                    .await
+                    .expect("semaphore should never be closed");
+                (i, lookup_registration_agency(&client, &doi, timeout).await)
+            }
+        })
+        .buffer_unordered(concurrency * BUFFER_CAPACITY_MULTIPLIER));
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    while let Some((i, agency)) = agencies.next().await {
+        *counts
+            .entry(agency.clone().unwrap_or_else(|| "unknown".to_string()))
+            .or_default() += 1;
+        failed[i].0.registration_agency = agency;
+        if cancel.is_cancelled() {
+            break;
+        }
+    }
+    cancel.check()?;
+
+    info!("Registration agencies of failed DOIs:");
+    for (agency, count) in &counts {
+        info!("  {}: {}", agency, count);
+    }
+    Ok(counts)
+}
+
+/// Write records to the sink implied by `path`, returning records written
+fn write_records<'a>(
+    records: impl IntoIterator<Item = &'a CitationRecord>,
+    path: &str,
+    options: &OutputOptions,
+) -> Result<usize> {
+    let mut sink = open_sink_with(Path::new(path), options)?;
+    for record in records {
+        sink.write_record(&serde_json::to_value(record)?)?;
+    }
+    sink.finish()
+}
+
 /// Write validation results split by source
 pub fn write_split_validation_results(
     results: &ValidationResults,
@@ -155,6 +1090,7 @@ pub fn write_split_validation_results(
     output_datacite: Option<&str>,
     output_crossref_failed: Option<&str>,
     output_datacite_failed: Option<&str>,
+    options: &OutputOptions,
 ) -> Result<(usize, usize)> {
     // Split valid results by source
     let (crossref_valid, datacite_valid): (Vec<_>, Vec<_>) = results
@@ -165,199 +1101,232 @@ pub fn write_split_validation_results(
     let crossref_count = crossref_valid.len();
     let datacite_count = datacite_valid.len();
 
-    // Write Crossref valid
     if let Some(path) = output_crossref {
         info!("Writing {} Crossref citations to: {}", crossref_count, path);
-        let file = File::create(path)
-            .with_context(|| format!("Failed to create output file: {}", path))?;
-        let mut writer = BufWriter::new(file);
-        for (record, _) in &crossref_valid {
-            writeln!(writer, "{}", serde_json::to_string(record)?)?;
-        }
-        writer.flush()?;
+        write_records(
+            crossref_valid.iter().map(|(record, _)| record),
+            path,
+            options,
+        )?;
     }
 
-    // Write DataCite valid
     if let Some(path) = output_datacite {
         info!("Writing {} DataCite citations to: {}", datacite_count, path);
-        let file = File::create(path)
-            .with_context(|| format!("Failed to create output file: {}", path))?;
-        let mut writer = BufWriter::new(file);
-        for (record, _) in &datacite_valid {
-            writeln!(writer, "{}", serde_json::to_string(record)?)?;
-        }
-        writer.flush()?;
+        write_records(
+            datacite_valid.iter().map(|(record, _)| record),
+            path,
+            options,
+        )?;
     }
 
-    // Write failed (to both files if provided - we can't know which source they belong to)
+    // Failed records go to their attributed source; those with an unknown prefix to both
+    let failed_for = |target: Source| {
+        results
+            .failed
+            .iter()
+            .filter(move |(_, source)| *source == target || *source == Source::All)
+            .map(|(record, _)| record)
+    };
     if let Some(path) = output_crossref_failed {
-        let file = File::create(path)
-            .with_context(|| format!("Failed to create output file: {}", path))?;
-        let mut writer = BufWriter::new(file);
-        for record in &results.failed {
-            writeln!(writer, "{}", serde_json::to_string(record)?)?;
-        }
-        writer.flush()?;
+        write_records(failed_for(Source::Crossref), path, options)?;
     }
 
     if let Some(path) = output_datacite_failed {
-        let file = File::create(path)
-            .with_context(|| format!("Failed to create output file: {}", path))?;
-        let mut writer = BufWriter::new(file);
-        for record in &results.failed {
-            writeln!(writer, "{}", serde_json::to_string(record)?)?;
-        }
-        writer.flush()?;
+        write_records(failed_for(Source::Datacite), path, options)?;
     }
 
     Ok((crossref_count, datacite_count))
 }
 
-/// Write arXiv validation results with automatic split by provenance
-pub fn write_arxiv_validation_results_with_split(
-    results: &ValidationResults,
-    output_arxiv: &str,
-    output_arxiv_failed: Option<&str>,
-) -> Result<()> {
-    let paths = SplitOutputPaths::from_base(output_arxiv);
-
-    // Open all three output files
-    let file_all =
-        File::create(&paths.all).with_context(|| format!("Failed to create: {:?}", paths.all))?;
-    let file_asserted = File::create(&paths.asserted)
-        .with_context(|| format!("Failed to create: {:?}", paths.asserted))?;
-    let file_mined = File::create(&paths.mined)
-        .with_context(|| format!("Failed to create: {:?}", paths.mined))?;
-
-    let mut writer_all = BufWriter::new(file_all);
-    let mut writer_asserted = BufWriter::new(file_asserted);
-    let mut writer_mined = BufWriter::new(file_mined);
-
-    for (record, _) in &results.valid {
-        // Use arxiv_id from record if present, otherwise extract from DOI
-        let arxiv_id = record.arxiv_id.as_deref().unwrap_or_else(|| {
-            record
-                .doi
-                .strip_prefix("10.48550/arXiv.")
-                .or_else(|| record.doi.strip_prefix("10.48550/arxiv."))
-                .unwrap_or(&record.doi)
-        });
+/// Field layout of records in split outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordLayout {
+    /// The CitationRecord fields (doi, arxiv_id, ...)
+    Generic,
+    /// arxiv_doi and arxiv_id (derived from the DOI when missing), plus published_doi
+    /// when linked
+    Arxiv,
+}
 
-        // Write to main file
-        let arxiv_record = serde_json::json!({
-            "arxiv_doi": record.doi,
-            "arxiv_id": arxiv_id,
-            "reference_count": record.reference_count,
-            "citation_count": record.citation_count,
-            "cited_by": record.cited_by
-        });
-        writeln!(writer_all, "{}", arxiv_record)?;
-
-        // Filter and write to asserted file
-        let asserted_cited_by = filter_cited_by_by_provenance(&record.cited_by, true);
-        if !asserted_cited_by.is_empty() {
-            let asserted_record = serde_json::json!({
-                "arxiv_doi": record.doi,
-                "arxiv_id": arxiv_id,
-                "reference_count": record.reference_count,
-                "citation_count": asserted_cited_by.len(),
-                "cited_by": asserted_cited_by,
-            });
-            writeln!(writer_asserted, "{}", asserted_record)?;
-        }
-
-        // Filter and write to mined file
-        let mined_cited_by = filter_cited_by_by_provenance(&record.cited_by, false);
-        if !mined_cited_by.is_empty() {
-            let mined_record = serde_json::json!({
-                "arxiv_doi": record.doi,
-                "arxiv_id": arxiv_id,
-                "reference_count": record.reference_count,
-                "citation_count": mined_cited_by.len(),
-                "cited_by": mined_cited_by,
-            });
-            writeln!(writer_mined, "{}", mined_record)?;
-        }
-    }
-
-    writer_all.flush()?;
-    writer_asserted.flush()?;
-    writer_mined.flush()?;
-
-    info!("Wrote split arXiv output files:");
-    info!("  All: {:?}", paths.all);
-    info!("  Asserted: {:?}", paths.asserted);
-    info!("  Mined: {:?}", paths.mined);
+/// The asserted or the mined citations of a record, for a split output
+struct CitedBySubset {
+    cited_by: Vec<Value>,
+}
 
-    // Handle failed outputs with split
-    if let Some(failed_base) = output_arxiv_failed {
-        let failed_paths = SplitOutputPaths::from_base(failed_base);
-
-        let file_all = File::create(&failed_paths.all)
-            .with_context(|| format!("Failed to create: {:?}", failed_paths.all))?;
-        let file_asserted = File::create(&failed_paths.asserted)
-            .with_context(|| format!("Failed to create: {:?}", failed_paths.asserted))?;
-        let file_mined = File::create(&failed_paths.mined)
-            .with_context(|| format!("Failed to create: {:?}", failed_paths.mined))?;
-
-        let mut writer_all = BufWriter::new(file_all);
-        let mut writer_asserted = BufWriter::new(file_asserted);
-        let mut writer_mined = BufWriter::new(file_mined);
-
-        for record in &results.failed {
-            let arxiv_id = record.arxiv_id.as_deref().unwrap_or_else(|| {
-                record
-                    .doi
-                    .strip_prefix("10.48550/arXiv.")
-                    .or_else(|| record.doi.strip_prefix("10.48550/arxiv."))
-                    .unwrap_or(&record.doi)
-            });
-
-            // Write to main file
-            let arxiv_record = serde_json::json!({
-                "arxiv_doi": record.doi,
-                "arxiv_id": arxiv_id,
-                "reference_count": record.reference_count,
-                "citation_count": record.citation_count,
-                "cited_by": record.cited_by
-            });
-            writeln!(writer_all, "{}", arxiv_record)?;
-
-            // Filter and write to asserted file
-            let asserted_cited_by = filter_cited_by_by_provenance(&record.cited_by, true);
-            if !asserted_cited_by.is_empty() {
-                let asserted_record = serde_json::json!({
-                    "arxiv_doi": record.doi,
-                    "arxiv_id": arxiv_id,
+impl RecordLayout {
+    /// Record JSON, with `cited_by` narrowed to a provenance subset when given
+    ///
+    /// The count fields a record carries always describe the cited_by written, so a
+    /// split output recounts them over its subset.
+    fn to_json(self, record: &CitationRecord, subset: Option<CitedBySubset>) -> Result<Value> {
+        let (provenance_counts, asserted_count) = match &subset {
+            Some(subset) => {
+                let (counts, asserted) = count_cited_by(&subset.cited_by);
+                (
+                    record.provenance_counts.map(|_| counts),
+                    record.asserted_citation_count.map(|_| asserted),
+                )
+            }
+            None => (record.provenance_counts, record.asserted_citation_count),
+        };
+        let mut json = match (self, subset) {
+            (RecordLayout::Generic, None) => serde_json::to_value(record)?,
+            (RecordLayout::Generic, Some(subset)) => {
+                let mut json = serde_json::json!({
+                    "doi": record.doi,
+                    "arxiv_id": record.arxiv_id,
                     "reference_count": record.reference_count,
-                    "citation_count": asserted_cited_by.len(),
-                    "cited_by": asserted_cited_by,
+                    "citation_count": subset.cited_by.len(),
+                    "cited_by": subset.cited_by,
                 });
-                writeln!(writer_asserted, "{}", asserted_record)?;
+                if let Some(method) = record.validation_method {
+                    json["validation_method"] = serde_json::to_value(method)?;
+                }
+                if let Some(status) = record.validation_status {
+                    json["validation_status"] = serde_json::to_value(status)?;
+                }
+                json
             }
-
-            // Filter and write to mined file
-            let mined_cited_by = filter_cited_by_by_provenance(&record.cited_by, false);
-            if !mined_cited_by.is_empty() {
-                let mined_record = serde_json::json!({
+            (RecordLayout::Arxiv, subset) => {
+                // Use arxiv_id from record if present, otherwise extract from DOI
+                let arxiv_id = record.arxiv_id.as_deref().unwrap_or_else(|| {
+                    record
+                        .doi
+                        .strip_prefix("10.48550/arXiv.")
+                        .or_else(|| record.doi.strip_prefix("10.48550/arxiv."))
+                        .unwrap_or(&record.doi)
+                });
+                let (citation_count, cited_by) = match subset {
+                    Some(subset) => (subset.cited_by.len(), subset.cited_by),
+                    None => (record.citation_count, record.cited_by.clone()),
+                };
+                let mut json = serde_json::json!({
                     "arxiv_doi": record.doi,
                     "arxiv_id": arxiv_id,
                     "reference_count": record.reference_count,
-                    "citation_count": mined_cited_by.len(),
-                    "cited_by": mined_cited_by,
+                    "citation_count": citation_count,
+                    "cited_by": cited_by,
                 });
-                writeln!(writer_mined, "{}", mined_record)?;
+                if let Some(ref published_doi) = record.published_doi {
+                    json["published_doi"] = Value::from(published_doi.as_str());
+                }
+                if let Some(method) = record.validation_method {
+                    json["validation_method"] = serde_json::to_value(method)?;
+                }
+                if let Some(status) = record.validation_status {
+                    json["validation_status"] = serde_json::to_value(status)?;
+                }
+                json
+            }
+        };
+        if let Some(counts) = provenance_counts {
+            if let (Value::Object(json), Value::Object(counts)) =
+                (&mut json, serde_json::to_value(counts)?)
+            {
+                json.extend(counts);
             }
         }
+        if let Some(asserted) = asserted_count {
+            json["asserted_citation_count"] = Value::from(asserted);
+        }
+        if let Some(ref publisher) = record.cited_publisher {
+            json["cited_publisher"] = Value::from(publisher.as_str());
+        }
+        Ok(json)
+    }
+}
 
-        writer_all.flush()?;
-        writer_asserted.flush()?;
-        writer_mined.flush()?;
+/// Write records to a base output plus its asserted and mined splits
+///
+/// Records without asserted (or mined) citations are left out of that split. When
+/// the base output is stdout, only the full records are written.
+fn write_split_records<'a>(
+    records: impl IntoIterator<Item = &'a CitationRecord>,
+    base: &str,
+    layout: RecordLayout,
+    options: &OutputOptions,
+) -> Result<SplitOutputPaths> {
+    let paths = SplitOutputPaths::from_base(base);
+    let to_stdout = base == STDOUT_PATH;
+
+    let mut sink_all = open_sink_with(&paths.all, options)?;
+    let mut split_sinks = if to_stdout {
+        None
+    } else {
+        Some((
+            open_sink_with(&paths.asserted, options)?,
+            open_sink_with(&paths.mined, options)?,
+        ))
+    };
+
+    for record in records {
+        sink_all.write_record(&layout.to_json(record, None)?)?;
+
+        if let Some((sink_asserted, sink_mined)) = split_sinks.as_mut() {
+            for (asserted, sink) in [(true, &mut *sink_asserted), (false, &mut *sink_mined)] {
+                let cited_by = filter_cited_by_by_provenance(&record.cited_by, asserted);
+                if !cited_by.is_empty() {
+                    let subset = CitedBySubset { cited_by };
+                    sink.write_record(&layout.to_json(record, Some(subset))?)?;
+                }
+            }
+        }
+    }
 
-        info!("Wrote split failed arXiv output files:");
-        info!("  All: {:?}", failed_paths.all);
-        info!("  Asserted: {:?}", failed_paths.asserted);
-        info!("  Mined: {:?}", failed_paths.mined);
+    sink_all.finish()?;
+    if let Some((sink_asserted, sink_mined)) = split_sinks {
+        sink_asserted.finish()?;
+        sink_mined.finish()?;
+    }
+
+    Ok(paths)
+}
+
+fn log_split_paths(label: &str, paths: &SplitOutputPaths) {
+    info!("Wrote split {}output files:", label);
+    info!("  All: {:?}", paths.all);
+    info!("  Asserted: {:?}", paths.asserted);
+    info!("  Mined: {:?}", paths.mined);
+}
+
+/// Set `published_doi` on valid arXiv records from DataCite relatedIdentifiers
+pub fn link_published_dois(results: &mut ValidationResults, datacite_records: &str) -> Result<()> {
+    let links = ArxivPublishedDois::load_from_jsonl_gz(datacite_records)?;
+    let linked = links.annotate(results.valid.iter_mut().map(|(record, _)| record));
+    info!(
+        "Linked {} of {} valid arXiv citations to published DOIs",
+        linked,
+        results.valid.len()
+    );
+    Ok(())
+}
+
+/// Set `cited_publisher` and `citing_publisher` on valid and failed records
+pub fn annotate_publishers(results: &mut ValidationResults, publishers: &PublisherNames) {
+    let records = results.valid.iter_mut().chain(results.failed.iter_mut());
+    let annotated = publishers.annotate(records.map(|(record, _)| record));
+    info!(
+        "Annotated {} of {} cited works with their publisher",
+        annotated,
+        results.valid.len() + results.failed.len()
+    );
+}
+
+/// Write arXiv validation results with automatic split by provenance
+pub fn write_arxiv_validation_results_with_split(
+    results: &ValidationResults,
+    output_arxiv: &str,
+    output_arxiv_failed: Option<&str>,
+    options: &OutputOptions,
+) -> Result<()> {
+    let valid = results.valid.iter().map(|(record, _)| record);
+    let paths = write_split_records(valid, output_arxiv, RecordLayout::Arxiv, options)?;
+    log_split_paths("arXiv ", &paths);
+
+    if let Some(failed_base) = output_arxiv_failed {
+        let failed = results.failed.iter().map(|(record, _)| record);
+        let failed_paths = write_split_records(failed, failed_base, RecordLayout::Arxiv, options)?;
+        log_split_paths("failed arXiv ", &failed_paths);
     }
 
     Ok(())
@@ -375,7 +1344,7 @@ fn filter_cited_by_by_provenance(
                 .get("provenance")
                 .and_then(|p| p.as_str())
                 .unwrap_or("mined");
-            let is_asserted = provenance == "publisher" || provenance == "crossref";
+            let is_asserted = Provenance::is_asserted_str(provenance);
             if keep_asserted {
                 is_asserted
             } else {
@@ -386,122 +1355,58 @@ fn filter_cited_by_by_provenance(
         .collect()
 }
 
-/// Write validation results with automatic split by provenance
-pub fn write_validation_results_with_split(
-    valid: &[(CitationRecord, Source)],
-    failed: &[CitationRecord],
+/// Move records resolved via doi.org out of the valid results
+///
+/// For a separate output: an HTTP resolution says the DOI exists, but not that the
+/// cited work is the one the index would have matched.
+pub fn split_off_http_resolved(results: &mut ValidationResults) -> Vec<(CitationRecord, Source)> {
+    let (http, rest) = std::mem::take(&mut results.valid)
+        .into_iter()
+        .partition(|(record, _)| record.validation_method == Some(ValidationMethod::Http));
+    results.valid = rest;
+    http
+}
+
+/// Write records resolved via doi.org, split by provenance like the valid output
+pub fn write_http_resolved_results(
+    records: &[(CitationRecord, Source)],
     output_path: &str,
-    output_failed: Option<&str>,
+    source: Source,
+    options: &OutputOptions,
 ) -> Result<()> {
-    let paths = SplitOutputPaths::from_base(output_path);
-
-    // Open all three output files
-    let file_all =
-        File::create(&paths.all).with_context(|| format!("Failed to create: {:?}", paths.all))?;
-    let file_asserted = File::create(&paths.asserted)
-        .with_context(|| format!("Failed to create: {:?}", paths.asserted))?;
-    let file_mined = File::create(&paths.mined)
-        .with_context(|| format!("Failed to create: {:?}", paths.mined))?;
-
-    let mut writer_all = BufWriter::new(file_all);
-    let mut writer_asserted = BufWriter::new(file_asserted);
-    let mut writer_mined = BufWriter::new(file_mined);
-
-    for (record, _source) in valid {
-        // Write to main file
-        writeln!(writer_all, "{}", serde_json::to_string(record)?)?;
-
-        // Filter and write to asserted file
-        let asserted_cited_by = filter_cited_by_by_provenance(&record.cited_by, true);
-        if !asserted_cited_by.is_empty() {
-            let asserted_record = serde_json::json!({
-                "doi": record.doi,
-                "arxiv_id": record.arxiv_id,
-                "reference_count": record.reference_count,
-                "citation_count": asserted_cited_by.len(),
-                "cited_by": asserted_cited_by,
-            });
-            writeln!(writer_asserted, "{}", asserted_record)?;
-        }
-
-        // Filter and write to mined file
-        let mined_cited_by = filter_cited_by_by_provenance(&record.cited_by, false);
-        if !mined_cited_by.is_empty() {
-            let mined_record = serde_json::json!({
-                "doi": record.doi,
-                "arxiv_id": record.arxiv_id,
-                "reference_count": record.reference_count,
-                "citation_count": mined_cited_by.len(),
-                "cited_by": mined_cited_by,
-            });
-            writeln!(writer_mined, "{}", mined_record)?;
-        }
-    }
-
-    writer_all.flush()?;
-    writer_asserted.flush()?;
-    writer_mined.flush()?;
-
-    // Handle failed outputs with split (similar logic)
-    if let Some(failed_base) = output_failed {
-        let failed_paths = SplitOutputPaths::from_base(failed_base);
-
-        let file_all = File::create(&failed_paths.all)
-            .with_context(|| format!("Failed to create: {:?}", failed_paths.all))?;
-        let file_asserted = File::create(&failed_paths.asserted)
-            .with_context(|| format!("Failed to create: {:?}", failed_paths.asserted))?;
-        let file_mined = File::create(&failed_paths.mined)
-            .with_context(|| format!("Failed to create: {:?}", failed_paths.mined))?;
-
-        let mut writer_all = BufWriter::new(file_all);
-        let mut writer_asserted = BufWriter::new(file_asserted);
-        let mut writer_mined = BufWriter::new(file_mined);
-
-        for record in failed {
-            // Write to main file
-            writeln!(writer_all, "{}", serde_json::to_string(record)?)?;
-
-            // Filter and write to asserted file
-            let asserted_cited_by = filter_cited_by_by_provenance(&record.cited_by, true);
-            if !asserted_cited_by.is_empty() {
-                let asserted_record = serde_json::json!({
-                    "doi": record.doi,
-                    "arxiv_id": record.arxiv_id,
-                    "reference_count": record.reference_count,
-                    "citation_count": asserted_cited_by.len(),
-                    "cited_by": asserted_cited_by,
-                });
-                writeln!(writer_asserted, "{}", asserted_record)?;
-            }
-
-            // Filter and write to mined file
-            let mined_cited_by = filter_cited_by_by_provenance(&record.cited_by, false);
-            if !mined_cited_by.is_empty() {
-                let mined_record = serde_json::json!({
-                    "doi": record.doi,
-                    "arxiv_id": record.arxiv_id,
-                    "reference_count": record.reference_count,
-                    "citation_count": mined_cited_by.len(),
-                    "cited_by": mined_cited_by,
-                });
-                writeln!(writer_mined, "{}", mined_record)?;
-            }
-        }
+    let layout = match source {
+        Source::Arxiv => RecordLayout::Arxiv,
+        _ => RecordLayout::Generic,
+    };
+    info!(
+        "Writing {} HTTP-resolved citations to: {}",
+        records.len(),
+        output_path
+    );
+    let paths = write_split_records(records.iter().map(|(r, _)| r), output_path, layout, options)?;
+    log_split_paths("HTTP-resolved ", &paths);
+    Ok(())
+}
 
-        writer_all.flush()?;
-        writer_asserted.flush()?;
-        writer_mined.flush()?;
+/// Write validation results with automatic split by provenance
+pub fn write_validation_results_with_split(
+    valid: &[(CitationRecord, Source)],
+    failed: &[(CitationRecord, Source)],
+    output_path: &str,
+    output_failed: Option<&str>,
+    options: &OutputOptions,
+) -> Result<()> {
+    let valid_records = valid.iter().map(|(record, _source)| record);
+    let paths = write_split_records(valid_records, output_path, RecordLayout::Generic, options)?;
 
-        info!("Wrote split failed output files:");
-        info!("  All: {:?}", failed_paths.all);
-        info!("  Asserted: {:?}", failed_paths.asserted);
-        info!("  Mined: {:?}", failed_paths.mined);
+    if let Some(failed_base) = output_failed {
+        let failed_records = failed.iter().map(|(record, _source)| record);
+        let failed_paths =
+            write_split_records(failed_records, failed_base, RecordLayout::Generic, options)?;
+        log_split_paths("failed ", &failed_paths);
     }
 
-    info!("Wrote split output files:");
-    info!("  All: {:?}", paths.all);
-    info!("  Asserted: {:?}", paths.asserted);
-    info!("  Mined: {:?}", paths.mined);
+    log_split_paths("", &paths);
 
     Ok(())
 }
@@ -509,6 +1414,9 @@ pub fn write_validation_results_with_split(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::HttpError;
+    use crate::index::{shard_of, ShardSelection};
+    use crate::validation::FileResolver;
     use serde_json::json;
     use std::io::Write;
     use tempfile::NamedTempFile;
@@ -517,9 +1425,18 @@ mod tests {
         CitationRecord {
             doi: doi.to_string(),
             arxiv_id: None,
+            id_type: None,
             reference_count: 0,
             citation_count: 1,
             cited_by: vec![json!({"doi": "10.1234/citing"})],
+            published_doi: None,
+            registration_agency: None,
+            http_error: None,
+            validation_method: None,
+            validation_status: None,
+            asserted_citation_count: None,
+            cited_publisher: None,
+            provenance_counts: None,
         }
     }
 
@@ -548,9 +1465,9 @@ mod tests {
             Some(&crossref_index),
             None,
             Source::Crossref,
-            false,
-            10,
-            5,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
         )
         .await
         .unwrap();
@@ -558,9 +1475,385 @@ mod tests {
         assert_eq!(results.stats.total_records, 2);
         assert_eq!(results.stats.crossref_matched, 1);
         assert_eq!(results.valid.len(), 1);
+        assert_eq!(
+            results.valid[0].0.validation_method,
+            Some(ValidationMethod::Index)
+        );
         assert_eq!(results.failed.len(), 1);
         assert_eq!(results.valid[0].0.doi, "10.1234/found");
-        assert_eq!(results.failed[0].doi, "10.1234/notfound");
+        assert_eq!(results.failed[0].0.doi, "10.1234/notfound");
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_sets_status() {
+        let mut crossref_index = DoiIndex::new();
+        crossref_index.insert("10.1234/found");
+
+        let records = vec![
+            create_test_record("10.1234/found"),
+            create_test_record("10.1234/typo"),
+            create_test_record("10.9999/elsewhere"),
+            create_test_record("10.1234/broken doi"),
+        ];
+        let input_file = create_test_jsonl(&records);
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&crossref_index),
+            None,
+            Source::Crossref,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            results.valid[0].0.validation_status,
+            Some(ValidationStatus::MatchedIndex)
+        );
+        let failed: Vec<_> = results
+            .failed
+            .iter()
+            .map(|(record, _)| record.validation_status)
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                Some(ValidationStatus::PrefixKnownUnresolved),
+                Some(ValidationStatus::PrefixUnknown),
+                Some(ValidationStatus::Malformed),
+            ]
+        );
+        assert_eq!(results.stats.failed_prefix_known, 1);
+        assert_eq!(results.stats.failed_prefix_unknown, 1);
+        assert_eq!(results.stats.failed_malformed, 1);
+
+        let written = serde_json::to_value(&results.failed[0].0).unwrap();
+        assert_eq!(written["validation_status"], "prefix-known-unresolved");
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_records_http_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("http.jsonl");
+        // Every DOI is in the checkpoint, so nothing is requested
+        std::fs::write(
+            &checkpoint,
+            concat!(
+                "{\"doi\":\"10.1234/gone\",\"resolves\":false,\"http_error\":\"not-found\"}\n",
+                "{\"doi\":\"10.1234/old\",\"resolves\":false}\n",
+            ),
+        )
+        .unwrap();
+        let mut stale = create_test_record("10.1234/old");
+        stale.http_error = Some(HttpError::Timeout);
+        let input_file = create_test_jsonl(&[create_test_record("10.1234/gone"), stale]);
+        let http = HttpFallback {
+            concurrency: 1,
+            timeout_secs: 1,
+            checkpoint: Some(checkpoint),
+            adaptive: None,
+            crossref: true,
+            datacite: false,
+            bad_prefix_threshold: 0,
+            resolver: None,
+            client: HttpClientConfig::default(),
+        };
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&DoiIndex::new()),
+            None,
+            Source::Crossref,
+            Some(&http),
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let errors: Vec<_> = results
+            .failed
+            .iter()
+            .map(|(record, _)| (record.doi.as_str(), record.http_error))
+            .collect();
+        // The input's stale error is dropped; the old checkpoint line has none
+        assert_eq!(
+            errors,
+            [
+                ("10.1234/gone", Some(HttpError::NotFound)),
+                ("10.1234/old", None)
+            ]
+        );
+        assert_eq!(
+            results.stats.http_errors,
+            BTreeMap::from([(HttpError::NotFound, 1)])
+        );
+        let written = serde_json::to_value(&results.failed[0].0).unwrap();
+        assert_eq!(written["http_error"], "not-found");
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_with_resolver() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("http.jsonl");
+        let resolver = FileResolver::new([
+            ("10.1234/live".to_string(), None),
+            ("10.1234/gone".to_string(), Some(HttpError::NotFound)),
+        ]);
+        let input_file = create_test_jsonl(&[
+            create_test_record("10.1234/live"),
+            create_test_record("10.1234/gone"),
+            create_test_record("10.1234/unknown"),
+        ]);
+        let http = HttpFallback {
+            concurrency: 2,
+            timeout_secs: 1,
+            checkpoint: Some(checkpoint.clone()),
+            adaptive: None,
+            crossref: true,
+            datacite: false,
+            bad_prefix_threshold: 0,
+            resolver: Some(Arc::new(resolver)),
+            client: HttpClientConfig::default(),
+        };
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&DoiIndex::new()),
+            None,
+            Source::Crossref,
+            Some(&http),
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.valid.len(), 1);
+        assert_eq!(results.valid[0].0.doi, "10.1234/live");
+        assert_eq!(
+            results.valid[0].0.validation_method,
+            Some(ValidationMethod::Http)
+        );
+        let mut errors: Vec<_> = results
+            .failed
+            .iter()
+            .map(|(record, _)| (record.doi.as_str(), record.http_error))
+            .collect();
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                ("10.1234/gone", Some(HttpError::NotFound)),
+                ("10.1234/unknown", Some(HttpError::Connection))
+            ]
+        );
+
+        // The unknown DOI failed transiently, so a later run checks it again
+        let reopened = HttpCheckpoint::open(&checkpoint).unwrap();
+        assert_eq!(reopened.get("10.1234/live"), Some((true, None)));
+        assert_eq!(reopened.get("10.1234/unknown"), None);
+    }
+
+    /// Answers each DOI after a delay that shrinks along the input, so later DOIs
+    /// complete first
+    #[derive(Debug)]
+    struct ReversingResolver;
+
+    impl DoiResolver for ReversingResolver {
+        fn check<'a>(&'a self, doi: &'a str) -> futures::future::BoxFuture<'a, Option<HttpError>> {
+            Box::pin(async move {
+                let n: u64 = doi.rsplit('/').next().unwrap().parse().unwrap();
+                tokio::time::sleep(Duration::from_millis(5 * (10 - n))).await;
+                (n % 2 == 1).then_some(HttpError::NotFound)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_results_keep_input_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("http.jsonl");
+        // A checkpoint hit in the middle of the input
+        std::fs::write(&checkpoint, "{\"doi\":\"10.1234/4\",\"resolves\":true}\n").unwrap();
+        let records: Vec<_> = (0..10)
+            .map(|n| create_test_record(&format!("10.1234/{}", n)))
+            .collect();
+        let input_file = create_test_jsonl(&records);
+        let http = HttpFallback {
+            concurrency: 10,
+            timeout_secs: 1,
+            checkpoint: Some(checkpoint),
+            adaptive: None,
+            crossref: true,
+            datacite: false,
+            bad_prefix_threshold: 0,
+            resolver: Some(Arc::new(ReversingResolver)),
+            client: HttpClientConfig::default(),
+        };
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&DoiIndex::new()),
+            None,
+            Source::Crossref,
+            Some(&http),
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let dois = |records: &[(CitationRecord, Source)]| -> Vec<String> {
+            records
+                .iter()
+                .map(|(record, _)| record.doi.clone())
+                .collect()
+        };
+        assert_eq!(
+            dois(&results.valid),
+            [
+                "10.1234/0",
+                "10.1234/2",
+                "10.1234/4",
+                "10.1234/6",
+                "10.1234/8"
+            ]
+        );
+        assert_eq!(
+            dois(&results.failed),
+            [
+                "10.1234/1",
+                "10.1234/3",
+                "10.1234/5",
+                "10.1234/7",
+                "10.1234/9"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_fuzzy_rescue() {
+        let mut crossref_index = DoiIndex::new();
+        crossref_index.insert("10.1234/abc-1");
+
+        let records = vec![
+            create_test_record("10.1234/abc\u{2010}1 "),
+            create_test_record("10.1234/abc\u{2010}2"),
+        ];
+        let input_file = create_test_jsonl(&records);
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&crossref_index),
+            None,
+            Source::Crossref,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.stats.fuzzy_rescued, 1);
+        assert_eq!(results.stats.crossref_matched, 1);
+        assert_eq!(results.valid[0].0.doi, "10.1234/abc-1");
+        assert_eq!(results.failed.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_merges_rescued_into_matched() {
+        let mut crossref_index = DoiIndex::new();
+        crossref_index.insert("10.1234/abc-1");
+
+        let citing = |doi: &str, provenance: &str| json!({"doi": doi, "provenance": provenance});
+        let with_citing = |doi: &str, cited_by: Vec<Value>| CitationRecord {
+            citation_count: cited_by.len(),
+            reference_count: cited_by.len(),
+            asserted_citation_count: Some(0),
+            provenance_counts: Some(ProvenanceCounts::default()),
+            cited_by,
+            ..create_test_record(doi)
+        };
+        // The same DOI as written and with a unicode dash, sharing one citing work
+        let records = vec![
+            with_citing(
+                "10.1234/abc-1",
+                vec![citing("10.9/a", "publisher"), citing("10.9/b", "mined")],
+            ),
+            with_citing(
+                "10.1234/abc\u{2010}1",
+                vec![citing("10.9/b", "mined"), citing("10.9/c", "structured")],
+            ),
+        ];
+        let input_file = create_test_jsonl(&records);
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&crossref_index),
+            None,
+            Source::Crossref,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.stats.fuzzy_rescued, 1);
+        assert_eq!(results.valid.len(), 1);
+        let record = &results.valid[0].0;
+        assert_eq!(record.doi, "10.1234/abc-1");
+        let citing_dois: Vec<_> = record.cited_by.iter().map(|e| e["doi"].clone()).collect();
+        assert_eq!(citing_dois, ["10.9/a", "10.9/b", "10.9/c"]);
+        assert_eq!(record.citation_count, 3);
+        assert_eq!(record.asserted_citation_count, Some(1));
+        let counts = record.provenance_counts.unwrap();
+        assert_eq!(counts.structured_citation_count, 1);
+        assert_eq!(counts.mined_citation_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_skips_unloaded_shards() {
+        let mut crossref_index = DoiIndex::new();
+        crossref_index.insert("10.1234/found");
+        let loaded = shard_of("10.1234/found", 64);
+        crossref_index.shards = Some(ShardSelection {
+            count: 64,
+            loaded: vec![loaded],
+        });
+        let other = (1..100)
+            .map(|i| format!("10.{}/x", 5000 + i))
+            .find(|doi| shard_of(doi, 64) != loaded)
+            .unwrap();
+
+        let records = vec![
+            create_test_record("10.1234/found"),
+            create_test_record("10.1234/missing"),
+            create_test_record(&other),
+        ];
+        let input_file = create_test_jsonl(&records);
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&crossref_index),
+            None,
+            Source::Crossref,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.stats.outside_shards, 1);
+        assert_eq!(results.outside_shards[0].doi, other);
+        assert_eq!(results.valid.len(), 1);
+        assert_eq!(results.failed.len(), 1);
+        assert_eq!(results.failed[0].0.doi, "10.1234/missing");
     }
 
     #[tokio::test]
@@ -579,9 +1872,9 @@ mod tests {
             None,
             Some(&datacite_index),
             Source::Datacite,
-            false,
-            10,
-            5,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
         )
         .await
         .unwrap();
@@ -603,6 +1896,8 @@ mod tests {
         let records = vec![
             create_test_record("10.1234/crossref"),
             create_test_record("10.48550/arXiv.2301.00001"),
+            create_test_record("10.1234/missing"),
+            create_test_record("10.48550/arXiv.9999.99999"),
             create_test_record("10.9999/unknown"),
         ];
         let input_file = create_test_jsonl(&records);
@@ -612,18 +1907,227 @@ mod tests {
             Some(&crossref_index),
             Some(&datacite_index),
             Source::All,
-            false,
-            10,
-            5,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
         )
         .await
         .unwrap();
 
-        assert_eq!(results.stats.total_records, 3);
+        assert_eq!(results.stats.total_records, 5);
         assert_eq!(results.stats.crossref_matched, 1);
         assert_eq!(results.stats.datacite_matched, 1);
         assert_eq!(results.valid.len(), 2);
+
+        // Unmatched DOIs are attributed by prefix, unknown prefixes to neither source
+        let failed: Vec<_> = results
+            .failed
+            .iter()
+            .map(|(record, source)| (record.doi.as_str(), *source))
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                ("10.1234/missing", Source::Crossref),
+                ("10.48550/arXiv.9999.99999", Source::Datacite),
+                ("10.9999/unknown", Source::All),
+            ]
+        );
+        assert_eq!(results.stats.crossref_failed, 1);
+        assert_eq!(results.stats.datacite_failed, 1);
+        assert_eq!(results.stats.unknown_prefix_failed, 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        write_split_validation_results(
+            &results,
+            Some(&path("crossref.jsonl")),
+            Some(&path("datacite.jsonl")),
+            Some(&path("crossref_failed.jsonl")),
+            Some(&path("datacite_failed.jsonl")),
+            &OutputOptions::default(),
+        )
+        .unwrap();
+        let dois = |name: &str| -> Vec<String> {
+            std::fs::read_to_string(path(name))
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<CitationRecord>(line).unwrap().doi)
+                .collect()
+        };
+        assert_eq!(
+            dois("crossref_failed.jsonl"),
+            vec!["10.1234/missing", "10.9999/unknown"]
+        );
+        assert_eq!(
+            dois("datacite_failed.jsonl"),
+            vec!["10.48550/arXiv.9999.99999", "10.9999/unknown"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_prefix_only_datacite() {
+        let mut datacite_index = DoiIndex::prefix_only();
+        datacite_index.insert("10.5438/known");
+
+        let records = vec![
+            create_test_record("10.5438/probable"),
+            create_test_record("10.9999/unknown"),
+        ];
+        let input_file = create_test_jsonl(&records);
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            None,
+            Some(&datacite_index),
+            Source::Datacite,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.stats.datacite_prefix_matched, 1);
+        assert_eq!(results.stats.datacite_matched, 0);
+        assert_eq!(results.valid.len(), 1);
+        assert_eq!(results.valid[0].0.doi, "10.5438/probable");
+        assert_eq!(results.valid[0].1, Source::Datacite);
+        assert_eq!(
+            results.valid[0].0.validation_method,
+            Some(ValidationMethod::Prefix)
+        );
+        assert_eq!(
+            results.valid[0].0.validation_status,
+            Some(ValidationStatus::MatchedPrefix)
+        );
         assert_eq!(results.failed.len(), 1);
+        assert_eq!(results.failed[0].0.validation_method, None);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_only_accepts_only_confirmed_prefixes() {
+        let mut datacite_index = DoiIndex::prefix_only();
+        datacite_index.insert("10.5438/known");
+        datacite_index.insert("10.6084/known");
+
+        // Every 10.5438 DOI resolves; of 10.6084, the evenly spaced sample (every
+        // second DOI) does not, and the others do
+        let mut results_by_doi = Vec::new();
+        let mut records = Vec::new();
+        for i in 0..20 {
+            let good = format!("10.5438/{}", i);
+            let mixed = format!("10.6084/{}", i);
+            results_by_doi.push((good.clone(), None));
+            results_by_doi.push((mixed.clone(), (i % 2 == 0).then_some(HttpError::NotFound)));
+            records.push(create_test_record(&good));
+            records.push(create_test_record(&mixed));
+        }
+        let input_file = create_test_jsonl(&records);
+        let http = HttpFallback {
+            concurrency: 4,
+            timeout_secs: 1,
+            checkpoint: None,
+            adaptive: None,
+            crossref: false,
+            datacite: true,
+            bad_prefix_threshold: 0,
+            resolver: Some(Arc::new(FileResolver::new(results_by_doi))),
+            client: HttpClientConfig::default(),
+        };
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            None,
+            Some(&datacite_index),
+            Source::Datacite,
+            Some(&http),
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let stats = &results.stats;
+        assert_eq!(stats.datacite_prefix_sampled, 20);
+        assert_eq!(stats.datacite_prefix_confirmed, 10);
+        assert_eq!(stats.datacite_prefixes_unconfirmed, 1);
+        assert_eq!(stats.datacite_http_resolved, 10);
+        assert_eq!(stats.datacite_failed, 10);
+
+        let method_of = |prefix: &str, method: ValidationMethod| {
+            results
+                .valid
+                .iter()
+                .filter(|(record, _)| {
+                    record.doi.starts_with(prefix) && record.validation_method == Some(method)
+                })
+                .count()
+        };
+        assert_eq!(method_of("10.5438/", ValidationMethod::Http), 10);
+        assert_eq!(method_of("10.5438/", ValidationMethod::Prefix), 10);
+        // No 10.6084 DOI is accepted on its prefix alone
+        assert_eq!(method_of("10.6084/", ValidationMethod::Prefix), 0);
+        assert_eq!(method_of("10.6084/", ValidationMethod::Http), 10);
+        assert!(results
+            .failed
+            .iter()
+            .all(|(record, _)| record.doi.starts_with("10.6084/")));
+    }
+
+    #[tokio::test]
+    async fn test_validate_citations_keeps_input_order_across_chunks() {
+        let mut crossref_index = DoiIndex::new();
+        let records: Vec<_> = (0..LOOKUP_CHUNK_LINES + 10)
+            .map(|i| {
+                let doi = format!("10.1234/{}", i);
+                if i % 3 == 0 {
+                    crossref_index.insert(&doi);
+                }
+                create_test_record(&doi)
+            })
+            .collect();
+        let input_file = create_test_jsonl(&records);
+
+        let results = validate_citations(
+            input_file.path().to_str().unwrap(),
+            Some(&crossref_index),
+            None,
+            Source::Crossref,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.stats.total_records, records.len());
+        let valid: Vec<_> = results.valid.iter().map(|(r, _)| r.doi.clone()).collect();
+        let expected: Vec<_> = (0..records.len())
+            .step_by(3)
+            .map(|i| format!("10.1234/{}", i))
+            .collect();
+        assert_eq!(valid, expected);
+        assert_eq!(results.failed[0].0.doi, "10.1234/1");
+
+        // A bad line is reported by its line number in the input
+        let mut bad = NamedTempFile::new().unwrap();
+        writeln!(bad, "{}", serde_json::to_string(&records[0]).unwrap()).unwrap();
+        writeln!(bad).unwrap();
+        writeln!(bad, "{{").unwrap();
+        let err = validate_citations(
+            bad.path().to_str().unwrap(),
+            Some(&crossref_index),
+            None,
+            Source::Crossref,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("line 3"));
     }
 
     #[tokio::test]
@@ -635,9 +2139,9 @@ mod tests {
             None,
             None,
             Source::All,
-            false,
-            10,
-            5,
+            None,
+            DoiConstraints::default(),
+            &CancellationToken::new(),
         )
         .await
         .unwrap();
@@ -664,6 +2168,15 @@ mod tests {
         let record: CitationRecord = serde_json::from_str(generic_json).unwrap();
         assert_eq!(record.doi, "10.1234/test");
         assert_eq!(record.arxiv_id, None);
+        assert_eq!(record.provenance_counts, None);
+
+        let counted = r#"{"doi":"10.1234/test","reference_count":1,"citation_count":5,"publisher_citation_count":4,"crossref_citation_count":0,"structured_citation_count":0,"mined_citation_count":1,"cited_by":[]}"#;
+        let record: CitationRecord = serde_json::from_str(counted).unwrap();
+        let counts = record.provenance_counts.unwrap();
+        assert_eq!(counts.publisher_citation_count, 4);
+        assert_eq!(counts.mined_citation_count, 1);
+        let written = serde_json::to_value(&record).unwrap();
+        assert_eq!(written["publisher_citation_count"], 4);
     }
 
     #[test]
@@ -677,18 +2190,37 @@ mod tests {
         let record_mixed = CitationRecord {
             doi: "10.1234/mixed".to_string(),
             arxiv_id: None,
+            id_type: None,
             reference_count: 2,
             citation_count: 2,
             cited_by: vec![
                 serde_json::json!({"doi": "10.5555/a", "provenance": "publisher"}),
                 serde_json::json!({"doi": "10.5555/b", "provenance": "mined"}),
             ],
+            published_doi: None,
+            registration_agency: None,
+            http_error: None,
+            validation_method: None,
+            validation_status: None,
+            asserted_citation_count: Some(1),
+            cited_publisher: None,
+            provenance_counts: Some(ProvenanceCounts {
+                publisher_citation_count: 1,
+                mined_citation_count: 1,
+                ..Default::default()
+            }),
         };
 
         let records = vec![(record_mixed, Source::Crossref)];
 
-        write_validation_results_with_split(&records, &[], base_path.to_str().unwrap(), None)
-            .unwrap();
+        write_validation_results_with_split(
+            &records,
+            &[],
+            base_path.to_str().unwrap(),
+            None,
+            &OutputOptions::default(),
+        )
+        .unwrap();
 
         // Verify main file exists
         assert!(base_path.exists());
@@ -705,5 +2237,93 @@ mod tests {
         assert!(mined_path.exists());
         let mined_content = std::fs::read_to_string(&mined_path).unwrap();
         assert!(mined_content.contains("mined"));
+
+        // The counts describe the cited_by each output holds
+        let counts = |path: &Path| {
+            let record: Value =
+                serde_json::from_str(std::fs::read_to_string(path).unwrap().trim()).unwrap();
+            (
+                record["citation_count"].as_u64(),
+                record["asserted_citation_count"].as_u64(),
+                record["mined_citation_count"].as_u64(),
+            )
+        };
+        assert_eq!(counts(&base_path), (Some(2), Some(1), Some(1)));
+        assert_eq!(counts(&asserted_path), (Some(1), Some(1), Some(0)));
+        assert_eq!(counts(&mined_path), (Some(1), Some(0), Some(1)));
+    }
+
+    #[test]
+    fn test_split_off_http_resolved() {
+        use tempfile::tempdir;
+
+        let with_method = |doi: &str, method| CitationRecord {
+            validation_method: Some(method),
+            ..create_test_record(doi)
+        };
+        let mut results = ValidationResults {
+            valid: vec![
+                (
+                    with_method("10.1234/a", ValidationMethod::Index),
+                    Source::Crossref,
+                ),
+                (
+                    with_method("10.1234/b", ValidationMethod::Http),
+                    Source::Crossref,
+                ),
+                (
+                    with_method("10.5438/c", ValidationMethod::Prefix),
+                    Source::Datacite,
+                ),
+            ],
+            failed: Vec::new(),
+            outside_shards: Vec::new(),
+            stats: MultiValidateStats::default(),
+        };
+
+        let http = split_off_http_resolved(&mut results);
+        assert_eq!(http.len(), 1);
+        assert_eq!(http[0].0.doi, "10.1234/b");
+        let kept: Vec<&str> = results.valid.iter().map(|(r, _)| r.doi.as_str()).collect();
+        assert_eq!(kept, ["10.1234/a", "10.5438/c"]);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("http.jsonl");
+        write_http_resolved_results(
+            &http,
+            path.to_str().unwrap(),
+            Source::Crossref,
+            &OutputOptions::default(),
+        )
+        .unwrap();
+        let written: Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(written["doi"], "10.1234/b");
+        assert_eq!(written["validation_method"], "http");
+    }
+
+    #[test]
+    fn test_http_fallback_applies_to() {
+        let http = HttpFallback {
+            concurrency: 1,
+            timeout_secs: 1,
+            checkpoint: None,
+            adaptive: None,
+            crossref: false,
+            datacite: true,
+            bad_prefix_threshold: 0,
+            resolver: None,
+            client: HttpClientConfig::default(),
+        };
+        assert!(!http.applies_to(Source::Crossref));
+        assert!(http.applies_to(Source::Datacite));
+        assert!(http.applies_to(Source::Arxiv));
+        assert!(http.applies_to(Source::All));
+
+        let none = HttpFallback {
+            datacite: false,
+            ..http
+        };
+        assert!(!none.applies_to(Source::All));
     }
 }
@@ -83,6 +83,26 @@ fn test_validate_help() {
     assert!(status.success(), "Validate --help should succeed");
 }
 
+#[test]
+fn test_revalidate_help() {
+    let status = Command::new("cargo")
+        .args(["run", "--", "revalidate", "--help"])
+        .status()
+        .expect("Failed to run revalidate --help");
+
+    assert!(status.success(), "Revalidate --help should succeed");
+}
+
+#[test]
+fn test_export_graph_help() {
+    let status = Command::new("cargo")
+        .args(["run", "--", "export-graph", "--help"])
+        .status()
+        .expect("Failed to run export-graph --help");
+
+    assert!(status.success(), "Export-graph --help should succeed");
+}
+
 #[test]
 fn test_crossref_mode_extraction() {
     let dir = tempdir().unwrap();
@@ -127,6 +147,75 @@ fn test_crossref_mode_extraction() {
     assert!(has_records, "Should have output records");
 }
 
+#[test]
+fn test_only_dois_restricts_citing_works() {
+    let dir = tempdir().unwrap();
+    let tar_path = create_test_crossref_tar_gz(dir.path());
+    let output_path = dir.path().join("output.jsonl");
+
+    // other-paper only cites itself, so nothing is left to invert
+    let list_path = dir.path().join("only.txt");
+    std::fs::write(&list_path, "https://doi.org/10.1234/OTHER-PAPER\n").unwrap();
+
+    let status = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "pipeline",
+            "--input",
+            tar_path.to_str().unwrap(),
+            "--source",
+            "crossref",
+            "--output-crossref",
+            output_path.to_str().unwrap(),
+            "--only-dois",
+            list_path.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run pipeline");
+
+    assert!(status.success(), "Pipeline should succeed");
+    let content = std::fs::read_to_string(&output_path).unwrap_or_default();
+    assert!(
+        content.trim().is_empty(),
+        "References of unlisted works should not be extracted"
+    );
+}
+
+#[test]
+fn test_skip_records_skips_leading_works() {
+    let dir = tempdir().unwrap();
+    let tar_path = create_test_crossref_tar_gz(dir.path());
+    let output_path = dir.path().join("output.jsonl");
+
+    // Skipping citing-paper leaves other-paper, which only cites itself
+    let status = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "pipeline",
+            "--input",
+            tar_path.to_str().unwrap(),
+            "--source",
+            "crossref",
+            "--output-crossref",
+            output_path.to_str().unwrap(),
+            "--skip-records",
+            "1",
+            "--limit-records",
+            "5",
+        ])
+        .status()
+        .expect("Failed to run pipeline");
+
+    assert!(status.success(), "Pipeline should succeed");
+    let content = std::fs::read_to_string(&output_path).unwrap_or_default();
+    assert!(
+        content.trim().is_empty(),
+        "References of skipped works should not be extracted"
+    );
+}
+
 /// Create a test tar.gz with provenance test data
 fn create_provenance_test_tar_gz(dir: &std::path::Path) -> std::path::PathBuf {
     let tar_path = dir.join("provenance_test.tar.gz");
@@ -400,3 +489,211 @@ fn test_index_save_and_load() {
 
     assert!(status.success());
 }
+
+#[test]
+fn test_resume_from_intermediates() {
+    let dir = tempdir().unwrap();
+    let datacite_path = create_test_datacite_records(dir.path());
+    let tar_path = create_test_crossref_tar_gz(dir.path());
+    let partitions = dir.path().join("partitions");
+    let output1 = dir.path().join("output1.jsonl");
+
+    let status = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "pipeline",
+            "--input",
+            tar_path.to_str().unwrap(),
+            "--datacite-records",
+            datacite_path.to_str().unwrap(),
+            "--source",
+            "datacite",
+            "--output-datacite",
+            output1.to_str().unwrap(),
+            "--temp-dir",
+            partitions.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run pipeline");
+    assert!(status.success());
+    assert!(
+        partitions.join("intermediates.json").exists(),
+        "A kept partition directory should have an intermediates manifest"
+    );
+
+    // Validation reuses the inverted output without reading the snapshot again
+    let output2 = dir.path().join("output2.jsonl");
+    let status = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "pipeline",
+            "--input",
+            dir.path().join("missing.tar.gz").to_str().unwrap(),
+            "--datacite-records",
+            datacite_path.to_str().unwrap(),
+            "--source",
+            "datacite",
+            "--output-datacite",
+            output2.to_str().unwrap(),
+            "--from-intermediates",
+            partitions.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run pipeline from intermediates");
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(&output1).unwrap(),
+        std::fs::read_to_string(&output2).unwrap()
+    );
+}
+
+#[test]
+fn test_resume_extraction_skips_processed_entries() {
+    let dir = tempdir().unwrap();
+    let datacite_path = create_test_datacite_records(dir.path());
+    let tar_path = create_test_crossref_tar_gz(dir.path());
+    let partitions = dir.path().join("partitions");
+    let output = dir.path().join("output.jsonl");
+    std::fs::create_dir_all(&partitions).unwrap();
+
+    // An interrupted run that got through the only entry but left no partitions, so
+    // a resumed run finds nothing to cite: the entry is not extracted again
+    let checkpoint = |last_entry: &str| {
+        format!(
+            r#"{{"run_id": "pipeline-test", "phase": "ConvertExtract",
+                "tar_entries_processed": 1, "last_tar_entry": "{}", "input": "{}",
+                "partitions_written": [], "partitions_inverted": [],
+                "stats": {{"json_files_processed": 1, "total_records": 2,
+                          "total_references": 0, "references_with_matches": 0,
+                          "total_arxiv_ids_extracted": 0}}}}"#,
+            last_entry,
+            tar_path.to_str().unwrap()
+        )
+    };
+    let run = || {
+        Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                "pipeline",
+                "--input",
+                tar_path.to_str().unwrap(),
+                "--datacite-records",
+                datacite_path.to_str().unwrap(),
+                "--source",
+                "datacite",
+                "--output-datacite",
+                output.to_str().unwrap(),
+                "--temp-dir",
+                partitions.to_str().unwrap(),
+                "--resume-extraction",
+            ])
+            .status()
+            .expect("Failed to run pipeline")
+    };
+
+    std::fs::write(
+        partitions.join("checkpoint.json"),
+        checkpoint("test/other.json"),
+    )
+    .unwrap();
+    assert!(
+        !run().success(),
+        "A watermark naming another entry should be rejected"
+    );
+
+    std::fs::write(
+        partitions.join("checkpoint.json"),
+        checkpoint("test/file1.json"),
+    )
+    .unwrap();
+    assert!(run().success());
+    let content = std::fs::read_to_string(&output).unwrap_or_default();
+    assert!(
+        content.trim().is_empty(),
+        "Entries before the watermark should not be extracted again"
+    );
+}
+
+#[test]
+fn test_resume_extraction_without_checkpoint_keeps_partitions() {
+    let dir = tempdir().unwrap();
+    let datacite_path = create_test_datacite_records(dir.path());
+    let tar_path = create_test_crossref_tar_gz(dir.path());
+    let partitions = dir.path().join("partitions");
+    std::fs::create_dir_all(&partitions).unwrap();
+    // A run that flushed partitions and died before saving a checkpoint
+    let partition = partitions.join("10.1234.parquet");
+    std::fs::write(&partition, b"rows").unwrap();
+
+    let status = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "pipeline",
+            "--input",
+            tar_path.to_str().unwrap(),
+            "--datacite-records",
+            datacite_path.to_str().unwrap(),
+            "--source",
+            "datacite",
+            "--output-datacite",
+            dir.path().join("output.jsonl").to_str().unwrap(),
+            "--temp-dir",
+            partitions.to_str().unwrap(),
+            "--resume-extraction",
+        ])
+        .status()
+        .expect("Failed to run pipeline");
+
+    assert!(
+        !status.success(),
+        "Extracting again into existing partitions would duplicate their rows"
+    );
+    assert_eq!(std::fs::read(&partition).unwrap(), b"rows");
+}
+
+#[test]
+fn test_uncompressed_tar_with_parse_workers() {
+    let dir = tempdir().unwrap();
+    let tar_gz_path = create_test_crossref_tar_gz(dir.path());
+    let tar_path = dir.path().join("test_crossref.tar");
+    let mut decoder = flate2::read::GzDecoder::new(File::open(&tar_gz_path).unwrap());
+    std::io::copy(&mut decoder, &mut File::create(&tar_path).unwrap()).unwrap();
+
+    let run = |input: &std::path::Path, output: &std::path::Path, workers: &str| {
+        Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                "pipeline",
+                "--input",
+                input.to_str().unwrap(),
+                "--source",
+                "crossref",
+                "--output-crossref",
+                output.to_str().unwrap(),
+                "--parse-workers",
+                workers,
+            ])
+            .status()
+            .expect("Failed to run pipeline")
+    };
+
+    let gz_output = dir.path().join("from_gz.jsonl");
+    assert!(run(&tar_gz_path, &gz_output, "1").success());
+    let tar_output = dir.path().join("from_tar.jsonl");
+    assert!(run(&tar_path, &tar_output, "3").success());
+    assert_eq!(
+        std::fs::read_to_string(&tar_output).unwrap(),
+        std::fs::read_to_string(&gz_output).unwrap(),
+        "An uncompressed tar read by workers should extract what the tar.gz does"
+    );
+
+    assert!(
+        !run(&tar_gz_path, &dir.path().join("rejected.jsonl"), "3").success(),
+        "A compressed input cannot be read by several workers"
+    );
+}